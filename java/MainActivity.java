@@ -295,6 +295,18 @@ public class MainActivity extends Activity {
         //% MAIN_ACTIVITY_ON_PAUSE
     }
 
+    @Override
+    public void onTrimMemory(int level) {
+        super.onTrimMemory(level);
+        QuadNative.activityOnTrimMemory(level);
+    }
+
+    @Override
+    public void onLowMemory() {
+        super.onLowMemory();
+        QuadNative.activityOnLowMemory();
+    }
+
     @Override
     protected void onActivityResult(int requestCode, int resultCode, Intent data) {
         //% MAIN_ACTIVITY_ON_ACTIVITY_RESULT
@@ -11,6 +11,8 @@ public class QuadNative {
     public native static void activityOnCreate(Object activity);
     public native static void activityOnResume();
     public native static void activityOnPause();
+    public native static void activityOnTrimMemory(int level);
+    public native static void activityOnLowMemory();
     public native static void activityOnDestroy();
 
     // belongs to QuadSurface class
@@ -24,6 +24,26 @@ var high_dpi = false;
 // if false, requestAnimationFrame will be called at the end of each frame
 var blocking_event_loop = false;
 
+// How the next frame is scheduled, see sapp_set_frame_strategy
+const FRAME_STRATEGY_ANIMATION_FRAME = 0;
+const FRAME_STRATEGY_EVERY_NTH = 1;
+const FRAME_STRATEGY_TIMER = 2;
+var frame_strategy = FRAME_STRATEGY_ANIMATION_FRAME;
+// n for FRAME_STRATEGY_EVERY_NTH, frames per second for FRAME_STRATEGY_TIMER
+var frame_strategy_param = 1;
+var skipped_animation_frames = 0;
+var frame_timer;
+var next_timer_frame = 0;
+
+// Pointer capture keeps mouse events coming to the canvas while the cursor is
+// outside of it: always while a button is held, and until released when the
+// app asks for it with capture_mouse
+var mouse_pointer_id = null;
+var mouse_captured = false;
+
+// KeyboardLayoutMap of the current layout, Chromium only
+var keyboard_layout_map = null;
+
 function init_webgl(version) {
     if (version == 1) {
         gl = canvas.getContext("webgl");
@@ -241,6 +261,20 @@ var GL = {
         }
         return source;
     },
+    // The glGetActiveAttrib/glGetActiveUniform outputs from a WebGLActiveInfo
+    writeActiveInfo: function (info, bufSize, length, size, type, name) {
+        if (!info) {
+            return;
+        }
+        var array = new Uint8Array(wasm_memory.buffer, name, bufSize);
+        var written = stringToUTF8(info.name, array, 0, bufSize - 1);
+        array[written] = 0;
+        if (length) {
+            getArray(length, Int32Array, 1)[0] = written;
+        }
+        getArray(size, Int32Array, 1)[0] = info.size;
+        getArray(type, Uint32Array, 1)[0] = info.type;
+    },
     populateUniformTable: function (program) {
         GL.validateGLObjectID(GL.programs, program, 'populateUniformTable', 'program');
         var p = GL.programs[program];
@@ -434,16 +468,49 @@ function resize(canvas, on_resize) {
     }
 }
 
-function animation() {
-    wasm_exports.frame();
-    if (!window.blocking_event_loop) {
-        if (animation_frame_timeout) {
-            window.cancelAnimationFrame(animation_frame_timeout);
+function schedule_animation() {
+    if (animation_frame_timeout) {
+        window.cancelAnimationFrame(animation_frame_timeout);
+        animation_frame_timeout = undefined;
+    }
+    if (frame_timer) {
+        window.clearTimeout(frame_timer);
+        frame_timer = undefined;
+    }
+    // Timers keep firing in hidden tabs, animation frames are paused there
+    if (frame_strategy == FRAME_STRATEGY_TIMER && !document.hidden) {
+        var interval = 1000 / frame_strategy_param;
+        var now = performance.now();
+        next_timer_frame += interval;
+        // After a pause, start over instead of catching up
+        if (next_timer_frame < now - interval) {
+            next_timer_frame = now;
         }
+        frame_timer = window.setTimeout(animation, Math.max(0, next_timer_frame - now));
+    } else {
         animation_frame_timeout = window.requestAnimationFrame(animation);
     }
 }
 
+function animation() {
+    animation_frame_timeout = undefined;
+    frame_timer = undefined;
+    if (frame_strategy == FRAME_STRATEGY_EVERY_NTH) {
+        skipped_animation_frames += 1;
+        if (skipped_animation_frames < frame_strategy_param) {
+            // Even with the blocking event loop, the requested frame is still due
+            schedule_animation();
+            return;
+        }
+        skipped_animation_frames = 0;
+    }
+    wasm_exports.frame();
+    // The frame may have called schedule_update already
+    if (!window.blocking_event_loop && !animation_frame_timeout && !frame_timer) {
+        schedule_animation();
+    }
+}
+
 const SAPP_EVENTTYPE_TOUCHES_BEGAN = 10;
 const SAPP_EVENTTYPE_TOUCHES_MOVED = 11;
 const SAPP_EVENTTYPE_TOUCHES_ENDED = 12;
@@ -683,10 +750,17 @@ var importObject = {
             GL.validateGLObjectID(GL.textures, texture, 'glBindTexture', 'texture');
             gl.bindTexture(target, GL.textures[texture]);
         },
+        glTexStorage2D: function (target, levels, internalFormat, width, height) {
+            gl.texStorage2D(target, levels, internalFormat, width, height);
+        },
         glTexImage2D: function (target, level, internalFormat, width, height, border, format, type, pixels) {
             gl.texImage2D(target, level, internalFormat, width, height, border, format, type,
                 pixels ? getArray(pixels, Uint8Array, texture_size(internalFormat, width, height)) : null);
         },
+        glCompressedTexImage2D: function (target, level, internalFormat, width, height, border, imageSize, data) {
+            gl.compressedTexImage2D(target, level, internalFormat, width, height, border,
+                getArray(data, Uint8Array, imageSize));
+        },
         glTexSubImage2D: function (target, level, xoffset, yoffset, width, height, format, type, pixels) {
             gl.texSubImage2D(target, level, xoffset, yoffset, width, height, format, type,
                 pixels ? getArray(pixels, Uint8Array, texture_size(format, width, height)) : null);
@@ -802,6 +876,12 @@ var importObject = {
                 return -1;
             }
         },
+        glUniformMatrix3fv: function (location, count, transpose, value) {
+            GL.validateGLObjectID(GL.uniforms, location, 'glUniformMatrix3fv', 'location');
+            assert((value & 3) == 0, 'Pointer to float data passed to glUniformMatrix3fv must be aligned to four bytes!');
+            var view = getArray(value, Float32Array, 9 * count);
+            gl.uniformMatrix3fv(GL.uniforms[location], !!transpose, view);
+        },
         glUniformMatrix4fv: function (location, count, transpose, value) {
             GL.validateGLObjectID(GL.uniforms, location, 'glUniformMatrix4fv', 'location');
             assert((value & 3) == 0, 'Pointer to float data passed to glUniformMatrix4fv must be aligned to four bytes!');
@@ -859,12 +939,18 @@ var importObject = {
         glDepthFunc: function (func) {
             gl.depthFunc(func);
         },
+        glBlendColor: function (red, green, blue, alpha) {
+            gl.blendColor(red, green, blue, alpha);
+        },
         glBlendFuncSeparate: function (sfactorRGB, dfactorRGB, sfactorAlpha, dfactorAlpha) {
             gl.blendFuncSeparate(sfactorRGB, dfactorRGB, sfactorAlpha, dfactorAlpha);
         },
         glViewport: function (x, y, width, height) {
             gl.viewport(x, y, width, height);
         },
+        glDepthRangef: function (n, f) {
+            gl.depthRange(n, f);
+        },
         glDrawArrays: function (mode, first, count) {
             gl.drawArrays(mode, first, count);
         },
@@ -990,6 +1076,14 @@ var importObject = {
 
             gl.shaderSource(GL.shaders[shader], source);
         },
+        glGetActiveAttrib: function (program, index, bufSize, length, size, type, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveAttrib', 'program');
+            GL.writeActiveInfo(gl.getActiveAttrib(GL.programs[program], index), bufSize, length, size, type, name);
+        },
+        glGetActiveUniform: function (program, index, bufSize, length, size, type, name) {
+            GL.validateGLObjectID(GL.programs, program, 'glGetActiveUniform', 'program');
+            GL.writeActiveInfo(gl.getActiveUniform(GL.programs[program], index), bufSize, length, size, type, name);
+        },
         glGetProgramInfoLog: function (program, maxLength, length, infoLog) {
             GL.validateGLObjectID(GL.programs, program, 'glGetProgramInfoLog', 'program');
             var log = gl.getProgramInfoLog(GL.programs[program]);
@@ -1170,12 +1264,32 @@ var importObject = {
                                dstX0, dstY0, dstX1, dstY1,
                                mask, filter);
         },
+        glCopyTexSubImage2D: function(target, level, xoffset, yoffset, x, y, width, height) {
+            gl.copyTexSubImage2D(target, level, xoffset, yoffset, x, y, width, height);
+        },
+        glClearBufferfv: function(buffer, drawbuffer, value) {
+            gl.clearBufferfv(buffer, drawbuffer, getArray(value, Float32Array, 4));
+        },
+        glInvalidateFramebuffer: function(target, numAttachments, attachments) {
+            gl.invalidateFramebuffer(target, getArray(attachments, Int32Array, numAttachments));
+        },
 
         setup_canvas_size: function (high_dpi) {
             window.high_dpi = high_dpi;
             resize(canvas);
         },
         run_animation_loop: function (blocking) {
+            if (navigator.keyboard && navigator.keyboard.getLayoutMap) {
+                var update_layout_map = function () {
+                    navigator.keyboard.getLayoutMap().then(function (map) {
+                        keyboard_layout_map = map;
+                    });
+                };
+                update_layout_map();
+                if (navigator.keyboard.addEventListener) {
+                    navigator.keyboard.addEventListener('layoutchange', update_layout_map);
+                }
+            }
             canvas.onmousemove = function (event) {
                 var relative_position = mouse_relative_position(event.clientX, event.clientY);
                 var x = relative_position.x;
@@ -1189,7 +1303,28 @@ var importObject = {
                     wasm_exports.raw_mouse_move(Math.floor(event.movementX), Math.floor(event.movementY));
                 }
             };
+            canvas.addEventListener('pointerdown', function (event) {
+                if (event.pointerType == "mouse") {
+                    mouse_pointer_id = event.pointerId;
+                    canvas.setPointerCapture(event.pointerId);
+                }
+            });
+            canvas.addEventListener('pointerup', function (event) {
+                if (event.pointerType == "mouse" && event.buttons == 0 && !mouse_captured
+                    && canvas.hasPointerCapture(event.pointerId)) {
+                    canvas.releasePointerCapture(event.pointerId);
+                }
+            });
+            canvas.addEventListener('pointermove', function (event) {
+                if (event.pointerType == "mouse") {
+                    mouse_pointer_id = event.pointerId;
+                }
+            });
             canvas.onmousedown = function (event) {
+                // back and forward buttons would navigate away from the page
+                if (event.button == 3 || event.button == 4) {
+                    event.preventDefault();
+                }
                 var relative_position = mouse_relative_position(event.clientX, event.clientY);
                 var x = relative_position.x;
                 var y = relative_position.y;
@@ -1204,6 +1339,10 @@ var importObject = {
                     wasm_exports.mouse_wheel(-event.deltaX, -event.deltaY);
                 });
             canvas.onmouseup = function (event) {
+                // back and forward buttons would navigate away from the page
+                if (event.button == 3 || event.button == 4) {
+                    event.preventDefault();
+                }
                 var relative_position = mouse_relative_position(event.clientX, event.clientY);
                 var x = relative_position.x;
                 var y = relative_position.y;
@@ -1242,7 +1381,7 @@ var importObject = {
                 // for "space", "quote", and "slash" preventDefault will prevent
                 // key_press event, so send it here instead
                 if (sapp_key_code == 32 || sapp_key_code == 39 || sapp_key_code == 47) {
-                    wasm_exports.key_press(sapp_key_code);
+                    wasm_exports.key_press(sapp_key_code, event.repeat);
                 }
             };
             canvas.onkeyup = function (event) {
@@ -1268,7 +1407,7 @@ var importObject = {
                 // workaround to make this behavior consistent
                 let chrome_only = sapp_key_code == 261 || event.ctrlKey;
                 if (chrome_only == false) {
-                    wasm_exports.key_press(event.charCode);
+                    wasm_exports.key_press(event.charCode, event.repeat);
                 }
             };
 
@@ -1378,7 +1517,7 @@ var importObject = {
             window.addEventListener("blur", checkFocus);
 
             window.blocking_event_loop = blocking;
-            window.requestAnimationFrame(animation);
+            schedule_animation();
         },
 
         fs_load_file: function (ptr, len) {
@@ -1432,9 +1571,61 @@ var importObject = {
                 document.exitPointerLock();
             }
         },
+        sapp_capture_mouse: function (capture) {
+            mouse_captured = capture;
+            if (mouse_pointer_id == null) {
+                return;
+            }
+            try {
+                if (capture) {
+                    canvas.setPointerCapture(mouse_pointer_id);
+                } else if (canvas.hasPointerCapture(mouse_pointer_id)) {
+                    canvas.releasePointerCapture(mouse_pointer_id);
+                }
+            } catch (e) {
+                // The pointer is gone, nothing to capture
+            }
+        },
+        sapp_key_label: function (keycode) {
+            if (keyboard_layout_map == null) {
+                return 0;
+            }
+            for (const [code, label] of keyboard_layout_map) {
+                if (into_sapp_keycode(code) == keycode) {
+                    var bytes = new TextEncoder().encode(label);
+                    var msg = wasm_exports.allocate_vec_u8(bytes.length + 1);
+                    var array = new Uint8Array(wasm_memory.buffer, msg, bytes.length + 1);
+                    array.set(bytes);
+                    array[bytes.length] = 0;
+                    return msg;
+                }
+            }
+            return 0;
+        },
+        sapp_upload_video: function (ptr, len, size) {
+            var video = document.getElementById(UTF8ToString(ptr, len));
+            if (!(video instanceof HTMLVideoElement) || video.readyState < 2) {
+                return false;
+            }
+            gl.texImage2D(gl.TEXTURE_2D, 0, gl.RGBA, gl.RGBA, gl.UNSIGNED_BYTE, video);
+            var out = getArray(size, Uint32Array, 2);
+            out[0] = video.videoWidth;
+            out[1] = video.videoHeight;
+            return true;
+        },
         sapp_set_cursor: function (ptr, len) {
             canvas.style.cursor = UTF8ToString(ptr, len);
         },
+        sapp_message_box: function (title_ptr, title_len, text_ptr, text_len, confirm) {
+            var title = UTF8ToString(title_ptr, title_len);
+            var text = UTF8ToString(text_ptr, text_len);
+            var message = title ? title + "\n\n" + text : text;
+            if (confirm) {
+                return window.confirm(message) ? 1 : 0;
+            }
+            window.alert(message);
+            return 1;
+        },
         sapp_is_fullscreen: function () {
             let fullscreenElement = document.fullscreenElement;
 
@@ -1453,10 +1644,17 @@ var importObject = {
             resize(canvas, wasm_exports.resize);
         },
         sapp_schedule_update: function () {
-            if (animation_frame_timeout) {
-                window.cancelAnimationFrame(animation_frame_timeout);
+            if (!animation_frame_timeout && !frame_timer) {
+                schedule_animation();
+            }
+        },
+        sapp_set_frame_strategy: function (kind, param) {
+            frame_strategy = kind;
+            frame_strategy_param = param;
+            skipped_animation_frames = 0;
+            if (animation_frame_timeout || frame_timer) {
+                schedule_animation();
             }
-            animation_frame_timeout = window.requestAnimationFrame(animation);
         },
         init_webgl
     }
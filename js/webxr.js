@@ -0,0 +1,138 @@
+// WebXR sessions for miniquad's `xr` module, built with the `webxr` feature.
+// Load this file after gl.js.
+
+const WEBXR_STARTED = 0;
+const WEBXR_ENDED = 1;
+const WEBXR_FAILED = 2;
+
+// "none", "left" and "right", for XRView.eye and XRInputSource.handedness
+const WEBXR_SIDES = { "none": 0, "left": 1, "right": 2 };
+
+var webxr_session = null;
+var webxr_reference_space = null;
+// The id of the XRWebGLLayer framebuffer in GL.framebuffers
+var webxr_framebuffer_id = 0;
+
+function webxr_write(values) {
+    var ptr = wasm_exports.webxr_buffer(values.length);
+    // After webxr_buffer, the wasm memory may have grown
+    new Float32Array(wasm_memory.buffer, ptr, values.length).set(values);
+}
+
+function webxr_pose_values(pose) {
+    var position = pose.transform.position;
+    var orientation = pose.transform.orientation;
+    return [position.x, position.y, position.z,
+            orientation.x, orientation.y, orientation.z, orientation.w];
+}
+
+function webxr_animation(time, frame) {
+    var session = frame.session;
+    session.requestAnimationFrame(webxr_animation);
+
+    var pose = frame.getViewerPose(webxr_reference_space);
+    // Tracking lost, the browser shows the last frame
+    if (pose == null) {
+        return;
+    }
+    var layer = session.renderState.baseLayer;
+    wasm_exports.webxr_begin_frame(webxr_framebuffer_id, layer.framebufferWidth, layer.framebufferHeight);
+
+    for (var i = 0; i < pose.views.length; i++) {
+        var view = pose.views[i];
+        var viewport = layer.getViewport(view);
+        webxr_write(Array.from(view.projectionMatrix).concat(Array.from(view.transform.inverse.matrix)));
+        wasm_exports.webxr_view(WEBXR_SIDES[view.eye] || 0,
+            viewport.x, viewport.y, viewport.width, viewport.height);
+    }
+
+    for (var source of session.inputSources) {
+        var aim = frame.getPose(source.targetRaySpace, webxr_reference_space);
+        if (aim == null) {
+            continue;
+        }
+        var grip = source.gripSpace ? frame.getPose(source.gripSpace, webxr_reference_space) : null;
+        var values = grip != null ? [1].concat(webxr_pose_values(grip)) : [0, 0, 0, 0, 0, 0, 0, 1];
+        values = values.concat(webxr_pose_values(aim));
+
+        var buttons = source.gamepad ? source.gamepad.buttons : [];
+        values.push(buttons.length);
+        for (var button of buttons) {
+            values.push(button.pressed ? 1 : 0, button.touched ? 1 : 0, button.value);
+        }
+        var axes = source.gamepad ? source.gamepad.axes : [];
+        values.push(axes.length);
+        for (var axis of axes) {
+            values.push(axis);
+        }
+        webxr_write(values);
+        wasm_exports.webxr_controller(WEBXR_SIDES[source.handedness] || 0);
+    }
+
+    wasm_exports.webxr_end_frame();
+}
+
+function webxr_end() {
+    // Without a reference space the session never started
+    var event = webxr_reference_space != null ? WEBXR_ENDED : WEBXR_FAILED;
+    webxr_session = null;
+    webxr_reference_space = null;
+    if (webxr_framebuffer_id != 0) {
+        GL.framebuffers[webxr_framebuffer_id] = null;
+        webxr_framebuffer_id = 0;
+    }
+    wasm_exports.webxr_session_event(event);
+    // The page's frames take over again, even with the blocking event loop
+    importObject.env.sapp_schedule_update();
+}
+
+miniquad_add_plugin({
+    name: "miniquad_webxr",
+    version: 1,
+    register_plugin: function (importObject) {
+        importObject.env.webxr_is_supported = function () {
+            return navigator.xr != undefined;
+        };
+        importObject.env.webxr_request_session = function () {
+            if (webxr_session != null) {
+                return;
+            }
+            if (navigator.xr == undefined) {
+                wasm_exports.webxr_session_event(WEBXR_FAILED);
+                return;
+            }
+            navigator.xr.requestSession("immersive-vr", { optionalFeatures: ["local-floor"] })
+                .then(function (session) {
+                    webxr_session = session;
+                    session.addEventListener("end", webxr_end);
+                    return gl.makeXRCompatible().then(function () {
+                        var layer = new XRWebGLLayer(session, gl);
+                        session.updateRenderState({ baseLayer: layer });
+                        webxr_framebuffer_id = GL.getNewId(GL.framebuffers);
+                        GL.framebuffers[webxr_framebuffer_id] = layer.framebuffer;
+                        return session.requestReferenceSpace("local-floor").catch(function () {
+                            return session.requestReferenceSpace("local");
+                        });
+                    }).then(function (space) {
+                        webxr_reference_space = space;
+                        wasm_exports.webxr_session_event(WEBXR_STARTED);
+                        session.requestAnimationFrame(webxr_animation);
+                    });
+                })
+                .catch(function (error) {
+                    console.error("WebXR session failed: " + error);
+                    if (webxr_session != null) {
+                        // webxr_end reports the failure
+                        webxr_session.end();
+                    } else {
+                        wasm_exports.webxr_session_event(WEBXR_FAILED);
+                    }
+                });
+        };
+        importObject.env.webxr_end_session = function () {
+            if (webxr_session != null) {
+                webxr_session.end();
+            }
+        };
+    },
+});
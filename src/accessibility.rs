@@ -0,0 +1,142 @@
+//! Hooks for screen readers, made for AccessKit <https://accesskit.dev> platform adapters
+//!
+//! miniquad does not depend on AccessKit. The application (or its UI library)
+//! creates the platform adapter from `window::window_handle` and hands it over
+//! with `set_adapter`. From then on miniquad keeps it informed: focus changes
+//! and window bounds go to the `Adapter` methods, on Windows `WM_GETOBJECT` is
+//! routed to it, and it is dropped before the window is destroyed.
+//!
+//! The adapter's action handler may be called on any thread. It forwards the
+//! requests through an `ActionSender`, and they arrive on the main thread, right
+//! before `update`, as `EventHandler::accessibility_action_event`:
+//!
+//! ```ignore
+//! struct Actions(miniquad::accessibility::ActionSender);
+//!
+//! impl accesskit::ActionHandler for Actions {
+//!     fn do_action(&mut self, request: accesskit::ActionRequest) {
+//!         self.0.send(request);
+//!     }
+//! }
+//!
+//! struct Unix(accesskit_unix::Adapter);
+//!
+//! impl miniquad::accessibility::Adapter for Unix {
+//!     fn focus_changed(&mut self, focused: bool) {
+//!         self.0.update_window_focus_state(focused);
+//!     }
+//!     fn bounds_changed(&mut self, x: f32, y: f32, width: f32, height: f32) {
+//!         let rect = accesskit::Rect::new(x as _, y as _, (x + width) as _, (y + height) as _);
+//!         self.0.set_root_window_bounds(rect, rect);
+//!     }
+//! }
+//!
+//! fn accessibility_action_event(&mut self, request: Box<dyn Any + Send>) {
+//!     if let Ok(request) = request.downcast::<accesskit::ActionRequest>() {
+//!         self.ui.handle_action(*request);
+//!     }
+//! }
+//! ```
+
+use crate::{event::EventHandler, native::Request, native_display};
+
+use std::{any::Any, cell::RefCell, sync::Mutex};
+
+/// What miniquad tells the accessibility adapter, all on the main thread.
+/// Every method has a default, implement those the platform adapter needs.
+pub trait Adapter {
+    /// The window gained or lost the keyboard focus
+    fn focus_changed(&mut self, _focused: bool) {}
+
+    /// The client area moved or was resized, in physical pixels relative to the
+    /// top-left of the desktop. Only sent on X11: the AccessKit Unix adapter needs
+    /// it, the Windows and macOS ones ask the window themselves.
+    fn bounds_changed(&mut self, _x: f32, _y: f32, _width: f32, _height: f32) {}
+
+    /// Windows only: a `WM_GETOBJECT` message. Return the adapter's result to
+    /// answer it, `None` leaves it to `DefWindowProc`.
+    fn wm_getobject(&mut self, _wparam: usize, _lparam: isize) -> Option<isize> {
+        None
+    }
+}
+
+thread_local! {
+    static ADAPTER: RefCell<Option<Box<dyn Adapter>>> = RefCell::new(None);
+}
+
+static PENDING_ACTIONS: Mutex<Vec<Box<dyn Any + Send>>> = Mutex::new(Vec::new());
+
+/// Attach the accessibility adapter, replacing (and dropping) the previous one.
+/// Must be called on the main thread, e.g. from `EventHandler` callbacks.
+pub fn set_adapter(adapter: Box<dyn Adapter>) {
+    ADAPTER.with(|current| *current.borrow_mut() = Some(adapter));
+}
+
+/// Drop the accessibility adapter
+pub fn remove_adapter() {
+    // Taken out first, so the adapter's Drop may call back into miniquad
+    let adapter = ADAPTER.with(|current| current.borrow_mut().take());
+    drop(adapter);
+}
+
+/// Forwards action requests from the adapter's action handler, on any thread,
+/// to `EventHandler::accessibility_action_event`
+#[derive(Debug, Clone)]
+pub struct ActionSender {
+    _private: (),
+}
+
+impl ActionSender {
+    /// Queue `request` for the next frame, and schedule that frame when
+    /// `blocking_event_loop` is on
+    pub fn send<T: Any + Send>(&self, request: T) {
+        if let Ok(mut pending) = PENDING_ACTIONS.lock() {
+            pending.push(Box::new(request));
+        }
+        let d = native_display().lock().unwrap();
+        let _ = d.native_requests.send(Request::ScheduleUpdate);
+    }
+}
+
+/// A sender to give to the accessibility adapter's action handler.
+/// Can't be used before the window exists.
+pub fn action_sender() -> ActionSender {
+    ActionSender { _private: () }
+}
+
+fn with_adapter<R>(f: impl FnOnce(&mut dyn Adapter) -> R) -> Option<R> {
+    ADAPTER.with(|adapter| {
+        // A platform call made by the adapter itself may come back here, skip it then
+        let mut adapter = adapter.try_borrow_mut().ok()?;
+        Some(f(adapter.as_deref_mut()?))
+    })
+}
+
+/// Called by the platform event loops
+pub(crate) fn focus_changed(focused: bool) {
+    with_adapter(|adapter| adapter.focus_changed(focused));
+}
+
+/// Called by the platform event loops
+#[cfg(target_os = "linux")]
+pub(crate) fn bounds_changed(x: f32, y: f32, width: f32, height: f32) {
+    with_adapter(|adapter| adapter.bounds_changed(x, y, width, height));
+}
+
+/// Called by the Windows window procedure
+#[cfg(windows)]
+pub(crate) fn wm_getobject(wparam: usize, lparam: isize) -> Option<isize> {
+    with_adapter(|adapter| adapter.wm_getobject(wparam, lparam)).flatten()
+}
+
+/// Deliver the action requests sent since the last frame, called by the platform
+/// event loops before `update`
+pub(crate) fn dispatch_actions(event_handler: &mut dyn EventHandler) {
+    let actions = PENDING_ACTIONS
+        .lock()
+        .map(|mut pending| std::mem::take(&mut *pending))
+        .unwrap_or_default();
+    for action in actions {
+        event_handler.accessibility_action_event(action);
+    }
+}
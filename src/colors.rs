@@ -0,0 +1,101 @@
+//! System colors and the native color picker
+//!
+//! `system_color` reads the colors the user chose for the desktop, to match
+//! selections and highlights with the rest of the OS:
+//!
+//! - Windows: the DWM colorization color for the accent, `GetSysColor` for the others.
+//! - macOS: the `NSColor` system colors, the accent needs macOS 10.14.
+//! - Linux: only the accent, from the XDG desktop portal settings.
+//!   Not every desktop has one.
+//! - Android, iOS and browsers: none.
+//!
+//! `pick_color` opens the platform's color dialog: `ChooseColor` on Windows, the
+//! shared `NSColorPanel` on macOS, and the Screenshot portal's on-screen picker on
+//! Linux. It does not block, the result arrives right before `update` as
+//! `EventHandler::color_picked_event`. The macOS panel stays open and reports
+//! every change, the other dialogs report once. Browsers report
+//! `FeatureUnsupported` and a cancelled pick, on Android and iOS nothing happens.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # struct Stage { brush: (f32, f32, f32, f32) }
+//! # impl EventHandler for Stage {
+//! # fn update(&mut self) {} fn draw(&mut self) {}
+//! fn color_picked_event(&mut self, color: Option<(f32, f32, f32, f32)>) {
+//!     if let Some(color) = color {
+//!         self.brush = color;
+//!     }
+//! }
+//! # }
+//! # let brush = (1., 0., 0., 1.);
+//! colors::pick_color(brush);
+//! ```
+
+use crate::{event::EventHandler, native, native_display};
+
+use std::sync::Mutex;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SystemColor {
+    /// The accent color of controls and focus rings
+    Accent,
+    /// Background of selected text and items
+    Highlight,
+    /// Selected text and items
+    HighlightText,
+    WindowBackground,
+    WindowText,
+}
+
+/// An OS color as RGBA in 0..1, None where the platform does not have it
+pub fn system_color(color: SystemColor) -> Option<(f32, f32, f32, f32)> {
+    #[cfg(target_os = "windows")]
+    return native::windows::system_color(color);
+    #[cfg(target_os = "macos")]
+    return native::macos::system_color(color);
+    #[cfg(target_os = "linux")]
+    return match color {
+        SystemColor::Accent => native::linux_portal::accent_color(),
+        _ => None,
+    };
+    #[cfg(not(any(target_os = "windows", target_os = "macos", target_os = "linux")))]
+    {
+        let _ = color;
+        None
+    }
+}
+
+/// Open the color picker with `initial` selected, see the module docs
+pub fn pick_color(initial: (f32, f32, f32, f32)) {
+    let d = native_display().lock().unwrap();
+    d.native_requests
+        .send(native::Request::PickColor(initial))
+        .unwrap();
+}
+
+/// A picked color, None for a cancelled dialog
+type Picked = Option<(f32, f32, f32, f32)>;
+
+static PICKED: Mutex<Vec<Picked>> = Mutex::new(Vec::new());
+
+/// A color was chosen, or the dialog cancelled with None. May be called from
+/// any thread, schedules the frame delivering it when `blocking_event_loop` is on.
+pub(crate) fn report_picked(color: Picked) {
+    if let Ok(mut picked) = PICKED.lock() {
+        picked.push(color);
+    }
+    let d = native_display().lock().unwrap();
+    let _ = d.native_requests.send(native::Request::ScheduleUpdate);
+}
+
+/// Deliver the colors picked since the last frame, called by the platform
+/// event loops before `update`
+pub(crate) fn dispatch_picked(event_handler: &mut dyn EventHandler) {
+    let picked = PICKED
+        .lock()
+        .map(|mut picked| std::mem::take(&mut *picked))
+        .unwrap_or_default();
+    for color in picked {
+        event_handler.color_picked_event(color);
+    }
+}
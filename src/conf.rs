@@ -38,8 +38,22 @@
 //! dpi_scale   -> 1.0
 //! ```
 //!
+//! [`Conf::high_dpi_policy`] says the same for every platform at once:
+//! [`HighDpiPolicy::Physical`] and [`HighDpiPolicy::Logical`] override
+//! `high_dpi`, the default [`HighDpiPolicy::Auto`] follows it.
+//!
+//! Window sizes, in `Conf` and for [`set_window_size`], are logical units.
+//! [`screen_size_physical`] is the framebuffer size, the one [`screen_size`]
+//! returns, in pixels, and [`screen_size_logical`] is the same size in logical
+//! units: `physical / dpi_scale`. Mouse positions are framebuffer pixels.
+//! On X11, which has no logical units, `dpi_scale` is the `Xft.dpi` setting
+//! over 96 with `high_dpi`, and the window is made that much larger.
+//!
 //! [`dpi_scale`]: super::window::dpi_scale
 //! [`screen_size`]: super::window::screen_size
+//! [`screen_size_physical`]: super::window::screen_size_physical
+//! [`screen_size_logical`]: super::window::screen_size_logical
+//! [`set_window_size`]: super::window::set_window_size
 //!
 //! ## Window positioning
 //!
@@ -71,6 +85,34 @@ pub enum LinuxX11Gl {
     EGLWithGLXFallback,
 }
 
+/// How the framebuffer is sized on HighDPI displays,
+/// see [High DPI rendering](index.html#high-dpi-rendering).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum HighDpiPolicy {
+    /// Follow [`Conf::high_dpi`], the platform default: `true` on Android,
+    /// `false` elsewhere.
+    #[default]
+    Auto,
+    /// The framebuffer has all the pixels of the display, `dpi_scale` is the
+    /// number of pixels per logical unit.
+    Physical,
+    /// The framebuffer has the logical size of the window, upscaled by the
+    /// window system, and `dpi_scale` is 1.
+    Logical,
+}
+
+impl HighDpiPolicy {
+    /// Whether the framebuffer gets the physical pixels, `high_dpi` being
+    /// [`Conf::high_dpi`]
+    pub fn is_high_dpi(self, high_dpi: bool) -> bool {
+        match self {
+            HighDpiPolicy::Auto => high_dpi,
+            HighDpiPolicy::Physical => true,
+            HighDpiPolicy::Logical => false,
+        }
+    }
+}
+
 /// On Linux, the backend used for windowing and event handling.
 ///
 /// Defaults to `X11Only`. The Wayland implementation is currently unstable
@@ -116,6 +158,26 @@ pub enum WebGLVersion {
     WebGL2,
 }
 
+/// On the Web, how the frames are scheduled. May be changed later with
+/// [`set_web_frame_strategy`].
+///
+/// [`set_web_frame_strategy`]: super::window::set_web_frame_strategy
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum WebFrameStrategy {
+    /// A frame on every `requestAnimationFrame`, at the display refresh rate.
+    /// With [`Platform::target_frame_rate`] the animation frames coming too early
+    /// are skipped. This is the default choice.
+    #[default]
+    AnimationFrame,
+    /// A frame every Nth `requestAnimationFrame`, e.g. 2 for 30 FPS on a 60Hz
+    /// display. Stays in step with the display.
+    EveryNthAnimationFrame(u32),
+    /// A `setTimeout` loop at this many frames per second, the page sleeps between
+    /// frames instead of waking up on each animation frame. While the tab is
+    /// hidden the animation frames take over, pausing the loop as usual.
+    Timer(u32),
+}
+
 /// On Wayland, specify how to draw client-side decoration (CSD) if server-side decoration (SSD) is
 /// not supported (e.g., on GNOME).
 ///
@@ -132,6 +194,31 @@ pub enum WaylandDecorations {
     ServerOnly,
 }
 
+/// On laptops with both an integrated and a discrete GPU, which one to render on.
+///
+/// - On Windows, the `NvOptimusEnablement` and `AmdPowerXpressRequestHighPerformance`
+///   exports are set for `HighPerformance`. The drivers only see them if the
+///   executable exports them, add to the application's build script:
+///   `println!("cargo:rustc-link-arg-bins=/EXPORT:NvOptimusEnablement");` and the
+///   same for `AmdPowerXpressRequestHighPerformance`.
+/// - On macOS, the Metal device is picked by `isLowPower`. OpenGL contexts run on
+///   the discrete GPU unless `LowPower` allows the integrated one.
+/// - On Linux, PRIME render offload is requested through `DRI_PRIME` for Mesa and
+///   `__NV_PRIME_RENDER_OFFLOAD` for NVIDIA, unless already set in the environment.
+/// - Ignored on WASM, iOS and Android.
+///
+/// The chosen one is reported in `ContextInfo::adapter`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GpuPreference {
+    /// Whatever the OS and the driver settings pick. This is the default choice.
+    #[default]
+    Default,
+    /// The discrete GPU.
+    HighPerformance,
+    /// The integrated GPU, to save battery.
+    LowPower,
+}
+
 /// Platform-specific settings.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Platform {
@@ -144,6 +231,9 @@ pub struct Platform {
     /// Specifies which WebGL version to use on the Web (1.0. or 2.0).
     pub webgl_version: WebGLVersion,
 
+    /// On the Web, how the frames are scheduled, see [`WebFrameStrategy`].
+    pub web_frame_strategy: WebFrameStrategy,
+
     /// Defines which rendering API to use on Apple platforms (Metal or OpenGL).
     pub apple_gfx_api: AppleGfxApi,
 
@@ -153,6 +243,9 @@ pub struct Platform {
     /// There is no guarantee the FPS will match the specified `swap_interval`.
     /// In other words, `swap_interval` is only a hint to the GPU driver and
     /// not a reliable way to limit the game's FPS.
+    /// May be changed later with [`set_swap_interval`].
+    ///
+    /// [`set_swap_interval`]: super::window::set_swap_interval
     pub swap_interval: Option<i32>,
 
     /// If `true`, the event loop will block until [`schedule_update`] is called.
@@ -167,6 +260,21 @@ pub struct Platform {
     /// [`schedule_update`]: super::window::schedule_update
     pub blocking_event_loop: bool,
 
+    /// Cap the frame rate, e.g. to keep menus from running the GPU flat out.
+    /// Unlike `swap_interval` this is done by miniquad, sleeping after each frame.
+    /// With `blocking_event_loop` it limits how often input and `schedule_update`
+    /// redraw. May be changed later with [`set_target_frame_rate`].
+    ///
+    /// [`set_target_frame_rate`]: super::window::set_target_frame_rate
+    pub target_frame_rate: Option<u32>,
+
+    /// Wait for the GPU to finish each frame before starting the next one,
+    /// trading throughput for input latency. May be changed later with
+    /// [`set_low_latency`].
+    ///
+    /// [`set_low_latency`]: super::window::set_low_latency
+    pub low_latency: bool,
+
     /// If `true`, the framebuffer includes an alpha channel.
     /// Currently supported only on Android.
     ///
@@ -188,6 +296,9 @@ pub struct Platform {
     /// Whether to automatically setup the panic hook for Android.
     /// Set this to false if your app does its own panic_hook setup to avoid conflicts.
     pub android_panic_hook: bool,
+
+    /// Which GPU to render on, on hybrid systems. See [`GpuPreference`].
+    pub gpu_preference: GpuPreference,
 }
 
 impl Default for Platform {
@@ -197,12 +308,48 @@ impl Default for Platform {
             linux_backend: LinuxBackend::default(),
             apple_gfx_api: AppleGfxApi::default(),
             webgl_version: WebGLVersion::default(),
+            web_frame_strategy: WebFrameStrategy::default(),
             blocking_event_loop: false,
+            target_frame_rate: None,
+            low_latency: false,
             swap_interval: None,
             framebuffer_alpha: false,
             wayland_decorations: WaylandDecorations::default(),
             linux_wm_class: "miniquad-application",
             android_panic_hook: true,
+            gpu_preference: GpuPreference::default(),
+        }
+    }
+}
+
+/// Kiosk mode, for museum installations and arcade builds, see [`Conf::kiosk`].
+/// Every option defaults to `true`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Kiosk {
+    /// Hide the mouse cursor, as with `window::show_mouse(false)`.
+    pub hide_cursor: bool,
+    /// Keep the mouse cursor inside the window, as with `window::set_cursor_grab(true)`.
+    pub confine_cursor: bool,
+    /// Swallow the system shortcuts switching away from the app, where the OS allows:
+    /// - On Windows, Alt-Tab, Alt-Esc, Ctrl-Esc and the Windows keys, while the window
+    ///   is in the foreground. Ctrl-Alt-Del can't be blocked.
+    /// - On macOS, app switching, Force Quit, the Dock and the menu bar.
+    /// - On X11, everything the window manager binds, through a keyboard grab.
+    /// - Not available on Wayland, WASM, iOS and Android.
+    pub block_system_shortcuts: bool,
+    /// Keep the screensaver and display sleep away while the app runs.
+    /// Through the org.freedesktop.ScreenSaver D-Bus service on Linux.
+    /// Not available on WASM, iOS and Android.
+    pub inhibit_screensaver: bool,
+}
+
+impl Default for Kiosk {
+    fn default() -> Kiosk {
+        Kiosk {
+            hide_cursor: true,
+            confine_cursor: true,
+            block_system_shortcuts: true,
+            inhibit_screensaver: true,
         }
     }
 }
@@ -225,6 +372,9 @@ pub struct Conf {
     /// Defaults to `false`.
     pub high_dpi: bool,
 
+    /// Overrides `high_dpi` unless `Auto`, the default.
+    pub high_dpi_policy: HighDpiPolicy,
+
     /// If `true`, create the window in fullscreen mode (ignored on WASM/Android).
     /// Defaults to `false`.
     pub fullscreen: bool,
@@ -241,6 +391,26 @@ pub struct Conf {
     /// If `true`, the user can resize the window.
     pub window_resizable: bool,
 
+    /// If `false`, the window has no title bar and no borders, e.g. for a splash
+    /// screen. On Wayland see `Platform::wayland_decorations` instead.
+    /// Defaults to `true`.
+    pub window_decorations: bool,
+
+    /// Create a utility window, for tool palettes and splash screens: it has no
+    /// taskbar entry and does not take the focus when shown.
+    /// - On Windows, a WS_EX_TOOLWINDOW with WS_EX_NOACTIVATE, clicking it does
+    ///   not activate it either
+    /// - On X11, a _NET_WM_WINDOW_TYPE_UTILITY window skipping the taskbar
+    /// - On macOS, a non-activating NSPanel, the app still shows in the Dock
+    /// - Ignored on Wayland, WASM, iOS and Android
+    ///
+    /// Defaults to `false`.
+    pub utility_window: bool,
+
+    /// Kiosk mode: the window goes fullscreen, whatever [`Conf::fullscreen`] says,
+    /// and the [`Kiosk`] options apply. Defaults to `None`.
+    pub kiosk: Option<Kiosk>,
+
     /// Optional icon data used by the OS where applicable:
     /// - On Windows, taskbar/title bar icon
     /// - On macOS, Dock/title bar icon
@@ -291,9 +461,13 @@ impl Default for Conf {
             window_width: 800,
             window_height: 600,
             high_dpi: false,
+            high_dpi_policy: HighDpiPolicy::Auto,
             fullscreen: false,
             sample_count: 1,
             window_resizable: true,
+            window_decorations: true,
+            utility_window: false,
+            kiosk: None,
             icon: Some(Icon::miniquad_logo()),
             platform: Default::default(),
             desktop_center: false,
@@ -309,9 +483,13 @@ impl Default for Conf {
             window_width: 800,
             window_height: 600,
             high_dpi: true,
+            high_dpi_policy: HighDpiPolicy::Auto,
             fullscreen: true, //
             sample_count: 1,
             window_resizable: false, //
+            window_decorations: true,
+            utility_window: false,
+            kiosk: None,
             icon: Some(Icon::miniquad_logo()),
             platform: Default::default(),
             desktop_center: false,
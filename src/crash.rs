@@ -0,0 +1,129 @@
+//! Panic hook leaving the desktop usable
+//!
+//! A panicking fullscreen game may leave the cursor confined or hidden, the
+//! keyboard grabbed, the window covering the screen and the user looking at a
+//! frozen frame. `install_panic_hook` chains a hook to the current one that,
+//! after the usual message on stderr:
+//!
+//! - releases the cursor grab and capture, shows the cursor, hides the window,
+//!   and on Windows restores the display mode, lifts the kiosk keyboard hook and
+//!   the screensaver inhibition, on macOS the kiosk presentation options;
+//! - shows the panic message in a native message box: `MessageBoxW`, an
+//!   `NSAlert`, or zenity/kdialog on Linux.
+//!
+//! The window has to be restored from the thread that created it. A panic on
+//! the render thread of `start_with_render_thread` is handled by the platform
+//! thread once the render thread is gone. Browsers, Android and iOS only get the
+//! stderr message.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # struct Stage;
+//! # impl EventHandler for Stage { fn update(&mut self) {} fn draw(&mut self) {} }
+//! miniquad::crash::install_panic_hook();
+//! miniquad::start(conf::Conf::default(), || Box::new(Stage));
+//! ```
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static INSTALLED: AtomicBool = AtomicBool::new(false);
+static TITLE: Mutex<String> = Mutex::new(String::new());
+/// A panic on another thread, for the platform thread to report
+static PENDING: Mutex<Option<String>> = Mutex::new(None);
+
+thread_local! {
+    /// Set by the platform on the thread owning the window
+    static RESTORE: RefCell<Option<Box<dyn FnOnce()>>> = RefCell::new(None);
+}
+
+/// Chain the crash handling described in the module docs to the current panic
+/// hook. Installing it more than once has no effect.
+pub fn install_panic_hook() {
+    if INSTALLED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+
+        let payload = info.payload();
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("Box<dyn Any>");
+        let message = match info.location() {
+            Some(location) => format!("{}\n\nat {}", message, location),
+            None => message.to_string(),
+        };
+        if !report(&message) {
+            // try_lock, the panic may have happened while holding it
+            if let Ok(mut pending) = PENDING.try_lock() {
+                *pending = Some(message);
+            }
+        }
+    }));
+}
+
+/// Restore the window and show `message`, false if not on the thread owning
+/// the window
+fn report(message: &str) -> bool {
+    let restore = RESTORE.with(|restore| restore.try_borrow_mut().ok()?.take());
+    let Some(restore) = restore else {
+        return false;
+    };
+    restore();
+
+    let title = TITLE
+        .try_lock()
+        .map(|title| title.clone())
+        .unwrap_or_default();
+    let title = if title.is_empty() {
+        "Crash".to_string()
+    } else {
+        format!("{} crashed", title)
+    };
+    show_error(&title, message);
+    true
+}
+
+#[allow(unused_variables)]
+fn show_error(title: &str, message: &str) {
+    use crate::MessageBoxButtons;
+
+    #[cfg(target_os = "windows")]
+    crate::native::windows::message_box(title, message, MessageBoxButtons::Ok, true);
+    #[cfg(target_os = "macos")]
+    crate::native::macos::message_box(title, message, MessageBoxButtons::Ok, true);
+    #[cfg(target_os = "linux")]
+    crate::native::linux_message_box::message_box(title, message, MessageBoxButtons::Ok, true);
+}
+
+/// Called by the platforms on the window's thread, once it exists.
+/// `restore` releases what the window holds, it runs once, on a panic.
+pub(crate) fn set_restore(window_title: &str, restore: impl FnOnce() + 'static) {
+    if let Ok(mut title) = TITLE.lock() {
+        *title = window_title.to_string();
+    }
+    RESTORE.with(|r| *r.borrow_mut() = Some(Box::new(restore)));
+}
+
+/// The window was destroyed normally, nothing to restore anymore
+pub(crate) fn clear_restore() {
+    RESTORE.with(|r| {
+        if let Ok(mut restore) = r.try_borrow_mut() {
+            *restore = None;
+        }
+    });
+}
+
+/// The render thread ended with a panic, called by the platform thread
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn render_thread_panicked() {
+    let message = PENDING.lock().ok().and_then(|mut pending| pending.take());
+    if let Some(message) = message {
+        report(&message);
+    }
+}
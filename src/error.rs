@@ -3,8 +3,71 @@
 //! This module provides comprehensive error handling for all miniquad operations,
 //! replacing the previous panic-heavy approach with proper Result types.
 
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt::{self, Display};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+static ERROR_HANDLER: Mutex<Option<fn(MiniquadError)>> = Mutex::new(None);
+static PENDING_ERRORS: Mutex<VecDeque<MiniquadError>> = Mutex::new(VecDeque::new());
+/// Set once an event loop takes the pending errors, before that nothing would
+/// ever read them
+static DISPATCHING: AtomicBool = AtomicBool::new(false);
+
+/// Errors waiting for the next frame, the oldest are dropped past this
+const MAX_PENDING_ERRORS: usize = 64;
+
+/// Receive the errors miniquad can recover from right when they happen:
+/// GL errors, invalid resource ids, unsupported platform features and the like.
+/// Useful to forward them to the application's own logging or telemetry.
+///
+/// Without a handler the errors are delivered to `EventHandler::error`
+/// at the start of the next frame instead. Errors raised before the event loop
+/// runs, e.g. while falling back to another display server, are printed to
+/// stderr.
+///
+/// The handler may be called from any thread miniquad runs code on.
+pub fn set_error_handler(handler: fn(MiniquadError)) {
+    if let Ok(mut current) = ERROR_HANDLER.lock() {
+        *current = Some(handler);
+    }
+}
+
+/// Go back to delivering the errors to `EventHandler::error`
+pub fn clear_error_handler() {
+    if let Ok(mut current) = ERROR_HANDLER.lock() {
+        *current = None;
+    }
+}
+
+/// Pass a non-fatal error to the handler set with `set_error_handler`,
+/// queue it for `EventHandler::error` if there is none
+pub(crate) fn report_error(error: impl Into<MiniquadError>) {
+    let error = error.into();
+    let handler = ERROR_HANDLER.lock().ok().and_then(|handler| *handler);
+    match handler {
+        Some(handler) => handler(error),
+        None if !DISPATCHING.load(Ordering::Relaxed) => eprintln!("miniquad: {}", error),
+        None => {
+            if let Ok(mut pending) = PENDING_ERRORS.lock() {
+                if pending.len() == MAX_PENDING_ERRORS {
+                    pending.pop_front();
+                }
+                pending.push_back(error);
+            }
+        }
+    }
+}
+
+/// Errors reported since the last call, oldest first
+pub(crate) fn take_pending_errors() -> VecDeque<MiniquadError> {
+    DISPATCHING.store(true, Ordering::Relaxed);
+    PENDING_ERRORS
+        .lock()
+        .map(|mut pending| std::mem::take(&mut *pending))
+        .unwrap_or_default()
+}
 
 /// Main error type for all miniquad operations
 #[derive(Debug, Clone)]
@@ -32,6 +95,8 @@ pub enum ResourceError {
     AlreadyDeleted(usize),
     /// Resource is in invalid state for operation
     InvalidState(String),
+    /// Resource of another rendering context, every context has its own IDs
+    ForeignContext(usize),
     /// Resource limit exceeded
     LimitExceeded { limit: usize, requested: usize },
 }
@@ -49,6 +114,8 @@ pub enum GraphicsError {
     InvalidTextureFormat(String),
     /// Buffer creation failed
     BufferCreationFailed(String),
+    /// Framebuffer status is not complete, with the GL status code
+    FramebufferIncomplete(u32),
 }
 
 /// Platform-specific errors
@@ -62,6 +129,8 @@ pub enum PlatformError {
     LibraryLoadFailed(String),
     /// Feature not supported on platform
     FeatureUnsupported(String),
+    /// A call to the operating system failed
+    OsError(String),
 }
 
 /// Graphics API errors (OpenGL, Metal, etc.)
@@ -69,6 +138,8 @@ pub enum PlatformError {
 pub enum GraphicsApiError {
     /// OpenGL error
     OpenGL(GLError),
+    /// OpenGL error, with the call or operation that raised it
+    OpenGLCall { call: String, error: GLError },
     /// Metal error
     #[cfg(target_vendor = "apple")]
     Metal(String),
@@ -145,6 +216,9 @@ impl Display for ResourceError {
                 write!(f, "Resource with ID {} already deleted", id)
             }
             ResourceError::InvalidState(msg) => write!(f, "Resource in invalid state: {}", msg),
+            ResourceError::ForeignContext(id) => {
+                write!(f, "Resource with ID {} belongs to another context", id)
+            }
             ResourceError::LimitExceeded { limit, requested } => {
                 write!(
                     f,
@@ -168,6 +242,9 @@ impl Display for GraphicsError {
             GraphicsError::BufferCreationFailed(msg) => {
                 write!(f, "Buffer creation failed: {}", msg)
             }
+            GraphicsError::FramebufferIncomplete(status) => {
+                write!(f, "Framebuffer incomplete: 0x{:X}", status)
+            }
         }
     }
 }
@@ -183,6 +260,7 @@ impl Display for PlatformError {
             }
             PlatformError::LibraryLoadFailed(msg) => write!(f, "Library loading failed: {}", msg),
             PlatformError::FeatureUnsupported(msg) => write!(f, "Feature not supported: {}", msg),
+            PlatformError::OsError(msg) => write!(f, "OS error: {}", msg),
         }
     }
 }
@@ -191,6 +269,9 @@ impl Display for GraphicsApiError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             GraphicsApiError::OpenGL(e) => write!(f, "OpenGL error: {}", e),
+            GraphicsApiError::OpenGLCall { call, error } => {
+                write!(f, "OpenGL error in {}: {}", call, error)
+            }
             #[cfg(target_vendor = "apple")]
             GraphicsApiError::Metal(msg) => write!(f, "Metal error: {}", msg),
             #[cfg(target_arch = "wasm32")]
@@ -1,10 +1,23 @@
+use crate::error::MiniquadError;
+use crate::graphics::letterbox::{viewport, ScaleMode};
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
 #[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
-#[repr(u8)]
 pub enum MouseButton {
-    Left = 0,
-    Middle = 1,
-    Right = 2,
-    Unknown = 255,
+    Left,
+    Middle,
+    Right,
+    /// Side button, "back" in browsers
+    X1,
+    /// Second side button, "forward" in browsers
+    X2,
+    /// Any other button, with the platform's own number for it: the X11 button,
+    /// the Linux input event code on Wayland, the `NSEvent` button number on macOS
+    /// and the DOM `MouseEvent.button` on wasm
+    Other(u16),
+    Unknown,
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -144,6 +157,163 @@ pub enum KeyCode {
     Unknown = 0x01ff,
 }
 
+impl KeyCode {
+    /// What the key is labeled with in the current keyboard layout, for showing
+    /// bindings: `KeyCode::W` is "Z" on AZERTY. Letters are uppercase.
+    ///
+    /// Only keys of the typing block (letters, digits, punctuation) depend on
+    /// the layout, this is `None` for the rest.
+    /// Browsers without `navigator.keyboard` (Firefox, Safari) get the US label.
+    pub fn label_for_current_layout(self) -> Option<String> {
+        let code = self as u16;
+        let layout_dependent =
+            (0x21..0x7f).contains(&code) || self == KeyCode::World1 || self == KeyCode::World2;
+        if !layout_dependent {
+            return None;
+        }
+
+        crate::native::key_label(self)
+            .filter(|label| !label.trim().is_empty())
+            // KeyCode is the keysym of the printable keys
+            .or_else(|| {
+                char::from_u32(code as u32)
+                    .filter(|chr| chr.is_ascii_graphic())
+                    .map(String::from)
+            })
+            .map(|label| label.to_uppercase())
+    }
+}
+
+impl From<u16> for KeyCode {
+    /// Inverse of `keycode as u16`, values that are not a `KeyCode` become `Unknown`
+    fn from(keycode: u16) -> KeyCode {
+        match keycode {
+            0x0020 => KeyCode::Space,
+            0x0027 => KeyCode::Apostrophe,
+            0x002c => KeyCode::Comma,
+            0x002d => KeyCode::Minus,
+            0x002e => KeyCode::Period,
+            0x002f => KeyCode::Slash,
+            0x0030 => KeyCode::Key0,
+            0x0031 => KeyCode::Key1,
+            0x0032 => KeyCode::Key2,
+            0x0033 => KeyCode::Key3,
+            0x0034 => KeyCode::Key4,
+            0x0035 => KeyCode::Key5,
+            0x0036 => KeyCode::Key6,
+            0x0037 => KeyCode::Key7,
+            0x0038 => KeyCode::Key8,
+            0x0039 => KeyCode::Key9,
+            0x003b => KeyCode::Semicolon,
+            0x003d => KeyCode::Equal,
+            0x0041 => KeyCode::A,
+            0x0042 => KeyCode::B,
+            0x0043 => KeyCode::C,
+            0x0044 => KeyCode::D,
+            0x0045 => KeyCode::E,
+            0x0046 => KeyCode::F,
+            0x0047 => KeyCode::G,
+            0x0048 => KeyCode::H,
+            0x0049 => KeyCode::I,
+            0x004a => KeyCode::J,
+            0x004b => KeyCode::K,
+            0x004c => KeyCode::L,
+            0x004d => KeyCode::M,
+            0x004e => KeyCode::N,
+            0x004f => KeyCode::O,
+            0x0050 => KeyCode::P,
+            0x0051 => KeyCode::Q,
+            0x0052 => KeyCode::R,
+            0x0053 => KeyCode::S,
+            0x0054 => KeyCode::T,
+            0x0055 => KeyCode::U,
+            0x0056 => KeyCode::V,
+            0x0057 => KeyCode::W,
+            0x0058 => KeyCode::X,
+            0x0059 => KeyCode::Y,
+            0x005a => KeyCode::Z,
+            0x005b => KeyCode::LeftBracket,
+            0x005c => KeyCode::Backslash,
+            0x005d => KeyCode::RightBracket,
+            0x0060 => KeyCode::GraveAccent,
+            0x0100 => KeyCode::World1,
+            0x0101 => KeyCode::World2,
+            0xff1b => KeyCode::Escape,
+            0xff0d => KeyCode::Enter,
+            0xff09 => KeyCode::Tab,
+            0xff08 => KeyCode::Backspace,
+            0xff63 => KeyCode::Insert,
+            0xffff => KeyCode::Delete,
+            0xff53 => KeyCode::Right,
+            0xff51 => KeyCode::Left,
+            0xff54 => KeyCode::Down,
+            0xff52 => KeyCode::Up,
+            0xff55 => KeyCode::PageUp,
+            0xff56 => KeyCode::PageDown,
+            0xff50 => KeyCode::Home,
+            0xff57 => KeyCode::End,
+            0xffe5 => KeyCode::CapsLock,
+            0xff14 => KeyCode::ScrollLock,
+            0xff7f => KeyCode::NumLock,
+            0xfd1d => KeyCode::PrintScreen,
+            0xff13 => KeyCode::Pause,
+            0xffbe => KeyCode::F1,
+            0xffbf => KeyCode::F2,
+            0xffc0 => KeyCode::F3,
+            0xffc1 => KeyCode::F4,
+            0xffc2 => KeyCode::F5,
+            0xffc3 => KeyCode::F6,
+            0xffc4 => KeyCode::F7,
+            0xffc5 => KeyCode::F8,
+            0xffc6 => KeyCode::F9,
+            0xffc7 => KeyCode::F10,
+            0xffc8 => KeyCode::F11,
+            0xffc9 => KeyCode::F12,
+            0xffca => KeyCode::F13,
+            0xffcb => KeyCode::F14,
+            0xffcc => KeyCode::F15,
+            0xffcd => KeyCode::F16,
+            0xffce => KeyCode::F17,
+            0xffcf => KeyCode::F18,
+            0xffd0 => KeyCode::F19,
+            0xffd1 => KeyCode::F20,
+            0xffd2 => KeyCode::F21,
+            0xffd3 => KeyCode::F22,
+            0xffd4 => KeyCode::F23,
+            0xffd5 => KeyCode::F24,
+            0xffd6 => KeyCode::F25,
+            0xffb0 => KeyCode::Kp0,
+            0xffb1 => KeyCode::Kp1,
+            0xffb2 => KeyCode::Kp2,
+            0xffb3 => KeyCode::Kp3,
+            0xffb4 => KeyCode::Kp4,
+            0xffb5 => KeyCode::Kp5,
+            0xffb6 => KeyCode::Kp6,
+            0xffb7 => KeyCode::Kp7,
+            0xffb8 => KeyCode::Kp8,
+            0xffb9 => KeyCode::Kp9,
+            0xffae => KeyCode::KpDecimal,
+            0xffaf => KeyCode::KpDivide,
+            0xffaa => KeyCode::KpMultiply,
+            0xffad => KeyCode::KpSubtract,
+            0xffab => KeyCode::KpAdd,
+            0xff8d => KeyCode::KpEnter,
+            0xffbd => KeyCode::KpEqual,
+            0xffe1 => KeyCode::LeftShift,
+            0xffe3 => KeyCode::LeftControl,
+            0xffe9 => KeyCode::LeftAlt,
+            0xffeb => KeyCode::LeftSuper,
+            0xffe2 => KeyCode::RightShift,
+            0xffe4 => KeyCode::RightControl,
+            0xffea => KeyCode::RightAlt,
+            0xffec => KeyCode::RightSuper,
+            0xff67 => KeyCode::Menu,
+            0xff04 => KeyCode::Back,
+            _ => KeyCode::Unknown,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Default)]
 pub struct KeyMods {
     pub shift: bool,
@@ -160,6 +330,99 @@ pub enum TouchPhase {
     Cancelled,
 }
 
+/// Side or corner of the window the user drags in a live resize,
+/// see `EventHandler::live_resize_event`
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum ResizeEdge {
+    Left,
+    Right,
+    Top,
+    Bottom,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl ResizeEdge {
+    /// The width follows the mouse
+    pub fn changes_width(self) -> bool {
+        !matches!(self, ResizeEdge::Top | ResizeEdge::Bottom)
+    }
+
+    /// The height follows the mouse
+    pub fn changes_height(self) -> bool {
+        !matches!(self, ResizeEdge::Left | ResizeEdge::Right)
+    }
+}
+
+/// The device behind a `PointerEvent`
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum PointerKind {
+    Mouse,
+    Touch,
+    /// A stylus: a graphics tablet, Apple Pencil or Windows Ink pen
+    Pen,
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, Copy)]
+pub enum PointerPhase {
+    /// A button was pressed, or a finger or pen touched the surface
+    Down,
+    /// A button was released, or a finger or pen left the surface
+    Up,
+    /// Motion, with or without buttons down
+    Moved,
+    /// The OS took the touch over, e.g. for a system gesture
+    Cancelled,
+}
+
+/// Buttons held during a `PointerEvent`. A touching finger or pen holds `left`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub struct PointerButtons {
+    pub left: bool,
+    pub middle: bool,
+    pub right: bool,
+    pub x1: bool,
+    pub x2: bool,
+}
+
+impl PointerButtons {
+    pub fn any(self) -> bool {
+        self.left || self.middle || self.right || self.x1 || self.x2
+    }
+
+    fn set(&mut self, button: MouseButton, down: bool) {
+        match button {
+            MouseButton::Left => self.left = down,
+            MouseButton::Middle => self.middle = down,
+            MouseButton::Right => self.right = down,
+            MouseButton::X1 => self.x1 = down,
+            MouseButton::X2 => self.x2 = down,
+            MouseButton::Other(_) | MouseButton::Unknown => (),
+        }
+    }
+}
+
+/// Mouse, touch and pen input in one shape, see `EventHandler::pointer_event`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PointerEvent {
+    pub kind: PointerKind,
+    /// Tells the fingers of a multi-touch apart, the same id as in `touch_event`.
+    /// 0 for the mouse.
+    pub id: u64,
+    pub phase: PointerPhase,
+    /// The button pressed or released by a `Down` or `Up`, `Left` for a touch
+    pub button: Option<MouseButton>,
+    pub x: f32,
+    pub y: f32,
+    /// 0..1, 0 when hovering. Devices without pressure report 0.5 while a
+    /// button is down or the surface touched.
+    pub pressure: f32,
+    /// Buttons held after this event
+    pub buttons: PointerButtons,
+}
+
 /// A trait defining event callbacks.
 pub trait EventHandler {
     /// On most platforms update() and draw() are called each frame, sequentially,
@@ -171,14 +434,38 @@ pub trait EventHandler {
     /// Note that in this case drawing from update may lead to crashes.
     fn update(&mut self);
     fn draw(&mut self);
+    /// Not sent while the context presenting to the window has an internal
+    /// resolution, see `RenderingBackend::set_internal_resolution`: its default
+    /// pass keeps its size.
     fn resize_event(&mut self, _width: f32, _height: f32) {}
+    /// The user started (`active`) or stopped dragging a border of the window.
+    /// In between, `resize_event`s come live as the border moves, on Windows
+    /// as well, where the frames go on during the modal resize loop.
+    /// `edge` is the dragged side or corner, telling which axes change,
+    /// when the platform knows it.
+    /// Implemented on Windows and macOS, and on Wayland without the edge.
+    fn live_resize_event(&mut self, _active: bool, _edge: Option<ResizeEdge>) {}
     fn mouse_motion_event(&mut self, _x: f32, _y: f32) {}
     fn mouse_wheel_event(&mut self, _x: f32, _y: f32) {}
     fn mouse_button_down_event(&mut self, _button: MouseButton, _x: f32, _y: f32) {}
     fn mouse_button_up_event(&mut self, _button: MouseButton, _x: f32, _y: f32) {}
+    /// Second press of the same button within the system double-click time and
+    /// distance, see `window::double_click_settings`.
+    /// Delivered right after its `mouse_button_down_event`.
+    fn double_click_event(&mut self, _button: MouseButton, _x: f32, _y: f32) {}
 
     fn char_event(&mut self, _character: char, _keymods: KeyMods, _repeat: bool) {}
 
+    /// Text committed by the keyboard or an input method, as a whole: a dead key
+    /// composition, an IME conversion or an emoji comes in one piece, never as
+    /// half of a UTF-16 surrogate pair. What a text field should insert.
+    /// Control characters are left out, Backspace, Enter and Tab are key events.
+    /// Every character is also sent to `char_event`, right before.
+    fn text_input_event(&mut self, _text: &str) {}
+
+    /// `repeat` is set for the repeats of a held key, on every platform, the
+    /// same for its `char_event`s. `window::set_key_repeat_enabled(false)`
+    /// drops them.
     fn key_down_event(&mut self, _keycode: KeyCode, _keymods: KeyMods, _repeat: bool) {}
 
     /// Note: you are not always guaranteed to receive a key_up event. For example on
@@ -200,6 +487,15 @@ pub trait EventHandler {
         }
     }
 
+    /// The mouse, touch and pen events above in a single stream, for UI code
+    /// handling every device the same way. Each `mouse_motion_event`,
+    /// `mouse_button_down_event`, `mouse_button_up_event` and `touch_event` is
+    /// followed by its `PointerEvent`; the raw events are still delivered.
+    /// Pens are told apart from the mouse, and their pressure reported, on macOS
+    /// and iOS; Windows tells pens and touch screens apart, without pressure.
+    /// Elsewhere a pen or a touch screen driving the mouse comes as a mouse.
+    fn pointer_event(&mut self, _event: PointerEvent) {}
+
     /// Represents raw hardware mouse motion event
     /// Note that these events are delivered regardless of input focus and not in pixels, but in
     /// hardware units instead. And those units may be different from pixels depending on the target platform
@@ -225,10 +521,544 @@ pub trait EventHandler {
     /// On Andoid quit_requested_event is called on a Destroy ndk callback
     fn quit_requested_event(&mut self) {}
 
+    /// The OS is ending the session: logout, shutdown or reboot. Sent for
+    /// WM_QUERYENDSESSION on Windows, a logout Apple event on macOS and SIGTERM
+    /// on Linux, the time to save what the user would lose.
+    /// Like `quit_requested_event`, calling `window::cancel_quit` keeps the app
+    /// running; on Windows and macOS that also holds the logout off, unless the
+    /// app was started with `start_with_render_thread`.
+    /// The default implementation calls `quit_requested_event`.
+    fn session_end_event(&mut self) {
+        self.quit_requested_event();
+    }
+
+    /// The OS is running low on memory and asks the app to release what it can.
+    /// Right now is only implemented on Android and iOS,
+    /// On Android low_memory_event is called on onLowMemory and onTrimMemory
+    /// with a level of TRIM_MEMORY_RUNNING_LOW or higher.
+    /// On iOS it is called on applicationDidReceiveMemoryWarning.
+    /// Unused pooled GPU buffers are released automatically on the next commit_frame.
+    fn low_memory_event(&mut self) {}
+
     /// A file has been dropped over the application.
     /// Applications can request the number of dropped files with
     /// `ctx.dropped_file_count()`, path of an individual file with
     /// `ctx.dropped_file_path()`, and for wasm targets the file bytes
     /// can be requested with `ctx.dropped_file_bytes()`.
     fn files_dropped_event(&mut self) {}
+
+    /// Another application changed the clipboard, e.g. to enable a paste button.
+    /// Not sent for `window::clipboard_set` from this app.
+    /// Implemented on Windows, macOS, X11 (with libXfixes) and Wayland,
+    /// browsers don't tell.
+    fn clipboard_changed_event(&mut self) {}
+
+    /// The tray icon shown with `tray::show` was clicked or one of its menu
+    /// items chosen.
+    fn tray_event(&mut self, _event: crate::tray::TrayEvent) {}
+
+    /// A screen reader asked for an action, forwarded by the accessibility
+    /// adapter through `accessibility::ActionSender`. The request is whatever
+    /// the adapter sent, usually an `accesskit::ActionRequest` to downcast.
+    fn accessibility_action_event(&mut self, _request: Box<dyn std::any::Any + Send>) {}
+
+    /// The color picker opened with `colors::pick_color` was closed, with the
+    /// chosen color as RGBA in 0..1, or None if it was cancelled.
+    /// On macOS it is sent for every change while the panel is open.
+    fn color_picked_event(&mut self, _color: Option<(f32, f32, f32, f32)>) {}
+
+    /// The message box shown with `window::message_box_async` returning `id`
+    /// was closed with `button`.
+    fn message_box_event(&mut self, _id: u32, _button: crate::MessageBoxButton) {}
+
+    /// The XR session entered with `xr::request_session` started, ended or failed
+    #[cfg(feature = "webxr")]
+    fn xr_session_event(&mut self, _event: crate::xr::XrSessionEvent) {}
+
+    /// A tracked controller of the XR session, sent for each controller right
+    /// before the `update` of every XR frame
+    #[cfg(feature = "webxr")]
+    fn xr_controller_event(&mut self, _controller: crate::xr::XrController) {}
+
+    /// The GPU hung or the graphics device was reset, see `graphics::device_lost`
+    /// for what is detected where. Delivered once, right before `update`.
+    /// After a reset the rendering context can't be used anymore.
+    /// The default implementation prints the diagnostics to stderr.
+    fn device_lost_event(&mut self, lost: crate::graphics::device_lost::DeviceLost) {
+        eprintln!("miniquad: device lost: {:?}", lost);
+    }
+
+    /// Something went wrong, but miniquad could carry on: a GL error, an invalid
+    /// resource id, a failed clipboard access or cursor grab and the like.
+    /// Errors are collected during the frame and delivered right before `update`,
+    /// unless a handler was set with `miniquad::set_error_handler`.
+    /// The default implementation prints them to stderr.
+    fn error(&mut self, error: MiniquadError) {
+        eprintln!("miniquad: {}", error);
+    }
+}
+
+/// Deliver the errors and the device loss reported since the last frame,
+/// called by the platform event loops before `update`
+pub(crate) fn dispatch_errors(event_handler: &mut dyn EventHandler) {
+    for error in crate::error::take_pending_errors() {
+        event_handler.error(error);
+    }
+    if let Some(lost) = crate::graphics::device_lost::take() {
+        event_handler.device_lost_event(lost);
+    }
+}
+
+/// `char_event` for each character of `text`, then `text_input_event` with
+/// the printable ones, called by the platforms for every committed text
+pub(crate) fn dispatch_text(
+    event_handler: &mut dyn EventHandler,
+    text: &str,
+    keymods: KeyMods,
+    repeat: bool,
+) {
+    if repeat && !key_repeat_enabled() {
+        return;
+    }
+    for character in text.chars() {
+        event_handler.char_event(character, keymods, repeat);
+    }
+    if text.chars().any(char::is_control) {
+        let printable: String = text.chars().filter(|c| !c.is_control()).collect();
+        if !printable.is_empty() {
+            event_handler.text_input_event(&printable);
+        }
+    } else if !text.is_empty() {
+        event_handler.text_input_event(text);
+    }
+}
+
+/// `session_end_event` if the pending quit request comes from the OS ending the
+/// session, `quit_requested_event` otherwise
+pub(crate) fn dispatch_quit_requested(event_handler: &mut dyn EventHandler) {
+    let session_ending = crate::native_display().lock().unwrap().session_ending;
+    if session_ending {
+        event_handler.session_end_event();
+    } else {
+        event_handler.quit_requested_event();
+    }
+}
+
+/// Keep the state for `window::live_resize_edge` and send `live_resize_event`,
+/// called by the platforms when an interactive resize starts or ends
+pub(crate) fn dispatch_live_resize(
+    event_handler: &mut dyn EventHandler,
+    active: bool,
+    edge: Option<ResizeEdge>,
+) {
+    {
+        let mut d = crate::native_display().lock().unwrap();
+        if d.live_resize == active {
+            return;
+        }
+        d.live_resize = active;
+        d.live_resize_edge = edge.filter(|_| active);
+    }
+    event_handler.live_resize_event(active, edge.filter(|_| active));
+}
+
+/// `dispatch_text` for a single character
+pub(crate) fn dispatch_char(
+    event_handler: &mut dyn EventHandler,
+    character: char,
+    keymods: KeyMods,
+    repeat: bool,
+) {
+    dispatch_text(
+        event_handler,
+        character.encode_utf8(&mut [0; 4]),
+        keymods,
+        repeat,
+    );
+}
+
+/// How close two presses of a mouse button have to be to make a double click
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DoubleClickSettings {
+    /// Longest delay between the presses, in seconds
+    pub time: f64,
+    /// Largest distance between the presses, in pixels
+    pub distance: f32,
+}
+
+impl Default for DoubleClickSettings {
+    fn default() -> Self {
+        DEFAULT_DOUBLE_CLICK
+    }
+}
+
+const DEFAULT_DOUBLE_CLICK: DoubleClickSettings = DoubleClickSettings {
+    time: 0.5,
+    distance: 4.,
+};
+
+static DOUBLE_CLICK: Mutex<DoubleClickSettings> = Mutex::new(DEFAULT_DOUBLE_CLICK);
+
+/// How a held key repeats, see `window::key_repeat_settings`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct KeyRepeatSettings {
+    /// Delay before the first repeat, in seconds
+    pub delay: f64,
+    /// Repeats per second after that
+    pub rate: f64,
+}
+
+impl Default for KeyRepeatSettings {
+    fn default() -> Self {
+        DEFAULT_KEY_REPEAT
+    }
+}
+
+const DEFAULT_KEY_REPEAT: KeyRepeatSettings = KeyRepeatSettings {
+    delay: 0.5,
+    rate: 25.,
+};
+
+static KEY_REPEAT: Mutex<KeyRepeatSettings> = Mutex::new(DEFAULT_KEY_REPEAT);
+static KEY_REPEAT_ENABLED: AtomicBool = AtomicBool::new(true);
+
+// f64 bits of the last event timestamp
+static EVENT_TIMESTAMP: AtomicU64 = AtomicU64::new(0);
+
+// Position of the last mouse event in window framebuffer pixels, for the software cursor
+static MOUSE_POSITION: Mutex<Option<(f32, f32)>> = Mutex::new(None);
+
+// Internal resolution and scale mode of the context that presented the last
+// frame, see `RenderingBackend::set_internal_resolution`
+static PRESENTED_RESOLUTION: Mutex<Option<(u32, u32, ScaleMode)>> = Mutex::new(None);
+
+// The device and pressure of the next mouse or touch event
+static POINTER_SOURCE: Mutex<Option<(PointerKind, Option<f32>)>> = Mutex::new(None);
+
+/// Called by the platforms that know the user settings
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+pub(crate) fn set_double_click_settings(settings: DoubleClickSettings) {
+    *DOUBLE_CLICK.lock().unwrap() = settings;
+}
+
+pub(crate) fn double_click_settings() -> DoubleClickSettings {
+    *DOUBLE_CLICK.lock().unwrap()
+}
+
+/// Called by the platforms that know the user settings
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "linux"))]
+pub(crate) fn set_key_repeat_settings(settings: KeyRepeatSettings) {
+    *KEY_REPEAT.lock().unwrap() = settings;
+}
+
+pub(crate) fn key_repeat_settings() -> KeyRepeatSettings {
+    *KEY_REPEAT.lock().unwrap()
+}
+
+pub(crate) fn set_key_repeat_enabled(enabled: bool) {
+    KEY_REPEAT_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub(crate) fn key_repeat_enabled() -> bool {
+    KEY_REPEAT_ENABLED.load(Ordering::Relaxed)
+}
+
+/// Called right before a mouse or touch event by the platforms that know it
+/// comes from another kind of device, or its pressure
+#[cfg(any(target_os = "windows", target_os = "macos", target_os = "ios"))]
+pub(crate) fn set_pointer_source(kind: PointerKind, pressure: Option<f32>) {
+    *POINTER_SOURCE.lock().unwrap() = Some((kind, pressure));
+}
+
+pub(crate) fn take_pointer_source() -> Option<(PointerKind, Option<f32>)> {
+    POINTER_SOURCE.lock().unwrap().take()
+}
+
+pub(crate) fn last_mouse_position() -> Option<(f32, f32)> {
+    *MOUSE_POSITION.lock().unwrap()
+}
+
+/// Called by the backends supporting `RenderingBackend::set_internal_resolution`
+/// on `commit_frame`: the events follow the context the window shows
+pub(crate) fn set_presented_resolution(resolution: Option<(u32, u32, ScaleMode)>) {
+    *PRESENTED_RESOLUTION.lock().unwrap() = resolution;
+}
+
+/// Window framebuffer pixels to pixels of the default pass of the presenting
+/// context, which differ while it has an internal resolution
+fn to_default_pass(x: f32, y: f32) -> (f32, f32) {
+    let Some((width, height, mode)) = *PRESENTED_RESOLUTION.lock().unwrap() else {
+        return (x, y);
+    };
+    let (screen_width, screen_height) = crate::window::screen_size();
+    let (vx, vy, vw, vh) = viewport(width, height, mode, screen_width, screen_height);
+    if vw <= 0 || vh <= 0 {
+        return (x, y);
+    }
+    (
+        (x - vx as f32) / vw as f32 * width as f32,
+        (y - vy as f32) / vh as f32 * height as f32,
+    )
+}
+
+pub(crate) fn event_timestamp() -> f64 {
+    f64::from_bits(EVENT_TIMESTAMP.load(Ordering::Relaxed))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn monotonic_time() -> f64 {
+    use std::sync::OnceLock;
+    use std::time::Instant;
+
+    static START: OnceLock<Instant> = OnceLock::new();
+    START.get_or_init(Instant::now).elapsed().as_secs_f64()
+}
+
+// No Instant on wasm, the browser clock is the best there is
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn monotonic_time() -> f64 {
+    crate::date::now()
+}
+
+/// Wraps the user's event handler on every platform: timestamps the events,
+/// synthesizes `double_click_event` and `pointer_event` and maps positions into
+/// the internal resolution
+pub(crate) struct TimedEventHandler {
+    inner: Box<dyn EventHandler>,
+    // button, time and position of the last press that may start a double click
+    last_press: Option<(MouseButton, f64, f32, f32)>,
+    mouse_buttons: PointerButtons,
+}
+
+impl TimedEventHandler {
+    pub(crate) fn new(inner: Box<dyn EventHandler>) -> TimedEventHandler {
+        TimedEventHandler {
+            inner,
+            last_press: None,
+            mouse_buttons: PointerButtons::default(),
+        }
+    }
+
+    /// The `pointer_event` following a mouse event
+    fn mouse_pointer_event(
+        &mut self,
+        phase: PointerPhase,
+        button: Option<MouseButton>,
+        x: f32,
+        y: f32,
+    ) {
+        if let Some(button) = button {
+            self.mouse_buttons.set(button, phase == PointerPhase::Down);
+        }
+        let (kind, pressure) = take_pointer_source().unwrap_or((PointerKind::Mouse, None));
+        let buttons = self.mouse_buttons;
+        let pressure = pressure.unwrap_or(if buttons.any() { 0.5 } else { 0. });
+        self.inner.pointer_event(PointerEvent {
+            kind,
+            id: 0,
+            phase,
+            button,
+            x,
+            y,
+            pressure,
+            buttons,
+        });
+    }
+
+    /// Remember the window position for the software cursor, the events get
+    /// it in the default pass
+    fn track_mouse(&self, x: f32, y: f32) -> (f32, f32) {
+        *MOUSE_POSITION.lock().unwrap() = Some((x, y));
+        to_default_pass(x, y)
+    }
+
+    fn stamp(&self) -> f64 {
+        let time = monotonic_time();
+        EVENT_TIMESTAMP.store(time.to_bits(), Ordering::Relaxed);
+        time
+    }
+}
+
+impl EventHandler for TimedEventHandler {
+    fn update(&mut self) {
+        self.inner.update();
+    }
+    fn draw(&mut self) {
+        self.inner.draw();
+    }
+    fn resize_event(&mut self, width: f32, height: f32) {
+        // The default pass keeps its size, only the presentation changes
+        if PRESENTED_RESOLUTION.lock().unwrap().is_some() {
+            return;
+        }
+        self.stamp();
+        self.inner.resize_event(width, height);
+    }
+    fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.stamp();
+        let (x, y) = self.track_mouse(x, y);
+        self.inner.mouse_motion_event(x, y);
+        self.mouse_pointer_event(PointerPhase::Moved, None, x, y);
+    }
+    fn mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.stamp();
+        self.inner.mouse_wheel_event(x, y);
+    }
+    fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        let time = self.stamp();
+        let (x, y) = self.track_mouse(x, y);
+        self.inner.mouse_button_down_event(button, x, y);
+        self.mouse_pointer_event(PointerPhase::Down, Some(button), x, y);
+
+        let settings = double_click_settings();
+        let double_click = self
+            .last_press
+            .is_some_and(|(last, last_time, last_x, last_y)| {
+                last == button
+                    && time - last_time <= settings.time
+                    && (x - last_x).abs() <= settings.distance
+                    && (y - last_y).abs() <= settings.distance
+            });
+        if double_click {
+            // A third press starts over instead of making another double click
+            self.last_press = None;
+            self.inner.double_click_event(button, x, y);
+        } else {
+            self.last_press = Some((button, time, x, y));
+        }
+    }
+    fn mouse_button_up_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.stamp();
+        let (x, y) = self.track_mouse(x, y);
+        self.inner.mouse_button_up_event(button, x, y);
+        self.mouse_pointer_event(PointerPhase::Up, Some(button), x, y);
+    }
+    fn double_click_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.inner.double_click_event(button, x, y);
+    }
+    fn char_event(&mut self, character: char, keymods: KeyMods, repeat: bool) {
+        if repeat && !key_repeat_enabled() {
+            return;
+        }
+        self.stamp();
+        self.inner.char_event(character, keymods, repeat);
+    }
+    fn live_resize_event(&mut self, active: bool, edge: Option<ResizeEdge>) {
+        self.stamp();
+        self.inner.live_resize_event(active, edge);
+    }
+    fn text_input_event(&mut self, text: &str) {
+        self.stamp();
+        self.inner.text_input_event(text);
+    }
+    fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        if repeat && !key_repeat_enabled() {
+            return;
+        }
+        self.stamp();
+        self.inner.key_down_event(keycode, keymods, repeat);
+    }
+    fn key_up_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
+        self.stamp();
+        self.inner.key_up_event(keycode, keymods);
+    }
+    fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.stamp();
+        let (x, y) = to_default_pass(x, y);
+        self.inner.touch_event(phase, id, x, y);
+
+        let (kind, pressure) = take_pointer_source().unwrap_or((PointerKind::Touch, None));
+        let (phase, touching) = match phase {
+            TouchPhase::Started => (PointerPhase::Down, true),
+            TouchPhase::Moved => (PointerPhase::Moved, true),
+            TouchPhase::Ended => (PointerPhase::Up, false),
+            TouchPhase::Cancelled => (PointerPhase::Cancelled, false),
+        };
+        let button =
+            matches!(phase, PointerPhase::Down | PointerPhase::Up).then_some(MouseButton::Left);
+        self.inner.pointer_event(PointerEvent {
+            kind,
+            id,
+            phase,
+            button,
+            x,
+            y,
+            pressure: if touching {
+                pressure.unwrap_or(0.5)
+            } else {
+                0.
+            },
+            buttons: PointerButtons {
+                left: touching,
+                ..Default::default()
+            },
+        });
+    }
+    fn pointer_event(&mut self, event: PointerEvent) {
+        self.inner.pointer_event(event);
+    }
+    fn raw_mouse_motion(&mut self, dx: f32, dy: f32) {
+        self.stamp();
+        self.inner.raw_mouse_motion(dx, dy);
+    }
+    fn window_minimized_event(&mut self) {
+        self.stamp();
+        self.inner.window_minimized_event();
+    }
+    fn window_restored_event(&mut self) {
+        self.stamp();
+        self.inner.window_restored_event();
+    }
+    fn quit_requested_event(&mut self) {
+        self.stamp();
+        self.inner.quit_requested_event();
+    }
+    fn session_end_event(&mut self) {
+        self.stamp();
+        self.inner.session_end_event();
+    }
+    fn low_memory_event(&mut self) {
+        self.stamp();
+        self.inner.low_memory_event();
+    }
+    fn files_dropped_event(&mut self) {
+        self.stamp();
+        self.inner.files_dropped_event();
+    }
+    fn clipboard_changed_event(&mut self) {
+        self.stamp();
+        self.inner.clipboard_changed_event();
+    }
+    fn tray_event(&mut self, event: crate::tray::TrayEvent) {
+        self.stamp();
+        self.inner.tray_event(event);
+    }
+    fn accessibility_action_event(&mut self, request: Box<dyn std::any::Any + Send>) {
+        self.stamp();
+        self.inner.accessibility_action_event(request);
+    }
+    fn color_picked_event(&mut self, color: Option<(f32, f32, f32, f32)>) {
+        self.stamp();
+        self.inner.color_picked_event(color);
+    }
+    fn message_box_event(&mut self, id: u32, button: crate::MessageBoxButton) {
+        self.stamp();
+        self.inner.message_box_event(id, button);
+    }
+    #[cfg(feature = "webxr")]
+    fn xr_session_event(&mut self, event: crate::xr::XrSessionEvent) {
+        self.stamp();
+        self.inner.xr_session_event(event);
+    }
+    #[cfg(feature = "webxr")]
+    fn xr_controller_event(&mut self, controller: crate::xr::XrController) {
+        self.stamp();
+        self.inner.xr_controller_event(controller);
+    }
+    fn device_lost_event(&mut self, lost: crate::graphics::device_lost::DeviceLost) {
+        self.inner.device_lost_event(lost);
+    }
+    fn error(&mut self, error: MiniquadError) {
+        self.inner.error(error);
+    }
 }
@@ -0,0 +1,205 @@
+//! Frame rate limiter, see `window::set_target_frame_rate`, and the low latency
+//! mode, see `window::set_low_latency`.
+//! The platform event loops call `sync_after_swap` right after the buffer swap
+//! and `wait` once the frame is presented, the web asks `frame_due` before each
+//! animation frame instead, as it can't sleep. gl.js may also be told to call
+//! back less often, see `window::set_web_frame_strategy`.
+//! The frame hook of `window::set_frame_hook` is run by `frame_stats`, at the
+//! start and at the end of each frame.
+
+use crate::event::monotonic_time;
+
+use std::sync::Mutex;
+
+/// `thread::sleep` wakes up late by up to a scheduler tick, the rest is spun
+#[cfg(not(target_arch = "wasm32"))]
+const SPIN_MARGIN: f64 = 0.002;
+
+/// requestAnimationFrame timestamps jitter a bit around the refresh
+#[cfg(target_arch = "wasm32")]
+const RAF_TOLERANCE: f64 = 0.001;
+
+/// In nanoseconds, a hung GPU should not freeze the window for good
+#[cfg(not(target_arch = "wasm32"))]
+const SYNC_TIMEOUT: u64 = 100_000_000;
+
+struct State {
+    target_frame_rate: Option<u32>,
+    low_latency: bool,
+    // When the last frame was allowed to start, in seconds
+    last_deadline: Option<f64>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    target_frame_rate: None,
+    low_latency: false,
+    last_deadline: None,
+});
+
+/// When a hook of `window::set_frame_hook` is called
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum FramePhase {
+    /// A frame starts, before its events and `update`
+    Begin,
+    /// After `draw`, the window's buffers were swapped
+    Presented,
+}
+
+type FrameHook = Box<dyn FnMut(FramePhase) + Send>;
+
+/// The hook, and how many times it was set, so a hook replacing itself is not
+/// put back after running
+static FRAME_HOOK: Mutex<(Option<FrameHook>, u64)> = Mutex::new((None, 0));
+
+pub(crate) fn set_frame_hook(hook: Option<FrameHook>) {
+    if let Ok(mut frame_hook) = FRAME_HOOK.lock() {
+        frame_hook.0 = hook;
+        frame_hook.1 += 1;
+    }
+}
+
+/// Called by `frame_stats::begin_frame` and `end_frame`
+pub(crate) fn run_frame_hook(phase: FramePhase) {
+    let Some((mut hook, generation)) = FRAME_HOOK
+        .lock()
+        .ok()
+        .and_then(|mut frame_hook| Some((frame_hook.0.take()?, frame_hook.1)))
+    else {
+        return;
+    };
+    // Not locked while running, the hook may set another one
+    hook(phase);
+    if let Ok(mut frame_hook) = FRAME_HOOK.lock() {
+        if frame_hook.1 == generation {
+            frame_hook.0 = Some(hook);
+        }
+    }
+}
+
+pub(crate) fn set_target_frame_rate(frame_rate: Option<u32>) {
+    let frame_rate = frame_rate.filter(|frame_rate| *frame_rate > 0);
+    if let Ok(mut state) = STATE.lock() {
+        #[cfg(target_os = "windows")]
+        unsafe {
+            // The default timer resolution is 15.6ms, far too coarse to sleep a frame
+            use winapi::um::timeapi::{timeBeginPeriod, timeEndPeriod};
+            match (state.target_frame_rate.is_some(), frame_rate.is_some()) {
+                (false, true) => {
+                    timeBeginPeriod(1);
+                }
+                (true, false) => {
+                    timeEndPeriod(1);
+                }
+                _ => {}
+            }
+        }
+        state.target_frame_rate = frame_rate;
+        state.last_deadline = None;
+    }
+}
+
+pub(crate) fn target_frame_rate() -> Option<u32> {
+    STATE.lock().ok().and_then(|state| state.target_frame_rate)
+}
+
+pub(crate) fn set_web_frame_strategy(strategy: crate::conf::WebFrameStrategy) {
+    #[cfg(target_arch = "wasm32")]
+    {
+        use crate::conf::WebFrameStrategy;
+
+        // 0: every animation frame, 1: every nth, 2: timer at this rate
+        let (kind, param) = match strategy {
+            WebFrameStrategy::EveryNthAnimationFrame(n) if n > 1 => (1, n),
+            WebFrameStrategy::Timer(frame_rate) if frame_rate > 0 => (2, frame_rate),
+            _ => (0, 1),
+        };
+        unsafe { crate::native::wasm::sapp_set_frame_strategy(kind, param) };
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    let _ = strategy;
+}
+
+pub(crate) fn set_low_latency(low_latency: bool) {
+    if let Ok(mut state) = STATE.lock() {
+        state.low_latency = low_latency;
+    }
+}
+
+pub(crate) fn low_latency() -> bool {
+    STATE.lock().map(|state| state.low_latency).unwrap_or(false)
+}
+
+/// In low latency mode, wait for the GPU to be done with the frame just swapped,
+/// so the driver can't queue more frames. Called with the GL context current.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn sync_after_swap() {
+    use crate::native::gl::*;
+
+    if !low_latency() {
+        return;
+    }
+    let fences = ["glFenceSync", "glClientWaitSync", "glDeleteSync"];
+    unsafe {
+        if fences.iter().all(|f| is_gl_func_loaded(f)) {
+            let fence = glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0);
+            glClientWaitSync(fence, GL_SYNC_FLUSH_COMMANDS_BIT, SYNC_TIMEOUT);
+            glDeleteSync(fence);
+        } else {
+            glFinish();
+        }
+    }
+}
+
+/// When the next frame may start. Deadlines follow each other by the frame
+/// interval, so a frame that ran late is made up by the next one, but after
+/// an idle period (blocking event loop, minimized window) the cadence starts over.
+fn next_deadline(state: &State, now: f64) -> Option<f64> {
+    let interval = 1.0 / state.target_frame_rate? as f64;
+    Some(match state.last_deadline {
+        Some(last) if last + interval > now - interval => last + interval,
+        _ => now,
+    })
+}
+
+/// Sleep, then spin, until the next frame may start
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn wait() {
+    let Ok(mut state) = STATE.lock() else {
+        return;
+    };
+    let now = monotonic_time();
+    let Some(deadline) = next_deadline(&state, now) else {
+        return;
+    };
+    state.last_deadline = Some(deadline);
+    drop(state);
+
+    loop {
+        let remaining = deadline - monotonic_time();
+        if remaining <= 0.0 {
+            break;
+        }
+        if remaining > SPIN_MARGIN {
+            std::thread::sleep(std::time::Duration::from_secs_f64(remaining - SPIN_MARGIN));
+        } else {
+            std::hint::spin_loop();
+        }
+    }
+}
+
+/// Whether this animation frame should update and draw
+#[cfg(target_arch = "wasm32")]
+pub(crate) fn frame_due() -> bool {
+    let Ok(mut state) = STATE.lock() else {
+        return true;
+    };
+    let now = monotonic_time();
+    let Some(deadline) = next_deadline(&state, now) else {
+        return true;
+    };
+    if now + RAF_TOLERANCE < deadline {
+        return false;
+    }
+    state.last_deadline = Some(deadline);
+    true
+}
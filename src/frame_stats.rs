@@ -0,0 +1,200 @@
+//! Timings of the last frame, see `window::frame_stats`, and the predicted
+//! present time of the current one, see `window::present_timing`.
+//! The platform event loops call `begin_frame` before `update`, `begin_present`
+//! after `draw` and `end_frame` once the buffers are swapped. Platforms that know
+//! when the display refreshes report it with `record_vblank`. `begin_frame` and
+//! `end_frame` also run the hook of `window::set_frame_hook`.
+
+use crate::event::monotonic_time;
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+/// Frames looked at to detect the vsync interval
+const VSYNC_WINDOW: usize = 60;
+
+/// A swap taking longer than this waited for vsync
+const BLOCKED_SWAP_MS: f64 = 1.0;
+
+/// How the last frame was spent, to adapt quality settings at runtime
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct FrameStats {
+    /// Time between the starts of the last two frames
+    pub frame_time_ms: f64,
+    /// `update` and `draw` of the last frame, everything before the buffer swap
+    pub cpu_time_ms: f64,
+    /// GPU time from the first render pass to the end of the frame.
+    /// Timer queries report it a few frames late, so this is the latest frame
+    /// with a result. `None` without timer queries: GLES and WebGL.
+    pub gpu_time_ms: Option<f64>,
+    /// Time blocked in the buffer swap, mostly waiting for vsync.
+    /// Always 0 where the platform presents on its own: browsers, iOS
+    /// and Metal on macOS.
+    pub present_wait_ms: f64,
+    /// Time between presented frames while they are held back by vsync,
+    /// the refresh interval, or a multiple of it when frames keep missing it.
+    /// `None` until most of the last second of frames waited in the swap.
+    pub vsync_interval_ms: Option<f64>,
+}
+
+/// When the frame being drawn is expected on screen, to sample animations at
+/// the time they are seen rather than when `draw` runs
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PresentTiming {
+    /// Predicted time the frame reaches the screen, on the clock of
+    /// `window::event_timestamp`
+    pub present_time: f64,
+    /// Refresh interval of the display in seconds, `None` until known
+    pub refresh_interval: Option<f64>,
+    /// Whether the prediction is based on display timings from the platform:
+    /// Wayland presentation-time, DWM on Windows and CADisplayLink on iOS.
+    /// Elsewhere it is extrapolated from the buffer swaps.
+    pub from_display: bool,
+}
+
+struct State {
+    stats: FrameStats,
+    present_timing: PresentTiming,
+    // A refresh of the display reported by the platform, and its interval, in seconds
+    vblank: Option<(f64, Option<f64>)>,
+    frame_start: Option<f64>,
+    present_start: f64,
+    present_end: Option<f64>,
+    // (time since the previous swap, whether the swap blocked) of the latest frames
+    frames: VecDeque<(f64, bool)>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    stats: FrameStats {
+        frame_time_ms: 0.0,
+        cpu_time_ms: 0.0,
+        gpu_time_ms: None,
+        present_wait_ms: 0.0,
+        vsync_interval_ms: None,
+    },
+    present_timing: PresentTiming {
+        present_time: 0.0,
+        refresh_interval: None,
+        from_display: false,
+    },
+    vblank: None,
+    frame_start: None,
+    present_start: 0.0,
+    present_end: None,
+    frames: VecDeque::new(),
+});
+
+pub(crate) fn stats() -> FrameStats {
+    STATE.lock().map(|state| state.stats).unwrap_or_default()
+}
+
+pub(crate) fn present_timing() -> PresentTiming {
+    STATE
+        .lock()
+        .map(|state| state.present_timing)
+        .unwrap_or_default()
+}
+
+pub(crate) fn begin_frame() {
+    // Before taking the time, the hook may wait for the headset
+    crate::frame_pacing::run_frame_hook(crate::FramePhase::Begin);
+    let now = monotonic_time() * 1000.0;
+    if let Ok(mut state) = STATE.lock() {
+        if let Some(frame_start) = state.frame_start {
+            state.stats.frame_time_ms = now - frame_start;
+        }
+        state.frame_start = Some(now);
+        state.present_start = now;
+        state.present_timing = predict_present(&state, now / 1000.0);
+    }
+}
+
+/// The first refresh after the frame is done, assuming it takes as long as the last one
+fn predict_present(state: &State, now: f64) -> PresentTiming {
+    let done = now + state.stats.cpu_time_ms / 1000.0;
+    let vsync_interval = state.stats.vsync_interval_ms.map(|ms| ms / 1000.0);
+    let next_refresh = |refresh: f64, interval: f64| {
+        refresh + ((done - refresh) / interval).ceil().max(0.0) * interval
+    };
+
+    if let Some((vblank, interval)) = state.vblank {
+        if let Some(interval) = interval.or(vsync_interval) {
+            return PresentTiming {
+                present_time: next_refresh(vblank, interval),
+                refresh_interval: Some(interval),
+                from_display: true,
+            };
+        }
+    }
+    match (state.present_end, vsync_interval) {
+        // Swaps held by vsync end right at a refresh
+        (Some(present_end), Some(interval)) => PresentTiming {
+            present_time: next_refresh(present_end / 1000.0, interval),
+            refresh_interval: Some(interval),
+            from_display: false,
+        },
+        _ => PresentTiming {
+            present_time: now + state.stats.frame_time_ms / 1000.0,
+            refresh_interval: None,
+            from_display: false,
+        },
+    }
+}
+
+/// Called by the platforms with a time the display refreshed (or will), on the
+/// `monotonic_time` clock, and the refresh interval when known. All in seconds.
+#[cfg(any(target_os = "linux", target_os = "windows", target_os = "ios"))]
+pub(crate) fn record_vblank(time: f64, refresh_interval: Option<f64>) {
+    if let Ok(mut state) = STATE.lock() {
+        state.vblank = Some((time, refresh_interval.filter(|interval| *interval > 0.0)));
+    }
+}
+
+pub(crate) fn begin_present() {
+    let now = monotonic_time() * 1000.0;
+    if let Ok(mut state) = STATE.lock() {
+        state.stats.cpu_time_ms = now - state.frame_start.unwrap_or(now);
+        state.present_start = now;
+    }
+}
+
+pub(crate) fn end_frame() {
+    let now = monotonic_time() * 1000.0;
+    if let Ok(mut state) = STATE.lock() {
+        let present_wait_ms = now - state.present_start;
+        state.stats.present_wait_ms = present_wait_ms;
+
+        // Swap to swap, a frame held by vsync ends on the refresh
+        if let Some(present_end) = state.present_end {
+            let blocked = present_wait_ms > BLOCKED_SWAP_MS;
+            state.frames.push_back((now - present_end, blocked));
+            if state.frames.len() > VSYNC_WINDOW {
+                state.frames.pop_front();
+            }
+        }
+        state.present_end = Some(now);
+        state.stats.vsync_interval_ms = vsync_interval(&state.frames);
+    }
+    crate::frame_pacing::run_frame_hook(crate::FramePhase::Presented);
+}
+
+/// Median swap to swap time of the frames held by vsync, if most of them were
+fn vsync_interval(frames: &VecDeque<(f64, bool)>) -> Option<f64> {
+    let mut blocked: Vec<f64> = frames
+        .iter()
+        .filter(|(_, blocked)| *blocked)
+        .map(|(interval, _)| *interval)
+        .collect();
+    if frames.len() < VSYNC_WINDOW || blocked.len() < VSYNC_WINDOW / 2 {
+        return None;
+    }
+    blocked.sort_by(|a, b| a.total_cmp(b));
+    Some(blocked[blocked.len() / 2])
+}
+
+/// Called by the rendering backends when a GPU frame time is available
+pub(crate) fn record_gpu_time(gpu_time_ms: f64) {
+    if let Ok(mut state) = STATE.lock() {
+        state.stats.gpu_time_ms = Some(gpu_time_ms);
+    }
+}
@@ -1,24 +1,48 @@
 //mod texture;
 
+use crate::error::{GraphicsError, MiniquadError};
 use crate::native::gl::*;
 
 use std::{error::Error, fmt::Display};
 
 //pub use texture::{FilterMode, TextureAccess, TextureFormat, TextureParams, TextureWrap};
 
+#[cfg(feature = "basis")]
+pub mod basis;
 pub mod buffer_pool;
+pub mod capture;
 pub mod command_buffer;
+mod deferred_delete;
+pub mod device_lost;
+pub mod frame_stream;
 mod gl;
 mod gl_safety;
+#[cfg(feature = "golden-image")]
+pub mod golden;
+pub mod instanced_quad;
+pub mod letterbox;
+pub mod mesh;
+mod null;
+pub mod post_chain;
 pub mod profiling;
+mod readback;
+mod shader_log;
+pub mod software_cursor;
+#[cfg(feature = "sprite-batch")]
+pub mod sprite_batch;
+pub mod streaming_pool;
+pub mod texture_atlas;
+pub mod transient;
 
 pub use gl::raw_gl;
 pub use gl_safety::SafeGL;
+pub use null::{NullCall, NullContext};
+pub use shader_log::{ShaderDiagnostic, ShaderDiagnosticSeverity};
 
 #[cfg(target_vendor = "apple")]
 mod metal;
 
-pub use gl::GlContext;
+pub use gl::{GlContext, GlState};
 
 #[cfg(target_vendor = "apple")]
 pub use metal::MetalContext;
@@ -41,8 +65,15 @@ pub enum UniformType {
     Int3,
     /// Four unsigned 32-bit integers (equivalent to `[u32; 4]`)
     Int4,
+    /// Three by three matrix of 32-bit floats (equivalent to `[f32; 9]`).
+    /// Metal-specific note: columns are not padded, declare it as
+    /// `packed_float3[3]` in the MSL uniforms struct.
+    Mat3,
     /// Four by four matrix of 32-bit floats
     Mat4,
+    /// GLSL `bool`, stored as a 32-bit integer, 0 being false (equivalent to `u32`).
+    /// Metal-specific note: MSL `bool` is one byte, declare it as `uint`.
+    Bool,
 }
 
 impl UniformType {
@@ -57,7 +88,9 @@ impl UniformType {
             UniformType::Int2 => 8,
             UniformType::Int3 => 12,
             UniformType::Int4 => 16,
+            UniformType::Mat3 => 36,
             UniformType::Mat4 => 64,
+            UniformType::Bool => 4,
         }
     }
 }
@@ -89,6 +122,94 @@ impl UniformDesc {
             ..self
         }
     }
+
+    /// A struct uniform, or an array of `array_count` structs, as the uniforms
+    /// of its fields, named the way GLSL reports them: `light.color`, or
+    /// `lights[1].color` for arrays. The fields follow each other in the
+    /// uniforms struct without padding, like all the other uniforms.
+    /// Fields may be structs too, from a nested `structure` call:
+    /// ```
+    /// # use miniquad::*;
+    /// let light = [
+    ///     UniformDesc::new("position", UniformType::Float3),
+    ///     UniformDesc::new("color", UniformType::Float4),
+    /// ];
+    /// let mut uniforms = vec![UniformDesc::new("mvp", UniformType::Mat4)];
+    /// uniforms.extend(UniformDesc::structure("lights", &light, 4));
+    /// assert_eq!(uniforms[2].name, "lights[0].color");
+    /// ```
+    pub fn structure(name: &str, fields: &[UniformDesc], array_count: usize) -> Vec<UniformDesc> {
+        let element_name = |i: usize| {
+            if array_count == 1 {
+                name.to_string()
+            } else {
+                format!("{}[{}]", name, i)
+            }
+        };
+        (0..array_count)
+            .flat_map(|i| {
+                let element_name = element_name(i);
+                fields.iter().map(move |field| UniformDesc {
+                    name: format!("{}.{}", element_name, field.name),
+                    ..field.clone()
+                })
+            })
+            .collect()
+    }
+
+    /// Bytes taken in the uniforms struct, all the array elements included
+    pub fn size(&self) -> usize {
+        self.uniform_type.size() * self.array_count
+    }
+}
+
+impl UniformBlockLayout {
+    /// Bytes of the uniforms struct this layout describes
+    pub fn size(&self) -> usize {
+        self.uniforms.iter().map(UniformDesc::size).sum()
+    }
+}
+
+/// A `#[repr(C)]` uniforms struct knowing its layout, for
+/// `apply_uniforms_checked`. The layout can go in the `ShaderMeta` as well:
+/// ```
+/// # use miniquad::*;
+/// #[repr(C)]
+/// struct Uniforms {
+///     mvp: [f32; 16],
+///     tint: [f32; 4],
+/// }
+///
+/// impl UniformBlock for Uniforms {
+///     fn layout() -> UniformBlockLayout {
+///         UniformBlockLayout {
+///             uniforms: vec![
+///                 UniformDesc::new("mvp", UniformType::Mat4),
+///                 UniformDesc::new("tint", UniformType::Float4),
+///             ],
+///         }
+///     }
+/// }
+/// assert_eq!(Uniforms::layout().size(), std::mem::size_of::<Uniforms>());
+/// ```
+pub trait UniformBlock {
+    fn layout() -> UniformBlockLayout;
+}
+
+/// `size` bytes of uniforms against the `declared` size of the uniform block.
+/// Bigger is fine, Metal uniforms structs may need padding.
+#[cfg(debug_assertions)]
+pub(crate) fn check_uniforms_size(
+    size: usize,
+    declared: Option<usize>,
+) -> Result<(), MiniquadError> {
+    match declared {
+        Some(declared) if size < declared => Err(MiniquadError::InvalidParameter(format!(
+            "{} bytes of uniforms for a uniform block of {} bytes, the uniforms struct does not match the ShaderMeta",
+            size, declared
+        ))),
+        _ => Ok(()),
+    }
 }
 
 #[derive(Clone)]
@@ -214,7 +335,7 @@ pub enum VertexStep {
     PerInstance,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct BufferLayout {
     pub stride: i32,
     pub step_func: VertexStep,
@@ -231,7 +352,7 @@ impl Default for BufferLayout {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct VertexAttribute {
     pub name: &'static str,
     pub format: VertexFormat,
@@ -272,7 +393,7 @@ pub struct PipelineLayout {
     pub attributes: &'static [VertexAttribute],
 }
 
-#[derive(Clone, Debug, Copy)]
+#[derive(Clone, Debug, Copy, PartialEq, Eq)]
 pub enum ShaderType {
     Vertex,
     Fragment,
@@ -292,10 +413,84 @@ pub enum ShaderError {
     CompilationError {
         shader_type: ShaderType,
         error_message: String,
+        /// `error_message` split into messages with their source location
+        diagnostics: Vec<ShaderDiagnostic>,
     },
     LinkError(String),
     /// Shader strings should never contains \00 in the middle
     FFINulError(std::ffi::NulError),
+    /// The `UniformBlockLayout` does not describe the uniforms of the program,
+    /// returned by `try_new_pipeline`
+    UniformLayoutMismatch(Vec<UniformMismatch>),
+    /// The pipeline attributes are not the active attributes of the program,
+    /// returned by `try_new_pipeline`
+    AttributeMismatch(AttributeMismatch),
+}
+
+/// The vertex attributes of a pipeline next to the ones its program uses.
+/// An attribute the program does not have is either misspelled or unused and
+/// removed by the compiler, an attribute the program uses and the pipeline does
+/// not feed reads a constant.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AttributeMismatch {
+    /// Pipeline attributes the program does not have
+    pub unknown: Vec<String>,
+    /// Program attributes the pipeline does not have, e.g. `vec2 in_uv`
+    pub missing: Vec<String>,
+    /// All the pipeline attributes, e.g. `in_pos: Float3`
+    pub pipeline: Vec<String>,
+    /// All the active program attributes, e.g. `vec3 in_pos`
+    pub program: Vec<String>,
+}
+
+impl Display for AttributeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.unknown.is_empty() {
+            write!(f, "not in the program: {}; ", self.unknown.join(", "))?;
+        }
+        if !self.missing.is_empty() {
+            write!(f, "not in the pipeline: {}; ", self.missing.join(", "))?;
+        }
+        write!(
+            f,
+            "pipeline [{}], program [{}]",
+            self.pipeline.join(", "),
+            self.program.join(", ")
+        )
+    }
+}
+
+/// A uniform the `UniformBlockLayout` gets wrong, found by comparing it with the
+/// active uniforms of the linked program. Uniforms the compiler removed are not
+/// reported, nor are layout arrays longer than what the program uses.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UniformMismatch {
+    /// Name of the uniform, as in the layout
+    pub name: String,
+    /// Where the layout puts it in the uniforms struct, None when the layout
+    /// does not have it
+    pub offset: Option<usize>,
+    /// The layout entry, e.g. `Float3` or `Float4[2]`, None when missing
+    pub layout: Option<String>,
+    /// The GLSL declaration, e.g. `vec4` or `vec4[3]`
+    pub program: String,
+}
+
+impl Display for UniformMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (&self.layout, self.offset) {
+            (Some(layout), Some(offset)) => write!(
+                f,
+                "`{}` at offset {}: the layout has {}, the program {}",
+                self.name, offset, layout, self.program
+            ),
+            _ => write!(
+                f,
+                "`{}`: {} in the program, missing from the layout",
+                self.name, self.program
+            ),
+        }
+    }
 }
 
 impl From<std::ffi::NulError> for ShaderError {
@@ -310,9 +505,20 @@ impl Display for ShaderError {
             Self::CompilationError {
                 shader_type,
                 error_message,
+                ..
             } => write!(f, "{shader_type} shader error:\n{error_message}"),
             Self::LinkError(msg) => write!(f, "Link shader error:\n{msg}"),
             Self::FFINulError(e) => write!(f, "{e}"),
+            Self::UniformLayoutMismatch(mismatches) => {
+                write!(f, "Uniform layout does not match the program:")?;
+                for mismatch in mismatches {
+                    write!(f, "\n  {mismatch}")?;
+                }
+                Ok(())
+            }
+            Self::AttributeMismatch(mismatch) => {
+                write!(f, "Vertex attributes do not match the program: {mismatch}")
+            }
         }
     }
 }
@@ -330,6 +536,19 @@ pub enum TextureFormat {
     Depth,
     Depth32,
     Alpha,
+    /// Block compressed formats below are 4x4 blocks of 16 bytes.
+    /// They can only be static textures with the data given at creation:
+    /// no render targets, updates, resizes or read backs.
+    /// Check `Features::supports_format` first.
+    ///
+    /// BC3 (DXT5), desktop GPUs.
+    BC3,
+    /// BC7, desktop GPUs, better quality than BC3.
+    BC7,
+    /// ETC2 RGBA8 with EAC alpha, GLES3 devices.
+    ETC2RGBA8,
+    /// ASTC with 4x4 blocks, recent mobile GPUs.
+    ASTC4x4,
 }
 impl TextureFormat {
     /// Returns the size in bytes of texture with `dimensions`.
@@ -342,8 +561,22 @@ impl TextureFormat {
             TextureFormat::Depth => 2 * square,
             TextureFormat::Depth32 => 4 * square,
             TextureFormat::Alpha => 1 * square,
+            TextureFormat::BC3
+            | TextureFormat::BC7
+            | TextureFormat::ETC2RGBA8
+            | TextureFormat::ASTC4x4 => ((width + 3) / 4) * ((height + 3) / 4) * 16,
         }
     }
+
+    pub fn is_compressed(self) -> bool {
+        matches!(
+            self,
+            TextureFormat::BC3
+                | TextureFormat::BC7
+                | TextureFormat::ETC2RGBA8
+                | TextureFormat::ASTC4x4
+        )
+    }
 }
 
 /// Sets the wrap parameter for texture.
@@ -382,6 +615,47 @@ pub enum TextureAccess {
 pub enum TextureKind {
     Texture2D,
     CubeMap,
+    /// Multisampled render texture that shaders can read each sample of,
+    /// with `sampler2DMS` and `texelFetch`, for custom resolves.
+    /// Has `TextureParams::sample_count` samples, no filtering, wrapping or mipmaps.
+    /// Requires `Features::multisample_textures`.
+    Texture2DMultisample,
+    /// GL_TEXTURE_EXTERNAL_OES texture an Android SurfaceTexture renders into,
+    /// sampled with `samplerExternalOES`. Created with `new_external_texture`.
+    External,
+}
+
+/// How the storage of a texture is allocated
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Default)]
+pub enum TextureAllocation {
+    /// Level by level, the size may change with `texture_resize`
+    #[default]
+    Mutable,
+    /// All the faces and levels at once, with a fixed size and format:
+    /// `glTexStorage2D`. Required for texture views and some formats, and
+    /// drivers may place it better. Falls back to `Mutable` without
+    /// `Features::immutable_textures` and for compressed formats, which
+    /// `texture_params` reports. Immutable textures can't be resized.
+    ///
+    /// Metal-specific note: Metal textures are always immutable, this is ignored.
+    Immutable,
+}
+
+/// Platform video surface a texture can be imported from,
+/// see `RenderingBackend::new_external_texture`.
+#[derive(Debug, Clone, Copy)]
+pub enum ExternalImage<'a> {
+    /// Android, GL. An empty `TextureKind::External` texture to construct a
+    /// `android.graphics.SurfaceTexture` with, from `texture_raw_id`.
+    /// New frames are latched by `SurfaceTexture.updateTexImage` on the GL thread.
+    /// Requires `Features::external_textures`.
+    SurfaceTexture,
+    /// Wasm. Id of an `HTMLVideoElement` in the document,
+    /// its current frame is uploaded to an RGBA8 texture.
+    VideoElement(&'a str),
+    /// iOS and macOS, Metal. A BGRA `CVPixelBufferRef`, wrapped without a copy
+    /// through a `CVMetalTextureCache`.
+    PixelBuffer(*mut std::ffi::c_void),
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -399,14 +673,29 @@ pub struct TextureParams {
     // And reallocate non-mipmapped texture(on metal) on generateMipmaps call
     // But! Reallocating cubemaps is too much struggle, so leave it for later.
     pub allocate_mipmaps: bool,
+    /// See `TextureAllocation`, `Mutable` by default
+    pub allocate: TextureAllocation,
     /// Only used for render textures. `sample_count > 1` allows anti-aliased render textures.
     ///
     /// On OpenGL, for a `sample_count > 1` render texture, render buffer object will
-    /// be created instead of a regulat texture.
+    /// be created instead of a regulat texture, unless the kind is
+    /// `TextureKind::Texture2DMultisample`.
     ///
     pub sample_count: i32,
 }
 
+impl TextureParams {
+    /// Mip levels of the texture: the full chain down to 1x1 with
+    /// `allocate_mipmaps`, one otherwise
+    pub fn mip_level_count(&self) -> u32 {
+        if self.allocate_mipmaps && self.sample_count <= 1 {
+            32 - self.width.max(self.height).max(1).leading_zeros()
+        } else {
+            1
+        }
+    }
+}
+
 impl Default for TextureParams {
     fn default() -> Self {
         TextureParams {
@@ -419,6 +708,7 @@ impl Default for TextureParams {
             width: 0,
             height: 0,
             allocate_mipmaps: false,
+            allocate: TextureAllocation::Mutable,
             sample_count: 1,
         }
     }
@@ -446,6 +736,23 @@ impl TextureId {
     }
 }
 
+/// `texture` of `from` as a texture of `to`, for contexts sharing their GPU
+/// objects, e.g. two contexts made by `window::new_rendering_backend` for the
+/// same window. Every context has its own resource IDs, a texture of `from`
+/// used directly with `to` is reported as `ResourceError::ForeignContext`.
+///
+/// `from` keeps owning the texture: deleting the returned ID only forgets it
+/// in `to`, and it is not valid anymore once `from` deleted it.
+/// Multisampled textures can't be shared.
+pub fn share_texture(
+    from: &dyn RenderingBackend,
+    to: &mut dyn RenderingBackend,
+    texture: TextureId,
+) -> Result<TextureId, MiniquadError> {
+    let raw = unsafe { from.texture_raw_id(texture) };
+    to.import_shared_texture(raw, from.texture_params(texture))
+}
+
 /// Pixel arithmetic description for blending operations.
 /// Will be used in an equation:
 /// `equation(sfactor * source_color, dfactor * destination_color)`
@@ -542,6 +849,72 @@ pub enum CompareFunc {
 
 type ColorMask = (bool, bool, bool, bool);
 
+/// What a pass begins with in one of its attachments, see `AttachmentAction`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoadAction<T> {
+    /// The content left by the previous passes
+    Load,
+    Clear(T),
+    /// Anything, the pass overwrites all of it. Saves reading the attachment
+    /// into tile memory on tile-based GPUs, the same as `Load` elsewhere.
+    DontCare,
+}
+
+/// Load and store behavior of one attachment, see `PassAction::Attachments`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AttachmentAction<T> {
+    pub load: LoadAction<T>,
+    /// Keep what the pass drew once it ends. False lets tile-based GPUs skip
+    /// writing the attachment back to memory, e.g. for a depth buffer only
+    /// needed during the pass, its content is undefined afterwards.
+    pub store: bool,
+}
+
+impl<T> AttachmentAction<T> {
+    /// Loaded and stored, the attachment is preserved
+    pub fn load() -> AttachmentAction<T> {
+        AttachmentAction {
+            load: LoadAction::Load,
+            store: true,
+        }
+    }
+
+    pub fn clear(value: T) -> AttachmentAction<T> {
+        AttachmentAction {
+            load: LoadAction::Clear(value),
+            store: true,
+        }
+    }
+
+    pub fn dont_care() -> AttachmentAction<T> {
+        AttachmentAction {
+            load: LoadAction::DontCare,
+            store: true,
+        }
+    }
+
+    /// The same load, but not stored
+    pub fn discard(self) -> AttachmentAction<T> {
+        AttachmentAction {
+            store: false,
+            ..self
+        }
+    }
+
+    fn cleared(value: Option<T>) -> AttachmentAction<T> {
+        match value {
+            Some(value) => AttachmentAction::clear(value),
+            None => AttachmentAction::load(),
+        }
+    }
+}
+
+impl<T> Default for AttachmentAction<T> {
+    fn default() -> AttachmentAction<T> {
+        AttachmentAction::load()
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum PassAction {
     Nothing,
@@ -550,6 +923,18 @@ pub enum PassAction {
         depth: Option<f32>,
         stencil: Option<i32>,
     },
+    /// Load and store behavior per attachment, e.g. for MRT passes or to spare
+    /// tile-based GPUs memory traffic.
+    Attachments {
+        /// One per color attachment, in order, the others are preserved
+        colors: Vec<AttachmentAction<(f32, f32, f32, f32)>>,
+        depth: AttachmentAction<f32>,
+        stencil: AttachmentAction<i32>,
+    },
+    /// Clear the color attachments with the color the pass was last cleared
+    /// with, in the previous frame for the default pass, and depth to 1.
+    /// Transparent black for a pass never cleared.
+    ClearPrevious,
 }
 
 impl PassAction {
@@ -560,6 +945,88 @@ impl PassAction {
             stencil: None,
         }
     }
+
+    /// Color preserved, depth cleared
+    pub fn clear_depth(depth: f32) -> PassAction {
+        PassAction::Clear {
+            color: None,
+            depth: Some(depth),
+            stencil: None,
+        }
+    }
+
+    /// Color and depth preserved, stencil cleared
+    pub fn clear_stencil(stencil: i32) -> PassAction {
+        PassAction::Clear {
+            color: None,
+            depth: None,
+            stencil: Some(stencil),
+        }
+    }
+
+    /// Color preserved, depth and stencil cleared
+    pub fn clear_depth_stencil(depth: f32, stencil: i32) -> PassAction {
+        PassAction::Clear {
+            color: None,
+            depth: Some(depth),
+            stencil: Some(stencil),
+        }
+    }
+
+    /// Action of the color attachment `index`, whatever the variant.
+    /// `ClearPrevious` is resolved by the backends before, see `ClearColors`.
+    pub(crate) fn color(&self, index: usize) -> AttachmentAction<(f32, f32, f32, f32)> {
+        match self {
+            PassAction::Nothing | PassAction::ClearPrevious => AttachmentAction::load(),
+            PassAction::Clear { color, .. } => AttachmentAction::cleared(*color),
+            PassAction::Attachments { colors, .. } => {
+                colors.get(index).copied().unwrap_or_default()
+            }
+        }
+    }
+
+    pub(crate) fn depth(&self) -> AttachmentAction<f32> {
+        match self {
+            PassAction::Nothing | PassAction::ClearPrevious => AttachmentAction::load(),
+            PassAction::Clear { depth, .. } => AttachmentAction::cleared(*depth),
+            PassAction::Attachments { depth, .. } => *depth,
+        }
+    }
+
+    pub(crate) fn stencil(&self) -> AttachmentAction<i32> {
+        match self {
+            PassAction::Nothing | PassAction::ClearPrevious => AttachmentAction::load(),
+            PassAction::Clear { stencil, .. } => AttachmentAction::cleared(*stencil),
+            PassAction::Attachments { stencil, .. } => *stencil,
+        }
+    }
+}
+
+/// The last clear color of every pass, for `PassAction::ClearPrevious`
+#[derive(Debug, Default)]
+pub(crate) struct ClearColors(std::collections::HashMap<Option<RenderPass>, (f32, f32, f32, f32)>);
+
+impl ClearColors {
+    /// `action` with `ClearPrevious` replaced by a `Clear`, the color of the
+    /// first color attachment remembered
+    pub fn resolve(&mut self, pass: Option<RenderPass>, action: PassAction) -> PassAction {
+        let action = match action {
+            PassAction::ClearPrevious => PassAction::Clear {
+                color: Some(self.0.get(&pass).copied().unwrap_or((0.0, 0.0, 0.0, 0.0))),
+                depth: Some(1.),
+                stencil: None,
+            },
+            action => action,
+        };
+        if let LoadAction::Clear(color) = action.color(0).load {
+            self.0.insert(pass, color);
+        }
+        action
+    }
+
+    pub fn forget(&mut self, pass: RenderPass) {
+        self.0.remove(&Some(pass));
+    }
 }
 
 impl Default for PassAction {
@@ -572,11 +1039,130 @@ impl Default for PassAction {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// State `begin_pass` puts back to the pass defaults, so what one subsystem
+/// left behind, like a UI library's scissor or a stencil reference, does not leak
+/// into the next pass. See `RenderingBackend::set_pass_state_reset`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PassStateReset {
+    /// Viewport to the whole pass target and depth range to 0..1. Default true
+    pub viewport: bool,
+    /// Scissor test disabled. Default true
+    pub scissor: bool,
+    /// Stencil reference of both faces to 0, the function and masks are kept.
+    /// Default true
+    pub stencil_reference: bool,
+    /// Constant blend color to transparent black. Default true
+    pub blend_color: bool,
+    /// Send the defaults to GL even when the state cache says they are set
+    /// already, for foreign code making raw GL calls on the same context.
+    /// Default false
+    pub bypass_cache: bool,
+}
+
+impl Default for PassStateReset {
+    fn default() -> PassStateReset {
+        PassStateReset {
+            viewport: true,
+            scissor: true,
+            stencil_reference: true,
+            blend_color: true,
+            bypass_cache: false,
+        }
+    }
+}
+
+impl PassStateReset {
+    /// Reset nothing, every pass starts with the state the previous one left
+    pub fn none() -> PassStateReset {
+        PassStateReset {
+            viewport: false,
+            scissor: false,
+            stencil_reference: false,
+            blend_color: false,
+            bypass_cache: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct RenderPass(usize);
 
+/// A mip level, and cube map face, of a texture as a render pass attachment,
+/// see `RenderingBackend::try_new_render_pass_targets`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct RenderTarget {
+    pub texture: TextureId,
+    /// Mip level, 0 being the full size one
+    pub level: u32,
+    /// Cube map face in the +X, -X, +Y, -Y, +Z, -Z order, 0 for 2D textures
+    pub layer: u32,
+}
+
+impl RenderTarget {
+    /// The full size level of `texture`
+    pub fn new(texture: TextureId) -> RenderTarget {
+        RenderTarget {
+            texture,
+            level: 0,
+            layer: 0,
+        }
+    }
+
+    pub fn level(self, level: u32) -> RenderTarget {
+        RenderTarget { level, ..self }
+    }
+
+    pub fn layer(self, layer: u32) -> RenderTarget {
+        RenderTarget { layer, ..self }
+    }
+
+    /// The level and layer exist in a texture created with `params`
+    pub(crate) fn validate(&self, params: &TextureParams) -> Result<(), MiniquadError> {
+        let levels = params.mip_level_count();
+        if self.level >= levels {
+            return Err(MiniquadError::InvalidParameter(format!(
+                "Render target level {} of a {}x{} texture with {} levels",
+                self.level, params.width, params.height, levels
+            )));
+        }
+        let layers = match params.kind {
+            TextureKind::CubeMap => 6,
+            _ => 1,
+        };
+        if self.layer >= layers {
+            return Err(MiniquadError::InvalidParameter(format!(
+                "Render target layer {} of a {:?} texture with {} layers",
+                self.layer, params.kind, layers
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Upper bound of `Limits::max_vertex_attributes`
 pub const MAX_VERTEX_ATTRIBUTES: usize = 16;
-pub const MAX_SHADERSTAGE_IMAGES: usize = 12;
+/// Upper bound of `Limits::max_images`
+pub const MAX_SHADERSTAGE_IMAGES: usize = 16;
+
+/// What the device allows, queried at the context creation and capped by
+/// `MAX_VERTEX_ATTRIBUTES` and `MAX_SHADERSTAGE_IMAGES`
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Limits {
+    /// Vertex attributes of a pipeline, GL_MAX_VERTEX_ATTRIBS.
+    /// Some GLES2 devices only have 8.
+    pub max_vertex_attributes: usize,
+    /// Images a shader samples, GL_MAX_TEXTURE_IMAGE_UNITS
+    pub max_images: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_vertex_attributes: MAX_VERTEX_ATTRIBUTES,
+            max_images: MAX_SHADERSTAGE_IMAGES,
+        }
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Features {
@@ -588,6 +1174,42 @@ pub struct Features {
     /// With resolve_attachments: false, not-none resolve_img in new_render_pass will
     /// result in a runtime panic.
     pub resolve_attachments: bool,
+    /// Persistently mapped buffers (GL4.4 or ARB/EXT_buffer_storage) are available,
+    /// so `BufferOrphaning::Persistent` will not fall back to `BufferOrphaning::Orphan`.
+    pub persistent_mapping: bool,
+    /// `PipelineParams::primitive_restart` is available.
+    /// Would be false on WebGl1, GL2, GLES2 and GL3.0.
+    pub primitive_restart: bool,
+    /// `TextureKind::Texture2DMultisample` is available.
+    /// Would be true only on GL3.2+ and GLES3.1+.
+    pub multisample_textures: bool,
+    /// `ExternalImage::SurfaceTexture` is available, OES_EGL_image_external.
+    pub external_textures: bool,
+    /// `TextureFormat::BC3` textures are available, EXT_texture_compression_s3tc.
+    pub bc3_textures: bool,
+    /// `TextureFormat::BC7` textures are available, GL4.2 or ARB/EXT_texture_compression_bptc.
+    pub bc7_textures: bool,
+    /// `TextureFormat::ETC2RGBA8` textures are available, GLES3 or ARB_ES3_compatibility.
+    pub etc2_textures: bool,
+    /// `TextureFormat::ASTC4x4` textures are available, KHR_texture_compression_astc_ldr.
+    pub astc_textures: bool,
+    /// `TextureAllocation::Immutable` is available, GL4.2, GLES3, WebGL2 or
+    /// ARB_texture_storage.
+    pub immutable_textures: bool,
+}
+
+impl Features {
+    /// False for compressed formats the device can't sample from,
+    /// uncompressed formats are always supported.
+    pub fn supports_format(&self, format: TextureFormat) -> bool {
+        match format {
+            TextureFormat::BC3 => self.bc3_textures,
+            TextureFormat::BC7 => self.bc7_textures,
+            TextureFormat::ETC2RGBA8 => self.etc2_textures,
+            TextureFormat::ASTC4x4 => self.astc_textures,
+            _ => true,
+        }
+    }
 }
 
 impl Default for Features {
@@ -595,6 +1217,15 @@ impl Default for Features {
         Features {
             instancing: true,
             resolve_attachments: true,
+            persistent_mapping: false,
+            primitive_restart: true,
+            multisample_textures: false,
+            external_textures: false,
+            bc3_textures: false,
+            bc7_textures: false,
+            etc2_textures: false,
+            astc_textures: false,
+            immutable_textures: false,
         }
     }
 }
@@ -682,6 +1313,8 @@ pub enum PrimitiveType {
     Triangles,
     Lines,
     Points,
+    TriangleStrip,
+    LineStrip,
 }
 
 impl From<PrimitiveType> for GLenum {
@@ -690,6 +1323,25 @@ impl From<PrimitiveType> for GLenum {
             PrimitiveType::Triangles => GL_TRIANGLES,
             PrimitiveType::Lines => GL_LINES,
             PrimitiveType::Points => GL_POINTS,
+            PrimitiveType::TriangleStrip => GL_TRIANGLE_STRIP,
+            PrimitiveType::LineStrip => GL_LINE_STRIP,
+        }
+    }
+}
+
+/// Type of the indices in an index buffer
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum IndexType {
+    U16,
+    U32,
+}
+
+impl IndexType {
+    /// Byte size of one index
+    pub fn size(&self) -> usize {
+        match self {
+            IndexType::U16 => 2,
+            IndexType::U32 => 4,
         }
     }
 }
@@ -740,12 +1392,47 @@ pub struct PipelineParams {
     pub stencil_test: Option<StencilState>,
     pub color_write: ColorMask,
     pub primitive_type: PrimitiveType,
+    /// Type of the indices in the index buffer.
+    /// If None - taken from the element size the index buffer was created with.
+    pub index_type: Option<IndexType>,
+    /// Start a new strip at the maximum index value, `0xFFFF` for `IndexType::U16`
+    /// and `0xFFFFFFFF` for `IndexType::U32`.
+    /// Requires `Features::primitive_restart`.
+    ///
+    /// Metal and WebGl2 specific note: the maximum index always restarts the strips,
+    /// whatever this is set to.
+    pub primitive_restart: bool,
 }
 
 // TODO(next major version bump): should be PipelineId
 #[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
 pub struct Pipeline(usize);
 
+/// Arguments of a `new_pipeline` call, identical calls share the same pipeline
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct PipelineDesc {
+    pub buffer_layout: Vec<BufferLayout>,
+    pub attributes: Vec<VertexAttribute>,
+    pub shader: ShaderId,
+    pub params: PipelineParams,
+}
+
+impl PipelineDesc {
+    pub fn new(
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> PipelineDesc {
+        PipelineDesc {
+            buffer_layout: buffer_layout.to_vec(),
+            attributes: attributes.to_vec(),
+            shader,
+            params,
+        }
+    }
+}
+
 impl Default for PipelineParams {
     fn default() -> PipelineParams {
         PipelineParams {
@@ -759,6 +1446,8 @@ impl Default for PipelineParams {
             stencil_test: None,
             color_write: (true, true, true, true),
             primitive_type: PrimitiveType::Triangles,
+            index_type: None,
+            primitive_restart: false,
         }
     }
 }
@@ -795,6 +1484,29 @@ pub enum BufferUsage {
     Stream,
 }
 
+/// How `buffer_update` hands new data to the driver for a buffer.
+///
+/// The right choice depends a lot on the GPU: desktop drivers usually handle
+/// `SubData` well, while tile-based mobile GPUs may stall on it when the buffer
+/// is still in use by a previous frame and prefer `Orphan` or `MultiBuffer`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum BufferOrphaning {
+    /// Update the existing storage in place with `glBufferSubData`.
+    #[default]
+    SubData,
+    /// Re-specify the storage with `glBufferData(NULL)` before each update,
+    /// letting the driver hand out fresh memory while the old one is still in flight.
+    Orphan,
+    /// Keep N copies of the buffer and rotate to the next one on each update.
+    /// Only the data written by the latest `buffer_update` is visible.
+    MultiBuffer(usize),
+    /// Write straight into persistently mapped, fence-guarded copies of the buffer,
+    /// rotating between them like `MultiBuffer`.
+    /// Needs GL4.4 or ARB/EXT_buffer_storage, see `Features::persistent_mapping`,
+    /// otherwise behaves like `Orphan`.
+    Persistent,
+}
+
 fn gl_buffer_target(buffer_type: &BufferType) -> GLenum {
     match buffer_type {
         BufferType::VertexBuffer => GL_ARRAY_BUFFER,
@@ -1050,6 +1762,41 @@ pub enum ShaderSource<'a> {
     Msl { program: &'a str },
 }
 
+/// `#define` lines after the `#version` directive, if any, see `new_shader_with_defines`
+pub(crate) fn inject_defines(source: &str, defines: &[(&str, &str)]) -> String {
+    let defines: String = defines
+        .iter()
+        .map(|(name, value)| format!("#define {} {}\n", name, value))
+        .collect();
+    let version_start = skip_comments(source);
+    let version_end = source[version_start..].starts_with("#version").then(|| {
+        source[version_start..]
+            .find('\n')
+            .map_or(source.len(), |end| version_start + end + 1)
+    });
+    match version_end {
+        Some(end) if end == source.len() => format!("{}\n{}", source, defines),
+        Some(end) => format!("{}{}{}", &source[..end], defines, &source[end..]),
+        None => format!("{}{}", defines, source),
+    }
+}
+
+/// Offset of what follows the white space and comments `source` starts with,
+/// GLSL allows them before `#version`
+fn skip_comments(source: &str) -> usize {
+    let mut rest = source;
+    loop {
+        rest = rest.trim_start();
+        if let Some(comment) = rest.strip_prefix("//") {
+            rest = comment.find('\n').map_or("", |end| &comment[end..]);
+        } else if let Some(comment) = rest.strip_prefix("/*") {
+            rest = comment.find("*/").map_or("", |end| &comment[end + 2..]);
+        } else {
+            return source.len() - rest.len();
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Hash, Eq)]
 pub enum RawId {
     OpenGl(crate::native::gl::GLuint),
@@ -1090,6 +1837,32 @@ pub struct ContextInfo {
     /// List of platform-dependent features that miniquad failed to make cross-platforms
     /// and therefore they might be missing.
     pub features: Features,
+    /// The GPU the context runs on: GL_RENDERER on OpenGL, the device name on metal.
+    /// See `conf::Platform::gpu_preference`.
+    pub adapter: String,
+    /// What the window's framebuffer got, `Conf` only asks for it
+    pub default_framebuffer: FramebufferInfo,
+    pub limits: Limits,
+}
+
+/// The formats of the default framebuffer, read back after the context creation:
+/// the platform may give more or less than `Conf` and `conf::Platform` ask for,
+/// like no stencil or no multisampling.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct FramebufferInfo {
+    pub red_bits: u8,
+    pub green_bits: u8,
+    pub blue_bits: u8,
+    pub alpha_bits: u8,
+    /// 0 without a depth buffer
+    pub depth_bits: u8,
+    /// 0 without a stencil buffer
+    pub stencil_bits: u8,
+    /// 1 without multisampling
+    pub sample_count: i32,
+    /// Colors written by the shaders are encoded to sRGB. On OpenGL that also
+    /// needs `GL_FRAMEBUFFER_SRGB` enabled, on WebGL it is always false.
+    pub srgb: bool,
 }
 
 impl ContextInfo {
@@ -1134,12 +1907,84 @@ pub trait RenderingBackend {
         shader: ShaderSource,
         meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError>;
+    /// Same as `new_shader`, with a `#define NAME VALUE` line for each of
+    /// `defines` injected right after the `#version` directive, or at the top of
+    /// the source when there is none (always the case with Metal).
+    /// One source for all the permutations of a shader:
+    /// ```ignore
+    /// let skinned = ctx.new_shader_with_defines(source, meta.clone(), &[("SKINNED", "1")]);
+    /// let fog = ctx.new_shader_with_defines(source, meta, &[("FOG_DENSITY", "0.05")]);
+    /// ```
+    /// Line numbers in the compilation errors are the ones of the source given.
+    fn new_shader_with_defines(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        defines: &[(&str, &str)],
+    ) -> Result<ShaderId, ShaderError> {
+        match shader {
+            ShaderSource::Glsl { vertex, fragment } => {
+                let vertex = inject_defines(vertex, defines);
+                let fragment = inject_defines(fragment, defines);
+                self.new_shader(
+                    ShaderSource::Glsl {
+                        vertex: &vertex,
+                        fragment: &fragment,
+                    },
+                    meta,
+                )
+            }
+            ShaderSource::Msl { program } => {
+                let program = inject_defines(program, defines);
+                self.new_shader(ShaderSource::Msl { program: &program }, meta)
+            }
+        }
+    }
+    /// Panics if the texture can't be created, see `try_new_texture`.
     fn new_texture(
         &mut self,
         access: TextureAccess,
         data: TextureSource,
         params: TextureParams,
-    ) -> TextureId;
+    ) -> TextureId {
+        self.try_new_texture(access, data, params)
+            .unwrap_or_else(|err| panic!("Failed to create texture: {}", err))
+    }
+    /// Same as `new_texture`, but returns an error on invalid parameters
+    /// or when the graphics API fails to allocate the texture.
+    fn try_new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> Result<TextureId, MiniquadError>;
+    /// Texture showing a platform video surface, see `ExternalImage` for what each
+    /// platform takes. The texture is static, call `texture_update_external`
+    /// for every new video frame.
+    fn new_external_texture(&mut self, _image: ExternalImage) -> Result<TextureId, MiniquadError> {
+        Err(GraphicsError::Unsupported(
+            "external textures are not supported by this backend".to_string(),
+        )
+        .into())
+    }
+    /// Show the current frame of `image` in a texture from `new_external_texture`.
+    /// A no-op for `ExternalImage::SurfaceTexture`, the SurfaceTexture latches frames itself.
+    fn texture_update_external(
+        &mut self,
+        _texture: TextureId,
+        _image: ExternalImage,
+    ) -> Result<(), MiniquadError> {
+        Err(GraphicsError::Unsupported(
+            "external textures are not supported by this backend".to_string(),
+        )
+        .into())
+    }
+    /// Highest `TextureParams::sample_count` render textures of the given format
+    /// can be created with, 1 when multisampling is not available.
+    /// Render textures and passes asking for more fail with an error.
+    fn max_sample_count(&self, _format: TextureFormat) -> i32 {
+        1
+    }
     fn new_render_texture(&mut self, params: TextureParams) -> TextureId {
         self.new_texture(TextureAccess::RenderTarget, TextureSource::Empty, params)
     }
@@ -1165,6 +2010,7 @@ pub trait RenderingBackend {
                 mag_filter: FilterMode::Linear,
                 mipmap_filter: MipmapFilterMode::None,
                 allocate_mipmaps: false,
+                allocate: TextureAllocation::Mutable,
                 sample_count: 1,
             },
         )
@@ -1178,12 +2024,32 @@ pub trait RenderingBackend {
     /// Get OpenGL's GLuint texture ID or metals ObjcId
     unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId;
 
+    /// Register `raw`, a texture of another context sharing its GPU objects
+    /// with this one, as a texture of this context, see [`share_texture`].
+    /// Deleting it here leaves it to the context owning it.
+    ///
+    /// Metal-specific note: not implemented, returns `GraphicsError::Unsupported`.
+    fn import_shared_texture(
+        &mut self,
+        raw: RawId,
+        params: TextureParams,
+    ) -> Result<TextureId, MiniquadError> {
+        let _ = (raw, params);
+        Err(GraphicsError::Unsupported(
+            "import_shared_texture is not implemented by this backend".to_string(),
+        )
+        .into())
+    }
+
     /// Update whole texture content
     /// bytes should be width * height * 4 size - non rgba8 textures are not supported yet anyway
     fn texture_update(&mut self, texture: TextureId, bytes: &[u8]) {
         let (width, height) = self.texture_size(texture);
         self.texture_update_part(texture, 0 as _, 0 as _, width as _, height as _, bytes)
     }
+    /// Change how the texture is sampled, e.g. pixelated or smooth, in place:
+    /// the texture and its content are kept. Draws applying the texture after
+    /// the call use the new filters.
     fn texture_set_filter(
         &mut self,
         texture: TextureId,
@@ -1200,6 +2066,8 @@ pub trait RenderingBackend {
         mipmap_filter: MipmapFilterMode,
     );
     fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode);
+    /// Change the wrap of the texture in place, like `texture_set_filter`.
+    /// `texture_params` then reports `wrap_x` as `TextureParams::wrap`.
     fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap);
     /// Metal-specific note: if texture was created without `params.generate_mipmaps`
     /// `generate_mipmaps` will do nothing.
@@ -1209,6 +2077,48 @@ pub trait RenderingBackend {
     fn texture_generate_mipmaps(&mut self, texture: TextureId);
     fn texture_resize(&mut self, texture: TextureId, width: u32, height: u32, bytes: Option<&[u8]>);
     fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]);
+    /// Read the `(x, y, width, height)` part of the texture, in the coordinates
+    /// of `texture_update_part`, into `bytes` converted to `format`, e.g. the
+    /// id under the mouse in a picking buffer or a thumbnail. Rows are in the
+    /// order of `texture_read_pixels`.
+    ///
+    /// RGB8, RGBA8, RGBA16F and Alpha textures can be read, as any of these
+    /// formats: GLES only reads RGBA pixels, converted on the CPU.
+    /// `bytes` has to be `format.size(width, height)` long.
+    ///
+    /// Metal-specific note: not implemented, returns `GraphicsError::Unsupported`.
+    fn texture_read_region(
+        &mut self,
+        texture: TextureId,
+        rect: (i32, i32, i32, i32),
+        format: TextureFormat,
+        bytes: &mut [u8],
+    ) -> Result<(), MiniquadError> {
+        let _ = (texture, rect, format, bytes);
+        Err(GraphicsError::Unsupported(
+            "texture_read_region is not implemented by this backend".to_string(),
+        )
+        .into())
+    }
+    /// A new RGBA8 texture, as large as the default framebuffer, with what was
+    /// drawn on it so far this frame. The copy stays on the GPU, nothing is
+    /// read back. Called at the end of a frame, after the last pass and before
+    /// `commit_frame`, it is "what was on screen last frame" for the next one,
+    /// e.g. for a screen transition. Delete it with `delete_texture`.
+    ///
+    /// Metal-specific note: not implemented, panics.
+    fn capture_window_to_texture(&mut self) -> TextureId {
+        self.try_capture_window_to_texture()
+            .unwrap_or_else(|err| panic!("Failed to capture the window: {}", err))
+    }
+    /// Same as `capture_window_to_texture`, but returns an error when called
+    /// in a pass, or when the backend can't copy the default framebuffer.
+    fn try_capture_window_to_texture(&mut self) -> Result<TextureId, MiniquadError> {
+        Err(GraphicsError::Unsupported(
+            "capture_window_to_texture is not implemented by this backend".to_string(),
+        )
+        .into())
+    }
     fn texture_update_part(
         &mut self,
         texture: TextureId,
@@ -1225,6 +2135,14 @@ pub trait RenderingBackend {
     ) -> RenderPass {
         self.new_render_pass_mrt(&[color_img], None, depth_img)
     }
+    /// Same as `new_render_pass`, but returns an error instead of panicking.
+    fn try_new_render_pass(
+        &mut self,
+        color_img: TextureId,
+        depth_img: Option<TextureId>,
+    ) -> Result<RenderPass, MiniquadError> {
+        self.try_new_render_pass_mrt(&[color_img], None, depth_img)
+    }
     /// Same as "new_render_pass", but allows multiple color attachments.
     /// if `resolve_img` is set, MSAA-resolve operation will happen in `end_render_pass`
     /// this operation require `color_img` to have sample_count > 1,resolve_img have
@@ -1237,7 +2155,45 @@ pub trait RenderingBackend {
         color_img: &[TextureId],
         resolve_img: Option<&[TextureId]>,
         depth_img: Option<TextureId>,
-    ) -> RenderPass;
+    ) -> RenderPass {
+        self.try_new_render_pass_mrt(color_img, resolve_img, depth_img)
+            .unwrap_or_else(|err| panic!("Failed to create render pass: {}", err))
+    }
+    /// Same as `new_render_pass_mrt`, but returns an error on missing attachments
+    /// or an incomplete framebuffer.
+    fn try_new_render_pass_mrt(
+        &mut self,
+        color_img: &[TextureId],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<TextureId>,
+    ) -> Result<RenderPass, MiniquadError>;
+    /// Same as `try_new_render_pass_mrt` without resolve attachments, rendering
+    /// to a mip level, or a cube map face, of each texture: one pass per level
+    /// for a downsample chain or a bloom pyramid. Levels above 0 need textures
+    /// created with `allocate_mipmaps`, see `TextureParams::mip_level_count`.
+    ///
+    /// `delete_render_pass` does not delete the textures of these passes,
+    /// several passes may render to the levels of one texture.
+    ///
+    /// OpenGL-specific note: GLES 2 and WebGL 1 only render to level 0.
+    fn try_new_render_pass_targets(
+        &mut self,
+        color: &[RenderTarget],
+        depth: Option<RenderTarget>,
+    ) -> Result<RenderPass, MiniquadError> {
+        if color
+            .iter()
+            .chain(depth.iter())
+            .any(|t| t.level != 0 || t.layer != 0)
+        {
+            return Err(GraphicsError::Unsupported(
+                "Mip level and cube map face render targets".to_string(),
+            )
+            .into());
+        }
+        let color: Vec<TextureId> = color.iter().map(|target| target.texture).collect();
+        self.try_new_render_pass_mrt(&color, None, depth.map(|target| target.texture))
+    }
     /// panics for depth-only or multiple color attachment render pass
     /// This function is, mostly, legacy. Using "render_pass_color_attachments"
     /// is recommended instead.
@@ -1255,6 +2211,9 @@ pub trait RenderingBackend {
     /// For depth-only render pass returns empty slice.
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId];
     fn delete_render_pass(&mut self, render_pass: RenderPass);
+    /// Calls with the same arguments as a live pipeline return its handle again
+    /// instead of creating a new one. A shared pipeline is deleted once
+    /// `delete_pipeline` was called as many times as it was returned.
     fn new_pipeline(
         &mut self,
         buffer_layout: &[BufferLayout],
@@ -1262,8 +2221,77 @@ pub trait RenderingBackend {
         shader: ShaderId,
         params: PipelineParams,
     ) -> Pipeline;
+    /// Same as `new_pipeline`, but returns `ShaderError::UniformLayoutMismatch`
+    /// when the shader's `UniformBlockLayout` does not match the uniforms of its
+    /// program and `ShaderError::AttributeMismatch` when `attributes` are not the
+    /// ones the program uses. `new_pipeline` only reports them through
+    /// `error::report_error`.
+    ///
+    /// Metal-specific note: the layout is not checked.
+    fn try_new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Result<Pipeline, MiniquadError> {
+        Ok(self.new_pipeline(buffer_layout, attributes, shader, params))
+    }
     fn apply_pipeline(&mut self, pipeline: &Pipeline);
     fn delete_pipeline(&mut self, pipeline: Pipeline);
+    /// Amount of distinct pipelines alive, a pipeline returned by several
+    /// `new_pipeline` calls counts once.
+    fn pipelines_alive(&self) -> usize {
+        0
+    }
+
+    /// Override the depth test of the applied pipeline, `None` disables it.
+    /// Lasts until the next `apply_pipeline`, so pipelines differing only in this
+    /// state can be shared.
+    ///
+    /// Metal-specific note: not implemented, the pipeline state is kept.
+    fn set_depth_test(&mut self, _depth_test: Option<Comparison>) {}
+
+    /// Override the blending of the applied pipeline until the next `apply_pipeline`,
+    /// same arguments as `PipelineParams::color_blend` and `alpha_blend`.
+    ///
+    /// Metal-specific note: not implemented, the pipeline state is kept.
+    fn set_blend(&mut self, _color_blend: Option<BlendState>, _alpha_blend: Option<BlendState>) {}
+
+    /// Override the face culling of the applied pipeline until the next `apply_pipeline`.
+    ///
+    /// Metal-specific note: not implemented, the pipeline state is kept.
+    fn set_cull_face(&mut self, _cull_face: CullFace) {}
+
+    /// Override `PipelineParams::color_write` of the applied pipeline until the
+    /// next `apply_pipeline`, e.g. `(false, false, false, false)` for a depth
+    /// prepass drawn with the pipelines of the main pass.
+    ///
+    /// Metal-specific note: not implemented, the pipeline state is kept.
+    fn set_color_write(&mut self, _color_write: ColorMask) {}
+
+    /// Override `StencilFaceState::test_ref` of both faces of the applied pipeline
+    /// until the next `apply_pipeline`, for outlines or portals drawn with one
+    /// pipeline and many reference values.
+    ///
+    /// Metal-specific note: not implemented, the pipeline state is kept.
+    fn set_stencil_reference(&mut self, _reference: i32) {}
+
+    /// Override `StencilFaceState::test_mask` of both faces of the applied pipeline
+    /// until the next `apply_pipeline`.
+    ///
+    /// Metal-specific note: not implemented, the pipeline state is kept.
+    fn set_stencil_compare_mask(&mut self, _mask: u32) {}
+
+    /// Name the pipeline in the profiler statistics, see [`profiling::DrawStats`].
+    fn set_pipeline_label(&mut self, pipeline: Pipeline, label: &str) {
+        profiling::set_pipeline_label(pipeline, label);
+    }
+
+    /// Name the render pass in the profiler statistics and GPU pass timings.
+    fn set_render_pass_label(&mut self, render_pass: RenderPass, label: &str) {
+        profiling::set_render_pass_label(render_pass, label);
+    }
 
     /// Create a buffer resource object.
     /// ```ignore
@@ -1284,10 +2312,40 @@ pub trait RenderingBackend {
     ///        BufferSource::slice(&vertices),
     ///    );
     /// ```
-    fn new_buffer(&mut self, type_: BufferType, usage: BufferUsage, data: BufferSource)
-        -> BufferId;
+    fn new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> BufferId {
+        self.try_new_buffer(type_, usage, data)
+            .unwrap_or_else(|err| panic!("Failed to create buffer: {}", err))
+    }
+    /// Same as `new_buffer`, but returns an error on invalid parameters
+    /// or when the graphics API runs out of memory.
+    fn try_new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> Result<BufferId, MiniquadError>;
     fn buffer_update(&mut self, buffer: BufferId, data: BufferSource);
 
+    /// Write `data` at `offset` bytes into the buffer, keeping the rest of it,
+    /// without orphaning nor rotating copies: the GPU must be done reading that
+    /// range. Meant for appending data during a frame, see `transient`.
+    fn buffer_update_range(&mut self, _buffer: BufferId, _offset: usize, _data: BufferSource) {
+        crate::error::report_error(GraphicsError::Unsupported(
+            "buffer_update_range is not implemented by this backend".to_string(),
+        ));
+    }
+
+    /// Choose how subsequent `buffer_update` calls upload the data, see [`BufferOrphaning`].
+    ///
+    /// Metal-specific note: metal buffers are always rotated between internal copies,
+    /// so this is a no-op there.
+    fn buffer_set_orphaning(&mut self, _buffer: BufferId, _orphaning: BufferOrphaning) {}
+
     /// Size of buffer in bytes.
     /// For 1 element, u16 buffer this will return 2.
     fn buffer_size(&mut self, buffer: BufferId) -> usize;
@@ -1310,6 +2368,26 @@ pub trait RenderingBackend {
     /// and thats why this function is not marked as unsafe
     fn delete_texture(&mut self, texture: TextureId);
 
+    /// Index of the frame being recorded: the amount of `commit_frame` calls
+    /// since the context creation.
+    fn frame_index(&self) -> u64 {
+        0
+    }
+
+    /// `delete_buffer` once `frames` more frames are committed after the current
+    /// one, when the GPU can't be reading it anymore: a buffer used by this
+    /// frame's draw calls is safe to delete with `streaming_pool::FRAMES_IN_FLIGHT`.
+    /// The handle must not be used after this call.
+    fn delete_buffer_deferred(&mut self, buffer: BufferId, _frames: u32) {
+        self.delete_buffer(buffer);
+    }
+
+    /// `delete_texture` once `frames` more frames are committed after the current
+    /// one, see `delete_buffer_deferred`.
+    fn delete_texture_deferred(&mut self, texture: TextureId, _frames: u32) {
+        self.delete_texture(texture);
+    }
+
     /// Delete GPU program, leaving handle unmodified.
     ///
     /// More high-level code on top of miniquad probably is going to call this in Drop
@@ -1323,10 +2401,35 @@ pub trait RenderingBackend {
     /// Should be applied after begin_pass.
     fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32);
 
+    /// Set a new viewport rectangle with sub-pixel coordinates, and the
+    /// `min_depth..max_depth` range the depth is mapped to, instead of `0..1`.
+    /// `apply_viewport` and begin_pass set the depth range back to `0..1`.
+    /// Should be applied after begin_pass.
+    ///
+    /// GL-specific note: fractional coordinates need GL4.1, they are rounded otherwise.
+    fn apply_viewport_f(&mut self, x: f32, y: f32, w: f32, h: f32, min_depth: f32, max_depth: f32) {
+        let _ = (min_depth, max_depth);
+        self.apply_viewport(
+            x.round() as i32,
+            y.round() as i32,
+            w.round() as i32,
+            h.round() as i32,
+        );
+    }
+
     /// Set a new scissor rectangle.
     /// Should be applied after begin_pass.
     fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32);
 
+    /// Set a new scissor rectangle `(x, y, w, h)`, or turn scissoring off with None.
+    /// Scissoring is off at the start of every pass.
+    /// Should be applied after begin_pass.
+    fn apply_scissor(&mut self, rect: Option<(i32, i32, i32, i32)>) {
+        if let Some((x, y, w, h)) = rect {
+            self.apply_scissor_rect(x, y, w, h);
+        }
+    }
+
     fn apply_bindings_from_slice(
         &mut self,
         vertex_buffers: &[BufferId],
@@ -1345,23 +2448,111 @@ pub trait RenderingBackend {
     fn apply_uniforms(&mut self, uniforms: UniformsSource) {
         self.apply_uniforms_from_bytes(uniforms.0.ptr as _, uniforms.0.size)
     }
+    /// In debug builds, `size` smaller than the uniform block of the shader
+    /// is reported with `error::report_error` and nothing is applied, see
+    /// `uniform_block_size`. `apply_uniforms_checked` checks in all builds.
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize);
 
+    /// Bytes of the uniform block of the applied pipeline's shader, the size
+    /// of its `ShaderMeta::uniforms`. None without a pipeline applied, or on a
+    /// backend not keeping track of it.
+    fn uniform_block_size(&self) -> Option<usize> {
+        None
+    }
+
     fn clear(
         &mut self,
         color: Option<(f32, f32, f32, f32)>,
         depth: Option<f32>,
         stencil: Option<i32>,
     );
+
+    /// Clear the `(x, y, w, h)` rectangle of the current pass, in the coordinates
+    /// of `apply_scissor_rect`, e.g. a UI panel or a minimap, without a pass of
+    /// its own. The viewport, the scissor and the write masks of the applied
+    /// pipeline are left as they were, and do not restrict the clear.
+    /// Should be called after begin_pass.
+    ///
+    /// Metal-specific note: drawn as a triangle covering the rectangle, with a
+    /// pipeline of its own, and only for passes with the view pixel formats.
+    fn clear_region(
+        &mut self,
+        rect: (i32, i32, i32, i32),
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        let _ = (rect, color, depth, stencil);
+        crate::error::report_error(GraphicsError::Unsupported(
+            "clear_region is not implemented by this backend".to_string(),
+        ));
+    }
+
     /// start rendering to the default frame buffer
     fn begin_default_pass(&mut self, action: PassAction);
     /// start rendering to an offscreen framebuffer
     fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction);
 
+    /// What the following `begin_pass` calls reset, `PassStateReset::default()`
+    /// until changed.
+    ///
+    /// Does nothing on Metal, every pass gets a new render command encoder with
+    /// all of this state at its defaults.
+    fn set_pass_state_reset(&mut self, _reset: PassStateReset) {}
+
     fn end_render_pass(&mut self);
 
     fn commit_frame(&mut self);
 
+    /// Give a copy of every frame presented to the window to `callback`, a frame
+    /// after it was drawn, without stalling the rendering. `None` stops the stream.
+    /// Returns false when not supported, see `frame_stream`.
+    fn set_frame_stream(&mut self, _callback: Option<frame_stream::FrameCallback>) -> bool {
+        false
+    }
+
+    /// Draw `cursor` at the mouse position in every `commit_frame` while the
+    /// system cursor is hidden, see `software_cursor`. `None` stops it.
+    fn set_software_cursor(&mut self, _cursor: Option<software_cursor::SoftwareCursor>) {}
+
+    /// Keep the default pass at `width` x `height` pixels whatever the window
+    /// size, e.g. for heavy scenes on 4K monitors: it is drawn offscreen and
+    /// `commit_frame` scales it to the window with `mode`, see `letterbox`.
+    /// Only this context's default pass changes. Once it presents a frame, mouse
+    /// and touch events come in pixels of its default pass, `resize_event` is
+    /// not sent and `window::screen_size` stays the window's: the events follow
+    /// the context that presented last. `None` draws into the window again.
+    /// Call it outside of any pass.
+    /// Returns false when not supported.
+    ///
+    /// Metal-specific note: not implemented, returns false.
+    fn set_internal_resolution(
+        &mut self,
+        _resolution: Option<(u32, u32)>,
+        _mode: letterbox::ScaleMode,
+    ) -> bool {
+        false
+    }
+
+    /// Snapshot the GL state, for a context shared with foreign GL code: video
+    /// decoders, native UI, other renderers. Call it before rendering with miniquad
+    /// and give the result to `restore_gl_state` once done, the foreign code then
+    /// finds its state back. The state cache is invalidated, see `invalidate_state_cache`.
+    ///
+    /// `None` on Metal and on wasm.
+    fn save_gl_state(&mut self) -> Option<GlState> {
+        None
+    }
+
+    /// Put back the state saved by `save_gl_state`.
+    /// Call `save_gl_state` or `invalidate_state_cache` before rendering with miniquad again.
+    fn restore_gl_state(&mut self, _state: &GlState) {}
+
+    /// Forget the cached GL state and reset it to miniquad's defaults, after GL
+    /// calls were made behind miniquad's back. Pipeline and bindings have to be
+    /// applied again, the current pass goes on.
+    fn invalidate_state_cache(&mut self) {}
+
     /// Draw elements using currently applied bindings and pipeline.
     ///
     /// + `base_element` specifies starting offset in `index_buffer`.
@@ -1371,4 +2562,54 @@ pub trait RenderingBackend {
     /// NOTE: num_instances > 1 might be not supported by the GPU (gl2.1 and gles2).
     /// `features.instancing` check is required.
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32);
+
+    /// Bind only textures, for draws with no vertex or index buffers at all,
+    /// the vertex shader pulling its data from textures with `gl_VertexID`
+    /// (`[[vertex_id]]` on Metal).
+    /// Vertex attributes are disabled, the pipeline is expected to have none.
+    fn apply_images(&mut self, _images: &[TextureId]) {}
+
+    /// Draw vertices in order, without an index buffer, using currently applied
+    /// pipeline and bindings or images.
+    ///
+    /// + `base_vertex` specifies the first vertex, its `gl_VertexID`.
+    /// + `num_vertices` specifies how many vertices to draw.
+    /// + `num_instances` specifies how many instances should be rendered.
+    ///
+    /// NOTE: num_instances > 1 might be not supported by the GPU (gl2.1 and gles2).
+    /// `features.instancing` check is required.
+    fn draw_vertices(&self, _base_vertex: i32, _num_vertices: i32, _num_instances: i32) {}
+}
+
+impl dyn RenderingBackend + '_ {
+    /// `apply_uniforms` with `T::layout` checked, in all builds, against the
+    /// size of `T` and the uniform block of the applied pipeline's shader:
+    /// a uniforms struct drifting from the `ShaderMeta` is an error instead of
+    /// uniforms read out of bounds.
+    pub fn apply_uniforms_checked<T: UniformBlock>(
+        &mut self,
+        uniforms: &T,
+    ) -> Result<(), MiniquadError> {
+        let layout = T::layout();
+        if std::mem::size_of::<T>() < layout.size() {
+            return Err(MiniquadError::InvalidParameter(format!(
+                "`{}` is {} bytes, its layout {} bytes",
+                std::any::type_name::<T>(),
+                std::mem::size_of::<T>(),
+                layout.size()
+            )));
+        }
+        if let Some(declared) = self.uniform_block_size() {
+            if declared != layout.size() {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "`{}` is laid out in {} bytes, the uniform block of the shader is {} bytes",
+                    std::any::type_name::<T>(),
+                    layout.size(),
+                    declared
+                )));
+            }
+        }
+        self.apply_uniforms(UniformsSource::table(uniforms));
+        Ok(())
+    }
 }
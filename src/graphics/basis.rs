@@ -0,0 +1,254 @@
+//! Basis Universal textures
+//!
+//! `.basis` and `.ktx2` files store an intermediate format that is transcoded at
+//! load time to the best block compression the device supports, see `best_format`.
+//! The transcoder itself is a large C++ library and is not part of miniquad:
+//! plug one in through `Transcoder`, e.g. a thin wrapper around the
+//! `basis-universal` crate.
+//!
+//! KTX2 files already holding BC3, BC7, ETC2, ASTC 4x4 or RGBA8 data are uploaded
+//! as is, without a transcoder.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::basis::*;
+//! # fn load(ctx: &mut dyn RenderingBackend, transcoder: &mut dyn Transcoder) {
+//! let data = std::fs::read("assets/rock.ktx2").unwrap();
+//! let texture = new_texture(ctx, &data, Some(transcoder), TextureParams::default()).unwrap();
+//! # }
+//! ```
+
+use crate::error::{GraphicsError, MiniquadError};
+use crate::graphics::*;
+
+use std::convert::TryInto;
+
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A,
+];
+const BASIS_SIGNATURE: [u8; 2] = *b"sB";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Basis,
+    Ktx2,
+}
+
+impl Container {
+    /// Tells the container from the first bytes of the payload.
+    pub fn detect(data: &[u8]) -> Option<Container> {
+        if data.starts_with(&KTX2_IDENTIFIER) {
+            Some(Container::Ktx2)
+        } else if data.starts_with(&BASIS_SIGNATURE) {
+            Some(Container::Basis)
+        } else {
+            None
+        }
+    }
+}
+
+/// First image of a Basis payload, as reported by the transcoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Mip levels stored in the payload, at least 1
+    pub levels: u32,
+}
+
+/// Turns Basis Universal (ETC1S or UASTC) data into GPU formats.
+pub trait Transcoder {
+    /// Dimensions and mip levels of the first image of a `.basis` or `.ktx2` payload,
+    /// None if the payload can't be read.
+    fn info(&mut self, data: &[u8]) -> Option<ImageInfo>;
+    /// Mip `level` of the first image in `format`, one of the compressed formats
+    /// or `TextureFormat::RGBA8`. None if the transcoding failed.
+    fn transcode(&mut self, data: &[u8], level: u32, format: TextureFormat) -> Option<Vec<u8>>;
+}
+
+/// The format Basis payloads get transcoded to on a device with `features`.
+/// ASTC 4x4 and BC7 keep most of the quality, ETC2 and BC3 less so,
+/// uncompressed RGBA8 is the fallback.
+pub fn best_format(features: &Features) -> TextureFormat {
+    [
+        TextureFormat::ASTC4x4,
+        TextureFormat::BC7,
+        TextureFormat::ETC2RGBA8,
+        TextureFormat::BC3,
+    ]
+    .iter()
+    .copied()
+    .find(|format| features.supports_format(*format))
+    .unwrap_or(TextureFormat::RGBA8)
+}
+
+/// Creates a static 2D texture from a `.basis` or `.ktx2` payload, with all the
+/// mip levels it has. Wrap and filters are taken from `params`, the rest from the payload.
+///
+/// The transcoder is only needed for Basis data, KTX2 files in a GPU format are
+/// uploaded directly. RGBA8 textures only get their first level,
+/// use `texture_generate_mipmaps` for the rest.
+pub fn new_texture(
+    ctx: &mut dyn RenderingBackend,
+    data: &[u8],
+    transcoder: Option<&mut dyn Transcoder>,
+    params: TextureParams,
+) -> Result<TextureId, MiniquadError> {
+    let container = Container::detect(data).ok_or_else(|| {
+        GraphicsError::InvalidTextureFormat("not a .basis or .ktx2 payload".to_string())
+    })?;
+
+    if container == Container::Ktx2 {
+        let header = Ktx2Header::parse(data)?;
+        if header.vk_format != 0 {
+            return new_texture_from_ktx2(ctx, data, &header, params);
+        }
+    }
+
+    let Some(transcoder) = transcoder else {
+        return Err(GraphicsError::Unsupported(
+            "Basis Universal payloads need a Transcoder".to_string(),
+        )
+        .into());
+    };
+    let info = transcoder.info(data).ok_or_else(|| {
+        GraphicsError::InvalidTextureFormat("the transcoder can't read the payload".to_string())
+    })?;
+    let format = best_format(&ctx.info().features);
+    let levels = if format.is_compressed() {
+        info.levels.max(1)
+    } else {
+        1
+    };
+    let levels = (0..levels)
+        .map(|level| {
+            transcoder.transcode(data, level, format).ok_or_else(|| {
+                MiniquadError::from(GraphicsError::InvalidTextureFormat(format!(
+                    "failed to transcode level {} to {:?}",
+                    level, format
+                )))
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let levels: Vec<&[u8]> = levels.iter().map(|level| &level[..]).collect();
+
+    create_texture(ctx, format, info.width, info.height, &levels, params)
+}
+
+fn new_texture_from_ktx2(
+    ctx: &mut dyn RenderingBackend,
+    data: &[u8],
+    header: &Ktx2Header,
+    params: TextureParams,
+) -> Result<TextureId, MiniquadError> {
+    // miniquad has no sRGB textures, sRGB data is sampled as is, like RGBA8 is
+    let format = match header.vk_format {
+        37 | 43 => TextureFormat::RGBA8,
+        137 | 138 => TextureFormat::BC3,
+        145 | 146 => TextureFormat::BC7,
+        151 | 152 => TextureFormat::ETC2RGBA8,
+        157 | 158 => TextureFormat::ASTC4x4,
+        vk_format => {
+            return Err(GraphicsError::InvalidTextureFormat(format!(
+                "unsupported KTX2 vkFormat {}",
+                vk_format
+            ))
+            .into())
+        }
+    };
+    if header.supercompression != 0 {
+        return Err(GraphicsError::Unsupported(format!(
+            "KTX2 supercompression scheme {}",
+            header.supercompression
+        ))
+        .into());
+    }
+    if header.faces != 1 || header.layers > 1 || header.depth > 1 {
+        return Err(GraphicsError::Unsupported(
+            "only single 2D image KTX2 files are supported".to_string(),
+        )
+        .into());
+    }
+
+    let levels = (0..header.levels.max(1))
+        .map(|level| {
+            let entry = 80 + level as usize * 24;
+            let offset = read_u64(data, entry)? as usize;
+            let length = read_u64(data, entry + 8)? as usize;
+            data.get(offset..offset.checked_add(length)?)
+        })
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(truncated)?;
+
+    create_texture(ctx, format, header.width, header.height, &levels, params)
+}
+
+fn create_texture(
+    ctx: &mut dyn RenderingBackend,
+    format: TextureFormat,
+    width: u32,
+    height: u32,
+    levels: &[&[u8]],
+    params: TextureParams,
+) -> Result<TextureId, MiniquadError> {
+    let levels = if format.is_compressed() {
+        levels
+    } else {
+        &levels[..1]
+    };
+    let params = TextureParams {
+        kind: TextureKind::Texture2D,
+        format,
+        width,
+        height,
+        allocate_mipmaps: levels.len() > 1,
+        sample_count: 1,
+        ..params
+    };
+    ctx.try_new_texture(
+        TextureAccess::Static,
+        TextureSource::Array(&[levels]),
+        params,
+    )
+}
+
+struct Ktx2Header {
+    vk_format: u32,
+    width: u32,
+    height: u32,
+    depth: u32,
+    layers: u32,
+    faces: u32,
+    levels: u32,
+    supercompression: u32,
+}
+
+impl Ktx2Header {
+    fn parse(data: &[u8]) -> Result<Ktx2Header, MiniquadError> {
+        let field = |index: usize| read_u32(data, 12 + index * 4).ok_or_else(truncated);
+        Ok(Ktx2Header {
+            vk_format: field(0)?,
+            width: field(2)?,
+            height: field(3)?,
+            depth: field(4)?,
+            layers: field(5)?,
+            faces: field(6)?,
+            levels: field(7)?,
+            supercompression: field(8)?,
+        })
+    }
+}
+
+fn truncated() -> MiniquadError {
+    GraphicsError::InvalidTextureFormat("truncated KTX2 file".to_string()).into()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    let bytes = data.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    let bytes = data.get(offset..offset + 8)?;
+    Some(u64::from_le_bytes(bytes.try_into().unwrap()))
+}
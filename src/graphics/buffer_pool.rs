@@ -9,6 +9,7 @@
 //! - Usage pattern tracking (static, dynamic, stream)
 //! - Automatic pool size management with limits
 //! - Comprehensive statistics for monitoring
+//! - Trimming on demand and on OS memory pressure notifications
 
 use crate::graphics::*;
 use crate::native::gl::{
@@ -16,12 +17,29 @@ use crate::native::gl::{
     GL_DYNAMIC_DRAW, GL_ELEMENT_ARRAY_BUFFER, GL_STATIC_DRAW, GL_STREAM_DRAW,
 };
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// Buffer pool configuration
 const MIN_POOL_SIZE: usize = 8; // Minimum buffers per bucket
 const MAX_POOL_SIZE: usize = 64; // Maximum buffers per bucket
 const MAX_TOTAL_BUFFERS: usize = 512; // Total buffer limit across all pools
 
+/// Set by the platform layer when the OS asks the app to release memory,
+/// picked up by the rendering context on the next `commit_frame`.
+static MEMORY_PRESSURE: AtomicBool = AtomicBool::new(false);
+
+/// Request the pooled buffers to be trimmed on the next frame.
+/// miniquad calls this itself on Android and iOS low memory notifications,
+/// on other platforms applications may call it when they know memory is tight.
+pub fn notify_memory_pressure() {
+    MEMORY_PRESSURE.store(true, Ordering::Relaxed);
+}
+
+/// Was there a memory pressure notification since the last call.
+pub(crate) fn take_memory_pressure() -> bool {
+    MEMORY_PRESSURE.swap(false, Ordering::Relaxed)
+}
+
 /// Size buckets for efficient allocation (powers of 2)
 const SIZE_BUCKETS: &[usize] = &[
     512,     // 512B - Small vertex data
@@ -125,7 +143,7 @@ impl BufferPool {
     }
 
     /// Get the appropriate size bucket for a given size
-    fn get_size_bucket(size: usize) -> usize {
+    pub(crate) fn get_size_bucket(size: usize) -> usize {
         // Find the smallest bucket that can fit this size
         for &bucket_size in SIZE_BUCKETS {
             if size <= bucket_size {
@@ -308,6 +326,60 @@ impl BufferPool {
         }
     }
 
+    /// Delete unused pooled buffers, least recently used first, until the pool
+    /// takes no more than `target_bytes` of GPU memory.
+    /// Buffers currently in use are never touched, so the pool may stay above the target.
+    /// Returns the amount of bytes freed.
+    pub fn trim(&mut self, target_bytes: usize) -> usize {
+        let mut available: Vec<(PoolKey, usize, std::time::Instant)> = self
+            .pools
+            .iter()
+            .flat_map(|(key, pool)| {
+                pool.iter()
+                    .enumerate()
+                    .map(move |(i, buffer)| (*key, i, buffer.last_used))
+            })
+            .collect();
+        available.sort_by_key(|(_, _, last_used)| *last_used);
+
+        let mut to_delete: HashMap<PoolKey, Vec<usize>> = HashMap::new();
+        let mut memory_usage = self.stats.memory_usage_bytes;
+        for (key, i, _) in available {
+            if memory_usage <= target_bytes {
+                break;
+            }
+            memory_usage = memory_usage.saturating_sub(key.size_bucket);
+            to_delete.entry(key).or_default().push(i);
+        }
+
+        let mut total_cleaned = 0;
+        let mut memory_freed = 0;
+        for (key, mut indices) in to_delete {
+            let pool = self.pools.get_mut(&key).unwrap();
+            // Remove from the back so the remaining indices stay valid
+            indices.sort_unstable_by(|a, b| b.cmp(a));
+            for i in indices {
+                let buffer = pool.swap_remove(i);
+                unsafe {
+                    glDeleteBuffers(1, &buffer.gl_buf as *const _);
+                }
+                memory_freed += buffer.size;
+                total_cleaned += 1;
+            }
+        }
+
+        self.stats.total_buffers = self.stats.total_buffers.saturating_sub(total_cleaned);
+        self.stats.buffers_available = self.stats.buffers_available.saturating_sub(total_cleaned);
+        self.stats.memory_usage_bytes = self.stats.memory_usage_bytes.saturating_sub(memory_freed);
+
+        self.pools.retain(|_, pool| !pool.is_empty());
+
+        if total_cleaned > 0 {
+            self.update_efficiency();
+        }
+        memory_freed
+    }
+
     /// Force cleanup of all pooled buffers (useful for context loss)
     pub fn clear_all(&mut self) {
         for (_, pool) in self.pools.iter() {
@@ -0,0 +1,1073 @@
+//! Single frame API capture
+//!
+//! `CaptureBackend` wraps any rendering backend and, when asked, records every
+//! call of one frame with all the argument values. The dump also lists all the
+//! live resources created through the wrapper, with their parameters and labels.
+//!
+//! A poor man's RenderDoc for the platforms where a real capture is hard to get,
+//! like WebGL or Android drivers.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::capture::CaptureBackend;
+//! let mut ctx = CaptureBackend::new(window::new_rendering_backend());
+//! ctx.capture_next_frame();
+//! // ... render a couple of frames as usual, through `ctx` ...
+//! if let Some(capture) = ctx.take_capture() {
+//!     capture.save("frame.json").unwrap();
+//! }
+//! ```
+
+use crate::graphics::profiling::json_escape;
+use crate::graphics::*;
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// Buffer and texture data longer than this is truncated in the dump
+const MAX_DUMPED_BYTES: usize = 256;
+
+/// One backend call with its arguments, formatted
+#[derive(Debug, Clone)]
+pub struct CapturedCall {
+    pub name: &'static str,
+    pub args: Vec<(&'static str, String)>,
+}
+
+/// Everything recorded during one frame
+#[derive(Debug, Clone, Default)]
+pub struct FrameCapture {
+    /// Index of the captured frame, counted in `commit_frame` calls
+    pub frame: u64,
+    /// Resources alive when the capture started, by name
+    pub resources: BTreeMap<String, String>,
+    pub calls: Vec<CapturedCall>,
+}
+
+impl FrameCapture {
+    pub fn to_text(&self) -> String {
+        let mut res = String::new();
+        let _ = writeln!(res, "Frame {}", self.frame);
+        let _ = writeln!(res, "Resources:");
+        for (name, description) in &self.resources {
+            let _ = writeln!(res, "  {}: {}", name, description);
+        }
+        let _ = writeln!(res, "Calls:");
+        for (i, call) in self.calls.iter().enumerate() {
+            let args: Vec<String> = call
+                .args
+                .iter()
+                .map(|(name, value)| format!("{}: {}", name, value))
+                .collect();
+            let _ = writeln!(res, "  {:5} {}({})", i, call.name, args.join(", "));
+        }
+        res
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut res = String::new();
+        let _ = write!(res, "{{\"frame\":{},\"resources\":{{", self.frame);
+        for (i, (name, description)) in self.resources.iter().enumerate() {
+            if i != 0 {
+                res.push(',');
+            }
+            let _ = write!(
+                res,
+                "\"{}\":\"{}\"",
+                json_escape(name),
+                json_escape(description)
+            );
+        }
+        res.push_str("},\"calls\":[");
+        for (i, call) in self.calls.iter().enumerate() {
+            if i != 0 {
+                res.push(',');
+            }
+            let _ = write!(res, "{{\"name\":\"{}\",\"args\":{{", call.name);
+            for (j, (name, value)) in call.args.iter().enumerate() {
+                if j != 0 {
+                    res.push(',');
+                }
+                let _ = write!(res, "\"{}\":\"{}\"", name, json_escape(value));
+            }
+            res.push_str("}}");
+        }
+        res.push_str("]}");
+        res
+    }
+
+    /// Write the dump to a file, as JSON if the path ends with ".json"
+    /// and as text otherwise.
+    pub fn save(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        let path = path.as_ref();
+        let json = path.extension().is_some_and(|ext| ext == "json");
+        std::fs::write(path, if json { self.to_json() } else { self.to_text() })
+    }
+}
+
+fn texture_name(texture: TextureId) -> String {
+    match texture.0 {
+        TextureIdInner::Managed(id) => format!("texture {}", id),
+        TextureIdInner::Raw(raw) => format!("raw texture {:?}", raw),
+    }
+}
+
+fn buffer_name(buffer: BufferId) -> String {
+    format!("buffer {}", buffer.0)
+}
+
+fn shader_name(shader: ShaderId) -> String {
+    format!("shader {}", shader.0)
+}
+
+fn pipeline_name(pipeline: Pipeline) -> String {
+    format!(
+        "pipeline {} \"{}\"",
+        pipeline.0,
+        profiling::pipeline_label(pipeline)
+    )
+}
+
+fn pass_name(pass: Option<RenderPass>) -> String {
+    match pass {
+        None => "default pass".to_string(),
+        Some(pass) => format!(
+            "pass {} \"{}\"",
+            pass.0,
+            profiling::render_pass_label(Some(pass))
+        ),
+    }
+}
+
+fn list<T>(items: &[T], f: impl Fn(&T) -> String) -> String {
+    let items: Vec<String> = items.iter().map(f).collect();
+    format!("[{}]", items.join(", "))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut res = String::new();
+    for byte in bytes.iter().take(MAX_DUMPED_BYTES) {
+        let _ = write!(res, "{:02x}", byte);
+    }
+    if bytes.len() > MAX_DUMPED_BYTES {
+        let _ = write!(res, "... ({} bytes)", bytes.len());
+    }
+    res
+}
+
+fn buffer_source(data: &BufferSource) -> String {
+    match data {
+        BufferSource::Empty { size, element_size } => {
+            format!("empty {} bytes, element size {}", size, element_size)
+        }
+        BufferSource::Slice(arg) => {
+            let bytes = unsafe { std::slice::from_raw_parts(arg.ptr as *const u8, arg.size) };
+            format!(
+                "{} bytes, element size {}: {}",
+                arg.size,
+                arg.element_size,
+                hex(bytes)
+            )
+        }
+    }
+}
+
+/// Uniform values, decoded with the shader meta when available
+fn uniforms(meta: Option<&ShaderMeta>, bytes: &[u8]) -> String {
+    let meta = match meta {
+        Some(meta) => meta,
+        None => return hex(bytes),
+    };
+    let mut offset = 0;
+    let mut values = vec![];
+    for uniform in &meta.uniforms.uniforms {
+        let size = uniform.size();
+        let data = match bytes.get(offset..offset + size) {
+            Some(data) => data,
+            None => break,
+        };
+        offset += size;
+        let words = data.chunks_exact(4).map(|w| [w[0], w[1], w[2], w[3]]);
+        let value: Vec<String> = match uniform.uniform_type {
+            UniformType::Int1 | UniformType::Int2 | UniformType::Int3 | UniformType::Int4 => {
+                words.map(|w| i32::from_ne_bytes(w).to_string()).collect()
+            }
+            UniformType::Bool => words.map(|w| (w != [0; 4]).to_string()).collect(),
+            _ => words.map(|w| f32::from_ne_bytes(w).to_string()).collect(),
+        };
+        values.push(format!("{} = [{}]", uniform.name, value.join(", ")));
+    }
+    format!("{{{}}}", values.join("; "))
+}
+
+/// Records the calls of a single frame, see the module documentation
+pub struct CaptureBackend {
+    inner: Box<dyn RenderingBackend>,
+    frame: u64,
+    armed: bool,
+    // RefCell because `draw` takes &self
+    recording: RefCell<Option<FrameCapture>>,
+    finished: Option<FrameCapture>,
+    resources: BTreeMap<String, String>,
+    shaders: HashMap<ShaderId, ShaderMeta>,
+    pipeline_shaders: HashMap<Pipeline, ShaderId>,
+    current_pipeline: Option<Pipeline>,
+}
+
+impl CaptureBackend {
+    pub fn new(inner: Box<dyn RenderingBackend>) -> CaptureBackend {
+        CaptureBackend {
+            inner,
+            frame: 0,
+            armed: false,
+            recording: RefCell::new(None),
+            finished: None,
+            resources: BTreeMap::new(),
+            shaders: HashMap::new(),
+            pipeline_shaders: HashMap::new(),
+            current_pipeline: None,
+        }
+    }
+
+    /// Record all the calls between the next two `commit_frame` calls
+    pub fn capture_next_frame(&mut self) {
+        self.armed = true;
+    }
+
+    /// A capture was requested and is not finished yet
+    pub fn is_capturing(&self) -> bool {
+        self.armed || self.recording.borrow().is_some()
+    }
+
+    /// The last finished capture, if any
+    pub fn take_capture(&mut self) -> Option<FrameCapture> {
+        self.finished.take()
+    }
+
+    /// The wrapped backend. Calls made on it directly are not recorded.
+    pub fn inner(&mut self) -> &mut dyn RenderingBackend {
+        &mut *self.inner
+    }
+
+    fn record(&self, name: &'static str, args: impl FnOnce() -> Vec<(&'static str, String)>) {
+        if let Some(capture) = self.recording.borrow_mut().as_mut() {
+            capture.calls.push(CapturedCall { name, args: args() });
+        }
+    }
+
+    fn current_meta(&self) -> Option<&ShaderMeta> {
+        let shader = self.pipeline_shaders.get(&self.current_pipeline?)?;
+        self.shaders.get(shader)
+    }
+
+    fn shader_created(
+        &mut self,
+        name: &'static str,
+        source: String,
+        defines: &[(&str, &str)],
+        meta: ShaderMeta,
+        res: &Result<ShaderId, ShaderError>,
+    ) {
+        let uniforms: Vec<String> = meta
+            .uniforms
+            .uniforms
+            .iter()
+            .map(|u| format!("{}: {:?}[{}]", u.name, u.uniform_type, u.array_count))
+            .collect();
+        let images = meta.images.clone();
+        self.record(name, || {
+            let mut args = vec![("source", source)];
+            if !defines.is_empty() {
+                args.push(("defines", format!("{:?}", defines)));
+            }
+            args.extend([
+                ("uniforms", uniforms.join(", ")),
+                ("images", images.join(", ")),
+                ("result", format!("{:?}", res)),
+            ]);
+            args
+        });
+        if let Ok(id) = *res {
+            self.resources.insert(
+                shader_name(id),
+                format!("uniforms [{}], images {:?}", uniforms.join(", "), images),
+            );
+            self.shaders.insert(id, meta);
+        }
+    }
+
+    fn pipeline_created(
+        &mut self,
+        pipeline: Pipeline,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) {
+        let description = format!(
+            "{}, layout {:?}, attributes {:?}, {:?}",
+            shader_name(shader),
+            buffer_layout,
+            attributes,
+            params
+        );
+        self.record("new_pipeline", || {
+            vec![
+                ("description", description.clone()),
+                ("result", pipeline_name(pipeline)),
+            ]
+        });
+        self.resources
+            .insert(format!("pipeline {}", pipeline.0), description);
+        self.pipeline_shaders.insert(pipeline, shader);
+    }
+}
+
+impl RenderingBackend for CaptureBackend {
+    fn info(&self) -> ContextInfo {
+        self.inner.info()
+    }
+
+    fn new_shader(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        let source = format!("{:?}", shader);
+        let res = self.inner.new_shader(shader, meta.clone());
+        self.shader_created("new_shader", source, &[], meta, &res);
+        res
+    }
+
+    fn new_shader_with_defines(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        defines: &[(&str, &str)],
+    ) -> Result<ShaderId, ShaderError> {
+        let source = format!("{:?}", shader);
+        let res = self
+            .inner
+            .new_shader_with_defines(shader, meta.clone(), defines);
+        self.shader_created("new_shader_with_defines", source, defines, meta, &res);
+        res
+    }
+
+    fn try_new_texture(
+        &mut self,
+        access: TextureAccess,
+        data: TextureSource,
+        params: TextureParams,
+    ) -> Result<TextureId, MiniquadError> {
+        let source = match &data {
+            TextureSource::Empty => "empty".to_string(),
+            TextureSource::Bytes(bytes) => hex(bytes),
+            TextureSource::Array(faces) => format!("{} faces", faces.len()),
+        };
+        let texture = self.inner.try_new_texture(access, data, params)?;
+        self.record("new_texture", || {
+            vec![
+                ("access", format!("{:?}", access)),
+                ("data", source),
+                ("params", format!("{:?}", params)),
+                ("result", texture_name(texture)),
+            ]
+        });
+        self.resources
+            .insert(texture_name(texture), format!("{:?} {:?}", access, params));
+        Ok(texture)
+    }
+
+    fn new_external_texture(&mut self, image: ExternalImage) -> Result<TextureId, MiniquadError> {
+        let texture = self.inner.new_external_texture(image)?;
+        self.record("new_external_texture", || {
+            vec![
+                ("image", format!("{:?}", image)),
+                ("result", texture_name(texture)),
+            ]
+        });
+        self.resources
+            .insert(texture_name(texture), format!("{:?}", image));
+        Ok(texture)
+    }
+
+    fn texture_update_external(
+        &mut self,
+        texture: TextureId,
+        image: ExternalImage,
+    ) -> Result<(), MiniquadError> {
+        self.record("texture_update_external", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("image", format!("{:?}", image)),
+            ]
+        });
+        self.inner.texture_update_external(texture, image)
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.inner.texture_params(texture)
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        self.inner.texture_raw_id(texture)
+    }
+
+    fn import_shared_texture(
+        &mut self,
+        raw: RawId,
+        params: TextureParams,
+    ) -> Result<TextureId, MiniquadError> {
+        let texture = self.inner.import_shared_texture(raw, params)?;
+        self.record("import_shared_texture", || {
+            vec![
+                ("raw", format!("{:?}", raw)),
+                ("params", format!("{:?}", params)),
+                ("result", texture_name(texture)),
+            ]
+        });
+        self.resources
+            .insert(texture_name(texture), format!("shared {:?}", params));
+        Ok(texture)
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        self.record("texture_set_min_filter", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("filter", format!("{:?}", filter)),
+                ("mipmap_filter", format!("{:?}", mipmap_filter)),
+            ]
+        });
+        self.inner
+            .texture_set_min_filter(texture, filter, mipmap_filter);
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        self.record("texture_set_mag_filter", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("filter", format!("{:?}", filter)),
+            ]
+        });
+        self.inner.texture_set_mag_filter(texture, filter);
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
+        self.record("texture_set_wrap", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("wrap_x", format!("{:?}", wrap_x)),
+                ("wrap_y", format!("{:?}", wrap_y)),
+            ]
+        });
+        self.inner.texture_set_wrap(texture, wrap_x, wrap_y);
+    }
+
+    fn texture_generate_mipmaps(&mut self, texture: TextureId) {
+        self.record("texture_generate_mipmaps", || {
+            vec![("texture", texture_name(texture))]
+        });
+        self.inner.texture_generate_mipmaps(texture);
+    }
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        self.record("texture_resize", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("width", width.to_string()),
+                ("height", height.to_string()),
+                ("bytes", bytes.map_or_else(|| "none".to_string(), hex)),
+            ]
+        });
+        self.inner.texture_resize(texture, width, height, bytes);
+        let params = self.inner.texture_params(texture);
+        if let Some(description) = self.resources.get_mut(&texture_name(texture)) {
+            *description = format!("{:?}", params);
+        }
+    }
+
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        self.record("texture_read_pixels", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("bytes", format!("{} bytes", bytes.len())),
+            ]
+        });
+        self.inner.texture_read_pixels(texture, bytes);
+    }
+
+    fn texture_read_region(
+        &mut self,
+        texture: TextureId,
+        rect: (i32, i32, i32, i32),
+        format: TextureFormat,
+        bytes: &mut [u8],
+    ) -> Result<(), MiniquadError> {
+        self.record("texture_read_region", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("rect", format!("{:?}", rect)),
+                ("format", format!("{:?}", format)),
+            ]
+        });
+        self.inner.texture_read_region(texture, rect, format, bytes)
+    }
+
+    fn try_capture_window_to_texture(&mut self) -> Result<TextureId, MiniquadError> {
+        let texture = self.inner.try_capture_window_to_texture()?;
+        self.record("capture_window_to_texture", || {
+            vec![("result", texture_name(texture))]
+        });
+        let params = self.inner.texture_params(texture);
+        self.resources.insert(
+            texture_name(texture),
+            format!("{:?} {:?}", TextureAccess::RenderTarget, params),
+        );
+        Ok(texture)
+    }
+
+    fn texture_update_part(
+        &mut self,
+        texture: TextureId,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        self.record("texture_update_part", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("x_offset", x_offset.to_string()),
+                ("y_offset", y_offset.to_string()),
+                ("width", width.to_string()),
+                ("height", height.to_string()),
+                ("bytes", hex(bytes)),
+            ]
+        });
+        self.inner
+            .texture_update_part(texture, x_offset, y_offset, width, height, bytes);
+    }
+
+    fn try_new_render_pass_mrt(
+        &mut self,
+        color_img: &[TextureId],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<TextureId>,
+    ) -> Result<RenderPass, MiniquadError> {
+        let pass = self
+            .inner
+            .try_new_render_pass_mrt(color_img, resolve_img, depth_img)?;
+        let description = format!(
+            "color {}, resolve {}, depth {}",
+            list(color_img, |t| texture_name(*t)),
+            resolve_img.map_or_else(|| "none".to_string(), |r| list(r, |t| texture_name(*t))),
+            depth_img.map_or_else(|| "none".to_string(), texture_name)
+        );
+        self.record("new_render_pass_mrt", || {
+            vec![
+                ("attachments", description.clone()),
+                ("result", pass_name(Some(pass))),
+            ]
+        });
+        self.resources
+            .insert(format!("pass {}", pass.0), description);
+        Ok(pass)
+    }
+
+    fn try_new_render_pass_targets(
+        &mut self,
+        color: &[RenderTarget],
+        depth: Option<RenderTarget>,
+    ) -> Result<RenderPass, MiniquadError> {
+        let pass = self.inner.try_new_render_pass_targets(color, depth)?;
+        let target_name = |target: &RenderTarget| {
+            format!(
+                "{} level {} layer {}",
+                texture_name(target.texture),
+                target.level,
+                target.layer
+            )
+        };
+        let description = format!(
+            "color {}, depth {}",
+            list(color, target_name),
+            depth.map_or_else(|| "none".to_string(), |t| target_name(&t))
+        );
+        self.record("new_render_pass_targets", || {
+            vec![
+                ("attachments", description.clone()),
+                ("result", pass_name(Some(pass))),
+            ]
+        });
+        self.resources
+            .insert(format!("pass {}", pass.0), description);
+        Ok(pass)
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        self.inner.render_pass_color_attachments(render_pass)
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        self.record("delete_render_pass", || {
+            vec![("render_pass", pass_name(Some(render_pass)))]
+        });
+        self.resources.remove(&format!("pass {}", render_pass.0));
+        self.inner.delete_render_pass(render_pass);
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        let pipeline = self
+            .inner
+            .new_pipeline(buffer_layout, attributes, shader, params);
+        self.pipeline_created(pipeline, buffer_layout, attributes, shader, params);
+        pipeline
+    }
+
+    fn try_new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Result<Pipeline, MiniquadError> {
+        let pipeline = self
+            .inner
+            .try_new_pipeline(buffer_layout, attributes, shader, params)?;
+        self.pipeline_created(pipeline, buffer_layout, attributes, shader, params);
+        Ok(pipeline)
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        self.record("apply_pipeline", || {
+            vec![("pipeline", pipeline_name(*pipeline))]
+        });
+        self.current_pipeline = Some(*pipeline);
+        self.inner.apply_pipeline(pipeline);
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        self.record("delete_pipeline", || {
+            vec![("pipeline", pipeline_name(pipeline))]
+        });
+        self.resources.remove(&format!("pipeline {}", pipeline.0));
+        self.pipeline_shaders.remove(&pipeline);
+        self.inner.delete_pipeline(pipeline);
+    }
+
+    fn pipelines_alive(&self) -> usize {
+        self.inner.pipelines_alive()
+    }
+
+    fn max_sample_count(&self, format: TextureFormat) -> i32 {
+        self.inner.max_sample_count(format)
+    }
+
+    fn set_depth_test(&mut self, depth_test: Option<Comparison>) {
+        self.record("set_depth_test", || {
+            vec![("depth_test", format!("{:?}", depth_test))]
+        });
+        self.inner.set_depth_test(depth_test);
+    }
+
+    fn set_blend(&mut self, color_blend: Option<BlendState>, alpha_blend: Option<BlendState>) {
+        self.record("set_blend", || {
+            vec![
+                ("color_blend", format!("{:?}", color_blend)),
+                ("alpha_blend", format!("{:?}", alpha_blend)),
+            ]
+        });
+        self.inner.set_blend(color_blend, alpha_blend);
+    }
+
+    fn set_cull_face(&mut self, cull_face: CullFace) {
+        self.record("set_cull_face", || {
+            vec![("cull_face", format!("{:?}", cull_face))]
+        });
+        self.inner.set_cull_face(cull_face);
+    }
+
+    fn set_color_write(&mut self, color_write: ColorMask) {
+        self.record("set_color_write", || {
+            vec![("color_write", format!("{:?}", color_write))]
+        });
+        self.inner.set_color_write(color_write);
+    }
+
+    fn set_stencil_reference(&mut self, reference: i32) {
+        self.record("set_stencil_reference", || {
+            vec![("reference", reference.to_string())]
+        });
+        self.inner.set_stencil_reference(reference);
+    }
+
+    fn set_stencil_compare_mask(&mut self, mask: u32) {
+        self.record("set_stencil_compare_mask", || {
+            vec![("mask", format!("{:#x}", mask))]
+        });
+        self.inner.set_stencil_compare_mask(mask);
+    }
+
+    fn set_pipeline_label(&mut self, pipeline: Pipeline, label: &str) {
+        self.inner.set_pipeline_label(pipeline, label);
+    }
+
+    fn set_render_pass_label(&mut self, render_pass: RenderPass, label: &str) {
+        self.inner.set_render_pass_label(render_pass, label);
+    }
+
+    fn try_new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> Result<BufferId, MiniquadError> {
+        let source = buffer_source(&data);
+        let buffer = self.inner.try_new_buffer(type_, usage, data)?;
+        self.record("new_buffer", || {
+            vec![
+                ("type", format!("{:?}", type_)),
+                ("usage", format!("{:?}", usage)),
+                ("data", source),
+                ("result", buffer_name(buffer)),
+            ]
+        });
+        let size = self.inner.buffer_size(buffer);
+        self.resources.insert(
+            buffer_name(buffer),
+            format!("{:?} {:?}, {} bytes", type_, usage, size),
+        );
+        Ok(buffer)
+    }
+
+    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
+        self.record("buffer_update", || {
+            vec![
+                ("buffer", buffer_name(buffer)),
+                ("data", buffer_source(&data)),
+            ]
+        });
+        self.inner.buffer_update(buffer, data);
+    }
+
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
+        self.record("buffer_update_range", || {
+            vec![
+                ("buffer", buffer_name(buffer)),
+                ("offset", offset.to_string()),
+                ("data", buffer_source(&data)),
+            ]
+        });
+        self.inner.buffer_update_range(buffer, offset, data);
+    }
+
+    fn buffer_set_orphaning(&mut self, buffer: BufferId, orphaning: BufferOrphaning) {
+        self.record("buffer_set_orphaning", || {
+            vec![
+                ("buffer", buffer_name(buffer)),
+                ("orphaning", format!("{:?}", orphaning)),
+            ]
+        });
+        self.inner.buffer_set_orphaning(buffer, orphaning);
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.inner.buffer_size(buffer)
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        self.record("delete_buffer", || vec![("buffer", buffer_name(buffer))]);
+        self.resources.remove(&buffer_name(buffer));
+        self.inner.delete_buffer(buffer);
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        self.record("delete_texture", || {
+            vec![("texture", texture_name(texture))]
+        });
+        self.resources.remove(&texture_name(texture));
+        self.inner.delete_texture(texture);
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        self.record("delete_shader", || vec![("shader", shader_name(program))]);
+        self.resources.remove(&shader_name(program));
+        self.shaders.remove(&program);
+        self.inner.delete_shader(program);
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.record("apply_viewport", || {
+            vec![
+                ("x", x.to_string()),
+                ("y", y.to_string()),
+                ("w", w.to_string()),
+                ("h", h.to_string()),
+            ]
+        });
+        self.inner.apply_viewport(x, y, w, h);
+    }
+
+    fn apply_viewport_f(&mut self, x: f32, y: f32, w: f32, h: f32, min_depth: f32, max_depth: f32) {
+        self.record("apply_viewport_f", || {
+            vec![
+                ("x", x.to_string()),
+                ("y", y.to_string()),
+                ("w", w.to_string()),
+                ("h", h.to_string()),
+                ("min_depth", min_depth.to_string()),
+                ("max_depth", max_depth.to_string()),
+            ]
+        });
+        self.inner
+            .apply_viewport_f(x, y, w, h, min_depth, max_depth);
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.record("apply_scissor_rect", || {
+            vec![
+                ("x", x.to_string()),
+                ("y", y.to_string()),
+                ("w", w.to_string()),
+                ("h", h.to_string()),
+            ]
+        });
+        self.inner.apply_scissor_rect(x, y, w, h);
+    }
+
+    fn apply_scissor(&mut self, rect: Option<(i32, i32, i32, i32)>) {
+        self.record("apply_scissor", || vec![("rect", format!("{:?}", rect))]);
+        self.inner.apply_scissor(rect);
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: BufferId,
+        textures: &[TextureId],
+    ) {
+        self.record("apply_bindings", || {
+            vec![
+                ("vertex_buffers", list(vertex_buffers, |b| buffer_name(*b))),
+                ("index_buffer", buffer_name(index_buffer)),
+                ("textures", list(textures, |t| texture_name(*t))),
+            ]
+        });
+        self.inner
+            .apply_bindings_from_slice(vertex_buffers, index_buffer, textures);
+    }
+
+    fn apply_images(&mut self, images: &[TextureId]) {
+        self.record("apply_images", || {
+            vec![("textures", list(images, |t| texture_name(*t)))]
+        });
+        self.inner.apply_images(images);
+    }
+
+    // Same contract as the wrapped backend: `uniform_ptr` points to `size` readable bytes
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        self.record("apply_uniforms", || {
+            let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+            vec![
+                ("size", size.to_string()),
+                ("values", uniforms(self.current_meta(), bytes)),
+            ]
+        });
+        self.inner.apply_uniforms_from_bytes(uniform_ptr, size);
+    }
+
+    fn uniform_block_size(&self) -> Option<usize> {
+        self.inner.uniform_block_size()
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.record("clear", || {
+            vec![
+                ("color", format!("{:?}", color)),
+                ("depth", format!("{:?}", depth)),
+                ("stencil", format!("{:?}", stencil)),
+            ]
+        });
+        self.inner.clear(color, depth, stencil);
+    }
+
+    fn clear_region(
+        &mut self,
+        rect: (i32, i32, i32, i32),
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.record("clear_region", || {
+            vec![
+                ("rect", format!("{:?}", rect)),
+                ("color", format!("{:?}", color)),
+                ("depth", format!("{:?}", depth)),
+                ("stencil", format!("{:?}", stencil)),
+            ]
+        });
+        self.inner.clear_region(rect, color, depth, stencil);
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.begin_pass(None, action);
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        self.record("begin_pass", || {
+            vec![
+                ("pass", pass_name(pass)),
+                ("action", format!("{:?}", action)),
+            ]
+        });
+        self.inner.begin_pass(pass, action);
+    }
+
+    fn set_pass_state_reset(&mut self, reset: PassStateReset) {
+        self.record("set_pass_state_reset", || {
+            vec![("reset", format!("{:?}", reset))]
+        });
+        self.inner.set_pass_state_reset(reset);
+    }
+
+    fn end_render_pass(&mut self) {
+        self.record("end_render_pass", Vec::new);
+        self.inner.end_render_pass();
+    }
+
+    fn frame_index(&self) -> u64 {
+        self.inner.frame_index()
+    }
+
+    fn delete_buffer_deferred(&mut self, buffer: BufferId, frames: u32) {
+        self.record("delete_buffer_deferred", || {
+            vec![
+                ("buffer", buffer_name(buffer)),
+                ("frames", frames.to_string()),
+            ]
+        });
+        self.resources.remove(&buffer_name(buffer));
+        self.inner.delete_buffer_deferred(buffer, frames);
+    }
+
+    fn delete_texture_deferred(&mut self, texture: TextureId, frames: u32) {
+        self.record("delete_texture_deferred", || {
+            vec![
+                ("texture", texture_name(texture)),
+                ("frames", frames.to_string()),
+            ]
+        });
+        self.resources.remove(&texture_name(texture));
+        self.inner.delete_texture_deferred(texture, frames);
+    }
+
+    fn commit_frame(&mut self) {
+        self.record("commit_frame", Vec::new);
+        self.inner.commit_frame();
+
+        if let Some(capture) = self.recording.get_mut().take() {
+            self.finished = Some(capture);
+        }
+        self.frame += 1;
+        if self.armed {
+            self.armed = false;
+            *self.recording.get_mut() = Some(FrameCapture {
+                frame: self.frame,
+                resources: self.resources.clone(),
+                calls: vec![],
+            });
+        }
+    }
+
+    fn set_frame_stream(&mut self, callback: Option<frame_stream::FrameCallback>) -> bool {
+        self.record("set_frame_stream", || {
+            vec![("callback", callback.is_some().to_string())]
+        });
+        self.inner.set_frame_stream(callback)
+    }
+
+    fn set_software_cursor(&mut self, cursor: Option<software_cursor::SoftwareCursor>) {
+        self.record("set_software_cursor", || {
+            vec![("cursor", format!("{:?}", cursor))]
+        });
+        self.inner.set_software_cursor(cursor);
+    }
+
+    fn set_internal_resolution(
+        &mut self,
+        resolution: Option<(u32, u32)>,
+        mode: letterbox::ScaleMode,
+    ) -> bool {
+        self.record("set_internal_resolution", || {
+            vec![
+                ("resolution", format!("{:?}", resolution)),
+                ("mode", format!("{:?}", mode)),
+            ]
+        });
+        self.inner.set_internal_resolution(resolution, mode)
+    }
+
+    fn save_gl_state(&mut self) -> Option<GlState> {
+        self.record("save_gl_state", Vec::new);
+        self.inner.save_gl_state()
+    }
+
+    fn restore_gl_state(&mut self, state: &GlState) {
+        self.record("restore_gl_state", Vec::new);
+        self.inner.restore_gl_state(state);
+    }
+
+    fn invalidate_state_cache(&mut self) {
+        self.record("invalidate_state_cache", Vec::new);
+        self.inner.invalidate_state_cache();
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        self.record("draw", || {
+            vec![
+                ("base_element", base_element.to_string()),
+                ("num_elements", num_elements.to_string()),
+                ("num_instances", num_instances.to_string()),
+            ]
+        });
+        self.inner.draw(base_element, num_elements, num_instances);
+    }
+
+    fn draw_vertices(&self, base_vertex: i32, num_vertices: i32, num_instances: i32) {
+        self.record("draw_vertices", || {
+            vec![
+                ("base_vertex", base_vertex.to_string()),
+                ("num_vertices", num_vertices.to_string()),
+                ("num_instances", num_instances.to_string()),
+            ]
+        });
+        self.inner
+            .draw_vertices(base_vertex, num_vertices, num_instances);
+    }
+}
@@ -16,6 +16,23 @@ use std::collections::HashMap;
 /// Maximum number of commands to batch before forced flush
 const MAX_BATCH_SIZE: usize = 1024;
 
+/// When a `CommandBuffer` is due to be executed before the end of the frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Once this many commands are queued, `MAX_BATCH_SIZE` (1024) by default
+    Commands(usize),
+    /// Once the queued commands take this many bytes, uniform data included
+    Bytes(usize),
+    /// Only on `CommandBuffer::flush`, e.g. from `commit_frame`
+    Explicit,
+}
+
+impl Default for FlushPolicy {
+    fn default() -> FlushPolicy {
+        FlushPolicy::Commands(MAX_BATCH_SIZE)
+    }
+}
+
 /// Maximum number of instances to batch into a single instanced draw call
 const MAX_INSTANCES_PER_DRAW: i32 = 16384;
 
@@ -51,6 +68,21 @@ pub enum Command {
     ApplyUniforms { data: Vec<u8> },
 }
 
+impl Command {
+    /// Approximate memory used by the command, for `FlushPolicy::Bytes`
+    fn size(&self) -> usize {
+        let heap = match self {
+            Command::DrawElements { bindings, .. } => {
+                bindings.vertex_buffers.len() * std::mem::size_of::<BufferId>()
+                    + bindings.images.len() * std::mem::size_of::<TextureId>()
+            }
+            Command::ApplyUniforms { data } => data.len(),
+            _ => 0,
+        };
+        std::mem::size_of::<Command>() + heap
+    }
+}
+
 /// Lightweight bindings representation for batching
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct CommandBindings {
@@ -191,14 +223,17 @@ impl BatchGroup {
 
     /// Check if we can merge similar draws into instanced draws
     fn can_instance(&self) -> bool {
-        // For now, simple instancing: all draws must have same element count
+        // For now, simple instancing: all draws must be of the same elements,
+        // the instanced draw repeats the first one
         if self.draws.len() < 2 {
             return false;
         }
 
         let first_draw = &self.draws[0];
         self.draws.iter().all(|draw| {
-            draw.num_elements == first_draw.num_elements && draw.num_instances == 1
+            draw.base_element == first_draw.base_element
+                && draw.num_elements == first_draw.num_elements
+                && draw.num_instances == 1
             // Only batch single-instance draws
         })
     }
@@ -216,8 +251,9 @@ pub struct CommandBuffer {
     stats: BatchStats,
 
     /// Configuration
-    max_batch_size: usize,
-    auto_flush: bool,
+    flush_policy: FlushPolicy,
+    /// Approximate size of `commands`
+    queued_bytes: usize,
 
     /// State tracking for optimization
     current_pipeline: Option<Pipeline>,
@@ -232,14 +268,34 @@ impl CommandBuffer {
             commands: Vec::with_capacity(MAX_BATCH_SIZE),
             batch_groups: Vec::new(),
             stats: BatchStats::default(),
-            max_batch_size: MAX_BATCH_SIZE,
-            auto_flush: true,
+            flush_policy: FlushPolicy::default(),
+            queued_bytes: 0,
             current_pipeline: None,
             current_bindings: None,
             last_state_changes: HashMap::new(),
         }
     }
 
+    /// When `is_flush_due` starts returning true
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.flush_policy = policy;
+    }
+
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.flush_policy
+    }
+
+    /// Whether the queued commands reached the flush policy limit. The owner of
+    /// the buffer is expected to `flush` it into its backend then, as
+    /// `GlContext::draw_batched` does.
+    pub fn is_flush_due(&self) -> bool {
+        match self.flush_policy {
+            FlushPolicy::Commands(count) => self.commands.len() >= count,
+            FlushPolicy::Bytes(bytes) => self.queued_bytes >= bytes,
+            FlushPolicy::Explicit => false,
+        }
+    }
+
     /// Add a draw elements command to the batch
     pub fn draw_elements(
         &mut self,
@@ -326,13 +382,9 @@ impl CommandBuffer {
 
     /// Add a command to the buffer
     fn add_command(&mut self, command: Command) {
+        self.queued_bytes += command.size();
         self.commands.push(command);
         self.stats.total_commands += 1;
-
-        // Auto-flush if buffer is getting full
-        if self.auto_flush && self.commands.len() >= self.max_batch_size {
-            self.flush();
-        }
     }
 
     /// Process commands into optimized batches
@@ -347,10 +399,11 @@ impl CommandBuffer {
                 params,
             } = command
             {
-                // Try to find a compatible batch group
+                // Only the previous group is tried, merging with an earlier one
+                // would move the draw before the ones in between
                 let mut found_group = false;
 
-                for group in &mut self.batch_groups {
+                if let Some(group) = self.batch_groups.last_mut() {
                     if group.is_compatible(
                         *pipeline,
                         bindings,
@@ -364,7 +417,6 @@ impl CommandBuffer {
                         );
                         compatible_commands += 1;
                         found_group = true;
-                        break;
                     }
                 }
 
@@ -394,8 +446,8 @@ impl CommandBuffer {
         };
     }
 
-    /// Execute all batched commands
-    pub fn execute(&mut self, gl_context: &mut super::gl::GlContext) -> Result<(), String> {
+    /// Execute all batched commands on any backend, `NullContext` included
+    pub fn execute(&mut self, ctx: &mut dyn RenderingBackend) -> Result<(), String> {
         if self.commands.is_empty() {
             return Ok(());
         }
@@ -407,16 +459,16 @@ impl CommandBuffer {
         for command in &self.commands {
             match command {
                 Command::StateChange { state_type } => {
-                    self.execute_state_change(state_type, gl_context);
+                    self.execute_state_change(state_type, ctx);
                 }
                 Command::BeginPass { pass, action } => {
-                    self.execute_begin_pass(*pass, action, gl_context);
+                    self.execute_begin_pass(*pass, action, ctx);
                 }
                 Command::EndPass => {
-                    self.execute_end_pass(gl_context);
+                    self.execute_end_pass(ctx);
                 }
                 Command::ApplyUniforms { data } => {
-                    self.execute_apply_uniforms(data, gl_context);
+                    self.execute_apply_uniforms(data, ctx);
                 }
                 Command::DrawElements { .. } => {
                     // Draw commands are handled by batch groups
@@ -434,17 +486,17 @@ impl CommandBuffer {
             if original_draw_count > 1 {
                 if group.can_instance() {
                     // Execute as instanced draw
-                    self.execute_instanced_batch(group, gl_context);
+                    self.execute_instanced_batch(group, ctx);
                     draws_saved += original_draw_count - 1;
                     instances_created += 1;
                 } else {
                     // Execute as multiple draws with same state
-                    self.execute_multi_draw_batch(group, gl_context);
+                    self.execute_multi_draw_batch(group, ctx);
                     draws_saved += original_draw_count - 1;
                 }
             } else {
                 // Single draw, execute normally
-                self.execute_single_draw_batch(group, gl_context);
+                self.execute_single_draw_batch(group, ctx);
             }
         }
 
@@ -461,6 +513,7 @@ impl CommandBuffer {
         // Clear commands after execution
         self.commands.clear();
         self.batch_groups.clear();
+        self.queued_bytes = 0;
 
         // Reset state tracking after execution
         self.current_pipeline = None;
@@ -469,13 +522,16 @@ impl CommandBuffer {
         Ok(())
     }
 
-    /// Force flush all pending commands
-    pub fn flush(&mut self) {
-        // This would normally execute against the GL context
-        // For now, just clear the commands
+    /// Execute all pending commands on `ctx` now, whatever the flush policy
+    pub fn flush(&mut self, ctx: &mut dyn RenderingBackend) -> Result<(), String> {
+        self.execute(ctx)
+    }
+
+    /// Drop all pending commands without executing them
+    pub fn discard(&mut self) {
         self.commands.clear();
         self.batch_groups.clear();
-        self.stats.flush_count += 1;
+        self.queued_bytes = 0;
 
         // Reset state tracking
         self.current_pipeline = None;
@@ -494,20 +550,16 @@ impl CommandBuffer {
 
     // Private execution methods
 
-    fn execute_state_change(
-        &self,
-        state_type: &StateChangeType,
-        gl_context: &mut super::gl::GlContext,
-    ) {
+    fn execute_state_change(&self, state_type: &StateChangeType, ctx: &mut dyn RenderingBackend) {
         match state_type {
             StateChangeType::Viewport { x, y, w, h } => {
-                gl_context.apply_viewport(*x, *y, *w, *h);
+                ctx.apply_viewport(*x, *y, *w, *h);
             }
             StateChangeType::Scissor { x, y, w, h } => {
-                gl_context.apply_scissor_rect(*x, *y, *w, *h);
+                ctx.apply_scissor_rect(*x, *y, *w, *h);
             }
             StateChangeType::Pipeline { pipeline } => {
-                gl_context.apply_pipeline(pipeline);
+                ctx.apply_pipeline(pipeline);
             }
         }
     }
@@ -516,74 +568,74 @@ impl CommandBuffer {
         &self,
         pass: Option<RenderPass>,
         action: &PassAction,
-        gl_context: &mut super::gl::GlContext,
+        ctx: &mut dyn RenderingBackend,
     ) {
-        gl_context.begin_pass(pass, action.clone());
+        ctx.begin_pass(pass, action.clone());
     }
 
-    fn execute_end_pass(&self, gl_context: &mut super::gl::GlContext) {
-        gl_context.end_render_pass();
+    fn execute_end_pass(&self, ctx: &mut dyn RenderingBackend) {
+        ctx.end_render_pass();
     }
 
-    fn execute_apply_uniforms(&self, data: &[u8], gl_context: &mut super::gl::GlContext) {
+    fn execute_apply_uniforms(&self, data: &[u8], ctx: &mut dyn RenderingBackend) {
         // Apply uniforms from raw data
-        gl_context.apply_uniforms_from_bytes(data.as_ptr(), data.len());
+        ctx.apply_uniforms_from_bytes(data.as_ptr(), data.len());
     }
 
-    fn execute_instanced_batch(&self, group: &BatchGroup, gl_context: &mut super::gl::GlContext) {
+    fn execute_instanced_batch(&self, group: &BatchGroup, ctx: &mut dyn RenderingBackend) {
         // Apply pipeline and bindings once
-        gl_context.apply_pipeline(&group.pipeline);
+        ctx.apply_pipeline(&group.pipeline);
 
         let bindings = Bindings {
             vertex_buffers: group.bindings.vertex_buffers.clone(),
             index_buffer: group.bindings.index_buffer,
             images: group.bindings.images.clone(),
         };
-        gl_context.apply_bindings(&bindings);
+        ctx.apply_bindings(&bindings);
 
         // Calculate total instance count (capped at MAX_INSTANCES_PER_DRAW)
         let total_instances = group.draws.len().min(MAX_INSTANCES_PER_DRAW as usize) as i32;
         let first_draw = &group.draws[0];
 
         // Execute as single instanced draw
-        gl_context.draw(
+        ctx.draw(
             first_draw.base_element,
             first_draw.num_elements,
             total_instances,
         );
     }
 
-    fn execute_multi_draw_batch(&self, group: &BatchGroup, gl_context: &mut super::gl::GlContext) {
+    fn execute_multi_draw_batch(&self, group: &BatchGroup, ctx: &mut dyn RenderingBackend) {
         // Apply pipeline and bindings once
-        gl_context.apply_pipeline(&group.pipeline);
+        ctx.apply_pipeline(&group.pipeline);
 
         let bindings = Bindings {
             vertex_buffers: group.bindings.vertex_buffers.clone(),
             index_buffer: group.bindings.index_buffer,
             images: group.bindings.images.clone(),
         };
-        gl_context.apply_bindings(&bindings);
+        ctx.apply_bindings(&bindings);
 
         // Execute all draws with shared state
         for draw in &group.draws {
-            gl_context.draw(draw.base_element, draw.num_elements, draw.num_instances);
+            ctx.draw(draw.base_element, draw.num_elements, draw.num_instances);
         }
     }
 
-    fn execute_single_draw_batch(&self, group: &BatchGroup, gl_context: &mut super::gl::GlContext) {
+    fn execute_single_draw_batch(&self, group: &BatchGroup, ctx: &mut dyn RenderingBackend) {
         // Apply pipeline and bindings
-        gl_context.apply_pipeline(&group.pipeline);
+        ctx.apply_pipeline(&group.pipeline);
 
         let bindings = Bindings {
             vertex_buffers: group.bindings.vertex_buffers.clone(),
             index_buffer: group.bindings.index_buffer,
             images: group.bindings.images.clone(),
         };
-        gl_context.apply_bindings(&bindings);
+        ctx.apply_bindings(&bindings);
 
         // Execute single draw
         let draw = &group.draws[0];
-        gl_context.draw(draw.base_element, draw.num_elements, draw.num_instances);
+        ctx.draw(draw.base_element, draw.num_elements, draw.num_instances);
     }
 }
 
@@ -0,0 +1,51 @@
+//! Deletions waiting for the frames that may still use a resource
+//!
+//! A buffer or texture deleted right after the draw calls using it may still be
+//! read by the GPU, which is a few frames behind: `delete_buffer_deferred` and
+//! `delete_texture_deferred` queue the deletion here and the backends run it
+//! from `commit_frame` once enough frames were committed.
+
+use crate::graphics::*;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Deferred {
+    Buffer(BufferId),
+    Texture(TextureId),
+}
+
+impl Deferred {
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        match self {
+            Deferred::Buffer(buffer) => ctx.delete_buffer(buffer),
+            Deferred::Texture(texture) => ctx.delete_texture(texture),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct DeferredDeletes {
+    /// With the frame index they are due at
+    pending: Vec<(u64, Deferred)>,
+}
+
+impl DeferredDeletes {
+    /// Delete `resource` once `frames` more frames are committed after the
+    /// current one, `frame_index` being the current frame
+    pub fn push(&mut self, frame_index: u64, frames: u32, resource: Deferred) {
+        self.pending.push((frame_index + frames as u64, resource));
+    }
+
+    /// The resources due once `frame_index` was committed, removed from the queue
+    pub fn take_due(&mut self, frame_index: u64) -> Vec<Deferred> {
+        let mut due = vec![];
+        self.pending.retain(|(frame, resource)| {
+            if *frame <= frame_index {
+                due.push(*resource);
+                false
+            } else {
+                true
+            }
+        });
+        due
+    }
+}
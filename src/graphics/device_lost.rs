@@ -0,0 +1,77 @@
+//! GPU hangs and device resets
+//!
+//! Instead of freezing silently, the backends report them through
+//! `EventHandler::device_lost_event`, delivered right before `update` like errors.
+//!
+//! - GL: `glGetGraphicsResetStatus` (GL_KHR_robustness and its ARB/EXT
+//!   variants) is polled on every `commit_frame`. Drivers only report resets for
+//!   contexts created with a reset notification strategy, on the others the
+//!   watchdog below is all there is.
+//! - GL: a fence is inserted on every `commit_frame`, a frame the GPU has not
+//!   finished after `gpu_timeout` is reported as a hang. Needs GL 3.2 or GLES 3,
+//!   not available on WebGL.
+//! - Metal: command buffers ending with an error, and command buffers running
+//!   longer than `gpu_timeout`.
+//!
+//! A lost device is reported once. After a reset the context is unusable, the
+//! application should save what it can and recreate the window or quit.
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceLostReason {
+    /// The context was reset, caused by this application
+    GuiltyReset,
+    /// The context was reset, caused by another application
+    InnocentReset,
+    /// The context was reset for an unknown reason
+    UnknownReset,
+    /// A frame did not finish on the GPU within `gpu_timeout`
+    Timeout,
+    /// A Metal command buffer finished with an error
+    CommandBufferError,
+}
+
+#[derive(Debug, Clone)]
+pub struct DeviceLost {
+    pub reason: DeviceLostReason,
+    /// How long the offending frame had been running on the GPU, in milliseconds
+    pub gpu_time_ms: Option<f64>,
+    /// GL_VERSION, or the Metal device name
+    pub device: String,
+    /// Human readable details, e.g. the Metal error description
+    pub message: String,
+}
+
+const DEFAULT_GPU_TIMEOUT_MS: u64 = 2000;
+
+static GPU_TIMEOUT_MS: AtomicU64 = AtomicU64::new(DEFAULT_GPU_TIMEOUT_MS);
+static REPORTED: AtomicBool = AtomicBool::new(false);
+static PENDING: Mutex<Option<DeviceLost>> = Mutex::new(None);
+
+/// GPU frames running longer than this are reported as a hang.
+/// Two seconds by default, the usual OS GPU timeout.
+pub fn set_gpu_timeout(timeout: Duration) {
+    GPU_TIMEOUT_MS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+pub fn gpu_timeout() -> Duration {
+    Duration::from_millis(GPU_TIMEOUT_MS.load(Ordering::Relaxed))
+}
+
+/// Only the first call has an effect
+pub(crate) fn report(lost: DeviceLost) {
+    if REPORTED.swap(true, Ordering::Relaxed) {
+        return;
+    }
+    if let Ok(mut pending) = PENDING.lock() {
+        *pending = Some(lost);
+    }
+}
+
+/// The device loss reported since the last frame, if any
+pub(crate) fn take() -> Option<DeviceLost> {
+    PENDING.lock().ok()?.take()
+}
@@ -0,0 +1,32 @@
+//! Presented frames streamed to CPU memory, for gameplay recording or GIF export
+//!
+//! `RenderingBackend::set_frame_stream` registers a callback getting a copy of
+//! every frame drawn to the window. On `commit_frame` the default framebuffer is
+//! read into one of two pixel buffer objects, while the other one, filled a
+//! frame earlier, is mapped and handed over. The copy never waits on the frame
+//! being rendered, frames arrive with one frame of latency.
+//!
+//! Needs GL3 or GLES3. Not available on WebGL, GL2 and Metal, where
+//! `set_frame_stream` returns false.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! let supported = ctx.set_frame_stream(Some(Box::new(|frame| {
+//!     // encode frame.pixels, ideally on another thread
+//!     println!("frame {}: {}x{}", frame.index, frame.width, frame.height);
+//! })));
+//! ```
+
+/// One presented frame
+#[derive(Debug, Clone)]
+pub struct CapturedFrame {
+    /// Counted in `commit_frame` calls since the stream started
+    pub index: u64,
+    pub width: u32,
+    pub height: u32,
+    /// RGBA8, `width * height * 4` bytes, first row at the top
+    pub pixels: Vec<u8>,
+}
+
+pub type FrameCallback = Box<dyn FnMut(CapturedFrame)>;
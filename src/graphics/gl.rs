@@ -1,13 +1,26 @@
 use std::ffi::CString;
 
-use crate::{window, ResourceManager};
+use crate::error::{GraphicsError, MiniquadError, ResourceError};
+use crate::{window, Namespace, ResourceManager};
 
 mod cache;
+mod frame_stream;
+mod gpu_timer;
+mod reflection;
+mod state;
+#[cfg(feature = "validation")]
+mod validation;
+mod watchdog;
 
 use super::buffer_pool::BufferPool;
 use super::command_buffer::CommandBuffer;
+use super::streaming_pool::{StreamingBuffer, StreamingPool, FRAMES_IN_FLIGHT};
 use super::*;
 use cache::*;
+use frame_stream::FrameStream;
+use gpu_timer::GpuPassTimer;
+pub use state::GlState;
+use watchdog::GpuWatchdog;
 
 /// Raw OpenGL bindings
 /// Highly unsafe, some of the functions could be missing due to incompatible GL version
@@ -23,17 +36,38 @@ pub mod raw_gl {
     }
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 struct Buffer {
+    // Currently active GL buffer, the one that gets bound for drawing
     gl_buf: GLuint,
+    // Buffer created by new_buffer, gl_buf may point to one of the copies instead
+    original: GLuint,
     buffer_type: BufferType,
+    usage: BufferUsage,
     size: usize,
+    // Size of the actual GL storage, bigger than `size` for pooled buffers
+    capacity: usize,
     // Dimension of the indices for this buffer,
     // used only as a type argument for glDrawElements and can be
     // 1, 2 or 4
     index_type: Option<u32>,
     // Track if this buffer is from the pool for proper cleanup
     from_pool: bool,
+    orphaning: BufferOrphaning,
+    // With BufferOrphaning::MultiBuffer - all the GL buffers in rotation,
+    // the first one is the original buffer
+    copies: Vec<GLuint>,
+    // With BufferOrphaning::Persistent - mapped buffers in rotation
+    streaming: Vec<StreamingBuffer>,
+    current_copy: usize,
+}
+
+impl Buffer {
+    /// GPU memory taken by the buffer and all its copies
+    fn allocated_bytes(&self) -> usize {
+        let copies = self.copies.len().max(1) + self.streaming.len();
+        self.capacity * copies
+    }
 }
 
 #[derive(Debug)]
@@ -41,12 +75,19 @@ struct ShaderUniform {
     gl_loc: UniformLocation,
     uniform_type: UniformType,
     array_count: i32,
+    // Bytes last uploaded to this location, GL keeps uniform values per program
+    // so glUniform* is skipped while they don't change. Empty until the first upload.
+    last_value: Vec<u8>,
 }
 
 struct ShaderInternal {
     program: GLuint,
     images: Vec<ShaderImage>,
     uniforms: Vec<ShaderUniform>,
+    /// What the uniform layout gets wrong, reported by the pipelines using it
+    uniform_mismatches: Vec<UniformMismatch>,
+    /// Active attributes, compared with the ones of the pipelines using it
+    attributes: Vec<(String, String)>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -87,6 +128,10 @@ impl TextureFormat {
             TextureFormat::Alpha => GL_ALPHA,
             #[cfg(not(target_arch = "wasm32"))]
             TextureFormat::Alpha => GL_R8,
+            TextureFormat::BC3 => GL_COMPRESSED_RGBA_S3TC_DXT5_EXT,
+            TextureFormat::BC7 => GL_COMPRESSED_RGBA_BPTC_UNORM,
+            TextureFormat::ETC2RGBA8 => GL_COMPRESSED_RGBA8_ETC2_EAC,
+            TextureFormat::ASTC4x4 => GL_COMPRESSED_RGBA_ASTC_4x4_KHR,
         }
     }
 }
@@ -104,6 +149,11 @@ impl From<TextureFormat> for (GLenum, GLenum, GLenum) {
             TextureFormat::Alpha => (GL_ALPHA, GL_ALPHA, GL_UNSIGNED_BYTE),
             #[cfg(not(target_arch = "wasm32"))]
             TextureFormat::Alpha => (GL_R8, GL_RED, GL_UNSIGNED_BYTE), // texture updates will swizzle Red -> Alpha to match WASM
+            // uploaded with glCompressedTexImage2D, format and pixel_type are unused
+            TextureFormat::BC3
+            | TextureFormat::BC7
+            | TextureFormat::ETC2RGBA8
+            | TextureFormat::ASTC4x4 => (format.sized_internal_format(), GL_RGBA, GL_UNSIGNED_BYTE),
         }
     }
 }
@@ -113,6 +163,8 @@ impl From<TextureKind> for GLuint {
         match kind {
             TextureKind::Texture2D => GL_TEXTURE_2D,
             TextureKind::CubeMap => GL_TEXTURE_CUBE_MAP,
+            TextureKind::Texture2DMultisample => GL_TEXTURE_2D_MULTISAMPLE,
+            TextureKind::External => GL_TEXTURE_EXTERNAL_OES,
         }
     }
 }
@@ -175,26 +227,81 @@ impl From<CompareFunc> for GLenum {
 }
 
 impl Texture {
-    pub fn new(
+    pub fn try_new(
         ctx: &mut GlContext,
         access: TextureAccess,
         source: TextureSource,
         params: TextureParams,
-    ) -> Texture {
+    ) -> Result<Texture, MiniquadError> {
         if let TextureSource::Bytes(bytes_data) = source {
-            assert_eq!(
-                params.format.size(params.width, params.height) as usize,
-                bytes_data.len()
-            );
+            let expected = params.format.size(params.width, params.height) as usize;
+            if bytes_data.len() != expected {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "Texture data is {} bytes, {:?} {}x{} needs {}",
+                    bytes_data.len(),
+                    params.format,
+                    params.width,
+                    params.height,
+                    expected
+                )));
+            }
+            if params.kind != TextureKind::Texture2D {
+                return Err(MiniquadError::InvalidParameter(
+                    "incompatible TextureKind and TextureSource. Cubemaps require TextureSource::Array of 6 textures.".to_string(),
+                ));
+            }
         }
-        if access != TextureAccess::RenderTarget {
-            assert!(
-                params.sample_count <= 1,
-                "Multisampling is only supported for render textures"
-            );
+        if let TextureSource::Array(array) = source {
+            if params.kind == TextureKind::CubeMap && array.len() != 6 {
+                return Err(MiniquadError::InvalidParameter(
+                    "Cubemaps require TextureSource::Array of 6 textures.".to_string(),
+                ));
+            }
+        }
+        if access != TextureAccess::RenderTarget && params.sample_count > 1 {
+            return Err(MiniquadError::InvalidParameter(
+                "Multisampling is only supported for render textures".to_string(),
+            ));
+        }
+        if params.kind == TextureKind::External {
+            return Err(MiniquadError::InvalidParameter(
+                "External textures are created with new_external_texture".to_string(),
+            ));
+        }
+        if params.sample_count > 1 {
+            let max_sample_count = ctx.max_sample_count(params.format);
+            if params.sample_count > max_sample_count {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "{} samples requested, {:?} render textures support at most {}",
+                    params.sample_count, params.format, max_sample_count
+                )));
+            }
         }
         let (internal_format, format, pixel_type) = params.format.into();
 
+        // The fallbacks of `TextureAllocation::Immutable`, `texture_params` reports
+        // the actual allocation. Alpha is unsized on WebGL, glTexStorage2D needs
+        // a sized format.
+        let mut params = params;
+        if !ctx.info.features.immutable_textures
+            || params.format.is_compressed()
+            || params.sample_count > 1
+            || cfg!(target_arch = "wasm32") && params.format == TextureFormat::Alpha
+        {
+            params.allocate = TextureAllocation::Mutable;
+        }
+        let immutable = params.allocate == TextureAllocation::Immutable;
+
+        // Errors of earlier calls should not be reported for this texture
+        SafeGL::clear_errors();
+
+        if params.kind == TextureKind::Texture2DMultisample {
+            return Self::try_new_multisample(ctx, access, source, params);
+        }
+        if params.format.is_compressed() {
+            return Self::try_new_compressed(ctx, access, source, params);
+        }
+
         if access == TextureAccess::RenderTarget && params.sample_count > 1 {
             let mut renderbuffer: u32 = 0;
             unsafe {
@@ -209,18 +316,21 @@ impl Texture {
                     params.height as _,
                 );
             }
-            return Texture {
+            if let Err(err) = SafeGL::check_error_with_context("glRenderbufferStorageMultisample") {
+                unsafe { glDeleteRenderbuffers(1, &renderbuffer as *const _) };
+                return Err(err);
+            }
+            return Ok(Texture {
                 raw: TextureOrRenderbuffer::Renderbuffer(renderbuffer),
                 params,
-            };
+            });
         }
 
-        ctx.cache.store_texture_binding(0);
+        let texture = SafeGL::gen_texture()?;
 
-        let mut texture: GLuint = 0;
+        ctx.cache.store_texture_binding(0);
 
         unsafe {
-            glGenTextures(1, &mut texture as *mut _);
             ctx.cache.bind_texture(0, params.kind.into(), texture);
             glPixelStorei(GL_UNPACK_ALIGNMENT, 1); // miniquad always uses row alignment of 1
 
@@ -236,24 +346,91 @@ impl Texture {
                 }
             }
 
+            if immutable {
+                let levels = match source {
+                    TextureSource::Array(array) => array
+                        .first()
+                        .map_or(1, |mipmaps| mipmaps.len() as u32)
+                        .max(params.mip_level_count()),
+                    _ => params.mip_level_count(),
+                };
+                glTexStorage2D(
+                    params.kind.into(),
+                    levels as _,
+                    params.format.sized_internal_format(),
+                    params.width as _,
+                    params.height as _,
+                );
+            }
+
             match source {
-                TextureSource::Empty => {
-                    // not quite sure if glTexImage2D(null) is really a requirement
-                    // but it was like this for quite a while and apparantly it works?
-                    glTexImage2D(
+                // Allocated by glTexStorage2D
+                TextureSource::Empty if immutable => {}
+                TextureSource::Bytes(source) if immutable => {
+                    glTexSubImage2D(
                         GL_TEXTURE_2D,
                         0,
-                        internal_format as i32,
-                        params.width as i32,
-                        params.height as i32,
                         0,
+                        0,
+                        params.width as _,
+                        params.height as _,
                         format,
                         pixel_type,
-                        std::ptr::null() as _,
+                        source.as_ptr() as *const _,
                     );
                 }
+                TextureSource::Array(array) if immutable => {
+                    for (cubemap_face, mipmaps) in array.iter().enumerate() {
+                        let target = match params.kind {
+                            TextureKind::CubeMap => {
+                                GL_TEXTURE_CUBE_MAP_POSITIVE_X + cubemap_face as u32
+                            }
+                            _ => GL_TEXTURE_2D,
+                        };
+                        for (level, bytes) in mipmaps.iter().enumerate() {
+                            glTexSubImage2D(
+                                target,
+                                level as _,
+                                0,
+                                0,
+                                (params.width >> level).max(1) as _,
+                                (params.height >> level).max(1) as _,
+                                format,
+                                pixel_type,
+                                bytes.as_ptr() as *const _,
+                            );
+                        }
+                    }
+                }
+                TextureSource::Empty => {
+                    // not quite sure if glTexImage2D(null) is really a requirement
+                    // but it was like this for quite a while and apparantly it works?
+                    // Every face and mip level, they may be render targets.
+                    let faces = match params.kind {
+                        TextureKind::CubeMap => 6,
+                        _ => 1,
+                    };
+                    for face in 0..faces {
+                        let target = match params.kind {
+                            TextureKind::CubeMap => GL_TEXTURE_CUBE_MAP_POSITIVE_X + face,
+                            _ => GL_TEXTURE_2D,
+                        };
+                        for level in 0..params.mip_level_count() {
+                            glTexImage2D(
+                                target,
+                                level as _,
+                                internal_format as i32,
+                                (params.width >> level).max(1) as i32,
+                                (params.height >> level).max(1) as i32,
+                                0,
+                                format,
+                                pixel_type,
+                                std::ptr::null() as _,
+                            );
+                        }
+                    }
+                }
                 TextureSource::Bytes(source) => {
-                    assert!(params.kind == TextureKind::Texture2D, "incompatible TextureKind and TextureSource. Cubemaps require TextureSource::Array of 6 textures.");
                     glTexImage2D(
                         GL_TEXTURE_2D,
                         0,
@@ -267,12 +444,6 @@ impl Texture {
                     );
                 }
                 TextureSource::Array(array) => {
-                    if params.kind == TextureKind::CubeMap {
-                        assert!(
-                            array.len() == 6,
-                            "Cubemaps require TextureSource::Array of 6 textures."
-                        );
-                    }
                     for (cubemap_face, mipmaps) in array.iter().enumerate() {
                         if mipmaps.len() != 1 {
                             glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_BASE_LEVEL, 0);
@@ -280,10 +451,10 @@ impl Texture {
                         }
                         for (mipmap_level, bytes) in mipmaps.iter().enumerate() {
                             let target = match params.kind {
-                                TextureKind::Texture2D => GL_TEXTURE_2D,
                                 TextureKind::CubeMap => {
                                     GL_TEXTURE_CUBE_MAP_POSITIVE_X + cubemap_face as u32
                                 }
+                                _ => GL_TEXTURE_2D,
                             };
                             glTexImage2D(
                                 target,
@@ -301,32 +472,230 @@ impl Texture {
                 }
             }
 
-            let wrap = match params.wrap {
-                TextureWrap::Repeat => GL_REPEAT,
-                TextureWrap::Mirror => GL_MIRRORED_REPEAT,
-                TextureWrap::Clamp => GL_CLAMP_TO_EDGE,
-            };
+            Self::apply_sampler_params(&params);
+        }
+        let res = SafeGL::check_error_with_context("texture upload");
+        ctx.cache.restore_texture_binding(0);
+        if let Err(err) = res {
+            unsafe { glDeleteTextures(1, &texture as *const _) };
+            return Err(err);
+        }
 
-            let min_filter = Self::gl_filter(params.min_filter, params.mipmap_filter);
-            let mag_filter = match params.mag_filter {
-                FilterMode::Nearest => GL_NEAREST,
-                FilterMode::Linear => GL_LINEAR,
-            };
+        Ok(Texture {
+            raw: TextureOrRenderbuffer::Texture(texture),
+            params,
+        })
+    }
+
+    /// Wrap and filters of the texture bound to slot 0
+    unsafe fn apply_sampler_params(params: &TextureParams) {
+        let wrap = match params.wrap {
+            TextureWrap::Repeat => GL_REPEAT,
+            TextureWrap::Mirror => GL_MIRRORED_REPEAT,
+            TextureWrap::Clamp => GL_CLAMP_TO_EDGE,
+        };
+
+        let min_filter = Self::gl_filter(params.min_filter, params.mipmap_filter);
+        let mag_filter = match params.mag_filter {
+            FilterMode::Nearest => GL_NEAREST,
+            FilterMode::Linear => GL_LINEAR,
+        };
+
+        glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_S, wrap as i32);
+        glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_T, wrap as i32);
+        glTexParameteri(params.kind.into(), GL_TEXTURE_MIN_FILTER, min_filter as i32);
+        glTexParameteri(params.kind.into(), GL_TEXTURE_MAG_FILTER, mag_filter as i32);
+    }
+
+    /// Compressed textures take their data at creation, one level after another
+    /// for `TextureSource::Array`, each level half the size of the previous one.
+    fn try_new_compressed(
+        ctx: &mut GlContext,
+        access: TextureAccess,
+        source: TextureSource,
+        params: TextureParams,
+    ) -> Result<Texture, MiniquadError> {
+        if !ctx.info.features.supports_format(params.format) {
+            return Err(GraphicsError::Unsupported(format!(
+                "{:?} textures are not supported by this GL",
+                params.format
+            ))
+            .into());
+        }
+        if access == TextureAccess::RenderTarget {
+            return Err(MiniquadError::InvalidParameter(format!(
+                "{:?} textures can't be render targets",
+                params.format
+            )));
+        }
+        let level_size = |level: usize| {
+            let width = (params.width >> level).max(1);
+            let height = (params.height >> level).max(1);
+            (width, height, params.format.size(width, height) as usize)
+        };
+        match source {
+            TextureSource::Empty => {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "{:?} textures need their data at creation",
+                    params.format
+                )));
+            }
+            TextureSource::Bytes(_) => {}
+            TextureSource::Array(array) => {
+                for mipmaps in array {
+                    for (level, bytes) in mipmaps.iter().enumerate() {
+                        let (width, height, expected) = level_size(level);
+                        if bytes.len() != expected {
+                            return Err(MiniquadError::InvalidParameter(format!(
+                                "Mip level {} is {} bytes, {:?} {}x{} needs {}",
+                                level,
+                                bytes.len(),
+                                params.format,
+                                width,
+                                height,
+                                expected
+                            )));
+                        }
+                    }
+                }
+            }
+        }
 
-            glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_S, wrap as i32);
-            glTexParameteri(params.kind.into(), GL_TEXTURE_WRAP_T, wrap as i32);
-            glTexParameteri(params.kind.into(), GL_TEXTURE_MIN_FILTER, min_filter as i32);
-            glTexParameteri(params.kind.into(), GL_TEXTURE_MAG_FILTER, mag_filter as i32);
+        let texture = SafeGL::gen_texture()?;
+        ctx.cache.store_texture_binding(0);
+        ctx.cache.bind_texture(0, params.kind.into(), texture);
+        let internal_format = params.format.sized_internal_format();
+        let upload = |target: GLenum, level: usize, bytes: &[u8]| {
+            let (width, height, _) = level_size(level);
+            unsafe {
+                glCompressedTexImage2D(
+                    target,
+                    level as _,
+                    internal_format,
+                    width as _,
+                    height as _,
+                    0,
+                    bytes.len() as _,
+                    bytes.as_ptr() as *const _,
+                );
+            }
+        };
+        unsafe {
+            match source {
+                TextureSource::Empty => unreachable!(),
+                TextureSource::Bytes(bytes) => upload(GL_TEXTURE_2D, 0, bytes),
+                TextureSource::Array(array) => {
+                    for (cubemap_face, mipmaps) in array.iter().enumerate() {
+                        let target = match params.kind {
+                            TextureKind::CubeMap => {
+                                GL_TEXTURE_CUBE_MAP_POSITIVE_X + cubemap_face as u32
+                            }
+                            _ => GL_TEXTURE_2D,
+                        };
+                        for (level, bytes) in mipmaps.iter().enumerate() {
+                            upload(target, level, bytes);
+                        }
+                    }
+                    let levels = array.first().map_or(1, |mipmaps| mipmaps.len());
+                    if levels > 1 {
+                        glTexParameteri(params.kind.into(), GL_TEXTURE_BASE_LEVEL, 0);
+                        glTexParameteri(
+                            params.kind.into(),
+                            GL_TEXTURE_MAX_LEVEL,
+                            levels as i32 - 1,
+                        );
+                    }
+                }
+            }
+            Self::apply_sampler_params(&params);
         }
+        let res = SafeGL::check_error_with_context("compressed texture upload");
         ctx.cache.restore_texture_binding(0);
+        if let Err(err) = res {
+            unsafe { glDeleteTextures(1, &texture as *const _) };
+            return Err(err);
+        }
 
-        Texture {
+        Ok(Texture {
             raw: TextureOrRenderbuffer::Texture(texture),
             params,
+        })
+    }
+
+    fn try_new_multisample(
+        ctx: &mut GlContext,
+        access: TextureAccess,
+        source: TextureSource,
+        params: TextureParams,
+    ) -> Result<Texture, MiniquadError> {
+        if !ctx.info.features.multisample_textures {
+            return Err(GraphicsError::Unsupported(
+                "multisample textures need GL3.2 or GLES3.1".to_string(),
+            )
+            .into());
         }
+        if access != TextureAccess::RenderTarget || !matches!(source, TextureSource::Empty) {
+            return Err(MiniquadError::InvalidParameter(
+                "multisample textures can only be empty render textures".to_string(),
+            ));
+        }
+
+        let texture = SafeGL::gen_texture()?;
+        ctx.cache.store_texture_binding(0);
+        ctx.cache
+            .bind_texture(0, GL_TEXTURE_2D_MULTISAMPLE, texture);
+        // never reached on wasm, WebGl has no multisample textures
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            let internal_format = params.format.sized_internal_format();
+            let samples = params.sample_count.max(1);
+            // glTexStorage2DMultisample is the only one on GLES3.1
+            if is_gl_func_loaded("glTexStorage2DMultisample") {
+                glTexStorage2DMultisample(
+                    GL_TEXTURE_2D_MULTISAMPLE,
+                    samples,
+                    internal_format,
+                    params.width as _,
+                    params.height as _,
+                    GL_TRUE as _,
+                );
+            } else {
+                glTexImage2DMultisample(
+                    GL_TEXTURE_2D_MULTISAMPLE,
+                    samples,
+                    internal_format,
+                    params.width as _,
+                    params.height as _,
+                    GL_TRUE as _,
+                );
+            }
+        }
+        let res = SafeGL::check_error_with_context("multisample texture allocation");
+        ctx.cache.restore_texture_binding(0);
+        if let Err(err) = res {
+            unsafe { glDeleteTextures(1, &texture as *const _) };
+            return Err(err);
+        }
+
+        Ok(Texture {
+            raw: TextureOrRenderbuffer::Texture(texture),
+            params,
+        })
     }
 
     pub fn resize(&mut self, ctx: &mut GlContext, width: u32, height: u32, source: Option<&[u8]>) {
+        assert!(
+            self.params.kind != TextureKind::Texture2DMultisample,
+            "Resize not yet implemented for multisample textures"
+        );
+        assert!(
+            self.params.allocate != TextureAllocation::Immutable,
+            "Immutable textures can't be resized"
+        );
+        assert!(
+            !self.params.format.is_compressed(),
+            "Compressed textures can't be resized"
+        );
         let raw = self
             .raw
             .texture()
@@ -449,6 +818,75 @@ impl Texture {
         }
     }
 
+    /// Pixels of the `(x, y, width, height)` rectangle as normalized RGBA. Read
+    /// as RGBA bytes, or floats for float textures: the only combinations GLES
+    /// guarantees.
+    pub fn read_region(&self, rect: (i32, i32, i32, i32)) -> Result<Vec<[f32; 4]>, MiniquadError> {
+        let raw = self.raw.texture().ok_or_else(|| {
+            GraphicsError::Unsupported("Multisampled textures can't be read back".to_string())
+        })?;
+        let (x, y, width, height) = rect;
+        let count = width as usize * height as usize;
+
+        let mut fbo = 0;
+        unsafe {
+            let mut binded_fbo: i32 = 0;
+            glGetIntegerv(gl::GL_FRAMEBUFFER_BINDING, &mut binded_fbo);
+            glGenFramebuffers(1, &mut fbo);
+            glBindFramebuffer(gl::GL_FRAMEBUFFER, fbo);
+            glFramebufferTexture2D(
+                gl::GL_FRAMEBUFFER,
+                gl::GL_COLOR_ATTACHMENT0,
+                gl::GL_TEXTURE_2D,
+                raw,
+                0,
+            );
+
+            // e.g. WebGL alpha textures, or float ones without EXT_color_buffer_float
+            let status = glCheckFramebufferStatus(GL_FRAMEBUFFER);
+            let pixels = if status != GL_FRAMEBUFFER_COMPLETE {
+                Err(GraphicsError::FramebufferIncomplete(status).into())
+            } else if self.params.format == TextureFormat::RGBA16F {
+                let mut pixels = vec![[0.0f32; 4]; count];
+                glReadPixels(
+                    x,
+                    y,
+                    width,
+                    height,
+                    GL_RGBA,
+                    GL_FLOAT,
+                    pixels.as_mut_ptr() as _,
+                );
+                Ok(pixels)
+            } else {
+                let mut bytes = vec![0u8; count * 4];
+                glReadPixels(
+                    x,
+                    y,
+                    width,
+                    height,
+                    GL_RGBA,
+                    GL_UNSIGNED_BYTE,
+                    bytes.as_mut_ptr() as _,
+                );
+                #[allow(unused_mut)]
+                let mut pixels = super::readback::decode(TextureFormat::RGBA8, &bytes);
+                // Alpha textures are red ones outside of WebGL
+                #[cfg(not(target_arch = "wasm32"))]
+                if self.params.format == TextureFormat::Alpha {
+                    for pixel in &mut pixels {
+                        *pixel = [0.0, 0.0, 0.0, pixel[0]];
+                    }
+                }
+                Ok(pixels)
+            };
+
+            glBindFramebuffer(gl::GL_FRAMEBUFFER, binded_fbo as _);
+            glDeleteFramebuffers(1, &fbo);
+            pixels
+        }
+    }
+
     #[inline]
     fn size(&self, width: u32, height: u32) -> usize {
         self.params.format.size(width, height) as usize
@@ -474,12 +912,21 @@ pub(crate) struct PipelineInternal {
     layout: Vec<Option<VertexAttributeInternal>>,
     shader: ShaderId,
     params: PipelineParams,
+    desc: PipelineDesc,
+    // new_pipeline calls that returned this pipeline, minus delete_pipeline calls
+    refs: usize,
 }
 
 type UniformLocation = Option<GLint>;
 
 pub struct ShaderImage {
+    name: String,
     gl_loc: UniformLocation,
+    /// Texture target and GLSL type of the sampler, None when it is not active
+    #[cfg(debug_assertions)]
+    sampler: Option<(GLenum, &'static str)>,
+    // Texture unit the sampler uniform was last set to
+    unit: Option<usize>,
 }
 
 fn get_uniform_location(program: GLuint, name: &str) -> Option<i32> {
@@ -500,10 +947,34 @@ pub(crate) struct RenderPassInternal {
     color_textures: Vec<TextureId>,
     resolves: Option<Vec<(u32, TextureId)>>,
     depth_texture: Option<TextureId>,
+    /// Mip level rendered to, the pass is that level's size
+    level: u32,
+    /// The textures are deleted with the pass, false for `try_new_render_pass_targets`
+    owns_textures: bool,
 }
 
-struct Textures(Vec<Texture>);
+struct Textures {
+    namespace: Namespace,
+    textures: Vec<Texture>,
+    /// Indices of the textures of other contexts, see `import_shared_texture`,
+    /// deleted by their context
+    shared: std::collections::HashSet<usize>,
+}
 impl Textures {
+    fn new(namespace: Namespace) -> Textures {
+        Textures {
+            namespace,
+            textures: vec![],
+            shared: Default::default(),
+        }
+    }
+
+    fn push(&mut self, texture: Texture) -> TextureId {
+        self.textures.push(texture);
+        let id = self.namespace.id(self.textures.len() - 1);
+        TextureId(TextureIdInner::Managed(id))
+    }
+
     fn get(&self, texture: TextureId) -> Texture {
         match texture.0 {
             TextureIdInner::Raw(RawId::OpenGl(texture)) => Texture {
@@ -512,8 +983,31 @@ impl Textures {
             },
             #[cfg(target_vendor = "apple")]
             TextureIdInner::Raw(RawId::Metal(..)) => panic!("Metal texture in OpenGL context!"),
-            TextureIdInner::Managed(texture) => self.0[texture],
+            TextureIdInner::Managed(texture) => self.textures[self.namespace.index(texture)],
+        }
+    }
+
+    /// Keep `params` in sync with the GL texture, raw textures have no params
+    fn update_params(&mut self, texture: TextureId, f: impl FnOnce(&mut TextureParams)) {
+        if let TextureIdInner::Managed(texture) = texture.0 {
+            f(&mut self.textures[self.namespace.index(texture)].params);
+        }
+    }
+
+    fn is_shared(&self, texture: TextureId) -> bool {
+        match texture.0 {
+            TextureIdInner::Managed(id) => self.shared.contains(&self.namespace.index(id)),
+            TextureIdInner::Raw(_) => false,
+        }
+    }
+
+    fn try_get(&self, texture: TextureId) -> Result<Texture, MiniquadError> {
+        if let TextureIdInner::Managed(id) = texture.0 {
+            if self.namespace.try_index(id)? >= self.textures.len() {
+                return Err(ResourceError::NotFound(id).into());
+            }
         }
+        Ok(self.get(texture))
     }
 }
 pub struct GlContext {
@@ -523,10 +1017,29 @@ pub struct GlContext {
     buffers: ResourceManager<Buffer>,
     textures: Textures,
     default_framebuffer: GLuint,
+    vao: GLuint,
     pub(crate) cache: GlCache,
     pub(crate) info: ContextInfo,
     buffer_pool: BufferPool,
+    streaming_pool: StreamingPool,
     command_buffer: CommandBuffer,
+    gpu_timer: GpuPassTimer,
+    watchdog: GpuWatchdog,
+    frame_stream: FrameStream,
+    software_cursor: Option<super::software_cursor::CursorRenderer>,
+    /// The default pass while an internal resolution is set
+    internal_resolution: Option<super::letterbox::Letterbox>,
+    clear_colors: ClearColors,
+    /// glInvalidateFramebuffer is available, GL4.3, GLES3 or WebGL2
+    invalidate_framebuffer: bool,
+    /// Attachments of the current pass not stored, invalidated by `end_render_pass`
+    pass_discards: Vec<GLenum>,
+    /// Frames committed, see `RenderingBackend::frame_index`
+    frame_index: u64,
+    deferred_deletes: super::deferred_delete::DeferredDeletes,
+    pass_state_reset: PassStateReset,
+    #[cfg(feature = "validation")]
+    bound: Option<validation::BoundResources>,
 }
 
 impl Default for GlContext {
@@ -537,6 +1050,9 @@ impl Default for GlContext {
 
 impl GlContext {
     pub fn new() -> GlContext {
+        #[cfg(feature = "tracy")]
+        super::profiling::tracy::start();
+
         unsafe {
             let mut default_framebuffer: GLuint = 0;
             glGetIntegerv(
@@ -547,22 +1063,48 @@ impl GlContext {
 
             glGenVertexArrays(1, &mut vao as *mut _);
             glBindVertexArray(vao);
-            let info = gl_info();
+            let mut info = gl_info();
+            let cache = GlCache {
+                limits: info.limits,
+                ..Default::default()
+            };
+            let streaming_pool = StreamingPool::new(&info);
+            info.features.persistent_mapping = streaming_pool.is_supported();
+            let gpu_timer = GpuPassTimer::new(&info);
+            let watchdog = GpuWatchdog::new(&info);
+            let frame_stream = FrameStream::new(&info);
+            let invalidate_framebuffer = invalidate_framebuffer_supported(&info);
+            let namespace = Namespace::next();
             let mut buffer_pool = BufferPool::new();
             // Warm up the pool with common buffer sizes for better performance
             let _ = buffer_pool.warm_up();
 
             GlContext {
                 default_framebuffer,
-                shaders: ResourceManager::default(),
-                pipelines: ResourceManager::default(),
-                passes: ResourceManager::default(),
-                buffers: ResourceManager::default(),
-                textures: Textures(vec![]),
+                vao,
+                shaders: ResourceManager::new(namespace),
+                pipelines: ResourceManager::new(namespace),
+                passes: ResourceManager::new(namespace),
+                buffers: ResourceManager::new(namespace),
+                textures: Textures::new(namespace),
                 info,
-                cache: GlCache::default(),
+                cache,
                 buffer_pool,
+                streaming_pool,
                 command_buffer: CommandBuffer::new(),
+                gpu_timer,
+                watchdog,
+                frame_stream,
+                software_cursor: None,
+                internal_resolution: None,
+                clear_colors: ClearColors::default(),
+                invalidate_framebuffer,
+                pass_discards: vec![],
+                frame_index: 0,
+                deferred_deletes: Default::default(),
+                pass_state_reset: PassStateReset::default(),
+                #[cfg(feature = "validation")]
+                bound: None,
             }
         }
     }
@@ -577,6 +1119,13 @@ impl GlContext {
         self.buffer_pool.get_stats().print_report();
     }
 
+    /// Release unused pooled buffers until the pool uses at most `target_bytes`.
+    /// Returns the amount of bytes freed.
+    /// Called automatically with 0 on OS low memory notifications, see `EventHandler::low_memory_event`.
+    pub fn trim_buffer_pool(&mut self, target_bytes: usize) -> usize {
+        self.buffer_pool.trim(target_bytes)
+    }
+
     /// Get current command buffer statistics
     pub fn command_buffer_stats(&self) -> super::command_buffer::BatchStats {
         self.command_buffer.get_stats()
@@ -591,39 +1140,260 @@ impl GlContext {
     pub fn flush_command_buffer(&mut self) {
         // Need to work around borrow checker by extracting command buffer
         let mut cmd_buffer = std::mem::take(&mut self.command_buffer);
-        let _ = cmd_buffer.execute(self);
+        let _ = cmd_buffer.flush(self);
         self.command_buffer = cmd_buffer;
     }
 
+    /// When `draw_batched` executes the queued commands before `commit_frame`
+    pub fn set_command_buffer_flush_policy(&mut self, policy: super::command_buffer::FlushPolicy) {
+        self.command_buffer.set_flush_policy(policy);
+    }
+
     pub fn features(&self) -> &Features {
         &self.info.features
     }
 
-    /// Internal draw method that uses command buffer for batching
-    pub fn draw_batched(&mut self, base_element: i32, num_elements: i32, num_instances: i32) {
-        assert!(
-            self.cache.cur_pipeline.is_some(),
-            "Drawing without any binded pipeline"
-        );
+    /// Framebuffer and size of the default pass: the window's, the internal
+    /// resolution one, or the XR session's while drawing an XR frame
+    fn default_pass_target(&self) -> (GLuint, i32, i32) {
+        #[cfg(feature = "webxr")]
+        if let Some(target) = crate::xr::framebuffer() {
+            return target;
+        }
+        if let Some(letterbox) = &self.internal_resolution {
+            let (width, height) = letterbox.size();
+            let framebuffer = self.passes[letterbox.render_pass().0].gl_fb;
+            return (framebuffer, width as i32, height as i32);
+        }
+        let (screen_width, screen_height) = window::screen_size();
+        (
+            self.default_framebuffer,
+            screen_width as i32,
+            screen_height as i32,
+        )
+    }
+
+    /// `PassAction::Attachments` at the beginning of a pass on `framebuffer`.
+    /// The attachments not stored are invalidated by `end_render_pass`.
+    fn load_attachments(&mut self, framebuffer: GLuint, color_count: usize, action: &PassAction) {
+        fn cleared<T>(action: AttachmentAction<T>) -> Option<T> {
+            match action.load {
+                LoadAction::Clear(value) => Some(value),
+                LoadAction::Load | LoadAction::DontCare => None,
+            }
+        }
+        let colors: Vec<_> = (0..color_count).map(|index| action.color(index)).collect();
+        let (depth, stencil) = (action.depth(), action.stencil());
+
+        // glClear gives all the color attachments the same color
+        let color_clears: Vec<_> = colors.iter().map(|color| cleared(*color)).collect();
+        if color_clears.windows(2).all(|pair| pair[0] == pair[1]) {
+            let color = color_clears.first().copied().flatten();
+            self.clear(color, cleared(depth), cleared(stencil));
+        } else {
+            self.clear(None, cleared(depth), cleared(stencil));
+            let color_write = self.cache.color_write;
+            self.cache.set_color_write((true, true, true, true));
+            for (index, color) in color_clears.iter().enumerate() {
+                if let Some((r, g, b, a)) = *color {
+                    unsafe { glClearBufferfv(GL_COLOR, index as _, [r, g, b, a].as_ptr()) };
+                }
+            }
+            self.cache.set_color_write(color_write);
+        }
 
-        if !self.info.features.instancing && num_instances != 1 {
-            eprintln!("Instanced rendering is not supported by the GPU");
-            eprintln!("Ignoring this draw call");
+        if !self.invalidate_framebuffer {
             return;
         }
+        // The window framebuffer has its own attachment names
+        let window = framebuffer == 0;
+        let color_attachment = |index: usize| {
+            if window {
+                GL_COLOR
+            } else {
+                GL_COLOR_ATTACHMENT0 + index as GLenum
+            }
+        };
+        let (depth_attachment, stencil_attachment) = if window {
+            (GL_DEPTH, GL_STENCIL)
+        } else {
+            (GL_DEPTH_ATTACHMENT, GL_STENCIL_ATTACHMENT)
+        };
+        let attachments = colors
+            .iter()
+            .enumerate()
+            .map(|(index, color)| {
+                let undefined = matches!(color.load, LoadAction::DontCare);
+                (color_attachment(index), undefined, color.store)
+            })
+            .chain([
+                (
+                    depth_attachment,
+                    matches!(depth.load, LoadAction::DontCare),
+                    depth.store,
+                ),
+                (
+                    stencil_attachment,
+                    matches!(stencil.load, LoadAction::DontCare),
+                    stencil.store,
+                ),
+            ]);
+        let mut dont_care = vec![];
+        for (attachment, undefined, store) in attachments {
+            if undefined {
+                dont_care.push(attachment);
+            }
+            if !store {
+                self.pass_discards.push(attachment);
+            }
+        }
+        if !dont_care.is_empty() {
+            unsafe {
+                glInvalidateFramebuffer(GL_FRAMEBUFFER, dont_care.len() as _, dont_care.as_ptr())
+            };
+        }
+    }
 
-        // Get current pipeline and create command bindings
-        let pipeline = self.cache.cur_pipeline.unwrap();
-        let pip = &self.pipelines[pipeline.0];
-        let primitive_type = pip.params.primitive_type;
-        let index_type = self.cache.index_type.expect("Unset index buffer type");
+    /// The images of the bindings against the samplers of the shader, checked
+    /// in debug builds: a missing image, or one of another type, would sample
+    /// whatever texture the unit had bound last
+    #[cfg(debug_assertions)]
+    fn check_images(&self, textures: &[TextureId]) -> Result<(), MiniquadError> {
+        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        let shader = &self.shaders[pip.shader.0];
+        for (n, image) in shader.images.iter().enumerate() {
+            let Some(&texture) = textures.get(n) else {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "no image bound for the sampler `{}`: the shader samples {} images, {} are bound",
+                    image.name,
+                    shader.images.len(),
+                    textures.len()
+                )));
+            };
+            // Raw textures have no params to compare with
+            let (Some((target, type_name)), TextureIdInner::Managed(_)) =
+                (image.sampler, texture.0)
+            else {
+                continue;
+            };
+            let kind = self.textures.try_get(texture)?.params.kind;
+            if GLuint::from(kind) != target {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "the sampler `{}` is a {}, but image {} is a {:?} texture",
+                    image.name, type_name, n, kind
+                )));
+            }
+        }
+        Ok(())
+    }
 
-        // Create bindings from current cache state
-        let current_bindings = super::command_buffer::CommandBindings {
-            vertex_buffers: vec![],    // Would need to track current vertex buffers
-            index_buffer: BufferId(0), // Would need to track current index buffer
-            images: vec![],            // Would need to track current textures
-        };
+    fn bind_images(&mut self, textures: &[TextureId]) {
+        #[cfg(debug_assertions)]
+        if let Err(err) = self.check_images(textures) {
+            crate::error::report_error(err);
+            return;
+        }
+
+        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        let shader = &mut self.shaders[pip.shader.0];
+
+        // Textures stay on the units they are already bound to, the sampler
+        // uniforms follow them instead
+        let mut taken = 0;
+        for (n, shader_image) in shader.images.iter_mut().enumerate() {
+            let bindings_image = textures.get(n).unwrap_or_else(|| {
+                panic!("No image bound for the sampler `{}`", shader_image.name)
+            });
+            if let Some(gl_loc) = shader_image.gl_loc {
+                let texture = self.textures.get(*bindings_image);
+                let raw = match texture.raw {
+                    TextureOrRenderbuffer::Texture(id) => id,
+                    TextureOrRenderbuffer::Renderbuffer(id) => id,
+                };
+                let unit = self
+                    .cache
+                    .texture_unit(texture.params.kind.into(), raw, taken);
+                taken |= 1 << unit;
+                if shader_image.unit != Some(unit) {
+                    unsafe { glUniform1i(gl_loc, unit as i32) };
+                    shader_image.unit = Some(unit);
+                }
+            }
+        }
+    }
+
+    /// Byte size of the indices the next draw reads, from the pipeline or the index buffer
+    fn index_size(&self) -> Option<u32> {
+        let pipeline = self.pipelines.get(self.cache.cur_pipeline?.0).ok()?;
+        pipeline
+            .params
+            .index_type
+            .map(|index_type| index_type.size() as u32)
+            .or(self.cache.index_type)
+    }
+
+    /// Enable primitive restart for the current pipeline and index type
+    fn update_primitive_restart(&mut self) {
+        let Some(Ok(pipeline)) = self.cache.cur_pipeline.map(|p| self.pipelines.get(p.0)) else {
+            return;
+        };
+        let restart = if pipeline.params.primitive_restart {
+            self.index_size()
+        } else {
+            None
+        };
+        if restart == self.cache.primitive_restart || !self.info.features.primitive_restart {
+            return;
+        }
+        // WebGl2 always restarts at the maximum index, nothing to set
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            if self.info.gl_version_string.contains("OpenGL ES") {
+                match restart {
+                    Some(_) => glEnable(GL_PRIMITIVE_RESTART_FIXED_INDEX),
+                    None => glDisable(GL_PRIMITIVE_RESTART_FIXED_INDEX),
+                }
+            } else {
+                // GL_PRIMITIVE_RESTART_FIXED_INDEX is GL4.3+, the index is set by hand instead
+                match restart {
+                    Some(index_size) => {
+                        glEnable(GL_PRIMITIVE_RESTART);
+                        glPrimitiveRestartIndex(u32::MAX >> (32 - index_size * 8));
+                    }
+                    None => glDisable(GL_PRIMITIVE_RESTART),
+                }
+            }
+        }
+        self.cache.primitive_restart = restart;
+    }
+
+    /// Internal draw method that uses command buffer for batching
+    pub fn draw_batched(&mut self, base_element: i32, num_elements: i32, num_instances: i32) {
+        assert!(
+            self.cache.cur_pipeline.is_some(),
+            "Drawing without any binded pipeline"
+        );
+
+        if !self.info.features.instancing && num_instances != 1 {
+            crate::error::report_error(GraphicsError::Unsupported(
+                "Instanced rendering is not supported by the GPU, ignoring this draw call"
+                    .to_string(),
+            ));
+            return;
+        }
+
+        // Get current pipeline and create command bindings
+        let pipeline = self.cache.cur_pipeline.unwrap();
+        let pip = &self.pipelines[pipeline.0];
+        let primitive_type = pip.params.primitive_type;
+        let index_type = self.index_size().expect("Unset index buffer type");
+
+        // Create bindings from current cache state
+        let current_bindings = super::command_buffer::CommandBindings {
+            vertex_buffers: vec![],    // Would need to track current vertex buffers
+            index_buffer: BufferId(0), // Would need to track current index buffer
+            images: vec![],            // Would need to track current textures
+        };
 
         // Add draw command to the buffer
         self.command_buffer.draw_elements(
@@ -641,17 +1411,81 @@ impl GlContext {
                 index_type,
             },
         );
+
+        if self.command_buffer.is_flush_due() {
+            self.flush_command_buffer();
+        }
+    }
+
+    /// What the shader meta and the pipeline attributes get wrong about the
+    /// program, see `try_new_pipeline`
+    fn shader_mismatches(
+        &self,
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+    ) -> Vec<ShaderError> {
+        let Ok(shader) = self.shaders.get(shader.0) else {
+            return vec![];
+        };
+        let mut errors = vec![];
+        if !shader.uniform_mismatches.is_empty() {
+            errors.push(ShaderError::UniformLayoutMismatch(
+                shader.uniform_mismatches.clone(),
+            ));
+        }
+        if let Some(mismatch) = reflection::attribute_mismatch(attributes, &shader.attributes) {
+            errors.push(ShaderError::AttributeMismatch(mismatch));
+        }
+        errors
     }
 }
 
+/// All the attachments of a pass have the same sample count, resolve
+/// attachments are single sampled and resolve multisampled colors
+fn validate_pass_sample_counts(
+    attachments: impl Iterator<Item = i32>,
+    resolves: impl Iterator<Item = i32>,
+) -> Result<(), MiniquadError> {
+    let samples: Vec<i32> = attachments.map(|samples| samples.max(1)).collect();
+    if samples.windows(2).any(|pair| pair[0] != pair[1]) {
+        return Err(MiniquadError::InvalidParameter(format!(
+            "render pass attachments have different sample counts: {:?}",
+            samples
+        )));
+    }
+    let resolves: Vec<i32> = resolves.collect();
+    if !resolves.is_empty() && samples.first().map_or(true, |samples| *samples == 1) {
+        return Err(MiniquadError::InvalidParameter(
+            "resolve attachments need multisampled color attachments".to_string(),
+        ));
+    }
+    if resolves.iter().any(|samples| *samples > 1) {
+        return Err(MiniquadError::InvalidParameter(
+            "resolve attachments can't be multisampled".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn triangle_count(primitive_type: PrimitiveType, num_vertices: i32, num_instances: i32) -> u64 {
+    let triangles = match primitive_type {
+        PrimitiveType::Triangles => num_vertices / 3,
+        PrimitiveType::TriangleStrip => (num_vertices - 2).max(0),
+        PrimitiveType::Lines | PrimitiveType::Points | PrimitiveType::LineStrip => 0,
+    };
+    (triangles * num_instances) as u64
+}
+
 fn load_shader_internal(
     vertex_shader: &str,
     fragment_shader: &str,
     meta: ShaderMeta,
+    line_offset: u32,
 ) -> Result<ShaderInternal, ShaderError> {
     unsafe {
-        let vertex_shader = load_shader(GL_VERTEX_SHADER, vertex_shader)?;
-        let fragment_shader = load_shader(GL_FRAGMENT_SHADER, fragment_shader)?;
+        // `line_offset` lines were injected by new_shader_with_defines
+        let vertex_shader = load_shader(GL_VERTEX_SHADER, vertex_shader, line_offset)?;
+        let fragment_shader = load_shader(GL_FRAGMENT_SHADER, fragment_shader, line_offset)?;
 
         let program = glCreateProgram();
         glAttachShader(program, vertex_shader);
@@ -685,9 +1519,18 @@ fn load_shader_internal(
         // Don't cache during shader creation - this is initialization
         glUseProgram(program);
 
+        #[cfg(debug_assertions)]
+        let samplers = reflection::active_samplers(program);
         #[rustfmt::skip]
         let images = meta.images.iter().map(|name| ShaderImage {
+            name: name.clone(),
             gl_loc: get_uniform_location(program, name),
+            #[cfg(debug_assertions)]
+            sampler: samplers
+                .iter()
+                .find(|(sampler, _, _)| sampler == name)
+                .map(|&(_, target, type_name)| (target, type_name)),
+            unit: None,
         }).collect();
 
         #[rustfmt::skip]
@@ -696,20 +1539,32 @@ fn load_shader_internal(
                 gl_loc: get_uniform_location(program, &uniform.name),
                 uniform_type: uniform.uniform_type,
                 array_count: uniform.array_count as _,
+                last_value: vec![],
             };
-            *offset += uniform.uniform_type.size() * uniform.array_count;
+            *offset += uniform.size();
             Some(res)
         }).collect();
 
+        let uniform_mismatches = reflection::uniform_mismatches(program, &meta.uniforms);
+        let attributes = reflection::active_attributes(program);
+
         Ok(ShaderInternal {
             program,
             images,
             uniforms,
+            uniform_mismatches,
+            attributes,
         })
     }
 }
 
-pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderError> {
+/// `line_offset` is the amount of lines added in front of the user source,
+/// the diagnostics report lines of the user source.
+pub fn load_shader(
+    shader_type: GLenum,
+    source: &str,
+    line_offset: u32,
+) -> Result<GLuint, ShaderError> {
     unsafe {
         let shader = glCreateShader(shader_type);
         assert!(shader != 0);
@@ -743,12 +1598,18 @@ pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderEr
                 error_message.pop();
             }
 
+            let shader_type = match shader_type {
+                GL_VERTEX_SHADER => ShaderType::Vertex,
+                GL_FRAGMENT_SHADER => ShaderType::Fragment,
+                _ => unreachable!(),
+            };
             return Err(ShaderError::CompilationError {
-                shader_type: match shader_type {
-                    GL_VERTEX_SHADER => ShaderType::Vertex,
-                    GL_FRAGMENT_SHADER => ShaderType::Fragment,
-                    _ => unreachable!(),
-                },
+                shader_type,
+                diagnostics: super::shader_log::parse_shader_log(
+                    shader_type,
+                    &error_message,
+                    line_offset,
+                ),
                 error_message,
             });
         }
@@ -757,128 +1618,65 @@ pub fn load_shader(shader_type: GLenum, source: &str) -> Result<GLuint, ShaderEr
     }
 }
 
-impl GlContext {
-    fn set_blend(&mut self, color_blend: Option<BlendState>, alpha_blend: Option<BlendState>) {
-        if color_blend.is_none() && alpha_blend.is_some() {
-            panic!("AlphaBlend without ColorBlend");
-        }
-        if self.cache.color_blend == color_blend && self.cache.alpha_blend == alpha_blend {
-            return;
-        }
-
-        unsafe {
-            if let Some(color_blend) = color_blend {
-                if self.cache.color_blend.is_none() {
-                    glEnable(GL_BLEND);
-                }
-
-                let BlendState {
-                    equation: eq_rgb,
-                    sfactor: src_rgb,
-                    dfactor: dst_rgb,
-                } = color_blend;
-
-                if let Some(BlendState {
-                    equation: eq_alpha,
-                    sfactor: src_alpha,
-                    dfactor: dst_alpha,
-                }) = alpha_blend
-                {
-                    glBlendFuncSeparate(
-                        src_rgb.into(),
-                        dst_rgb.into(),
-                        src_alpha.into(),
-                        dst_alpha.into(),
-                    );
-                    glBlendEquationSeparate(eq_rgb.into(), eq_alpha.into());
-                } else {
-                    glBlendFunc(src_rgb.into(), dst_rgb.into());
-                    glBlendEquationSeparate(eq_rgb.into(), eq_rgb.into());
-                }
-            } else if self.cache.color_blend.is_some() {
-                glDisable(GL_BLEND);
-            }
-        }
-
-        self.cache.color_blend = color_blend;
-        self.cache.alpha_blend = alpha_blend;
-    }
-
-    fn set_stencil(&mut self, stencil_test: Option<StencilState>) {
-        if self.cache.stencil == stencil_test {
-            return;
-        }
-        unsafe {
-            if let Some(stencil) = stencil_test {
-                if self.cache.stencil.is_none() {
-                    glEnable(GL_STENCIL_TEST);
-                }
-
-                let front = &stencil.front;
-                glStencilOpSeparate(
-                    GL_FRONT,
-                    front.fail_op.into(),
-                    front.depth_fail_op.into(),
-                    front.pass_op.into(),
-                );
-                glStencilFuncSeparate(
-                    GL_FRONT,
-                    front.test_func.into(),
-                    front.test_ref,
-                    front.test_mask,
-                );
-                glStencilMaskSeparate(GL_FRONT, front.write_mask);
-
-                let back = &stencil.back;
-                glStencilOpSeparate(
-                    GL_BACK,
-                    back.fail_op.into(),
-                    back.depth_fail_op.into(),
-                    back.pass_op.into(),
-                );
-                glStencilFuncSeparate(
-                    GL_BACK,
-                    back.test_func.into(),
-                    back.test_ref,
-                    back.test_mask,
-                );
-                glStencilMaskSeparate(GL_BACK, back.write_mask);
-            } else if self.cache.stencil.is_some() {
-                glDisable(GL_STENCIL_TEST);
-            }
-        }
-
-        self.cache.stencil = stencil_test;
+/// Attach a render texture to the bound framebuffer.
+/// Multisampled render textures are renderbuffers, unless created as `Texture2DMultisample`.
+/// `level` and `layer` are checked by `RenderTarget::validate`
+unsafe fn attach_texture(texture: Texture, attachment: GLenum, level: u32, layer: u32) {
+    if let Some(raw) = texture.raw.renderbuffer() {
+        glFramebufferRenderbuffer(GL_FRAMEBUFFER, attachment, GL_RENDERBUFFER, raw);
+    } else {
+        let raw = texture.raw.texture().unwrap();
+        let target = match texture.params.kind {
+            TextureKind::Texture2DMultisample => GL_TEXTURE_2D_MULTISAMPLE,
+            TextureKind::CubeMap => GL_TEXTURE_CUBE_MAP_POSITIVE_X + layer,
+            _ => GL_TEXTURE_2D,
+        };
+        glFramebufferTexture2D(GL_FRAMEBUFFER, attachment, target, raw, level as _);
     }
+}
 
-    fn set_cull_face(&mut self, cull_face: CullFace) {
-        if self.cache.cull_face == cull_face {
-            return;
-        }
-
-        match cull_face {
-            CullFace::Nothing => unsafe {
-                glDisable(GL_CULL_FACE);
-            },
-            CullFace::Front => unsafe {
-                glEnable(GL_CULL_FACE);
-                glCullFace(GL_FRONT);
-            },
-            CullFace::Back => unsafe {
-                glEnable(GL_CULL_FACE);
-                glCullFace(GL_BACK);
-            },
-        }
-        self.cache.cull_face = cull_face;
+/// The status of a new framebuffer as a `try_new_render_pass` result
+fn check_framebuffer_status(status: GLenum) -> Result<(), MiniquadError> {
+    // Depth-only passes don't set the draw buffers to GL_NONE,
+    // GL before 4.1 reports that as incomplete, but it works anyway.
+    if status != GL_FRAMEBUFFER_COMPLETE
+        && status != GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER
+        && status != GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER
+    {
+        Err(GraphicsError::FramebufferIncomplete(status).into())
+    } else {
+        SafeGL::check_error_with_context("framebuffer creation")
     }
+}
 
-    fn set_color_write(&mut self, color_write: ColorMask) {
-        if self.cache.color_write == color_write {
-            return;
+/// Extension names, with glGetStringi on GL3+ where glGetString(GL_EXTENSIONS) is gone
+#[cfg(not(target_arch = "wasm32"))]
+fn gl_extensions(gl2: bool) -> Vec<String> {
+    unsafe {
+        if !gl2 && is_gl_func_loaded("glGetStringi") {
+            let mut count = 0;
+            glGetIntegerv(GL_NUM_EXTENSIONS, &mut count);
+            (0..count.max(0) as GLuint)
+                .filter_map(|i| {
+                    let name = glGetStringi(GL_EXTENSIONS, i);
+                    (!name.is_null()).then(|| {
+                        std::ffi::CStr::from_ptr(name as _)
+                            .to_string_lossy()
+                            .into_owned()
+                    })
+                })
+                .collect()
+        } else {
+            let names = glGetString(GL_EXTENSIONS);
+            if names.is_null() {
+                return vec![];
+            }
+            std::ffi::CStr::from_ptr(names as _)
+                .to_string_lossy()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect()
         }
-        let (r, g, b, a) = color_write;
-        unsafe { glColorMask(r as _, g as _, b as _, a as _) }
-        self.cache.color_write = color_write;
     }
 }
 
@@ -889,6 +1687,14 @@ fn gl_info() -> ContextInfo {
         .to_str()
         .unwrap()
         .to_string();
+    let renderer = unsafe { glGetString(super::gl::GL_RENDERER) };
+    let adapter = if renderer.is_null() {
+        String::new()
+    } else {
+        unsafe { std::ffi::CStr::from_ptr(renderer as _) }
+            .to_string_lossy()
+            .into_owned()
+    };
     //let gles2 = !gles3 && gl_version_string.contains("OpenGL ES");
 
     let gl2 = gl_version_string.is_empty()
@@ -896,9 +1702,54 @@ fn gl_info() -> ContextInfo {
         || gl_version_string.starts_with("OpenGL ES 2");
     let webgl1 = gl_version_string == "WebGL 1.0";
 
+    #[cfg(target_arch = "wasm32")]
+    let primitive_restart = gl_version_string.contains("WebGL 2.0");
+    #[cfg(not(target_arch = "wasm32"))]
+    let primitive_restart = gl_version_string.contains("OpenGL ES 3")
+        || (!gl2
+            && !gl_version_string.starts_with("3.0")
+            && is_gl_func_loaded("glPrimitiveRestartIndex"));
+
+    #[cfg(target_arch = "wasm32")]
+    let multisample_textures = false;
+    #[cfg(not(target_arch = "wasm32"))]
+    let multisample_textures = if gl_version_string.contains("OpenGL ES") {
+        (gl_version_string.contains("OpenGL ES 3.1") || gl_version_string.contains("OpenGL ES 3.2"))
+            && is_gl_func_loaded("glTexStorage2DMultisample")
+    } else {
+        (gl_version_string.starts_with("3.2")
+            || gl_version_string.starts_with("3.3")
+            || gl_version_string.starts_with("4"))
+            && is_gl_func_loaded("glTexImage2DMultisample")
+    };
+
+    #[cfg(target_arch = "wasm32")]
+    let immutable_textures = gl_version_string.contains("WebGL 2.0");
+    #[cfg(not(target_arch = "wasm32"))]
+    let immutable_textures = !gl2 && is_gl_func_loaded("glTexStorage2D");
+
+    // WebGl needs the compressed texture extensions enabled from JS, not done yet
+    #[cfg(target_arch = "wasm32")]
+    let extensions: Vec<String> = vec![];
+    #[cfg(not(target_arch = "wasm32"))]
+    let extensions = gl_extensions(gl2);
+    let has_extension = |name: &str| extensions.iter().any(|extension| extension == name);
+
     let features = Features {
         instancing: !gl2,
         resolve_attachments: !webgl1 && !gl2,
+        // filled in by StreamingPool detection
+        persistent_mapping: false,
+        primitive_restart,
+        multisample_textures,
+        external_textures: has_extension("GL_OES_EGL_image_external"),
+        bc3_textures: has_extension("GL_EXT_texture_compression_s3tc"),
+        bc7_textures: has_extension("GL_ARB_texture_compression_bptc")
+            || has_extension("GL_EXT_texture_compression_bptc"),
+        etc2_textures: gl_version_string.contains("OpenGL ES 3")
+            || has_extension("GL_ARB_ES3_compatibility"),
+        astc_textures: has_extension("GL_KHR_texture_compression_astc_ldr"),
+        immutable_textures,
     };
 
     let mut glsl_support = GlslSupport::default();
@@ -944,7 +1795,117 @@ fn gl_info() -> ContextInfo {
         gl_version_string,
         glsl_support,
         features,
+        adapter,
+        default_framebuffer: default_framebuffer_info(),
+        limits: gl_limits(),
+    }
+}
+
+/// The device limits, capped by the sizes of the `GlCache` arrays
+fn gl_limits() -> Limits {
+    // GLES2 guarantees 8 of both, for a failed query
+    let get = |name: GLenum| unsafe {
+        let mut value = 0;
+        glGetIntegerv(name, &mut value);
+        if value > 0 {
+            value as usize
+        } else {
+            8
+        }
+    };
+    Limits {
+        max_vertex_attributes: get(GL_MAX_VERTEX_ATTRIBS).min(MAX_VERTEX_ATTRIBUTES),
+        max_images: get(GL_MAX_TEXTURE_IMAGE_UNITS).min(MAX_SHADERSTAGE_IMAGES),
+    }
+}
+
+/// glInvalidateFramebuffer is core in WebGL2
+#[cfg(target_arch = "wasm32")]
+fn invalidate_framebuffer_supported(info: &ContextInfo) -> bool {
+    info.gl_version_string.contains("WebGL 2.0")
+}
+
+/// glInvalidateFramebuffer is core in GL4.3 and GLES3
+#[cfg(not(target_arch = "wasm32"))]
+fn invalidate_framebuffer_supported(info: &ContextInfo) -> bool {
+    let version = &info.gl_version_string;
+    let gl43 = ["4.3", "4.4", "4.5", "4.6"]
+        .iter()
+        .any(|gl43| version.starts_with(gl43));
+    (version.contains("OpenGL ES 3") || gl43) && is_gl_func_loaded("glInvalidateFramebuffer")
+}
+
+/// The formats of the framebuffer bound at the context creation, the default one
+fn default_framebuffer_info() -> FramebufferInfo {
+    let get = |name: GLenum| unsafe {
+        let mut value = 0;
+        glGetIntegerv(name, &mut value);
+        value
+    };
+    let mut info = FramebufferInfo {
+        sample_count: get(GL_SAMPLES).max(1),
+        ..Default::default()
+    };
+
+    // Core profiles removed GL_RED_BITS and the others, attachment queries are
+    // GL3+ and not in WebGL
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let version = unsafe { glGetString(GL_VERSION) };
+        let version = unsafe { std::ffi::CStr::from_ptr(version as _) }.to_string_lossy();
+        let gles = version.contains("OpenGL ES");
+        if !version.starts_with('2') && !version.starts_with("OpenGL ES 2") {
+            let (color, depth, stencil) = if get(GL_FRAMEBUFFER_BINDING) != 0 {
+                (
+                    GL_COLOR_ATTACHMENT0,
+                    GL_DEPTH_ATTACHMENT,
+                    GL_STENCIL_ATTACHMENT,
+                )
+            } else if gles {
+                (GL_BACK, GL_DEPTH, GL_STENCIL)
+            } else {
+                (GL_BACK_LEFT, GL_DEPTH, GL_STENCIL)
+            };
+            let attachment = |attachment: GLenum, pname: GLenum| unsafe {
+                let mut value = 0;
+                glGetFramebufferAttachmentParameteriv(
+                    GL_FRAMEBUFFER,
+                    attachment,
+                    GL_FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE,
+                    &mut value,
+                );
+                if value == GL_NONE as i32 {
+                    return 0;
+                }
+                glGetFramebufferAttachmentParameteriv(
+                    GL_FRAMEBUFFER,
+                    attachment,
+                    pname,
+                    &mut value,
+                );
+                value
+            };
+            info.red_bits = attachment(color, GL_FRAMEBUFFER_ATTACHMENT_RED_SIZE) as u8;
+            info.green_bits = attachment(color, GL_FRAMEBUFFER_ATTACHMENT_GREEN_SIZE) as u8;
+            info.blue_bits = attachment(color, GL_FRAMEBUFFER_ATTACHMENT_BLUE_SIZE) as u8;
+            info.alpha_bits = attachment(color, GL_FRAMEBUFFER_ATTACHMENT_ALPHA_SIZE) as u8;
+            info.depth_bits = attachment(depth, GL_FRAMEBUFFER_ATTACHMENT_DEPTH_SIZE) as u8;
+            info.stencil_bits = attachment(stencil, GL_FRAMEBUFFER_ATTACHMENT_STENCIL_SIZE) as u8;
+            info.srgb =
+                attachment(color, GL_FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING) == GL_SRGB as i32;
+            SafeGL::clear_errors();
+            return info;
+        }
     }
+
+    info.red_bits = get(GL_RED_BITS) as u8;
+    info.green_bits = get(GL_GREEN_BITS) as u8;
+    info.blue_bits = get(GL_BLUE_BITS) as u8;
+    info.alpha_bits = get(GL_ALPHA_BITS) as u8;
+    info.depth_bits = get(GL_DEPTH_BITS) as u8;
+    info.stencil_bits = get(GL_STENCIL_BITS) as u8;
+    SafeGL::clear_errors();
+    info
 }
 
 impl RenderingBackend for GlContext {
@@ -961,33 +1922,156 @@ impl RenderingBackend for GlContext {
             ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
             _ => panic!("Metal source on OpenGl context"),
         };
-        let shader = load_shader_internal(vertex, fragment, meta)?;
+        let shader = load_shader_internal(vertex, fragment, meta, 0)?;
         Ok(ShaderId(self.shaders.add(shader)))
     }
 
-    fn new_texture(
+    fn new_shader_with_defines(
+        &mut self,
+        shader: ShaderSource,
+        meta: ShaderMeta,
+        defines: &[(&str, &str)],
+    ) -> Result<ShaderId, ShaderError> {
+        let (fragment, vertex) = match shader {
+            ShaderSource::Glsl { fragment, vertex } => (fragment, vertex),
+            _ => panic!("Metal source on OpenGl context"),
+        };
+        let shader = load_shader_internal(
+            &inject_defines(vertex, defines),
+            &inject_defines(fragment, defines),
+            meta,
+            defines.len() as u32,
+        )?;
+        Ok(ShaderId(self.shaders.add(shader)))
+    }
+
+    fn try_new_texture(
         &mut self,
         access: TextureAccess,
         source: TextureSource,
         params: TextureParams,
-    ) -> TextureId {
-        let texture = Texture::new(self, access, source, params);
-        self.textures.0.push(texture);
-        TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
+    ) -> Result<TextureId, MiniquadError> {
+        let texture = Texture::try_new(self, access, source, params)?;
+        let texture = self.textures.push(texture);
+        super::profiling::track_texture(texture, &params);
+        Ok(texture)
+    }
+
+    fn import_shared_texture(
+        &mut self,
+        raw: RawId,
+        params: TextureParams,
+    ) -> Result<TextureId, MiniquadError> {
+        // What GL knows of a raw texture, plus its params
+        let texture = self.textures.get(TextureId::from_raw_id(raw));
+        let texture = self.textures.push(Texture { params, ..texture });
+        let index = self.textures.textures.len() - 1;
+        self.textures.shared.insert(index);
+        Ok(texture)
+    }
+
+    fn new_external_texture(&mut self, image: ExternalImage) -> Result<TextureId, MiniquadError> {
+        let kind = match image {
+            ExternalImage::SurfaceTexture if self.info.features.external_textures => {
+                TextureKind::External
+            }
+            ExternalImage::VideoElement(_) if cfg!(target_arch = "wasm32") => {
+                TextureKind::Texture2D
+            }
+            _ => {
+                return Err(GraphicsError::Unsupported(format!(
+                    "{:?} external textures are not supported by this GL",
+                    image
+                ))
+                .into())
+            }
+        };
+        // External textures only allow clamping and no mipmaps
+        let params = TextureParams {
+            kind,
+            ..Default::default()
+        };
+
+        let raw = SafeGL::gen_texture()?;
+        self.cache.store_texture_binding(0);
+        self.cache.bind_texture(0, kind.into(), raw);
+        unsafe { Texture::apply_sampler_params(&params) };
+        self.cache.restore_texture_binding(0);
+
+        let texture = self.textures.push(Texture {
+            raw: TextureOrRenderbuffer::Texture(raw),
+            params,
+        });
+        super::profiling::track_texture(texture, &params);
+
+        if let Err(err) = self.texture_update_external(texture, image) {
+            self.delete_texture(texture);
+            return Err(err);
+        }
+        Ok(texture)
+    }
+
+    fn texture_update_external(
+        &mut self,
+        texture: TextureId,
+        image: ExternalImage,
+    ) -> Result<(), MiniquadError> {
+        let t = self.textures.try_get(texture)?;
+        match image {
+            ExternalImage::SurfaceTexture if t.params.kind == TextureKind::External => Ok(()),
+            #[cfg(target_arch = "wasm32")]
+            ExternalImage::VideoElement(element_id) if t.params.kind == TextureKind::Texture2D => {
+                let raw = t.raw.texture().unwrap();
+                let mut size = [0u32; 2];
+                self.cache.store_texture_binding(0);
+                self.cache.bind_texture(0, GL_TEXTURE_2D, raw);
+                let uploaded = unsafe {
+                    crate::native::wasm::sapp_upload_video(
+                        element_id.as_ptr(),
+                        element_id.len(),
+                        size.as_mut_ptr(),
+                    )
+                };
+                self.cache.restore_texture_binding(0);
+                if !uploaded {
+                    return Err(ResourceError::InvalidState(format!(
+                        "no video frame in #{}",
+                        element_id
+                    ))
+                    .into());
+                }
+                self.textures.update_params(texture, |params| {
+                    params.width = size[0];
+                    params.height = size[1];
+                    super::profiling::track_texture(texture, params);
+                });
+                Ok(())
+            }
+            _ => Err(MiniquadError::InvalidParameter(format!(
+                "{:?} can't update this texture",
+                image
+            ))),
+        }
     }
 
     fn delete_texture(&mut self, texture: TextureId) {
         //self.cache.clear_texture_bindings();
 
+        if self.textures.is_shared(texture) {
+            return;
+        }
         let t = self.textures.get(texture);
         match &t.raw {
             TextureOrRenderbuffer::Texture(raw) => unsafe {
+                // GL unbinds it, and the name may come back with a new texture
+                self.cache.forget_texture(*raw);
                 glDeleteTextures(1, raw as *const _);
             },
             TextureOrRenderbuffer::Renderbuffer(raw) => unsafe {
                 glDeleteRenderbuffers(1, raw as *const _);
             },
         }
+        super::profiling::untrack(super::profiling::GpuResourceId::Texture(texture));
     }
 
     fn delete_shader(&mut self, program: ShaderId) {
@@ -999,11 +2083,52 @@ impl RenderingBackend for GlContext {
     }
 
     fn delete_pipeline(&mut self, pipeline: Pipeline) {
-        let _ = self.pipelines.remove(pipeline.0);
+        let Ok(pipeline_data) = self.pipelines.get_mut(pipeline.0) else {
+            return;
+        };
+        pipeline_data.refs -= 1;
+        if pipeline_data.refs == 0 {
+            let _ = self.pipelines.remove(pipeline.0);
+        }
+    }
+
+    fn pipelines_alive(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    fn max_sample_count(&self, format: TextureFormat) -> i32 {
+        // No multisampled renderbuffers on GL2 and WebGl1
+        if !self.info.features.resolve_attachments {
+            return 1;
+        }
+        let mut samples = 0;
+        unsafe {
+            // GL4.2 and GLES3 know it per format, sorted from the highest
+            #[cfg(not(target_arch = "wasm32"))]
+            if is_gl_func_loaded("glGetInternalformativ") {
+                glGetInternalformativ(
+                    GL_RENDERBUFFER,
+                    format.sized_internal_format(),
+                    GL_SAMPLES,
+                    1,
+                    &mut samples,
+                );
+            }
+            #[cfg(target_arch = "wasm32")]
+            let _ = format;
+            if samples == 0 {
+                glGetIntegerv(GL_MAX_SAMPLES, &mut samples);
+            }
+        }
+        samples.max(1)
     }
 
     fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
         let t = self.textures.get(texture);
+        // multisample textures are only read with texelFetch, without a sampler
+        if t.params.kind == TextureKind::Texture2DMultisample {
+            return;
+        }
         let raw = t
             .raw
             .texture()
@@ -1011,6 +2136,9 @@ impl RenderingBackend for GlContext {
 
         self.cache.store_texture_binding(0);
         self.cache.bind_texture(0, t.params.kind.into(), raw);
+        // TextureParams has a single wrap mode
+        self.textures
+            .update_params(texture, |params| params.wrap = wrap_x);
         let wrap_x = match wrap_x {
             TextureWrap::Repeat => GL_REPEAT,
             TextureWrap::Mirror => GL_MIRRORED_REPEAT,
@@ -1024,8 +2152,8 @@ impl RenderingBackend for GlContext {
         };
 
         unsafe {
-            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_S, wrap_x as i32);
-            glTexParameteri(GL_TEXTURE_2D, GL_TEXTURE_WRAP_T, wrap_y as i32);
+            glTexParameteri(t.params.kind.into(), GL_TEXTURE_WRAP_S, wrap_x as i32);
+            glTexParameteri(t.params.kind.into(), GL_TEXTURE_WRAP_T, wrap_y as i32);
         }
         self.cache.restore_texture_binding(0);
     }
@@ -1037,6 +2165,10 @@ impl RenderingBackend for GlContext {
         mipmap_filter: MipmapFilterMode,
     ) {
         let t = self.textures.get(texture);
+        // multisample textures are only read with texelFetch, without a sampler
+        if t.params.kind == TextureKind::Texture2DMultisample {
+            return;
+        }
         let raw = t.raw.texture().expect(
             "texture_set_min_filter not yet implemented for RenderBuffer(multisampled) textures",
         );
@@ -1044,6 +2176,10 @@ impl RenderingBackend for GlContext {
         self.cache.store_texture_binding(0);
         self.cache.bind_texture(0, t.params.kind.into(), raw);
 
+        self.textures.update_params(texture, |params| {
+            params.min_filter = filter;
+            params.mipmap_filter = mipmap_filter;
+        });
         let filter = Texture::gl_filter(filter, mipmap_filter);
         unsafe {
             glTexParameteri(t.params.kind.into(), GL_TEXTURE_MIN_FILTER, filter as i32);
@@ -1052,14 +2188,19 @@ impl RenderingBackend for GlContext {
     }
     fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
         let t = self.textures.get(texture);
-        let raw = t
-            .raw
-            .texture()
-            .expect("texture_set_wrap not yet implemented for RenderBuffer(multisampled) textures");
+        // multisample textures are only read with texelFetch, without a sampler
+        if t.params.kind == TextureKind::Texture2DMultisample {
+            return;
+        }
+        let raw = t.raw.texture().expect(
+            "texture_set_mag_filter not yet implemented for RenderBuffer(multisampled) textures",
+        );
 
         self.cache.store_texture_binding(0);
         self.cache.bind_texture(0, t.params.kind.into(), raw);
 
+        self.textures
+            .update_params(texture, |params| params.mag_filter = filter);
         let filter = match filter {
             FilterMode::Nearest => GL_NEAREST,
             FilterMode::Linear => GL_LINEAR,
@@ -1078,16 +2219,102 @@ impl RenderingBackend for GlContext {
     ) {
         let mut t = self.textures.get(texture);
         t.resize(self, width, height, source);
-        if let TextureIdInner::Managed(tex_id) = texture.0 {
-            self.textures.0[tex_id].params = t.params;
-        };
+        self.textures.update_params(texture, |params| {
+            *params = t.params;
+            super::profiling::track_texture(texture, params);
+        });
     }
     fn texture_read_pixels(&mut self, texture: TextureId, source: &mut [u8]) {
         let t = self.textures.get(texture);
         t.read_pixels(source);
     }
+    fn texture_read_region(
+        &mut self,
+        texture: TextureId,
+        rect: (i32, i32, i32, i32),
+        format: TextureFormat,
+        bytes: &mut [u8],
+    ) -> Result<(), MiniquadError> {
+        let t = self.textures.try_get(texture)?;
+        if t.params.kind != TextureKind::Texture2D {
+            return Err(GraphicsError::Unsupported(format!(
+                "{:?} textures can't be read back",
+                t.params.kind
+            ))
+            .into());
+        }
+        super::readback::validate(&t.params, rect, format, bytes)?;
+        let pixels = t.read_region(rect)?;
+        super::readback::encode(&pixels, format, bytes);
+        Ok(())
+    }
+    fn try_capture_window_to_texture(&mut self) -> Result<TextureId, MiniquadError> {
+        if self.cache.cur_pass.is_some() {
+            return Err(ResourceError::InvalidState(
+                "capture_window_to_texture called in a pass, call end_render_pass first"
+                    .to_string(),
+            )
+            .into());
+        }
+        let (framebuffer, width, height) = self.default_pass_target();
+        let texture = self.try_new_texture(
+            TextureAccess::RenderTarget,
+            TextureSource::Empty,
+            TextureParams {
+                width: width as _,
+                height: height as _,
+                format: TextureFormat::RGBA8,
+                wrap: TextureWrap::Clamp,
+                ..Default::default()
+            },
+        )?;
+        let raw = self.textures.get(texture).raw.texture().unwrap();
+
+        unsafe {
+            if self.info.features.resolve_attachments {
+                // A blit also resolves a multisampled default framebuffer,
+                // glCopyTexSubImage2D refuses them
+                let mut fbo = 0;
+                glGenFramebuffers(1, &mut fbo);
+                glBindFramebuffer(GL_DRAW_FRAMEBUFFER, fbo);
+                glFramebufferTexture2D(
+                    GL_DRAW_FRAMEBUFFER,
+                    GL_COLOR_ATTACHMENT0,
+                    GL_TEXTURE_2D,
+                    raw,
+                    0,
+                );
+                glBindFramebuffer(GL_READ_FRAMEBUFFER, framebuffer);
+                glBlitFramebuffer(
+                    0,
+                    0,
+                    width,
+                    height,
+                    0,
+                    0,
+                    width,
+                    height,
+                    GL_COLOR_BUFFER_BIT,
+                    GL_NEAREST,
+                );
+                glDeleteFramebuffers(1, &fbo);
+            } else {
+                glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
+                self.cache.store_texture_binding(0);
+                self.cache.bind_texture(0, GL_TEXTURE_2D, raw);
+                glCopyTexSubImage2D(GL_TEXTURE_2D, 0, 0, 0, 0, 0, width, height);
+                self.cache.restore_texture_binding(0);
+            }
+            glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
+        }
+        Ok(texture)
+    }
     fn texture_generate_mipmaps(&mut self, texture: TextureId) {
         let t = self.textures.get(texture);
+        // multisample textures are only read with texelFetch, without a sampler
+        if t.params.kind == TextureKind::Texture2DMultisample {
+            return;
+        }
         let raw = t.raw.texture().expect(
             "texture_generate_mipmaps not yet implemented for RenderBuffer(multisampled) textures",
         );
@@ -1125,62 +2352,60 @@ impl RenderingBackend for GlContext {
         RawId::OpenGl(raw)
     }
 
-    fn new_render_pass_mrt(
+    fn try_new_render_pass_mrt(
         &mut self,
         color_img: &[TextureId],
         resolve_img: Option<&[TextureId]>,
         depth_img: Option<TextureId>,
-    ) -> RenderPass {
+    ) -> Result<RenderPass, MiniquadError> {
         if color_img.is_empty() && depth_img.is_none() {
-            panic!("Render pass should have at least one non-none target");
+            return Err(MiniquadError::InvalidParameter(
+                "Render pass should have at least one non-none target".to_string(),
+            ));
+        }
+        let attachments = color_img
+            .iter()
+            .chain(resolve_img.unwrap_or(&[]))
+            .chain(depth_img.iter());
+        for texture in attachments {
+            self.textures.try_get(*texture)?;
+        }
+        if let Some(resolve_img) = resolve_img {
+            if resolve_img.len() != color_img.len() {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "{} resolve attachments for {} color attachments",
+                    resolve_img.len(),
+                    color_img.len()
+                )));
+            }
         }
+        validate_pass_sample_counts(
+            color_img
+                .iter()
+                .chain(depth_img.iter())
+                .map(|texture| self.textures.get(*texture).params.sample_count),
+            resolve_img
+                .unwrap_or(&[])
+                .iter()
+                .map(|texture| self.textures.get(*texture).params.sample_count),
+        )?;
+
+        SafeGL::clear_errors();
+
         let mut gl_fb = 0;
 
         let mut resolves = None;
+        let mut status: GLenum;
         unsafe {
             glGenFramebuffers(1, &mut gl_fb as *mut _);
             glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
             for (i, color_img) in color_img.iter().enumerate() {
                 let texture = self.textures.get(*color_img);
-                if texture.params.sample_count > 1 {
-                    let raw = texture.raw.renderbuffer().unwrap();
-                    glFramebufferRenderbuffer(
-                        GL_FRAMEBUFFER,
-                        GL_COLOR_ATTACHMENT0 + i as u32,
-                        GL_RENDERBUFFER,
-                        raw,
-                    );
-                } else {
-                    let raw = texture.raw.texture().unwrap();
-                    glFramebufferTexture2D(
-                        GL_FRAMEBUFFER,
-                        GL_COLOR_ATTACHMENT0 + i as u32,
-                        GL_TEXTURE_2D,
-                        raw,
-                        0,
-                    );
-                }
+                attach_texture(texture, GL_COLOR_ATTACHMENT0 + i as u32, 0, 0);
             }
             if let Some(depth_img) = depth_img {
                 let texture = self.textures.get(depth_img);
-                if texture.params.sample_count > 1 {
-                    let raw = texture.raw.renderbuffer().unwrap();
-                    glFramebufferRenderbuffer(
-                        GL_FRAMEBUFFER,
-                        GL_DEPTH_ATTACHMENT,
-                        GL_RENDERBUFFER,
-                        raw,
-                    );
-                } else {
-                    let raw = texture.raw.texture().unwrap();
-                    glFramebufferTexture2D(
-                        GL_FRAMEBUFFER,
-                        GL_DEPTH_ATTACHMENT,
-                        GL_TEXTURE_2D,
-                        raw,
-                        0,
-                    );
-                }
+                attach_texture(texture, GL_DEPTH_ATTACHMENT, 0, 0);
             }
             let mut attachments = vec![];
             for i in 0..color_img.len() {
@@ -1190,6 +2415,7 @@ impl RenderingBackend for GlContext {
             if color_img.len() > 1 {
                 glDrawBuffers(color_img.len() as _, attachments.as_ptr() as _);
             }
+            status = glCheckFramebufferStatus(GL_FRAMEBUFFER);
 
             if let Some(resolve_img) = resolve_img {
                 resolves = Some(vec![]);
@@ -1209,20 +2435,98 @@ impl RenderingBackend for GlContext {
                         0,
                     );
                     let fb_status = glCheckFramebufferStatus(GL_FRAMEBUFFER);
-                    assert!(fb_status != 0);
+                    if status == GL_FRAMEBUFFER_COMPLETE {
+                        status = fb_status;
+                    }
                     glDrawBuffers(1, attachments.as_ptr() as _);
                 }
             }
             glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
         }
+
+        if let Err(err) = check_framebuffer_status(status) {
+            unsafe {
+                glDeleteFramebuffers(1, &gl_fb as *const _);
+                for (resolve_fb, _) in resolves.iter().flatten() {
+                    glDeleteFramebuffers(1, resolve_fb as *const _);
+                }
+            }
+            return Err(err);
+        }
+
         let pass = RenderPassInternal {
             gl_fb,
             color_textures: color_img.to_vec(),
             resolves,
             depth_texture: depth_img,
+            level: 0,
+            owns_textures: true,
         };
 
-        RenderPass(self.passes.add(pass))
+        Ok(RenderPass(self.passes.add(pass)))
+    }
+    fn try_new_render_pass_targets(
+        &mut self,
+        color: &[RenderTarget],
+        depth: Option<RenderTarget>,
+    ) -> Result<RenderPass, MiniquadError> {
+        if color.is_empty() && depth.is_none() {
+            return Err(MiniquadError::InvalidParameter(
+                "Render pass should have at least one non-none target".to_string(),
+            ));
+        }
+        for target in color.iter().chain(depth.iter()) {
+            let texture = self.textures.try_get(target.texture)?;
+            target.validate(&texture.params)?;
+        }
+        validate_pass_sample_counts(
+            color
+                .iter()
+                .chain(depth.iter())
+                .map(|target| self.textures.get(target.texture).params.sample_count),
+            std::iter::empty(),
+        )?;
+
+        SafeGL::clear_errors();
+
+        let mut gl_fb = 0;
+        let status;
+        unsafe {
+            glGenFramebuffers(1, &mut gl_fb as *mut _);
+            glBindFramebuffer(GL_FRAMEBUFFER, gl_fb);
+            for (i, target) in color.iter().enumerate() {
+                let texture = self.textures.get(target.texture);
+                let attachment = GL_COLOR_ATTACHMENT0 + i as u32;
+                attach_texture(texture, attachment, target.level, target.layer);
+            }
+            if let Some(target) = depth {
+                let texture = self.textures.get(target.texture);
+                attach_texture(texture, GL_DEPTH_ATTACHMENT, target.level, target.layer);
+            }
+            if color.len() > 1 {
+                let attachments: Vec<GLenum> = (0..color.len() as u32)
+                    .map(|i| GL_COLOR_ATTACHMENT0 + i)
+                    .collect();
+                glDrawBuffers(color.len() as _, attachments.as_ptr() as _);
+            }
+            status = glCheckFramebufferStatus(GL_FRAMEBUFFER);
+            glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
+        }
+        if let Err(err) = check_framebuffer_status(status) {
+            unsafe { glDeleteFramebuffers(1, &gl_fb as *const _) };
+            return Err(err);
+        }
+
+        let pass = RenderPassInternal {
+            gl_fb,
+            color_textures: color.iter().map(|target| target.texture).collect(),
+            resolves: None,
+            depth_texture: depth.map(|target| target.texture),
+            level: color.first().or(depth.as_ref()).unwrap().level,
+            owns_textures: false,
+        };
+
+        Ok(RenderPass(self.passes.add(pass)))
     }
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
         self.passes
@@ -1236,6 +2540,10 @@ impl RenderingBackend for GlContext {
         // Get render pass data and then remove it
         if let Ok(render_pass) = self.passes.remove(pass_id) {
             unsafe { glDeleteFramebuffers(1, &render_pass.gl_fb as *const _) }
+            self.clear_colors.forget(RenderPass(pass_id));
+            if !render_pass.owns_textures {
+                return;
+            }
 
             for color_texture in &render_pass.color_textures {
                 self.delete_texture(*color_texture);
@@ -1250,10 +2558,7 @@ impl RenderingBackend for GlContext {
                 self.delete_texture(depth_texture);
             }
         } else {
-            eprintln!(
-                "Warning: Attempting to delete invalid render pass ID {}",
-                pass_id
-            );
+            crate::error::report_error(ResourceError::NotFound(pass_id));
         }
     }
 
@@ -1264,6 +2569,24 @@ impl RenderingBackend for GlContext {
         shader: ShaderId,
         params: PipelineParams,
     ) -> Pipeline {
+        let desc = PipelineDesc::new(buffer_layout, attributes, shader, params);
+        // Pipelines are few, a linear search is fine and f32s in params are not Hash
+        let existing = self.pipelines.iter().find(|(_, p)| p.desc == desc);
+        if let Some((id, _)) = existing {
+            self.pipelines[id].refs += 1;
+            return Pipeline(id);
+        }
+
+        if params.primitive_restart && !self.info.features.primitive_restart {
+            crate::error::report_error(GraphicsError::Unsupported(
+                "Primitive restart is not supported by the GPU, strips will not be restarted"
+                    .to_string(),
+            ));
+        }
+        for error in self.shader_mismatches(attributes, shader) {
+            crate::error::report_error(error);
+        }
+
         #[derive(Clone, Copy, Default)]
         struct BufferCacheData {
             stride: i32,
@@ -1374,85 +2697,78 @@ impl RenderingBackend for GlContext {
             layout: vertex_layout,
             shader,
             params,
+            desc,
+            refs: 1,
         };
 
         Pipeline(self.pipelines.add(pipeline))
     }
 
+    fn try_new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Result<Pipeline, MiniquadError> {
+        self.shaders.get(shader.0)?;
+        if let Some(error) = self
+            .shader_mismatches(attributes, shader)
+            .into_iter()
+            .next()
+        {
+            return Err(error.into());
+        }
+        Ok(self.new_pipeline(buffer_layout, attributes, shader, params))
+    }
+
     fn apply_pipeline(&mut self, pipeline: &Pipeline) {
         self.cache.cur_pipeline = Some(*pipeline);
+        super::profiling::record_apply_pipeline(*pipeline);
+        SafeGL::set_call_scope(Some((self.cache.cur_pass, Some(*pipeline))));
 
         {
             let pipeline_data = match self.pipelines.get(pipeline.0) {
                 Ok(p) => p,
                 Err(_) => {
-                    eprintln!(
-                        "Warning: Invalid pipeline ID {} in apply_pipeline",
-                        pipeline.0
-                    );
+                    crate::error::report_error(ResourceError::NotFound(pipeline.0));
                     return;
                 }
             };
             let shader = match self.shaders.get(pipeline_data.shader.0) {
                 Ok(s) => s,
                 Err(_) => {
-                    eprintln!(
-                        "Warning: Invalid shader ID {} in pipeline",
-                        pipeline_data.shader.0
-                    );
+                    crate::error::report_error(ResourceError::NotFound(pipeline_data.shader.0));
                     return;
                 }
             };
             // Use enhanced caching for program switching
             self.cache.use_program(shader.program);
-
-            unsafe {
-                glEnable(GL_SCISSOR_TEST);
-            }
-
-            if pipeline_data.params.depth_write {
-                unsafe {
-                    glEnable(GL_DEPTH_TEST);
-                    glDepthFunc(pipeline_data.params.depth_test.into())
-                }
-            } else {
-                unsafe {
-                    glDisable(GL_DEPTH_TEST);
-                }
-            }
-
-            match pipeline_data.params.front_face_order {
-                FrontFaceOrder::Clockwise => unsafe {
-                    glFrontFace(GL_CW);
-                },
-                FrontFaceOrder::CounterClockwise => unsafe {
-                    glFrontFace(GL_CCW);
-                },
-            }
         }
 
         // Get pipeline data again and copy the values to avoid borrowing issues
         if let Ok(pipeline_data) = self.pipelines.get(pipeline.0) {
-            let cull_face = pipeline_data.params.cull_face;
-            let color_blend = pipeline_data.params.color_blend;
-            let alpha_blend = pipeline_data.params.alpha_blend;
-            let stencil_test = pipeline_data.params.stencil_test;
-            let color_write = pipeline_data.params.color_write;
-
-            // Now we can call mutable methods
-            self.set_cull_face(cull_face);
-            self.set_blend(color_blend, alpha_blend);
-            self.set_stencil(stencil_test);
-            self.set_color_write(color_write);
+            let params = pipeline_data.params;
+            let depth_test = params.depth_write.then_some(params.depth_test);
+
+            // Only the states differing from the previous pipeline reach GL
+            self.cache.set_front_face(params.front_face_order);
+            self.set_depth_test(depth_test);
+            self.cache.set_depth_write(params.depth_write);
+            self.set_cull_face(params.cull_face);
+            self.set_blend(params.color_blend, params.alpha_blend);
+            self.cache.set_stencil(params.stencil_test);
+            self.cache.set_color_write(params.color_write);
         }
+        self.update_primitive_restart();
     }
 
-    fn new_buffer(
+    fn try_new_buffer(
         &mut self,
         type_: BufferType,
         usage: BufferUsage,
         data: BufferSource,
-    ) -> BufferId {
+    ) -> Result<BufferId, MiniquadError> {
         let gl_target = gl_buffer_target(&type_);
         let (size, element_size) = match &data {
             BufferSource::Slice(data) => (data.size, data.element_size),
@@ -1464,27 +2780,23 @@ impl RenderingBackend for GlContext {
             {
                 Some(element_size as u32)
             }
-            BufferType::IndexBuffer => panic!(
+            BufferType::IndexBuffer => {
+                return Err(MiniquadError::InvalidParameter(format!(
                 "Unsupported index buffer element size: {}. Only 1, 2, and 4 bytes are supported",
                 element_size
-            ),
+            )))
+            }
             BufferType::VertexBuffer => None,
         };
 
+        // Errors of earlier calls should not be reported for this buffer
+        SafeGL::clear_errors();
+
         // Try to acquire buffer from pool first
         let (gl_buf, from_pool) = match self.buffer_pool.acquire_buffer(type_, usage, size) {
             Ok(pooled_buf) => (pooled_buf, true),
-            Err(_) => {
-                // Pool failed, fall back to direct allocation
-                let mut gl_buf: u32 = 0;
-                unsafe {
-                    glGenBuffers(1, &mut gl_buf as *mut _);
-                    if gl_buf == 0 {
-                        panic!("Failed to generate GL buffer");
-                    }
-                }
-                (gl_buf, false)
-            }
+            // Pool failed, fall back to direct allocation
+            Err(_) => (SafeGL::gen_buffer()?, false),
         };
 
         // Upload data to the buffer
@@ -1508,19 +2820,42 @@ impl RenderingBackend for GlContext {
                     glBufferSubData(gl_target, 0, size as _, data.ptr as _);
                 }
             }
-
-            self.cache.restore_buffer_binding(gl_target);
+        }
+        let res = SafeGL::check_error_with_context("buffer upload");
+        self.cache.restore_buffer_binding(gl_target);
+        if let Err(err) = res {
+            if from_pool {
+                let _ = self.buffer_pool.release_buffer(gl_buf);
+            } else {
+                unsafe { glDeleteBuffers(1, &gl_buf as *const _) };
+            }
+            return Err(err);
         }
 
+        let capacity = if from_pool {
+            BufferPool::get_size_bucket(size)
+        } else {
+            size
+        };
         let buffer = Buffer {
             gl_buf,
+            original: gl_buf,
             buffer_type: type_,
+            usage,
             size,
+            capacity,
             index_type,
             from_pool,
+            orphaning: BufferOrphaning::default(),
+            copies: vec![],
+            streaming: vec![],
+            current_copy: 0,
         };
+        let bytes = buffer.allocated_bytes();
 
-        BufferId(self.buffers.add(buffer))
+        let buffer = BufferId(self.buffers.add(buffer));
+        super::profiling::track_buffer(buffer, type_, usage, bytes);
+        Ok(buffer)
     }
 
     fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
@@ -1531,10 +2866,10 @@ impl RenderingBackend for GlContext {
             ),
         };
         debug_assert!(data.is_slice);
-        let buffer = match self.buffers.get(buffer.0) {
+        let buffer = match self.buffers.get_mut(buffer.0) {
             Ok(b) => b,
             Err(_) => {
-                eprintln!("Warning: Invalid buffer ID {} in buffer_update", buffer.0);
+                crate::error::report_error(ResourceError::NotFound(buffer.0));
                 return;
             }
         };
@@ -1548,14 +2883,161 @@ impl RenderingBackend for GlContext {
 
         assert!(size <= buffer.size);
 
+        if !buffer.streaming.is_empty() {
+            buffer.current_copy = (buffer.current_copy + 1) % buffer.streaming.len();
+            let copy = &mut buffer.streaming[buffer.current_copy];
+            if let Some(last_use) = copy.last_use {
+                self.streaming_pool.wait(last_use);
+            }
+            unsafe { std::ptr::copy_nonoverlapping(data.ptr as *const u8, copy.ptr, size) };
+            copy.last_use = Some(self.streaming_pool.sync_point());
+            buffer.gl_buf = copy.gl_buf;
+            return;
+        }
+
+        if !buffer.copies.is_empty() {
+            buffer.current_copy = (buffer.current_copy + 1) % buffer.copies.len();
+            buffer.gl_buf = buffer.copies[buffer.current_copy];
+        }
+
+        let gl_target = gl_buffer_target(&buffer.buffer_type);
+        self.cache.store_buffer_binding(gl_target);
+        self.cache
+            .bind_buffer(gl_target, buffer.gl_buf, buffer.index_type);
+        unsafe {
+            if buffer.orphaning == BufferOrphaning::Orphan {
+                glBufferData(
+                    gl_target,
+                    buffer.capacity as _,
+                    std::ptr::null() as *const _,
+                    gl_usage(&buffer.usage),
+                );
+            }
+            glBufferSubData(gl_target, 0, size as _, data.ptr as _)
+        };
+        self.cache.restore_buffer_binding(gl_target);
+    }
+
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
+        let data = match data {
+            BufferSource::Slice(data) => data,
+            BufferSource::Empty { .. } => panic!("buffer_update_range expects BufferSource::Slice"),
+        };
+        let buffer = match self.buffers.get_mut(buffer.0) {
+            Ok(b) => b,
+            Err(_) => {
+                crate::error::report_error(ResourceError::NotFound(buffer.0));
+                return;
+            }
+        };
+        if let Some(index_type) = buffer.index_type {
+            assert_eq!(data.element_size as u32, index_type);
+        }
+        assert!(offset + data.size <= buffer.size);
+
+        // The copy in use, persistently mapped ones are written directly
+        if let Some(copy) = buffer.streaming.get(buffer.current_copy) {
+            unsafe {
+                std::ptr::copy_nonoverlapping(
+                    data.ptr as *const u8,
+                    copy.ptr.add(offset),
+                    data.size,
+                )
+            };
+            return;
+        }
         let gl_target = gl_buffer_target(&buffer.buffer_type);
         self.cache.store_buffer_binding(gl_target);
         self.cache
             .bind_buffer(gl_target, buffer.gl_buf, buffer.index_type);
-        unsafe { glBufferSubData(gl_target, 0, size as _, data.ptr as _) };
+        unsafe { glBufferSubData(gl_target, offset as _, data.size as _, data.ptr as _) };
         self.cache.restore_buffer_binding(gl_target);
     }
 
+    fn buffer_set_orphaning(&mut self, id: BufferId, orphaning: BufferOrphaning) {
+        let buffer = match self.buffers.get_mut(id.0) {
+            Ok(b) => b,
+            Err(_) => {
+                crate::error::report_error(ResourceError::NotFound(id.0));
+                return;
+            }
+        };
+        if buffer.orphaning == orphaning {
+            return;
+        }
+
+        let gl_target = gl_buffer_target(&buffer.buffer_type);
+
+        // Drop the extra copies from the previous mode,
+        // going back to the buffer created by new_buffer.
+        for gl_buf in buffer.copies.iter().skip(1) {
+            unsafe { glDeleteBuffers(1, gl_buf as *const _) };
+        }
+        for copy in buffer.streaming.drain(..) {
+            self.streaming_pool.release(gl_target, copy);
+        }
+        buffer.copies.clear();
+        buffer.current_copy = 0;
+        buffer.gl_buf = buffer.original;
+
+        let mut orphaning = orphaning;
+        if orphaning == BufferOrphaning::Persistent {
+            let cache = &mut self.cache;
+            cache.store_buffer_binding(gl_target);
+            for _ in 0..FRAMES_IN_FLIGHT {
+                let copy = self
+                    .streaming_pool
+                    .acquire(gl_target, buffer.capacity, |gl_buf| {
+                        cache.bind_buffer(gl_target, gl_buf, buffer.index_type)
+                    });
+                match copy {
+                    Some(copy) => buffer.streaming.push(copy),
+                    None => break,
+                }
+            }
+            cache.restore_buffer_binding(gl_target);
+
+            if buffer.streaming.len() != FRAMES_IN_FLIGHT {
+                for copy in buffer.streaming.drain(..) {
+                    self.streaming_pool.release(gl_target, copy);
+                }
+                orphaning = BufferOrphaning::Orphan;
+            }
+        }
+
+        if let BufferOrphaning::MultiBuffer(count) = orphaning {
+            if count > 1 {
+                let gl_usage = gl_usage(&buffer.usage);
+                buffer.copies.push(buffer.gl_buf);
+                self.cache.store_buffer_binding(gl_target);
+                for _ in 1..count {
+                    let mut gl_buf: GLuint = 0;
+                    unsafe {
+                        glGenBuffers(1, &mut gl_buf as *mut _);
+                        self.cache.bind_buffer(gl_target, gl_buf, buffer.index_type);
+                        glBufferData(
+                            gl_target,
+                            buffer.capacity as _,
+                            std::ptr::null() as *const _,
+                            gl_usage,
+                        );
+                    }
+                    buffer.copies.push(gl_buf);
+                }
+                self.cache.restore_buffer_binding(gl_target);
+            }
+        }
+
+        buffer.orphaning = orphaning;
+        super::profiling::track_buffer(
+            id,
+            buffer.buffer_type,
+            buffer.usage,
+            buffer.allocated_bytes(),
+        );
+        self.cache.clear_vertex_attributes();
+    }
+
     /// Size of buffer in bytes
     fn buffer_size(&mut self, buffer: BufferId) -> usize {
         self.buffers.get(buffer.0).map(|b| b.size).unwrap_or(0) // Return 0 for invalid buffer
@@ -1570,29 +3052,76 @@ impl RenderingBackend for GlContext {
     /// this function is not marked as unsafe
     fn delete_buffer(&mut self, buffer: BufferId) {
         if let Ok(buffer_data) = self.buffers.get(buffer.0) {
+            let original = buffer_data.original;
             if buffer_data.from_pool {
                 // Return pooled buffer back to the pool for reuse
-                let _ = self.buffer_pool.release_buffer(buffer_data.gl_buf);
+                let _ = self.buffer_pool.release_buffer(original);
             } else {
                 // Delete non-pooled buffer immediately
-                unsafe { glDeleteBuffers(1, &buffer_data.gl_buf as *const _) }
+                unsafe { glDeleteBuffers(1, &original as *const _) }
+            }
+            // Extra multi-buffering copies are never pooled
+            for gl_buf in buffer_data.copies.iter().skip(1) {
+                unsafe { glDeleteBuffers(1, gl_buf as *const _) }
+            }
+            let gl_target = gl_buffer_target(&buffer_data.buffer_type);
+            for copy in &buffer_data.streaming {
+                self.streaming_pool.release(gl_target, *copy);
             }
         }
         self.cache.clear_buffer_bindings();
         self.cache.clear_vertex_attributes();
         let _ = self.buffers.remove(buffer.0);
+        super::profiling::untrack(super::profiling::GpuResourceId::Buffer(buffer));
     }
 
     /// Set a new viewport rectangle.
     /// Should be applied after begin_pass.
     fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
         self.cache.apply_viewport(x, y, w, h);
+        self.cache.apply_depth_range(0., 1.);
+    }
+
+    fn apply_viewport_f(&mut self, x: f32, y: f32, w: f32, h: f32, min_depth: f32, max_depth: f32) {
+        self.cache.apply_viewport_f(x, y, w, h);
+        self.cache.apply_depth_range(min_depth, max_depth);
     }
 
     /// Set a new scissor rectangle.
     /// Should be applied after begin_pass.
     fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
-        self.cache.apply_scissor(x, y, w, h);
+        self.cache.apply_scissor(Some((x, y, w, h)));
+    }
+
+    fn apply_scissor(&mut self, rect: Option<(i32, i32, i32, i32)>) {
+        self.cache.apply_scissor(rect);
+    }
+
+    fn set_depth_test(&mut self, depth_test: Option<Comparison>) {
+        self.cache.set_depth_test(depth_test);
+    }
+
+    fn set_blend(&mut self, color_blend: Option<BlendState>, alpha_blend: Option<BlendState>) {
+        if color_blend.is_none() && alpha_blend.is_some() {
+            panic!("AlphaBlend without ColorBlend");
+        }
+        self.cache.set_blend(color_blend, alpha_blend);
+    }
+
+    fn set_cull_face(&mut self, cull_face: CullFace) {
+        self.cache.set_cull_face(cull_face);
+    }
+
+    fn set_color_write(&mut self, color_write: ColorMask) {
+        self.cache.set_color_write(color_write);
+    }
+
+    fn set_stencil_reference(&mut self, reference: i32) {
+        self.cache.set_stencil_reference(reference);
+    }
+
+    fn set_stencil_compare_mask(&mut self, mask: u32) {
+        self.cache.set_stencil_compare_mask(mask);
     }
 
     fn apply_bindings_from_slice(
@@ -1601,26 +3130,22 @@ impl RenderingBackend for GlContext {
         index_buffer: BufferId,
         textures: &[TextureId],
     ) {
-        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
-        let shader = &self.shaders[pip.shader.0];
-
-        for (n, shader_image) in shader.images.iter().enumerate() {
-            let bindings_image = textures
-                .get(n)
-                .unwrap_or_else(|| panic!("Image count in bindings and shader did not match!"));
-            if let Some(gl_loc) = shader_image.gl_loc {
-                let texture = self.textures.get(*bindings_image);
-                let raw = match texture.raw {
-                    TextureOrRenderbuffer::Texture(id) => id,
-                    TextureOrRenderbuffer::Renderbuffer(id) => id,
-                };
-                unsafe {
-                    self.cache.bind_texture(n, texture.params.kind.into(), raw);
-                    glUniform1i(gl_loc, n as i32);
-                }
+        #[cfg(feature = "validation")]
+        {
+            let res = validation::validate_bindings(self, vertex_buffers, index_buffer, textures);
+            if let Err(err) = res {
+                self.bound = None;
+                crate::error::report_error(err);
+                return;
             }
+            self.bound = Some(validation::BoundResources::new(
+                vertex_buffers,
+                Some(index_buffer),
+            ));
         }
 
+        self.bind_images(textures);
+
         self.cache.bind_buffer(
             GL_ELEMENT_ARRAY_BUFFER,
             self.buffers[index_buffer.0].gl_buf,
@@ -1629,7 +3154,7 @@ impl RenderingBackend for GlContext {
 
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
 
-        for attr_index in 0..MAX_VERTEX_ATTRIBUTES {
+        for attr_index in 0..self.cache.limits.max_vertex_attributes {
             let cached_attr = &mut self.cache.attributes[attr_index];
 
             let pip_attribute = pip.layout.get(attr_index).copied();
@@ -1640,7 +3165,7 @@ impl RenderingBackend for GlContext {
                     "Attribute index outside of vertex_buffers length"
                 );
                 let vb = vertex_buffers[attribute.buffer_index];
-                let vb = self.buffers[vb.0];
+                let vb = &self.buffers[vb.0];
 
                 if cached_attr.map_or(true, |cached_attr| {
                     attribute != cached_attr.attribute || cached_attr.gl_vbuf != vb.gl_buf
@@ -1690,22 +3215,49 @@ impl RenderingBackend for GlContext {
                 *cached_attr = None;
             }
         }
+        self.update_primitive_restart();
+    }
+
+    fn uniform_block_size(&self) -> Option<usize> {
+        let pipeline = &self.pipelines[self.cache.cur_pipeline?.0];
+        let shader = &self.shaders[pipeline.shader.0];
+        let size = shader
+            .uniforms
+            .iter()
+            .map(|uniform| uniform.uniform_type.size() * uniform.array_count as usize)
+            .sum();
+        Some(size)
     }
 
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        #[cfg(debug_assertions)]
+        if let Err(err) = check_uniforms_size(size, self.uniform_block_size()) {
+            crate::error::report_error(err);
+            return;
+        }
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
-        let shader = &self.shaders[pip.shader.0];
+        let shader = &mut self.shaders[pip.shader.0];
 
         let mut offset = 0;
 
-        for uniform in shader.uniforms.iter() {
+        for uniform in shader.uniforms.iter_mut() {
             use UniformType::*;
 
+            let len = uniform.uniform_type.size() * uniform.array_count as usize;
             assert!(
-                offset as i32 <= size as i32 - uniform.uniform_type.size() as i32 / 4,
+                offset * 4 + len <= size,
                 "Uniforms struct does not match shader uniforms layout"
             );
 
+            let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr.add(offset * 4), len) };
+            if uniform.last_value == bytes {
+                offset += len / 4;
+                continue;
+            }
+            uniform.last_value.clear();
+            uniform.last_value.extend_from_slice(bytes);
+
             unsafe {
                 let data = (uniform_ptr as *const f32).add(offset);
                 let data_int = (uniform_ptr as *const i32).add(offset);
@@ -1736,13 +3288,19 @@ impl RenderingBackend for GlContext {
                         Int4 => {
                             glUniform4iv(gl_loc, uniform.array_count, data_int);
                         }
+                        Bool => {
+                            glUniform1iv(gl_loc, uniform.array_count, data_int);
+                        }
+                        Mat3 => {
+                            glUniformMatrix3fv(gl_loc, uniform.array_count, 0, data);
+                        }
                         Mat4 => {
                             glUniformMatrix4fv(gl_loc, uniform.array_count, 0, data);
                         }
                     }
                 }
             }
-            offset += uniform.uniform_type.size() / 4 * uniform.array_count as usize;
+            offset += len / 4;
         }
     }
 
@@ -1774,11 +3332,51 @@ impl RenderingBackend for GlContext {
             }
         }
 
-        if bits != 0 {
-            unsafe {
-                glClear(bits);
+        if bits == 0 {
+            return;
+        }
+
+        // Write masks of the applied pipeline apply to glClear too, open them meanwhile
+        let color_write = self.cache.color_write;
+        let depth_write = self.cache.depth_write;
+        let stencil_faces = self.cache.stencil_faces;
+        if color.is_some() {
+            self.cache.set_color_write((true, true, true, true));
+        }
+        if depth.is_some() {
+            self.cache.set_depth_write(true);
+        }
+        if stencil.is_some() {
+            for (index, face) in stencil_faces.iter().enumerate() {
+                let open = StencilFaceState {
+                    write_mask: !0,
+                    ..*face
+                };
+                self.cache.set_stencil_face(index, open);
             }
         }
+        unsafe {
+            glClear(bits);
+        }
+        self.cache.set_color_write(color_write);
+        self.cache.set_depth_write(depth_write);
+        for (index, face) in stencil_faces.iter().enumerate() {
+            self.cache.set_stencil_face(index, *face);
+        }
+    }
+
+    fn clear_region(
+        &mut self,
+        rect: (i32, i32, i32, i32),
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        // glClear is restricted by the scissor test, and only by it
+        let scissor = self.cache.scissor;
+        self.cache.apply_scissor(Some(rect));
+        self.clear(color, depth, stencil);
+        self.cache.apply_scissor(scissor);
     }
 
     fn begin_default_pass(&mut self, action: PassAction) {
@@ -1786,17 +3384,13 @@ impl RenderingBackend for GlContext {
     }
 
     fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        let action = self.clear_colors.resolve(pass, action);
         self.cache.cur_pass = pass;
+        self.gpu_timer.begin_pass(pass);
+        super::profiling::record_begin_pass(pass);
+        SafeGL::set_call_scope(Some((pass, None)));
         let (framebuffer, w, h) = match pass {
-            None => {
-                let (screen_width, screen_height) = window::screen_size();
-
-                (
-                    self.default_framebuffer,
-                    screen_width as i32,
-                    screen_height as i32,
-                )
-            }
+            None => self.default_pass_target(),
             Some(pass) => {
                 let pass = &self.passes[pass.0];
                 // new_render_pass will panic with both color and depth components none
@@ -1807,10 +3401,11 @@ impl RenderingBackend for GlContext {
                     .copied()
                     .or(pass.depth_texture)
                     .unwrap();
+                let params = self.textures.get(texture).params;
                 (
                     pass.gl_fb,
-                    self.textures.get(texture).params.width as i32,
-                    self.textures.get(texture).params.height as i32,
+                    (params.width >> pass.level).max(1) as i32,
+                    (params.height >> pass.level).max(1) as i32,
                 )
             }
         };
@@ -1818,9 +3413,8 @@ impl RenderingBackend for GlContext {
             glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
         }
 
-        // Use enhanced caching for viewport and scissor
-        self.cache.apply_viewport(0, 0, w, h);
-        self.cache.apply_scissor(0, 0, w, h);
+        self.cache.reset_pass_state(self.pass_state_reset, w, h);
+        self.pass_discards.clear();
         match action {
             PassAction::Nothing => {}
             PassAction::Clear {
@@ -1830,12 +3424,78 @@ impl RenderingBackend for GlContext {
             } => {
                 self.clear(color, depth, stencil);
             }
+            PassAction::Attachments { .. } => {
+                let color_count = pass.map_or(1, |pass| self.passes[pass.0].color_textures.len());
+                self.load_attachments(framebuffer, color_count, &action);
+            }
+            PassAction::ClearPrevious => unreachable!("resolved by ClearColors"),
+        }
+    }
+
+    fn set_pass_state_reset(&mut self, reset: PassStateReset) {
+        self.pass_state_reset = reset;
+    }
+
+    fn set_frame_stream(&mut self, callback: Option<super::frame_stream::FrameCallback>) -> bool {
+        self.frame_stream.set_callback(callback)
+    }
+
+    fn set_software_cursor(&mut self, cursor: Option<super::software_cursor::SoftwareCursor>) {
+        if self.software_cursor.is_none() {
+            if cursor.is_none() {
+                return;
+            }
+            self.software_cursor = Some(super::software_cursor::CursorRenderer::new(self));
+        }
+        self.software_cursor.as_mut().unwrap().set_cursor(cursor);
+    }
+
+    fn set_internal_resolution(
+        &mut self,
+        resolution: Option<(u32, u32)>,
+        mode: super::letterbox::ScaleMode,
+    ) -> bool {
+        use super::letterbox::{Letterbox, ScaleMode};
+
+        let reused = match self.internal_resolution.take() {
+            Some(mut letterbox) if Some(letterbox.size()) == resolution => {
+                letterbox.set_mode(mode);
+                Some(letterbox)
+            }
+            Some(letterbox) => {
+                letterbox.delete(self);
+                None
+            }
+            None => None,
+        };
+        self.internal_resolution = match (reused, resolution) {
+            (Some(letterbox), _) => Some(letterbox),
+            (None, Some((width, height))) => {
+                assert!(
+                    width > 0 && height > 0,
+                    "Internal resolution of {}x{}",
+                    width,
+                    height
+                );
+                Some(Letterbox::new(self, width, height, mode))
+            }
+            (None, None) => None,
+        };
+        if let Some(letterbox) = &self.internal_resolution {
+            // Nearest keeps integer scaling crisp, other scales need filtering
+            let filter = match mode {
+                ScaleMode::Integer => FilterMode::Nearest,
+                ScaleMode::Fit | ScaleMode::Stretch => FilterMode::Linear,
+            };
+            self.texture_set_filter(letterbox.texture(), filter, MipmapFilterMode::None);
         }
+        true
     }
 
     fn end_render_pass(&mut self) {
         unsafe {
-            if let Some(pass) = self.cache.cur_pass.take() {
+            let pass = self.cache.cur_pass.take();
+            if let Some(pass) = pass {
                 let pass = &self.passes[pass.0];
                 if let Some(resolves) = &pass.resolves {
                     glBindFramebuffer(GL_READ_FRAMEBUFFER, pass.gl_fb);
@@ -1860,10 +3520,59 @@ impl RenderingBackend for GlContext {
                     }
                 }
             }
-            glBindFramebuffer(GL_FRAMEBUFFER, self.default_framebuffer);
+            if !self.pass_discards.is_empty() {
+                // After the resolves, which read the multisampled attachments
+                let framebuffer = match pass {
+                    Some(pass) => self.passes[pass.0].gl_fb,
+                    None => self.default_pass_target().0,
+                };
+                let discards = std::mem::take(&mut self.pass_discards);
+                glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
+                glInvalidateFramebuffer(GL_FRAMEBUFFER, discards.len() as _, discards.as_ptr());
+            }
+            glBindFramebuffer(GL_FRAMEBUFFER, self.default_pass_target().0);
             self.cache.bind_buffer(GL_ARRAY_BUFFER, 0, None);
             self.cache.bind_buffer(GL_ELEMENT_ARRAY_BUFFER, 0, None);
         }
+        self.gpu_timer.end_pass();
+        SafeGL::set_call_scope(None);
+    }
+
+    fn save_gl_state(&mut self) -> Option<GlState> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let state = unsafe { GlState::save() };
+            self.invalidate_state_cache();
+            Some(state)
+        }
+        #[cfg(target_arch = "wasm32")]
+        None
+    }
+
+    fn restore_gl_state(&mut self, state: &GlState) {
+        #[cfg(not(target_arch = "wasm32"))]
+        unsafe {
+            state.restore()
+        };
+        #[cfg(target_arch = "wasm32")]
+        let _ = state;
+    }
+
+    fn invalidate_state_cache(&mut self) {
+        unsafe {
+            glBindVertexArray(self.vao);
+        }
+        self.cache.invalidate();
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.info.features.primitive_restart {
+            unsafe {
+                if self.info.gl_version_string.contains("OpenGL ES") {
+                    glDisable(GL_PRIMITIVE_RESTART_FIXED_INDEX);
+                } else {
+                    glDisable(GL_PRIMITIVE_RESTART);
+                }
+            }
+        }
     }
 
     fn commit_frame(&mut self) {
@@ -1872,9 +3581,41 @@ impl RenderingBackend for GlContext {
         let _ = cmd_buffer.execute(self);
         self.command_buffer = cmd_buffer;
 
+        // Scaled to the window, the default pass is the window again meanwhile
+        if let Some(letterbox) = self.internal_resolution.take() {
+            letterbox.draw(self);
+            self.internal_resolution = Some(letterbox);
+        }
+        // Mouse and touch events map into the default pass of this context
+        // from now on, as it is the one the window shows
+        crate::event::set_presented_resolution(self.internal_resolution.as_ref().map(
+            |letterbox| {
+                let (width, height) = letterbox.size();
+                (width, height, letterbox.mode())
+            },
+        ));
+
+        // On top of everything, and part of the streamed frame
+        if let Some(mut cursor) = self.software_cursor.take() {
+            cursor.draw(self);
+            self.software_cursor = Some(cursor);
+        }
+
         self.cache.clear_buffer_bindings();
         self.cache.clear_texture_bindings();
 
+        self.frame_stream.end_frame(self.default_framebuffer);
+        self.streaming_pool.end_frame();
+
+        self.gpu_timer.end_frame();
+        self.watchdog.end_frame();
+        super::profiling::flush_state_changes();
+        super::profiling::end_frame();
+
+        if super::buffer_pool::take_memory_pressure() {
+            self.buffer_pool.trim(0);
+        }
+
         // Periodically clean up old unused buffers from the pool
         // This happens approximately every 60 frames at 60fps = once per second
         static mut FRAME_COUNT: u32 = 0;
@@ -1884,23 +3625,62 @@ impl RenderingBackend for GlContext {
                 self.buffer_pool.cleanup_old_buffers();
             }
         }
+
+        let committed = self.frame_index;
+        self.frame_index += 1;
+        for resource in self.deferred_deletes.take_due(committed) {
+            resource.delete(self);
+        }
+    }
+
+    fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    fn delete_buffer_deferred(&mut self, buffer: BufferId, frames: u32) {
+        let resource = super::deferred_delete::Deferred::Buffer(buffer);
+        self.deferred_deletes
+            .push(self.frame_index, frames, resource);
+    }
+
+    fn delete_texture_deferred(&mut self, texture: TextureId, frames: u32) {
+        let resource = super::deferred_delete::Deferred::Texture(texture);
+        self.deferred_deletes
+            .push(self.frame_index, frames, resource);
     }
 
     fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        #[cfg(feature = "validation")]
+        if let Err(err) = validation::validate_draw(
+            self,
+            self.bound.as_ref(),
+            base_element,
+            num_elements,
+            num_instances,
+        ) {
+            crate::error::report_error(err);
+            return;
+        }
+
         assert!(
             self.cache.cur_pipeline.is_some(),
             "Drawing without any binded pipeline"
         );
 
         if !self.info.features.instancing && num_instances != 1 {
-            eprintln!("Instanced rendering is not supported by the GPU");
-            eprintln!("Ignoring this draw call");
+            crate::error::report_error(GraphicsError::Unsupported(
+                "Instanced rendering is not supported by the GPU, ignoring this draw call"
+                    .to_string(),
+            ));
             return;
         }
 
         let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        let triangles = triangle_count(pip.params.primitive_type, num_elements, num_instances);
         let primitive_type = pip.params.primitive_type.into();
-        let index_type = self.cache.index_type.expect("Unset index buffer type");
+        let index_type = self.index_size().expect("Unset index buffer type");
+
+        super::profiling::record_draw_call(triangles);
 
         unsafe {
             glDrawElementsInstanced(
@@ -1917,4 +3697,59 @@ impl RenderingBackend for GlContext {
             );
         }
     }
+
+    fn apply_images(&mut self, images: &[TextureId]) {
+        #[cfg(feature = "validation")]
+        {
+            if let Err(err) = validation::validate_images(self, images) {
+                self.bound = None;
+                crate::error::report_error(err);
+                return;
+            }
+            self.bound = Some(validation::BoundResources::new(&[], None));
+        }
+
+        self.bind_images(images);
+        self.cache.clear_vertex_attributes();
+    }
+
+    fn draw_vertices(&self, base_vertex: i32, num_vertices: i32, num_instances: i32) {
+        #[cfg(feature = "validation")]
+        if let Err(err) = validation::validate_draw_vertices(
+            self,
+            self.bound.as_ref(),
+            base_vertex,
+            num_vertices,
+            num_instances,
+        ) {
+            crate::error::report_error(err);
+            return;
+        }
+
+        assert!(
+            self.cache.cur_pipeline.is_some(),
+            "Drawing without any binded pipeline"
+        );
+
+        if !self.info.features.instancing && num_instances != 1 {
+            crate::error::report_error(GraphicsError::Unsupported(
+                "Instanced rendering is not supported by the GPU, ignoring this draw call"
+                    .to_string(),
+            ));
+            return;
+        }
+
+        let pip = &self.pipelines[self.cache.cur_pipeline.unwrap().0];
+        let triangles = triangle_count(pip.params.primitive_type, num_vertices, num_instances);
+        super::profiling::record_draw_call(triangles);
+
+        unsafe {
+            glDrawArraysInstanced(
+                pip.params.primitive_type.into(),
+                base_vertex,
+                num_vertices,
+                num_instances,
+            );
+        }
+    }
 }
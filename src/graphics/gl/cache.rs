@@ -26,6 +26,17 @@ pub struct CachedTexture {
     pub texture: GLuint,
 }
 
+/// Stencil state of a face after glEnable(GL_STENCIL_TEST) on a fresh context
+const DEFAULT_STENCIL_FACE: StencilFaceState = StencilFaceState {
+    fail_op: StencilOp::Keep,
+    depth_fail_op: StencilOp::Keep,
+    pass_op: StencilOp::Keep,
+    test_func: CompareFunc::Always,
+    test_ref: 0,
+    test_mask: !0,
+    write_mask: !0,
+};
+
 pub struct GlCache {
     pub stored_index_buffer: GLuint,
     pub stored_index_type: Option<u32>,
@@ -36,6 +47,9 @@ pub struct GlCache {
     pub index_type: Option<u32>,
     pub vertex_buffer: GLuint,
     pub textures: [CachedTexture; MAX_SHADERSTAGE_IMAGES],
+    /// When each unit was last handed out by `texture_unit`
+    pub texture_unit_use: [u64; MAX_SHADERSTAGE_IMAGES],
+    pub texture_unit_clock: u64,
     pub cur_pipeline: Option<Pipeline>,
     pub cur_pass: Option<RenderPass>,
     pub color_blend: Option<BlendState>,
@@ -43,11 +57,30 @@ pub struct GlCache {
     pub stencil: Option<StencilState>,
     pub color_write: ColorMask,
     pub cull_face: CullFace,
+    pub depth_test: Option<Comparison>,
+    // Values the state last had in GL, kept while the state is disabled so
+    // enabling it again only emits what differs
+    pub front_face: FrontFaceOrder,
+    pub depth_func: Comparison,
+    pub depth_write: bool,
+    pub cull_face_mode: GLenum,
+    /// src_rgb, dst_rgb, src_alpha, dst_alpha
+    pub blend_func: [GLenum; 4],
+    /// rgb, alpha
+    pub blend_equation: [GLenum; 2],
+    /// front, back
+    pub stencil_faces: [StencilFaceState; 2],
+    pub blend_color: (f32, f32, f32, f32),
+    /// Index size in bytes primitive restart is enabled for
+    pub primitive_restart: Option<u32>,
     pub attributes: [Option<CachedAttribute>; MAX_VERTEX_ATTRIBUTES],
+    /// The part of `textures` and `attributes` the device has
+    pub limits: Limits,
 
     // Enhanced caching for performance optimization
     pub current_program: GLuint,
     pub viewport: (i32, i32, i32, i32),
+    pub depth_range: (f32, f32),
     pub scissor: Option<(i32, i32, i32, i32)>,
 
     // Dirty flags to avoid redundant state changes
@@ -60,9 +93,10 @@ impl GlCache {
     pub fn bind_buffer(&mut self, target: GLenum, buffer: GLuint, index_type: Option<u32>) {
         if target == GL_ARRAY_BUFFER {
             if self.vertex_buffer != buffer {
-                let _ = profiling::get_profiler()
-                    .lock()
-                    .map(|mut p| p.record_buffer_bind(target, buffer));
+                profiling::record_buffer_bind(target, buffer);
+                profiling::record_state_change();
+                #[cfg(feature = "tracy")]
+                profiling::tracy::record_buffer_bind();
                 self.vertex_buffer = buffer;
                 unsafe {
                     glBindBuffer(target, buffer);
@@ -70,9 +104,10 @@ impl GlCache {
             }
         } else {
             if self.index_buffer != buffer {
-                let _ = profiling::get_profiler()
-                    .lock()
-                    .map(|mut p| p.record_buffer_bind(target, buffer));
+                profiling::record_buffer_bind(target, buffer);
+                profiling::record_state_change();
+                #[cfg(feature = "tracy")]
+                profiling::tracy::record_buffer_bind();
                 self.index_buffer = buffer;
                 unsafe {
                     glBindBuffer(target, buffer);
@@ -109,9 +144,10 @@ impl GlCache {
             if self.textures[slot_index].target != target
                 || self.textures[slot_index].texture != texture
             {
-                let _ = profiling::get_profiler()
-                    .lock()
-                    .map(|mut p| p.record_texture_bind(slot_index as u32, texture));
+                profiling::record_texture_bind(slot_index as u32, texture);
+                profiling::record_state_change();
+                #[cfg(feature = "tracy")]
+                profiling::tracy::record_texture_bind();
                 let target = if target == 0 { GL_TEXTURE_2D } else { target };
                 glBindTexture(target, texture);
                 self.textures[slot_index] = CachedTexture { target, texture };
@@ -119,6 +155,41 @@ impl GlCache {
         }
     }
 
+    /// Unit for drawing with `texture`: the one it is already bound to or else the
+    /// least recently used one, which it gets bound to.
+    /// Units with their bit set in `taken` are left alone.
+    pub fn texture_unit(&mut self, target: GLuint, texture: GLuint, taken: u32) -> usize {
+        let free = (0..self.limits.max_images).filter(|unit| taken & (1 << unit) == 0);
+        let resident = free.clone().find(|&unit| {
+            self.textures[unit].texture == texture && self.textures[unit].target == target
+        });
+        let unit = match resident {
+            Some(unit) => unit,
+            None => {
+                let unit = free
+                    .min_by_key(|&unit| self.texture_unit_use[unit])
+                    .expect("More images than the texture units of `Limits::max_images`");
+                self.bind_texture(unit, target, texture);
+                unit
+            }
+        };
+        self.texture_unit_clock += 1;
+        self.texture_unit_use[unit] = self.texture_unit_clock;
+        unit
+    }
+
+    /// Drop a deleted texture from the units it was bound to
+    pub fn forget_texture(&mut self, texture: GLuint) {
+        for cached in self.textures.iter_mut() {
+            if cached.texture == texture {
+                *cached = CachedTexture {
+                    target: 0,
+                    texture: 0,
+                };
+            }
+        }
+    }
+
     pub fn store_texture_binding(&mut self, slot_index: usize) {
         self.stored_target = self.textures[slot_index].target;
         self.stored_texture = self.textures[slot_index].texture;
@@ -137,7 +208,7 @@ impl GlCache {
     }
 
     pub fn clear_texture_bindings(&mut self) {
-        for ix in 0..MAX_SHADERSTAGE_IMAGES {
+        for ix in 0..self.limits.max_images {
             if self.textures[ix].texture != 0 {
                 self.bind_texture(ix, self.textures[ix].target, 0);
                 self.textures[ix] = CachedTexture {
@@ -149,7 +220,7 @@ impl GlCache {
     }
 
     pub fn clear_vertex_attributes(&mut self) {
-        for attr_index in 0..MAX_VERTEX_ATTRIBUTES {
+        for attr_index in 0..self.limits.max_vertex_attributes {
             let cached_attr = &mut self.attributes[attr_index];
 
             if cached_attr.is_some() {
@@ -159,12 +230,247 @@ impl GlCache {
         }
     }
 
+    /// Forget everything cached and put the GL state back to what a default cache
+    /// describes, for when GL calls were made behind miniquad's back.
+    /// The current pass and the device limits are kept, the pipeline and bindings
+    /// have to be applied again.
+    /// Expects miniquad's vertex array object to be bound.
+    pub fn invalidate(&mut self) {
+        unsafe {
+            glBindBuffer(GL_ARRAY_BUFFER, 0);
+            glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, 0);
+            for slot_index in 0..self.limits.max_images {
+                glActiveTexture(GL_TEXTURE0 + slot_index as GLuint);
+                glBindTexture(GL_TEXTURE_2D, 0);
+                glBindTexture(GL_TEXTURE_CUBE_MAP, 0);
+            }
+            for attr_index in 0..self.limits.max_vertex_attributes {
+                glDisableVertexAttribArray(attr_index as GLuint);
+            }
+            glDisable(GL_BLEND);
+            glDisable(GL_STENCIL_TEST);
+            glDisable(GL_CULL_FACE);
+            glDisable(GL_DEPTH_TEST);
+            glDisable(GL_SCISSOR_TEST);
+            depth_range(0., 1.);
+            glColorMask(1, 1, 1, 1);
+            glDepthMask(1);
+            glStencilMask(!0);
+            glFrontFace(GL_CCW);
+            glDepthFunc(GL_LESS);
+            glCullFace(GL_BACK);
+            glBlendFuncSeparate(GL_ONE, GL_ZERO, GL_ONE, GL_ZERO);
+            glBlendEquationSeparate(GL_FUNC_ADD, GL_FUNC_ADD);
+            glBlendColor(0., 0., 0., 0.);
+            glStencilFuncSeparate(GL_FRONT_AND_BACK, GL_ALWAYS, 0, !0);
+            glStencilOpSeparate(GL_FRONT_AND_BACK, GL_KEEP, GL_KEEP, GL_KEEP);
+        }
+
+        *self = GlCache {
+            cur_pass: self.cur_pass,
+            limits: self.limits,
+            ..GlCache::default()
+        };
+    }
+
+    pub fn set_front_face(&mut self, front_face: FrontFaceOrder) {
+        if self.front_face == front_face {
+            return;
+        }
+        profiling::record_state_change();
+        let mode = match front_face {
+            FrontFaceOrder::Clockwise => GL_CW,
+            FrontFaceOrder::CounterClockwise => GL_CCW,
+        };
+        unsafe { glFrontFace(mode) };
+        self.front_face = front_face;
+    }
+
+    /// None disables the depth test
+    pub fn set_depth_test(&mut self, depth_test: Option<Comparison>) {
+        unsafe {
+            match depth_test {
+                Some(func) => {
+                    if self.depth_test.is_none() {
+                        profiling::record_state_change();
+                        glEnable(GL_DEPTH_TEST);
+                    }
+                    if self.depth_func != func {
+                        profiling::record_state_change();
+                        glDepthFunc(func.into());
+                        self.depth_func = func;
+                    }
+                }
+                None if self.depth_test.is_some() => {
+                    profiling::record_state_change();
+                    glDisable(GL_DEPTH_TEST);
+                }
+                None => {}
+            }
+        }
+        self.depth_test = depth_test;
+    }
+
+    pub fn set_depth_write(&mut self, depth_write: bool) {
+        if self.depth_write == depth_write {
+            return;
+        }
+        profiling::record_state_change();
+        unsafe { glDepthMask(depth_write as _) };
+        self.depth_write = depth_write;
+    }
+
+    pub fn set_cull_face(&mut self, cull_face: CullFace) {
+        let mode = match cull_face {
+            CullFace::Nothing => None,
+            CullFace::Front => Some(GL_FRONT),
+            CullFace::Back => Some(GL_BACK),
+        };
+        unsafe {
+            match mode {
+                Some(mode) => {
+                    if self.cull_face == CullFace::Nothing {
+                        profiling::record_state_change();
+                        glEnable(GL_CULL_FACE);
+                    }
+                    if self.cull_face_mode != mode {
+                        profiling::record_state_change();
+                        glCullFace(mode);
+                        self.cull_face_mode = mode;
+                    }
+                }
+                None if self.cull_face != CullFace::Nothing => {
+                    profiling::record_state_change();
+                    glDisable(GL_CULL_FACE);
+                }
+                None => {}
+            }
+        }
+        self.cull_face = cull_face;
+    }
+
+    /// Without `alpha_blend` alpha is blended like the color
+    pub fn set_blend(&mut self, color_blend: Option<BlendState>, alpha_blend: Option<BlendState>) {
+        unsafe {
+            match color_blend {
+                Some(color) => {
+                    if self.color_blend.is_none() {
+                        profiling::record_state_change();
+                        glEnable(GL_BLEND);
+                    }
+                    let alpha = alpha_blend.unwrap_or(color);
+                    let func = [
+                        color.sfactor.into(),
+                        color.dfactor.into(),
+                        alpha.sfactor.into(),
+                        alpha.dfactor.into(),
+                    ];
+                    if self.blend_func != func {
+                        profiling::record_state_change();
+                        glBlendFuncSeparate(func[0], func[1], func[2], func[3]);
+                        self.blend_func = func;
+                    }
+                    let equation = [color.equation.into(), alpha.equation.into()];
+                    if self.blend_equation != equation {
+                        profiling::record_state_change();
+                        glBlendEquationSeparate(equation[0], equation[1]);
+                        self.blend_equation = equation;
+                    }
+                }
+                None if self.color_blend.is_some() => {
+                    profiling::record_state_change();
+                    glDisable(GL_BLEND);
+                }
+                None => {}
+            }
+        }
+        self.color_blend = color_blend;
+        self.alpha_blend = alpha_blend;
+    }
+
+    /// None disables the stencil test
+    pub fn set_stencil(&mut self, stencil: Option<StencilState>) {
+        match stencil {
+            Some(stencil) => {
+                if self.stencil.is_none() {
+                    profiling::record_state_change();
+                    unsafe { glEnable(GL_STENCIL_TEST) };
+                }
+                self.set_stencil_face(0, stencil.front);
+                self.set_stencil_face(1, stencil.back);
+            }
+            None if self.stencil.is_some() => {
+                profiling::record_state_change();
+                unsafe { glDisable(GL_STENCIL_TEST) };
+            }
+            None => {}
+        }
+        self.stencil = stencil;
+    }
+
+    /// Operations, function and write mask of the front (0) or back (1) face,
+    /// regardless of the stencil test being enabled
+    pub fn set_stencil_face(&mut self, index: usize, state: StencilFaceState) {
+        let cached = self.stencil_faces[index];
+        let face = [GL_FRONT, GL_BACK][index];
+        unsafe {
+            let ops = (state.fail_op, state.depth_fail_op, state.pass_op);
+            if (cached.fail_op, cached.depth_fail_op, cached.pass_op) != ops {
+                profiling::record_state_change();
+                glStencilOpSeparate(face, ops.0.into(), ops.1.into(), ops.2.into());
+            }
+            let func = (state.test_func, state.test_ref, state.test_mask);
+            if (cached.test_func, cached.test_ref, cached.test_mask) != func {
+                profiling::record_state_change();
+                glStencilFuncSeparate(face, func.0.into(), func.1, func.2);
+            }
+            if cached.write_mask != state.write_mask {
+                profiling::record_state_change();
+                glStencilMaskSeparate(face, state.write_mask);
+            }
+        }
+        self.stencil_faces[index] = state;
+    }
+
+    /// `test_ref` of both faces, the rest of their state kept
+    pub fn set_stencil_reference(&mut self, reference: i32) {
+        for index in 0..2 {
+            let face = StencilFaceState {
+                test_ref: reference,
+                ..self.stencil_faces[index]
+            };
+            self.set_stencil_face(index, face);
+        }
+    }
+
+    /// `test_mask` of both faces, the rest of their state kept
+    pub fn set_stencil_compare_mask(&mut self, mask: u32) {
+        for index in 0..2 {
+            let face = StencilFaceState {
+                test_mask: mask,
+                ..self.stencil_faces[index]
+            };
+            self.set_stencil_face(index, face);
+        }
+    }
+
+    pub fn set_color_write(&mut self, color_write: ColorMask) {
+        if self.color_write == color_write {
+            return;
+        }
+        profiling::record_state_change();
+        let (r, g, b, a) = color_write;
+        unsafe { glColorMask(r as _, g as _, b as _, a as _) };
+        self.color_write = color_write;
+    }
+
     /// Enhanced program caching with profiling
     pub fn use_program(&mut self, program: GLuint) {
         if self.current_program != program || self.program_dirty {
-            let _ = profiling::get_profiler()
-                .lock()
-                .map(|mut p| p.record_program_use(program));
+            profiling::record_program_use(program);
+            profiling::record_state_change();
+            #[cfg(feature = "tracy")]
+            profiling::tracy::record_program_use();
             self.current_program = program;
             self.program_dirty = false;
             unsafe {
@@ -185,20 +491,108 @@ impl GlCache {
         }
     }
 
-    /// Enhanced scissor caching
-    pub fn apply_scissor(&mut self, x: i32, y: i32, w: i32, h: i32) {
-        let new_scissor = Some((x, y, w, h));
-        if self.scissor != new_scissor || self.scissor_dirty {
-            self.scissor = new_scissor;
+    /// Viewport with sub-pixel coordinates, needs glViewportIndexedf (GL4.1) or gets rounded.
+    /// Not cached, the next `apply_viewport` always reaches GL.
+    pub fn apply_viewport_f(&mut self, x: f32, y: f32, w: f32, h: f32) {
+        self.viewport_dirty = true;
+        #[cfg(not(target_arch = "wasm32"))]
+        if is_gl_func_loaded("glViewportIndexedf") {
+            unsafe { glViewportIndexedf(0, x, y, w, h) };
+            return;
+        }
+        unsafe {
+            glViewport(
+                x.round() as i32,
+                y.round() as i32,
+                w.round() as i32,
+                h.round() as i32,
+            );
+        }
+    }
+
+    pub fn apply_depth_range(&mut self, near: f32, far: f32) {
+        if self.depth_range != (near, far) {
+            self.depth_range = (near, far);
+            unsafe { depth_range(near, far) };
+        }
+    }
+
+    pub fn set_blend_color(&mut self, color: (f32, f32, f32, f32)) {
+        if self.blend_color != color {
+            profiling::record_state_change();
+            unsafe { glBlendColor(color.0, color.1, color.2, color.3) };
+            self.blend_color = color;
+        }
+    }
+
+    /// Put the state `reset` asks for back to the defaults of a `w` x `h` pass.
+    /// With `bypass_cache` the cached values are assumed stale and GL gets them
+    /// all again.
+    pub fn reset_pass_state(&mut self, reset: PassStateReset, w: i32, h: i32) {
+        if reset.viewport {
+            if reset.bypass_cache {
+                self.viewport_dirty = true;
+                self.depth_range = (f32::NAN, f32::NAN);
+            }
+            self.apply_viewport(0, 0, w, h);
+            self.apply_depth_range(0., 1.);
+        }
+        if reset.scissor {
+            self.scissor_dirty |= reset.bypass_cache;
+            self.apply_scissor(None);
+        }
+        if reset.stencil_reference {
+            for index in 0..2 {
+                let cached = self.stencil_faces[index];
+                if reset.bypass_cache {
+                    // Anything but 0, so the function below reaches GL
+                    self.stencil_faces[index].test_ref = !0;
+                }
+                self.set_stencil_face(
+                    index,
+                    StencilFaceState {
+                        test_ref: 0,
+                        ..cached
+                    },
+                );
+            }
+        }
+        if reset.blend_color {
+            if reset.bypass_cache {
+                self.blend_color = (f32::NAN, f32::NAN, f32::NAN, f32::NAN);
+            }
+            self.set_blend_color((0., 0., 0., 0.));
+        }
+    }
+
+    /// Enhanced scissor caching, None disables the scissor test
+    pub fn apply_scissor(&mut self, scissor: Option<(i32, i32, i32, i32)>) {
+        if self.scissor != scissor || self.scissor_dirty {
+            self.scissor = scissor;
             self.scissor_dirty = false;
             unsafe {
-                glEnable(GL_SCISSOR_TEST);
-                glScissor(x, y, w, h);
+                match scissor {
+                    Some((x, y, w, h)) => {
+                        glEnable(GL_SCISSOR_TEST);
+                        glScissor(x, y, w, h);
+                    }
+                    None => glDisable(GL_SCISSOR_TEST),
+                }
             }
         }
     }
 }
 
+/// glDepthRangef is GLES and GL4.1+, older desktop GL only has the double version
+unsafe fn depth_range(near: f32, far: f32) {
+    #[cfg(not(target_arch = "wasm32"))]
+    if !is_gl_func_loaded("glDepthRangef") {
+        glDepthRange(near as f64, far as f64);
+        return;
+    }
+    glDepthRangef(near, far);
+}
+
 impl Default for GlCache {
     fn default() -> Self {
         Self {
@@ -214,6 +608,8 @@ impl Default for GlCache {
                 target: 0,
                 texture: 0,
             }; MAX_SHADERSTAGE_IMAGES],
+            texture_unit_use: [0; MAX_SHADERSTAGE_IMAGES],
+            texture_unit_clock: 0,
             cur_pipeline: None,
             cur_pass: None,
             color_blend: None,
@@ -221,11 +617,23 @@ impl Default for GlCache {
             stencil: None,
             color_write: (true, true, true, true),
             cull_face: CullFace::Nothing,
+            depth_test: None,
+            front_face: FrontFaceOrder::CounterClockwise,
+            depth_func: Comparison::Less,
+            depth_write: true,
+            cull_face_mode: GL_BACK,
+            blend_func: [GL_ONE, GL_ZERO, GL_ONE, GL_ZERO],
+            blend_equation: [GL_FUNC_ADD, GL_FUNC_ADD],
+            stencil_faces: [DEFAULT_STENCIL_FACE; 2],
+            blend_color: (0., 0., 0., 0.),
+            primitive_restart: None,
             attributes: [None; MAX_VERTEX_ATTRIBUTES],
+            limits: Limits::default(),
 
             // Enhanced caching state
             current_program: 0,
             viewport: (0, 0, 0, 0),
+            depth_range: (0., 1.),
             scissor: None,
 
             // All dirty on init to force first setup
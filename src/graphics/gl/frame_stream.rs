@@ -0,0 +1,144 @@
+use crate::graphics::frame_stream::{CapturedFrame, FrameCallback};
+use crate::graphics::*;
+
+/// A pixel buffer object and the frame read into it
+#[derive(Debug, Default)]
+struct Readback {
+    pbo: GLuint,
+    capacity: usize,
+    /// Frame index, width and height of the pending read
+    frame: Option<(u64, u32, u32)>,
+}
+
+/// Double-buffered asynchronous readback of the default framebuffer,
+/// see `frame_stream`
+pub struct FrameStream {
+    supported: bool,
+    callback: Option<FrameCallback>,
+    readbacks: [Readback; 2],
+    next: usize,
+    frame: u64,
+}
+
+impl std::fmt::Debug for FrameStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("FrameStream")
+            .field("supported", &self.supported)
+            .field("active", &self.callback.is_some())
+            .field("frame", &self.frame)
+            .finish()
+    }
+}
+
+impl FrameStream {
+    pub fn new(info: &ContextInfo) -> FrameStream {
+        // Pixel pack buffers are GL3 and GLES3, WebGL2 only reads them back
+        // asynchronously through getBufferSubData
+        #[cfg(not(target_arch = "wasm32"))]
+        let supported = is_gl_func_loaded("glMapBufferRange")
+            && (info.glsl_support.v130 || info.glsl_support.v300es);
+        #[cfg(target_arch = "wasm32")]
+        let supported = {
+            let _ = info;
+            false
+        };
+        FrameStream {
+            supported,
+            callback: None,
+            readbacks: Default::default(),
+            next: 0,
+            frame: 0,
+        }
+    }
+
+    /// Start or stop the stream, false if pixel pack buffers are not supported
+    pub fn set_callback(&mut self, callback: Option<FrameCallback>) -> bool {
+        if !self.supported {
+            return false;
+        }
+        if callback.is_none() {
+            for readback in &mut self.readbacks {
+                if readback.pbo != 0 {
+                    unsafe { glDeleteBuffers(1, &readback.pbo) };
+                }
+                *readback = Readback::default();
+            }
+        } else if self.callback.is_none() {
+            self.frame = 0;
+        }
+        self.callback = callback;
+        true
+    }
+
+    /// Read the frame just drawn into `framebuffer` and hand over the previous one.
+    /// Called in `commit_frame`, outside of any pass.
+    pub fn end_frame(&mut self, framebuffer: GLuint) {
+        if self.callback.is_none() {
+            return;
+        }
+        let (width, height) = crate::window::screen_size();
+        let (width, height) = (width as u32, height as u32);
+        let size = width as usize * height as usize * 4;
+        if size == 0 {
+            // Minimized
+            return;
+        }
+
+        unsafe {
+            let readback = &mut self.readbacks[self.next];
+            if readback.pbo == 0 {
+                glGenBuffers(1, &mut readback.pbo);
+            }
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, readback.pbo);
+            if readback.capacity != size {
+                glBufferData(
+                    GL_PIXEL_PACK_BUFFER,
+                    size as _,
+                    std::ptr::null(),
+                    GL_STREAM_READ,
+                );
+                readback.capacity = size;
+            }
+            // Rows of RGBA8 are always 4 bytes aligned, GL_PACK_ALIGNMENT can stay
+            glBindFramebuffer(GL_FRAMEBUFFER, framebuffer);
+            glReadPixels(
+                0,
+                0,
+                width as _,
+                height as _,
+                GL_RGBA,
+                GL_UNSIGNED_BYTE,
+                std::ptr::null_mut(),
+            );
+            readback.frame = Some((self.frame, width, height));
+            self.frame += 1;
+
+            self.next = 1 - self.next;
+            let previous = &mut self.readbacks[self.next];
+            if let Some((index, width, height)) = previous.frame.take() {
+                let row = width as usize * 4;
+                let len = row * height as usize;
+                glBindBuffer(GL_PIXEL_PACK_BUFFER, previous.pbo);
+                let ptr = glMapBufferRange(GL_PIXEL_PACK_BUFFER, 0, len as _, GL_MAP_READ_BIT);
+                if !ptr.is_null() {
+                    let data = std::slice::from_raw_parts(ptr as *const u8, len);
+                    // GL rows go bottom to top
+                    let mut pixels = Vec::with_capacity(len);
+                    for y in (0..height as usize).rev() {
+                        pixels.extend_from_slice(&data[y * row..(y + 1) * row]);
+                    }
+                    glUnmapBuffer(GL_PIXEL_PACK_BUFFER);
+                    if let Some(callback) = &mut self.callback {
+                        callback(CapturedFrame {
+                            index,
+                            width,
+                            height,
+                            pixels,
+                        });
+                    }
+                }
+            }
+            glBindBuffer(GL_PIXEL_PACK_BUFFER, 0);
+        }
+    }
+}
@@ -0,0 +1,293 @@
+use crate::graphics::profiling;
+use crate::graphics::*;
+
+use std::collections::VecDeque;
+
+/// Upper bound on queries waiting for results, in case the driver never reports them
+const MAX_PENDING_QUERIES: usize = 64;
+
+#[derive(Debug)]
+struct PassQuery {
+    frame: u64,
+    label: String,
+    query: GLuint,
+}
+
+/// GL_TIME_ELAPSED queries around render passes, feeding the frame profiler,
+/// and GL_TIMESTAMP queries around the whole frame for `window::frame_stats`.
+/// While profiling, passes are also wrapped in debug groups named after the
+/// pass label, for RenderDoc and the like.
+#[derive(Debug)]
+pub struct GpuPassTimer {
+    supported: bool,
+    /// glPushDebugGroup is available, GL4.3 or KHR_debug
+    debug_groups: bool,
+    debug_group_open: bool,
+    free: Vec<GLuint>,
+    active: Option<PassQuery>,
+    pending: VecDeque<PassQuery>,
+    // Timestamp queries, a query object can't change its target once used
+    free_timestamps: Vec<GLuint>,
+    frame_start: Option<GLuint>,
+    pending_frames: VecDeque<(GLuint, GLuint)>,
+    #[cfg(feature = "tracy")]
+    tracy: TracyGpuZones,
+}
+
+impl GpuPassTimer {
+    pub fn new(info: &ContextInfo) -> GpuPassTimer {
+        // Timer queries are core since GL3.3, GLES and WebGL need an extension
+        // with different entry points, so they are not measured for now.
+        let supported = info.glsl_support.v330 && !info.gl_version_string.contains("OpenGL ES");
+        #[cfg(not(target_arch = "wasm32"))]
+        let debug_groups = is_gl_func_loaded("glPushDebugGroup");
+        #[cfg(target_arch = "wasm32")]
+        let debug_groups = false;
+        GpuPassTimer {
+            supported,
+            debug_groups,
+            debug_group_open: false,
+            free: vec![],
+            active: None,
+            pending: VecDeque::new(),
+            free_timestamps: vec![],
+            frame_start: None,
+            pending_frames: VecDeque::new(),
+            #[cfg(feature = "tracy")]
+            tracy: TracyGpuZones::default(),
+        }
+    }
+
+    fn timestamp(&mut self) -> GLuint {
+        let query = self.free_timestamps.pop().unwrap_or_else(|| {
+            let mut query = 0;
+            unsafe { glGenQueries(1, &mut query) };
+            query
+        });
+        unsafe { glQueryCounter(query, GL_TIMESTAMP) };
+        query
+    }
+
+    fn enabled(&self) -> bool {
+        self.supported && profiling::is_frame_profiler_enabled()
+    }
+
+    pub fn begin_pass(&mut self, pass: Option<RenderPass>) {
+        if self.supported && self.frame_start.is_none() {
+            self.frame_start = Some(self.timestamp());
+        }
+
+        // The label takes a lock on the pass labels, only build it when used
+        if !profiling::is_frame_profiler_enabled() && !cfg!(feature = "tracy") {
+            return;
+        }
+        let label = profiling::render_pass_label(pass);
+
+        if self.debug_groups && !self.debug_group_open && profiling::is_frame_profiler_enabled() {
+            #[cfg(not(target_arch = "wasm32"))]
+            unsafe {
+                glPushDebugGroup(
+                    GL_DEBUG_SOURCE_APPLICATION,
+                    0,
+                    label.len() as GLsizei,
+                    label.as_ptr() as *const GLchar,
+                )
+            };
+            self.debug_group_open = true;
+        }
+
+        #[cfg(feature = "tracy")]
+        {
+            if self.supported {
+                self.tracy.begin_pass(&label);
+            }
+        }
+
+        if !self.enabled() || self.active.is_some() {
+            return;
+        }
+        let query = self.free.pop().unwrap_or_else(|| {
+            let mut query = 0;
+            unsafe { glGenQueries(1, &mut query) };
+            query
+        });
+        unsafe { glBeginQuery(GL_TIME_ELAPSED, query) };
+        self.active = Some(PassQuery {
+            frame: profiling::current_frame_index(),
+            label,
+            query,
+        });
+    }
+
+    pub fn end_pass(&mut self) {
+        if self.debug_group_open {
+            #[cfg(not(target_arch = "wasm32"))]
+            unsafe {
+                glPopDebugGroup()
+            };
+            self.debug_group_open = false;
+        }
+
+        #[cfg(feature = "tracy")]
+        self.tracy.end_pass();
+
+        if let Some(query) = self.active.take() {
+            unsafe { glEndQuery(GL_TIME_ELAPSED) };
+            self.pending.push_back(query);
+        }
+    }
+
+    /// Hand over all the results the GPU already has
+    pub fn end_frame(&mut self) {
+        #[cfg(feature = "tracy")]
+        self.tracy.end_frame();
+
+        while let Some(query) = self.pending.front() {
+            let mut available: GLint = 0;
+            unsafe { glGetQueryObjectiv(query.query, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+            if available == 0 && self.pending.len() <= MAX_PENDING_QUERIES {
+                break;
+            }
+            let query = self.pending.pop_front().unwrap();
+            if available != 0 {
+                let mut time: GLuint64 = 0;
+                unsafe { glGetQueryObjectui64v(query.query, GL_QUERY_RESULT, &mut time) };
+                profiling::record_gpu_pass(query.frame, query.label, time as f64 / 1_000_000.0);
+            }
+            self.free.push(query.query);
+        }
+
+        if let Some(start) = self.frame_start.take() {
+            let end = self.timestamp();
+            self.pending_frames.push_back((start, end));
+        }
+        while let Some(&(start, end)) = self.pending_frames.front() {
+            let mut available: GLint = 0;
+            unsafe { glGetQueryObjectiv(end, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+            if available == 0 && self.pending_frames.len() <= MAX_PENDING_QUERIES {
+                break;
+            }
+            self.pending_frames.pop_front();
+            if available != 0 {
+                let mut start_time: GLuint64 = 0;
+                let mut end_time: GLuint64 = 0;
+                unsafe {
+                    glGetQueryObjectui64v(start, GL_QUERY_RESULT, &mut start_time);
+                    glGetQueryObjectui64v(end, GL_QUERY_RESULT, &mut end_time);
+                }
+                let time = end_time.saturating_sub(start_time);
+                crate::frame_stats::record_gpu_time(time as f64 / 1_000_000.0);
+            }
+            self.free_timestamps.push(start);
+            self.free_timestamps.push(end);
+        }
+    }
+}
+
+#[cfg(feature = "tracy")]
+struct TracyPass {
+    span: tracy_client::GpuSpan,
+    start: GLuint,
+    end: GLuint,
+}
+
+/// GL_TIMESTAMP queries around render passes, reported to a Tracy GPU context.
+/// Kept apart from the GL_TIME_ELAPSED queries: a query object can't change
+/// its target once used.
+#[cfg(feature = "tracy")]
+#[derive(Default)]
+struct TracyGpuZones {
+    context: Option<tracy_client::GpuContext>,
+    free: Vec<GLuint>,
+    active: Option<TracyPass>,
+    pending: VecDeque<TracyPass>,
+}
+
+#[cfg(feature = "tracy")]
+impl std::fmt::Debug for TracyGpuZones {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TracyGpuZones")
+            .field("pending", &self.pending.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "tracy")]
+impl TracyGpuZones {
+    fn context(&mut self) -> Option<&tracy_client::GpuContext> {
+        if self.context.is_none() {
+            let client = tracy_client::Client::running()?;
+            let mut timestamp: GLint64 = 0;
+            unsafe { glGetInteger64v(GL_TIMESTAMP, &mut timestamp) };
+            self.context = client
+                .new_gpu_context(
+                    Some("miniquad"),
+                    tracy_client::GpuContextType::OpenGL,
+                    timestamp,
+                    1.0,
+                )
+                .ok();
+        }
+        self.context.as_ref()
+    }
+
+    fn timestamp(&mut self) -> GLuint {
+        let query = self.free.pop().unwrap_or_else(|| {
+            let mut query = 0;
+            unsafe { glGenQueries(1, &mut query) };
+            query
+        });
+        unsafe { glQueryCounter(query, GL_TIMESTAMP) };
+        query
+    }
+
+    fn begin_pass(&mut self, label: &str) {
+        if self.active.is_some() {
+            return;
+        }
+        let span = match self
+            .context()
+            .map(|context| context.span_alloc(label, "", "", 0))
+        {
+            Some(Ok(span)) => span,
+            _ => return,
+        };
+        let start = self.timestamp();
+        self.active = Some(TracyPass {
+            span,
+            start,
+            end: 0,
+        });
+    }
+
+    fn end_pass(&mut self) {
+        if let Some(mut pass) = self.active.take() {
+            pass.end = self.timestamp();
+            pass.span.end_zone();
+            self.pending.push_back(pass);
+        }
+    }
+
+    fn end_frame(&mut self) {
+        while let Some(pass) = self.pending.front() {
+            let mut available: GLint = 0;
+            unsafe { glGetQueryObjectiv(pass.end, GL_QUERY_RESULT_AVAILABLE, &mut available) };
+            if available == 0 && self.pending.len() <= MAX_PENDING_QUERIES {
+                break;
+            }
+            let pass = self.pending.pop_front().unwrap();
+            if available != 0 {
+                let mut start: GLuint64 = 0;
+                let mut end: GLuint64 = 0;
+                unsafe {
+                    glGetQueryObjectui64v(pass.start, GL_QUERY_RESULT, &mut start);
+                    glGetQueryObjectui64v(pass.end, GL_QUERY_RESULT, &mut end);
+                }
+                pass.span.upload_timestamp_start(start as i64);
+                pass.span.upload_timestamp_end(end as i64);
+            }
+            self.free.push(pass.start);
+            self.free.push(pass.end);
+        }
+    }
+}
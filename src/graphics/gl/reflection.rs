@@ -0,0 +1,249 @@
+//! Comparison of the shader meta and the pipeline attributes with the active
+//! uniforms and attributes of a linked program.
+//!
+//! The uniforms struct is read with the offsets the layout implies, so a `Float4`
+//! declared for a `vec3` padded like a Rust or std140 struct shifts every
+//! following uniform and the shader silently gets garbage. A misspelled vertex
+//! attribute is not fed at all, and the draw calls render nothing. The program
+//! knows better: `glGetActiveUniform` and `glGetActiveAttrib` give the name,
+//! type and array size of everything it uses.
+
+use super::*;
+
+/// GLSL name of a uniform or attribute type, None for samplers and images,
+/// which are not part of the uniforms struct
+fn glsl_type(type_: GLenum) -> Option<&'static str> {
+    Some(match type_ {
+        GL_FLOAT => "float",
+        GL_FLOAT_VEC2 => "vec2",
+        GL_FLOAT_VEC3 => "vec3",
+        GL_FLOAT_VEC4 => "vec4",
+        GL_INT => "int",
+        GL_INT_VEC2 => "ivec2",
+        GL_INT_VEC3 => "ivec3",
+        GL_INT_VEC4 => "ivec4",
+        GL_UNSIGNED_INT => "uint",
+        GL_UNSIGNED_INT_VEC2 => "uvec2",
+        GL_UNSIGNED_INT_VEC3 => "uvec3",
+        GL_UNSIGNED_INT_VEC4 => "uvec4",
+        GL_BOOL => "bool",
+        GL_BOOL_VEC2 => "bvec2",
+        GL_BOOL_VEC3 => "bvec3",
+        GL_BOOL_VEC4 => "bvec4",
+        GL_FLOAT_MAT2 => "mat2",
+        GL_FLOAT_MAT3 => "mat3",
+        GL_FLOAT_MAT4 => "mat4",
+        _ => return None,
+    })
+}
+
+/// Whether `apply_uniforms` uploads `uniform_type` in a way the GL accepts for
+/// a uniform of GL type `type_`
+fn compatible(uniform_type: UniformType, type_: GLenum) -> bool {
+    match uniform_type {
+        UniformType::Float1 => type_ == GL_FLOAT || type_ == GL_BOOL,
+        UniformType::Float2 => type_ == GL_FLOAT_VEC2,
+        UniformType::Float3 => type_ == GL_FLOAT_VEC3,
+        UniformType::Float4 => type_ == GL_FLOAT_VEC4,
+        UniformType::Int1 | UniformType::Bool => type_ == GL_INT || type_ == GL_BOOL,
+        UniformType::Int2 => type_ == GL_INT_VEC2,
+        UniformType::Int3 => type_ == GL_INT_VEC3,
+        UniformType::Int4 => type_ == GL_INT_VEC4,
+        UniformType::Mat3 => type_ == GL_FLOAT_MAT3,
+        UniformType::Mat4 => type_ == GL_FLOAT_MAT4,
+    }
+}
+
+fn declaration(type_name: &str, array_count: usize) -> String {
+    if array_count == 1 {
+        type_name.to_string()
+    } else {
+        format!("{}[{}]", type_name, array_count)
+    }
+}
+
+/// The uniforms of `program` `layout` gets wrong
+pub(super) fn uniform_mismatches(
+    program: GLuint,
+    layout: &UniformBlockLayout,
+) -> Vec<UniformMismatch> {
+    let offsets: Vec<usize> = layout
+        .uniforms
+        .iter()
+        .scan(0, |offset, uniform| {
+            let res = *offset;
+            *offset += uniform.size();
+            Some(res)
+        })
+        .collect();
+
+    let mut active = 0;
+    unsafe { glGetProgramiv(program, GL_ACTIVE_UNIFORMS, &mut active) };
+
+    let mut mismatches = vec![];
+    for index in 0..active.max(0) as GLuint {
+        let mut name = [0u8; 256];
+        let mut length = 0;
+        let mut size = 0;
+        let mut type_ = 0;
+        unsafe {
+            glGetActiveUniform(
+                program,
+                index,
+                name.len() as _,
+                &mut length,
+                &mut size,
+                &mut type_,
+                name.as_mut_ptr() as *mut _,
+            );
+        }
+        let Some(type_name) = glsl_type(type_) else {
+            continue;
+        };
+        let name = String::from_utf8_lossy(&name[..length.clamp(0, 255) as usize]);
+        // Arrays are reported as `name[0]`
+        let name = name.strip_suffix("[0]").unwrap_or(&name);
+        let size = size.max(1) as usize;
+
+        let Some(i) = layout.uniforms.iter().position(|u| u.name == name) else {
+            mismatches.push(UniformMismatch {
+                name: name.to_string(),
+                offset: None,
+                layout: None,
+                program: declaration(type_name, size),
+            });
+            continue;
+        };
+        let uniform = &layout.uniforms[i];
+        // The program may report a shorter array when the last elements are unused
+        if !compatible(uniform.uniform_type, type_) || uniform.array_count < size {
+            mismatches.push(UniformMismatch {
+                name: name.to_string(),
+                offset: Some(offsets[i]),
+                layout: Some(declaration(
+                    &format!("{:?}", uniform.uniform_type),
+                    uniform.array_count,
+                )),
+                program: declaration(type_name, size),
+            });
+        }
+    }
+    mismatches.sort_by_key(|m| m.offset.unwrap_or(usize::MAX));
+    mismatches
+}
+
+/// Texture target and GLSL name of a sampler type, None for the other types
+#[cfg(debug_assertions)]
+fn sampler_type(type_: GLenum) -> Option<(GLenum, &'static str)> {
+    Some(match type_ {
+        GL_SAMPLER_2D => (GL_TEXTURE_2D, "sampler2D"),
+        GL_SAMPLER_2D_SHADOW => (GL_TEXTURE_2D, "sampler2DShadow"),
+        GL_INT_SAMPLER_2D => (GL_TEXTURE_2D, "isampler2D"),
+        GL_UNSIGNED_INT_SAMPLER_2D => (GL_TEXTURE_2D, "usampler2D"),
+        GL_SAMPLER_CUBE => (GL_TEXTURE_CUBE_MAP, "samplerCube"),
+        GL_SAMPLER_2D_MULTISAMPLE => (GL_TEXTURE_2D_MULTISAMPLE, "sampler2DMS"),
+        GL_SAMPLER_EXTERNAL_OES => (GL_TEXTURE_EXTERNAL_OES, "samplerExternalOES"),
+        _ => return None,
+    })
+}
+
+/// The active samplers of `program` with their texture target and GLSL type
+#[cfg(debug_assertions)]
+pub(super) fn active_samplers(program: GLuint) -> Vec<(String, GLenum, &'static str)> {
+    let mut active = 0;
+    unsafe { glGetProgramiv(program, GL_ACTIVE_UNIFORMS, &mut active) };
+
+    let mut samplers = vec![];
+    for index in 0..active.max(0) as GLuint {
+        let mut name = [0u8; 256];
+        let mut length = 0;
+        let mut size = 0;
+        let mut type_ = 0;
+        unsafe {
+            glGetActiveUniform(
+                program,
+                index,
+                name.len() as _,
+                &mut length,
+                &mut size,
+                &mut type_,
+                name.as_mut_ptr() as *mut _,
+            );
+        }
+        let Some((target, type_name)) = sampler_type(type_) else {
+            continue;
+        };
+        let name = String::from_utf8_lossy(&name[..length.clamp(0, 255) as usize]);
+        // Arrays are reported as `name[0]`
+        let name = name.strip_suffix("[0]").unwrap_or(&name);
+        samplers.push((name.to_string(), target, type_name));
+    }
+    samplers
+}
+
+/// The active attributes of `program`, as GLSL declarations: `vec3 in_pos`
+pub(super) fn active_attributes(program: GLuint) -> Vec<(String, String)> {
+    let mut active = 0;
+    unsafe { glGetProgramiv(program, GL_ACTIVE_ATTRIBUTES, &mut active) };
+
+    let mut attributes = vec![];
+    for index in 0..active.max(0) as GLuint {
+        let mut name = [0u8; 256];
+        let mut length = 0;
+        let mut size = 0;
+        let mut type_ = 0;
+        unsafe {
+            glGetActiveAttrib(
+                program,
+                index,
+                name.len() as _,
+                &mut length,
+                &mut size,
+                &mut type_,
+                name.as_mut_ptr() as *mut _,
+            );
+        }
+        let name = String::from_utf8_lossy(&name[..length.clamp(0, 255) as usize]);
+        // Some drivers list the built-ins, like `gl_VertexID`
+        if name.starts_with("gl_") {
+            continue;
+        }
+        let type_name = glsl_type(type_).map_or_else(|| format!("0x{:X}", type_), String::from);
+        attributes.push((name.into_owned(), type_name));
+    }
+    attributes.sort();
+    attributes
+}
+
+/// Compare the attributes of a pipeline with the active ones of its program,
+/// from `active_attributes`
+pub(super) fn attribute_mismatch(
+    attributes: &[VertexAttribute],
+    active: &[(String, String)],
+) -> Option<AttributeMismatch> {
+    let unknown: Vec<String> = attributes
+        .iter()
+        .filter(|attribute| !active.iter().any(|(name, _)| name == attribute.name))
+        .map(|attribute| attribute.name.to_string())
+        .collect();
+    let missing: Vec<String> = active
+        .iter()
+        .filter(|(name, _)| !attributes.iter().any(|attribute| attribute.name == name))
+        .map(|(name, type_name)| format!("{} {}", type_name, name))
+        .collect();
+    if unknown.is_empty() && missing.is_empty() {
+        return None;
+    }
+    Some(AttributeMismatch {
+        unknown,
+        missing,
+        pipeline: attributes
+            .iter()
+            .map(|attribute| format!("{}: {:?}", attribute.name, attribute.format))
+            .collect(),
+        program: active
+            .iter()
+            .map(|(name, type_name)| format!("{} {}", type_name, name))
+            .collect(),
+    })
+}
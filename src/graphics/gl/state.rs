@@ -0,0 +1,156 @@
+//! Snapshot of the GL state, for sharing a context with foreign GL code.
+//!
+//! miniquad caches the GL state it sets, so GL calls made by someone else go
+//! unnoticed and both sides end up rendering with the other's state. `save` and
+//! `restore` cover what either side is likely to touch: bindings, viewport and
+//! scissor, blending, depth, culling and color mask. Stencil functions and
+//! pixel store parameters are not saved.
+//! Not available on wasm, the WebGL context can't be shared there anyway.
+
+use super::*;
+
+/// GL state saved by `RenderingBackend::save_gl_state`
+#[derive(Clone, Debug)]
+#[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+pub struct GlState {
+    program: GLint,
+    array_buffer: GLint,
+    element_array_buffer: GLint,
+    vertex_array: GLint,
+    framebuffer: GLint,
+    active_texture: GLint,
+    /// Units saved in `textures_2d` and `textures_cube`, the device may have less
+    texture_units: usize,
+    textures_2d: [GLint; MAX_SHADERSTAGE_IMAGES],
+    textures_cube: [GLint; MAX_SHADERSTAGE_IMAGES],
+    viewport: [GLint; 4],
+    scissor_box: [GLint; 4],
+    scissor_test: bool,
+    blend: bool,
+    blend_func: [GLint; 4],
+    blend_equation: [GLint; 2],
+    depth_test: bool,
+    depth_func: GLint,
+    depth_mask: GLint,
+    stencil_test: bool,
+    cull_face: bool,
+    cull_face_mode: GLint,
+    front_face: GLint,
+    color_mask: [GLint; 4],
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn get(pname: GLenum) -> GLint {
+    let mut value = 0;
+    glGetIntegerv(pname, &mut value);
+    value
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn get4(pname: GLenum) -> [GLint; 4] {
+    let mut value = [0; 4];
+    glGetIntegerv(pname, value.as_mut_ptr());
+    value
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+unsafe fn set_enabled(cap: GLenum, enabled: bool) {
+    if enabled {
+        glEnable(cap);
+    } else {
+        glDisable(cap);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl GlState {
+    pub(super) unsafe fn save() -> GlState {
+        let active_texture = get(GL_ACTIVE_TEXTURE);
+        let texture_units =
+            (get(GL_MAX_COMBINED_TEXTURE_IMAGE_UNITS).max(0) as usize).min(MAX_SHADERSTAGE_IMAGES);
+        let mut textures_2d = [0; MAX_SHADERSTAGE_IMAGES];
+        let mut textures_cube = [0; MAX_SHADERSTAGE_IMAGES];
+        for slot_index in 0..texture_units {
+            glActiveTexture(GL_TEXTURE0 + slot_index as GLuint);
+            textures_2d[slot_index] = get(GL_TEXTURE_BINDING_2D);
+            textures_cube[slot_index] = get(GL_TEXTURE_BINDING_CUBE_MAP);
+        }
+        glActiveTexture(active_texture as GLenum);
+
+        GlState {
+            program: get(GL_CURRENT_PROGRAM),
+            array_buffer: get(GL_ARRAY_BUFFER_BINDING),
+            element_array_buffer: get(GL_ELEMENT_ARRAY_BUFFER_BINDING),
+            vertex_array: get(GL_VERTEX_ARRAY_BINDING),
+            framebuffer: get(GL_FRAMEBUFFER_BINDING),
+            active_texture,
+            texture_units,
+            textures_2d,
+            textures_cube,
+            viewport: get4(GL_VIEWPORT),
+            scissor_box: get4(GL_SCISSOR_BOX),
+            scissor_test: glIsEnabled(GL_SCISSOR_TEST) != 0,
+            blend: glIsEnabled(GL_BLEND) != 0,
+            blend_func: [
+                get(GL_BLEND_SRC_RGB),
+                get(GL_BLEND_DST_RGB),
+                get(GL_BLEND_SRC_ALPHA),
+                get(GL_BLEND_DST_ALPHA),
+            ],
+            blend_equation: [get(GL_BLEND_EQUATION_RGB), get(GL_BLEND_EQUATION_ALPHA)],
+            depth_test: glIsEnabled(GL_DEPTH_TEST) != 0,
+            depth_func: get(GL_DEPTH_FUNC),
+            depth_mask: get(GL_DEPTH_WRITEMASK),
+            stencil_test: glIsEnabled(GL_STENCIL_TEST) != 0,
+            cull_face: glIsEnabled(GL_CULL_FACE) != 0,
+            cull_face_mode: get(GL_CULL_FACE_MODE),
+            front_face: get(GL_FRONT_FACE),
+            color_mask: get4(GL_COLOR_WRITEMASK),
+        }
+    }
+
+    pub(super) unsafe fn restore(&self) {
+        glBindVertexArray(self.vertex_array as GLuint);
+        glUseProgram(self.program as GLuint);
+        glBindBuffer(GL_ARRAY_BUFFER, self.array_buffer as GLuint);
+        // Part of the vertex array state, bound after it
+        glBindBuffer(GL_ELEMENT_ARRAY_BUFFER, self.element_array_buffer as GLuint);
+        glBindFramebuffer(GL_FRAMEBUFFER, self.framebuffer as GLuint);
+        for slot_index in 0..self.texture_units {
+            glActiveTexture(GL_TEXTURE0 + slot_index as GLuint);
+            glBindTexture(GL_TEXTURE_2D, self.textures_2d[slot_index] as GLuint);
+            glBindTexture(
+                GL_TEXTURE_CUBE_MAP,
+                self.textures_cube[slot_index] as GLuint,
+            );
+        }
+        glActiveTexture(self.active_texture as GLenum);
+
+        let [x, y, w, h] = self.viewport;
+        glViewport(x, y, w, h);
+        let [x, y, w, h] = self.scissor_box;
+        glScissor(x, y, w, h);
+        set_enabled(GL_SCISSOR_TEST, self.scissor_test);
+
+        set_enabled(GL_BLEND, self.blend);
+        let [src_rgb, dst_rgb, src_alpha, dst_alpha] = self.blend_func;
+        glBlendFuncSeparate(
+            src_rgb as GLenum,
+            dst_rgb as GLenum,
+            src_alpha as GLenum,
+            dst_alpha as GLenum,
+        );
+        let [eq_rgb, eq_alpha] = self.blend_equation;
+        glBlendEquationSeparate(eq_rgb as GLenum, eq_alpha as GLenum);
+
+        set_enabled(GL_DEPTH_TEST, self.depth_test);
+        glDepthFunc(self.depth_func as GLenum);
+        glDepthMask(self.depth_mask as GLboolean);
+        set_enabled(GL_STENCIL_TEST, self.stencil_test);
+        set_enabled(GL_CULL_FACE, self.cull_face);
+        glCullFace(self.cull_face_mode as GLenum);
+        glFrontFace(self.front_face as GLenum);
+        let [r, g, b, a] = self.color_mask;
+        glColorMask(r as _, g as _, b as _, a as _);
+    }
+}
@@ -0,0 +1,205 @@
+//! Checks done before `apply_bindings` and `draw` with the `validation` feature.
+//!
+//! Invalid calls are reported through `crate::error::report_error` and skipped,
+//! instead of panicking deep inside the backend or handing garbage to the driver.
+//! Indices are not read back, so a draw may still fetch vertices past the end of a
+//! per-vertex buffer; everything else the GL would silently accept is checked here.
+
+use super::*;
+
+/// Resources of the last successful `apply_bindings`
+#[derive(Debug, Clone)]
+pub(super) struct BoundResources {
+    vertex_buffers: Vec<BufferId>,
+    // None after `apply_images`
+    index_buffer: Option<BufferId>,
+}
+
+impl BoundResources {
+    pub(super) fn new(
+        vertex_buffers: &[BufferId],
+        index_buffer: Option<BufferId>,
+    ) -> BoundResources {
+        BoundResources {
+            vertex_buffers: vertex_buffers.to_vec(),
+            index_buffer,
+        }
+    }
+}
+
+fn gl_type_size(type_: GLenum) -> i64 {
+    match type_ {
+        GL_BYTE | GL_UNSIGNED_BYTE => 1,
+        GL_SHORT | GL_UNSIGNED_SHORT => 2,
+        _ => 4,
+    }
+}
+
+fn current_pipeline(ctx: &GlContext) -> Result<&PipelineInternal, MiniquadError> {
+    let pipeline = ctx.cache.cur_pipeline.ok_or_else(|| {
+        ResourceError::InvalidState("no pipeline applied, call apply_pipeline first".to_string())
+    })?;
+    Ok(ctx.pipelines.get(pipeline.0)?)
+}
+
+fn buffer_of_type(
+    ctx: &GlContext,
+    buffer: BufferId,
+    type_: BufferType,
+) -> Result<&Buffer, MiniquadError> {
+    let data = ctx.buffers.get(buffer.0)?;
+    if data.buffer_type != type_ {
+        return Err(MiniquadError::InvalidParameter(format!(
+            "buffer {} is a {:?}, expected a {:?}",
+            buffer.0, data.buffer_type, type_
+        )));
+    }
+    Ok(data)
+}
+
+/// Every attribute of the pipeline has a vertex buffer big enough for the
+/// first vertex and, for per-instance attributes, for all the instances.
+fn validate_attributes(
+    ctx: &GlContext,
+    pipeline: &PipelineInternal,
+    vertex_buffers: &[BufferId],
+    num_instances: i32,
+) -> Result<(), MiniquadError> {
+    for attribute in pipeline.layout.iter().flatten() {
+        let buffer = *vertex_buffers.get(attribute.buffer_index).ok_or_else(|| {
+            MiniquadError::InvalidParameter(format!(
+                "attribute {} reads vertex buffer {}, but only {} vertex buffers are bound",
+                attribute.attr_loc,
+                attribute.buffer_index,
+                vertex_buffers.len()
+            ))
+        })?;
+        let data = buffer_of_type(ctx, buffer, BufferType::VertexBuffer)?;
+
+        let mut required = attribute.offset + attribute.size as i64 * gl_type_size(attribute.type_);
+        if attribute.divisor > 0 && num_instances > 1 {
+            let elements = (num_instances as i64 - 1) / attribute.divisor as i64;
+            required += elements * attribute.stride as i64;
+        }
+        if required > data.size as i64 {
+            return Err(MiniquadError::InvalidParameter(format!(
+                "attribute {} needs {} bytes of vertex buffer {}, it has {}",
+                attribute.attr_loc, required, buffer.0, data.size
+            )));
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn validate_bindings(
+    ctx: &GlContext,
+    vertex_buffers: &[BufferId],
+    index_buffer: BufferId,
+    textures: &[TextureId],
+) -> Result<(), MiniquadError> {
+    let pipeline = current_pipeline(ctx)?;
+    validate_images(ctx, textures)?;
+
+    let index_data = buffer_of_type(ctx, index_buffer, BufferType::IndexBuffer)?;
+    if index_data.index_type.is_none() && pipeline.params.index_type.is_none() {
+        return Err(ResourceError::InvalidState(format!(
+            "index buffer {} has no index type",
+            index_buffer.0
+        ))
+        .into());
+    }
+
+    validate_attributes(ctx, pipeline, vertex_buffers, 1)
+}
+
+pub(super) fn validate_images(
+    ctx: &GlContext,
+    textures: &[TextureId],
+) -> Result<(), MiniquadError> {
+    let pipeline = current_pipeline(ctx)?;
+    let shader = ctx.shaders.get(pipeline.shader.0)?;
+
+    if textures.len() < shader.images.len() {
+        return Err(MiniquadError::InvalidParameter(format!(
+            "the shader samples {} images, but {} are bound",
+            shader.images.len(),
+            textures.len()
+        )));
+    }
+    for texture in &textures[..shader.images.len()] {
+        if let TextureOrRenderbuffer::Renderbuffer(_) = ctx.textures.try_get(*texture)?.raw {
+            return Err(MiniquadError::InvalidParameter(
+                "multisampled render textures can't be sampled, resolve them first".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+pub(super) fn validate_draw(
+    ctx: &GlContext,
+    bound: Option<&BoundResources>,
+    base_element: i32,
+    num_elements: i32,
+    num_instances: i32,
+) -> Result<(), MiniquadError> {
+    let pipeline = current_pipeline(ctx)?;
+    let bound = bound.ok_or_else(|| {
+        ResourceError::InvalidState(
+            "no valid bindings applied, call apply_bindings first".to_string(),
+        )
+    })?;
+    if base_element < 0 || num_elements < 0 || num_instances < 0 {
+        return Err(MiniquadError::InvalidParameter(format!(
+            "negative draw arguments: base_element {}, num_elements {}, num_instances {}",
+            base_element, num_elements, num_instances
+        )));
+    }
+
+    let index_buffer = bound.index_buffer.ok_or_else(|| {
+        ResourceError::InvalidState(
+            "no index buffer bound, use draw_vertices after apply_images".to_string(),
+        )
+    })?;
+    let index_data = buffer_of_type(ctx, index_buffer, BufferType::IndexBuffer)?;
+    let index_size = pipeline
+        .params
+        .index_type
+        .map(|index_type| index_type.size() as u32)
+        .or(index_data.index_type)
+        .unwrap_or(1) as usize;
+    let index_count = index_data.size / index_size;
+    let end = base_element as usize + num_elements as usize;
+    if end > index_count {
+        return Err(MiniquadError::InvalidParameter(format!(
+            "drawing indices {}..{} of index buffer {}, it has {} indices",
+            base_element, end, index_buffer.0, index_count
+        )));
+    }
+
+    // The pipeline may have changed since apply_bindings
+    validate_attributes(ctx, pipeline, &bound.vertex_buffers, num_instances)
+}
+
+pub(super) fn validate_draw_vertices(
+    ctx: &GlContext,
+    bound: Option<&BoundResources>,
+    base_vertex: i32,
+    num_vertices: i32,
+    num_instances: i32,
+) -> Result<(), MiniquadError> {
+    let pipeline = current_pipeline(ctx)?;
+    let bound = bound.ok_or_else(|| {
+        ResourceError::InvalidState(
+            "no valid bindings applied, call apply_bindings or apply_images first".to_string(),
+        )
+    })?;
+    if base_vertex < 0 || num_vertices < 0 || num_instances < 0 {
+        return Err(MiniquadError::InvalidParameter(format!(
+            "negative draw arguments: base_vertex {}, num_vertices {}, num_instances {}",
+            base_vertex, num_vertices, num_instances
+        )));
+    }
+
+    validate_attributes(ctx, pipeline, &bound.vertex_buffers, num_instances)
+}
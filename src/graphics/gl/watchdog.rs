@@ -0,0 +1,140 @@
+use crate::graphics::device_lost::{self, DeviceLost, DeviceLostReason};
+use crate::graphics::*;
+
+use std::collections::VecDeque;
+
+/// Upper bound on frames in flight, the oldest one times out long before
+const MAX_PENDING_FENCES: usize = 8;
+
+/// Reset status entry point, core or one of the robustness extensions
+#[derive(Debug, Clone, Copy)]
+enum ResetStatus {
+    Core,
+    Khr,
+    Arb,
+    Ext,
+}
+
+/// Polls the reset status and fences every frame, see `device_lost`
+#[derive(Debug)]
+pub struct GpuWatchdog {
+    fences_supported: bool,
+    reset_status: Option<ResetStatus>,
+    /// Fence and submission time, in seconds
+    pending: VecDeque<(GLsync, f64)>,
+    device: String,
+    lost: bool,
+}
+
+impl GpuWatchdog {
+    pub fn new(info: &ContextInfo) -> GpuWatchdog {
+        #[cfg(not(target_arch = "wasm32"))]
+        let (fences_supported, reset_status) = {
+            let fences_supported = ["glFenceSync", "glDeleteSync", "glClientWaitSync"]
+                .iter()
+                .all(|f| is_gl_func_loaded(f));
+            let reset_status = [
+                ("glGetGraphicsResetStatus", ResetStatus::Core),
+                ("glGetGraphicsResetStatusKHR", ResetStatus::Khr),
+                ("glGetGraphicsResetStatusARB", ResetStatus::Arb),
+                ("glGetGraphicsResetStatusEXT", ResetStatus::Ext),
+            ]
+            .iter()
+            .find(|(name, _)| is_gl_func_loaded(name))
+            .map(|(_, status)| *status);
+            (fences_supported, reset_status)
+        };
+        // WebGL reports a lost context through an event on the canvas instead
+        #[cfg(target_arch = "wasm32")]
+        let (fences_supported, reset_status) = (false, None);
+
+        GpuWatchdog {
+            fences_supported,
+            reset_status,
+            pending: VecDeque::new(),
+            device: info.gl_version_string.clone(),
+            lost: false,
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        if self.lost {
+            return;
+        }
+
+        if let Some(reason) = self.reset_reason() {
+            self.report(
+                reason,
+                None,
+                "the GL context was reset, it has to be recreated".to_string(),
+            );
+            return;
+        }
+
+        if !self.fences_supported {
+            return;
+        }
+        let now = crate::date::now();
+        while let Some(&(fence, submitted)) = self.pending.front() {
+            let res = unsafe { glClientWaitSync(fence, 0, 0) };
+            if res == GL_ALREADY_SIGNALED || res == GL_CONDITION_SATISFIED {
+                unsafe { glDeleteSync(fence) };
+                self.pending.pop_front();
+                continue;
+            }
+            let elapsed = now - submitted;
+            if elapsed > device_lost::gpu_timeout().as_secs_f64() {
+                self.report(
+                    DeviceLostReason::Timeout,
+                    Some(elapsed * 1000.0),
+                    format!(
+                        "a frame is still running on the GPU after {:.1} s, {} frames queued",
+                        elapsed,
+                        self.pending.len()
+                    ),
+                );
+            }
+            break;
+        }
+        if self.pending.len() < MAX_PENDING_FENCES {
+            let fence = unsafe { glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0) };
+            self.pending.push_back((fence, now));
+        }
+    }
+
+    fn reset_reason(&self) -> Option<DeviceLostReason> {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let status = unsafe {
+                match self.reset_status? {
+                    ResetStatus::Core => glGetGraphicsResetStatus(),
+                    ResetStatus::Khr => glGetGraphicsResetStatusKHR(),
+                    ResetStatus::Arb => glGetGraphicsResetStatusARB(),
+                    ResetStatus::Ext => glGetGraphicsResetStatusEXT(),
+                }
+            };
+            match status {
+                GL_GUILTY_CONTEXT_RESET => Some(DeviceLostReason::GuiltyReset),
+                GL_INNOCENT_CONTEXT_RESET => Some(DeviceLostReason::InnocentReset),
+                GL_UNKNOWN_CONTEXT_RESET => Some(DeviceLostReason::UnknownReset),
+                _ => None,
+            }
+        }
+        #[cfg(target_arch = "wasm32")]
+        None
+    }
+
+    fn report(&mut self, reason: DeviceLostReason, gpu_time_ms: Option<f64>, message: String) {
+        self.lost = true;
+        // Nothing polls the fences once lost
+        for (fence, _) in self.pending.drain(..) {
+            unsafe { glDeleteSync(fence) };
+        }
+        device_lost::report(DeviceLost {
+            reason,
+            gpu_time_ms,
+            device: self.device.clone(),
+            message,
+        });
+    }
+}
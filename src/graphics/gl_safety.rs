@@ -6,6 +6,8 @@
 use crate::error::{GLError, GraphicsApiError, MiniquadError};
 use crate::graphics::*;
 
+use std::cell::Cell;
+
 /// Maximum number of buffers that can be generated at once
 const MAX_BUFFERS: i32 = 1024;
 
@@ -25,10 +27,58 @@ pub struct TexImageParams {
     pub type_: GLenum,
 }
 
+thread_local! {
+    /// The pass and pipeline the backend is drawing with, None outside passes,
+    /// for the reports of `SafeGL::set_error_checks`
+    static CALL_SCOPE: Cell<Option<(Option<RenderPass>, Option<Pipeline>)>> = const { Cell::new(None) };
+}
+
 /// Safe OpenGL wrapper with error checking
 pub struct SafeGL;
 
 impl SafeGL {
+    /// Debug builds only: check `glGetError` after every GL call of the backend
+    /// and report errors through `set_error_handler`, with the call, where the
+    /// backend made it and the labels of the current pass and pipeline (see
+    /// `set_render_pass_label`). Off by default, it stalls the GPU pipeline.
+    /// The checks are compiled out of release builds.
+    ///
+    /// Native GL only: WebGL calls go through `js/gl.js`, the browser console
+    /// reports their errors.
+    pub fn set_error_checks(enabled: bool) {
+        #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+        crate::native::gl::CHECK_ERRORS.store(enabled, std::sync::atomic::Ordering::Relaxed);
+        #[cfg(not(all(debug_assertions, not(target_arch = "wasm32"))))]
+        let _ = enabled;
+    }
+
+    /// Called by the GL function wrappers with the checks on
+    #[cfg(all(debug_assertions, not(target_arch = "wasm32")))]
+    pub(crate) fn report_call_error(
+        name: &str,
+        location: &std::panic::Location<'static>,
+        error: GLenum,
+    ) {
+        let scope = match CALL_SCOPE.with(Cell::get) {
+            None => "outside passes".to_string(),
+            Some((pass, None)) => format!("in {}", profiling::render_pass_label(pass)),
+            Some((pass, Some(pipeline))) => format!(
+                "in {} with {}",
+                profiling::render_pass_label(pass),
+                profiling::pipeline_label(pipeline)
+            ),
+        };
+        crate::error::report_error(MiniquadError::GraphicsApi(GraphicsApiError::OpenGLCall {
+            call: format!("{} at {}, {}", name, location, scope),
+            error: GLError::from_gl_enum(error),
+        }));
+    }
+
+    /// Called by the GL backend on begin_pass, apply_pipeline and end_render_pass
+    pub(crate) fn set_call_scope(scope: Option<(Option<RenderPass>, Option<Pipeline>)>) {
+        CALL_SCOPE.with(|s| s.set(scope));
+    }
+
     /// Check for OpenGL errors and convert to our error type
     pub fn check_error() -> Result<(), GLError> {
         let error = unsafe { glGetError() };
@@ -38,14 +88,27 @@ impl SafeGL {
         }
     }
 
+    /// Drop all the pending errors, so the next check only reports
+    /// errors of the calls made after this one
+    pub fn clear_errors() {
+        // glGetError may keep returning an error on a lost context, don't loop forever
+        for _ in 0..16 {
+            if unsafe { glGetError() } == GL_NO_ERROR {
+                break;
+            }
+        }
+    }
+
     /// Check for OpenGL errors with context message
     pub fn check_error_with_context(context: &str) -> Result<(), MiniquadError> {
-        Self::check_error()
-            .map_err(|e| MiniquadError::GraphicsApi(GraphicsApiError::OpenGL(e)))
-            .map_err(|e| {
-                eprintln!("OpenGL error in {}: {}", context, e);
-                e
-            })
+        Self::check_error().map_err(|error| {
+            let e = MiniquadError::GraphicsApi(GraphicsApiError::OpenGLCall {
+                call: context.to_string(),
+                error,
+            });
+            crate::error::report_error(e.clone());
+            e
+        })
     }
 
     /// Safely generate buffers with validation
@@ -323,6 +386,7 @@ impl SafeGL {
 
             return Err(MiniquadError::Shader(ShaderError::CompilationError {
                 shader_type: shader_type_name,
+                diagnostics: super::shader_log::parse_shader_log(shader_type_name, &error_msg, 0),
                 error_message: error_msg,
             }));
         }
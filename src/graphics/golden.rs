@@ -0,0 +1,266 @@
+//! Golden-image regression tests
+//!
+//! Render a callback into an offscreen RGBA8 pass, read the pixels back and
+//! compare them with a reference PNG. Works with any backend: a real GL context
+//! or `NullContext` when no GPU is around (the readback is all zeros then, only
+//! the calls are checked).
+//!
+//! Missing references are written instead of compared, so the first run of a new
+//! test records it. Set `MINIQUAD_UPDATE_GOLDEN=1` to re-record all of them.
+//! On mismatch the rendered image is saved next to the reference as
+//! `<name>.actual.png`.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::golden::*;
+//! # fn test(ctx: &mut dyn RenderingBackend) {
+//! let image = render_offscreen(ctx, 64, 64, PassAction::clear_color(1., 0., 0., 1.), |ctx| {
+//!     // apply_pipeline, apply_bindings, draw...
+//! });
+//! assert_golden("tests/golden/red.png", &image, Tolerance::default());
+//! # }
+//! ```
+
+use crate::graphics::*;
+
+use std::{fmt, fs, io, path::Path};
+
+/// Pixels read back from an offscreen pass, RGBA8, top row first
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+/// How far the rendered image may be from the reference
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Largest allowed difference of a single channel, 0..=255
+    pub channel: u8,
+    /// Share of pixels, 0.0..=1.0, allowed to exceed `channel`
+    pub pixels: f32,
+}
+
+impl Default for Tolerance {
+    /// Absorbs rounding differences between drivers, not a wrong draw
+    fn default() -> Self {
+        Tolerance {
+            channel: 2,
+            pixels: 0.0,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum GoldenError {
+    Io(io::Error),
+    Png(String),
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+    Mismatch {
+        mismatched_pixels: usize,
+        total_pixels: usize,
+        max_difference: u8,
+    },
+}
+
+impl fmt::Display for GoldenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GoldenError::Io(err) => write!(f, "IO error: {}", err),
+            GoldenError::Png(err) => write!(f, "PNG error: {}", err),
+            GoldenError::SizeMismatch { expected, actual } => write!(
+                f,
+                "Reference is {}x{}, rendered image is {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+            GoldenError::Mismatch {
+                mismatched_pixels,
+                total_pixels,
+                max_difference,
+            } => write!(
+                f,
+                "{} of {} pixels differ from the reference, by up to {}",
+                mismatched_pixels, total_pixels, max_difference
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GoldenError {}
+
+impl From<io::Error> for GoldenError {
+    fn from(err: io::Error) -> GoldenError {
+        GoldenError::Io(err)
+    }
+}
+
+/// Render `f` into a `width`x`height` RGBA8 pass started with `action`,
+/// then read the pixels back. The pass and its texture are deleted afterwards.
+pub fn render_offscreen<F>(
+    ctx: &mut dyn RenderingBackend,
+    width: u32,
+    height: u32,
+    action: PassAction,
+    f: F,
+) -> RenderedImage
+where
+    F: FnOnce(&mut dyn RenderingBackend),
+{
+    let texture = ctx.new_render_texture(TextureParams {
+        width,
+        height,
+        format: TextureFormat::RGBA8,
+        ..Default::default()
+    });
+    let pass = ctx.new_render_pass(texture, None);
+
+    ctx.begin_pass(Some(pass), action);
+    f(ctx);
+    ctx.end_render_pass();
+
+    let mut data = vec![0; width as usize * height as usize * 4];
+    ctx.texture_read_pixels(texture, &mut data);
+    ctx.delete_render_pass(pass);
+    ctx.delete_texture(texture);
+
+    // GL reads render targets bottom row first, Metal top row first
+    if ctx.info().backend == Backend::OpenGl {
+        let row = width as usize * 4;
+        data = data.chunks_exact(row).rev().flatten().copied().collect();
+    }
+    RenderedImage {
+        width,
+        height,
+        data,
+    }
+}
+
+pub fn load_png(path: impl AsRef<Path>) -> Result<RenderedImage, GoldenError> {
+    let file = fs::File::open(path)?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder
+        .read_info()
+        .map_err(|err| GoldenError::Png(err.to_string()))?;
+    let mut buf = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buf)
+        .map_err(|err| GoldenError::Png(err.to_string()))?;
+    let buf = &buf[..info.buffer_size()];
+
+    let data = match info.color_type {
+        png::ColorType::Rgba => buf.to_vec(),
+        png::ColorType::Rgb => buf
+            .chunks_exact(3)
+            .flat_map(|p| [p[0], p[1], p[2], 255])
+            .collect(),
+        png::ColorType::GrayscaleAlpha => buf
+            .chunks_exact(2)
+            .flat_map(|p| [p[0], p[0], p[0], p[1]])
+            .collect(),
+        png::ColorType::Grayscale => buf.iter().flat_map(|&p| [p, p, p, 255]).collect(),
+        png::ColorType::Indexed => {
+            return Err(GoldenError::Png("indexed PNG was not expanded".to_string()))
+        }
+    };
+    Ok(RenderedImage {
+        width: info.width,
+        height: info.height,
+        data,
+    })
+}
+
+pub fn save_png(path: impl AsRef<Path>, image: &RenderedImage) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let file = io::BufWriter::new(fs::File::create(path)?);
+    let mut encoder = png::Encoder::new(file, image.width, image.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .map_err(|err| GoldenError::Png(err.to_string()))?;
+    writer
+        .write_image_data(&image.data)
+        .map_err(|err| GoldenError::Png(err.to_string()))
+}
+
+/// Compare two images of the same size pixel by pixel
+pub fn compare(
+    expected: &RenderedImage,
+    actual: &RenderedImage,
+    tolerance: Tolerance,
+) -> Result<(), GoldenError> {
+    if (expected.width, expected.height) != (actual.width, actual.height) {
+        return Err(GoldenError::SizeMismatch {
+            expected: (expected.width, expected.height),
+            actual: (actual.width, actual.height),
+        });
+    }
+
+    let mut mismatched_pixels = 0;
+    let mut max_difference = 0;
+    for (a, b) in expected
+        .data
+        .chunks_exact(4)
+        .zip(actual.data.chunks_exact(4))
+    {
+        let difference = a.iter().zip(b).map(|(a, b)| a.abs_diff(*b)).max().unwrap();
+        max_difference = max_difference.max(difference);
+        if difference > tolerance.channel {
+            mismatched_pixels += 1;
+        }
+    }
+
+    let total_pixels = expected.width as usize * expected.height as usize;
+    if mismatched_pixels as f32 > tolerance.pixels * total_pixels as f32 {
+        return Err(GoldenError::Mismatch {
+            mismatched_pixels,
+            total_pixels,
+            max_difference,
+        });
+    }
+    Ok(())
+}
+
+/// `MINIQUAD_UPDATE_GOLDEN` asks to re-record the references
+fn update_requested() -> bool {
+    std::env::var("MINIQUAD_UPDATE_GOLDEN")
+        .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Compare `image` with the reference PNG at `path`.
+/// The reference is written instead when it does not exist yet or
+/// `MINIQUAD_UPDATE_GOLDEN` is `1` or `true`.
+pub fn check_golden(
+    path: impl AsRef<Path>,
+    image: &RenderedImage,
+    tolerance: Tolerance,
+) -> Result<(), GoldenError> {
+    let path = path.as_ref();
+    if !path.exists() || update_requested() {
+        return save_png(path, image);
+    }
+
+    let expected = load_png(path)?;
+    let result = compare(&expected, image, tolerance);
+    if result.is_err() {
+        let _ = save_png(path.with_extension("actual.png"), image);
+    }
+    result
+}
+
+/// `check_golden`, panicking with the reference path on failure
+pub fn assert_golden(path: impl AsRef<Path>, image: &RenderedImage, tolerance: Tolerance) {
+    let path = path.as_ref();
+    if let Err(err) = check_golden(path, image, tolerance) {
+        panic!("Golden image {} failed: {}", path.display(), err);
+    }
+}
@@ -0,0 +1,307 @@
+//! Instanced 2D quads, e.g. particles, without a camera or matrices
+//!
+//! Every quad is one `QuadInstance` in a per-instance vertex buffer: its
+//! center, size, rotation, texture rectangle and color. The vertex shader
+//! places the shared unit quad from them and the `screen_size` uniform, so a
+//! whole particle system is one buffer update and one draw call.
+//!
+//! `new_pipeline` is the preset alone, for custom buffers, blending or depth,
+//! with the layout of `BUFFER_LAYOUTS` and `ATTRIBUTES`. `InstancedQuads` owns
+//! a pipeline and the buffers on top of it.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::instanced_quad::{InstancedQuads, QuadInstance};
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! # let pixels = [0u8; 8 * 8 * 4];
+//! let texture = ctx.new_texture_from_rgba8(8, 8, &pixels);
+//! let mut particles = InstancedQuads::new(&mut *ctx, 1000);
+//! let instances: Vec<QuadInstance> = (0..1000)
+//!     .map(|i| QuadInstance::new(i as f32, 100.0, 8.0, 8.0))
+//!     .collect();
+//!
+//! // each frame
+//! ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+//! let (width, height) = window::screen_size();
+//! particles.draw(&mut *ctx, texture, &instances, width, height);
+//! ctx.end_render_pass();
+//! ctx.commit_frame();
+//! ```
+
+use crate::graphics::*;
+
+/// One quad, in pixels of the pass from its top left corner
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuadInstance {
+    /// Center of the quad
+    pub position: [f32; 2],
+    /// Width and height
+    pub scale: [f32; 2],
+    /// Clockwise, in radians, around the center
+    pub rotation: f32,
+    /// Texture coordinates of the top left and bottom right corners: `[u0, v0, u1, v1]`
+    pub uv: [f32; 4],
+    /// Multiplied with the texture color
+    pub color: [f32; 4],
+}
+
+impl QuadInstance {
+    /// The whole texture, untinted and unrotated, `w` x `h` large and centered at `x`, `y`
+    pub fn new(x: f32, y: f32, w: f32, h: f32) -> QuadInstance {
+        QuadInstance {
+            position: [x, y],
+            scale: [w, h],
+            rotation: 0.0,
+            uv: [0.0, 0.0, 1.0, 1.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+}
+
+/// The uniforms of the preset
+#[repr(C)]
+pub struct Uniforms {
+    /// Size of the pass the quads are drawn into, in the units of `QuadInstance`
+    pub screen_size: [f32; 2],
+}
+
+/// Buffer 0 holds the unit quad corners, buffer 1 the `QuadInstance`s
+pub const BUFFER_LAYOUTS: [BufferLayout; 2] = [
+    BufferLayout {
+        stride: 0,
+        step_func: VertexStep::PerVertex,
+        step_rate: 1,
+    },
+    BufferLayout {
+        stride: 0,
+        step_func: VertexStep::PerInstance,
+        step_rate: 1,
+    },
+];
+
+pub const ATTRIBUTES: [VertexAttribute; 6] = [
+    VertexAttribute::with_buffer("in_corner", VertexFormat::Float2, 0),
+    VertexAttribute::with_buffer("in_position", VertexFormat::Float2, 1),
+    VertexAttribute::with_buffer("in_scale", VertexFormat::Float2, 1),
+    VertexAttribute::with_buffer("in_rotation", VertexFormat::Float1, 1),
+    VertexAttribute::with_buffer("in_uv", VertexFormat::Float4, 1),
+    VertexAttribute::with_buffer("in_color", VertexFormat::Float4, 1),
+];
+
+/// Corners of the unit quad, buffer 0 of the preset
+pub const CORNERS: [[f32; 2]; 4] = [[-0.5, -0.5], [0.5, -0.5], [0.5, 0.5], [-0.5, 0.5]];
+
+/// Indices of the two triangles of `CORNERS`
+pub const INDICES: [u16; 6] = [0, 1, 2, 0, 2, 3];
+
+/// The shader and vertex layout of the preset, with `params` for the rest
+pub fn new_pipeline(ctx: &mut dyn RenderingBackend, params: PipelineParams) -> Pipeline {
+    let shader = ctx
+        .new_shader(
+            match ctx.info().backend {
+                Backend::OpenGl => ShaderSource::Glsl {
+                    vertex: shader::VERTEX,
+                    fragment: shader::FRAGMENT,
+                },
+                Backend::Metal => ShaderSource::Msl {
+                    program: shader::METAL,
+                },
+            },
+            shader::meta(),
+        )
+        .unwrap_or_else(|e| panic!("Failed to load instanced quad shader: {}", e));
+    ctx.new_pipeline(&BUFFER_LAYOUTS, &ATTRIBUTES, shader, params)
+}
+
+pub struct InstancedQuads {
+    pipeline: Pipeline,
+    bindings: Bindings,
+    /// Instances the instance buffer holds
+    capacity: usize,
+}
+
+impl InstancedQuads {
+    /// An alpha blended preset pipeline and buffers for `capacity` instances,
+    /// grown by `draw` if needed
+    pub fn new(ctx: &mut dyn RenderingBackend, capacity: usize) -> InstancedQuads {
+        let corners = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&CORNERS),
+        );
+        let instances = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<QuadInstance>(capacity.max(1)),
+        );
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&INDICES),
+        );
+        let pipeline = new_pipeline(
+            ctx,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        InstancedQuads {
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![corners, instances],
+                index_buffer,
+                images: vec![],
+            },
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn pipeline(&self) -> Pipeline {
+        self.pipeline
+    }
+
+    /// Draw `instances` textured with `texture` in the current pass, `width` x
+    /// `height` pixels large, in one draw call. Needs a pass begun, the applied
+    /// pipeline, bindings and uniforms are replaced.
+    pub fn draw(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        texture: TextureId,
+        instances: &[QuadInstance],
+        width: f32,
+        height: f32,
+    ) {
+        if instances.is_empty() {
+            return;
+        }
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            ctx.delete_buffer(self.bindings.vertex_buffers[1]);
+            self.bindings.vertex_buffers[1] = ctx.new_buffer(
+                BufferType::VertexBuffer,
+                BufferUsage::Stream,
+                BufferSource::empty::<QuadInstance>(self.capacity),
+            );
+        }
+        ctx.buffer_update(
+            self.bindings.vertex_buffers[1],
+            BufferSource::slice(instances),
+        );
+        self.bindings.images = vec![texture];
+
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_bindings(&self.bindings);
+        ctx.apply_uniforms(UniformsSource::table(&Uniforms {
+            screen_size: [width, height],
+        }));
+        ctx.draw(0, INDICES.len() as i32, instances.len() as i32);
+    }
+
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        ctx.delete_pipeline(self.pipeline);
+        for buffer in self.bindings.vertex_buffers {
+            ctx.delete_buffer(buffer);
+        }
+        ctx.delete_buffer(self.bindings.index_buffer);
+    }
+}
+
+mod shader {
+    use crate::graphics::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 in_corner;
+    attribute vec2 in_position;
+    attribute vec2 in_scale;
+    attribute float in_rotation;
+    attribute vec4 in_uv;
+    attribute vec4 in_color;
+
+    uniform vec2 screen_size;
+
+    varying mediump vec2 texcoord;
+    varying lowp vec4 color;
+
+    void main() {
+        vec2 local = in_corner * in_scale;
+        float s = sin(in_rotation);
+        float c = cos(in_rotation);
+        vec2 pos = in_position + vec2(local.x * c - local.y * s, local.x * s + local.y * c);
+        gl_Position = vec4(pos.x / screen_size.x * 2.0 - 1.0, 1.0 - pos.y / screen_size.y * 2.0, 0, 1);
+        texcoord = mix(in_uv.xy, in_uv.zw, in_corner + 0.5);
+        color = in_color;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying mediump vec2 texcoord;
+    varying lowp vec4 color;
+
+    uniform sampler2D tex;
+
+    void main() {
+        gl_FragColor = texture2D(tex, texcoord) * color;
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+
+    using namespace metal;
+
+    struct Uniforms
+    {
+        float2 screen_size;
+    };
+
+    struct Vertex
+    {
+        float2 in_corner   [[attribute(0)]];
+        float2 in_position [[attribute(1)]];
+        float2 in_scale    [[attribute(2)]];
+        float in_rotation  [[attribute(3)]];
+        float4 in_uv       [[attribute(4)]];
+        float4 in_color    [[attribute(5)]];
+    };
+
+    struct RasterizerData
+    {
+        float4 position [[position]];
+        float2 uv       [[user(locn0)]];
+        float4 color    [[user(locn1)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]], constant Uniforms& uniforms [[buffer(0)]])
+    {
+        RasterizerData out;
+
+        float2 local = v.in_corner * v.in_scale;
+        float s = sin(v.in_rotation);
+        float c = cos(v.in_rotation);
+        float2 pos = v.in_position + float2(local.x * c - local.y * s, local.x * s + local.y * c);
+        out.position = float4(pos.x / uniforms.screen_size.x * 2.0 - 1.0, 1.0 - pos.y / uniforms.screen_size.y * 2.0, 0.0, 1.0);
+        out.uv = mix(v.in_uv.xy, v.in_uv.zw, v.in_corner + 0.5);
+        out.color = v.in_color;
+
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]])
+    {
+        return tex.sample(texSmplr, in.uv) * in.color;
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["tex".to_string()],
+            uniforms: UniformBlockLayout {
+                uniforms: vec![UniformDesc::new("screen_size", UniformType::Float2)],
+            },
+        }
+    }
+}
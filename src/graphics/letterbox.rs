@@ -0,0 +1,316 @@
+//! Fixed resolution rendering, scaled up to the window
+//!
+//! The game draws into an offscreen pass of a fixed size, `Letterbox::draw`
+//! then puts it on the default framebuffer, either at the largest integer
+//! scale that fits (crisp pixel art) or as large as the aspect ratio allows.
+//! The rest of the window is cleared with the border color.
+//!
+//! The placement is computed in framebuffer pixels from `window::screen_size`
+//! on every draw, so window resizes and DPI changes need no extra handling.
+//! Use `screen_to_game` to map mouse positions back into the game.
+//!
+//! `RenderingBackend::set_internal_resolution` does all of this for the
+//! default pass, see there.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::letterbox::{Letterbox, ScaleMode};
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! let letterbox = Letterbox::new(&mut *ctx, 320, 180, ScaleMode::Integer);
+//!
+//! // each frame
+//! letterbox.begin_pass(&mut *ctx, PassAction::clear_color(0.1, 0.1, 0.2, 1.0));
+//! // ... draw the game in 320x180 ...
+//! ctx.end_render_pass();
+//! letterbox.draw(&mut *ctx);
+//! ctx.commit_frame();
+//! ```
+
+use crate::graphics::*;
+
+/// How the offscreen image is fit into the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScaleMode {
+    /// Largest whole multiple of the game size that fits, so every game pixel
+    /// is the same number of screen pixels. Falls back to `Fit` when the window
+    /// is smaller than the game.
+    Integer,
+    /// As large as fits, keeping the aspect ratio
+    Fit,
+    /// The whole window, ignoring the aspect ratio
+    Stretch,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Vertex {
+    pos: [f32; 2],
+}
+
+pub struct Letterbox {
+    color: TextureId,
+    pass: RenderPass,
+    pipeline: Pipeline,
+    bindings: Bindings,
+    width: u32,
+    height: u32,
+    mode: ScaleMode,
+    border_color: (f32, f32, f32, f32),
+}
+
+impl Letterbox {
+    /// Offscreen color and depth targets of `width` x `height` and the pipeline
+    /// drawing them to the window.
+    pub fn new(
+        ctx: &mut dyn RenderingBackend,
+        width: u32,
+        height: u32,
+        mode: ScaleMode,
+    ) -> Letterbox {
+        let color = ctx.new_render_texture(TextureParams {
+            width,
+            height,
+            min_filter: FilterMode::Nearest,
+            mag_filter: FilterMode::Nearest,
+            ..Default::default()
+        });
+        let depth = ctx.new_render_texture(TextureParams {
+            width,
+            height,
+            format: TextureFormat::Depth,
+            ..Default::default()
+        });
+        let pass = ctx.new_render_pass(color, Some(depth));
+
+        #[rustfmt::skip]
+        let vertices: [Vertex; 4] = [
+            Vertex { pos: [-1.0, -1.0] },
+            Vertex { pos: [ 1.0, -1.0] },
+            Vertex { pos: [ 1.0,  1.0] },
+            Vertex { pos: [-1.0,  1.0] },
+        ];
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&vertices),
+        );
+        let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&indices),
+        );
+
+        let shader = ctx
+            .new_shader(
+                match ctx.info().backend {
+                    Backend::OpenGl => ShaderSource::Glsl {
+                        vertex: shader::VERTEX,
+                        fragment: shader::FRAGMENT,
+                    },
+                    Backend::Metal => ShaderSource::Msl {
+                        program: shader::METAL,
+                    },
+                },
+                shader::meta(),
+            )
+            .unwrap_or_else(|e| panic!("Failed to load letterbox shader: {}", e));
+        let pipeline = ctx.new_pipeline(
+            &[BufferLayout::default()],
+            &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+            shader,
+            PipelineParams::default(),
+        );
+
+        Letterbox {
+            color,
+            pass,
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: vec![color],
+            },
+            width,
+            height,
+            mode,
+            border_color: (0.0, 0.0, 0.0, 1.0),
+        }
+    }
+
+    /// Size of the offscreen image
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The offscreen pass, for drawing into it with `begin_pass` directly
+    pub fn render_pass(&self) -> RenderPass {
+        self.pass
+    }
+
+    /// Color texture of the offscreen pass
+    pub fn texture(&self) -> TextureId {
+        self.color
+    }
+
+    pub fn mode(&self) -> ScaleMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ScaleMode) {
+        self.mode = mode;
+    }
+
+    /// Color of the window area around the image, black by default
+    pub fn set_border_color(&mut self, r: f32, g: f32, b: f32, a: f32) {
+        self.border_color = (r, g, b, a);
+    }
+
+    /// Begin the offscreen pass, end it with `end_render_pass` as usual.
+    pub fn begin_pass(&self, ctx: &mut dyn RenderingBackend, action: PassAction) {
+        ctx.begin_pass(Some(self.pass), action);
+    }
+
+    /// Where the image goes in a `screen_width` x `screen_height` framebuffer:
+    /// x, y from the top left corner, width, height, all in framebuffer pixels.
+    pub fn viewport(&self, screen_width: f32, screen_height: f32) -> (i32, i32, i32, i32) {
+        viewport(
+            self.width,
+            self.height,
+            self.mode,
+            screen_width,
+            screen_height,
+        )
+    }
+
+    /// Window position in framebuffer pixels, as given to mouse and touch events,
+    /// to game pixels. None for positions on the border.
+    pub fn screen_to_game(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let (screen_width, screen_height) = crate::window::screen_size();
+        let (vx, vy, vw, vh) = self.viewport(screen_width, screen_height);
+        if vw <= 0 || vh <= 0 {
+            return None;
+        }
+        let gx = (x - vx as f32) / vw as f32 * self.width as f32;
+        let gy = (y - vy as f32) / vh as f32 * self.height as f32;
+        if gx < 0.0 || gy < 0.0 || gx >= self.width as f32 || gy >= self.height as f32 {
+            return None;
+        }
+        Some((gx, gy))
+    }
+
+    /// Draw the offscreen image on the default framebuffer, clearing the border.
+    /// Should be called after the offscreen pass ended, before anything drawn
+    /// at the window resolution, like UI.
+    pub fn draw(&self, ctx: &mut dyn RenderingBackend) {
+        let (screen_width, screen_height) = crate::window::screen_size();
+        let (x, y, w, h) = self.viewport(screen_width, screen_height);
+        let (r, g, b, a) = self.border_color;
+
+        ctx.begin_default_pass(PassAction::clear_color(r, g, b, a));
+        if w > 0 && h > 0 {
+            // apply_viewport counts y from the bottom
+            ctx.apply_viewport(x, screen_height as i32 - y - h, w, h);
+            ctx.apply_pipeline(&self.pipeline);
+            ctx.apply_bindings(&self.bindings);
+            ctx.draw(0, 6, 1);
+        }
+        ctx.end_render_pass();
+    }
+
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        // Deletes the color and depth textures as well
+        ctx.delete_render_pass(self.pass);
+        ctx.delete_pipeline(self.pipeline);
+        ctx.delete_buffer(self.bindings.vertex_buffers[0]);
+        ctx.delete_buffer(self.bindings.index_buffer);
+    }
+}
+
+/// `Letterbox::viewport` of a `width` x `height` image fit with `mode`
+pub(crate) fn viewport(
+    width: u32,
+    height: u32,
+    mode: ScaleMode,
+    screen_width: f32,
+    screen_height: f32,
+) -> (i32, i32, i32, i32) {
+    let (width, height) = (width as f32, height as f32);
+    let (w, h) = match mode {
+        ScaleMode::Stretch => (screen_width, screen_height),
+        ScaleMode::Integer if screen_width >= width && screen_height >= height => {
+            let scale = (screen_width / width).min(screen_height / height).floor();
+            (width * scale, height * scale)
+        }
+        ScaleMode::Integer | ScaleMode::Fit => {
+            let scale = (screen_width / width).min(screen_height / height);
+            (width * scale, height * scale)
+        }
+    };
+    let (w, h) = (w.round() as i32, h.round() as i32);
+    let x = (screen_width as i32 - w) / 2;
+    let y = (screen_height as i32 - h) / 2;
+    (x, y, w, h)
+}
+
+mod shader {
+    use crate::graphics::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 in_pos;
+
+    varying lowp vec2 texcoord;
+
+    void main() {
+        gl_Position = vec4(in_pos, 0, 1);
+        texcoord = in_pos * 0.5 + 0.5;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec2 texcoord;
+
+    uniform sampler2D tex;
+
+    void main() {
+        gl_FragColor = texture2D(tex, texcoord);
+    }"#;
+
+    // Metal render targets have their first row at the top
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+
+    using namespace metal;
+
+    struct Vertex
+    {
+        float2 in_pos   [[attribute(0)]];
+    };
+
+    struct RasterizerData
+    {
+        float4 position [[position]];
+        float2 uv       [[user(locn0)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]])
+    {
+        RasterizerData out;
+
+        out.position = float4(v.in_pos.xy, 0.0, 1.0);
+        out.uv = float2(v.in_pos.x * 0.5 + 0.5, 0.5 - v.in_pos.y * 0.5);
+
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]])
+    {
+        return tex.sample(texSmplr, in.uv);
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["tex".to_string()],
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+        }
+    }
+}
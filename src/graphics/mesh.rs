@@ -0,0 +1,262 @@
+//! Vertex and index buffers kept together, as loaded from a model
+//!
+//! A `Mesh` owns its buffers and knows how to draw them: the index type, the
+//! primitive type, the named ranges of indices making its sub-meshes (one per
+//! material, typically) and, optionally, a bounding box for culling. Draws go
+//! through a `CommandBuffer`, executed with `CommandBuffer::flush`.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::command_buffer::CommandBuffer;
+//! # use miniquad::graphics::mesh::{BoundingBox, Mesh};
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! # let pipeline: Pipeline = unimplemented!();
+//! let positions: [[f32; 3]; 4] = [[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]];
+//! let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+//! let mut mesh = Mesh::new(&mut *ctx, &positions, &indices);
+//! mesh.add_sub_mesh("first", 0..3);
+//! mesh.add_sub_mesh("second", 3..6);
+//! mesh.set_bounds(BoundingBox::from_points(positions));
+//!
+//! let mut commands = CommandBuffer::new();
+//! ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+//! mesh.draw_sub_mesh(&mut commands, pipeline, &[], 1);
+//! commands.flush(&mut *ctx).unwrap();
+//! ctx.end_render_pass();
+//! ctx.commit_frame();
+//! ```
+
+use crate::graphics::command_buffer::{CommandBuffer, DrawElementsParams};
+use crate::graphics::*;
+
+use std::ops::Range;
+
+/// The index types of a mesh, `u16` or `u32`
+pub trait MeshIndex: Copy {
+    const TYPE: IndexType;
+}
+
+impl MeshIndex for u16 {
+    const TYPE: IndexType = IndexType::U16;
+}
+
+impl MeshIndex for u32 {
+    const TYPE: IndexType = IndexType::U32;
+}
+
+/// Axis aligned box containing all the vertices of a mesh
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub min: [f32; 3],
+    pub max: [f32; 3],
+}
+
+impl BoundingBox {
+    /// The smallest box containing `points`, None without any point
+    pub fn from_points(points: impl IntoIterator<Item = [f32; 3]>) -> Option<BoundingBox> {
+        points.into_iter().fold(None, |bounds, point| {
+            Some(match bounds {
+                None => BoundingBox {
+                    min: point,
+                    max: point,
+                },
+                Some(bounds) => bounds.union(&BoundingBox {
+                    min: point,
+                    max: point,
+                }),
+            })
+        })
+    }
+
+    /// The smallest box containing both
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        let mut union = *self;
+        for axis in 0..3 {
+            union.min[axis] = union.min[axis].min(other.min[axis]);
+            union.max[axis] = union.max[axis].max(other.max[axis]);
+        }
+        union
+    }
+
+    pub fn center(&self) -> [f32; 3] {
+        [0, 1, 2].map(|axis| (self.min[axis] + self.max[axis]) / 2.0)
+    }
+
+    pub fn size(&self) -> [f32; 3] {
+        [0, 1, 2].map(|axis| self.max[axis] - self.min[axis])
+    }
+}
+
+/// A range of indices of a mesh drawn on its own, e.g. with its own material
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubMesh {
+    pub name: String,
+    /// In indices, not bytes
+    pub elements: Range<i32>,
+}
+
+pub struct Mesh {
+    vertex_buffers: Vec<BufferId>,
+    index_buffer: BufferId,
+    index_type: IndexType,
+    primitive_type: PrimitiveType,
+    num_elements: i32,
+    sub_meshes: Vec<SubMesh>,
+    bounds: Option<BoundingBox>,
+}
+
+impl Mesh {
+    /// Immutable buffers holding `vertices` and `indices`, drawn as triangles
+    pub fn new<V, I: MeshIndex>(
+        ctx: &mut dyn RenderingBackend,
+        vertices: &[V],
+        indices: &[I],
+    ) -> Mesh {
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(vertices),
+        );
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(indices),
+        );
+        Mesh::from_buffers(
+            vec![vertex_buffer],
+            index_buffer,
+            I::TYPE,
+            indices.len() as i32,
+        )
+    }
+
+    /// A mesh owning already created buffers, e.g. one vertex buffer per
+    /// attribute. `num_elements` is the number of indices in `index_buffer`.
+    pub fn from_buffers(
+        vertex_buffers: Vec<BufferId>,
+        index_buffer: BufferId,
+        index_type: IndexType,
+        num_elements: i32,
+    ) -> Mesh {
+        Mesh {
+            vertex_buffers,
+            index_buffer,
+            index_type,
+            primitive_type: PrimitiveType::Triangles,
+            num_elements,
+            sub_meshes: vec![],
+            bounds: None,
+        }
+    }
+
+    /// Has to match `PipelineParams::primitive_type` of the pipelines drawing it
+    pub fn set_primitive_type(&mut self, primitive_type: PrimitiveType) {
+        self.primitive_type = primitive_type;
+    }
+
+    pub fn primitive_type(&self) -> PrimitiveType {
+        self.primitive_type
+    }
+
+    pub fn index_type(&self) -> IndexType {
+        self.index_type
+    }
+
+    /// Number of indices of the whole mesh
+    pub fn num_elements(&self) -> i32 {
+        self.num_elements
+    }
+
+    /// Name the indices `elements`, returns the index of the sub-mesh
+    pub fn add_sub_mesh(&mut self, name: &str, elements: Range<i32>) -> usize {
+        assert!(
+            0 <= elements.start
+                && elements.start <= elements.end
+                && elements.end <= self.num_elements,
+            "Sub-mesh {:?} outside of the {} mesh indices",
+            elements,
+            self.num_elements
+        );
+        self.sub_meshes.push(SubMesh {
+            name: name.to_string(),
+            elements,
+        });
+        self.sub_meshes.len() - 1
+    }
+
+    pub fn sub_meshes(&self) -> &[SubMesh] {
+        &self.sub_meshes
+    }
+
+    /// The first sub-mesh with this name
+    pub fn find_sub_mesh(&self, name: &str) -> Option<usize> {
+        self.sub_meshes
+            .iter()
+            .position(|sub_mesh| sub_mesh.name == name)
+    }
+
+    pub fn set_bounds(&mut self, bounds: Option<BoundingBox>) {
+        self.bounds = bounds;
+    }
+
+    pub fn bounds(&self) -> Option<BoundingBox> {
+        self.bounds
+    }
+
+    /// The mesh buffers with `images`, to draw the mesh without a command buffer
+    pub fn bindings(&self, images: &[TextureId]) -> Bindings {
+        Bindings {
+            vertex_buffers: self.vertex_buffers.clone(),
+            index_buffer: self.index_buffer,
+            images: images.to_vec(),
+        }
+    }
+
+    /// Queue a draw of the indices `elements` with `pipeline` and `images`
+    pub fn draw(
+        &self,
+        commands: &mut CommandBuffer,
+        pipeline: Pipeline,
+        images: &[TextureId],
+        elements: Range<i32>,
+    ) {
+        if elements.is_empty() {
+            return;
+        }
+        commands.draw_elements(
+            pipeline,
+            &self.bindings(images),
+            DrawElementsParams {
+                base_element: elements.start,
+                num_elements: elements.end - elements.start,
+                num_instances: 1,
+                primitive_type: self.primitive_type,
+                index_type: self.index_type.size() as u32,
+            },
+        );
+    }
+
+    /// Queue a draw of the sub-mesh `index`, see `add_sub_mesh`
+    pub fn draw_sub_mesh(
+        &self,
+        commands: &mut CommandBuffer,
+        pipeline: Pipeline,
+        images: &[TextureId],
+        index: usize,
+    ) {
+        let elements = self.sub_meshes[index].elements.clone();
+        self.draw(commands, pipeline, images, elements);
+    }
+
+    /// Queue a draw of the whole mesh
+    pub fn draw_all(&self, commands: &mut CommandBuffer, pipeline: Pipeline, images: &[TextureId]) {
+        self.draw(commands, pipeline, images, 0..self.num_elements);
+    }
+
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        for buffer in self.vertex_buffers {
+            ctx.delete_buffer(buffer);
+        }
+        ctx.delete_buffer(self.index_buffer);
+    }
+}
@@ -6,6 +6,12 @@ use crate::native::apple::{
 };
 
 use super::*;
+use crate::error::{GraphicsError, MiniquadError, ResourceError};
+use crate::Namespace;
+
+mod buffer_pool;
+
+use buffer_pool::MetalBufferPool;
 
 // https://developer.apple.com/metal/Metal-Feature-Set-Tables.pdf
 const MAX_UNIFORM_BUFFER_SIZE: u64 = 4 * 1024 * 1024;
@@ -15,6 +21,48 @@ const UNIFORM_BUFFER_ALIGN: u64 = 256;
 #[cfg(all(target_os = "ios", not(target_arch = "x86_64")))]
 const UNIFORM_BUFFER_ALIGN: u64 = 16;
 
+// Away from the uniforms at 0 and the vertex buffers right after them
+const CLEAR_PARAMS_INDEX: u64 = 30;
+
+/// A triangle covering the viewport, at the cleared depth and with the cleared color
+const CLEAR_REGION_SHADER: &str = r#"
+    #include <metal_stdlib>
+
+    using namespace metal;
+
+    struct ClearParams
+    {
+        float4 color;
+        float depth;
+    };
+
+    struct ClearColors
+    {
+        float4 color0 [[color(0)]];
+        float4 color1 [[color(1)]];
+    };
+
+    vertex float4 vertexShader(uint vid [[vertex_id]], constant ClearParams& params [[buffer(30)]])
+    {
+        float2 pos = float2((vid << 1) & 2, vid & 2);
+        return float4(pos * 2.0 - 1.0, params.depth, 1.0);
+    }
+
+    fragment ClearColors fragmentShader(constant ClearParams& params [[buffer(30)]])
+    {
+        ClearColors out;
+        out.color0 = params.color;
+        out.color1 = params.color;
+        return out;
+    }"#;
+
+#[repr(C)]
+struct ClearParams {
+    color: [f32; 4],
+    depth: f32,
+    _padding: [f32; 3],
+}
+
 impl From<VertexFormat> for MTLVertexFormat {
     fn from(vf: VertexFormat) -> Self {
         match vf {
@@ -50,6 +98,8 @@ impl From<UniformType> for MTLVertexFormat {
             UniformType::Int2 => MTLVertexFormat::Int2,
             UniformType::Int3 => MTLVertexFormat::Int3,
             UniformType::Int4 => MTLVertexFormat::Int4,
+            UniformType::Bool => MTLVertexFormat::UInt,
+            UniformType::Mat3 => MTLVertexFormat::Float3,
             UniformType::Mat4 => MTLVertexFormat::Float4,
         }
     }
@@ -145,12 +195,24 @@ impl From<VertexStep> for MTLVertexStepFunction {
     }
 }
 
+impl From<TextureWrap> for MTLSamplerAddressMode {
+    fn from(wrap: TextureWrap) -> Self {
+        match wrap {
+            TextureWrap::Repeat => MTLSamplerAddressMode::Repeat,
+            TextureWrap::Mirror => MTLSamplerAddressMode::MirrorRepeat,
+            TextureWrap::Clamp => MTLSamplerAddressMode::ClampToEdge,
+        }
+    }
+}
+
 impl From<PrimitiveType> for MTLPrimitiveType {
     fn from(primitive_type: PrimitiveType) -> Self {
         match primitive_type {
             PrimitiveType::Triangles => MTLPrimitiveType::Triangle,
             PrimitiveType::Lines => MTLPrimitiveType::Line,
             PrimitiveType::Points => MTLPrimitiveType::Point,
+            PrimitiveType::TriangleStrip => MTLPrimitiveType::TriangleStrip,
+            PrimitiveType::LineStrip => MTLPrimitiveType::LineStrip,
         }
     }
 }
@@ -191,6 +253,22 @@ fn roundup_ub_buffer(current_buffer: u64) -> u64 {
     ((current_buffer) + ((UNIFORM_BUFFER_ALIGN) - 1)) & !((UNIFORM_BUFFER_ALIGN) - 1)
 }
 
+/// Load and store actions of a MTLRenderPassAttachmentDescriptor
+unsafe fn set_attachment_action<T>(attachment: ObjcId, load: LoadAction<T>, store: bool) {
+    let load = match load {
+        LoadAction::Load => MTLLoadAction::Load,
+        LoadAction::Clear(_) => MTLLoadAction::Clear,
+        LoadAction::DontCare => MTLLoadAction::DontCare,
+    };
+    let store = if store {
+        MTLStoreAction::Store
+    } else {
+        MTLStoreAction::DontCare
+    };
+    msg_send_![attachment, setLoadAction: load];
+    msg_send_![attachment, setStoreAction: store];
+}
+
 // this scenario:
 // buffer.update(); draw(buffer); buffer.update(); draw(buffer);
 // is very problematic with metal's ownership model.
@@ -214,6 +292,8 @@ pub struct Buffer {
 struct ShaderInternal {
     vertex_function: ObjcId,
     fragment_function: ObjcId,
+    /// Bytes of `ShaderMeta::uniforms`
+    uniforms_size: usize,
     //uniforms: Vec<ShaderUniform>,
     // the distance, in bytes, between two uniforms in uniforms buffer
     //stride: u64,
@@ -223,6 +303,8 @@ struct RenderPassInternal {
     render_pass_desc: ObjcId,
     texture: Vec<TextureId>,
     _depth_texture: Option<TextureId>,
+    /// Mip level rendered to, the pass is that level's size
+    level: u32,
 }
 
 #[derive(Clone, Debug)]
@@ -231,7 +313,10 @@ struct PipelineInternal {
     depth_stencil_state: ObjcId,
     //layout: Vec<BufferLayout>,
     //attributes: Vec<VertexAttributeInternal>,
-    _shader: ShaderId,
+    shader: ShaderId,
+    primitive_type: MTLPrimitiveType,
+    index_type: MTLIndexType,
+    desc: PipelineDesc,
     //params: PipelineParams,
 }
 
@@ -241,15 +326,22 @@ struct Texture {
     sampler: ObjcId,
     sampler_descriptor: ObjcId,
     params: TextureParams,
+    // CVMetalTexture owning `texture` for ExternalImage::PixelBuffer textures, null otherwise
+    cv_texture: *mut std::ffi::c_void,
 }
-struct Textures(Vec<Texture>);
+struct Textures(Vec<Texture>, Namespace);
 
 impl Textures {
+    fn push(&mut self, texture: Texture) -> TextureId {
+        self.0.push(texture);
+        TextureId(TextureIdInner::Managed(self.1.id(self.0.len() - 1)))
+    }
+
     fn get(&self, texture: TextureId) -> Texture {
         match texture.0 {
             TextureIdInner::Raw(RawId::Metal(_texture)) => unimplemented!(),
             TextureIdInner::Raw(RawId::OpenGl(_)) => panic!("Gl texture in Metal context!"),
-            TextureIdInner::Managed(texture) => self.0[texture],
+            TextureIdInner::Managed(texture) => self.0[self.1.index(texture)],
         }
     }
 
@@ -257,7 +349,7 @@ impl Textures {
         match texture.0 {
             TextureIdInner::Raw(RawId::Metal(_texture)) => unimplemented!(),
             TextureIdInner::Raw(RawId::OpenGl(_)) => panic!("Gl texture in Metal context!"),
-            TextureIdInner::Managed(texture) => &mut self.0[texture],
+            TextureIdInner::Managed(texture) => &mut self.0[self.1.index(texture)],
         }
     }
 }
@@ -267,6 +359,8 @@ pub struct MetalContext {
     pipelines: Vec<PipelineInternal>,
     textures: Textures,
     passes: Vec<RenderPassInternal>,
+    /// IDs of the resources above, see `Namespace`
+    namespace: Namespace,
     command_queue: ObjcId,
     command_buffer: Option<ObjcId>,
     render_encoder: Option<ObjcId>,
@@ -279,6 +373,21 @@ pub struct MetalContext {
     // cached pipeline from apply_pipeline
     current_pipeline: Option<Pipeline>,
     current_ub_offset: u64,
+    buffer_pool: MetalBufferPool,
+    // size of the current pass render target, from begin_pass
+    pass_size: (f64, f64),
+    // viewport and scissor of the current pass, None for the whole pass
+    viewport: Option<MTLViewport>,
+    scissor: Option<(i32, i32, i32, i32)>,
+    // pipeline and depth stencil states of clear_region, by cleared (color, depth, stencil)
+    clear_pipelines: std::collections::HashMap<(bool, bool, bool), (ObjcId, ObjcId)>,
+    // CVMetalTextureCache for external textures, created on first use
+    cv_texture_cache: *mut std::ffi::c_void,
+    software_cursor: Option<super::software_cursor::CursorRenderer>,
+    clear_colors: ClearColors,
+    /// Frames committed, see `RenderingBackend::frame_index`
+    frame_index: u64,
+    deferred_deletes: super::deferred_delete::DeferredDeletes,
 }
 
 impl Default for MetalContext {
@@ -289,12 +398,20 @@ impl Default for MetalContext {
 
 impl MetalContext {
     pub fn new() -> MetalContext {
+        #[cfg(feature = "tracy")]
+        super::profiling::tracy::start();
+
         unsafe {
             let view = crate::window::apple_view();
             assert!(!view.is_null());
             let device: ObjcId = msg_send![view, device];
             assert!(!device.is_null());
             let command_queue: ObjcId = msg_send![device, newCommandQueue];
+            crate::native_display().lock().unwrap().context_handle =
+                Some(crate::ContextHandle::Metal {
+                    device: device as _,
+                    command_queue: command_queue as _,
+                });
 
             if false {
                 let capture_manager = msg_send_![class![MTLCaptureManager], sharedCaptureManager];
@@ -356,6 +473,7 @@ impl MetalContext {
                 msg_send![device, newBufferWithLength:MAX_UNIFORM_BUFFER_SIZE
                           options:options],
             ];
+            let namespace = Namespace::next();
 
             MetalContext {
                 command_queue,
@@ -366,16 +484,249 @@ impl MetalContext {
                 buffers: vec![],
                 shaders: vec![],
                 pipelines: vec![],
-                textures: Textures(Vec::new()),
+                textures: Textures(Vec::new(), namespace),
                 passes: vec![],
+                namespace,
                 index_buffer: None,
                 current_pipeline: None,
                 uniform_buffers,
                 current_frame_index: 1,
                 current_ub_offset: 0,
+                buffer_pool: MetalBufferPool::new(),
+                pass_size: (0., 0.),
+                viewport: None,
+                scissor: None,
+                clear_pipelines: Default::default(),
+                cv_texture_cache: std::ptr::null_mut(),
+                software_cursor: None,
+                clear_colors: ClearColors::default(),
+                frame_index: 0,
+                deferred_deletes: Default::default(),
             }
         }
     }
+
+    /// Report a command buffer that ended with an error or ran longer than
+    /// `device_lost::gpu_timeout`. `gpu_time` in seconds.
+    unsafe fn check_device_lost(&self, command_buffer: ObjcId, gpu_time: f64) {
+        use super::device_lost::{self, DeviceLost, DeviceLostReason};
+
+        // MTLCommandBufferStatusError
+        const STATUS_ERROR: u64 = 5;
+
+        let status: u64 = msg_send![command_buffer, status];
+        let (reason, message) = if status == STATUS_ERROR {
+            let error: ObjcId = msg_send![command_buffer, error];
+            let message = if error.is_null() {
+                "the command buffer failed".to_string()
+            } else {
+                let description: ObjcId = msg_send![error, localizedDescription];
+                apple_util::nsstring_to_string(description)
+            };
+            (DeviceLostReason::CommandBufferError, message)
+        } else if gpu_time > device_lost::gpu_timeout().as_secs_f64() {
+            (
+                DeviceLostReason::Timeout,
+                format!("a frame ran for {:.1} s on the GPU", gpu_time),
+            )
+        } else {
+            return;
+        };
+        let name: ObjcId = msg_send![self.device, name];
+        device_lost::report(DeviceLost {
+            reason,
+            gpu_time_ms: (gpu_time > 0.0).then_some(gpu_time * 1000.0),
+            device: apple_util::nsstring_to_string(name),
+            message,
+        });
+    }
+
+    /// Sampler state and its descriptor for the filters of `params`
+    unsafe fn new_sampler(&self, params: &TextureParams) -> (ObjcId, ObjcId) {
+        let sampler_descriptor = msg_send_![class!(MTLSamplerDescriptor), new];
+        msg_send_![sampler_descriptor, retain];
+        let min_filter = match params.min_filter {
+            FilterMode::Nearest => MTLSamplerMinMagFilter::Nearest,
+            FilterMode::Linear => MTLSamplerMinMagFilter::Linear,
+        };
+
+        let mag_filter = match params.mag_filter {
+            FilterMode::Nearest => MTLSamplerMinMagFilter::Nearest,
+            FilterMode::Linear => MTLSamplerMinMagFilter::Linear,
+        };
+
+        let mipmap_filter = match params.mipmap_filter {
+            MipmapFilterMode::None => MTLSamplerMipFilter::NotMipmapped,
+            MipmapFilterMode::Nearest => MTLSamplerMipFilter::Nearest,
+            MipmapFilterMode::Linear => MTLSamplerMipFilter::Linear,
+        };
+        msg_send_![sampler_descriptor, setMinFilter: min_filter];
+        msg_send_![sampler_descriptor, setMagFilter: mag_filter];
+        msg_send_![sampler_descriptor, setMipFilter: mipmap_filter];
+        let wrap: MTLSamplerAddressMode = params.wrap.into();
+        msg_send_![sampler_descriptor, setSAddressMode: wrap];
+        msg_send_![sampler_descriptor, setTAddressMode: wrap];
+
+        let sampler_state = msg_send_![
+            self.device,
+            newSamplerStateWithDescriptor: sampler_descriptor
+        ];
+        (sampler_state, sampler_descriptor)
+    }
+
+    /// Edit the sampler descriptor and params of `texture` with `f` and swap
+    /// its sampler state for one made from the descriptor. Encoders retain the
+    /// old one while they use it.
+    unsafe fn update_sampler(
+        &mut self,
+        texture: TextureId,
+        f: impl FnOnce(ObjcId, &mut TextureParams),
+    ) {
+        let device = self.device;
+        let texture = self.textures.get_mut(texture);
+        f(texture.sampler_descriptor, &mut texture.params);
+        msg_send_![texture.sampler, release];
+        texture.sampler = msg_send_![
+            device,
+            newSamplerStateWithDescriptor: texture.sampler_descriptor
+        ];
+    }
+
+    /// Get current buffer pool statistics
+    pub fn buffer_pool_stats(&self) -> super::buffer_pool::BufferPoolStats {
+        self.buffer_pool.get_stats()
+    }
+
+    /// Print a detailed buffer pool performance report
+    pub fn print_buffer_pool_report(&self) {
+        self.buffer_pool.get_stats().print_report();
+    }
+
+    /// Release unused pooled buffers until the pool uses at most `target_bytes`.
+    /// Returns the amount of bytes freed.
+    pub fn trim_buffer_pool(&mut self, target_bytes: usize) -> usize {
+        self.buffer_pool.trim(target_bytes)
+    }
+
+    /// The states `clear_region` draws with, created on first use
+    fn clear_pipeline(&mut self, color: bool, depth: bool, stencil: bool) -> (ObjcId, ObjcId) {
+        if let Some(states) = self.clear_pipelines.get(&(color, depth, stencil)) {
+            return *states;
+        }
+        let shader = self
+            .new_shader(
+                ShaderSource::Msl {
+                    program: CLEAR_REGION_SHADER,
+                },
+                ShaderMeta {
+                    images: vec![],
+                    uniforms: UniformBlockLayout { uniforms: vec![] },
+                },
+            )
+            .unwrap();
+        let shader = &self.shaders[self.namespace.index(shader.0)];
+
+        unsafe {
+            let descriptor = msg_send_![class!(MTLRenderPipelineDescriptor), new];
+            msg_send_![descriptor, setVertexFunction:shader.vertex_function];
+            msg_send_![descriptor, setFragmentFunction:shader.fragment_function];
+            let color_attachments = msg_send_![descriptor, colorAttachments];
+            let view_pixel_format: MTLPixelFormat = msg_send![self.view, colorPixelFormat];
+            // MTLColorWriteMaskAll or MTLColorWriteMaskNone
+            let write_mask: u64 = if color { 0xf } else { 0 };
+            for i in 0..2 {
+                let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: i];
+                msg_send_![color_attachment, setPixelFormat: view_pixel_format];
+                msg_send_![color_attachment, setWriteMask: write_mask];
+            }
+            msg_send_![
+                descriptor,
+                setDepthAttachmentPixelFormat: MTLPixelFormat::Depth32Float_Stencil8
+            ];
+            msg_send_![
+                descriptor,
+                setStencilAttachmentPixelFormat: MTLPixelFormat::Depth32Float_Stencil8
+            ];
+
+            let mut error: ObjcId = nil;
+            let pipeline_state: ObjcId = msg_send![
+                self.device,
+                newRenderPipelineStateWithDescriptor: descriptor
+                error: &mut error
+            ];
+            if pipeline_state.is_null() {
+                let description: ObjcId = msg_send![error, localizedDescription];
+                let string = apple_util::nsstring_to_string(description);
+                panic!("newRenderPipelineStateWithDescriptor error: {}", string);
+            }
+
+            let depth_stencil_desc = msg_send_![class!(MTLDepthStencilDescriptor), new];
+            msg_send_![depth_stencil_desc, setDepthWriteEnabled: BOOL::from(depth)];
+            msg_send_![depth_stencil_desc, setDepthCompareFunction: MTLCompareFunction::Always];
+            if stencil {
+                let stencil_desc = msg_send_![class!(MTLStencilDescriptor), new];
+                msg_send_![stencil_desc, setStencilCompareFunction: MTLCompareFunction::Always];
+                // MTLStencilOperationReplace, with the reference value set by clear_region
+                msg_send_![stencil_desc, setDepthStencilPassOperation: 2u64];
+                msg_send_![stencil_desc, setWriteMask: 0xffu32];
+                msg_send_![depth_stencil_desc, setFrontFaceStencil: stencil_desc];
+                msg_send_![depth_stencil_desc, setBackFaceStencil: stencil_desc];
+            }
+            let depth_stencil_state = msg_send_![
+                self.device,
+                newDepthStencilStateWithDescriptor: depth_stencil_desc
+            ];
+
+            let states = (pipeline_state, depth_stencil_state);
+            self.clear_pipelines.insert((color, depth, stencil), states);
+            states
+        }
+    }
+
+    /// A render pass for validated targets
+    fn new_pass(&mut self, color: &[RenderTarget], depth: Option<RenderTarget>) -> RenderPass {
+        unsafe {
+            let render_pass_desc =
+                msg_send_![class!(MTLRenderPassDescriptor), renderPassDescriptor];
+            msg_send_![render_pass_desc, retain];
+            assert!(!render_pass_desc.is_null());
+            for (i, target) in color.iter().enumerate() {
+                let color_texture = self.textures.get(target.texture).texture;
+                let color_attachment = msg_send_![msg_send_![render_pass_desc, colorAttachments], objectAtIndexedSubscript:i];
+                msg_send_![color_attachment, setTexture: color_texture];
+                msg_send_![color_attachment, setLevel: target.level as u64];
+                msg_send_![color_attachment, setSlice: target.layer as u64];
+                msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
+                msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
+            }
+            if let Some(target) = depth {
+                let depth_texture = self.textures.get(target.texture).texture;
+
+                let depth_attachment = msg_send_![render_pass_desc, depthAttachment];
+                msg_send_![depth_attachment, setTexture: depth_texture];
+                msg_send_![depth_attachment, setLevel: target.level as u64];
+                msg_send_![depth_attachment, setSlice: target.layer as u64];
+                msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Clear];
+                msg_send_![depth_attachment, setStoreAction: MTLStoreAction::Store];
+                msg_send_![depth_attachment, setClearDepth:1.];
+
+                let stencil_attachment = msg_send_![render_pass_desc, stencilAttachment];
+                msg_send_![stencil_attachment, setTexture: depth_texture];
+                msg_send_![stencil_attachment, setLevel: target.level as u64];
+                msg_send_![stencil_attachment, setSlice: target.layer as u64];
+            }
+            let pass = RenderPassInternal {
+                render_pass_desc,
+                texture: color.iter().map(|target| target.texture).collect(),
+                _depth_texture: depth.map(|target| target.texture),
+                level: color.first().or(depth.as_ref()).unwrap().level,
+            };
+
+            self.passes.push(pass);
+
+            RenderPass(self.namespace.id(self.passes.len() - 1))
+        }
+    }
 }
 
 impl RenderingBackend for MetalContext {
@@ -387,37 +738,199 @@ impl RenderingBackend for MetalContext {
             features: Features {
                 instancing: true,
                 resolve_attachments: false,
+                persistent_mapping: false,
+                primitive_restart: true,
+                multisample_textures: false,
+                external_textures: false,
+                bc3_textures: false,
+                bc7_textures: false,
+                etc2_textures: false,
+                astc_textures: false,
+                immutable_textures: true,
             },
+            adapter: unsafe {
+                let name: ObjcId = msg_send![self.device, name];
+                apple_util::nsstring_to_string(name)
+            },
+            default_framebuffer: unsafe {
+                // Raw MTLPixelFormat values, not all of them are in the enum
+                const INVALID: u64 = 0;
+                const BGRA8_UNORM_SRGB: u64 = 81;
+                let color: u64 = msg_send![self.view, colorPixelFormat];
+                let depth_stencil: u64 = msg_send![self.view, depthStencilPixelFormat];
+                let sample_count: u64 = msg_send![self.view, sampleCount];
+                FramebufferInfo {
+                    red_bits: 8,
+                    green_bits: 8,
+                    blue_bits: 8,
+                    alpha_bits: 8,
+                    depth_bits: if depth_stencil == INVALID { 0 } else { 32 },
+                    stencil_bits: if depth_stencil == MTLPixelFormat::Depth32Float_Stencil8 as u64 {
+                        8
+                    } else {
+                        0
+                    },
+                    sample_count: sample_count as i32,
+                    srgb: color == BGRA8_UNORM_SRGB,
+                }
+            },
+            // Metal has 31 buffers and 128 textures per stage
+            limits: Limits::default(),
         }
     }
     fn buffer_size(&mut self, buffer: BufferId) -> usize {
-        let buffer = &self.buffers[buffer.0];
+        let buffer = &self.buffers[self.namespace.index(buffer.0)];
         buffer.size
     }
     fn delete_buffer(&mut self, buffer: BufferId) {
-        let buffer = &self.buffers[buffer.0];
-        unsafe {
-            for buffer in &buffer.raw {
-                msg_send_![*buffer, release];
-            }
+        super::profiling::untrack(super::profiling::GpuResourceId::Buffer(buffer));
+        let buffer = &self.buffers[self.namespace.index(buffer.0)];
+        for raw in &buffer.raw {
+            self.buffer_pool.release(*raw, buffer.size);
         }
     }
     fn delete_texture(&mut self, texture: TextureId) {
+        super::profiling::untrack(super::profiling::GpuResourceId::Texture(texture));
         let texture = self.textures.get(texture);
         unsafe {
-            msg_send_![texture.texture, release];
+            if texture.cv_texture.is_null() {
+                msg_send_![texture.texture, release];
+            } else {
+                CFRelease(texture.cv_texture as _);
+            }
         }
     }
-    fn apply_viewport(&mut self, _x: i32, _y: i32, _w: i32, _h: i32) {}
-    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+    fn new_external_texture(&mut self, image: ExternalImage) -> Result<TextureId, MiniquadError> {
+        if !matches!(image, ExternalImage::PixelBuffer(_)) {
+            return Err(GraphicsError::Unsupported(format!(
+                "{:?} external textures are not supported on metal",
+                image
+            ))
+            .into());
+        }
+        // CVPixelBuffers come as BGRA, sampled as RGBA like any RGBA8 texture
+        let params = TextureParams::default();
+        let (sampler, sampler_descriptor) = unsafe { self.new_sampler(&params) };
+        let texture = self.textures.push(Texture {
+            texture: nil,
+            sampler,
+            sampler_descriptor,
+            params,
+            cv_texture: std::ptr::null_mut(),
+        });
+        super::profiling::track_texture(texture, &params);
+
+        if let Err(err) = self.texture_update_external(texture, image) {
+            self.delete_texture(texture);
+            return Err(err);
+        }
+        Ok(texture)
+    }
+    fn texture_update_external(
+        &mut self,
+        texture: TextureId,
+        image: ExternalImage,
+    ) -> Result<(), MiniquadError> {
+        let ExternalImage::PixelBuffer(pixel_buffer) = image else {
+            return Err(MiniquadError::InvalidParameter(format!(
+                "{:?} can't update this texture",
+                image
+            )));
+        };
+        unsafe {
+            if self.cv_texture_cache.is_null() {
+                let status = CVMetalTextureCacheCreate(
+                    std::ptr::null(),
+                    std::ptr::null(),
+                    self.device,
+                    std::ptr::null(),
+                    &mut self.cv_texture_cache,
+                );
+                if status != 0 {
+                    return Err(GraphicsError::CreationFailed(format!(
+                        "CVMetalTextureCacheCreate failed: {}",
+                        status
+                    ))
+                    .into());
+                }
+            }
+            CVMetalTextureCacheFlush(self.cv_texture_cache, 0);
+
+            let width = CVPixelBufferGetWidth(pixel_buffer);
+            let height = CVPixelBufferGetHeight(pixel_buffer);
+            let mut cv_texture = std::ptr::null_mut();
+            let status = CVMetalTextureCacheCreateTextureFromImage(
+                std::ptr::null(),
+                self.cv_texture_cache,
+                pixel_buffer,
+                std::ptr::null(),
+                MTLPixelFormat::BGRA8Unorm,
+                width,
+                height,
+                0,
+                &mut cv_texture,
+            );
+            if status != 0 || cv_texture.is_null() {
+                return Err(ResourceError::InvalidState(format!(
+                    "CVMetalTextureCacheCreateTextureFromImage failed: {}",
+                    status
+                ))
+                .into());
+            }
+
+            let t = self.textures.get_mut(texture);
+            if !t.cv_texture.is_null() {
+                CFRelease(t.cv_texture as _);
+            }
+            t.cv_texture = cv_texture;
+            t.texture = CVMetalTextureGetTexture(cv_texture);
+            t.params.width = width as u32;
+            t.params.height = height as u32;
+            let params = t.params;
+            super::profiling::track_texture(texture, &params);
+        }
+        Ok(())
+    }
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.apply_viewport_f(x as f32, y as f32, w as f32, h as f32, 0., 1.);
+    }
+    fn apply_viewport_f(&mut self, x: f32, y: f32, w: f32, h: f32, min_depth: f32, max_depth: f32) {
         assert!(self.render_encoder.is_some());
 
-        let (_, screen_height) = crate::window::screen_size();
-        let r = MTLScissorRect {
-            x: x as _,
-            y: (screen_height as i32 - (y + h)) as u64,
-            width: w as _,
-            height: h as _,
+        let (_, pass_height) = self.pass_size;
+        let viewport = MTLViewport {
+            origin_x: x as f64,
+            origin_y: pass_height - (y + h) as f64,
+            width: w as f64,
+            height: h as f64,
+            znear: min_depth as f64,
+            zfar: max_depth as f64,
+        };
+        self.viewport = Some(viewport);
+        unsafe { msg_send_![self.render_encoder.unwrap(), setViewport: viewport] };
+    }
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.apply_scissor(Some((x, y, w, h)));
+    }
+    fn apply_scissor(&mut self, rect: Option<(i32, i32, i32, i32)>) {
+        assert!(self.render_encoder.is_some());
+        self.scissor = rect;
+
+        let (pass_width, pass_height) = self.pass_size;
+        let r = match rect {
+            Some((x, y, w, h)) => MTLScissorRect {
+                x: x as _,
+                y: (pass_height as i32 - (y + h)) as u64,
+                width: w as _,
+                height: h as _,
+            },
+            // Metal has no switch for it, the scissor covers the whole pass instead
+            None => MTLScissorRect {
+                x: 0,
+                y: 0,
+                width: pass_width as _,
+                height: pass_height as _,
+            },
         };
         unsafe { msg_send_![self.render_encoder.unwrap(), setScissorRect: r] };
     }
@@ -427,62 +940,50 @@ impl RenderingBackend for MetalContext {
         filter: FilterMode,
         mipmap_filter: MipmapFilterMode,
     ) {
-        let texture = self.textures.get_mut(texture);
-
-        let filter = match filter {
+        let mtl_filter = match filter {
             FilterMode::Nearest => MTLSamplerMinMagFilter::Nearest,
             FilterMode::Linear => MTLSamplerMinMagFilter::Linear,
         };
 
-        let mipmap_filter = match mipmap_filter {
+        let mtl_mipmap_filter = match mipmap_filter {
             MipmapFilterMode::None => MTLSamplerMipFilter::NotMipmapped,
             MipmapFilterMode::Nearest => MTLSamplerMipFilter::Nearest,
             MipmapFilterMode::Linear => MTLSamplerMipFilter::Linear,
         };
 
-        texture.sampler = unsafe {
-            let sampler_descriptor = msg_send_![class!(MTLSamplerDescriptor), new];
-            msg_send_![sampler_descriptor, setMinFilter: filter];
-            msg_send_![sampler_descriptor, setMipFilter: mipmap_filter];
-            msg_send_![
-                self.device,
-                newSamplerStateWithDescriptor: sampler_descriptor
-            ]
+        unsafe {
+            self.update_sampler(texture, |sampler_descriptor, params| {
+                msg_send_![sampler_descriptor, setMinFilter: mtl_filter];
+                msg_send_![sampler_descriptor, setMipFilter: mtl_mipmap_filter];
+                params.min_filter = filter;
+                params.mipmap_filter = mipmap_filter;
+            })
         };
     }
     fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
-        let texture = self.textures.get_mut(texture);
-
-        let filter = match filter {
+        let mtl_filter = match filter {
             FilterMode::Nearest => MTLSamplerMinMagFilter::Nearest,
             FilterMode::Linear => MTLSamplerMinMagFilter::Linear,
         };
 
-        texture.sampler = unsafe {
-            msg_send_![texture.sampler_descriptor, setMagFilter: filter];
-            msg_send_![self.device, newSamplerStateWithDescriptor: texture.sampler_descriptor]
+        unsafe {
+            self.update_sampler(texture, |sampler_descriptor, params| {
+                msg_send_![sampler_descriptor, setMagFilter: mtl_filter];
+                params.mag_filter = filter;
+            })
         };
     }
     fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, wrap_y: TextureWrap) {
-        let texture = self.textures.get_mut(texture);
+        let wrap_s: MTLSamplerAddressMode = wrap_x.into();
+        let wrap_t: MTLSamplerAddressMode = wrap_y.into();
 
-        let wrap_s = match wrap_x {
-            TextureWrap::Repeat => MTLSamplerAddressMode::Repeat,
-            TextureWrap::Mirror => MTLSamplerAddressMode::MirrorRepeat,
-            TextureWrap::Clamp => MTLSamplerAddressMode::ClampToEdge,
-        };
-
-        let wrap_t = match wrap_y {
-            TextureWrap::Repeat => MTLSamplerAddressMode::Repeat,
-            TextureWrap::Mirror => MTLSamplerAddressMode::MirrorRepeat,
-            TextureWrap::Clamp => MTLSamplerAddressMode::ClampToEdge,
-        };
-
-        texture.sampler = unsafe {
-            //msg_send_![texture.sampler_descriptor, setRAddressMode: wrap];
-            msg_send_![texture.sampler_descriptor, setSAddressMode: wrap_s];
-            msg_send_![texture.sampler_descriptor, setTAddressMode: wrap_t];
-            msg_send_![self.device, newSamplerStateWithDescriptor: texture.sampler_descriptor]
+        unsafe {
+            self.update_sampler(texture, |sampler_descriptor, params| {
+                msg_send_![sampler_descriptor, setSAddressMode: wrap_s];
+                msg_send_![sampler_descriptor, setTAddressMode: wrap_t];
+                // TextureParams has a single wrap mode
+                params.wrap = wrap_x;
+            })
         };
     }
     fn texture_resize(
@@ -533,63 +1034,143 @@ impl RenderingBackend for MetalContext {
         self.end_render_pass();
     }
 
-    fn new_render_pass_mrt(
+    fn clear_region(
+        &mut self,
+        rect: (i32, i32, i32, i32),
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        assert!(
+            self.render_encoder.is_some(),
+            "clear_region before begin_pass"
+        );
+        if color.is_none() && depth.is_none() && stencil.is_none() {
+            return;
+        }
+        let render_encoder = self.render_encoder.unwrap();
+        let (pipeline_state, depth_stencil_state) =
+            self.clear_pipeline(color.is_some(), depth.is_some(), stencil.is_some());
+
+        let (r, g, b, a) = color.unwrap_or_default();
+        let params = ClearParams {
+            color: [r, g, b, a],
+            depth: depth.unwrap_or_default(),
+            _padding: [0.; 3],
+        };
+        let (pass_width, pass_height) = self.pass_size;
+        let whole_pass = MTLViewport {
+            origin_x: 0.,
+            origin_y: 0.,
+            width: pass_width,
+            height: pass_height,
+            znear: 0.,
+            zfar: 1.,
+        };
+        let (viewport, scissor) = (self.viewport, self.scissor);
+
+        unsafe {
+            msg_send_![render_encoder, setRenderPipelineState: pipeline_state];
+            msg_send_![render_encoder, setDepthStencilState: depth_stencil_state];
+            msg_send_![render_encoder, setStencilReferenceValue: stencil.unwrap_or(0) as u32];
+            msg_send_![render_encoder, setViewport: whole_pass];
+            self.apply_scissor(Some(rect));
+            msg_send_![render_encoder,
+                       setVertexBytes:&params as *const _ as *const std::ffi::c_void
+                       length:std::mem::size_of::<ClearParams>() as u64
+                       atIndex:CLEAR_PARAMS_INDEX];
+            msg_send_![render_encoder,
+                       setFragmentBytes:&params as *const _ as *const std::ffi::c_void
+                       length:std::mem::size_of::<ClearParams>() as u64
+                       atIndex:CLEAR_PARAMS_INDEX];
+            msg_send_![render_encoder,
+                       drawPrimitives:MTLPrimitiveType::Triangle
+                       vertexStart:0u64
+                       vertexCount:3u64];
+
+            // Back to the state of the caller
+            msg_send_![render_encoder, setStencilReferenceValue: 0u32];
+            msg_send_![render_encoder, setViewport: viewport.unwrap_or(whole_pass)];
+            self.apply_scissor(scissor);
+            if let Some(pipeline) = self.current_pipeline {
+                let pipeline = &self.pipelines[self.namespace.index(pipeline.0)];
+                msg_send_![render_encoder, setRenderPipelineState: pipeline.pipeline_state];
+                msg_send_![render_encoder, setDepthStencilState: pipeline.depth_stencil_state];
+            }
+        }
+    }
+
+    fn try_new_render_pass_mrt(
         &mut self,
         color_img: &[TextureId],
         resolve_img: Option<&[TextureId]>,
         depth_img: Option<TextureId>,
-    ) -> RenderPass {
+    ) -> Result<RenderPass, MiniquadError> {
         if resolve_img.is_some() {
-            unimplemented!("resolve textures are not yet implemented on metal");
+            return Err(GraphicsError::Unsupported(
+                "resolve textures are not yet implemented on metal".to_string(),
+            )
+            .into());
         }
-        unsafe {
-            let render_pass_desc =
-                msg_send_![class!(MTLRenderPassDescriptor), renderPassDescriptor];
-            msg_send_![render_pass_desc, retain];
-            assert!(!render_pass_desc.is_null());
-            for (i, color_img) in color_img.iter().enumerate() {
-                let color_texture = self.textures.get(*color_img).texture;
-                let color_attachment = msg_send_![msg_send_![render_pass_desc, colorAttachments], objectAtIndexedSubscript:i];
-                msg_send_![color_attachment, setTexture: color_texture];
-                msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
-                msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
-            }
-            if let Some(depth_img) = depth_img {
-                let depth_texture = self.textures.get(depth_img).texture;
-
-                let depth_attachment = msg_send_![render_pass_desc, depthAttachment];
-                msg_send_![depth_attachment, setTexture: depth_texture];
-                msg_send_![depth_attachment, setLoadAction: MTLLoadAction::Clear];
-                msg_send_![depth_attachment, setStoreAction: MTLStoreAction::Store];
-                msg_send_![depth_attachment, setClearDepth:1.];
+        if color_img.is_empty() && depth_img.is_none() {
+            return Err(MiniquadError::InvalidParameter(
+                "Render pass should have at least one non-none target".to_string(),
+            ));
+        }
+        let multisampled = color_img
+            .iter()
+            .chain(depth_img.iter())
+            .any(|texture| self.textures.get(*texture).params.sample_count > 1);
+        if multisampled {
+            return Err(GraphicsError::Unsupported(
+                "multisampled render passes are not yet implemented on metal".to_string(),
+            )
+            .into());
+        }
+        let color: Vec<RenderTarget> = color_img.iter().copied().map(RenderTarget::new).collect();
+        Ok(self.new_pass(&color, depth_img.map(RenderTarget::new)))
+    }
 
-                let stencil_attachment = msg_send_![render_pass_desc, stencilAttachment];
-                msg_send_![stencil_attachment, setTexture: depth_texture];
+    fn try_new_render_pass_targets(
+        &mut self,
+        color: &[RenderTarget],
+        depth: Option<RenderTarget>,
+    ) -> Result<RenderPass, MiniquadError> {
+        if color.is_empty() && depth.is_none() {
+            return Err(MiniquadError::InvalidParameter(
+                "Render pass should have at least one non-none target".to_string(),
+            ));
+        }
+        for target in color.iter().chain(depth.iter()) {
+            let params = self.textures.get(target.texture).params;
+            if params.sample_count > 1 {
+                return Err(GraphicsError::Unsupported(
+                    "multisampled render passes are not yet implemented on metal".to_string(),
+                )
+                .into());
             }
-            let pass = RenderPassInternal {
-                render_pass_desc,
-                texture: color_img.to_vec(),
-                _depth_texture: depth_img,
-            };
-
-            self.passes.push(pass);
-
-            RenderPass(self.passes.len() - 1)
+            target.validate(&params)?;
         }
+        Ok(self.new_pass(color, depth))
     }
 
     fn delete_render_pass(&mut self, render_pass: RenderPass) {
-        let render_pass = &self.passes[render_pass.0];
+        let render_pass = &self.passes[self.namespace.index(render_pass.0)];
         unsafe {
             msg_send_![render_pass.render_pass_desc, release];
         }
     }
 
     fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
-        &self.passes[render_pass.0].texture
+        &self.passes[self.namespace.index(render_pass.0)].texture
     }
 
-    fn new_buffer(&mut self, _: BufferType, _usage: BufferUsage, data: BufferSource) -> BufferId {
+    fn try_new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> Result<BufferId, MiniquadError> {
         let mut raw = [nil; BUFFERS_IN_ROTATION];
         let size = match &data {
             BufferSource::Slice(data) => data.size,
@@ -597,30 +1178,15 @@ impl RenderingBackend for MetalContext {
         };
         #[allow(clippy::needless_range_loop)]
         for i in 0..BUFFERS_IN_ROTATION {
-            let buffer: ObjcId = if let BufferSource::Slice(data) = &data {
+            // Pooled buffers are always in shared storage mode,
+            // so the data may be just copied into the buffer contents.
+            let buffer = self.buffer_pool.acquire(self.device, size);
+            if let BufferSource::Slice(data) = &data {
                 debug_assert!(data.is_slice);
-                let size = data.size as u64;
-                unsafe {
-                    msg_send![self.device,
-                              newBufferWithBytes:data.ptr
-                              length:size
-                              options:MTLResourceOptions::StorageModeShared]
-                }
-            } else {
-                #[cfg(target_os = "macos")]
-                let options = MTLResourceOptions::CPUCacheModeWriteCombined
-                    | MTLResourceOptions::StorageModeManaged;
-                #[cfg(target_os = "ios")]
-                let options = MTLResourceOptions::CPUCacheModeWriteCombined;
                 unsafe {
-                    msg_send![self.device,
-                              newBufferWithLength:size
-                              options:options]
+                    let dest: *mut std::ffi::c_void = msg_send![buffer, contents];
+                    std::ptr::copy(data.ptr, dest, data.size);
                 }
-            };
-
-            unsafe {
-                msg_send_![buffer, retain];
             }
             raw[i] = buffer;
         }
@@ -631,7 +1197,10 @@ impl RenderingBackend for MetalContext {
             next_value: 0,
         };
         self.buffers.push(buffer);
-        BufferId(self.buffers.len() - 1)
+        let buffer = BufferId(self.namespace.id(self.buffers.len() - 1));
+        let bytes = MetalBufferPool::capacity(size) * BUFFERS_IN_ROTATION;
+        super::profiling::track_buffer(buffer, type_, usage, bytes);
+        Ok(buffer)
     }
 
     fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
@@ -639,23 +1208,35 @@ impl RenderingBackend for MetalContext {
             BufferSource::Slice(data) => data,
             _ => panic!("buffer_update expects BufferSource::slice"),
         };
-        let buffer = &mut self.buffers[buffer.0];
+        let buffer = &mut self.buffers[self.namespace.index(buffer.0)];
         assert!(data.size <= buffer.size);
 
         unsafe {
             let dest: *mut std::ffi::c_void = msg_send![buffer.raw[buffer.next_value], contents];
             std::ptr::copy(data.ptr, dest, data.size);
-
-            #[cfg(target_os = "macos")]
-            msg_send_![buffer.raw[buffer.next_value], didModifyRange:NSRange::new(0, data.size as u64)];
         }
         buffer.value = buffer.next_value;
     }
 
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
+        let data = match data {
+            BufferSource::Slice(data) => data,
+            _ => panic!("buffer_update_range expects BufferSource::slice"),
+        };
+        let buffer = &self.buffers[self.namespace.index(buffer.0)];
+        assert!(offset + data.size <= buffer.size);
+
+        // The copy the next draws use, not rotated
+        unsafe {
+            let dest: *mut std::ffi::c_void = msg_send![buffer.raw[buffer.value], contents];
+            std::ptr::copy(data.ptr, dest.add(offset), data.size);
+        }
+    }
+
     fn new_shader(
         &mut self,
         shader: ShaderSource,
-        _meta: ShaderMeta,
+        meta: ShaderMeta,
     ) -> Result<ShaderId, ShaderError> {
         unsafe {
             let program = match shader {
@@ -683,18 +1264,32 @@ impl RenderingBackend for MetalContext {
             let shader = ShaderInternal {
                 vertex_function,
                 fragment_function,
+                uniforms_size: meta.uniforms.size(),
             };
             self.shaders.push(shader);
-            Ok(ShaderId(self.shaders.len() - 1))
+            Ok(ShaderId(self.namespace.id(self.shaders.len() - 1)))
         }
     }
 
-    fn new_texture(
+    fn try_new_texture(
         &mut self,
         access: TextureAccess,
         bytes: TextureSource,
         params: TextureParams,
-    ) -> TextureId {
+    ) -> Result<TextureId, MiniquadError> {
+        if let TextureSource::Bytes(bytes) = bytes {
+            let expected = params.format.size(params.width, params.height) as usize;
+            if bytes.len() != expected {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "Texture data is {} bytes, {:?} {}x{} needs {}",
+                    bytes.len(),
+                    params.format,
+                    params.width,
+                    params.height,
+                    expected
+                )));
+            }
+        }
         let descriptor = unsafe {
             msg_send_![class!(MTLTextureDescriptor),
                        texture2DDescriptorWithPixelFormat:MTLPixelFormat::from(params.format)
@@ -749,53 +1344,44 @@ impl RenderingBackend for MetalContext {
             TextureKind::CubeMap => unsafe {
                 msg_send_![descriptor, setTextureType: MTLTextureType::CubeArray];
             },
+            TextureKind::Texture2DMultisample => {
+                return Err(GraphicsError::Unsupported(
+                    "multisample textures are not yet implemented on metal".to_string(),
+                )
+                .into());
+            }
+            TextureKind::External => {
+                return Err(MiniquadError::InvalidParameter(
+                    "External textures are created with new_external_texture".to_string(),
+                ));
+            }
         }
 
         let texture = unsafe {
-            let sampler_descriptor = msg_send_![class!(MTLSamplerDescriptor), new];
-            msg_send_![sampler_descriptor, retain];
-            let min_filter = match params.min_filter {
-                FilterMode::Nearest => MTLSamplerMinMagFilter::Nearest,
-                FilterMode::Linear => MTLSamplerMinMagFilter::Linear,
-            };
-
-            let mag_filter = match params.mag_filter {
-                FilterMode::Nearest => MTLSamplerMinMagFilter::Nearest,
-                FilterMode::Linear => MTLSamplerMinMagFilter::Linear,
-            };
-
-            let mipmap_filter = match params.mipmap_filter {
-                MipmapFilterMode::None => MTLSamplerMipFilter::NotMipmapped,
-                MipmapFilterMode::Nearest => MTLSamplerMipFilter::Nearest,
-                MipmapFilterMode::Linear => MTLSamplerMipFilter::Linear,
-            };
-            msg_send_![sampler_descriptor, setMinFilter: min_filter];
-            msg_send_![sampler_descriptor, setMagFilter: mag_filter];
-            msg_send_![sampler_descriptor, setMipFilter: mipmap_filter];
-
-            let sampler_state = msg_send_![
-                self.device,
-                newSamplerStateWithDescriptor: sampler_descriptor
-            ];
+            let (sampler_state, sampler_descriptor) = self.new_sampler(&params);
             let raw_texture = msg_send_![self.device, newTextureWithDescriptor: descriptor];
+            if raw_texture.is_null() {
+                msg_send_![sampler_descriptor, release];
+                return Err(GraphicsError::Unsupported(format!(
+                    "Metal failed to allocate a {}x{} {:?} texture",
+                    params.width, params.height, params.format
+                ))
+                .into());
+            }
             msg_send_![raw_texture, retain];
-            self.textures.0.push(Texture {
+            self.textures.push(Texture {
                 sampler: sampler_state,
                 texture: raw_texture,
                 sampler_descriptor,
                 params,
-            });
-            TextureId(TextureIdInner::Managed(self.textures.0.len() - 1))
+                cv_texture: std::ptr::null_mut(),
+            })
         };
+        super::profiling::track_texture(texture, &params);
 
         match bytes {
             TextureSource::Empty => {}
             TextureSource::Bytes(bytes) => {
-                assert_eq!(
-                    params.format.size(params.width, params.height) as usize,
-                    bytes.len()
-                );
-
                 self.texture_update_part(
                     texture,
                     0,
@@ -835,7 +1421,7 @@ impl RenderingBackend for MetalContext {
                 }
             }
         }
-        texture
+        Ok(texture)
     }
 
     fn texture_update_part(
@@ -875,8 +1461,13 @@ impl RenderingBackend for MetalContext {
         shader: ShaderId,
         params: PipelineParams,
     ) -> Pipeline {
+        let desc = PipelineDesc::new(buffer_layout, attributes, shader, params);
+        if let Some(id) = self.pipelines.iter().position(|p| p.desc == desc) {
+            return Pipeline(self.namespace.id(id));
+        }
+
         unsafe {
-            let shader_internal = &self.shaders[shader.0];
+            let shader_internal = &self.shaders[self.namespace.index(shader.0)];
 
             let vertex_descriptor: ObjcId =
                 msg_send![class!(MTLVertexDescriptor), vertexDescriptor];
@@ -1036,13 +1627,20 @@ impl RenderingBackend for MetalContext {
                 depth_stencil_state,
                 //layout: buffer_layout.to_vec(),
                 //attributes: vertex_layout,
-                _shader: shader,
+                shader,
+                primitive_type: params.primitive_type.into(),
+                index_type: match params.index_type {
+                    Some(IndexType::U32) => MTLIndexType::UInt32,
+                    // Index buffers did not know their type, u16 was the only one
+                    Some(IndexType::U16) | None => MTLIndexType::UInt16,
+                },
+                desc,
                 //params,
             };
 
             self.pipelines.push(pipeline);
 
-            Pipeline(self.pipelines.len() - 1)
+            Pipeline(self.namespace.id(self.pipelines.len() - 1))
         }
     }
 
@@ -1052,10 +1650,11 @@ impl RenderingBackend for MetalContext {
             "apply_pipeline before begin_pass"
         );
         let render_encoder = self.render_encoder.unwrap();
+        super::profiling::record_apply_pipeline(*pipeline);
 
         unsafe {
             self.current_pipeline = Some(*pipeline);
-            let pipeline = &self.pipelines[pipeline.0];
+            let pipeline = &self.pipelines[self.namespace.index(pipeline.0)];
 
             msg_send_![render_encoder, setRenderPipelineState: pipeline.pipeline_state];
             msg_send_![render_encoder, setDepthStencilState:pipeline.depth_stencil_state];
@@ -1078,39 +1677,57 @@ impl RenderingBackend for MetalContext {
         unsafe {
             let render_encoder = self.render_encoder.unwrap();
             for (index, vertex_buffer) in vertex_buffers.iter().enumerate() {
-                let buffer = &mut self.buffers[vertex_buffer.0];
+                let buffer = &mut self.buffers[self.namespace.index(vertex_buffer.0)];
                 let () = msg_send![render_encoder,
                                    setVertexBuffer:buffer.raw[buffer.value]
                                    offset:0
                                    atIndex:(index + 1) as u64];
                 buffer.next_value = buffer.value + 1;
             }
-            let index_buffer = &mut self.buffers[index_buffer.0];
+            let index_buffer = &mut self.buffers[self.namespace.index(index_buffer.0)];
             self.index_buffer = Some(index_buffer.raw[index_buffer.value]);
             index_buffer.next_value = index_buffer.value + 1;
+        }
+        self.apply_images(textures);
+    }
 
-            let img_count = textures.len();
-            if img_count > 0 {
-                for (n, img) in textures.iter().enumerate() {
-                    let Texture {
-                        sampler, texture, ..
-                    } = self.textures.get(*img);
-                    msg_send_![render_encoder, setFragmentSamplerState:sampler
-                               atIndex:n
-                    ];
-                    msg_send_![render_encoder, setFragmentTexture:texture
-                               atIndex:n
-                    ];
-                }
+    fn apply_images(&mut self, images: &[TextureId]) {
+        assert!(
+            self.render_encoder.is_some(),
+            "apply_images before begin_pass"
+        );
+
+        let render_encoder = self.render_encoder.unwrap();
+        for (n, img) in images.iter().enumerate() {
+            let Texture {
+                sampler, texture, ..
+            } = self.textures.get(*img);
+            unsafe {
+                msg_send_![render_encoder, setFragmentSamplerState:sampler
+                           atIndex:n
+                ];
+                msg_send_![render_encoder, setFragmentTexture:texture
+                           atIndex:n
+                ];
             }
         }
     }
 
+    fn uniform_block_size(&self) -> Option<usize> {
+        let pipeline = &self.pipelines[self.namespace.index(self.current_pipeline?.0)];
+        Some(self.shaders[self.namespace.index(pipeline.shader.0)].uniforms_size)
+    }
+
     fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
         assert!(
             self.current_pipeline.is_some(),
             "apply_uniforms before apply_pipeline"
         );
+        #[cfg(debug_assertions)]
+        if let Err(err) = check_uniforms_size(size, self.uniform_block_size()) {
+            crate::error::report_error(err);
+            return;
+        }
         assert!(
             self.render_encoder.is_some(),
             "apply_uniforms before begin_pass"
@@ -1153,12 +1770,14 @@ impl RenderingBackend for MetalContext {
     }
 
     fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        let action = self.clear_colors.resolve(pass, action);
+        super::profiling::record_begin_pass(pass);
         unsafe {
             if self.command_buffer.is_none() {
                 self.command_buffer = Some(msg_send![self.command_queue, commandBuffer]);
             }
 
-            let (descriptor, _, _) = match pass {
+            let (descriptor, pass_width, pass_height) = match pass {
                 None => {
                     let (screen_width, screen_height) = crate::window::screen_size();
                     (
@@ -1168,7 +1787,7 @@ impl RenderingBackend for MetalContext {
                     )
                 }
                 Some(pass) => {
-                    let pass = &self.passes[pass.0];
+                    let pass = &self.passes[self.namespace.index(pass.0)];
                     // new_render_pass will panic with both color and depth components none
                     // so unwrap is safe here
                     let texture = pass
@@ -1178,32 +1797,42 @@ impl RenderingBackend for MetalContext {
                         //.or(pass.depth_texture)
                         .unwrap();
 
+                    let params = self.textures.get(texture).params;
                     (
                         pass.render_pass_desc,
-                        self.textures.get(texture).params.width as f64,
-                        self.textures.get(texture).params.height as f64,
+                        (params.width >> pass.level).max(1) as f64,
+                        (params.height >> pass.level).max(1) as f64,
                     )
                 }
             };
             assert!(!descriptor.is_null());
+            self.pass_size = (pass_width, pass_height);
 
+            let color_count = pass.map_or(1, |pass| {
+                self.passes[self.namespace.index(pass.0)].texture.len()
+            });
             let color_attachments = msg_send_![descriptor, colorAttachments];
-            let color_attachment = msg_send_![color_attachments, objectAtIndexedSubscript: 0];
-
-            msg_send_![color_attachment, setStoreAction: MTLStoreAction::Store];
-
-            match action {
-                PassAction::Clear { color, .. } => {
-                    msg_send_![color_attachment, setLoadAction: MTLLoadAction::Clear];
-
-                    if let Some(color) = color {
-                        msg_send_![color_attachment, setClearColor:MTLClearColor::new(color.0 as _, color.1 as _, color.2 as _, color.3 as _)];
-                    }
-                }
-                PassAction::Nothing => {
-                    msg_send_![color_attachment, setLoadAction: MTLLoadAction::Load];
+            for index in 0..color_count {
+                let color_attachment =
+                    msg_send_![color_attachments, objectAtIndexedSubscript: index];
+                let color = action.color(index);
+                set_attachment_action(color_attachment, color.load, color.store);
+                if let LoadAction::Clear((r, g, b, a)) = color.load {
+                    msg_send_![color_attachment, setClearColor:MTLClearColor::new(r as _, g as _, b as _, a as _)];
                 }
             }
+            let depth = action.depth();
+            let depth_attachment = msg_send_![descriptor, depthAttachment];
+            set_attachment_action(depth_attachment, depth.load, depth.store);
+            if let LoadAction::Clear(value) = depth.load {
+                msg_send_![depth_attachment, setClearDepth: value as f64];
+            }
+            let stencil = action.stencil();
+            let stencil_attachment = msg_send_![descriptor, stencilAttachment];
+            set_attachment_action(stencil_attachment, stencil.load, stencil.store);
+            if let LoadAction::Clear(value) = stencil.load {
+                msg_send_![stencil_attachment, setClearStencil: value as u32];
+            }
 
             let render_encoder = msg_send_![
                 self.command_buffer.unwrap(),
@@ -1225,6 +1854,15 @@ impl RenderingBackend for MetalContext {
             //     height: h as u64,
             // });
 
+            self.viewport = None;
+            self.scissor = None;
+
+            // Shows up as the pass name in Xcode GPU captures
+            if super::profiling::is_frame_profiler_enabled() {
+                let label = apple_util::str_to_nsstring(&super::profiling::render_pass_label(pass));
+                msg_send_![render_encoder, setLabel: label];
+            }
+
             self.render_encoder = Some(render_encoder);
         }
     }
@@ -1248,13 +1886,29 @@ impl RenderingBackend for MetalContext {
         assert!(self.index_buffer.is_some());
         let index_buffer = self.index_buffer.unwrap();
 
-        assert!(base_element == 0); // TODO: figure indexBufferOffset/baseVertex
+        let pipeline = &self.pipelines[self.namespace.index(self.current_pipeline.unwrap().0)];
+        // Has to be a multiple of 4, for u16 indices too
+        let index_size = match pipeline.index_type {
+            MTLIndexType::UInt16 => 2,
+            MTLIndexType::UInt32 => 4,
+        };
+        let offset = base_element as u64 * index_size;
+        assert!(
+            offset % 4 == 0,
+            "Metal needs base_element at a multiple of 4 bytes"
+        );
+        let triangles = match pipeline.primitive_type {
+            MTLPrimitiveType::Triangle => num_elements / 3,
+            MTLPrimitiveType::TriangleStrip => (num_elements - 2).max(0),
+            _ => 0,
+        };
+        super::profiling::record_draw_call((triangles * num_instances) as u64);
         unsafe {
-            msg_send_![render_encoder, drawIndexedPrimitives:MTLPrimitiveType::Triangle
+            msg_send_![render_encoder, drawIndexedPrimitives:pipeline.primitive_type
                        indexCount:num_elements as u64
-                       indexType:MTLIndexType::UInt16
+                       indexType:pipeline.index_type
                        indexBuffer:index_buffer
-                       indexBufferOffset:0
+                       indexBufferOffset:offset
                        instanceCount:num_instances as u64
                        baseVertex:0
                        baseInstance:0
@@ -1262,6 +1916,27 @@ impl RenderingBackend for MetalContext {
         }
     }
 
+    fn draw_vertices(&self, base_vertex: i32, num_vertices: i32, num_instances: i32) {
+        assert!(self.render_encoder.is_some(), "draw before begin_pass!");
+        let render_encoder = self.render_encoder.unwrap();
+
+        let pipeline = &self.pipelines[self.namespace.index(self.current_pipeline.unwrap().0)];
+        let triangles = match pipeline.primitive_type {
+            MTLPrimitiveType::Triangle => num_vertices / 3,
+            MTLPrimitiveType::TriangleStrip => (num_vertices - 2).max(0),
+            _ => 0,
+        };
+        super::profiling::record_draw_call((triangles * num_instances) as u64);
+        unsafe {
+            msg_send_![render_encoder, drawPrimitives:pipeline.primitive_type
+                       vertexStart:base_vertex as u64
+                       vertexCount:num_vertices as u64
+                       instanceCount:num_instances as u64
+                       baseInstance:0
+            ];
+        }
+    }
+
     fn delete_shader(&mut self, _shader: ShaderId) {
         // TODO: place holder
     }
@@ -1269,7 +1944,25 @@ impl RenderingBackend for MetalContext {
         // TODO: place holder
     }
 
+    fn pipelines_alive(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    fn set_software_cursor(&mut self, cursor: Option<super::software_cursor::SoftwareCursor>) {
+        if self.software_cursor.is_none() {
+            if cursor.is_none() {
+                return;
+            }
+            self.software_cursor = Some(super::software_cursor::CursorRenderer::new(self));
+        }
+        self.software_cursor.as_mut().unwrap().set_cursor(cursor);
+    }
+
     fn commit_frame(&mut self) {
+        if let Some(mut cursor) = self.software_cursor.take() {
+            cursor.draw(self);
+            self.software_cursor = Some(cursor);
+        }
         unsafe {
             assert!(!self.command_queue.is_null());
             let drawable: ObjcId = msg_send!(self.view, currentDrawable);
@@ -1277,15 +1970,50 @@ impl RenderingBackend for MetalContext {
             msg_send_![self.command_buffer.unwrap(), presentDrawable: drawable];
             msg_send_![self.command_buffer.unwrap(), commit];
             msg_send_![self.command_buffer.unwrap(), waitUntilCompleted];
+            // Seconds, 0 when the GPU did not run the command buffer
+            let gpu_start: f64 = msg_send![self.command_buffer.unwrap(), GPUStartTime];
+            let gpu_end: f64 = msg_send![self.command_buffer.unwrap(), GPUEndTime];
+            if gpu_end > gpu_start {
+                crate::frame_stats::record_gpu_time((gpu_end - gpu_start) * 1000.0);
+            }
+            self.check_device_lost(self.command_buffer.unwrap(), gpu_end - gpu_start);
         }
         for buffer in &mut self.buffers {
             buffer.next_value = 0;
         }
+        // The command buffer is completed, released buffers are safe to reuse
+        self.buffer_pool.end_frame();
+        super::profiling::end_frame();
+        if super::buffer_pool::take_memory_pressure() {
+            self.buffer_pool.trim(0);
+        }
         self.current_ub_offset = 0;
         self.current_pipeline = None;
         self.command_buffer = None;
         if (self.current_frame_index + 1) >= 3 {
             self.current_frame_index = 0;
         }
+
+        let committed = self.frame_index;
+        self.frame_index += 1;
+        for resource in self.deferred_deletes.take_due(committed) {
+            resource.delete(self);
+        }
+    }
+
+    fn frame_index(&self) -> u64 {
+        self.frame_index
+    }
+
+    fn delete_buffer_deferred(&mut self, buffer: BufferId, frames: u32) {
+        let resource = super::deferred_delete::Deferred::Buffer(buffer);
+        self.deferred_deletes
+            .push(self.frame_index, frames, resource);
+    }
+
+    fn delete_texture_deferred(&mut self, texture: TextureId, frames: u32) {
+        let resource = super::deferred_delete::Deferred::Texture(texture);
+        self.deferred_deletes
+            .push(self.frame_index, frames, resource);
     }
 }
@@ -0,0 +1,130 @@
+//! Buffer pooling for the Metal backend
+//!
+//! Metal counterpart of `graphics::buffer_pool`: MTLBuffers are allocated with
+//! the same size buckets and handed out again instead of being released.
+//!
+//! Key features:
+//! - Size-based bucket allocation, shared with the GL pool
+//! - Shared storage mode, so the CPU writes straight into the buffer contents
+//! - Buffers freed during a frame are reused only after the frame is completed
+//! - Same `BufferPoolStats` as the GL pool
+
+use crate::graphics::buffer_pool::{BufferPool, BufferPoolStats};
+use crate::native::apple::{apple_util::msg_send_, frameworks::*};
+use std::collections::HashMap;
+
+/// Maximum buffers kept per bucket. Each miniquad buffer holds
+/// `BUFFERS_IN_ROTATION` MTLBuffers, so this is a lot higher than on GL.
+const MAX_POOL_SIZE: usize = 256;
+
+#[derive(Debug, Default)]
+pub struct MetalBufferPool {
+    // Available buffers per size bucket
+    pools: HashMap<usize, Vec<ObjcId>>,
+    // Buffers released during the current frame, the GPU may still read them
+    pending: Vec<(usize, ObjcId)>,
+    stats: BufferPoolStats,
+}
+
+impl MetalBufferPool {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Size of the buffer `acquire` is going to return for `size` bytes.
+    /// The buckets stop growing at 32MB, bigger buffers get their exact size.
+    pub fn capacity(size: usize) -> usize {
+        BufferPool::get_size_bucket(size).max(size)
+    }
+
+    /// Get a shared storage buffer of at least `size` bytes
+    pub fn acquire(&mut self, device: ObjcId, size: usize) -> ObjcId {
+        let capacity = Self::capacity(size);
+
+        if let Some(buffer) = self.pools.get_mut(&capacity).and_then(|pool| pool.pop()) {
+            self.stats.cache_hits += 1;
+            self.stats.buffers_in_use += 1;
+            self.stats.buffers_available = self.stats.buffers_available.saturating_sub(1);
+            return buffer;
+        }
+
+        self.stats.cache_misses += 1;
+        let length = capacity as u64;
+        let buffer: ObjcId = unsafe {
+            msg_send![device,
+                      newBufferWithLength:length
+                      options:MTLResourceOptions::StorageModeShared]
+        };
+        assert!(!buffer.is_null());
+
+        self.stats.total_buffers += 1;
+        self.stats.buffers_in_use += 1;
+        self.stats.pool_allocations += 1;
+        self.stats.memory_usage_bytes += capacity;
+        buffer
+    }
+
+    /// Give the buffer back. It becomes available after the next `end_frame`.
+    pub fn release(&mut self, buffer: ObjcId, size: usize) {
+        self.pending.push((Self::capacity(size), buffer));
+        self.stats.pool_deallocations += 1;
+    }
+
+    /// Should be called once the frame's command buffer is completed
+    pub fn end_frame(&mut self) {
+        for (capacity, buffer) in self.pending.drain(..) {
+            self.stats.buffers_in_use = self.stats.buffers_in_use.saturating_sub(1);
+            let pool = self.pools.entry(capacity).or_default();
+            if pool.len() < MAX_POOL_SIZE {
+                pool.push(buffer);
+                self.stats.buffers_available += 1;
+            } else {
+                unsafe { msg_send_![buffer, release] };
+                self.stats.total_buffers = self.stats.total_buffers.saturating_sub(1);
+                self.stats.memory_usage_bytes =
+                    self.stats.memory_usage_bytes.saturating_sub(capacity);
+            }
+        }
+        self.update_efficiency();
+    }
+
+    /// Release available buffers, biggest first, until the pool takes
+    /// no more than `target_bytes`. Returns the amount of bytes freed.
+    pub fn trim(&mut self, target_bytes: usize) -> usize {
+        let mut buckets: Vec<usize> = self.pools.keys().copied().collect();
+        buckets.sort_unstable_by(|a, b| b.cmp(a));
+
+        let mut memory_freed = 0;
+        for capacity in buckets {
+            let pool = self.pools.get_mut(&capacity).unwrap();
+            while self.stats.memory_usage_bytes > target_bytes {
+                let Some(buffer) = pool.pop() else {
+                    break;
+                };
+                unsafe { msg_send_![buffer, release] };
+                memory_freed += capacity;
+                self.stats.total_buffers = self.stats.total_buffers.saturating_sub(1);
+                self.stats.buffers_available = self.stats.buffers_available.saturating_sub(1);
+                self.stats.memory_usage_bytes =
+                    self.stats.memory_usage_bytes.saturating_sub(capacity);
+            }
+        }
+        self.pools.retain(|_, pool| !pool.is_empty());
+        self.update_efficiency();
+        memory_freed
+    }
+
+    pub fn get_stats(&self) -> BufferPoolStats {
+        self.stats.clone()
+    }
+
+    fn update_efficiency(&mut self) {
+        if self.stats.total_buffers == 0 {
+            self.stats.pool_efficiency = 100.0;
+        } else {
+            self.stats.pool_efficiency =
+                (self.stats.buffers_in_use as f64 / self.stats.total_buffers as f64) * 100.0;
+        }
+        self.stats.gpu_allocations_saved = self.stats.cache_hits;
+    }
+}
@@ -0,0 +1,696 @@
+//! Headless rendering backend that draws nothing
+//!
+//! `NullContext` implements the whole `RenderingBackend` without a window or a
+//! GL context: resources are only bookkept in memory and the frame calls are
+//! recorded, so the rendering logic built on top of miniquad can be unit-tested
+//! in CI containers.
+//!
+//! ```
+//! # use miniquad::*;
+//! let mut ctx = NullContext::new();
+//! let buffer = ctx.new_buffer(
+//!     BufferType::VertexBuffer,
+//!     BufferUsage::Immutable,
+//!     BufferSource::slice(&[0.0f32, 1.0, 2.0]),
+//! );
+//! assert_eq!(ctx.buffer_data(buffer), &[0, 0, 0, 0, 0, 0, 128, 63, 0, 0, 0, 64]);
+//!
+//! ctx.begin_default_pass(PassAction::Nothing);
+//! ctx.end_render_pass();
+//! ctx.commit_frame();
+//! assert_eq!(ctx.take_calls().len(), 3);
+//! ```
+
+use crate::graphics::*;
+use crate::{Namespace, ResourceManager};
+
+use std::cell::RefCell;
+
+/// A frame call recorded by `NullContext`
+#[derive(Debug, Clone, PartialEq)]
+pub enum NullCall {
+    ApplyPipeline(Pipeline),
+    ApplyBindings {
+        vertex_buffers: Vec<BufferId>,
+        index_buffer: BufferId,
+        images: Vec<TextureId>,
+    },
+    ApplyImages(Vec<TextureId>),
+    ApplyUniforms(Vec<u8>),
+    ApplyViewport {
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+    },
+    ApplyScissorRect {
+        x: i32,
+        y: i32,
+        w: i32,
+        h: i32,
+    },
+    ApplyViewportF {
+        x: f32,
+        y: f32,
+        w: f32,
+        h: f32,
+        min_depth: f32,
+        max_depth: f32,
+    },
+    ApplyScissor(Option<(i32, i32, i32, i32)>),
+    Clear {
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    },
+    ClearRegion {
+        rect: (i32, i32, i32, i32),
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    },
+    BeginPass {
+        pass: Option<RenderPass>,
+        action: PassAction,
+    },
+    EndRenderPass,
+    CommitFrame,
+    Draw {
+        base_element: i32,
+        num_elements: i32,
+        num_instances: i32,
+    },
+    DrawVertices {
+        base_vertex: i32,
+        num_vertices: i32,
+        num_instances: i32,
+    },
+    BufferUpdate(BufferId),
+    TextureUpdate(TextureId),
+}
+
+struct NullTexture {
+    params: TextureParams,
+    data: Vec<u8>,
+}
+
+struct NullBuffer {
+    type_: BufferType,
+    usage: BufferUsage,
+    index_type: Option<usize>,
+    data: Vec<u8>,
+}
+
+struct NullPipeline {
+    desc: PipelineDesc,
+    refs: usize,
+}
+
+struct NullPass {
+    color: Vec<TextureId>,
+    depth: Option<TextureId>,
+}
+
+pub struct NullContext {
+    shaders: ResourceManager<ShaderMeta>,
+    pipelines: ResourceManager<NullPipeline>,
+    passes: ResourceManager<NullPass>,
+    buffers: ResourceManager<NullBuffer>,
+    namespace: Namespace,
+    // Texture ids are indices, deleted textures leave a hole
+    textures: Vec<Option<NullTexture>>,
+    current_pipeline: Option<Pipeline>,
+    calls: RefCell<Vec<NullCall>>,
+}
+
+impl Default for NullContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NullContext {
+    pub fn new() -> NullContext {
+        let namespace = Namespace::next();
+        NullContext {
+            shaders: ResourceManager::new(namespace),
+            pipelines: ResourceManager::new(namespace),
+            passes: ResourceManager::new(namespace),
+            buffers: ResourceManager::new(namespace),
+            namespace,
+            textures: vec![],
+            current_pipeline: None,
+            calls: RefCell::new(vec![]),
+        }
+    }
+
+    fn record(&self, call: NullCall) {
+        self.calls.borrow_mut().push(call);
+    }
+
+    fn texture(&self, texture: TextureId) -> &NullTexture {
+        match texture.0 {
+            TextureIdInner::Managed(id) => self
+                .textures
+                .get(self.namespace.index(id))
+                .and_then(|texture| texture.as_ref())
+                .unwrap_or_else(|| panic!("Invalid texture ID {}", id)),
+            TextureIdInner::Raw(_) => panic!("Raw textures are not supported by NullContext"),
+        }
+    }
+
+    fn texture_mut(&mut self, texture: TextureId) -> &mut NullTexture {
+        match texture.0 {
+            TextureIdInner::Managed(id) => self
+                .textures
+                .get_mut(self.namespace.index(id))
+                .and_then(|texture| texture.as_mut())
+                .unwrap_or_else(|| panic!("Invalid texture ID {}", id)),
+            TextureIdInner::Raw(_) => panic!("Raw textures are not supported by NullContext"),
+        }
+    }
+
+    /// Calls recorded since the last `take_calls`, oldest first
+    pub fn calls(&self) -> Vec<NullCall> {
+        self.calls.borrow().clone()
+    }
+
+    /// Calls recorded since the last `take_calls`, the record is emptied
+    pub fn take_calls(&mut self) -> Vec<NullCall> {
+        std::mem::take(self.calls.get_mut())
+    }
+
+    /// Amount of recorded `draw` and `draw_vertices` calls
+    pub fn draw_call_count(&self) -> usize {
+        self.calls
+            .borrow()
+            .iter()
+            .filter(|call| matches!(call, NullCall::Draw { .. } | NullCall::DrawVertices { .. }))
+            .count()
+    }
+
+    /// Current content of the buffer, as uploaded by `new_buffer` and `buffer_update`
+    pub fn buffer_data(&self, buffer: BufferId) -> &[u8] {
+        &self.buffers[buffer.0].data
+    }
+
+    pub fn buffer_type(&self, buffer: BufferId) -> (BufferType, BufferUsage) {
+        let buffer = &self.buffers[buffer.0];
+        (buffer.type_, buffer.usage)
+    }
+
+    /// Current content of the texture, all zeros for render targets
+    pub fn texture_data(&self, texture: TextureId) -> &[u8] {
+        &self.texture(texture).data
+    }
+
+    pub fn shader_meta(&self, shader: ShaderId) -> &ShaderMeta {
+        &self.shaders[shader.0]
+    }
+
+    pub fn pipeline_params(&self, pipeline: Pipeline) -> PipelineParams {
+        self.pipelines[pipeline.0].desc.params
+    }
+
+    pub fn pipeline_shader(&self, pipeline: Pipeline) -> ShaderId {
+        self.pipelines[pipeline.0].desc.shader
+    }
+
+    pub fn render_pass_depth_attachment(&self, render_pass: RenderPass) -> Option<TextureId> {
+        self.passes[render_pass.0].depth
+    }
+
+    pub fn current_pipeline(&self) -> Option<Pipeline> {
+        self.current_pipeline
+    }
+
+    pub fn live_shaders(&self) -> usize {
+        self.shaders.len()
+    }
+
+    pub fn live_pipelines(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    pub fn live_render_passes(&self) -> usize {
+        self.passes.len()
+    }
+
+    pub fn live_buffers(&self) -> usize {
+        self.buffers.len()
+    }
+
+    pub fn live_textures(&self) -> usize {
+        self.textures.iter().flatten().count()
+    }
+}
+
+impl RenderingBackend for NullContext {
+    fn info(&self) -> ContextInfo {
+        ContextInfo {
+            backend: Backend::OpenGl,
+            gl_version_string: String::new(),
+            glsl_support: GlslSupport::default(),
+            features: Features::default(),
+            adapter: String::new(),
+            default_framebuffer: FramebufferInfo::default(),
+            limits: Limits::default(),
+        }
+    }
+
+    fn new_shader(
+        &mut self,
+        _shader: ShaderSource,
+        meta: ShaderMeta,
+    ) -> Result<ShaderId, ShaderError> {
+        Ok(ShaderId(self.shaders.add(meta)))
+    }
+
+    fn try_new_texture(
+        &mut self,
+        access: TextureAccess,
+        source: TextureSource,
+        params: TextureParams,
+    ) -> Result<TextureId, MiniquadError> {
+        if access != TextureAccess::RenderTarget && params.sample_count > 1 {
+            return Err(MiniquadError::InvalidParameter(
+                "Multisampling is only supported for render textures".to_string(),
+            ));
+        }
+        let size = params.format.size(params.width, params.height) as usize;
+        let data = match source {
+            TextureSource::Empty => vec![0; size],
+            TextureSource::Bytes(bytes) if bytes.len() == size => bytes.to_vec(),
+            TextureSource::Bytes(bytes) => {
+                return Err(MiniquadError::InvalidParameter(format!(
+                    "Texture data is {} bytes, {:?} {}x{} needs {}",
+                    bytes.len(),
+                    params.format,
+                    params.width,
+                    params.height,
+                    size
+                )))
+            }
+            TextureSource::Array(array) => array
+                .first()
+                .and_then(|mipmaps| mipmaps.first())
+                .map_or_else(|| vec![0; size], |bytes| bytes.to_vec()),
+        };
+        self.textures.push(Some(NullTexture { params, data }));
+        let id = self.namespace.id(self.textures.len() - 1);
+        Ok(TextureId(TextureIdInner::Managed(id)))
+    }
+
+    fn texture_params(&self, texture: TextureId) -> TextureParams {
+        self.texture(texture).params
+    }
+
+    unsafe fn texture_raw_id(&self, texture: TextureId) -> RawId {
+        match texture.0 {
+            TextureIdInner::Managed(id) => RawId::OpenGl(id as _),
+            TextureIdInner::Raw(raw) => raw,
+        }
+    }
+
+    fn texture_set_min_filter(
+        &mut self,
+        texture: TextureId,
+        filter: FilterMode,
+        mipmap_filter: MipmapFilterMode,
+    ) {
+        let params = &mut self.texture_mut(texture).params;
+        params.min_filter = filter;
+        params.mipmap_filter = mipmap_filter;
+    }
+
+    fn texture_set_mag_filter(&mut self, texture: TextureId, filter: FilterMode) {
+        self.texture_mut(texture).params.mag_filter = filter;
+    }
+
+    fn texture_set_wrap(&mut self, texture: TextureId, wrap_x: TextureWrap, _wrap_y: TextureWrap) {
+        // TextureParams has a single wrap mode
+        self.texture_mut(texture).params.wrap = wrap_x;
+    }
+
+    fn texture_generate_mipmaps(&mut self, _texture: TextureId) {}
+
+    fn texture_resize(
+        &mut self,
+        texture: TextureId,
+        width: u32,
+        height: u32,
+        bytes: Option<&[u8]>,
+    ) {
+        let texture = self.texture_mut(texture);
+        texture.params.width = width;
+        texture.params.height = height;
+        let size = texture.params.format.size(width, height) as usize;
+        texture.data = match bytes {
+            Some(bytes) => {
+                assert_eq!(bytes.len(), size);
+                bytes.to_vec()
+            }
+            None => vec![0; size],
+        };
+    }
+
+    fn texture_read_pixels(&mut self, texture: TextureId, bytes: &mut [u8]) {
+        let data = &self.texture(texture).data;
+        let len = data.len().min(bytes.len());
+        bytes[..len].copy_from_slice(&data[..len]);
+    }
+
+    fn texture_read_region(
+        &mut self,
+        texture: TextureId,
+        rect: (i32, i32, i32, i32),
+        format: TextureFormat,
+        bytes: &mut [u8],
+    ) -> Result<(), MiniquadError> {
+        let texture = self.texture(texture);
+        super::readback::validate(&texture.params, rect, format, bytes)?;
+        let (x, y, width, height) = rect;
+        let pixel_size = texture.params.format.size(1, 1) as usize;
+        let texture_row = texture.params.width as usize * pixel_size;
+        let row = width as usize * pixel_size;
+        let mut region = Vec::with_capacity(row * height as usize);
+        for y in y as usize..(y + height) as usize {
+            let start = y * texture_row + x as usize * pixel_size;
+            region.extend_from_slice(&texture.data[start..start + row]);
+        }
+        let pixels = super::readback::decode(texture.params.format, &region);
+        super::readback::encode(&pixels, format, bytes);
+        Ok(())
+    }
+
+    fn texture_update_part(
+        &mut self,
+        texture: TextureId,
+        x_offset: i32,
+        y_offset: i32,
+        width: i32,
+        height: i32,
+        bytes: &[u8],
+    ) {
+        self.record(NullCall::TextureUpdate(texture));
+        let texture = self.texture_mut(texture);
+        let pixel_size = texture.params.format.size(1, 1) as usize;
+        let texture_row = texture.params.width as usize * pixel_size;
+        let row = width as usize * pixel_size;
+        assert!(x_offset >= 0 && y_offset >= 0);
+        assert!(x_offset + width <= texture.params.width as i32);
+        assert!(y_offset + height <= texture.params.height as i32);
+        assert_eq!(bytes.len(), row * height as usize);
+        for y in 0..height as usize {
+            let start = (y_offset as usize + y) * texture_row + x_offset as usize * pixel_size;
+            texture.data[start..start + row].copy_from_slice(&bytes[y * row..(y + 1) * row]);
+        }
+    }
+
+    fn try_new_render_pass_mrt(
+        &mut self,
+        color_img: &[TextureId],
+        resolve_img: Option<&[TextureId]>,
+        depth_img: Option<TextureId>,
+    ) -> Result<RenderPass, MiniquadError> {
+        if color_img.is_empty() && depth_img.is_none() {
+            return Err(MiniquadError::InvalidParameter(
+                "Render pass should have at least one non-none target".to_string(),
+            ));
+        }
+        let attachments = color_img
+            .iter()
+            .chain(resolve_img.unwrap_or(&[]))
+            .chain(depth_img.iter());
+        for texture in attachments {
+            if let TextureIdInner::Managed(id) = texture.0 {
+                let index = self.namespace.try_index(id)?;
+                if !matches!(self.textures.get(index), Some(Some(_))) {
+                    return Err(crate::error::ResourceError::NotFound(id).into());
+                }
+            }
+        }
+        let pass = NullPass {
+            color: color_img.to_vec(),
+            depth: depth_img,
+        };
+        Ok(RenderPass(self.passes.add(pass)))
+    }
+
+    fn render_pass_color_attachments(&self, render_pass: RenderPass) -> &[TextureId] {
+        &self.passes[render_pass.0].color
+    }
+
+    fn delete_render_pass(&mut self, render_pass: RenderPass) {
+        let _ = self.passes.remove(render_pass.0);
+    }
+
+    fn new_pipeline(
+        &mut self,
+        buffer_layout: &[BufferLayout],
+        attributes: &[VertexAttribute],
+        shader: ShaderId,
+        params: PipelineParams,
+    ) -> Pipeline {
+        assert!(
+            self.shaders.get(shader.0).is_ok(),
+            "Invalid shader ID {} in new_pipeline",
+            shader.0
+        );
+        let desc = PipelineDesc::new(buffer_layout, attributes, shader, params);
+        let existing = self.pipelines.iter().find(|(_, p)| p.desc == desc);
+        if let Some((id, _)) = existing {
+            self.pipelines[id].refs += 1;
+            return Pipeline(id);
+        }
+        Pipeline(self.pipelines.add(NullPipeline { desc, refs: 1 }))
+    }
+
+    fn apply_pipeline(&mut self, pipeline: &Pipeline) {
+        self.record(NullCall::ApplyPipeline(*pipeline));
+        self.current_pipeline = Some(*pipeline);
+    }
+
+    fn delete_pipeline(&mut self, pipeline: Pipeline) {
+        let Ok(pipeline_data) = self.pipelines.get_mut(pipeline.0) else {
+            return;
+        };
+        pipeline_data.refs -= 1;
+        if pipeline_data.refs > 0 {
+            return;
+        }
+        if self.current_pipeline == Some(pipeline) {
+            self.current_pipeline = None;
+        }
+        let _ = self.pipelines.remove(pipeline.0);
+    }
+
+    fn pipelines_alive(&self) -> usize {
+        self.pipelines.len()
+    }
+
+    fn try_new_buffer(
+        &mut self,
+        type_: BufferType,
+        usage: BufferUsage,
+        data: BufferSource,
+    ) -> Result<BufferId, MiniquadError> {
+        let (data, element_size) = match data {
+            BufferSource::Slice(data) => {
+                // Safe: Arg always points to `size` bytes borrowed for its lifetime
+                let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+                (bytes.to_vec(), data.element_size)
+            }
+            BufferSource::Empty { size, element_size } => (vec![0; size], element_size),
+        };
+        let index_type = match type_ {
+            BufferType::IndexBuffer if [1, 2, 4].contains(&element_size) => Some(element_size),
+            BufferType::IndexBuffer => {
+                return Err(MiniquadError::InvalidParameter(format!(
+                "Unsupported index buffer element size: {}. Only 1, 2, and 4 bytes are supported",
+                element_size
+            )))
+            }
+            BufferType::VertexBuffer => None,
+        };
+        let buffer = NullBuffer {
+            type_,
+            usage,
+            index_type,
+            data,
+        };
+        Ok(BufferId(self.buffers.add(buffer)))
+    }
+
+    fn buffer_update(&mut self, buffer: BufferId, data: BufferSource) {
+        let data = match data {
+            BufferSource::Slice(data) => data,
+            BufferSource::Empty { .. } => panic!("buffer_update expects BufferSource::Slice"),
+        };
+        self.record(NullCall::BufferUpdate(buffer));
+        let buffer = &mut self.buffers[buffer.0];
+        if let Some(index_type) = buffer.index_type {
+            assert_eq!(data.element_size, index_type);
+        }
+        assert!(data.size <= buffer.data.len());
+        let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+        buffer.data[..data.size].copy_from_slice(bytes);
+    }
+
+    fn buffer_update_range(&mut self, buffer: BufferId, offset: usize, data: BufferSource) {
+        let data = match data {
+            BufferSource::Slice(data) => data,
+            BufferSource::Empty { .. } => panic!("buffer_update_range expects BufferSource::Slice"),
+        };
+        self.record(NullCall::BufferUpdate(buffer));
+        let buffer = &mut self.buffers[buffer.0];
+        if let Some(index_type) = buffer.index_type {
+            assert_eq!(data.element_size, index_type);
+        }
+        assert!(offset + data.size <= buffer.data.len());
+        let bytes = unsafe { std::slice::from_raw_parts(data.ptr as *const u8, data.size) };
+        buffer.data[offset..offset + data.size].copy_from_slice(bytes);
+    }
+
+    fn buffer_size(&mut self, buffer: BufferId) -> usize {
+        self.buffers[buffer.0].data.len()
+    }
+
+    fn delete_buffer(&mut self, buffer: BufferId) {
+        let _ = self.buffers.remove(buffer.0);
+    }
+
+    fn delete_texture(&mut self, texture: TextureId) {
+        if let TextureIdInner::Managed(id) = texture.0 {
+            if let Ok(index) = self.namespace.try_index(id) {
+                if let Some(texture) = self.textures.get_mut(index) {
+                    *texture = None;
+                }
+            }
+        }
+    }
+
+    fn delete_shader(&mut self, program: ShaderId) {
+        let _ = self.shaders.remove(program.0);
+    }
+
+    fn apply_viewport(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.record(NullCall::ApplyViewport { x, y, w, h });
+    }
+
+    fn apply_viewport_f(&mut self, x: f32, y: f32, w: f32, h: f32, min_depth: f32, max_depth: f32) {
+        self.record(NullCall::ApplyViewportF {
+            x,
+            y,
+            w,
+            h,
+            min_depth,
+            max_depth,
+        });
+    }
+
+    fn apply_scissor_rect(&mut self, x: i32, y: i32, w: i32, h: i32) {
+        self.record(NullCall::ApplyScissorRect { x, y, w, h });
+    }
+
+    fn apply_scissor(&mut self, rect: Option<(i32, i32, i32, i32)>) {
+        self.record(NullCall::ApplyScissor(rect));
+    }
+
+    fn apply_bindings_from_slice(
+        &mut self,
+        vertex_buffers: &[BufferId],
+        index_buffer: BufferId,
+        textures: &[TextureId],
+    ) {
+        self.record(NullCall::ApplyBindings {
+            vertex_buffers: vertex_buffers.to_vec(),
+            index_buffer,
+            images: textures.to_vec(),
+        });
+    }
+
+    fn apply_images(&mut self, images: &[TextureId]) {
+        self.record(NullCall::ApplyImages(images.to_vec()));
+    }
+
+    fn uniform_block_size(&self) -> Option<usize> {
+        let shader = self.pipeline_shader(self.current_pipeline?);
+        Some(self.shader_meta(shader).uniforms.size())
+    }
+
+    // The pointer comes straight from the trait signature, same as in the GL backend
+    #[allow(clippy::not_unsafe_ptr_arg_deref)]
+    fn apply_uniforms_from_bytes(&mut self, uniform_ptr: *const u8, size: usize) {
+        let bytes = unsafe { std::slice::from_raw_parts(uniform_ptr, size) };
+        self.record(NullCall::ApplyUniforms(bytes.to_vec()));
+    }
+
+    fn clear(
+        &mut self,
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.record(NullCall::Clear {
+            color,
+            depth,
+            stencil,
+        });
+    }
+
+    fn clear_region(
+        &mut self,
+        rect: (i32, i32, i32, i32),
+        color: Option<(f32, f32, f32, f32)>,
+        depth: Option<f32>,
+        stencil: Option<i32>,
+    ) {
+        self.record(NullCall::ClearRegion {
+            rect,
+            color,
+            depth,
+            stencil,
+        });
+    }
+
+    fn begin_default_pass(&mut self, action: PassAction) {
+        self.begin_pass(None, action);
+    }
+
+    fn begin_pass(&mut self, pass: Option<RenderPass>, action: PassAction) {
+        self.record(NullCall::BeginPass { pass, action });
+    }
+
+    fn end_render_pass(&mut self) {
+        self.record(NullCall::EndRenderPass);
+    }
+
+    fn commit_frame(&mut self) {
+        self.record(NullCall::CommitFrame);
+    }
+
+    fn draw(&self, base_element: i32, num_elements: i32, num_instances: i32) {
+        assert!(
+            self.current_pipeline.is_some(),
+            "Drawing without any binded pipeline"
+        );
+        self.record(NullCall::Draw {
+            base_element,
+            num_elements,
+            num_instances,
+        });
+    }
+
+    fn draw_vertices(&self, base_vertex: i32, num_vertices: i32, num_instances: i32) {
+        assert!(
+            self.current_pipeline.is_some(),
+            "Drawing without any binded pipeline"
+        );
+        self.record(NullCall::DrawVertices {
+            base_vertex,
+            num_vertices,
+            num_instances,
+        });
+    }
+}
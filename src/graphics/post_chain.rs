@@ -0,0 +1,319 @@
+//! Fullscreen post-processing passes
+//!
+//! The scene is drawn into an offscreen target of the window size, then each
+//! pass of the chain draws a fullscreen quad sampling the output of the previous
+//! one, ping-ponging between two targets. The last enabled pass draws to the
+//! default framebuffer. The targets follow the window size on their own.
+//!
+//! Pass shaders get the previous output as their first image. On GL they can
+//! use `VERTEX` as the vertex shader, its `texcoord` varying covers the screen.
+//! On Metal, prepend `METAL_VERTEX` to the fragment function, it passes
+//! `RasterizerData` with `uv` to `fragmentShader`.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::post_chain::{self, PostChain};
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! # let (fragment, meta) = ("", ShaderMeta { images: vec![], uniforms: UniformBlockLayout { uniforms: vec![] } });
+//! let mut chain = PostChain::new(&mut *ctx);
+//! let shader = ctx
+//!     .new_shader(ShaderSource::Glsl { vertex: post_chain::VERTEX, fragment }, meta)
+//!     .unwrap();
+//! let vignette = chain.add_pass(&mut *ctx, shader, &[]);
+//!
+//! // each frame
+//! chain.begin_scene(&mut *ctx, PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+//! // ... draw the scene ...
+//! ctx.end_render_pass();
+//! chain.set_uniforms(vignette, &0.5f32);
+//! chain.draw(&mut *ctx);
+//! ctx.commit_frame();
+//! ```
+
+use crate::graphics::*;
+
+/// Fullscreen quad vertex shader for the GL passes
+pub const VERTEX: &str = r#"#version 100
+attribute vec2 in_pos;
+
+varying lowp vec2 texcoord;
+
+void main() {
+    gl_Position = vec4(in_pos, 0, 1);
+    texcoord = in_pos * 0.5 + 0.5;
+}"#;
+
+/// Fullscreen quad vertex function for the Metal passes.
+/// Metal render targets have their first row at the top, `uv` accounts for it.
+pub const METAL_VERTEX: &str = r#"
+#include <metal_stdlib>
+
+using namespace metal;
+
+struct Vertex
+{
+    float2 in_pos   [[attribute(0)]];
+};
+
+struct RasterizerData
+{
+    float4 position [[position]];
+    float2 uv       [[user(locn0)]];
+};
+
+vertex RasterizerData vertexShader(Vertex v [[stage_in]])
+{
+    RasterizerData out;
+
+    out.position = float4(v.in_pos.xy, 0.0, 1.0);
+    out.uv = float2(v.in_pos.x * 0.5 + 0.5, 0.5 - v.in_pos.y * 0.5);
+
+    return out;
+}
+"#;
+
+const COPY_FRAGMENT: &str = r#"#version 100
+varying lowp vec2 texcoord;
+
+uniform sampler2D tex;
+
+void main() {
+    gl_FragColor = texture2D(tex, texcoord);
+}"#;
+
+const METAL_COPY_FRAGMENT: &str = r#"
+fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]])
+{
+    return tex.sample(texSmplr, in.uv);
+}"#;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Vertex {
+    pos: [f32; 2],
+}
+
+struct PostPass {
+    pipeline: Pipeline,
+    /// Images after the previous pass output
+    images: Vec<TextureId>,
+    uniforms: Vec<u8>,
+    enabled: bool,
+}
+
+pub struct PostChain {
+    /// Scene target with a depth attachment, and the other one of the ping-pong
+    targets: [RenderPass; 2],
+    width: u32,
+    height: u32,
+    passes: Vec<PostPass>,
+    /// Draws the scene as is when no pass is enabled
+    copy: Pipeline,
+    vertex_buffer: BufferId,
+    index_buffer: BufferId,
+}
+
+impl PostChain {
+    /// Targets of the current window size and an empty chain
+    pub fn new(ctx: &mut dyn RenderingBackend) -> PostChain {
+        #[rustfmt::skip]
+        let vertices: [Vertex; 4] = [
+            Vertex { pos: [-1.0, -1.0] },
+            Vertex { pos: [ 1.0, -1.0] },
+            Vertex { pos: [ 1.0,  1.0] },
+            Vertex { pos: [-1.0,  1.0] },
+        ];
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&vertices),
+        );
+        let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&indices),
+        );
+
+        let metal_copy = format!("{}{}", METAL_VERTEX, METAL_COPY_FRAGMENT);
+        let shader = ctx
+            .new_shader(
+                match ctx.info().backend {
+                    Backend::OpenGl => ShaderSource::Glsl {
+                        vertex: VERTEX,
+                        fragment: COPY_FRAGMENT,
+                    },
+                    Backend::Metal => ShaderSource::Msl {
+                        program: &metal_copy,
+                    },
+                },
+                ShaderMeta {
+                    images: vec!["tex".to_string()],
+                    uniforms: UniformBlockLayout { uniforms: vec![] },
+                },
+            )
+            .unwrap_or_else(|e| panic!("Failed to load post chain copy shader: {}", e));
+        let copy = new_pass_pipeline(ctx, shader);
+
+        let (width, height) = screen_size();
+        PostChain {
+            targets: new_targets(ctx, width, height),
+            width,
+            height,
+            passes: vec![],
+            copy,
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    /// Append a pass drawing with `shader`. Its first image is the previous
+    /// output, followed by `images`. Returns the pass index.
+    pub fn add_pass(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        shader: ShaderId,
+        images: &[TextureId],
+    ) -> usize {
+        self.passes.push(PostPass {
+            pipeline: new_pass_pipeline(ctx, shader),
+            images: images.to_vec(),
+            uniforms: vec![],
+            enabled: true,
+        });
+        self.passes.len() - 1
+    }
+
+    /// Uniforms applied before drawing the pass `index`, kept until changed
+    pub fn set_uniforms<T>(&mut self, index: usize, uniforms: &T) {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(uniforms as *const T as *const u8, std::mem::size_of::<T>())
+        };
+        self.passes[index].uniforms = bytes.to_vec();
+    }
+
+    /// Disabled passes are skipped, the next pass samples the output before them
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        self.passes[index].enabled = enabled;
+    }
+
+    pub fn is_enabled(&self, index: usize) -> bool {
+        self.passes[index].enabled
+    }
+
+    /// Size of the targets, the framebuffer size as of the last `begin_scene`
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Begin the scene pass, end it with `end_render_pass` as usual.
+    /// Recreates the targets first if the window size changed.
+    pub fn begin_scene(&mut self, ctx: &mut dyn RenderingBackend, action: PassAction) {
+        let (width, height) = screen_size();
+        if (width, height) != (self.width, self.height) {
+            for target in self.targets.iter().copied() {
+                ctx.delete_render_pass(target);
+            }
+            self.targets = new_targets(ctx, width, height);
+            self.width = width;
+            self.height = height;
+        }
+        ctx.begin_pass(Some(self.targets[0]), action);
+    }
+
+    /// Run the enabled passes over the scene, the last one to the default framebuffer.
+    pub fn draw(&self, ctx: &mut dyn RenderingBackend) {
+        let enabled: Vec<usize> = (0..self.passes.len())
+            .filter(|&i| self.passes[i].enabled)
+            .collect();
+
+        let mut source = 0;
+        if enabled.is_empty() {
+            ctx.begin_default_pass(PassAction::Nothing);
+            self.draw_quad(ctx, self.copy, source, &[], &[]);
+            ctx.end_render_pass();
+            return;
+        }
+        for (n, &i) in enabled.iter().enumerate() {
+            let last = n + 1 == enabled.len();
+            if last {
+                ctx.begin_default_pass(PassAction::Nothing);
+            } else {
+                ctx.begin_pass(Some(self.targets[1 - source]), PassAction::Nothing);
+            }
+            let pass = &self.passes[i];
+            self.draw_quad(ctx, pass.pipeline, source, &pass.images, &pass.uniforms);
+            ctx.end_render_pass();
+            source = 1 - source;
+        }
+    }
+
+    fn draw_quad(
+        &self,
+        ctx: &mut dyn RenderingBackend,
+        pipeline: Pipeline,
+        source: usize,
+        images: &[TextureId],
+        uniforms: &[u8],
+    ) {
+        let mut bindings = Bindings {
+            vertex_buffers: vec![self.vertex_buffer],
+            index_buffer: self.index_buffer,
+            images: vec![ctx.render_pass_texture(self.targets[source])],
+        };
+        bindings.images.extend_from_slice(images);
+
+        ctx.apply_pipeline(&pipeline);
+        ctx.apply_bindings(&bindings);
+        if !uniforms.is_empty() {
+            ctx.apply_uniforms_from_bytes(uniforms.as_ptr(), uniforms.len());
+        }
+        ctx.draw(0, 6, 1);
+    }
+
+    /// Delete the targets, the pipelines and the quad. Pass shaders and
+    /// the extra pass images are owned by the caller.
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        for target in self.targets.iter().copied() {
+            ctx.delete_render_pass(target);
+        }
+        for pass in self.passes {
+            ctx.delete_pipeline(pass.pipeline);
+        }
+        ctx.delete_pipeline(self.copy);
+        ctx.delete_buffer(self.vertex_buffer);
+        ctx.delete_buffer(self.index_buffer);
+    }
+}
+
+fn screen_size() -> (u32, u32) {
+    let (width, height) = crate::window::screen_size();
+    ((width as u32).max(1), (height as u32).max(1))
+}
+
+fn new_targets(ctx: &mut dyn RenderingBackend, width: u32, height: u32) -> [RenderPass; 2] {
+    let color = TextureParams {
+        width,
+        height,
+        ..Default::default()
+    };
+    let scene_color = ctx.new_render_texture(color);
+    let scene_depth = ctx.new_render_texture(TextureParams {
+        format: TextureFormat::Depth,
+        ..color
+    });
+    let other_color = ctx.new_render_texture(color);
+    [
+        ctx.new_render_pass(scene_color, Some(scene_depth)),
+        ctx.new_render_pass(other_color, None),
+    ]
+}
+
+fn new_pass_pipeline(ctx: &mut dyn RenderingBackend, shader: ShaderId) -> Pipeline {
+    ctx.new_pipeline(
+        &[BufferLayout::default()],
+        &[VertexAttribute::new("in_pos", VertexFormat::Float2)],
+        shader,
+        PipelineParams::default(),
+    )
+}
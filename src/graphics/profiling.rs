@@ -2,13 +2,53 @@
 //!
 //! This module provides instrumentation to measure redundant GL state changes
 //! which are the primary target for optimization in the state caching system.
+//! State changes are counted only with the `profiling` feature: `GlCache` bumps
+//! thread-local counters without any locking, and those are merged into the
+//! global `GlStateProfiler` once per frame. Without the feature the
+//! instrumentation compiles to nothing.
+//!
+//! Per-frame CPU zones and GPU pass timings live in the frame profiler,
+//! see `enable_frame_profiler`, and can be shown on screen with `ProfilerOverlay`
+//! or saved for offline analysis with `export_trace`.
+//! Estimated GPU memory usage per resource is available with `gpu_memory_stats`.
+//! With the `tracy` feature the same instrumentation is forwarded to Tracy.
+
+mod draw_stats;
+mod frame;
+mod memory;
+mod overlay;
+mod trace;
+#[cfg(feature = "tracy")]
+pub(crate) mod tracy;
+
+pub use draw_stats::{pipeline_label, render_pass_label, DrawCounters, DrawStats};
+pub(crate) use draw_stats::{set_pipeline_label, set_render_pass_label};
+pub use frame::*;
+pub use memory::*;
+pub use overlay::ProfilerOverlay;
+pub(crate) use trace::escape as json_escape;
+pub use trace::{export_trace, trace_json};
 
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
+#[cfg(feature = "profiling")]
+use std::cell::RefCell;
+#[cfg(feature = "profiling")]
+use std::sync::atomic::{AtomicBool, Ordering};
+
 /// Global profiler instance for tracking GL state changes
 static PROFILER: std::sync::OnceLock<Arc<Mutex<GlStateProfiler>>> = std::sync::OnceLock::new();
 
+#[cfg(feature = "profiling")]
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+#[cfg(feature = "profiling")]
+thread_local! {
+    // Counters of the thread doing the GL calls, merged into PROFILER by `flush_state_changes`
+    static LOCAL_PROFILER: RefCell<GlStateProfiler> = RefCell::new(GlStateProfiler::new());
+}
+
 /// Statistics about GL state changes
 #[derive(Debug, Default, Clone)]
 pub struct StateChangeStats {
@@ -110,6 +150,18 @@ impl GlStateProfiler {
         self.stats.clone()
     }
 
+    /// Add up counters collected elsewhere
+    pub fn merge(&mut self, stats: &StateChangeStats) {
+        self.stats.total_calls += stats.total_calls;
+        self.stats.redundant_calls += stats.redundant_calls;
+        self.stats.buffer_binds += stats.buffer_binds;
+        self.stats.texture_binds += stats.texture_binds;
+        self.stats.program_uses += stats.program_uses;
+        self.stats.redundant_buffer_binds += stats.redundant_buffer_binds;
+        self.stats.redundant_texture_binds += stats.redundant_texture_binds;
+        self.stats.redundant_program_uses += stats.redundant_program_uses;
+    }
+
     /// Record a buffer binding operation
     pub fn record_buffer_bind(&mut self, target: u32, buffer: u32) {
         if !self.enabled {
@@ -200,6 +252,8 @@ pub fn get_profiler() -> Arc<Mutex<GlStateProfiler>> {
 
 /// Enable profiling
 pub fn enable_profiling() {
+    #[cfg(feature = "profiling")]
+    ENABLED.store(true, Ordering::Relaxed);
     if let Ok(mut profiler) = get_profiler().lock() {
         profiler.enable();
     }
@@ -207,66 +261,112 @@ pub fn enable_profiling() {
 
 /// Disable profiling
 pub fn disable_profiling() {
+    #[cfg(feature = "profiling")]
+    ENABLED.store(false, Ordering::Relaxed);
     if let Ok(mut profiler) = get_profiler().lock() {
         profiler.disable();
     }
 }
 
-/// Reset profiling statistics
+/// Reset profiling statistics, including the not yet merged counters of the calling thread
 pub fn reset_profiling() {
+    #[cfg(feature = "profiling")]
+    LOCAL_PROFILER.with(|local| local.borrow_mut().reset());
     if let Ok(mut profiler) = get_profiler().lock() {
         profiler.reset();
     }
 }
 
+/// Count a buffer bind on the calling thread
+#[cfg(feature = "profiling")]
+pub fn record_buffer_bind(target: u32, buffer: u32) {
+    if ENABLED.load(Ordering::Relaxed) {
+        LOCAL_PROFILER.with(|local| local.borrow_mut().record_buffer_bind(target, buffer));
+    }
+}
+
+/// Count a texture bind on the calling thread
+#[cfg(feature = "profiling")]
+pub fn record_texture_bind(slot: u32, texture: u32) {
+    if ENABLED.load(Ordering::Relaxed) {
+        LOCAL_PROFILER.with(|local| local.borrow_mut().record_texture_bind(slot, texture));
+    }
+}
+
+/// Count a program switch on the calling thread
+#[cfg(feature = "profiling")]
+pub fn record_program_use(program: u32) {
+    if ENABLED.load(Ordering::Relaxed) {
+        LOCAL_PROFILER.with(|local| local.borrow_mut().record_program_use(program));
+    }
+}
+
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub fn record_buffer_bind(_target: u32, _buffer: u32) {}
+
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub fn record_texture_bind(_slot: u32, _texture: u32) {}
+
+#[cfg(not(feature = "profiling"))]
+#[inline(always)]
+pub fn record_program_use(_program: u32) {}
+
+/// Merge the counters of the calling thread into the global profiler.
+/// Called by the GL backend once per frame, in `commit_frame`.
+pub fn flush_state_changes() {
+    #[cfg(feature = "profiling")]
+    LOCAL_PROFILER.with(|local| {
+        let mut local = local.borrow_mut();
+        if local.stats.total_calls == 0 {
+            return;
+        }
+        // The tracker is kept, a bind right after the frame boundary
+        // may still be redundant
+        let stats = std::mem::take(&mut local.stats);
+        if let Ok(mut profiler) = get_profiler().lock() {
+            profiler.merge(&stats);
+        }
+    });
+}
+
 /// Get current profiling statistics
 pub fn get_stats() -> Option<StateChangeStats> {
+    flush_state_changes();
     get_profiler()
         .lock()
         .ok()
         .map(|profiler| profiler.get_stats())
 }
 
-/// Print a profiling report
+/// Print a profiling report: GL state changes, and the GPU time and draw calls
+/// of every render pass while the frame profiler is enabled.
 pub fn print_report() {
     if let Some(stats) = get_stats() {
         stats.print_report();
     }
+    frame::print_pass_breakdown();
 }
 
 // Macros for easy profiling instrumentation
 #[macro_export]
 macro_rules! profile_buffer_bind {
     ($target:expr, $buffer:expr) => {
-        #[cfg(feature = "profiling")]
-        {
-            if let Ok(mut profiler) = $crate::graphics::profiling::get_profiler().lock() {
-                profiler.record_buffer_bind($target, $buffer);
-            }
-        }
+        $crate::graphics::profiling::record_buffer_bind($target, $buffer);
     };
 }
 
 #[macro_export]
 macro_rules! profile_texture_bind {
     ($slot:expr, $texture:expr) => {
-        #[cfg(feature = "profiling")]
-        {
-            if let Ok(mut profiler) = $crate::graphics::profiling::get_profiler().lock() {
-                profiler.record_texture_bind($slot, $texture);
-            }
-        }
+        $crate::graphics::profiling::record_texture_bind($slot, $texture);
     };
 }
 
 #[macro_export]
 macro_rules! profile_program_use {
     ($program:expr) => {
-        #[cfg(feature = "profiling")]
-        {
-            if let Ok(mut profiler) = $crate::graphics::profiling::get_profiler().lock() {
-                profiler.record_program_use($program);
-            }
-        }
+        $crate::graphics::profiling::record_program_use($program);
     };
 }
@@ -0,0 +1,168 @@
+//! Draw statistics per render pass and pipeline
+//!
+//! While the frame profiler is enabled, every draw call, its triangles and the
+//! GL state changes done through the state cache are attributed to the current
+//! render pass and pipeline. Passes and pipelines are reported by their labels,
+//! see `RenderingBackend::set_pipeline_label` and
+//! `RenderingBackend::set_render_pass_label`.
+//!
+//! ```no_run
+//! # use miniquad::graphics::profiling;
+//! if let Some(frame) = profiling::last_frame() {
+//!     for (pipeline, counters) in frame.draw_stats_per_pipeline() {
+//!         println!("{}: {} draw calls", pipeline, counters.draw_calls);
+//!     }
+//! }
+//! ```
+
+use crate::graphics::{Pipeline, RenderPass};
+
+use std::cell::RefCell;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+static LABELS: OnceLock<Mutex<Labels>> = OnceLock::new();
+
+thread_local! {
+    // Draw calls are recorded on the rendering thread only,
+    // no need to lock anything on every draw
+    static RECORDER: RefCell<DrawStatsRecorder> = RefCell::new(DrawStatsRecorder::default());
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DrawCounters {
+    pub draw_calls: u64,
+    pub triangles: u64,
+    pub state_changes: u64,
+}
+
+impl DrawCounters {
+    fn add(&mut self, other: &DrawCounters) {
+        self.draw_calls += other.draw_calls;
+        self.triangles += other.triangles;
+        self.state_changes += other.state_changes;
+    }
+}
+
+/// Counters of one pipeline within one render pass
+#[derive(Debug, Clone)]
+pub struct DrawStats {
+    pub pass: String,
+    pub pipeline: String,
+    pub counters: DrawCounters,
+}
+
+#[derive(Debug, Default)]
+struct Labels {
+    pipelines: HashMap<Pipeline, String>,
+    passes: HashMap<RenderPass, String>,
+}
+
+#[derive(Debug, Default)]
+struct DrawStatsRecorder {
+    pass: Option<RenderPass>,
+    pipeline: Option<Pipeline>,
+    counters: HashMap<(Option<RenderPass>, Option<Pipeline>), DrawCounters>,
+}
+
+impl DrawStatsRecorder {
+    fn current(&mut self) -> &mut DrawCounters {
+        self.counters.entry((self.pass, self.pipeline)).or_default()
+    }
+}
+
+fn labels() -> &'static Mutex<Labels> {
+    LABELS.get_or_init(|| Mutex::new(Labels::default()))
+}
+
+pub(crate) fn set_pipeline_label(pipeline: Pipeline, label: &str) {
+    if let Ok(mut labels) = labels().lock() {
+        labels.pipelines.insert(pipeline, label.to_string());
+    }
+}
+
+pub(crate) fn set_render_pass_label(pass: RenderPass, label: &str) {
+    if let Ok(mut labels) = labels().lock() {
+        labels.passes.insert(pass, label.to_string());
+    }
+}
+
+/// Label given with `set_pipeline_label`, "pipeline N" if there is none
+pub fn pipeline_label(pipeline: Pipeline) -> String {
+    labels()
+        .lock()
+        .ok()
+        .and_then(|labels| labels.pipelines.get(&pipeline).cloned())
+        .unwrap_or_else(|| format!("pipeline {}", pipeline.0))
+}
+
+/// Label given with `set_render_pass_label`, "pass N" if there is none.
+/// `None` is the default pass.
+pub fn render_pass_label(pass: Option<RenderPass>) -> String {
+    match pass {
+        None => "default pass".to_string(),
+        Some(pass) => labels()
+            .lock()
+            .ok()
+            .and_then(|labels| labels.passes.get(&pass).cloned())
+            .unwrap_or_else(|| format!("pass {}", pass.0)),
+    }
+}
+
+pub(super) fn set_current_pass(pass: Option<RenderPass>) {
+    RECORDER.with(|recorder| {
+        let mut recorder = recorder.borrow_mut();
+        recorder.pass = pass;
+        recorder.pipeline = None;
+    });
+}
+
+pub(super) fn set_current_pipeline(pipeline: Pipeline) {
+    RECORDER.with(|recorder| recorder.borrow_mut().pipeline = Some(pipeline));
+}
+
+pub(super) fn record_draw(triangles: u64) {
+    RECORDER.with(|recorder| {
+        let mut recorder = recorder.borrow_mut();
+        let counters = recorder.current();
+        counters.draw_calls += 1;
+        counters.triangles += triangles;
+    });
+}
+
+pub(super) fn record_state_change() {
+    RECORDER.with(|recorder| recorder.borrow_mut().current().state_changes += 1);
+}
+
+/// Everything recorded since the last call, most draw calls first
+pub(super) fn take_frame_stats() -> Vec<DrawStats> {
+    let counters = RECORDER.with(|recorder| std::mem::take(&mut recorder.borrow_mut().counters));
+    let mut stats: Vec<DrawStats> = counters
+        .into_iter()
+        .map(|((pass, pipeline), counters)| DrawStats {
+            pass: render_pass_label(pass),
+            pipeline: pipeline.map_or_else(|| "no pipeline".to_string(), pipeline_label),
+            counters,
+        })
+        .collect();
+    stats.sort_by_key(|stats| Reverse(stats.counters.draw_calls));
+    stats
+}
+
+/// Sum the counters with the same key, most draw calls first
+pub(super) fn group_by(
+    stats: &[DrawStats],
+    key: impl Fn(&DrawStats) -> &str,
+) -> Vec<(String, DrawCounters)> {
+    let mut groups: Vec<(String, DrawCounters)> = vec![];
+    for stats in stats {
+        let key = key(stats);
+        match groups.iter_mut().find(|(group, _)| group == key) {
+            Some((_, counters)) => counters.add(&stats.counters),
+            None => groups.push((key.to_string(), stats.counters)),
+        }
+    }
+    groups.sort_by_key(|(_, counters)| Reverse(counters.draw_calls));
+    groups
+}
@@ -0,0 +1,455 @@
+//! Hierarchical frame profiler
+//!
+//! Collects per-frame timings instead of aggregate counters:
+//! - Scoped CPU zones, nested zones form a tree per frame
+//! - GPU time of every render pass, measured with timer queries
+//! - Draw calls, triangles and state changes per render pass and pipeline
+//! - History of the last `FRAME_HISTORY` frames and rolling averages over it
+//!
+//! Disabled by default, zones cost a single atomic load while disabled. With the
+//! `profiling` feature it starts enabled, so every render pass is measured and
+//! wrapped in a debug group without any manual instrumentation, and
+//! `profiling::print_report` includes the per-pass breakdown.
+//! With the `tracy` feature zones and frames are also sent to Tracy,
+//! whether the frame profiler is enabled or not.
+//!
+//! ```no_run
+//! miniquad::graphics::profiling::enable_frame_profiler();
+//! {
+//!     let _zone = miniquad::graphics::profiling::zone("update");
+//!     // ...
+//! }
+//! if let Some(frame) = miniquad::graphics::profiling::last_frame() {
+//!     frame.print_report();
+//! }
+//! ```
+
+use super::draw_stats::{self, DrawCounters, DrawStats};
+use crate::graphics::{Pipeline, RenderPass};
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+/// Amount of finished frames kept for queries
+pub const FRAME_HISTORY: usize = 120;
+
+/// Amount of frames the rolling averages are computed over
+pub const AVERAGE_WINDOW: usize = 60;
+
+static ENABLED: AtomicBool = AtomicBool::new(cfg!(feature = "profiling"));
+static DRAW_CALLS: AtomicU64 = AtomicU64::new(0);
+static FRAME_PROFILER: OnceLock<Mutex<FrameProfiler>> = OnceLock::new();
+
+/// A finished CPU zone
+#[derive(Debug, Clone)]
+pub struct ZoneTiming {
+    pub name: &'static str,
+    /// Index of the parent zone in `FrameProfile::zones`
+    pub parent: Option<usize>,
+    pub depth: usize,
+    /// Start of the zone, relative to the start of the frame
+    pub start_ms: f64,
+    pub duration_ms: f64,
+}
+
+/// GPU time spent on one render pass
+#[derive(Debug, Clone)]
+pub struct GpuPassTiming {
+    pub label: String,
+    pub duration_ms: f64,
+}
+
+/// Everything measured during one frame
+#[derive(Debug, Clone, Default)]
+pub struct FrameProfile {
+    pub index: u64,
+    /// Start of the frame, in milliseconds since the unix epoch
+    pub start_ms: f64,
+    /// Time between two `commit_frame` calls
+    pub cpu_time_ms: f64,
+    pub draw_calls: u64,
+    /// Zones in the order they were opened
+    pub zones: Vec<ZoneTiming>,
+    /// GPU timings arrive a few frames later, so this is empty
+    /// for the latest frames. Empty if timer queries are not supported.
+    pub gpu_passes: Vec<GpuPassTiming>,
+    /// Per pass and pipeline counters, most draw calls first
+    pub draw_stats: Vec<DrawStats>,
+}
+
+impl FrameProfile {
+    /// Zone name with all the parents, like "draw/sprites"
+    pub fn zone_path(&self, zone: usize) -> String {
+        let zone = &self.zones[zone];
+        match zone.parent {
+            Some(parent) => format!("{}/{}", self.zone_path(parent), zone.name),
+            None => zone.name.to_string(),
+        }
+    }
+
+    /// Counters summed over all the passes, most draw calls first
+    pub fn draw_stats_per_pipeline(&self) -> Vec<(String, DrawCounters)> {
+        draw_stats::group_by(&self.draw_stats, |stats| &stats.pipeline)
+    }
+
+    /// Counters summed over all the pipelines, most draw calls first
+    pub fn draw_stats_per_pass(&self) -> Vec<(String, DrawCounters)> {
+        draw_stats::group_by(&self.draw_stats, |stats| &stats.pass)
+    }
+
+    /// Total GPU time of all the measured passes
+    pub fn gpu_time_ms(&self) -> f64 {
+        self.gpu_passes.iter().map(|pass| pass.duration_ms).sum()
+    }
+
+    pub fn print_report(&self) {
+        println!("\n=== Frame {} ===", self.index);
+        println!("CPU frame time: {:.3} ms", self.cpu_time_ms);
+        println!("Draw calls: {}", self.draw_calls);
+        for zone in &self.zones {
+            println!(
+                "{:indent$}{}: {:.3} ms",
+                "",
+                zone.name,
+                zone.duration_ms,
+                indent = 2 + zone.depth * 2
+            );
+        }
+        if !self.gpu_passes.is_empty() {
+            println!("GPU time: {:.3} ms", self.gpu_time_ms());
+            for pass in &self.gpu_passes {
+                println!("  {}: {:.3} ms", pass.label, pass.duration_ms);
+            }
+        }
+        if !self.draw_stats.is_empty() {
+            println!("Draws:");
+            for stats in &self.draw_stats {
+                println!(
+                    "  {} / {}: {} draw calls, {} triangles, {} state changes",
+                    stats.pass,
+                    stats.pipeline,
+                    stats.counters.draw_calls,
+                    stats.counters.triangles,
+                    stats.counters.state_changes
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct FrameProfiler {
+    current: FrameProfile,
+    frame_start: Option<f64>,
+    // Indices of the zones still open in current.zones
+    open_zones: Vec<usize>,
+    history: VecDeque<FrameProfile>,
+}
+
+fn profiler() -> &'static Mutex<FrameProfiler> {
+    FRAME_PROFILER.get_or_init(|| Mutex::new(FrameProfiler::default()))
+}
+
+fn now_ms() -> f64 {
+    crate::date::now() * 1000.0
+}
+
+/// Start collecting frame profiles.
+///
+/// On GL render passes are measured with GL_TIME_ELAPSED queries, and only one
+/// of those may be active at a time: `ElapsedQuery` should not be used
+/// inside a render pass while the frame profiler is enabled.
+/// Passes are also wrapped in debug groups (GL4.3 or KHR_debug) or get their
+/// render command encoder labeled (Metal), named after the pass label.
+pub fn enable_frame_profiler() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Stop collecting frame profiles, the history is kept
+pub fn disable_frame_profiler() {
+    ENABLED.store(false, Ordering::Relaxed);
+}
+
+pub fn is_frame_profiler_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Open a CPU zone, nested in the currently open one.
+/// Should be paired with `end_zone`, or use `zone` to close it automatically.
+pub fn begin_zone(name: &'static str) {
+    #[cfg(feature = "tracy")]
+    super::tracy::begin_zone(name);
+
+    if !is_frame_profiler_enabled() {
+        return;
+    }
+    let now = now_ms();
+    if let Ok(mut profiler) = profiler().lock() {
+        let frame_start = *profiler.frame_start.get_or_insert(now);
+        let parent = profiler.open_zones.last().copied();
+        let depth = profiler.open_zones.len();
+        let index = profiler.current.zones.len();
+        profiler.current.zones.push(ZoneTiming {
+            name,
+            parent,
+            depth,
+            start_ms: now - frame_start,
+            duration_ms: 0.0,
+        });
+        profiler.open_zones.push(index);
+    }
+}
+
+/// Close the innermost open CPU zone
+pub fn end_zone() {
+    #[cfg(feature = "tracy")]
+    super::tracy::end_zone();
+
+    if !is_frame_profiler_enabled() {
+        return;
+    }
+    let now = now_ms();
+    if let Ok(mut profiler) = profiler().lock() {
+        let frame_start = profiler.frame_start.unwrap_or(now);
+        if let Some(index) = profiler.open_zones.pop() {
+            let zone = &mut profiler.current.zones[index];
+            zone.duration_ms = now - frame_start - zone.start_ms;
+        }
+    }
+}
+
+/// Closes its zone when dropped
+#[must_use]
+pub struct ZoneGuard {
+    active: bool,
+}
+
+impl Drop for ZoneGuard {
+    fn drop(&mut self) {
+        if self.active {
+            end_zone();
+        }
+    }
+}
+
+/// Open a CPU zone that lasts until the returned guard is dropped
+pub fn zone(name: &'static str) -> ZoneGuard {
+    let active = is_frame_profiler_enabled() || cfg!(feature = "tracy");
+    if active {
+        begin_zone(name);
+    }
+    ZoneGuard { active }
+}
+
+/// Count a draw call for the current frame. Called by the rendering backends.
+pub(crate) fn record_draw_call(triangles: u64) {
+    #[cfg(feature = "tracy")]
+    super::tracy::record_draw_call();
+
+    if is_frame_profiler_enabled() {
+        DRAW_CALLS.fetch_add(1, Ordering::Relaxed);
+        draw_stats::record_draw(triangles);
+    }
+}
+
+/// Following draws belong to `pass`. Called by the rendering backends in `begin_pass`.
+pub(crate) fn record_begin_pass(pass: Option<RenderPass>) {
+    if is_frame_profiler_enabled() {
+        draw_stats::set_current_pass(pass);
+    }
+}
+
+/// Following draws and state changes belong to `pipeline`.
+/// Called by the rendering backends in `apply_pipeline`.
+pub(crate) fn record_apply_pipeline(pipeline: Pipeline) {
+    if is_frame_profiler_enabled() {
+        draw_stats::set_current_pipeline(pipeline);
+    }
+}
+
+/// Count a GL state change for the current pass and pipeline. Called by `GlCache`.
+pub(crate) fn record_state_change() {
+    if is_frame_profiler_enabled() {
+        draw_stats::record_state_change();
+    }
+}
+
+/// Index of the frame currently being recorded
+pub fn current_frame_index() -> u64 {
+    profiler().lock().map(|p| p.current.index).unwrap_or(0)
+}
+
+/// Finish the current frame. Called by the rendering backends in `commit_frame`.
+pub(crate) fn end_frame() {
+    #[cfg(feature = "tracy")]
+    super::tracy::end_frame();
+
+    if !is_frame_profiler_enabled() {
+        return;
+    }
+    let now = now_ms();
+    if let Ok(mut profiler) = profiler().lock() {
+        let frame_start = profiler.frame_start.unwrap_or(now);
+        // Zones left open are closed at the end of the frame
+        while let Some(index) = profiler.open_zones.pop() {
+            let zone = &mut profiler.current.zones[index];
+            zone.duration_ms = now - frame_start - zone.start_ms;
+        }
+        profiler.current.start_ms = frame_start;
+        profiler.current.cpu_time_ms = now - frame_start;
+        profiler.current.draw_calls = DRAW_CALLS.swap(0, Ordering::Relaxed);
+        profiler.current.draw_stats = draw_stats::take_frame_stats();
+
+        let next = FrameProfile {
+            index: profiler.current.index + 1,
+            ..Default::default()
+        };
+        let finished = std::mem::replace(&mut profiler.current, next);
+        profiler.history.push_back(finished);
+        if profiler.history.len() > FRAME_HISTORY {
+            profiler.history.pop_front();
+        }
+        profiler.frame_start = Some(now);
+    }
+}
+
+/// Attach a GPU pass timing to an already recorded (or the current) frame
+pub(crate) fn record_gpu_pass(frame: u64, label: String, duration_ms: f64) {
+    if let Ok(mut profiler) = profiler().lock() {
+        let timing = GpuPassTiming { label, duration_ms };
+        if profiler.current.index == frame {
+            profiler.current.gpu_passes.push(timing);
+        } else if let Some(profile) = profiler.history.iter_mut().find(|f| f.index == frame) {
+            profile.gpu_passes.push(timing);
+        }
+    }
+}
+
+/// The latest finished frame
+pub fn last_frame() -> Option<FrameProfile> {
+    profiler().lock().ok()?.history.back().cloned()
+}
+
+/// A finished frame by its index, if it is still in the history
+pub fn frame(index: u64) -> Option<FrameProfile> {
+    profiler()
+        .lock()
+        .ok()?
+        .history
+        .iter()
+        .find(|f| f.index == index)
+        .cloned()
+}
+
+/// All the frames in the history, oldest first
+pub fn frame_history() -> Vec<FrameProfile> {
+    profiler()
+        .lock()
+        .map(|p| p.history.iter().cloned().collect())
+        .unwrap_or_default()
+}
+
+fn rolling_average(f: impl Fn(&FrameProfile) -> Option<f64>) -> Option<f64> {
+    let profiler = profiler().lock().ok()?;
+    let values: Vec<f64> = profiler
+        .history
+        .iter()
+        .rev()
+        .take(AVERAGE_WINDOW)
+        .filter_map(f)
+        .collect();
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+/// Average CPU frame time over the last `AVERAGE_WINDOW` frames
+pub fn average_frame_ms() -> Option<f64> {
+    rolling_average(|frame| Some(frame.cpu_time_ms))
+}
+
+/// Average time of a zone over the last `AVERAGE_WINDOW` frames it was recorded in.
+/// `path` is the zone name with its parents, like "draw/sprites",
+/// zones with the same path in one frame are summed.
+pub fn average_zone_ms(path: &str) -> Option<f64> {
+    rolling_average(|frame| {
+        let mut total = None;
+        for i in 0..frame.zones.len() {
+            if frame.zone_path(i) == path {
+                *total.get_or_insert(0.0) += frame.zones[i].duration_ms;
+            }
+        }
+        total
+    })
+}
+
+/// Average GPU time of a render pass over the last `AVERAGE_WINDOW` frames
+/// with a GPU timing for that pass.
+pub fn average_gpu_pass_ms(label: &str) -> Option<f64> {
+    rolling_average(|frame| {
+        let mut total = None;
+        for pass in frame.gpu_passes.iter().filter(|pass| pass.label == label) {
+            *total.get_or_insert(0.0) += pass.duration_ms;
+        }
+        total
+    })
+}
+
+/// Averages per render pass over the last `AVERAGE_WINDOW` frames, part of
+/// `profiling::print_report`
+pub(crate) fn print_pass_breakdown() {
+    let frames = frame_history();
+    let frames = &frames[frames.len().saturating_sub(AVERAGE_WINDOW)..];
+    if frames.is_empty() {
+        return;
+    }
+
+    // label, GPU ms summed and frames with a timing, draw calls summed
+    let mut passes: Vec<(String, f64, usize, u64)> = vec![];
+    for frame in frames {
+        for pass in &frame.gpu_passes {
+            let i = entry(&mut passes, &pass.label);
+            passes[i].1 += pass.duration_ms;
+            passes[i].2 += 1;
+        }
+        for (label, counters) in frame.draw_stats_per_pass() {
+            let i = entry(&mut passes, &label);
+            passes[i].3 += counters.draw_calls;
+        }
+    }
+    fn entry(passes: &mut Vec<(String, f64, usize, u64)>, label: &str) -> usize {
+        passes
+            .iter()
+            .position(|pass| pass.0 == label)
+            .unwrap_or_else(|| {
+                passes.push((label.to_string(), 0.0, 0, 0));
+                passes.len() - 1
+            })
+    }
+
+    println!("\n=== Render passes, last {} frames ===", frames.len());
+    for (label, gpu_ms, timed, draw_calls) in passes {
+        let gpu = if timed > 0 {
+            format!("{:.3} ms GPU", gpu_ms / timed as f64)
+        } else {
+            "no GPU timing".to_string()
+        };
+        println!(
+            "  {}: {}, {:.1} draw calls",
+            label,
+            gpu,
+            draw_calls as f64 / frames.len() as f64
+        );
+    }
+}
+
+/// Open a CPU zone until the end of the enclosing scope
+#[macro_export]
+macro_rules! profile_zone {
+    ($name:expr) => {
+        let _profile_zone_guard = $crate::graphics::profiling::zone($name);
+    };
+}
@@ -0,0 +1,222 @@
+//! GPU memory accounting
+//!
+//! The rendering backends report every texture and buffer allocation,
+//! so the memory usage can be broken down by texture format and buffer usage
+//! and the largest resources can be listed.
+//!
+//! Sizes are estimates computed from the resource parameters: mipmaps,
+//! cubemap faces, MSAA samples and multi-buffering copies are included,
+//! driver-side padding and alignment is not.
+//!
+//! ```no_run
+//! let stats = miniquad::graphics::profiling::gpu_memory_stats();
+//! println!("{} MB", stats.total_bytes / 1024 / 1024);
+//! miniquad::graphics::profiling::print_gpu_memory_report(10);
+//! ```
+
+use crate::graphics::{
+    BufferId, BufferType, BufferUsage, TextureFormat, TextureId, TextureKind, TextureParams,
+};
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
+static GPU_MEMORY: OnceLock<Mutex<GpuMemoryTracker>> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GpuResourceId {
+    Texture(TextureId),
+    Buffer(BufferId),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GpuResourceKind {
+    Texture(TextureFormat),
+    Buffer(BufferType, BufferUsage),
+}
+
+/// A live GPU resource and its estimated size
+#[derive(Debug, Clone, Copy)]
+pub struct GpuAllocation {
+    pub id: GpuResourceId,
+    pub kind: GpuResourceKind,
+    pub bytes: usize,
+}
+
+/// Totals over all the live GPU resources
+#[derive(Debug, Clone, Default)]
+pub struct GpuMemoryStats {
+    pub total_bytes: usize,
+    /// Highest `total_bytes` seen so far
+    pub peak_bytes: usize,
+    pub texture_bytes: usize,
+    pub buffer_bytes: usize,
+    pub texture_count: usize,
+    pub buffer_count: usize,
+    /// Bytes per texture format, largest first
+    pub by_texture_format: Vec<(TextureFormat, usize)>,
+    /// Bytes per buffer usage, largest first
+    pub by_buffer_usage: Vec<(BufferUsage, usize)>,
+}
+
+impl GpuMemoryStats {
+    pub fn print_report(&self) {
+        println!("\n=== GPU Memory ===");
+        println!(
+            "Total: {} (peak: {})",
+            format_bytes(self.total_bytes),
+            format_bytes(self.peak_bytes)
+        );
+        println!(
+            "Textures: {} in {} textures",
+            format_bytes(self.texture_bytes),
+            self.texture_count
+        );
+        for (format, bytes) in &self.by_texture_format {
+            println!("  {:?}: {}", format, format_bytes(*bytes));
+        }
+        println!(
+            "Buffers: {} in {} buffers",
+            format_bytes(self.buffer_bytes),
+            self.buffer_count
+        );
+        for (usage, bytes) in &self.by_buffer_usage {
+            println!("  {:?}: {}", usage, format_bytes(*bytes));
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct GpuMemoryTracker {
+    allocations: HashMap<GpuResourceId, GpuAllocation>,
+    total_bytes: usize,
+    peak_bytes: usize,
+}
+
+fn tracker() -> &'static Mutex<GpuMemoryTracker> {
+    GPU_MEMORY.get_or_init(|| Mutex::new(GpuMemoryTracker::default()))
+}
+
+fn format_bytes(bytes: usize) -> String {
+    if bytes >= 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+    } else if bytes >= 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024.0)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+/// Estimated size of a texture with the given parameters
+pub fn texture_bytes(params: &TextureParams) -> usize {
+    let mut bytes = params.format.size(params.width, params.height) as usize;
+    if params.kind == TextureKind::CubeMap {
+        bytes *= 6;
+    }
+    if params.allocate_mipmaps {
+        // The whole mip chain adds up to a third of the base level
+        bytes += bytes / 3;
+    }
+    bytes * params.sample_count.max(1) as usize
+}
+
+/// Record a new allocation, or the new size of an existing one
+pub(crate) fn track(id: GpuResourceId, kind: GpuResourceKind, bytes: usize) {
+    if let Ok(mut tracker) = tracker().lock() {
+        let allocation = GpuAllocation { id, kind, bytes };
+        if let Some(old) = tracker.allocations.insert(id, allocation) {
+            tracker.total_bytes -= old.bytes;
+        }
+        tracker.total_bytes += bytes;
+        tracker.peak_bytes = tracker.peak_bytes.max(tracker.total_bytes);
+    }
+}
+
+pub(crate) fn track_texture(texture: TextureId, params: &TextureParams) {
+    track(
+        GpuResourceId::Texture(texture),
+        GpuResourceKind::Texture(params.format),
+        texture_bytes(params),
+    );
+}
+
+pub(crate) fn track_buffer(buffer: BufferId, type_: BufferType, usage: BufferUsage, bytes: usize) {
+    track(
+        GpuResourceId::Buffer(buffer),
+        GpuResourceKind::Buffer(type_, usage),
+        bytes,
+    );
+}
+
+/// Forget a deleted resource
+pub(crate) fn untrack(id: GpuResourceId) {
+    if let Ok(mut tracker) = tracker().lock() {
+        if let Some(old) = tracker.allocations.remove(&id) {
+            tracker.total_bytes -= old.bytes;
+        }
+    }
+}
+
+pub fn gpu_memory_stats() -> GpuMemoryStats {
+    let tracker = match tracker().lock() {
+        Ok(tracker) => tracker,
+        Err(_) => return GpuMemoryStats::default(),
+    };
+    let mut stats = GpuMemoryStats {
+        total_bytes: tracker.total_bytes,
+        peak_bytes: tracker.peak_bytes,
+        ..Default::default()
+    };
+    let mut by_format: HashMap<TextureFormat, usize> = HashMap::new();
+    let mut by_usage: HashMap<BufferUsage, usize> = HashMap::new();
+    for allocation in tracker.allocations.values() {
+        match allocation.kind {
+            GpuResourceKind::Texture(format) => {
+                stats.texture_bytes += allocation.bytes;
+                stats.texture_count += 1;
+                *by_format.entry(format).or_default() += allocation.bytes;
+            }
+            GpuResourceKind::Buffer(_, usage) => {
+                stats.buffer_bytes += allocation.bytes;
+                stats.buffer_count += 1;
+                *by_usage.entry(usage).or_default() += allocation.bytes;
+            }
+        }
+    }
+    stats.by_texture_format = by_format.into_iter().collect();
+    stats
+        .by_texture_format
+        .sort_by_key(|entry| Reverse(entry.1));
+    stats.by_buffer_usage = by_usage.into_iter().collect();
+    stats.by_buffer_usage.sort_by_key(|entry| Reverse(entry.1));
+    stats
+}
+
+/// The `n` largest live resources, largest first
+pub fn largest_gpu_allocations(n: usize) -> Vec<GpuAllocation> {
+    let mut allocations: Vec<GpuAllocation> = match tracker().lock() {
+        Ok(tracker) => tracker.allocations.values().copied().collect(),
+        Err(_) => return vec![],
+    };
+    allocations.sort_by_key(|allocation| Reverse(allocation.bytes));
+    allocations.truncate(n);
+    allocations
+}
+
+/// Print the totals followed by the `top_n` largest resources
+pub fn print_gpu_memory_report(top_n: usize) {
+    gpu_memory_stats().print_report();
+    let largest = largest_gpu_allocations(top_n);
+    if !largest.is_empty() {
+        println!("Largest resources:");
+        for allocation in largest {
+            println!(
+                "  {:?} {:?}: {}",
+                allocation.id,
+                allocation.kind,
+                format_bytes(allocation.bytes)
+            );
+        }
+    }
+}
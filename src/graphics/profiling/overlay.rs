@@ -0,0 +1,388 @@
+//! Built-in on-screen overlay with the profiler numbers
+//!
+//! Draws a small panel in the top left corner: frame time graph of the frame
+//! profiler history, and the latest frame's CPU/GPU time, draw calls and
+//! redundant state changes. Everything is plain colored quads, including the
+//! tiny built-in bitmap font, so it works with any backend and needs no assets.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::profiling::ProfilerOverlay;
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! let mut overlay = ProfilerOverlay::new(&mut *ctx);
+//! overlay.set_visible(true);
+//!
+//! // each frame, after everything else was drawn
+//! overlay.draw(&mut *ctx);
+//! ctx.commit_frame();
+//! ```
+
+use super::*;
+use crate::graphics::command_buffer::BatchStats;
+use crate::graphics::*;
+
+const MAX_QUADS: usize = 4096;
+
+/// Frame times at the top of the graph
+const GRAPH_MAX_MS: f64 = 33.3;
+const GRAPH_HEIGHT: f32 = 60.0;
+const PANEL_WIDTH: f32 = 250.0;
+const MARGIN: f32 = 5.0;
+/// Size of one pixel of the bitmap font, in logical pixels
+const FONT_PIXEL: f32 = 2.0;
+const LINE_HEIGHT: f32 = FONT_PIXEL * 7.0;
+
+const BACKGROUND: [f32; 4] = [0.0, 0.0, 0.0, 0.6];
+const TEXT: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const GOOD: [f32; 4] = [0.2, 0.9, 0.2, 1.0];
+const SLOW: [f32; 4] = [0.9, 0.9, 0.2, 1.0];
+const BAD: [f32; 4] = [0.9, 0.2, 0.2, 1.0];
+const TARGET_LINE: [f32; 4] = [1.0, 1.0, 1.0, 0.3];
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Vertex {
+    pos: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Toggleable profiler overlay, see the module docs
+pub struct ProfilerOverlay {
+    pipeline: Pipeline,
+    bindings: Bindings,
+    vertices: Vec<Vertex>,
+    visible: bool,
+    batch_stats: Option<BatchStats>,
+    last_redundant_calls: u64,
+    redundant_calls: u64,
+}
+
+impl ProfilerOverlay {
+    /// Create the GPU resources of the overlay. The overlay starts hidden.
+    pub fn new(ctx: &mut dyn RenderingBackend) -> ProfilerOverlay {
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<Vertex>(MAX_QUADS * 4),
+        );
+        let indices: Vec<u16> = (0..MAX_QUADS as u16)
+            .flat_map(|i| [i * 4, i * 4 + 1, i * 4 + 2, i * 4, i * 4 + 2, i * 4 + 3])
+            .collect();
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&indices),
+        );
+
+        let shader = ctx
+            .new_shader(
+                match ctx.info().backend {
+                    Backend::OpenGl => ShaderSource::Glsl {
+                        vertex: shader::VERTEX,
+                        fragment: shader::FRAGMENT,
+                    },
+                    Backend::Metal => ShaderSource::Msl {
+                        program: shader::METAL,
+                    },
+                },
+                shader::meta(),
+            )
+            .unwrap_or_else(|e| panic!("Failed to load profiler overlay shader: {}", e));
+
+        let pipeline = ctx.new_pipeline(
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("in_pos", VertexFormat::Float2),
+                VertexAttribute::new("in_color", VertexFormat::Float4),
+            ],
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        ProfilerOverlay {
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: vec![],
+            },
+            vertices: Vec::with_capacity(MAX_QUADS * 4),
+            visible: false,
+            batch_stats: None,
+            last_redundant_calls: 0,
+            redundant_calls: 0,
+        }
+    }
+
+    pub fn is_visible(&self) -> bool {
+        self.visible
+    }
+
+    /// Show or hide the overlay.
+    /// Showing it also enables the frame profiler, as the overlay is empty without it.
+    pub fn set_visible(&mut self, visible: bool) {
+        self.visible = visible;
+        if visible {
+            enable_frame_profiler();
+        }
+    }
+
+    pub fn toggle(&mut self) {
+        self.set_visible(!self.visible);
+    }
+
+    /// Command buffer batching numbers to display,
+    /// for example from `GlContext::command_buffer_stats`.
+    pub fn set_batch_stats(&mut self, stats: Option<BatchStats>) {
+        self.batch_stats = stats;
+    }
+
+    /// Draw the overlay in its own pass on top of the default framebuffer.
+    /// Should be called after all the other passes, right before `commit_frame`.
+    pub fn draw(&mut self, ctx: &mut dyn RenderingBackend) {
+        // Keep track of the state changes even while hidden, so the number
+        // is right in the first visible frame.
+        if let Some(stats) = get_stats() {
+            self.redundant_calls = stats
+                .redundant_calls
+                .saturating_sub(self.last_redundant_calls);
+            self.last_redundant_calls = stats.redundant_calls;
+        }
+        if !self.visible {
+            return;
+        }
+
+        let (screen_width, screen_height) = crate::window::screen_size();
+        let dpi_scale = crate::window::dpi_scale();
+        self.vertices.clear();
+        self.build(screen_width / dpi_scale, screen_height / dpi_scale);
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        ctx.buffer_update(
+            self.bindings.vertex_buffers[0],
+            BufferSource::slice(&self.vertices),
+        );
+        ctx.begin_default_pass(PassAction::Nothing);
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_bindings(&self.bindings);
+        ctx.draw(0, (self.vertices.len() / 4 * 6) as i32, 1);
+        ctx.end_render_pass();
+    }
+
+    /// Fill `self.vertices`, in logical pixels of a `width`x`height` screen
+    fn build(&mut self, width: f32, height: f32) {
+        let history = frame_history();
+        let last = history.last();
+
+        let mut lines = vec![];
+        if let Some(frame) = last {
+            let fps = average_frame_ms().map_or(0.0, |ms| 1000.0 / ms.max(0.001));
+            lines.push(format!("FPS {:.1}", fps));
+            lines.push(format!("CPU {:.2} MS", frame.cpu_time_ms));
+            // The latest frames are usually still waiting for the GPU results
+            if let Some(gpu) = history.iter().rev().find(|f| !f.gpu_passes.is_empty()) {
+                lines.push(format!("GPU {:.2} MS", gpu.gpu_time_ms()));
+            }
+            lines.push(format!("DC {}", frame.draw_calls));
+        }
+        lines.push(format!("RB {}", self.redundant_calls));
+        if let Some(batch) = &self.batch_stats {
+            lines.push(format!("BATCH {:.0}%", batch.batching_efficiency()));
+        }
+
+        let panel_height =
+            MARGIN * 3.0 + GRAPH_HEIGHT + LINE_HEIGHT * lines.len() as f32 - FONT_PIXEL * 2.0;
+        self.quad(
+            0.0,
+            0.0,
+            PANEL_WIDTH,
+            panel_height,
+            BACKGROUND,
+            width,
+            height,
+        );
+
+        // Frame time graph, newest frame on the right
+        let graph_width = PANEL_WIDTH - MARGIN * 2.0;
+        let bar_width = graph_width / FRAME_HISTORY as f32;
+        let graph_bottom = MARGIN + GRAPH_HEIGHT;
+        for (i, frame) in history.iter().enumerate() {
+            let x = MARGIN + graph_width - (history.len() - i) as f32 * bar_width;
+            let ms = frame.cpu_time_ms.min(GRAPH_MAX_MS);
+            let bar_height = (ms / GRAPH_MAX_MS) as f32 * GRAPH_HEIGHT;
+            let color = if frame.cpu_time_ms <= 1000.0 / 59.0 {
+                GOOD
+            } else if frame.cpu_time_ms <= 1000.0 / 29.0 {
+                SLOW
+            } else {
+                BAD
+            };
+            self.quad(
+                x,
+                graph_bottom - bar_height,
+                bar_width,
+                bar_height,
+                color,
+                width,
+                height,
+            );
+        }
+        let target_y = graph_bottom - (1000.0 / 60.0 / GRAPH_MAX_MS) as f32 * GRAPH_HEIGHT;
+        self.quad(
+            MARGIN,
+            target_y,
+            graph_width,
+            1.0,
+            TARGET_LINE,
+            width,
+            height,
+        );
+
+        let mut y = graph_bottom + MARGIN;
+        for line in &lines {
+            self.text(line, MARGIN, y, width, height);
+            y += LINE_HEIGHT;
+        }
+    }
+
+    fn text(&mut self, text: &str, x: f32, y: f32, width: f32, height: f32) {
+        let mut x = x;
+        for c in text.chars() {
+            let rows = glyph(c);
+            for (row, bits) in rows.iter().enumerate() {
+                for col in 0..3 {
+                    if bits & (0b100 >> col) != 0 {
+                        self.quad(
+                            x + col as f32 * FONT_PIXEL,
+                            y + row as f32 * FONT_PIXEL,
+                            FONT_PIXEL,
+                            FONT_PIXEL,
+                            TEXT,
+                            width,
+                            height,
+                        );
+                    }
+                }
+            }
+            x += FONT_PIXEL * 4.0;
+        }
+    }
+
+    /// Add a quad in logical pixels, y pointing down
+    #[allow(clippy::too_many_arguments)]
+    fn quad(&mut self, x: f32, y: f32, w: f32, h: f32, color: [f32; 4], width: f32, height: f32) {
+        if self.vertices.len() / 4 >= MAX_QUADS {
+            return;
+        }
+        let x0 = x / width * 2.0 - 1.0;
+        let x1 = (x + w) / width * 2.0 - 1.0;
+        let y0 = 1.0 - y / height * 2.0;
+        let y1 = 1.0 - (y + h) / height * 2.0;
+        for pos in [[x0, y0], [x1, y0], [x1, y1], [x0, y1]] {
+            self.vertices.push(Vertex { pos, color });
+        }
+    }
+}
+
+/// 3x5 bitmap of a character, one row per byte, unknown characters are blank
+fn glyph(c: char) -> [u8; 5] {
+    match c.to_ascii_uppercase() {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        '%' => [0b101, 0b001, 0b010, 0b100, 0b101],
+        'A' => [0b010, 0b101, 0b111, 0b101, 0b101],
+        'B' => [0b110, 0b101, 0b110, 0b101, 0b110],
+        'C' => [0b111, 0b100, 0b100, 0b100, 0b111],
+        'D' => [0b110, 0b101, 0b101, 0b101, 0b110],
+        'E' => [0b111, 0b100, 0b110, 0b100, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'G' => [0b111, 0b100, 0b101, 0b101, 0b111],
+        'H' => [0b101, 0b101, 0b111, 0b101, 0b101],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        'P' => [0b111, 0b101, 0b111, 0b100, 0b100],
+        'R' => [0b110, 0b101, 0b110, 0b101, 0b101],
+        'S' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        'T' => [0b111, 0b010, 0b010, 0b010, 0b010],
+        'U' => [0b101, 0b101, 0b101, 0b101, 0b111],
+        _ => [0; 5],
+    }
+}
+
+mod shader {
+    use crate::graphics::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 in_pos;
+    attribute vec4 in_color;
+
+    varying lowp vec4 color;
+
+    void main() {
+        gl_Position = vec4(in_pos, 0, 1);
+        color = in_color;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 color;
+
+    void main() {
+        gl_FragColor = color;
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+
+    using namespace metal;
+
+    struct Vertex
+    {
+        float2 in_pos   [[attribute(0)]];
+        float4 in_color [[attribute(1)]];
+    };
+
+    struct RasterizerData
+    {
+        float4 position [[position]];
+        float4 color    [[user(locn0)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]])
+    {
+        RasterizerData out;
+
+        out.position = float4(v.in_pos.xy, 0.0, 1.0);
+        out.color = v.in_color;
+
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]])
+    {
+        return in.color;
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec![],
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+        }
+    }
+}
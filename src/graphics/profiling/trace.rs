@@ -0,0 +1,105 @@
+//! Chrome trace export of the frame profiler history
+//!
+//! The output is the JSON trace event format, understood by chrome://tracing
+//! and https://ui.perfetto.dev. Three tracks are written:
+//! - "Frames": one span per frame, from one `commit_frame` to the next
+//! - "CPU": the profiler zones, nested as they were recorded
+//! - "GPU": render pass timings. Timer queries give durations only, so the
+//!   passes are laid out one after another from the start of their frame.
+
+use super::*;
+
+use std::fmt::Write;
+
+const PID: u32 = 1;
+const FRAMES_TID: u32 = 1;
+const CPU_TID: u32 = 2;
+const GPU_TID: u32 = 3;
+
+/// Write the frame history (see `FRAME_HISTORY`) to `path` as a Chrome trace
+pub fn export_trace(path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+    std::fs::write(path, trace_json())
+}
+
+/// The frame history (see `FRAME_HISTORY`) as a Chrome trace JSON string.
+/// Useful on platforms without a filesystem.
+pub fn trace_json() -> String {
+    let frames = frame_history();
+    let mut events = vec![
+        thread_name(FRAMES_TID, "Frames"),
+        thread_name(CPU_TID, "CPU"),
+        thread_name(GPU_TID, "GPU"),
+    ];
+
+    for frame in &frames {
+        let frame_start_us = frame.start_ms * 1000.0;
+        events.push(complete_event(
+            &format!("Frame {}", frame.index),
+            FRAMES_TID,
+            frame_start_us,
+            frame.cpu_time_ms * 1000.0,
+            &format!("\"draw_calls\":{}", frame.draw_calls),
+        ));
+
+        for (i, zone) in frame.zones.iter().enumerate() {
+            events.push(complete_event(
+                zone.name,
+                CPU_TID,
+                frame_start_us + zone.start_ms * 1000.0,
+                zone.duration_ms * 1000.0,
+                &format!("\"path\":\"{}\"", escape(&frame.zone_path(i))),
+            ));
+        }
+
+        let mut gpu_ts = frame_start_us;
+        for pass in &frame.gpu_passes {
+            events.push(complete_event(
+                &pass.label,
+                GPU_TID,
+                gpu_ts,
+                pass.duration_ms * 1000.0,
+                "",
+            ));
+            gpu_ts += pass.duration_ms * 1000.0;
+        }
+    }
+
+    format!(
+        "{{\"displayTimeUnit\":\"ms\",\"traceEvents\":[\n{}\n]}}\n",
+        events.join(",\n")
+    )
+}
+
+fn thread_name(tid: u32, name: &str) -> String {
+    format!(
+        "{{\"name\":\"thread_name\",\"ph\":\"M\",\"pid\":{},\"tid\":{},\"args\":{{\"name\":\"{}\"}}}}",
+        PID, tid, name
+    )
+}
+
+fn complete_event(name: &str, tid: u32, ts_us: f64, dur_us: f64, args: &str) -> String {
+    format!(
+        "{{\"name\":\"{}\",\"ph\":\"X\",\"pid\":{},\"tid\":{},\"ts\":{:.3},\"dur\":{:.3},\"args\":{{{}}}}}",
+        escape(name),
+        PID,
+        tid,
+        ts_us,
+        dur_us,
+        args
+    )
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            c if (c as u32) < 0x20 => {
+                let _ = write!(res, "\\u{:04x}", c as u32);
+            }
+            c => res.push(c),
+        }
+    }
+    res
+}
@@ -0,0 +1,75 @@
+//! Tracy profiler hooks, enabled with the `tracy` feature
+//!
+//! Forwards the existing instrumentation to a Tracy client:
+//! - CPU zones opened with `begin_zone`/`zone`/`profile_zone!` become Tracy zones
+//! - `commit_frame` emits a frame mark
+//! - GL state changes done through `GlCache` and draw calls are plotted per frame
+//! - On GL 3.3+ render passes are reported to a Tracy GPU context,
+//!   measured with GL_TIMESTAMP queries
+//!
+//! The Tracy client is started by the rendering backend on context creation.
+//! Without the feature nothing in here is compiled.
+
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracy_client::{plot_name, Client, Span};
+
+static BUFFER_BINDS: AtomicU64 = AtomicU64::new(0);
+static TEXTURE_BINDS: AtomicU64 = AtomicU64::new(0);
+static PROGRAM_USES: AtomicU64 = AtomicU64::new(0);
+static DRAW_CALLS: AtomicU64 = AtomicU64::new(0);
+
+thread_local! {
+    // Tracy zones have to be closed on the thread they were opened on
+    static OPEN_SPANS: RefCell<Vec<Span>> = const { RefCell::new(Vec::new()) };
+}
+
+pub(crate) fn start() {
+    let _ = Client::start();
+}
+
+pub(crate) fn begin_zone(name: &'static str) {
+    if let Some(client) = Client::running() {
+        let span = client.span_alloc(Some(name), "", "", 0, 0);
+        OPEN_SPANS.with(|spans| spans.borrow_mut().push(span));
+    }
+}
+
+pub(crate) fn end_zone() {
+    OPEN_SPANS.with(|spans| {
+        spans.borrow_mut().pop();
+    });
+}
+
+pub(crate) fn record_buffer_bind() {
+    BUFFER_BINDS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_texture_bind() {
+    TEXTURE_BINDS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_program_use() {
+    PROGRAM_USES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn record_draw_call() {
+    DRAW_CALLS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Plot the per-frame counters and mark the end of the frame
+pub(crate) fn end_frame() {
+    let buffer_binds = BUFFER_BINDS.swap(0, Ordering::Relaxed);
+    let texture_binds = TEXTURE_BINDS.swap(0, Ordering::Relaxed);
+    let program_uses = PROGRAM_USES.swap(0, Ordering::Relaxed);
+    let draw_calls = DRAW_CALLS.swap(0, Ordering::Relaxed);
+
+    if let Some(client) = Client::running() {
+        client.plot(plot_name!("buffer binds"), buffer_binds as f64);
+        client.plot(plot_name!("texture binds"), texture_binds as f64);
+        client.plot(plot_name!("program uses"), program_uses as f64);
+        client.plot(plot_name!("draw calls"), draw_calls as f64);
+        client.frame_mark();
+    }
+}
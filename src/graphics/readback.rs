@@ -0,0 +1,172 @@
+//! CPU side of `RenderingBackend::texture_read_region`
+//!
+//! GLES only guarantees glReadPixels into RGBA unsigned bytes, or RGBA floats
+//! for float color buffers, whatever the texture format. The backends read
+//! what they can and the pixels are converted here into the asked format.
+
+use crate::graphics::*;
+
+/// Formats `texture_read_region` can read from and write to
+fn is_readable(format: TextureFormat) -> bool {
+    matches!(
+        format,
+        TextureFormat::RGB8 | TextureFormat::RGBA8 | TextureFormat::RGBA16F | TextureFormat::Alpha
+    )
+}
+
+/// Checks shared by the backends: formats, `rect` inside the texture and the
+/// size of `bytes`
+pub(crate) fn validate(
+    params: &TextureParams,
+    rect: (i32, i32, i32, i32),
+    format: TextureFormat,
+    bytes: &[u8],
+) -> Result<(), MiniquadError> {
+    for format in [params.format, format] {
+        if !is_readable(format) {
+            return Err(GraphicsError::Unsupported(format!(
+                "{:?} textures can't be read back",
+                format
+            ))
+            .into());
+        }
+    }
+    let (x, y, width, height) = rect;
+    if x < 0
+        || y < 0
+        || width < 0
+        || height < 0
+        || x.checked_add(width)
+            .map_or(true, |right| right > params.width as i32)
+        || y.checked_add(height)
+            .map_or(true, |bottom| bottom > params.height as i32)
+    {
+        return Err(MiniquadError::InvalidParameter(format!(
+            "{:?} outside of the {}x{} texture",
+            rect, params.width, params.height
+        )));
+    }
+    let size = format.size(width as u32, height as u32) as usize;
+    if bytes.len() != size {
+        return Err(MiniquadError::InvalidParameter(format!(
+            "{} bytes for a {}x{} {:?} region of {} bytes",
+            bytes.len(),
+            width,
+            height,
+            format,
+            size
+        )));
+    }
+    Ok(())
+}
+
+/// Pixels of `bytes` in `format`, as normalized RGBA.
+/// Alpha textures are black with their value as alpha, as sampled.
+pub(crate) fn decode(format: TextureFormat, bytes: &[u8]) -> Vec<[f32; 4]> {
+    let unorm = |value: u8| value as f32 / 255.0;
+    match format {
+        TextureFormat::RGBA8 => bytes
+            .chunks_exact(4)
+            .map(|p| [unorm(p[0]), unorm(p[1]), unorm(p[2]), unorm(p[3])])
+            .collect(),
+        TextureFormat::RGB8 => bytes
+            .chunks_exact(3)
+            .map(|p| [unorm(p[0]), unorm(p[1]), unorm(p[2]), 1.0])
+            .collect(),
+        TextureFormat::Alpha => bytes.iter().map(|&a| [0.0, 0.0, 0.0, unorm(a)]).collect(),
+        TextureFormat::RGBA16F => bytes
+            .chunks_exact(8)
+            .map(|p| {
+                let half = |i: usize| f16_to_f32(u16::from_ne_bytes([p[i * 2], p[i * 2 + 1]]));
+                [half(0), half(1), half(2), half(3)]
+            })
+            .collect(),
+        _ => unreachable!("{:?} is not readable", format),
+    }
+}
+
+/// Write `pixels` into `bytes` in `format`, validated before
+pub(crate) fn encode(pixels: &[[f32; 4]], format: TextureFormat, bytes: &mut [u8]) {
+    let unorm = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    match format {
+        TextureFormat::RGBA8 => {
+            for (pixel, dst) in pixels.iter().zip(bytes.chunks_exact_mut(4)) {
+                for channel in 0..4 {
+                    dst[channel] = unorm(pixel[channel]);
+                }
+            }
+        }
+        TextureFormat::RGB8 => {
+            for (pixel, dst) in pixels.iter().zip(bytes.chunks_exact_mut(3)) {
+                for channel in 0..3 {
+                    dst[channel] = unorm(pixel[channel]);
+                }
+            }
+        }
+        TextureFormat::Alpha => {
+            for (pixel, dst) in pixels.iter().zip(bytes.iter_mut()) {
+                *dst = unorm(pixel[3]);
+            }
+        }
+        TextureFormat::RGBA16F => {
+            for (pixel, dst) in pixels.iter().zip(bytes.chunks_exact_mut(8)) {
+                for channel in 0..4 {
+                    let half = f32_to_f16(pixel[channel]).to_ne_bytes();
+                    dst[channel * 2..channel * 2 + 2].copy_from_slice(&half);
+                }
+            }
+        }
+        _ => unreachable!("{:?} is not readable", format),
+    }
+}
+
+fn f16_to_f32(half: u16) -> f32 {
+    let sign = (half as u32 & 0x8000) << 16;
+    let exponent = (half >> 10) & 0x1f;
+    let mantissa = (half & 0x3ff) as u32;
+    let bits = match exponent {
+        // zero or subnormal, a normal f32
+        0 => {
+            let value = mantissa as f32 * 2f32.powi(-24);
+            return if sign == 0 { value } else { -value };
+        }
+        0x1f => sign | 0x7f80_0000 | (mantissa << 13),
+        _ => sign | ((exponent as u32 + 127 - 15) << 23) | (mantissa << 13),
+    };
+    f32::from_bits(bits)
+}
+
+/// Rounded to the nearest even, out of range values become infinities
+fn f32_to_f16(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xff) as i32;
+    let mantissa = bits & 0x7f_ffff;
+
+    if exponent == 0xff {
+        let nan = if mantissa != 0 { 0x200 } else { 0 };
+        return sign | 0x7c00 | nan;
+    }
+    let exponent = exponent - 127 + 15;
+    if exponent >= 0x1f {
+        return sign | 0x7c00;
+    }
+    if exponent <= 0 {
+        if exponent < -10 {
+            return sign;
+        }
+        // subnormal, with the implicit bit made explicit
+        let mantissa = mantissa | 0x80_0000;
+        let shift = (14 - exponent) as u32;
+        let half = mantissa >> shift;
+        let rest = mantissa & ((1 << shift) - 1);
+        let halfway = 1 << (shift - 1);
+        let round = (rest > halfway || (rest == halfway && half & 1 == 1)) as u32;
+        return sign | (half + round) as u16;
+    }
+    let half = ((exponent as u32) << 10) | (mantissa >> 13);
+    let rest = mantissa & 0x1fff;
+    let round = (rest > 0x1000 || (rest == 0x1000 && half & 1 == 1)) as u32;
+    // a carry out of the mantissa correctly bumps the exponent, up to infinity
+    sign | (half + round) as u16
+}
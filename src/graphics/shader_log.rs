@@ -0,0 +1,242 @@
+//! Parsing of the driver shader compilation logs
+//!
+//! Every driver has its own log format, the common ones are:
+//! - `ERROR: 0:12: 'foo' : undeclared identifier` (ANGLE, WebGL, Apple, AMD, most GLES drivers)
+//! - `0:12(5): error: 'foo' undeclared` (Mesa)
+//! - `0(12) : error C1008: undefined variable "foo"` (NVIDIA)
+//!
+//! Lines that don't match any of them are kept as diagnostics without a location.
+
+use super::ShaderType;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShaderDiagnosticSeverity {
+    Error,
+    Warning,
+}
+
+/// One message of the driver compilation log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ShaderDiagnostic {
+    pub stage: ShaderType,
+    pub severity: ShaderDiagnosticSeverity,
+    /// 1-based line in the source given to `new_shader`, `None` when the driver
+    /// did not report one or it points to a line miniquad added itself
+    pub line: Option<u32>,
+    /// 1-based column, only reported by some drivers
+    pub column: Option<u32>,
+    pub message: String,
+}
+
+impl std::fmt::Display for ShaderDiagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            ShaderDiagnosticSeverity::Error => "error",
+            ShaderDiagnosticSeverity::Warning => "warning",
+        };
+        write!(f, "{} shader", self.stage)?;
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => write!(f, " {}:{}", line, column)?,
+            (Some(line), None) => write!(f, " {}", line)?,
+            _ => {}
+        }
+        write!(f, ": {}: {}", severity, self.message)
+    }
+}
+
+fn number(s: &str) -> Option<(u32, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let n = s[..end].parse().ok()?;
+    Some((n, &s[end..]))
+}
+
+/// `0:12(5):`, `0:12:` or `0(12) :`, returns line, column and the rest of the message
+fn location(s: &str) -> Option<(u32, Option<u32>, &str)> {
+    let (_source_string, rest) = number(s)?;
+    if let Some(rest) = rest.strip_prefix(':') {
+        let (line, rest) = number(rest)?;
+        let (column, rest) = match rest.strip_prefix('(') {
+            Some(rest) => {
+                let (column, rest) = number(rest)?;
+                (Some(column), rest.strip_prefix(')')?)
+            }
+            None => (None, rest),
+        };
+        return Some((line, column, rest.trim_start().strip_prefix(':')?));
+    }
+    let (line, rest) = number(rest.strip_prefix('(')?)?;
+    let rest = rest.strip_prefix(')')?.trim_start().strip_prefix(':')?;
+    Some((line, None, rest))
+}
+
+/// `error: ...`, `warning: ...` or `error C1008: ...`
+fn severity_prefix(s: &str) -> Option<(ShaderDiagnosticSeverity, &str)> {
+    let s = s.trim_start();
+    let (severity, rest) = if let Some(rest) = s.strip_prefix("error") {
+        (ShaderDiagnosticSeverity::Error, rest)
+    } else if let Some(rest) = s.strip_prefix("warning") {
+        (ShaderDiagnosticSeverity::Warning, rest)
+    } else {
+        return None;
+    };
+    let colon = rest.find(':')?;
+    // Only an error code is allowed between the severity and the colon
+    if rest[..colon].trim().contains(char::is_whitespace) {
+        return None;
+    }
+    Some((severity, &rest[colon + 1..]))
+}
+
+/// Split a driver log into diagnostics.
+/// `line_offset` is the amount of lines added in front of the user source
+/// before it was handed to the driver.
+pub(crate) fn parse_shader_log(
+    stage: ShaderType,
+    log: &str,
+    line_offset: u32,
+) -> Vec<ShaderDiagnostic> {
+    let mut diagnostics = vec![];
+    for log_line in log.lines() {
+        let log_line = log_line.trim();
+        if log_line.is_empty() {
+            continue;
+        }
+
+        let (mut severity, rest) = if let Some(rest) = log_line.strip_prefix("ERROR:") {
+            (ShaderDiagnosticSeverity::Error, rest.trim_start())
+        } else if let Some(rest) = log_line.strip_prefix("WARNING:") {
+            (ShaderDiagnosticSeverity::Warning, rest.trim_start())
+        } else {
+            (ShaderDiagnosticSeverity::Error, log_line)
+        };
+
+        let (line, column, mut message) = match location(rest) {
+            Some((line, column, message)) => (Some(line), column, message),
+            None => (None, None, rest),
+        };
+        if let Some((prefixed, rest)) = severity_prefix(message) {
+            severity = prefixed;
+            message = rest;
+        }
+
+        let line = line.and_then(|line| line.checked_sub(line_offset).filter(|line| *line > 0));
+        diagnostics.push(ShaderDiagnostic {
+            stage,
+            severity,
+            line,
+            column,
+            message: message.trim().to_string(),
+        });
+    }
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diagnostic(
+        severity: ShaderDiagnosticSeverity,
+        line: Option<u32>,
+        column: Option<u32>,
+        message: &str,
+    ) -> ShaderDiagnostic {
+        ShaderDiagnostic {
+            stage: ShaderType::Fragment,
+            severity,
+            line,
+            column,
+            message: message.to_string(),
+        }
+    }
+
+    #[test]
+    fn angle_log() {
+        let log = "ERROR: 0:12: 'foo' : undeclared identifier\n\
+                   WARNING: 0:3: extension 'GL_OES_standard_derivatives' is not supported\n\
+                   ERROR: 2 compilation errors.  No code generated.\n";
+        assert_eq!(
+            parse_shader_log(ShaderType::Fragment, log, 0),
+            vec![
+                diagnostic(
+                    ShaderDiagnosticSeverity::Error,
+                    Some(12),
+                    None,
+                    "'foo' : undeclared identifier"
+                ),
+                diagnostic(
+                    ShaderDiagnosticSeverity::Warning,
+                    Some(3),
+                    None,
+                    "extension 'GL_OES_standard_derivatives' is not supported"
+                ),
+                diagnostic(
+                    ShaderDiagnosticSeverity::Error,
+                    None,
+                    None,
+                    "2 compilation errors.  No code generated."
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn mesa_log() {
+        let log = "0:12(5): error: `foo' undeclared\n\
+                   0:7(10): warning: `bar' used uninitialized\n";
+        assert_eq!(
+            parse_shader_log(ShaderType::Fragment, log, 0),
+            vec![
+                diagnostic(
+                    ShaderDiagnosticSeverity::Error,
+                    Some(12),
+                    Some(5),
+                    "`foo' undeclared"
+                ),
+                diagnostic(
+                    ShaderDiagnosticSeverity::Warning,
+                    Some(7),
+                    Some(10),
+                    "`bar' used uninitialized"
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn nvidia_log() {
+        let log = "0(12) : error C1008: undefined variable \"foo\"\n\
+                   0(4) : warning C7022: unrecognized profile specifier \"highp\"\n";
+        assert_eq!(
+            parse_shader_log(ShaderType::Fragment, log, 0),
+            vec![
+                diagnostic(
+                    ShaderDiagnosticSeverity::Error,
+                    Some(12),
+                    None,
+                    "undefined variable \"foo\""
+                ),
+                diagnostic(
+                    ShaderDiagnosticSeverity::Warning,
+                    Some(4),
+                    None,
+                    "unrecognized profile specifier \"highp\""
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn line_offset() {
+        // Two lines added in front of the source: line 14 of the log is line 12
+        // of the user source, lines 1 and 2 are miniquad's own
+        let log = "ERROR: 0:14: 'foo' : undeclared identifier\n\
+                   ERROR: 0:2: 'FOO' : macro redefinition\n";
+        let diagnostics = parse_shader_log(ShaderType::Vertex, log, 2);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].stage, ShaderType::Vertex);
+        assert_eq!(diagnostics[0].line, Some(12));
+        assert_eq!(diagnostics[1].line, None);
+        assert_eq!(diagnostics[1].message, "'FOO' : macro redefinition");
+    }
+}
@@ -0,0 +1,240 @@
+//! Cursor image drawn by miniquad instead of the system
+//!
+//! Games with a styled cursor hide the system one and draw a sprite at the
+//! mouse position. `RenderingBackend::set_software_cursor` does it for them:
+//! while `window::show_mouse(false)` is in effect, `commit_frame` draws the
+//! texture on top of the frame, with its hotspot at the last mouse position,
+//! in a single draw call. Nothing is drawn before the first mouse event, nor
+//! while the system cursor is shown.
+//!
+//! The texture is drawn alpha blended, at its size in pixels times `scale`.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::software_cursor::SoftwareCursor;
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! # let pixels = [0u8; 32 * 32 * 4];
+//! let texture = ctx.new_texture_from_rgba8(32, 32, &pixels);
+//! ctx.set_software_cursor(Some(SoftwareCursor {
+//!     hotspot: (4.0, 2.0),
+//!     ..SoftwareCursor::new(texture)
+//! }));
+//! window::show_mouse(false);
+//! ```
+
+use crate::graphics::*;
+
+/// A cursor image for `RenderingBackend::set_software_cursor`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SoftwareCursor {
+    pub texture: TextureId,
+    /// The texture pixel put at the mouse position, from the top left corner
+    pub hotspot: (f32, f32),
+    /// Framebuffer pixels per texture pixel, e.g. `window::dpi_scale()` to keep
+    /// the cursor the same size on high DPI screens
+    pub scale: f32,
+}
+
+impl SoftwareCursor {
+    /// `texture` with its hotspot at the top left corner, unscaled
+    pub fn new(texture: TextureId) -> SoftwareCursor {
+        SoftwareCursor {
+            texture,
+            hotspot: (0.0, 0.0),
+            scale: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Vertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+}
+
+/// The GPU side of the software cursor, owned by the backends
+pub(crate) struct CursorRenderer {
+    cursor: Option<SoftwareCursor>,
+    pipeline: Pipeline,
+    bindings: Bindings,
+}
+
+impl CursorRenderer {
+    pub fn new(ctx: &mut dyn RenderingBackend) -> CursorRenderer {
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<Vertex>(4),
+        );
+        let indices: [u16; 6] = [0, 1, 2, 0, 2, 3];
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&indices),
+        );
+
+        let shader = ctx
+            .new_shader(
+                match ctx.info().backend {
+                    Backend::OpenGl => ShaderSource::Glsl {
+                        vertex: shader::VERTEX,
+                        fragment: shader::FRAGMENT,
+                    },
+                    Backend::Metal => ShaderSource::Msl {
+                        program: shader::METAL,
+                    },
+                },
+                shader::meta(),
+            )
+            .unwrap_or_else(|e| panic!("Failed to load software cursor shader: {}", e));
+        let pipeline = ctx.new_pipeline(
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("in_pos", VertexFormat::Float2),
+                VertexAttribute::new("in_uv", VertexFormat::Float2),
+            ],
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        CursorRenderer {
+            cursor: None,
+            pipeline,
+            bindings: Bindings {
+                vertex_buffers: vec![vertex_buffer],
+                index_buffer,
+                images: vec![],
+            },
+        }
+    }
+
+    pub fn set_cursor(&mut self, cursor: Option<SoftwareCursor>) {
+        self.cursor = cursor;
+    }
+
+    /// Draw the cursor on the default framebuffer, called by `commit_frame`
+    pub fn draw(&mut self, ctx: &mut dyn RenderingBackend) {
+        let Some(cursor) = self.cursor else {
+            return;
+        };
+        if crate::native_display().lock().unwrap().mouse_shown {
+            return;
+        }
+        let Some((mouse_x, mouse_y)) = crate::event::last_mouse_position() else {
+            return;
+        };
+
+        let (screen_width, screen_height) = crate::window::screen_size();
+        let (width, height) = ctx.texture_size(cursor.texture);
+        let x = mouse_x - cursor.hotspot.0 * cursor.scale;
+        let y = mouse_y - cursor.hotspot.1 * cursor.scale;
+        let w = width as f32 * cursor.scale;
+        let h = height as f32 * cursor.scale;
+
+        // Framebuffer pixels with y down to clip space
+        let x0 = x / screen_width * 2.0 - 1.0;
+        let x1 = (x + w) / screen_width * 2.0 - 1.0;
+        let y0 = 1.0 - y / screen_height * 2.0;
+        let y1 = 1.0 - (y + h) / screen_height * 2.0;
+        let vertices = [
+            Vertex {
+                pos: [x0, y0],
+                uv: [0.0, 0.0],
+            },
+            Vertex {
+                pos: [x1, y0],
+                uv: [1.0, 0.0],
+            },
+            Vertex {
+                pos: [x1, y1],
+                uv: [1.0, 1.0],
+            },
+            Vertex {
+                pos: [x0, y1],
+                uv: [0.0, 1.0],
+            },
+        ];
+        ctx.buffer_update(
+            self.bindings.vertex_buffers[0],
+            BufferSource::slice(&vertices),
+        );
+        self.bindings.images = vec![cursor.texture];
+
+        ctx.begin_default_pass(PassAction::Nothing);
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_bindings(&self.bindings);
+        ctx.draw(0, 6, 1);
+        ctx.end_render_pass();
+    }
+}
+
+mod shader {
+    use crate::graphics::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 in_pos;
+    attribute vec2 in_uv;
+
+    varying lowp vec2 texcoord;
+
+    void main() {
+        gl_Position = vec4(in_pos, 0, 1);
+        texcoord = in_uv;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec2 texcoord;
+
+    uniform sampler2D tex;
+
+    void main() {
+        gl_FragColor = texture2D(tex, texcoord);
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+
+    using namespace metal;
+
+    struct Vertex
+    {
+        float2 in_pos   [[attribute(0)]];
+        float2 in_uv    [[attribute(1)]];
+    };
+
+    struct RasterizerData
+    {
+        float4 position [[position]];
+        float2 uv       [[user(locn0)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]])
+    {
+        RasterizerData out;
+
+        out.position = float4(v.in_pos.xy, 0.0, 1.0);
+        out.uv = v.in_uv;
+
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]])
+    {
+        return tex.sample(texSmplr, in.uv);
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["tex".to_string()],
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+        }
+    }
+}
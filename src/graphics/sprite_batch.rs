@@ -0,0 +1,339 @@
+//! Textured quads merged into as few draw calls as their textures allow
+//!
+//! `SpriteBatch::push` only collects quads on the CPU. `SpriteBatch::draw`
+//! writes their vertices into one stream vertex buffer and queues one draw per
+//! run of consecutive quads sharing a texture in a `CommandBuffer`, which is
+//! then executed on the backend. Quads keep their submission order, so alpha
+//! blended sprites overlap as pushed: interleaving textures costs draw calls,
+//! not correctness.
+//!
+//! Positions are in pixels of the current pass, from its top left corner.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::sprite_batch::{SpriteBatch, Quad};
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! # let pixels = [0u8; 16 * 16 * 4];
+//! let texture = ctx.new_texture_from_rgba8(16, 16, &pixels);
+//! let mut batch = SpriteBatch::new(&mut *ctx);
+//!
+//! // each frame
+//! ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+//! for i in 0..100 {
+//!     batch.push(Quad::new(texture, i as f32 * 16.0, 0.0, 16.0, 16.0));
+//! }
+//! let (width, height) = window::screen_size();
+//! batch.draw(&mut *ctx, width, height);
+//! ctx.end_render_pass();
+//! ctx.commit_frame();
+//! ```
+
+use crate::graphics::command_buffer::{CommandBuffer, DrawElementsParams};
+use crate::graphics::*;
+
+/// Quads per vertex buffer upload, the most 16 bit indices can address
+const MAX_QUADS: usize = 65536 / 4;
+
+/// Quads the vertex buffer is first created for
+const INITIAL_QUADS: usize = 1024;
+
+/// One textured quad
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quad {
+    pub texture: TextureId,
+    /// Affine transform `[a, b, c, d, x, y]` of the unit square: its corner
+    /// `(u, v)` goes to `(a * u + c * v + x, b * u + d * v + y)`, in pixels
+    pub transform: [f32; 6],
+    /// Texture coordinates of the `(0, 0)` and `(1, 1)` corners: `[u0, v0, u1, v1]`
+    pub uv: [f32; 4],
+    /// Multiplied with the texture color
+    pub color: [f32; 4],
+}
+
+impl Quad {
+    /// The whole texture, untinted, on the `w` x `h` rectangle at `x`, `y`
+    pub fn new(texture: TextureId, x: f32, y: f32, w: f32, h: f32) -> Quad {
+        Quad {
+            texture,
+            transform: [w, 0.0, 0.0, h, x, y],
+            uv: [0.0, 0.0, 1.0, 1.0],
+            color: [1.0, 1.0, 1.0, 1.0],
+        }
+    }
+
+    /// `w` x `h` rectangle centered at `x`, `y` and rotated by `angle` radians
+    pub fn rotated(texture: TextureId, x: f32, y: f32, w: f32, h: f32, angle: f32) -> Quad {
+        let (sin, cos) = angle.sin_cos();
+        let (a, b, c, d) = (cos * w, sin * w, -sin * h, cos * h);
+        Quad {
+            transform: [a, b, c, d, x - (a + c) / 2.0, y - (b + d) / 2.0],
+            ..Quad::new(texture, 0.0, 0.0, 0.0, 0.0)
+        }
+    }
+
+    /// Part of the texture, `[u0, v0, u1, v1]`, e.g. a frame of a sprite sheet
+    pub fn with_uv(self, uv: [f32; 4]) -> Quad {
+        Quad { uv, ..self }
+    }
+
+    pub fn with_color(self, color: [f32; 4]) -> Quad {
+        Quad { color, ..self }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Vertex {
+    pos: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+pub struct SpriteBatch {
+    quads: Vec<Quad>,
+    vertices: Vec<Vertex>,
+    commands: CommandBuffer,
+    pipeline: Pipeline,
+    vertex_buffer: BufferId,
+    index_buffer: BufferId,
+    /// Quads `vertex_buffer` holds
+    capacity: usize,
+}
+
+impl SpriteBatch {
+    /// The pipeline, alpha blended, and the buffers of the batch
+    pub fn new(ctx: &mut dyn RenderingBackend) -> SpriteBatch {
+        let vertex_buffer = ctx.new_buffer(
+            BufferType::VertexBuffer,
+            BufferUsage::Stream,
+            BufferSource::empty::<Vertex>(INITIAL_QUADS * 4),
+        );
+        let indices: Vec<u16> = (0..MAX_QUADS as u16)
+            .flat_map(|quad| {
+                let first = quad * 4;
+                [first, first + 1, first + 2, first, first + 2, first + 3]
+            })
+            .collect();
+        let index_buffer = ctx.new_buffer(
+            BufferType::IndexBuffer,
+            BufferUsage::Immutable,
+            BufferSource::slice(&indices),
+        );
+
+        let shader = ctx
+            .new_shader(
+                match ctx.info().backend {
+                    Backend::OpenGl => ShaderSource::Glsl {
+                        vertex: shader::VERTEX,
+                        fragment: shader::FRAGMENT,
+                    },
+                    Backend::Metal => ShaderSource::Msl {
+                        program: shader::METAL,
+                    },
+                },
+                shader::meta(),
+            )
+            .unwrap_or_else(|e| panic!("Failed to load sprite batch shader: {}", e));
+        let pipeline = ctx.new_pipeline(
+            &[BufferLayout::default()],
+            &[
+                VertexAttribute::new("in_pos", VertexFormat::Float2),
+                VertexAttribute::new("in_uv", VertexFormat::Float2),
+                VertexAttribute::new("in_color", VertexFormat::Float4),
+            ],
+            shader,
+            PipelineParams {
+                color_blend: Some(BlendState::new(
+                    Equation::Add,
+                    BlendFactor::Value(BlendValue::SourceAlpha),
+                    BlendFactor::OneMinusValue(BlendValue::SourceAlpha),
+                )),
+                ..Default::default()
+            },
+        );
+
+        SpriteBatch {
+            quads: vec![],
+            vertices: vec![],
+            commands: CommandBuffer::new(),
+            pipeline,
+            vertex_buffer,
+            index_buffer,
+            capacity: INITIAL_QUADS,
+        }
+    }
+
+    /// Queue a quad for the next `draw`
+    pub fn push(&mut self, quad: Quad) {
+        self.quads.push(quad);
+    }
+
+    /// Quads queued since the last `draw`
+    pub fn len(&self) -> usize {
+        self.quads.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.quads.is_empty()
+    }
+
+    /// Drop the queued quads without drawing them
+    pub fn clear(&mut self) {
+        self.quads.clear();
+    }
+
+    /// Draw and clear the queued quads in the current pass, `width` x `height`
+    /// pixels large. Needs a pass begun, the applied pipeline and bindings are
+    /// replaced.
+    pub fn draw(&mut self, ctx: &mut dyn RenderingBackend, width: f32, height: f32) {
+        let quads = std::mem::take(&mut self.quads);
+        for chunk in quads.chunks(MAX_QUADS) {
+            self.draw_chunk(ctx, chunk, width, height);
+        }
+        self.quads = quads;
+        self.quads.clear();
+    }
+
+    fn draw_chunk(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        quads: &[Quad],
+        width: f32,
+        height: f32,
+    ) {
+        if quads.len() > self.capacity {
+            self.capacity = quads.len().next_power_of_two().min(MAX_QUADS);
+            ctx.delete_buffer(self.vertex_buffer);
+            self.vertex_buffer = ctx.new_buffer(
+                BufferType::VertexBuffer,
+                BufferUsage::Stream,
+                BufferSource::empty::<Vertex>(self.capacity * 4),
+            );
+        }
+
+        // Pixels with y down to clip space
+        let to_clip = |x: f32, y: f32| [x / width * 2.0 - 1.0, 1.0 - y / height * 2.0];
+        self.vertices.clear();
+        for quad in quads {
+            let [a, b, c, d, x, y] = quad.transform;
+            let [u0, v0, u1, v1] = quad.uv;
+            for (u, v) in [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)] {
+                self.vertices.push(Vertex {
+                    pos: to_clip(a * u + c * v + x, b * u + d * v + y),
+                    uv: [u0 + (u1 - u0) * u, v0 + (v1 - v0) * v],
+                    color: quad.color,
+                });
+            }
+        }
+        ctx.buffer_update(self.vertex_buffer, BufferSource::slice(&self.vertices));
+
+        // One draw per run of quads with the same texture
+        let mut start = 0;
+        while start < quads.len() {
+            let texture = quads[start].texture;
+            let count = quads[start..]
+                .iter()
+                .take_while(|quad| quad.texture == texture)
+                .count();
+            self.commands.draw_elements(
+                self.pipeline,
+                &Bindings {
+                    vertex_buffers: vec![self.vertex_buffer],
+                    index_buffer: self.index_buffer,
+                    images: vec![texture],
+                },
+                DrawElementsParams {
+                    base_element: start as i32 * 6,
+                    num_elements: count as i32 * 6,
+                    num_instances: 1,
+                    primitive_type: PrimitiveType::Triangles,
+                    index_type: 2,
+                },
+            );
+            start += count;
+        }
+        // Before the vertex buffer is written again
+        let _ = self.commands.flush(ctx);
+    }
+
+    /// Statistics of the command buffer the draws go through
+    pub fn stats(&self) -> super::command_buffer::BatchStats {
+        self.commands.get_stats()
+    }
+
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        ctx.delete_pipeline(self.pipeline);
+        ctx.delete_buffer(self.vertex_buffer);
+        ctx.delete_buffer(self.index_buffer);
+    }
+}
+
+mod shader {
+    use crate::graphics::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 in_pos;
+    attribute vec2 in_uv;
+    attribute vec4 in_color;
+
+    varying lowp vec2 texcoord;
+    varying lowp vec4 color;
+
+    void main() {
+        gl_Position = vec4(in_pos, 0, 1);
+        texcoord = in_uv;
+        color = in_color;
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec2 texcoord;
+    varying lowp vec4 color;
+
+    uniform sampler2D tex;
+
+    void main() {
+        gl_FragColor = texture2D(tex, texcoord) * color;
+    }"#;
+
+    pub const METAL: &str = r#"
+    #include <metal_stdlib>
+
+    using namespace metal;
+
+    struct Vertex
+    {
+        float2 in_pos   [[attribute(0)]];
+        float2 in_uv    [[attribute(1)]];
+        float4 in_color [[attribute(2)]];
+    };
+
+    struct RasterizerData
+    {
+        float4 position [[position]];
+        float2 uv       [[user(locn0)]];
+        float4 color    [[user(locn1)]];
+    };
+
+    vertex RasterizerData vertexShader(Vertex v [[stage_in]])
+    {
+        RasterizerData out;
+
+        out.position = float4(v.in_pos.xy, 0.0, 1.0);
+        out.uv = v.in_uv;
+        out.color = v.in_color;
+
+        return out;
+    }
+
+    fragment float4 fragmentShader(RasterizerData in [[stage_in]], texture2d<float> tex [[texture(0)]], sampler texSmplr [[sampler(0)]])
+    {
+        return tex.sample(texSmplr, in.uv) * in.color;
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec!["tex".to_string()],
+            uniforms: UniformBlockLayout { uniforms: vec![] },
+        }
+    }
+}
@@ -0,0 +1,247 @@
+//! Persistently mapped streaming buffers for per-frame data
+//!
+//! On GL 4.4+ or with ARB/EXT_buffer_storage the driver can give us
+//! a pointer into buffer storage that stays valid for the whole lifetime
+//! of the buffer. Writing per-frame data through that pointer avoids both
+//! the driver-side copy of `glBufferSubData` and the implicit synchronization
+//! it might need.
+//!
+//! Key features:
+//! - Persistently and coherently mapped buffers, no map/unmap per update
+//! - Fence per frame (and on demand), so a region is never overwritten while
+//!   the GPU may still read from it
+//! - Released buffers are kept around and reused by size
+//! - When buffer storage is not available, `is_supported` is false and callers
+//!   fall back to orphaning
+
+use crate::graphics::*;
+use std::collections::{HashMap, VecDeque};
+
+/// Number of copies in rotation for each persistently mapped buffer
+pub const FRAMES_IN_FLIGHT: usize = 3;
+
+/// Maximum amount of released buffers kept for reuse, per size
+const MAX_FREE_BUFFERS: usize = 16;
+
+/// Time to wait for a fence in a single glClientWaitSync call, in nanoseconds
+const FENCE_WAIT_TIMEOUT: GLuint64 = 1_000_000;
+
+const STORAGE_FLAGS: GLbitfield = GL_MAP_WRITE_BIT | GL_MAP_PERSISTENT_BIT | GL_MAP_COHERENT_BIT;
+
+/// A persistently mapped GL buffer
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct StreamingBuffer {
+    pub gl_buf: GLuint,
+    pub ptr: *mut u8,
+    pub capacity: usize,
+    /// Sync point of the last write, see `StreamingPool::sync_point`.
+    /// None until the buffer is written, nothing to wait for then.
+    pub last_use: Option<u64>,
+}
+
+#[derive(Debug)]
+pub struct StreamingPool {
+    supported: bool,
+    // glBufferStorage comes from GL_EXT_buffer_storage on GLES
+    use_ext: bool,
+    // Commands submitted since the last fence belong to this sync point
+    sync_point: u64,
+    fences: VecDeque<(u64, GLsync)>,
+    free: HashMap<(GLenum, usize), Vec<StreamingBuffer>>,
+}
+
+impl StreamingPool {
+    pub fn new(info: &ContextInfo) -> StreamingPool {
+        let (supported, use_ext) = detect_buffer_storage(info);
+        StreamingPool {
+            supported,
+            use_ext,
+            sync_point: 0,
+            fences: VecDeque::new(),
+            free: HashMap::new(),
+        }
+    }
+
+    /// Persistent mapping is available on the current context
+    pub fn is_supported(&self) -> bool {
+        self.supported
+    }
+
+    /// Current sync point. Everything submitted from now on will be
+    /// guarded by the next inserted fence.
+    pub(crate) fn sync_point(&self) -> u64 {
+        self.sync_point
+    }
+
+    /// Get a mapped buffer of exactly `capacity` bytes.
+    /// `bind` is called with a freshly generated buffer and should bind it to `gl_target`.
+    pub(crate) fn acquire(
+        &mut self,
+        gl_target: GLenum,
+        capacity: usize,
+        bind: impl FnOnce(GLuint),
+    ) -> Option<StreamingBuffer> {
+        if !self.supported {
+            return None;
+        }
+
+        if let Some(buffer) = self
+            .free
+            .get_mut(&(gl_target, capacity))
+            .and_then(|free| free.pop())
+        {
+            if let Some(last_use) = buffer.last_use {
+                self.wait(last_use);
+            }
+            return Some(buffer);
+        }
+
+        let mut gl_buf: GLuint = 0;
+        unsafe {
+            glGenBuffers(1, &mut gl_buf as *mut _);
+            if gl_buf == 0 {
+                return None;
+            }
+            bind(gl_buf);
+            if self.use_ext {
+                glBufferStorageEXT(gl_target, capacity as _, std::ptr::null(), STORAGE_FLAGS);
+            } else {
+                glBufferStorage(gl_target, capacity as _, std::ptr::null(), STORAGE_FLAGS);
+            }
+            let ptr = glMapBufferRange(gl_target, 0, capacity as _, STORAGE_FLAGS);
+            if ptr.is_null() {
+                glDeleteBuffers(1, &gl_buf as *const _);
+                return None;
+            }
+            Some(StreamingBuffer {
+                gl_buf,
+                ptr: ptr as *mut u8,
+                capacity,
+                last_use: None,
+            })
+        }
+    }
+
+    /// Give the buffer back to the pool. It will be reused only after
+    /// the GPU is done with its last write.
+    pub(crate) fn release(&mut self, gl_target: GLenum, buffer: StreamingBuffer) {
+        let free = self.free.entry((gl_target, buffer.capacity)).or_default();
+        if free.len() < MAX_FREE_BUFFERS {
+            free.push(buffer);
+        } else {
+            // Deleting a mapped buffer implicitly unmaps it
+            unsafe { glDeleteBuffers(1, &buffer.gl_buf as *const _) };
+        }
+    }
+
+    /// Block until the GPU is done with everything submitted up to `sync_point`.
+    pub(crate) fn wait(&mut self, sync_point: u64) {
+        if !self.supported {
+            return;
+        }
+        // The data was written during the current sync point and is possibly
+        // still referenced by draw calls in flight, fence it right away.
+        if sync_point >= self.sync_point {
+            self.insert_fence();
+        }
+
+        while let Some(&(fence_point, fence)) = self.fences.front() {
+            if fence_point > sync_point {
+                break;
+            }
+            if fence_point == sync_point {
+                client_wait(fence);
+            }
+            unsafe { glDeleteSync(fence) };
+            self.fences.pop_front();
+        }
+    }
+
+    /// Fence everything submitted this frame and forget about the fences
+    /// the GPU already passed.
+    pub(crate) fn end_frame(&mut self) {
+        if !self.supported {
+            return;
+        }
+        self.insert_fence();
+
+        while let Some(&(_, fence)) = self.fences.front() {
+            let res = unsafe { glClientWaitSync(fence, 0, 0) };
+            if res != GL_ALREADY_SIGNALED && res != GL_CONDITION_SATISFIED {
+                break;
+            }
+            unsafe { glDeleteSync(fence) };
+            self.fences.pop_front();
+        }
+    }
+
+    fn insert_fence(&mut self) {
+        let fence = unsafe { glFenceSync(GL_SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        self.fences.push_back((self.sync_point, fence));
+        self.sync_point += 1;
+    }
+}
+
+fn client_wait(fence: GLsync) {
+    loop {
+        let res =
+            unsafe { glClientWaitSync(fence, GL_SYNC_FLUSH_COMMANDS_BIT, FENCE_WAIT_TIMEOUT) };
+        if res != GL_TIMEOUT_EXPIRED {
+            // GL_ALREADY_SIGNALED, GL_CONDITION_SATISFIED or GL_WAIT_FAILED,
+            // in any case waiting longer is not going to help
+            break;
+        }
+    }
+}
+
+/// Returns (supported, use the EXT entry point)
+#[cfg(not(target_arch = "wasm32"))]
+fn detect_buffer_storage(info: &ContextInfo) -> (bool, bool) {
+    let sync_funcs = [
+        "glMapBufferRange",
+        "glFenceSync",
+        "glDeleteSync",
+        "glClientWaitSync",
+    ];
+    if !sync_funcs.iter().all(|f| is_gl_func_loaded(f)) {
+        return (false, false);
+    }
+
+    let version = &info.gl_version_string;
+    let gles = version.contains("OpenGL ES");
+    if !gles
+        && (version.starts_with("4.4") || version.starts_with("4.5") || version.starts_with("4.6"))
+        && is_gl_func_loaded("glBufferStorage")
+    {
+        return (true, false);
+    }
+
+    if !is_gl_func_loaded("glGetStringi") {
+        return (false, false);
+    }
+    let mut num_extensions: GLint = 0;
+    unsafe { glGetIntegerv(GL_NUM_EXTENSIONS, &mut num_extensions as *mut _) };
+    for i in 0..num_extensions.max(0) as GLuint {
+        let name = unsafe { glGetStringi(GL_EXTENSIONS, i) };
+        if name.is_null() {
+            continue;
+        }
+        let name = unsafe { std::ffi::CStr::from_ptr(name as _) };
+        match name.to_bytes() {
+            b"GL_ARB_buffer_storage" if is_gl_func_loaded("glBufferStorage") => {
+                return (true, false)
+            }
+            b"GL_EXT_buffer_storage" if is_gl_func_loaded("glBufferStorageEXT") => {
+                return (true, true)
+            }
+            _ => {}
+        }
+    }
+    (false, false)
+}
+
+/// WebGL has no persistent mapping
+#[cfg(target_arch = "wasm32")]
+fn detect_buffer_storage(_info: &ContextInfo) -> (bool, bool) {
+    (false, false)
+}
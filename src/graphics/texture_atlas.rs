@@ -0,0 +1,214 @@
+//! Pack many small RGBA8 images into one texture
+//!
+//! Drawing from a single texture keeps the bindings the same between draws,
+//! so the command buffer can batch them. Images are placed with a skyline
+//! packer: the atlas remembers the height of the used area along its width
+//! and puts each image at the lowest spot it fits, leftmost on ties.
+//!
+//! Images can be inserted at any time, each insertion uploads only the
+//! image's own rect with `texture_update_part`.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::texture_atlas::TextureAtlas;
+//! # fn glyphs(ctx: &mut dyn RenderingBackend, glyph: &[u8]) {
+//! let mut atlas = TextureAtlas::new(ctx, 1024, 1024, 1);
+//! let entry = atlas.insert(ctx, 16, 16, glyph).expect("atlas is full");
+//! let (u, v, w, h) = entry.uv;
+//! # }
+//! ```
+
+use crate::graphics::*;
+
+/// Where an image ended up in the atlas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AtlasEntry {
+    /// x, y, width, height in pixels
+    pub rect: (u32, u32, u32, u32),
+    /// x, y, width, height in texture coordinates, 0..1
+    pub uv: (f32, f32, f32, f32),
+}
+
+/// Horizontal span of the skyline, everything below `y` is taken
+#[derive(Debug, Clone, Copy)]
+struct Segment {
+    x: u32,
+    y: u32,
+    width: u32,
+}
+
+#[derive(Debug)]
+pub struct TextureAtlas {
+    texture: TextureId,
+    width: u32,
+    height: u32,
+    padding: u32,
+    skyline: Vec<Segment>,
+}
+
+impl TextureAtlas {
+    /// Transparent `width` x `height` RGBA8 atlas. `padding` empty pixels are
+    /// kept between images so linear filtering does not bleed neighbours in.
+    pub fn new(
+        ctx: &mut dyn RenderingBackend,
+        width: u32,
+        height: u32,
+        padding: u32,
+    ) -> TextureAtlas {
+        let zeros = vec![0; TextureFormat::RGBA8.size(width, height) as usize];
+        let texture = ctx.new_texture(
+            TextureAccess::Static,
+            TextureSource::Bytes(&zeros),
+            TextureParams {
+                format: TextureFormat::RGBA8,
+                width,
+                height,
+                ..Default::default()
+            },
+        );
+        TextureAtlas {
+            texture,
+            width,
+            height,
+            padding,
+            skyline: vec![Segment { x: 0, y: 0, width }],
+        }
+    }
+
+    pub fn texture(&self) -> TextureId {
+        self.texture
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// Place a `width` x `height` RGBA8 image and upload it.
+    /// None when there is no room left for it.
+    pub fn insert(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Option<AtlasEntry> {
+        assert_eq!(
+            rgba.len(),
+            TextureFormat::RGBA8.size(width, height) as usize,
+            "Image data does not match its size"
+        );
+        let (x, y) = self.allocate(width, height)?;
+        if width > 0 && height > 0 {
+            ctx.texture_update_part(self.texture, x as _, y as _, width as _, height as _, rgba);
+        }
+        Some(AtlasEntry {
+            rect: (x, y, width, height),
+            uv: (
+                x as f32 / self.width as f32,
+                y as f32 / self.height as f32,
+                width as f32 / self.width as f32,
+                height as f32 / self.height as f32,
+            ),
+        })
+    }
+
+    /// Insert all the `(width, height, rgba)` images, tallest first as that packs
+    /// tighter. The entries are in the order of `images`, None for the ones that
+    /// did not fit.
+    pub fn pack(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        images: &[(u32, u32, &[u8])],
+    ) -> Vec<Option<AtlasEntry>> {
+        let mut order: Vec<usize> = (0..images.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse((images[i].1, images[i].0)));
+
+        let mut entries = vec![None; images.len()];
+        for i in order {
+            let (width, height, rgba) = images[i];
+            entries[i] = self.insert(ctx, width, height, rgba);
+        }
+        entries
+    }
+
+    /// Forget all the images, the texture keeps its pixels until they are
+    /// overwritten by new insertions.
+    pub fn clear(&mut self) {
+        self.skyline = vec![Segment {
+            x: 0,
+            y: 0,
+            width: self.width,
+        }];
+    }
+
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        ctx.delete_texture(self.texture);
+    }
+
+    /// Top left corner for a padded `width` x `height` rect, updating the skyline
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        let mut best: Option<(usize, u32, u32)> = None;
+        for (index, segment) in self.skyline.iter().enumerate() {
+            // Images touching the right or bottom edge need no padding there
+            let reserved = (width + self.padding).min(self.width.saturating_sub(segment.x));
+            let Some(y) = self.fit(index, width.max(reserved)) else {
+                continue;
+            };
+            if y + height <= self.height && best.map_or(true, |(_, _, best_y)| y < best_y) {
+                best = Some((index, segment.x, y));
+            }
+        }
+        let (index, x, y) = best?;
+
+        let width = (width + self.padding).min(self.width - x);
+        let top = (y + height + self.padding).min(self.height);
+        self.skyline.insert(index, Segment { x, y: top, width });
+
+        // Cut the segments the new one now covers
+        let end = x + width;
+        let next = index + 1;
+        while next < self.skyline.len() && self.skyline[next].x < end {
+            let segment = &mut self.skyline[next];
+            let segment_end = segment.x + segment.width;
+            if segment_end <= end {
+                self.skyline.remove(next);
+            } else {
+                segment.width = segment_end - end;
+                segment.x = end;
+                break;
+            }
+        }
+
+        // Merge neighbours at the same height
+        let mut i = 0;
+        while i + 1 < self.skyline.len() {
+            if self.skyline[i].y == self.skyline[i + 1].y {
+                self.skyline[i].width += self.skyline[i + 1].width;
+                self.skyline.remove(i + 1);
+            } else {
+                i += 1;
+            }
+        }
+
+        Some((x, y))
+    }
+
+    /// Lowest y a `width` wide rect starting at the segment `index` can sit at,
+    /// None if it would stick out on the right.
+    fn fit(&self, index: usize, width: u32) -> Option<u32> {
+        let x = self.skyline[index].x;
+        if x + width > self.width {
+            return None;
+        }
+        let mut y = 0;
+        let mut covered = 0;
+        for segment in &self.skyline[index..] {
+            if covered >= width {
+                break;
+            }
+            y = y.max(segment.y);
+            covered = segment.x + segment.width - x;
+        }
+        Some(y)
+    }
+}
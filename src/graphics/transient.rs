@@ -0,0 +1,224 @@
+//! Vertices and indices streamed every frame, without orphaning
+//!
+//! Immediate-mode UIs make new draw lists every frame. Instead of a buffer per
+//! draw list, or a buffer orphaned for each of them, `TransientVertices` and
+//! `TransientIndices` append them into stream buffers with
+//! `buffer_update_range`. There is one buffer per frame in flight: the frame
+//! being recorded writes its own while the GPU still reads the ones of the
+//! previous frames, so nothing waits. The allocations start over when
+//! `RenderingBackend::frame_index` changes, and a buffer too small for a frame
+//! is replaced by a larger one.
+//!
+//! `draw` has no base vertex, so the indices are rebased on the vertices they
+//! go with: `TransientIndices::push` adds the first vertex returned by
+//! `TransientVertices::push` to every index.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::graphics::transient::{TransientIndices, TransientVertices};
+//! # let mut ctx: Box<dyn RenderingBackend> = window::new_rendering_backend();
+//! # let pipeline: Pipeline = unimplemented!();
+//! # let draw_lists: Vec<(Vec<[f32; 4]>, Vec<u16>)> = vec![];
+//! let mut vertices = TransientVertices::<[f32; 4]>::new(&mut *ctx, 4096);
+//! let mut indices = TransientIndices::<u16>::new(&mut *ctx, 8192);
+//!
+//! // each frame
+//! ctx.begin_default_pass(PassAction::clear_color(0.0, 0.0, 0.0, 1.0));
+//! ctx.apply_pipeline(&pipeline);
+//! for (list_vertices, list_indices) in &draw_lists {
+//!     let (vertex_buffer, first_vertex) = vertices.push(&mut *ctx, list_vertices);
+//!     let (index_buffer, elements) = indices.push(&mut *ctx, list_indices, first_vertex);
+//!     ctx.apply_bindings(&Bindings {
+//!         vertex_buffers: vec![vertex_buffer],
+//!         index_buffer,
+//!         images: vec![],
+//!     });
+//!     ctx.draw(elements.start, elements.end - elements.start, 1);
+//! }
+//! ctx.end_render_pass();
+//! ctx.commit_frame();
+//! ```
+
+use crate::graphics::mesh::MeshIndex;
+use crate::graphics::streaming_pool::FRAMES_IN_FLIGHT;
+use crate::graphics::*;
+
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::ops::Range;
+
+/// One stream buffer per frame in flight, appended to during a frame
+struct FrameBuffers<T> {
+    buffer_type: BufferType,
+    /// With the elements each holds
+    buffers: Vec<(BufferId, usize)>,
+    /// Frame of the allocations in `len`
+    frame: Option<u64>,
+    /// Elements written this frame in the buffer of the frame
+    len: usize,
+    /// Allocations start at multiples of it, in elements
+    align: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> FrameBuffers<T> {
+    fn new(
+        ctx: &mut dyn RenderingBackend,
+        buffer_type: BufferType,
+        capacity: usize,
+        align: usize,
+    ) -> FrameBuffers<T> {
+        let capacity = capacity.max(1);
+        let buffers = (0..FRAMES_IN_FLIGHT)
+            .map(|_| (Self::new_buffer(ctx, buffer_type, capacity), capacity))
+            .collect();
+        FrameBuffers {
+            buffer_type,
+            buffers,
+            frame: None,
+            len: 0,
+            align,
+            _marker: PhantomData,
+        }
+    }
+
+    fn new_buffer(
+        ctx: &mut dyn RenderingBackend,
+        buffer_type: BufferType,
+        capacity: usize,
+    ) -> BufferId {
+        ctx.new_buffer(
+            buffer_type,
+            BufferUsage::Stream,
+            BufferSource::empty::<T>(capacity),
+        )
+    }
+
+    /// Write `data` after the previous allocations of the frame, returns the
+    /// buffer and the index of the first element in it
+    fn push(&mut self, ctx: &mut dyn RenderingBackend, data: &[T]) -> (BufferId, usize) {
+        let frame = ctx.frame_index();
+        if self.frame != Some(frame) {
+            self.frame = Some(frame);
+            self.len = 0;
+        }
+        let slot = (frame % FRAMES_IN_FLIGHT as u64) as usize;
+        let (mut buffer, capacity) = self.buffers[slot];
+        let mut start = (self.len + self.align - 1) / self.align * self.align;
+        if start + data.len() > capacity {
+            // The draws of the frame so far keep reading the old one
+            ctx.delete_buffer_deferred(buffer, FRAMES_IN_FLIGHT as u32);
+            let capacity = (capacity * 2).max(data.len()).next_power_of_two();
+            buffer = Self::new_buffer(ctx, self.buffer_type, capacity);
+            self.buffers[slot] = (buffer, capacity);
+            start = 0;
+        }
+        if !data.is_empty() {
+            let offset = start * std::mem::size_of::<T>();
+            ctx.buffer_update_range(buffer, offset, BufferSource::slice(data));
+        }
+        self.len = start + data.len();
+        (buffer, start)
+    }
+
+    fn delete(self, ctx: &mut dyn RenderingBackend) {
+        for (buffer, _) in self.buffers {
+            ctx.delete_buffer(buffer);
+        }
+    }
+}
+
+/// Vertex buffers for the vertices of the current frame
+pub struct TransientVertices<V> {
+    buffers: FrameBuffers<V>,
+}
+
+impl<V> TransientVertices<V> {
+    /// Room for `capacity` vertices per frame, grown as needed
+    pub fn new(ctx: &mut dyn RenderingBackend, capacity: usize) -> TransientVertices<V> {
+        TransientVertices {
+            buffers: FrameBuffers::new(ctx, BufferType::VertexBuffer, capacity, 1),
+        }
+    }
+
+    /// Stream `vertices` for draws of the current frame: the buffer they are in
+    /// and the index of the first one, for `TransientIndices::push`
+    pub fn push(&mut self, ctx: &mut dyn RenderingBackend, vertices: &[V]) -> (BufferId, u32) {
+        let (buffer, first) = self.buffers.push(ctx, vertices);
+        (buffer, first as u32)
+    }
+
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        self.buffers.delete(ctx);
+    }
+}
+
+/// Index buffers for the indices of the current frame. Allocations start at
+/// multiples of 4 bytes, as Metal needs for `draw`'s `base_element`.
+pub struct TransientIndices<I> {
+    buffers: FrameBuffers<I>,
+    /// Indices being rebased
+    scratch: Vec<I>,
+}
+
+impl<I: MeshIndex + Into<u32> + TryFrom<u32>> TransientIndices<I> {
+    /// Room for `capacity` indices per frame, grown as needed
+    pub fn new(ctx: &mut dyn RenderingBackend, capacity: usize) -> TransientIndices<I> {
+        let align = 4 / I::TYPE.size().min(4);
+        TransientIndices {
+            buffers: FrameBuffers::new(ctx, BufferType::IndexBuffer, capacity, align),
+            scratch: vec![],
+        }
+    }
+
+    /// Stream `indices` plus `first_vertex` for a draw of the current frame:
+    /// the buffer they are in and the elements to `draw`.
+    /// Panics when a rebased index does not fit in `I`, see `try_push`.
+    pub fn push(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        indices: &[I],
+        first_vertex: u32,
+    ) -> (BufferId, Range<i32>) {
+        self.try_push(ctx, indices, first_vertex)
+            .unwrap_or_else(|err| panic!("Failed to stream indices: {}", err))
+    }
+
+    /// Same as `push`, but returns an error when a rebased index does not fit
+    /// in `I`, e.g. u16 indices after 65536 vertices in a frame
+    pub fn try_push(
+        &mut self,
+        ctx: &mut dyn RenderingBackend,
+        indices: &[I],
+        first_vertex: u32,
+    ) -> Result<(BufferId, Range<i32>), MiniquadError> {
+        let indices = if first_vertex == 0 {
+            indices
+        } else {
+            self.scratch.clear();
+            for &index in indices {
+                let index: u32 = index.into();
+                let rebased = index
+                    .checked_add(first_vertex)
+                    .and_then(|index| I::try_from(index).ok())
+                    .ok_or_else(|| {
+                        MiniquadError::InvalidParameter(format!(
+                            "index {} after {} vertices does not fit in {:?} indices",
+                            index,
+                            first_vertex,
+                            I::TYPE
+                        ))
+                    })?;
+                self.scratch.push(rebased);
+            }
+            &self.scratch
+        };
+        let (buffer, start) = self.buffers.push(ctx, indices);
+        let start = start as i32;
+        Ok((buffer, start..start + indices.len() as i32))
+    }
+
+    pub fn delete(self, ctx: &mut dyn RenderingBackend) {
+        self.buffers.delete(ctx);
+    }
+}
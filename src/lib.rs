@@ -7,21 +7,36 @@
     clippy::missing_safety_doc
 )]
 
+pub mod accessibility;
+pub mod colors;
 pub mod conf;
+pub mod crash;
 pub mod error;
 mod event;
+mod frame_pacing;
+mod frame_stats;
 pub mod fs;
 pub mod graphics;
+mod message_box;
 pub mod native;
+pub mod recording;
+pub mod tray;
 use crate::error::{ResourceError, ResourceResult};
 use std::collections::HashMap;
 use std::ops::{Index, IndexMut};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 #[cfg(feature = "log-impl")]
 pub mod log;
 
-pub use error::{MiniquadError, Result};
+#[cfg(feature = "webxr")]
+pub mod xr;
+
+pub use error::{clear_error_handler, set_error_handler, MiniquadError, Result};
 pub use event::*;
+pub use frame_pacing::FramePhase;
+pub use frame_stats::{FrameStats, PresentTiming};
+pub use message_box::{MessageBoxButton, MessageBoxButtons};
 
 pub use graphics::*;
 
@@ -29,38 +44,108 @@ mod default_icon;
 
 pub use native::gl;
 
+/// Bits of a resource ID numbering the resource, the higher ones are its
+/// `Namespace`: 24 on 32 bit targets, where namespaces wrap around after 256
+/// contexts, 48 on 64 bit ones.
+const NAMESPACE_SHIFT: u32 = usize::BITS / 4 * 3;
+
+/// The resource IDs of one rendering context. The IDs of different contexts
+/// differ in their high bits, so an ID used with a context that did not make
+/// it, e.g. one from a destroyed context, is reported instead of silently
+/// naming another resource. The first context keeps the plain IDs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Namespace(usize);
+
+impl Namespace {
+    /// A namespace no other context uses
+    pub fn next() -> Namespace {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        Namespace(NEXT.fetch_add(1, Ordering::Relaxed) & (usize::MAX >> NAMESPACE_SHIFT))
+    }
+
+    /// ID of the resource `index` of this namespace
+    pub fn id(self, index: usize) -> usize {
+        assert!(
+            index >> NAMESPACE_SHIFT == 0,
+            "Too many resources in one context"
+        );
+        (self.0 << NAMESPACE_SHIFT) | index
+    }
+
+    /// Index of the resource `id`, an error for IDs of other namespaces
+    pub fn try_index(self, id: usize) -> ResourceResult<usize> {
+        if id >> NAMESPACE_SHIFT == self.0 {
+            Ok(id & ((1 << NAMESPACE_SHIFT) - 1))
+        } else {
+            Err(ResourceError::ForeignContext(id))
+        }
+    }
+
+    /// Same as `try_index`, panics for IDs of other namespaces
+    pub fn index(self, id: usize) -> usize {
+        self.try_index(id).unwrap_or_else(|e| panic!("{}", e))
+    }
+}
+
 #[derive(Clone)]
 pub(crate) struct ResourceManager<T> {
+    namespace: Namespace,
     id: usize,
     resources: HashMap<usize, T>,
 }
 
-impl<T> Default for ResourceManager<T> {
-    fn default() -> Self {
+impl<T> ResourceManager<T> {
+    /// Resources with IDs of `namespace`, shared by the managers of a context
+    pub fn new(namespace: Namespace) -> Self {
         Self {
+            namespace,
             id: 0,
             resources: HashMap::new(),
         }
     }
-}
 
-impl<T> ResourceManager<T> {
     pub fn add(&mut self, resource: T) -> usize {
-        self.resources.insert(self.id, resource);
+        let id = self.namespace.id(self.id);
+        self.resources.insert(id, resource);
         self.id += 1;
-        self.id - 1
+        id
+    }
+
+    /// NotFound, or ForeignContext for IDs of another context
+    fn missing(&self, id: usize) -> ResourceError {
+        match self.namespace.try_index(id) {
+            Ok(_) => ResourceError::NotFound(id),
+            Err(e) => e,
+        }
     }
 
     /// Remove a resource by ID, returning an error if not found
     pub fn remove(&mut self, id: usize) -> ResourceResult<T> {
-        self.resources
-            .remove(&id)
-            .ok_or(ResourceError::NotFound(id))
+        match self.resources.remove(&id) {
+            Some(resource) => Ok(resource),
+            None => Err(self.missing(id)),
+        }
     }
 
     /// Get a reference to a resource by ID
     pub fn get(&self, id: usize) -> ResourceResult<&T> {
-        self.resources.get(&id).ok_or(ResourceError::NotFound(id))
+        self.resources.get(&id).ok_or_else(|| self.missing(id))
+    }
+
+    /// Get a mutable reference to a resource by ID
+    pub fn get_mut(&mut self, id: usize) -> ResourceResult<&mut T> {
+        let missing = self.missing(id);
+        self.resources.get_mut(&id).ok_or(missing)
+    }
+
+    /// Amount of live resources
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    /// Live resources with their IDs, in no particular order
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        self.resources.iter().map(|(id, resource)| (*id, resource))
     }
 }
 
@@ -71,8 +156,8 @@ impl<T> Index<usize> for ResourceManager<T> {
     fn index(&self, index: usize) -> &Self::Output {
         self.resources.get(&index).unwrap_or_else(|| {
             panic!(
-                "Resource with ID {} not found. Consider using get() for safe access.",
-                index
+                "{}. Consider using get() for safe access.",
+                self.missing(index)
             )
         })
     }
@@ -80,12 +165,10 @@ impl<T> Index<usize> for ResourceManager<T> {
 
 impl<T> IndexMut<usize> for ResourceManager<T> {
     fn index_mut(&mut self, index: usize) -> &mut Self::Output {
-        self.resources.get_mut(&index).unwrap_or_else(|| {
-            panic!(
-                "Resource with ID {} not found. Consider using get_mut() for safe access.",
-                index
-            )
-        })
+        let missing = self.missing(index);
+        self.resources
+            .get_mut(&index)
+            .unwrap_or_else(|| panic!("{}. Consider using get_mut() for safe access.", missing))
     }
 }
 
@@ -164,6 +247,34 @@ pub mod window {
         Box::new(GlContext::new())
     }
 
+    /// Monotonic time, in seconds, at which miniquad received the event being
+    /// handled, or the last one outside of the event callbacks.
+    /// Only the difference between two timestamps is meaningful.
+    pub fn event_timestamp() -> f64 {
+        crate::event::event_timestamp()
+    }
+
+    /// The user's double-click time and distance, used for `EventHandler::double_click_event`.
+    /// Read from the system on Windows and macOS, elsewhere it is 0.5s and 4 pixels.
+    pub fn double_click_settings() -> DoubleClickSettings {
+        crate::event::double_click_settings()
+    }
+
+    /// The user's key repeat delay and rate.
+    /// Read from the system on Windows, macOS, X11 and Wayland, elsewhere it is
+    /// 0.5s and 25 repeats per second.
+    pub fn key_repeat_settings() -> KeyRepeatSettings {
+        crate::event::key_repeat_settings()
+    }
+
+    /// Deliver the repeats of held keys, on by default. Text fields want them,
+    /// a game reading `key_down_event` usually does not: when disabled the
+    /// `key_down_event`, `char_event` and `text_input_event` of the repeats are
+    /// dropped, only the first press comes through.
+    pub fn set_key_repeat_enabled(enabled: bool) {
+        crate::event::set_key_repeat_enabled(enabled)
+    }
+
     /// The current framebuffer size in pixels
     /// NOTE: [High DPI Rendering](../conf/index.html#high-dpi-rendering)
     pub fn screen_size() -> (f32, f32) {
@@ -171,7 +282,27 @@ pub mod window {
         (d.screen_width as f32, d.screen_height as f32)
     }
 
-    /// The dpi scaling factor (window pixels to framebuffer pixels)
+    /// The current framebuffer size in pixels, same as `screen_size`, for
+    /// render targets and viewports
+    /// NOTE: [High DPI Rendering](../conf/index.html#high-dpi-rendering)
+    pub fn screen_size_physical() -> (f32, f32) {
+        screen_size()
+    }
+
+    /// The current framebuffer size in logical units, the ones of the window
+    /// size: `screen_size_physical() / dpi_scale()`. UI laid out in them keeps
+    /// its size on HighDPI displays.
+    /// NOTE: [High DPI Rendering](../conf/index.html#high-dpi-rendering)
+    pub fn screen_size_logical() -> (f32, f32) {
+        let d = native_display().lock().unwrap();
+        (
+            d.screen_width as f32 / d.dpi_scale,
+            d.screen_height as f32 / d.dpi_scale,
+        )
+    }
+
+    /// The dpi scaling factor: framebuffer pixels per logical unit, 1 unless
+    /// high dpi was requested and the display is a high-dpi one
     /// NOTE: [High DPI Rendering](../conf/index.html#high-dpi-rendering)
     pub fn dpi_scale() -> f32 {
         let d = native_display().lock().unwrap();
@@ -212,6 +343,29 @@ pub mod window {
         native::current_monitor_impl()
     }
 
+    /// Show a native message box and wait until it is closed. Needs no window
+    /// and no rendering context: it can report a failed startup, before `start`.
+    /// Escape and the close button answer `Cancel`, or `No` without a cancel
+    /// button. On macOS it has to be called from the main thread before `start`.
+    /// Browsers only have `alert` and `confirm`: `YesNoCancel` can't answer
+    /// `Cancel`, and the title is shown above the text. Not implemented on
+    /// Android and iOS.
+    pub fn message_box(
+        title: &str,
+        text: &str,
+        buttons: crate::MessageBoxButtons,
+    ) -> crate::MessageBoxButton {
+        crate::message_box::show(title, text, buttons)
+    }
+
+    /// `message_box` without waiting: the answer arrives as
+    /// `EventHandler::message_box_event` with the returned id. Frames keep
+    /// coming while the box is open, except on macOS and in browsers where the
+    /// box is modal to the event loop.
+    pub fn message_box_async(title: &str, text: &str, buttons: crate::MessageBoxButtons) -> u32 {
+        crate::message_box::show_async(title, text, buttons)
+    }
+
     pub fn blocking_event_loop() -> bool {
         let d = native_display().lock().unwrap();
         d.blocking_event_loop
@@ -252,6 +406,7 @@ pub mod window {
     pub fn cancel_quit() {
         let mut d = native_display().lock().unwrap();
         d.quit_requested = false;
+        d.session_ending = false;
     }
     /// Capture mouse cursor to the current window
     /// On WASM this will automatically hide cursor
@@ -286,14 +441,47 @@ pub mod window {
         }
     }
 
-    /// Show or hide the mouse cursor
+    /// Show or hide the mouse cursor.
+    /// A hidden cursor may be replaced with an image drawn by miniquad,
+    /// see `RenderingBackend::set_software_cursor`.
     pub fn show_mouse(shown: bool) {
-        let d = native_display().lock().unwrap();
+        let mut d = native_display().lock().unwrap();
+        d.mouse_shown = shown;
         d.native_requests
             .send(native::Request::ShowMouse(shown))
             .unwrap();
     }
 
+    /// Move the mouse cursor, in the same coordinates as `mouse_motion_event`.
+    /// A `mouse_motion_event` to the new position may follow.
+    ///
+    /// Implemented on X11, Windows and macOS. Wayland and browsers don't let
+    /// applications move the pointer, use `set_cursor_grab` and `raw_mouse_motion` there.
+    pub fn set_mouse_position(x: f32, y: f32) {
+        let d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetMousePosition { x, y })
+            .unwrap();
+    }
+
+    /// Keep delivering mouse motion and button events while the cursor is
+    /// outside the window, until `capture_mouse(false)`.
+    /// Coordinates are still window-relative and may be negative.
+    ///
+    /// Drags are captured anyway: once a button is pressed inside the window, its
+    /// motion and button up events arrive wherever the cursor goes. This is for
+    /// keeping the capture past that, e.g. a drag-and-drop that continues after
+    /// the button is released.
+    /// X11 and Windows implement both; on Windows the capture only covers events
+    /// outside the window while some button is held. Browsers use pointer capture.
+    /// macOS and Wayland only capture drags.
+    pub fn capture_mouse(capture: bool) {
+        let d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::CaptureMouse(capture))
+            .unwrap();
+    }
+
     /// Set the mouse cursor icon.
     pub fn set_mouse_cursor(cursor_icon: CursorIcon) {
         let d = native_display().lock().unwrap();
@@ -313,6 +501,17 @@ pub mod window {
             .unwrap();
     }
 
+    /// Hide the window, or show it again.
+    /// A hidden window gets no input and may not be drawn; with a tray icon
+    /// (`tray::show`) this is "minimize to tray".
+    /// Not implemented on Wayland, Android, iOS and wasm.
+    pub fn set_window_visible(visible: bool) {
+        let d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetWindowVisible(visible))
+            .unwrap();
+    }
+
     pub fn set_window_position(new_x: u32, new_y: u32) {
         let d = native_display().lock().unwrap();
         d.native_requests
@@ -328,6 +527,62 @@ pub mod window {
         d.screen_position
     }
 
+    /// Switch vsync, applied before the next frame. Platforms honor what they can:
+    /// WGL, GLX, EGL and macOS switch between `Off` and `On`, `Adaptive` needs the
+    /// `*_EXT_swap_control_tear` extension (WGL and GLX only). iOS and browsers
+    /// always wait for the vertical blank. `swap_interval` tells what was applied.
+    pub fn set_swap_interval(interval: crate::SwapInterval) {
+        let d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::SetSwapInterval(interval))
+            .unwrap();
+    }
+
+    /// Only these rectangles of the frame being drawn changed since the last one:
+    /// `(x, y, width, height)` in framebuffer pixels, from the top left corner.
+    /// A hint for the next present only, letting the compositor skip the rest
+    /// of the window, which saves power for mostly static tool UIs.
+    ///
+    /// Used with `EGL_KHR_swap_buffers_with_damage` or its EXT variant, on X11
+    /// with EGL, Wayland and Android. Ignored elsewhere.
+    /// To redraw only the dirty region as well, see `buffer_age`.
+    pub fn set_dirty_region(rects: &[(i32, i32, i32, i32)]) {
+        let mut d = native_display().lock().unwrap();
+        d.dirty_region = Some(rects.to_vec());
+    }
+
+    /// How many frames ago the content of the framebuffer being drawn was
+    /// presented: with 1 it holds the last frame and only the dirty region needs
+    /// redrawing, with 2 the dirty regions of the last two frames, and so on.
+    /// 0 when the content is undefined.
+    ///
+    /// None when unknown, as without `EGL_EXT_buffer_age` and on every platform
+    /// other than X11 with EGL, Wayland and Android: the whole frame has to be drawn.
+    pub fn buffer_age() -> Option<u32> {
+        let d = native_display().lock().unwrap();
+        d.buffer_age
+    }
+
+    /// Is the user dragging a border of the window, see `EventHandler::live_resize_event`
+    pub fn is_live_resizing() -> bool {
+        let d = native_display().lock().unwrap();
+        d.live_resize
+    }
+
+    /// The side or corner dragged in the live resize going on, when the
+    /// platform knows it
+    pub fn live_resize_edge() -> Option<crate::ResizeEdge> {
+        let d = native_display().lock().unwrap();
+        d.live_resize_edge
+    }
+
+    /// The swap interval in effect, which may differ from what was asked for
+    /// with `conf.platform.swap_interval` or `set_swap_interval`
+    pub fn swap_interval() -> crate::SwapInterval {
+        let d = native_display().lock().unwrap();
+        d.swap_interval
+    }
+
     pub fn set_fullscreen(fullscreen: bool) {
         let d = native_display().lock().unwrap();
         d.native_requests
@@ -376,6 +631,81 @@ pub mod window {
             .unwrap();
     }
 
+    /// Timings of the last frame: CPU, GPU, time waiting in the swap and the
+    /// detected vsync interval
+    pub fn frame_stats() -> crate::FrameStats {
+        crate::frame_stats::stats()
+    }
+
+    /// When the frame being drawn is expected on screen and the display refresh
+    /// interval, updated before each `update`. Animating to `present_time` instead
+    /// of the time `draw` is called keeps motion smooth when frames are late.
+    pub fn present_timing() -> crate::PresentTiming {
+        crate::frame_stats::present_timing()
+    }
+
+    /// Cap the frame rate, `None` to run as fast as vsync (or the
+    /// `blocking_event_loop`) lets it. Frames are delayed after the buffer swap with
+    /// a sleep followed by a short spin, so they are evenly spaced. On the web the
+    /// animation frames that come too early are skipped instead.
+    pub fn set_target_frame_rate(frame_rate: Option<u32>) {
+        crate::frame_pacing::set_target_frame_rate(frame_rate);
+    }
+
+    /// The frame rate cap, see `set_target_frame_rate`
+    pub fn target_frame_rate() -> Option<u32> {
+        crate::frame_pacing::target_frame_rate()
+    }
+
+    /// How the browser schedules the frames, e.g. a 30 FPS timer to save battery
+    /// without waking up on every animation frame. Ignored outside browsers.
+    pub fn set_web_frame_strategy(strategy: crate::conf::WebFrameStrategy) {
+        crate::frame_pacing::set_web_frame_strategy(strategy);
+    }
+
+    /// Wait for the GPU to finish each frame right after the buffer swap, so the
+    /// driver never queues frames ahead: less input latency for less throughput.
+    /// With OpenGL this is a fence (or `glFinish`). Metal already waits for every
+    /// command buffer. iOS and browsers present on their own and are not affected.
+    pub fn set_low_latency(low_latency: bool) {
+        crate::frame_pacing::set_low_latency(low_latency);
+    }
+
+    /// Whether the low latency mode is on, see `set_low_latency`
+    pub fn low_latency() -> bool {
+        crate::frame_pacing::low_latency()
+    }
+
+    /// The native window, for libraries that talk to the platform directly,
+    /// like an AccessKit adapter. `None` in browsers and on Android, where the
+    /// surface comes and goes.
+    pub fn window_handle() -> Option<crate::WindowHandle> {
+        let d = native_display().lock().unwrap();
+        d.window_handle
+    }
+
+    /// The native rendering context, e.g. to create an OpenXR session rendering
+    /// with the same context, while the window shows a mirror view. See
+    /// `set_frame_hook` to drive the OpenXR frame loop.
+    ///
+    /// `None` in browsers and with OpenGL on macOS and iOS. With
+    /// `start_with_render_thread` the GL context is current on the render thread.
+    pub fn context_handle() -> Option<crate::ContextHandle> {
+        let d = native_display().lock().unwrap();
+        d.context_handle
+    }
+
+    /// Call `hook` at the start of every frame, before the events and `update`,
+    /// and once the window's buffers are swapped, e.g. to call `xrWaitFrame`
+    /// and `xrBeginFrame`, then `xrEndFrame`. The hook runs where `update` runs,
+    /// with the context current. `None` removes it.
+    ///
+    /// To let the headset pace the frames, turn the window's vsync off with
+    /// `set_swap_interval(SwapInterval::Off)`.
+    pub fn set_frame_hook(hook: Option<Box<dyn FnMut(crate::FramePhase) + Send>>) {
+        crate::frame_pacing::set_frame_hook(hook);
+    }
+
     #[cfg(target_vendor = "apple")]
     pub fn apple_gfx_api() -> crate::conf::AppleGfxApi {
         let d = native_display().lock().unwrap();
@@ -393,6 +723,8 @@ pub mod window {
     }
 }
 
+/// Mouse cursor shapes, named after the CSS cursors.
+/// Platforms without a matching native cursor show the closest one they have.
 #[derive(Debug, Copy, Clone, PartialEq, Hash, Eq)]
 pub enum CursorIcon {
     Default,
@@ -407,6 +739,30 @@ pub enum CursorIcon {
     NSResize,
     NESWResize,
     NWSEResize,
+    ContextMenu,
+    /// Busy, but still accepting input
+    Progress,
+    Cell,
+    VerticalText,
+    Alias,
+    Copy,
+    NoDrop,
+    Grab,
+    Grabbing,
+    AllScroll,
+    ZoomIn,
+    ZoomOut,
+    /// Resizing a single edge or corner
+    NResize,
+    EResize,
+    SResize,
+    WResize,
+    NEResize,
+    NWResize,
+    SEResize,
+    SWResize,
+    ColResize,
+    RowResize,
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -418,6 +774,100 @@ pub struct ScreenMetrics {
     pub high_dpi: bool,
 }
 
+/// Whether the buffer swap waits for the vertical blank, see `window::set_swap_interval`
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SwapInterval {
+    /// Present as soon as the frame is done, tearing is possible
+    Off,
+    /// Wait for the vertical blank
+    On,
+    /// Wait for the vertical blank, unless the frame already missed it: then
+    /// present right away, tearing rather than stuttering. Needs
+    /// `EXT_swap_control_tear`, `On` is used without it.
+    Adaptive,
+}
+
+impl SwapInterval {
+    /// From the `conf.platform.swap_interval` and swap extension convention
+    pub(crate) fn from_gl(interval: i32) -> SwapInterval {
+        match interval {
+            0 => SwapInterval::Off,
+            i if i < 0 => SwapInterval::Adaptive,
+            _ => SwapInterval::On,
+        }
+    }
+
+    pub(crate) fn to_gl(self) -> i32 {
+        match self {
+            SwapInterval::Off => 0,
+            SwapInterval::On => 1,
+            SwapInterval::Adaptive => -1,
+        }
+    }
+}
+
+/// Native handles of the window, see `window::window_handle`
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WindowHandle {
+    Xlib {
+        display: *mut std::ffi::c_void,
+        window: u64,
+    },
+    Wayland {
+        display: *mut std::ffi::c_void,
+        surface: *mut std::ffi::c_void,
+    },
+    Win32 {
+        hwnd: *mut std::ffi::c_void,
+        hinstance: *mut std::ffi::c_void,
+    },
+    AppKit {
+        ns_window: *mut std::ffi::c_void,
+        ns_view: *mut std::ffi::c_void,
+    },
+    UiKit {
+        ui_view: *mut std::ffi::c_void,
+        ui_view_controller: *mut std::ffi::c_void,
+    },
+}
+
+// Only handed around, every use of the pointers is up to the platform's own rules
+unsafe impl Send for WindowHandle {}
+unsafe impl Sync for WindowHandle {}
+
+/// Native handles of the rendering context, see `window::context_handle`.
+/// What the OpenXR graphics bindings take.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ContextHandle {
+    Wgl {
+        hdc: *mut std::ffi::c_void,
+        hglrc: *mut std::ffi::c_void,
+    },
+    Glx {
+        display: *mut std::ffi::c_void,
+        fb_config: *mut std::ffi::c_void,
+        visual_id: u64,
+        drawable: u64,
+        context: *mut std::ffi::c_void,
+    },
+    /// On X11, Wayland and Android. The surface changes when Android recreates
+    /// the window.
+    Egl {
+        display: *mut std::ffi::c_void,
+        config: *mut std::ffi::c_void,
+        context: *mut std::ffi::c_void,
+        surface: *mut std::ffi::c_void,
+    },
+    /// The MTLDevice and the MTLCommandQueue the frames are committed to
+    Metal {
+        device: *mut std::ffi::c_void,
+        command_queue: *mut std::ffi::c_void,
+    },
+}
+
+unsafe impl Send for ContextHandle {}
+unsafe impl Sync for ContextHandle {}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct MonitorMetrics {
     pub width: f32,
@@ -429,12 +879,38 @@ pub struct MonitorMetrics {
 }
 
 /// Start miniquad.
-pub fn start<F>(conf: conf::Conf, f: F)
+pub fn start<F>(mut conf: conf::Conf, f: F)
 where
     F: 'static + FnOnce() -> Box<dyn EventHandler>,
 {
+    let kiosk = conf.kiosk;
+    if kiosk.is_some() {
+        conf.fullscreen = true;
+    }
+    // The platforms only look at high_dpi
+    conf.high_dpi = conf.high_dpi_policy.is_high_dpi(conf.high_dpi);
+    let f = move || -> Box<dyn EventHandler> {
+        if let Some(kiosk) = kiosk {
+            // Queued, applied by the platform once the window is up
+            if kiosk.hide_cursor {
+                window::show_mouse(false);
+            }
+            if kiosk.confine_cursor {
+                window::set_cursor_grab(true);
+            }
+        }
+        Box::new(event::TimedEventHandler::new(f()))
+    };
+    frame_pacing::set_target_frame_rate(conf.platform.target_frame_rate);
+    frame_pacing::set_low_latency(conf.platform.low_latency);
+
     #[cfg(target_os = "linux")]
     {
+        let _screensaver = kiosk
+            .filter(|kiosk| kiosk.inhibit_screensaver)
+            .and_then(|_| native::linux_screensaver::ScreenSaverInhibitor::new(&conf.window_title));
+        native::linux_session::install_sigterm_handler();
+        native::linux_gpu::apply_gpu_preference(conf.platform.gpu_preference);
         let mut f = Some(f);
         let f = &mut f;
         match conf.platform.linux_backend {
@@ -446,14 +922,17 @@ where
             }
             conf::LinuxBackend::X11WithWaylandFallback => {
                 if let Err(err) = native::linux_x11::run(&conf, f) {
-                    eprintln!("{err:?}");
-                    eprintln!("Failed to initialize through X11! Trying wayland instead");
+                    error::report_error(error::PlatformError::DisplayInitFailed(format!(
+                        "{err:?}. Failed to initialize through X11! Trying wayland instead"
+                    )));
                     native::linux_wayland::run(&conf, f);
                 }
             }
             conf::LinuxBackend::WaylandWithX11Fallback => {
                 if native::linux_wayland::run(&conf, f).is_none() {
-                    eprintln!("Failed to initialize through wayland! Trying X11 instead");
+                    error::report_error(error::PlatformError::DisplayInitFailed(
+                        "Failed to initialize through wayland! Trying X11 instead".to_string(),
+                    ));
                     native::linux_x11::run(&conf, f).unwrap()
                 }
             }
@@ -485,3 +964,32 @@ where
         native::ios::run(conf, f);
     }
 }
+
+/// Start miniquad with `EventHandler::update` and `draw` on a dedicated render
+/// thread, with the GL context current there, while the OS events are pumped on
+/// the main thread. Window drags and modal resize loops then no longer stall
+/// the frames.
+///
+/// `f` is called on the render thread, so the handler and its rendering backend
+/// live there. Events are queued by the main thread and delivered on the render
+/// thread before the next `update`, `resize_event` included.
+///
+/// Supported on Windows and on macOS with `AppleGfxApi::OpenGl`,
+/// elsewhere this is the same as `start`.
+pub fn start_with_render_thread<F>(conf: conf::Conf, f: F)
+where
+    F: 'static + Send + FnOnce() -> Box<dyn EventHandler>,
+{
+    let supported = cfg!(target_os = "windows")
+        || (cfg!(target_os = "macos") && conf.platform.apple_gfx_api == conf::AppleGfxApi::OpenGl);
+    if !supported {
+        start(conf, f);
+        return;
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let forwarder = native::render_thread::prepare(f);
+        start(conf, move || Box::new(forwarder));
+    }
+}
@@ -0,0 +1,148 @@
+//! Native message boxes, see `window::message_box`
+//!
+//! `MessageBoxW` on Windows, `NSAlert` on macOS, zenity or kdialog on Linux and
+//! `alert`/`confirm` in browsers. None of them need the rendering context, so
+//! they can report a failed context creation.
+
+use crate::{event::EventHandler, native, native_display};
+
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{mpsc, Mutex};
+
+/// The buttons a message box offers
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MessageBoxButtons {
+    Ok,
+    OkCancel,
+    YesNo,
+    YesNoCancel,
+}
+
+/// The button a message box was closed with
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MessageBoxButton {
+    Ok,
+    Cancel,
+    Yes,
+    No,
+}
+
+impl MessageBoxButtons {
+    /// What closing the box with Escape or its close button means
+    pub(crate) fn dismissed(self) -> MessageBoxButton {
+        match self {
+            MessageBoxButtons::Ok => MessageBoxButton::Ok,
+            MessageBoxButtons::YesNo => MessageBoxButton::No,
+            MessageBoxButtons::OkCancel | MessageBoxButtons::YesNoCancel => {
+                MessageBoxButton::Cancel
+            }
+        }
+    }
+}
+
+/// Where the answer of a message box shown by the platform thread goes
+#[derive(Debug)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+pub(crate) enum Reply {
+    /// `EventHandler::message_box_event` with this id
+    Event(u32),
+    /// A blocking `message_box` called from another thread
+    Channel(mpsc::Sender<MessageBoxButton>),
+}
+
+/// A message box for the platform thread, for the platforms where only the
+/// main thread may show one
+#[derive(Debug)]
+pub(crate) struct MessageBoxRequest {
+    pub title: String,
+    pub text: String,
+    pub buttons: MessageBoxButtons,
+    pub reply: Reply,
+}
+
+impl MessageBoxRequest {
+    /// Show the box and send the answer, on the platform thread
+    pub fn run(self) {
+        let button = show(&self.title, &self.text, self.buttons);
+        match self.reply {
+            Reply::Event(id) => report(id, button),
+            Reply::Channel(tx) => {
+                let _ = tx.send(button);
+            }
+        }
+    }
+}
+
+static NEXT_ID: AtomicU32 = AtomicU32::new(1);
+static ANSWERED: Mutex<Vec<(u32, MessageBoxButton)>> = Mutex::new(Vec::new());
+
+/// Blocking, on the calling thread or the main one where the platform requires it
+pub(crate) fn show(title: &str, text: &str, buttons: MessageBoxButtons) -> MessageBoxButton {
+    #[cfg(target_os = "windows")]
+    return native::windows::message_box(title, text, buttons, false);
+    #[cfg(target_os = "macos")]
+    return native::macos::message_box(title, text, buttons, false);
+    #[cfg(target_os = "linux")]
+    return native::linux_message_box::message_box(title, text, buttons, false);
+    #[cfg(target_arch = "wasm32")]
+    return native::wasm::message_box(title, text, buttons);
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    {
+        let _ = (title, text);
+        crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+            "Message boxes are not implemented on mobile".to_string(),
+        ));
+        buttons.dismissed()
+    }
+}
+
+/// Returns the id `message_box_event` will come with
+pub(crate) fn show_async(title: &str, text: &str, buttons: MessageBoxButtons) -> u32 {
+    let id = NEXT_ID.fetch_add(1, Ordering::Relaxed);
+    #[cfg(target_os = "macos")]
+    {
+        let d = native_display().lock().unwrap();
+        d.native_requests
+            .send(native::Request::MessageBox(Box::new(MessageBoxRequest {
+                title: title.to_string(),
+                text: text.to_string(),
+                buttons,
+                reply: Reply::Event(id),
+            })))
+            .unwrap();
+    }
+    // Browser dialogs block the page whatever we do
+    #[cfg(target_arch = "wasm32")]
+    report(id, show(title, text, buttons));
+    #[cfg(not(any(target_os = "macos", target_arch = "wasm32")))]
+    {
+        let (title, text) = (title.to_string(), text.to_string());
+        std::thread::Builder::new()
+            .name("miniquad message box".to_string())
+            .spawn(move || report(id, show(&title, &text, buttons)))
+            .expect("Failed to spawn the message box thread");
+    }
+    id
+}
+
+/// May be called from any thread, schedules the frame delivering the answer
+/// when `blocking_event_loop` is on.
+fn report(id: u32, button: MessageBoxButton) {
+    if let Ok(mut answered) = ANSWERED.lock() {
+        answered.push((id, button));
+    }
+    let d = native_display().lock().unwrap();
+    let _ = d.native_requests.send(native::Request::ScheduleUpdate);
+}
+
+/// Deliver the answers since the last frame, called by the platform event loops
+/// before `update`
+pub(crate) fn dispatch_answers(event_handler: &mut dyn EventHandler) {
+    let answered = ANSWERED
+        .lock()
+        .map(|mut answered| std::mem::take(&mut *answered))
+        .unwrap_or_default();
+    for (id, button) in answered {
+        event_handler.message_box_event(id, button);
+    }
+}
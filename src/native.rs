@@ -15,10 +15,25 @@ pub(crate) struct NativeDisplayData {
     pub high_dpi: bool,
     pub quit_requested: bool,
     pub quit_ordered: bool,
+    /// The quit request comes from the OS ending the session,
+    /// see `event::dispatch_quit_requested`
+    pub session_ending: bool,
     pub native_requests: mpsc::Sender<Request>,
     pub clipboard: Box<dyn Clipboard>,
     pub dropped_files: DroppedFiles,
     pub blocking_event_loop: bool,
+    pub window_handle: Option<crate::WindowHandle>,
+    pub context_handle: Option<crate::ContextHandle>,
+    pub swap_interval: crate::SwapInterval,
+    /// See `event::dispatch_live_resize`
+    pub live_resize: bool,
+    pub live_resize_edge: Option<crate::ResizeEdge>,
+    /// Last `window::show_mouse`, for the software cursor
+    pub mouse_shown: bool,
+    /// See `window::set_dirty_region`, taken by the next present
+    pub dirty_region: Option<Vec<(i32, i32, i32, i32)>>,
+    /// See `window::buffer_age`, set by the platform before `draw`
+    pub buffer_age: Option<u32>,
 
     #[cfg(target_vendor = "apple")]
     pub view: crate::native::apple::frameworks::ObjcId,
@@ -47,10 +62,19 @@ impl NativeDisplayData {
             high_dpi: false,
             quit_requested: false,
             quit_ordered: false,
+            session_ending: false,
             native_requests,
             clipboard,
             dropped_files: Default::default(),
             blocking_event_loop: false,
+            window_handle: None,
+            context_handle: None,
+            swap_interval: crate::SwapInterval::On,
+            live_resize: false,
+            live_resize_edge: None,
+            mouse_shown: true,
+            dirty_region: None,
+            buffer_age: None,
             #[cfg(target_vendor = "apple")]
             gfx_api: crate::conf::AppleGfxApi::OpenGl,
             #[cfg(target_vendor = "apple")]
@@ -66,11 +90,18 @@ pub(crate) enum Request {
     ScheduleUpdate,
     SetCursorGrab(bool),
     ShowMouse(bool),
+    SetMousePosition { x: f32, y: f32 },
+    CaptureMouse(bool),
+    SetWindowVisible(bool),
+    SetTray(Option<Box<crate::tray::Tray>>),
     SetMouseCursor(crate::CursorIcon),
     SetWindowSize { new_width: u32, new_height: u32 },
     SetWindowPosition { new_x: u32, new_y: u32 },
     SetFullscreen(bool),
+    SetSwapInterval(crate::SwapInterval),
     ShowKeyboard(bool),
+    PickColor((f32, f32, f32, f32)),
+    MessageBox(Box<crate::message_box::MessageBoxRequest>),
 }
 
 pub trait Clipboard: Send + Sync {
@@ -80,12 +111,33 @@ pub trait Clipboard: Send + Sync {
 
 pub mod module;
 
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) mod render_thread;
+
 #[cfg(target_os = "linux")]
 pub mod linux_x11;
 
 #[cfg(target_os = "linux")]
 pub mod linux_wayland;
 
+#[cfg(target_os = "linux")]
+pub mod linux_tray;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod linux_screensaver;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod linux_portal;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod linux_session;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod linux_gpu;
+
+#[cfg(target_os = "linux")]
+pub(crate) mod linux_message_box;
+
 #[cfg(target_os = "android")]
 pub mod android;
 
@@ -166,6 +218,22 @@ pub fn current_monitor_impl() -> crate::MonitorMetrics {
     return wasm::primary_monitor();
 }
 
+/// What the key types in the current layout, for platforms where `KeyCode`
+/// is the physical key. X11 and Wayland translate keys through the layout already.
+pub fn key_label(keycode: crate::KeyCode) -> Option<String> {
+    #[cfg(target_os = "macos")]
+    return macos::key_label(keycode);
+    #[cfg(target_os = "windows")]
+    return windows::key_label(keycode);
+    #[cfg(target_arch = "wasm32")]
+    return wasm::key_label(keycode);
+    #[cfg(not(any(target_os = "macos", target_os = "windows", target_arch = "wasm32")))]
+    {
+        let _ = keycode;
+        None
+    }
+}
+
 #[cfg(target_os = "linux")]
 fn linux_monitor() -> crate::MonitorMetrics {
     #[cfg(feature = "wayland")]
@@ -6,7 +6,7 @@ use crate::{
     },
 };
 
-use std::{cell::RefCell, sync::mpsc, thread};
+use std::{cell::RefCell, collections::HashSet, sync::mpsc, thread};
 
 pub use crate::native::gl::{self, *};
 
@@ -68,6 +68,7 @@ enum Message {
     Pause,
     Resume,
     Destroy,
+    LowMemory,
 }
 unsafe impl Send for Message {}
 
@@ -127,6 +128,7 @@ pub unsafe fn console_error(msg: *const ::core::ffi::c_char) {
 
 struct MainThreadState {
     libegl: LibEgl,
+    presenter: egl::Presenter,
     egl_display: egl::EGLDisplay,
     egl_config: egl::EGLConfig,
     egl_context: egl::EGLContext,
@@ -137,6 +139,12 @@ struct MainThreadState {
     fullscreen: bool,
     update_requested: bool,
     keymods: KeyMods,
+    // Android reports repeats as more key downs, told apart by the keys held
+    held_keys: HashSet<KeyCode>,
+    // The characters typed by a repeat are repeats too
+    key_repeating: bool,
+    // Belongs to the surface, applied again to every new one
+    swap_interval: i32,
 }
 
 impl MainThreadState {
@@ -168,6 +176,12 @@ impl MainThreadState {
         );
 
         assert!(!self.surface.is_null());
+        crate::native_display().lock().unwrap().context_handle = Some(crate::ContextHandle::Egl {
+            display: self.egl_display as _,
+            config: self.egl_config as _,
+            context: self.egl_context as _,
+            surface: self.surface as _,
+        });
 
         let res = (self.libegl.eglMakeCurrent)(
             self.egl_display,
@@ -177,6 +191,13 @@ impl MainThreadState {
         );
 
         assert!(res != 0);
+        self.apply_swap_interval();
+    }
+
+    unsafe fn apply_swap_interval(&mut self) {
+        let applied = egl::swap_interval(&mut self.libegl, self.egl_display, self.swap_interval);
+        crate::native_display().lock().unwrap().swap_interval =
+            crate::SwapInterval::from_gl(applied);
     }
 
     fn process_message(&mut self, msg: Message) {
@@ -205,8 +226,12 @@ impl MainThreadState {
             }
             Message::Character { character } => {
                 if let Some(character) = char::from_u32(character) {
-                    self.event_handler
-                        .char_event(character, Default::default(), false);
+                    crate::event::dispatch_char(
+                        &mut *self.event_handler,
+                        character,
+                        Default::default(),
+                        self.key_repeating,
+                    );
                 }
             }
             Message::KeyDown { keycode } => {
@@ -217,8 +242,9 @@ impl MainThreadState {
                     KeyCode::LeftSuper | KeyCode::RightSuper => self.keymods.logo = true,
                     _ => {}
                 }
+                self.key_repeating = !self.held_keys.insert(keycode);
                 self.event_handler
-                    .key_down_event(keycode, self.keymods, false);
+                    .key_down_event(keycode, self.keymods, self.key_repeating);
             }
             Message::KeyUp { keycode } => {
                 match keycode {
@@ -228,9 +254,15 @@ impl MainThreadState {
                     KeyCode::LeftSuper | KeyCode::RightSuper => self.keymods.logo = false,
                     _ => {}
                 }
+                self.held_keys.remove(&keycode);
+                self.key_repeating = false;
                 self.event_handler.key_up_event(keycode, self.keymods);
             }
             Message::Pause => self.event_handler.window_minimized_event(),
+            Message::LowMemory => {
+                crate::graphics::buffer_pool::notify_memory_pressure();
+                self.event_handler.low_memory_event();
+            }
             Message::Resume => {
                 if self.fullscreen {
                     unsafe {
@@ -249,15 +281,29 @@ impl MainThreadState {
     }
 
     fn frame(&mut self) {
+        crate::frame_stats::begin_frame();
+        crate::event::dispatch_errors(&mut *self.event_handler);
+        crate::accessibility::dispatch_actions(&mut *self.event_handler);
+        crate::colors::dispatch_picked(&mut *self.event_handler);
+        crate::message_box::dispatch_answers(&mut *self.event_handler);
         self.event_handler.update();
 
         if self.surface.is_null() == false {
             self.update_requested = false;
+            unsafe {
+                self.presenter
+                    .begin_frame(&mut self.libegl, self.egl_display, self.surface);
+            }
             self.event_handler.draw();
 
+            crate::frame_stats::begin_present();
             unsafe {
-                (self.libegl.eglSwapBuffers)(self.egl_display, self.surface);
+                self.presenter
+                    .swap_buffers(&mut self.libegl, self.egl_display, self.surface);
             }
+            crate::frame_pacing::sync_after_swap();
+            crate::frame_stats::end_frame();
+            crate::frame_pacing::wait();
         }
     }
 
@@ -279,6 +325,12 @@ impl MainThreadState {
                 let env = attach_jni_env();
                 ndk_utils::call_void_method!(env, ACTIVITY, "showKeyboard", "(Z)V", show as i32);
             },
+            SetSwapInterval(interval) => {
+                self.swap_interval = interval.to_gl();
+                if !self.surface.is_null() {
+                    unsafe { self.apply_swap_interval() };
+                }
+            }
             _ => {}
         }
     }
@@ -448,12 +500,20 @@ where
         crate::set_or_replace_display(NativeDisplayData {
             high_dpi: conf.high_dpi,
             blocking_event_loop: conf.platform.blocking_event_loop,
+            context_handle: Some(crate::ContextHandle::Egl {
+                display: egl_display as _,
+                config: egl_config as _,
+                context: egl_context as _,
+                surface: surface as _,
+            }),
             ..NativeDisplayData::new(screen_width as _, screen_height as _, tx, clipboard)
         });
 
         let event_handler = f.0();
+        let presenter = egl::Presenter::new(&mut libegl, egl_display);
         let mut s = MainThreadState {
             libegl,
+            presenter,
             egl_display,
             egl_config,
             egl_context,
@@ -469,7 +529,11 @@ where
                 alt: false,
                 logo: false,
             },
+            held_keys: HashSet::new(),
+            key_repeating: false,
+            swap_interval: conf.platform.swap_interval.unwrap_or(1),
         };
+        s.apply_swap_interval();
 
         while !s.quit {
             while let Ok(request) = requests_rx.try_recv() {
@@ -550,6 +614,30 @@ unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnPause(
     send_message(Message::Pause);
 }
 
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnTrimMemory(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+    level: ndk_sys::jint,
+) {
+    // ComponentCallbacks2.TRIM_MEMORY_RUNNING_LOW
+    const TRIM_MEMORY_RUNNING_LOW: ndk_sys::jint = 10;
+    // ComponentCallbacks2.TRIM_MEMORY_UI_HIDDEN, just a notification that the app went to background
+    const TRIM_MEMORY_UI_HIDDEN: ndk_sys::jint = 20;
+
+    if level >= TRIM_MEMORY_RUNNING_LOW && level != TRIM_MEMORY_UI_HIDDEN {
+        send_message(Message::LowMemory);
+    }
+}
+
+#[no_mangle]
+unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnLowMemory(
+    _: *mut ndk_sys::JNIEnv,
+    _: ndk_sys::jobject,
+) {
+    send_message(Message::LowMemory);
+}
+
 #[no_mangle]
 unsafe extern "C" fn Java_quad_1native_QuadNative_activityOnDestroy(
     _: *mut ndk_sys::JNIEnv,
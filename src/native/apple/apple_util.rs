@@ -147,7 +147,10 @@ pub unsafe fn get_event_key_modifier(event: ObjcId) -> KeyMods {
 
 pub unsafe fn get_event_keycode(event: ObjcId) -> Option<KeyCode> {
     let scan_code: core::ffi::c_ushort = msg_send![event, keyCode];
+    translate_scan_code(scan_code)
+}
 
+pub fn translate_scan_code(scan_code: core::ffi::c_ushort) -> Option<KeyCode> {
     Some(match scan_code {
         0x00 => KeyCode::A,
         0x01 => KeyCode::S,
@@ -409,21 +412,22 @@ pub fn load_mouse_cursor(cursor: CursorIcon) -> ObjcId {
         CursorIcon::Default => load_native_cursor("arrowCursor"),
         CursorIcon::Pointer => load_native_cursor("pointingHandCursor"),
         CursorIcon::Text => load_native_cursor("IBeamCursor"),
-        CursorIcon::NotAllowed /*| CursorIcon::NoDrop*/ => load_native_cursor("operationNotAllowedCursor"),
-        CursorIcon::Crosshair => load_native_cursor("crosshairCursor"),
-        /*
-        CursorIcon::Grabbing | CursorIcon::Grab => load_native_cursor("closedHandCursor"),
+        CursorIcon::NotAllowed | CursorIcon::NoDrop => {
+            load_native_cursor("operationNotAllowedCursor")
+        }
+        CursorIcon::Crosshair | CursorIcon::Cell => load_native_cursor("crosshairCursor"),
+        CursorIcon::Grab => load_native_cursor("openHandCursor"),
+        CursorIcon::Grabbing => load_native_cursor("closedHandCursor"),
         CursorIcon::VerticalText => load_native_cursor("IBeamCursorForVerticalLayout"),
         CursorIcon::Copy => load_native_cursor("dragCopyCursor"),
         CursorIcon::Alias => load_native_cursor("dragLinkCursor"),
         CursorIcon::ContextMenu => load_native_cursor("contextualMenuCursor"),
-        */
-        //CursorIcon::EResize => load_native_cursor("resizeRightCursor"),
-        //CursorIcon::NResize => load_native_cursor("resizeUpCursor"),
-        //CursorIcon::WResize => load_native_cursor("resizeLeftCursor"),
-        //CursorIcon::SResize => load_native_cursor("resizeDownCursor"),
-        CursorIcon::EWResize => load_native_cursor("resizeLeftRightCursor"),
-        CursorIcon::NSResize => load_native_cursor("resizeUpDownCursor"),
+        CursorIcon::EResize => load_native_cursor("resizeRightCursor"),
+        CursorIcon::NResize => load_native_cursor("resizeUpCursor"),
+        CursorIcon::WResize => load_native_cursor("resizeLeftCursor"),
+        CursorIcon::SResize => load_native_cursor("resizeDownCursor"),
+        CursorIcon::EWResize | CursorIcon::ColResize => load_native_cursor("resizeLeftRightCursor"),
+        CursorIcon::NSResize | CursorIcon::RowResize => load_native_cursor("resizeUpDownCursor"),
 
         // Undocumented cursors: https://stackoverflow.com/a/46635398/5435443
         // Unfortunately undocumented cursors requires NSTracking areas that
@@ -93,6 +93,9 @@ extern "C" {
         usedBufLen: *mut u64,
     ) -> u64;
     pub fn NSLog(fmt: ObjcId, ...);
+
+    pub fn CFDataGetBytePtr(theData: *const c_void) -> *const u8;
+    pub fn CFRelease(cf: *const c_void);
 }
 
 #[link(name = "ImageIO", kind = "framework")]
@@ -117,6 +120,39 @@ extern "C" {
     pub static NSPasteboardTypeFileURL: ObjcId;
 }
 
+#[cfg(target_os = "macos")]
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    pub static kTISPropertyUnicodeKeyLayoutData: CFStringRef;
+    pub fn TISCopyCurrentKeyboardLayoutInputSource() -> *mut c_void;
+    pub fn TISGetInputSourceProperty(source: *mut c_void, key: CFStringRef) -> *const c_void;
+    pub fn LMGetKbdType() -> u8;
+    pub fn UCKeyTranslate(
+        keyLayoutPtr: *const u8,
+        virtualKeyCode: u16,
+        keyAction: u16,
+        modifierKeyState: u32,
+        keyboardType: u32,
+        keyTranslateOptions: u32,
+        deadKeyState: *mut u32,
+        maxStringLength: u64,
+        actualStringLength: *mut u64,
+        unicodeString: *mut u16,
+    ) -> i32;
+}
+
+#[cfg(target_os = "macos")]
+pub const kUCKeyActionDisplay: u16 = 3;
+#[cfg(target_os = "macos")]
+pub const kUCKeyTranslateNoDeadKeysMask: u32 = 1;
+
+#[cfg(target_os = "macos")]
+#[link(name = "OpenGL", kind = "framework")]
+extern "C" {
+    pub fn CGLLockContext(ctx: *mut c_void) -> i32;
+    pub fn CGLUnlockContext(ctx: *mut c_void) -> i32;
+}
+
 #[cfg(target_os = "ios")]
 #[link(name = "GLKit", kind = "framework")]
 extern "C" {}
@@ -232,6 +268,32 @@ extern "C" {
     pub fn MTLCopyAllDevices() -> ObjcId; //TODO: Array
 }
 
+#[link(name = "CoreVideo", kind = "framework")]
+extern "C" {
+    pub fn CVPixelBufferGetWidth(pixel_buffer: *mut c_void) -> usize;
+    pub fn CVPixelBufferGetHeight(pixel_buffer: *mut c_void) -> usize;
+    pub fn CVMetalTextureCacheCreate(
+        allocator: *const c_void,
+        cache_attributes: *const c_void,
+        metal_device: ObjcId,
+        texture_attributes: *const c_void,
+        cache_out: *mut *mut c_void,
+    ) -> i32;
+    pub fn CVMetalTextureCacheCreateTextureFromImage(
+        allocator: *const c_void,
+        texture_cache: *mut c_void,
+        source_image: *mut c_void,
+        texture_attributes: *const c_void,
+        pixel_format: MTLPixelFormat,
+        width: usize,
+        height: usize,
+        plane_index: usize,
+        texture_out: *mut *mut c_void,
+    ) -> i32;
+    pub fn CVMetalTextureCacheFlush(texture_cache: *mut c_void, options: u64);
+    pub fn CVMetalTextureGetTexture(image: *mut c_void) -> ObjcId;
+}
+
 // Foundation
 
 #[repr(C)]
@@ -414,6 +476,10 @@ pub enum NSWindowStyleMask {
     NSMiniaturizableWindowMask = 1 << 2,
     NSResizableWindowMask = 1 << 3,
 
+    // NSPanel only
+    NSUtilityWindowMask = 1 << 4,
+    NSNonactivatingPanelMask = 1 << 7,
+
     NSTexturedBackgroundWindowMask = 1 << 8,
 
     NSUnifiedTitleAndToolbarWindowMask = 1 << 12,
@@ -461,6 +527,8 @@ impl NSRange {
     }
 }
 
+pub const NSNotFound: u64 = i64::MAX as u64;
+
 unsafe impl Encode for NSRange {
     fn encode() -> Encoding {
         let encoding = format!(
@@ -858,6 +926,17 @@ unsafe impl Encode for NSDragOperation {
     }
 }
 
+#[repr(C)]
+#[derive(Copy, Debug, Clone)]
+pub struct MTLViewport {
+    pub origin_x: f64,
+    pub origin_y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub znear: f64,
+    pub zfar: f64,
+}
+
 #[repr(C)]
 #[derive(Copy, Debug, Clone)]
 pub struct MTLScissorRect {
@@ -34,6 +34,8 @@ pub const EGL_HEIGHT: u32 = 12374;
 pub const EGL_SURFACE_TYPE: u32 = 12339;
 pub const EGL_NONE: u32 = 12344;
 pub const EGL_CONTEXT_CLIENT_VERSION: u32 = 12440;
+pub const EGL_EXTENSIONS: u32 = 12373;
+pub const EGL_BUFFER_AGE_EXT: u32 = 12605;
 
 pub type NativeDisplayType = EGLNativeDisplayType;
 pub type NativePixmapType = EGLNativePixmapType;
@@ -166,6 +168,89 @@ impl std::error::Error for EglError {}
 
 pub struct Egl {}
 
+/// `eglSwapInterval` on the current surface, returns the interval applied.
+/// EGL has no late swap tearing, adaptive is plain vsync.
+pub unsafe fn swap_interval(egl: &mut LibEgl, display: EGLDisplay, interval: i32) -> i32 {
+    let interval = if interval < 0 { 1 } else { interval };
+    if (egl.eglSwapInterval)(display, interval) == 0 {
+        crate::error::report_error(crate::error::PlatformError::OsError(
+            "eglSwapInterval failed".to_string(),
+        ));
+        // The default of every EGL surface
+        return 1;
+    }
+    interval
+}
+
+type SwapBuffersWithDamage =
+    unsafe extern "C" fn(EGLDisplay, EGLSurface, *const EGLint, EGLint) -> EGLBoolean;
+
+/// Swaps with the damage of `window::set_dirty_region` and reports the buffer
+/// age for `window::buffer_age`, where the EGL extensions are there
+pub struct Presenter {
+    swap_with_damage: Option<SwapBuffersWithDamage>,
+    buffer_age: bool,
+}
+
+impl Presenter {
+    pub unsafe fn new(egl: &mut LibEgl, display: EGLDisplay) -> Presenter {
+        let extensions = (egl.eglQueryString)(display, EGL_EXTENSIONS as _);
+        let extensions = if extensions.is_null() {
+            ""
+        } else {
+            std::ffi::CStr::from_ptr(extensions).to_str().unwrap_or("")
+        };
+        let has = |name: &str| extensions.split(' ').any(|extension| extension == name);
+        let get_proc = |name: &str| {
+            let name = std::ffi::CString::new(name).unwrap();
+            (egl.eglGetProcAddress)(name.as_ptr())
+        };
+
+        let swap_with_damage = if has("EGL_KHR_swap_buffers_with_damage") {
+            get_proc("eglSwapBuffersWithDamageKHR")
+        } else if has("EGL_EXT_swap_buffers_with_damage") {
+            get_proc("eglSwapBuffersWithDamageEXT")
+        } else {
+            None
+        };
+        Presenter {
+            swap_with_damage: swap_with_damage
+                .map(|f| std::mem::transmute::<unsafe extern "C" fn(), SwapBuffersWithDamage>(f)),
+            buffer_age: has("EGL_EXT_buffer_age"),
+        }
+    }
+
+    /// Record the age of the back buffer about to be drawn, before `draw`
+    pub unsafe fn begin_frame(&self, egl: &mut LibEgl, display: EGLDisplay, surface: EGLSurface) {
+        let mut age = 0;
+        let age = (self.buffer_age
+            && (egl.eglQuerySurface)(display, surface, EGL_BUFFER_AGE_EXT as _, &mut age) != 0)
+            .then_some(age as u32);
+        crate::native_display().lock().unwrap().buffer_age = age;
+    }
+
+    /// `eglSwapBuffers`, limited to the dirty region set for this frame
+    pub unsafe fn swap_buffers(&self, egl: &mut LibEgl, display: EGLDisplay, surface: EGLSurface) {
+        let (region, height) = {
+            let mut d = crate::native_display().lock().unwrap();
+            (d.dirty_region.take(), d.screen_height)
+        };
+        match (self.swap_with_damage, region) {
+            (Some(swap_with_damage), Some(region)) if !region.is_empty() => {
+                // EGL counts y from the bottom
+                let rects: Vec<EGLint> = region
+                    .iter()
+                    .flat_map(|&(x, y, w, h)| [x, height - y - h, w, h])
+                    .collect();
+                swap_with_damage(display, surface, rects.as_ptr(), region.len() as _);
+            }
+            _ => {
+                (egl.eglSwapBuffers)(display, surface);
+            }
+        }
+    }
+}
+
 pub unsafe fn create_egl_context(
     egl: &mut LibEgl,
     display: *mut std::ffi::c_void,
@@ -10,6 +10,7 @@ pub type GLint = ::core::ffi::c_int;
 pub type GLubyte = ::core::ffi::c_uchar;
 pub type GLushort = ::core::ffi::c_ushort;
 pub type GLuint = ::core::ffi::c_uint;
+pub type GLint64 = ::core::ffi::c_longlong;
 pub type GLuint64 = ::core::ffi::c_ulonglong;
 pub type GLsizei = ::core::ffi::c_int;
 pub type GLchar = ::core::ffi::c_char;
@@ -26,8 +27,17 @@ pub type GLclampf = f32;
 pub type GLdouble = f64;
 pub type GLclampd = f64;
 
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct __GLsync {
+    _unused: [u8; 0],
+}
+pub type GLsync = *mut __GLsync;
+
 pub const GL_INT_2_10_10_10_REV: u32 = 0x8D9F;
 pub const GL_PROGRAM_POINT_SIZE: u32 = 0x8642;
+pub const GL_PRIMITIVE_RESTART: u32 = 0x8F9D;
+pub const GL_PRIMITIVE_RESTART_FIXED_INDEX: u32 = 0x8D69;
 pub const GL_STENCIL_ATTACHMENT: u32 = 0x8D20;
 pub const GL_DEPTH_ATTACHMENT: u32 = 0x8D00;
 pub const GL_COLOR_ATTACHMENT2: u32 = 0x8CE2;
@@ -35,6 +45,8 @@ pub const GL_COLOR_ATTACHMENT0: u32 = 0x8CE0;
 pub const GL_COLOR_ATTACHMENT22: u32 = 0x8CF6;
 pub const GL_DRAW_FRAMEBUFFER: u32 = 0x8CA9;
 pub const GL_FRAMEBUFFER_COMPLETE: u32 = 0x8CD5;
+pub const GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER: u32 = 0x8CDB;
+pub const GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER: u32 = 0x8CDC;
 pub const GL_NUM_EXTENSIONS: u32 = 0x821D;
 pub const GL_INFO_LOG_LENGTH: u32 = 0x8B84;
 pub const GL_VERTEX_SHADER: u32 = 0x8B31;
@@ -58,6 +70,15 @@ pub const GL_SAMPLE_ALPHA_TO_COVERAGE: u32 = 0x809E;
 pub const GL_RGBA16F: u32 = 0x881A;
 pub const GL_CONSTANT_ALPHA: u32 = 0x8003;
 pub const GL_READ_FRAMEBUFFER: u32 = 0x8CA8;
+pub const GL_MAP_WRITE_BIT: u32 = 0x0002;
+pub const GL_MAP_PERSISTENT_BIT: u32 = 0x0040;
+pub const GL_MAP_COHERENT_BIT: u32 = 0x0080;
+pub const GL_SYNC_GPU_COMMANDS_COMPLETE: u32 = 0x9117;
+pub const GL_SYNC_FLUSH_COMMANDS_BIT: u32 = 0x00000001;
+pub const GL_ALREADY_SIGNALED: u32 = 0x911A;
+pub const GL_TIMEOUT_EXPIRED: u32 = 0x911B;
+pub const GL_CONDITION_SATISFIED: u32 = 0x911C;
+pub const GL_WAIT_FAILED: u32 = 0x911D;
 pub const GL_TEXTURE0: u32 = 0x84C0;
 pub const GL_TEXTURE_MIN_LOD: u32 = 0x813A;
 pub const GL_CLAMP_TO_EDGE: u32 = 0x812F;
@@ -68,6 +89,9 @@ pub const GL_NEAREST_MIPMAP_NEAREST: u32 = 0x2700;
 pub const GL_UNSIGNED_SHORT_4_4_4_4: u32 = 0x8033;
 pub const GL_SRC_ALPHA_SATURATE: u32 = 0x0308;
 pub const GL_STREAM_DRAW: u32 = 0x88E0;
+pub const GL_STREAM_READ: u32 = 0x88E1;
+pub const GL_PIXEL_PACK_BUFFER: u32 = 0x88EB;
+pub const GL_MAP_READ_BIT: u32 = 0x0001;
 pub const GL_ONE: u32 = 1;
 pub const GL_NEAREST_MIPMAP_LINEAR: u32 = 0x2702;
 pub const GL_RGB10_A2: u32 = 0x8059;
@@ -105,6 +129,14 @@ pub const GL_TEXTURE_3D: u32 = 0x806F;
 pub const GL_CW: u32 = 0x0900;
 pub const GL_LINEAR: u32 = 0x2601;
 pub const GL_RENDERBUFFER: u32 = 0x8D41;
+pub const GL_SAMPLES: u32 = 0x80A9;
+pub const GL_TEXTURE_2D_MULTISAMPLE: u32 = 0x9100;
+pub const GL_TEXTURE_EXTERNAL_OES: u32 = 0x8D65;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+pub const GL_COMPRESSED_RGBA_BPTC_UNORM: u32 = 0x8E8C;
+pub const GL_COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+pub const GL_COMPRESSED_RGBA_ASTC_4x4_KHR: u32 = 0x93B0;
+pub const GL_MAX_SAMPLES: u32 = 0x8D57;
 pub const GL_GEQUAL: u32 = 0x0206;
 pub const GL_COLOR_BUFFER_BIT: u32 = 0x00004000;
 pub const GL_RGBA32F: u32 = 0x8814;
@@ -117,6 +149,10 @@ pub const GL_TEXTURE_MIN_FILTER: u32 = 0x2801;
 pub const GL_LINEAR_MIPMAP_NEAREST: u32 = 0x2701;
 pub const GL_EXTENSIONS: u32 = 0x1F03;
 pub const GL_NO_ERROR: u32 = 0;
+pub const GL_GUILTY_CONTEXT_RESET: u32 = 0x8253;
+pub const GL_INNOCENT_CONTEXT_RESET: u32 = 0x8254;
+pub const GL_UNKNOWN_CONTEXT_RESET: u32 = 0x8255;
+pub const GL_DEBUG_SOURCE_APPLICATION: u32 = 0x824A;
 pub const GL_REPLACE: u32 = 0x1E01;
 pub const GL_KEEP: u32 = 0x1E00;
 pub const GL_CCW: u32 = 0x0901;
@@ -148,6 +184,26 @@ pub const GL_INCR_WRAP: u32 = 0x8507;
 pub const GL_LESS: u32 = 0x0201;
 pub const GL_MULTISAMPLE: u32 = 0x809D;
 pub const GL_FRAMEBUFFER_BINDING: u32 = 0x8CA6;
+pub const GL_CURRENT_PROGRAM: u32 = 0x8B8D;
+pub const GL_ARRAY_BUFFER_BINDING: u32 = 0x8894;
+pub const GL_ELEMENT_ARRAY_BUFFER_BINDING: u32 = 0x8895;
+pub const GL_VERTEX_ARRAY_BINDING: u32 = 0x85B5;
+pub const GL_ACTIVE_TEXTURE: u32 = 0x84E0;
+pub const GL_TEXTURE_BINDING_2D: u32 = 0x8069;
+pub const GL_TEXTURE_BINDING_CUBE_MAP: u32 = 0x8514;
+pub const GL_VIEWPORT: u32 = 0x0BA2;
+pub const GL_SCISSOR_BOX: u32 = 0x0C10;
+pub const GL_BLEND_SRC_RGB: u32 = 0x80C9;
+pub const GL_BLEND_DST_RGB: u32 = 0x80C8;
+pub const GL_BLEND_SRC_ALPHA: u32 = 0x80CB;
+pub const GL_BLEND_DST_ALPHA: u32 = 0x80CA;
+pub const GL_BLEND_EQUATION_RGB: u32 = 0x8009;
+pub const GL_BLEND_EQUATION_ALPHA: u32 = 0x883D;
+pub const GL_DEPTH_FUNC: u32 = 0x0B74;
+pub const GL_DEPTH_WRITEMASK: u32 = 0x0B72;
+pub const GL_COLOR_WRITEMASK: u32 = 0x0C23;
+pub const GL_CULL_FACE_MODE: u32 = 0x0B45;
+pub const GL_FRONT_FACE: u32 = 0x0B46;
 pub const GL_BACK: u32 = 0x0405;
 pub const GL_ALWAYS: u32 = 0x0207;
 pub const GL_FUNC_ADD: u32 = 0x8006;
@@ -234,6 +290,7 @@ pub const GL_MAX_CUBE_MAP_TEXTURE_SIZE: u32 = 0x851C;
 pub const GL_MAX_3D_TEXTURE_SIZE: u32 = 0x8073;
 pub const GL_MAX_ARRAY_TEXTURE_LAYERS: u32 = 0x88FF;
 pub const GL_MAX_VERTEX_ATTRIBS: u32 = 0x8869;
+pub const GL_MAX_TEXTURE_IMAGE_UNITS: u32 = 0x8872;
 pub const GL_CLAMP_TO_BORDER: u32 = 0x812D;
 pub const GL_TEXTURE_BORDER_COLOR: u32 = 0x1004;
 pub const GL_UNPACK_ALIGNMENT: u32 = 3317;
@@ -244,9 +301,48 @@ pub const GL_TEXTURE_SWIZZLE_A: u32 = 36421;
 pub const GL_TEXTURE_SWIZZLE_RGBA: u32 = 36422;
 pub const GL_DRAW_FRAMEBUFFER_BINDING: u32 = 36006;
 pub const GL_TIME_ELAPSED: u32 = 35007;
+pub const GL_TIMESTAMP: u32 = 36392;
 pub const GL_QUERY_RESULT: u32 = 34918;
 pub const GL_QUERY_RESULT_AVAILABLE: u32 = 34919;
 pub const GL_VENDOR: u32 = 0x1F00;
+pub const GL_RENDERER: u32 = 0x1F01;
+pub const GL_ACTIVE_UNIFORMS: u32 = 0x8B86;
+pub const GL_ACTIVE_ATTRIBUTES: u32 = 0x8B89;
+pub const GL_DEPTH_BITS: u32 = 0x0D56;
+pub const GL_STENCIL_BITS: u32 = 0x0D57;
+pub const GL_BACK_LEFT: u32 = 0x0402;
+pub const GL_SRGB: u32 = 0x8C40;
+pub const GL_FRAMEBUFFER_ATTACHMENT_OBJECT_TYPE: u32 = 0x8CD0;
+pub const GL_FRAMEBUFFER_ATTACHMENT_COLOR_ENCODING: u32 = 0x8210;
+pub const GL_FRAMEBUFFER_ATTACHMENT_RED_SIZE: u32 = 0x8212;
+pub const GL_FRAMEBUFFER_ATTACHMENT_GREEN_SIZE: u32 = 0x8213;
+pub const GL_FRAMEBUFFER_ATTACHMENT_BLUE_SIZE: u32 = 0x8214;
+pub const GL_FRAMEBUFFER_ATTACHMENT_ALPHA_SIZE: u32 = 0x8215;
+pub const GL_FRAMEBUFFER_ATTACHMENT_DEPTH_SIZE: u32 = 0x8216;
+pub const GL_FRAMEBUFFER_ATTACHMENT_STENCIL_SIZE: u32 = 0x8217;
+pub const GL_FLOAT_VEC2: u32 = 0x8B50;
+pub const GL_FLOAT_VEC3: u32 = 0x8B51;
+pub const GL_FLOAT_VEC4: u32 = 0x8B52;
+pub const GL_INT_VEC2: u32 = 0x8B53;
+pub const GL_INT_VEC3: u32 = 0x8B54;
+pub const GL_INT_VEC4: u32 = 0x8B55;
+pub const GL_BOOL: u32 = 0x8B56;
+pub const GL_BOOL_VEC2: u32 = 0x8B57;
+pub const GL_BOOL_VEC3: u32 = 0x8B58;
+pub const GL_BOOL_VEC4: u32 = 0x8B59;
+pub const GL_FLOAT_MAT2: u32 = 0x8B5A;
+pub const GL_FLOAT_MAT3: u32 = 0x8B5B;
+pub const GL_FLOAT_MAT4: u32 = 0x8B5C;
+pub const GL_UNSIGNED_INT_VEC2: u32 = 0x8DC6;
+pub const GL_UNSIGNED_INT_VEC3: u32 = 0x8DC7;
+pub const GL_UNSIGNED_INT_VEC4: u32 = 0x8DC8;
+pub const GL_SAMPLER_2D: u32 = 0x8B5E;
+pub const GL_SAMPLER_CUBE: u32 = 0x8B60;
+pub const GL_SAMPLER_2D_SHADOW: u32 = 0x8B62;
+pub const GL_INT_SAMPLER_2D: u32 = 0x8DCA;
+pub const GL_UNSIGNED_INT_SAMPLER_2D: u32 = 0x8DD2;
+pub const GL_SAMPLER_2D_MULTISAMPLE: u32 = 0x9108;
+pub const GL_SAMPLER_EXTERNAL_OES: u32 = 0x8D66;
 pub const GL_VERSION: u32 = 0x1F02;
 pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
 pub const GL_FRONT_AND_BACK: GLenum = 0x0408;
@@ -304,6 +400,19 @@ pub const ERROR_INVALID_VERSION_ARB: u32 = 0x2095;
 pub const ERROR_INVALID_PROFILE_ARB: u32 = 0x2096;
 pub const ERROR_INCOMPATIBLE_DEVICE_CONTEXTS_ARB: u32 = 0x2054;
 
+/// Check `glGetError` after every call, see `SafeGL::set_error_checks`
+#[cfg(debug_assertions)]
+pub(crate) static CHECK_ERRORS: std::sync::atomic::AtomicBool =
+    std::sync::atomic::AtomicBool::new(false);
+
+#[cfg(debug_assertions)]
+fn check_call(name: &'static str, location: &'static std::panic::Location<'static>) {
+    let error = unsafe { glGetError() };
+    if error != GL_NO_ERROR {
+        crate::graphics::SafeGL::report_call_error(name, location, error);
+    }
+}
+
 macro_rules! gl_loader {
     (
         $(
@@ -319,12 +428,31 @@ macro_rules! gl_loader {
         }
 
         $(
-            #[allow(clippy::too_many_arguments)]
+            #[allow(clippy::too_many_arguments, clippy::let_unit_value)]
+            #[cfg_attr(debug_assertions, track_caller)]
             pub unsafe fn $fn($($arg: $t),*) -> $res {
-                __pfns::$fn.unwrap()( $($arg),* )
+                let res = __pfns::$fn.unwrap()( $($arg),* );
+                #[cfg(debug_assertions)]
+                if CHECK_ERRORS.load(::std::sync::atomic::Ordering::Relaxed)
+                    && stringify!($fn) != "glGetError"
+                {
+                    check_call(stringify!($fn), ::std::panic::Location::caller());
+                }
+                res
             }
         )*
 
+        /// Was the given GL function found by the loader.
+        /// Calling a function that was not loaded will panic.
+        pub fn is_gl_func_loaded(name: &str) -> bool {
+            match name {
+                $(
+                    stringify!($fn) => unsafe { __pfns::$fn }.is_some(),
+                )*
+                _ => false,
+            }
+        }
+
         pub fn load_gl_funcs<T: FnMut(&str) -> Option<unsafe extern "C" fn() -> ()>>(mut getprocaddr: T) {
             $(
                 unsafe {
@@ -379,6 +507,24 @@ gl_loader!(
     fn glLinkProgram(program: GLuint) -> (),
     fn glPixelStorei(pname: GLenum, param: GLint) -> (),
     fn glGetUniformLocation(program: GLuint, name: *const GLchar) -> GLint,
+    fn glGetActiveAttrib(
+        program: GLuint,
+        index: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar
+    ) -> (),
+    fn glGetActiveUniform(
+        program: GLuint,
+        index: GLuint,
+        bufSize: GLsizei,
+        length: *mut GLsizei,
+        size: *mut GLint,
+        type_: *mut GLenum,
+        name: *mut GLchar
+    ) -> (),
     fn glGetShaderiv(shader: GLuint, pname: GLenum, params: *mut GLint) -> (),
     fn glGetProgramInfoLog(
         program: GLuint,
@@ -516,6 +662,16 @@ gl_loader!(
         height: GLsizei,
         border: GLint
     ) -> (),
+    fn glCopyTexSubImage2D(
+        target: GLenum,
+        level: GLint,
+        xoffset: GLint,
+        yoffset: GLint,
+        x: GLint,
+        y: GLint,
+        width: GLsizei,
+        height: GLsizei
+    ) -> (),
     fn glClearDepthf(d: GLfloat) -> (),
     fn glClearDepth(depth: GLclampd) -> (),
     fn glFramebufferTexture2D(
@@ -527,6 +683,9 @@ gl_loader!(
     ) -> (),
     fn glCreateProgram() -> GLuint,
     fn glViewport(x: GLint, y: GLint, width: GLsizei, height: GLsizei) -> (),
+    fn glViewportIndexedf(index: GLuint, x: GLfloat, y: GLfloat, w: GLfloat, h: GLfloat) -> (),
+    fn glDepthRange(n: GLdouble, f: GLdouble) -> (),
+    fn glDepthRangef(n: GLfloat, f: GLfloat) -> (),
     fn glDeleteBuffers(n: GLsizei, buffers: *const GLuint) -> (),
     fn glDrawArrays(mode: GLenum, first: GLint, count: GLsizei) -> (),
     fn glDrawElementsInstanced(
@@ -582,6 +741,7 @@ gl_loader!(
     fn glTexParameteri(target: GLenum, pname: GLenum, param: GLint) -> (),
     fn glGetIntegerv(pname: GLenum, params: *mut GLint) -> (),
     fn glEnable(cap: GLenum) -> (),
+    fn glIsEnabled(cap: GLenum) -> GLboolean,
     fn glBlitFramebuffer(
         srcX0: GLint,
         srcY0: GLint,
@@ -594,11 +754,22 @@ gl_loader!(
         mask: GLbitfield,
         filter: GLenum
     ) -> (),
+    fn glInvalidateFramebuffer(
+        target: GLenum,
+        numAttachments: GLsizei,
+        attachments: *const GLenum
+    ) -> (),
     fn glStencilMask(mask: GLuint) -> (),
     fn glStencilMaskSeparate(face: GLenum, mask: GLuint) -> (),
     fn glAttachShader(program: GLuint, shader: GLuint) -> (),
     fn glDetachShader(program: GLuint, shader: GLuint) -> (),
     fn glGetError() -> GLenum,
+    fn glGetGraphicsResetStatus() -> GLenum,
+    fn glGetGraphicsResetStatusKHR() -> GLenum,
+    fn glGetGraphicsResetStatusARB() -> GLenum,
+    fn glGetGraphicsResetStatusEXT() -> GLenum,
+    fn glPushDebugGroup(source: GLenum, id: GLuint, length: GLsizei, message: *const GLchar) -> (),
+    fn glPopDebugGroup() -> (),
     fn glClearColor(red: GLclampf, green: GLclampf, blue: GLclampf, alpha: GLclampf) -> (),
     fn glBlendColor(red: GLclampf, green: GLclampf, blue: GLclampf, alpha: GLclampf) -> (),
     fn glTexParameterf(target: GLenum, pname: GLenum, param: GLfloat) -> (),
@@ -646,9 +817,70 @@ gl_loader!(
     fn glGenQueries(n: GLsizei, ids: *mut GLuint) -> (),
     fn glGetQueryObjectiv(id: GLuint, pname: GLenum, params: *mut GLint) -> (),
     fn glGetQueryObjectui64v(id: GLuint, pname: GLenum, params: *mut GLuint64) -> (),
+    fn glQueryCounter(id: GLuint, target: GLenum) -> (),
+    fn glGetInteger64v(pname: GLenum, data: *mut GLint64) -> (),
     fn glFlush() -> (),
     fn glFinish() -> (),
-    fn glPolygonMode(face: GLenum, mode: GLenum) -> ()
+    fn glPolygonMode(face: GLenum, mode: GLenum) -> (),
+    fn glMapBufferRange(
+        target: GLenum,
+        offset: GLintptr,
+        length: GLsizeiptr,
+        access: GLbitfield
+    ) -> *mut GLvoid,
+    fn glUnmapBuffer(target: GLenum) -> GLboolean,
+    fn glBufferStorage(
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+        flags: GLbitfield
+    ) -> (),
+    fn glBufferStorageEXT(
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const GLvoid,
+        flags: GLbitfield
+    ) -> (),
+    fn glFenceSync(condition: GLenum, flags: GLbitfield) -> GLsync,
+    fn glDeleteSync(sync: GLsync) -> (),
+    fn glClientWaitSync(sync: GLsync, flags: GLbitfield, timeout: GLuint64) -> GLenum,
+    fn glPrimitiveRestartIndex(index: GLuint) -> (),
+    fn glTexImage2DMultisample(
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        fixedsamplelocations: GLboolean
+    ) -> (),
+    fn glGetFramebufferAttachmentParameteriv(
+        target: GLenum,
+        attachment: GLenum,
+        pname: GLenum,
+        params: *mut GLint
+    ) -> (),
+    fn glTexStorage2D(
+        target: GLenum,
+        levels: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei
+    ) -> (),
+    fn glTexStorage2DMultisample(
+        target: GLenum,
+        samples: GLsizei,
+        internalformat: GLenum,
+        width: GLsizei,
+        height: GLsizei,
+        fixedsamplelocations: GLboolean
+    ) -> (),
+    fn glGetInternalformativ(
+        target: GLenum,
+        internalformat: GLenum,
+        pname: GLenum,
+        bufSize: GLsizei,
+        params: *mut GLint
+    ) -> ()
 );
 
 // note that glGetString only works after first glSwapBuffer,
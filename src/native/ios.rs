@@ -5,7 +5,7 @@
 use {
     crate::{
         conf::{self, AppleGfxApi, Conf},
-        event::{EventHandler, KeyCode, KeyMods, TouchPhase},
+        event::{EventHandler, KeyCode, KeyMods, PointerKind, TouchPhase},
         fs,
         native::{
             apple::{
@@ -90,6 +90,8 @@ enum Message {
         touch_id: u64,
         x: f32,
         y: f32,
+        kind: PointerKind,
+        pressure: Option<f32>,
     },
     Character {
         character: u32,
@@ -103,6 +105,7 @@ enum Message {
     Pause,
     Resume,
     Destroy,
+    LowMemory,
 }
 unsafe impl Send for Message {}
 
@@ -114,8 +117,15 @@ impl MainThreadState {
     fn process_request(&mut self, request: crate::native::Request) {
         use crate::native::Request::*;
 
-        if let ScheduleUpdate = request {
-            self.update_requested = true;
+        match request {
+            ScheduleUpdate => self.update_requested = true,
+            SetSwapInterval(crate::SwapInterval::On) => {}
+            SetSwapInterval(_) => {
+                crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                    "iOS always waits for the vertical blank".to_string(),
+                ))
+            }
+            _ => {}
         }
     }
 }
@@ -149,11 +159,25 @@ pub fn define_glk_or_mtk_view(superclass: &Class) -> *const Class {
                     ios_pos.y *= content_scale_factor;
                 }
 
+                // UITouchTypePencil
+                let touch_type: i64 = msg_send![ios_touch, type];
+                let kind = if touch_type == 2 {
+                    PointerKind::Pen
+                } else {
+                    PointerKind::Touch
+                };
+                // 0 without 3D Touch or a Pencil
+                let max_force: f64 = msg_send![ios_touch, maximumPossibleForce];
+                let force: f64 = msg_send![ios_touch, force];
+                let pressure = (max_force > 0.).then(|| (force / max_force) as f32);
+
                 send_message(Message::Touch {
                     phase,
                     touch_id,
                     x: ios_pos.x as f32,
                     y: ios_pos.y as f32,
+                    kind,
+                    pressure,
                 });
             }
         }
@@ -196,20 +220,34 @@ pub fn define_glk_or_mtk_view(superclass: &Class) -> *const Class {
                 let mut state = payload.state.lock().unwrap();
                 state.quit = true;
             }
+            Message::LowMemory => {
+                crate::graphics::buffer_pool::notify_memory_pressure();
+                if let Some(ref mut event_handler) = payload.event_handler {
+                    event_handler.low_memory_event();
+                }
+            }
             Message::Touch {
                 phase,
                 touch_id,
                 x,
                 y,
+                kind,
+                pressure,
             } => {
                 if let Some(ref mut event_handler) = payload.event_handler {
+                    crate::event::set_pointer_source(kind, pressure);
                     event_handler.touch_event(phase, touch_id, x, y);
                 }
             }
             Message::Character { character } => {
                 if let Some(character) = char::from_u32(character) {
                     if let Some(ref mut event_handler) = payload.event_handler {
-                        event_handler.char_event(character, Default::default(), false);
+                        crate::event::dispatch_char(
+                            &mut **event_handler,
+                            character,
+                            Default::default(),
+                            false,
+                        );
                     }
                 }
             }
@@ -344,8 +382,17 @@ pub fn define_glk_or_mtk_view_dlg(superclass: &Class) -> *const Class {
         }
 
         if let Some(ref mut event_handler) = payload.event_handler {
+            crate::frame_stats::begin_frame();
+            crate::event::dispatch_errors(&mut **event_handler);
+            crate::accessibility::dispatch_actions(&mut **event_handler);
+            crate::colors::dispatch_picked(&mut **event_handler);
+            crate::message_box::dispatch_answers(&mut **event_handler);
             event_handler.update();
             event_handler.draw();
+            // Presented by the view itself after this returns
+            crate::frame_stats::begin_present();
+            crate::frame_stats::end_frame();
+            crate::frame_pacing::wait();
             let mut s = payload.state.lock().unwrap();
             s.update_requested = false;
         }
@@ -354,6 +401,17 @@ pub fn define_glk_or_mtk_view_dlg(superclass: &Class) -> *const Class {
     extern "C" fn draw_in_rect2(this: &Object, s: Sel, o: ObjcId) {
         draw_in_rect(this, s, o, nil);
     }
+    extern "C" fn display_link_fired(_: &Object, _: Sel, display_link: ObjcId) {
+        unsafe {
+            let target: f64 = msg_send![display_link, targetTimestamp];
+            let timestamp: f64 = msg_send![display_link, timestamp];
+            // CADisplayLink counts like CACurrentMediaTime, the system uptime
+            let process_info: ObjcId = msg_send![class!(NSProcessInfo), processInfo];
+            let uptime: f64 = msg_send![process_info, systemUptime];
+            let time = crate::event::monotonic_time() - (uptime - target);
+            crate::frame_stats::record_vblank(time, Some(target - timestamp));
+        }
+    }
 
     unsafe {
         decl.add_method(
@@ -365,6 +423,11 @@ pub fn define_glk_or_mtk_view_dlg(superclass: &Class) -> *const Class {
             sel!(drawInMTKView:),
             draw_in_rect2 as extern "C" fn(&Object, Sel, ObjcId),
         );
+
+        decl.add_method(
+            sel!(displayLinkFired:),
+            display_link_fired as extern "C" fn(&Object, Sel, ObjcId),
+        );
     }
 
     decl.add_ivar::<*mut c_void>("display_ptr");
@@ -547,6 +610,10 @@ pub fn define_app_delegate() -> *const Class {
                 gfx_api: conf.platform.apple_gfx_api,
                 blocking_event_loop: conf.platform.blocking_event_loop,
                 view: view.view,
+                window_handle: Some(crate::WindowHandle::UiKit {
+                    ui_view: view.view as _,
+                    ui_view_controller: view.view_ctrl as _,
+                }),
                 ..NativeDisplayData::new(conf.window_width, conf.window_height, tx, clipboard)
             });
 
@@ -582,6 +649,13 @@ pub fn define_app_delegate() -> *const Class {
             (*view.view_dlg).set_ivar("display_ptr", payload_ptr);
             (*textfield_dlg).set_ivar("display_ptr", payload_ptr);
 
+            // Only to know when the frames will be shown, the views draw on their own
+            let display_link: ObjcId = msg_send![class!(CADisplayLink),
+                displayLinkWithTarget: view.view_dlg
+                selector: sel!(displayLinkFired:)];
+            let run_loop: ObjcId = msg_send![class!(NSRunLoop), mainRunLoop];
+            msg_send_![display_link, addToRunLoop: run_loop forMode: NSRunLoopCommonModes];
+
             msg_send_![window_obj, addSubview: view.view];
 
             msg_send_![window_obj, setRootViewController: view.view_ctrl];
@@ -673,6 +747,10 @@ pub fn define_app_delegate() -> *const Class {
         send_message(Message::Pause);
     }
 
+    extern "C" fn application_did_receive_memory_warning(_: &Object, _: Sel, _: ObjcId) {
+        send_message(Message::LowMemory);
+    }
+
     unsafe {
         decl.add_method(
             sel!(application: didFinishLaunchingWithOptions:),
@@ -687,6 +765,10 @@ pub fn define_app_delegate() -> *const Class {
             sel!(applicationWillResignActive:),
             application_will_resign_active as extern "C" fn(&Object, Sel, ObjcId),
         );
+        decl.add_method(
+            sel!(applicationDidReceiveMemoryWarning:),
+            application_did_receive_memory_warning as extern "C" fn(&Object, Sel, ObjcId),
+        );
     }
     decl.register()
 }
@@ -0,0 +1,33 @@
+//! `Platform::gpu_preference` on Linux
+//!
+//! EGL_EXT_device_enumeration lists the GPUs, but a window surface can only be
+//! made on the display the X server or the compositor gives us. The GL drivers
+//! move the rendering to another GPU with PRIME render offload instead, asked
+//! for through the environment and read when the display is opened:
+//! `DRI_PRIME` for Mesa, `__NV_PRIME_RENDER_OFFLOAD` and
+//! `__GLX_VENDOR_LIBRARY_NAME` for the NVIDIA driver.
+//!
+//! Variables already set, e.g. by `prime-run` or the desktop's "Launch using
+//! Discrete Graphics Card", are left alone. Mesa renders on the integrated GPU
+//! by default, so `LowPower` changes nothing.
+
+use crate::conf::GpuPreference;
+
+fn set_default(name: &str, value: &str) {
+    if std::env::var_os(name).is_none() {
+        std::env::set_var(name, value);
+    }
+}
+
+/// Called before loading libGL/libEGL, while miniquad is the only thread
+pub(crate) fn apply_gpu_preference(gpu_preference: GpuPreference) {
+    if gpu_preference != GpuPreference::HighPerformance {
+        return;
+    }
+    set_default("DRI_PRIME", "1");
+    // Only with the NVIDIA kernel module loaded, GLX would find no vendor library
+    if std::path::Path::new("/proc/driver/nvidia/version").exists() {
+        set_default("__NV_PRIME_RENDER_OFFLOAD", "1");
+        set_default("__GLX_VENDOR_LIBRARY_NAME", "nvidia");
+    }
+}
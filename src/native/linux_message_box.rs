@@ -0,0 +1,100 @@
+//! Message boxes for X11 and Wayland, shown by zenity or kdialog, whichever is
+//! installed. Without them the message goes to stderr.
+
+use crate::{MessageBoxButton, MessageBoxButtons};
+
+use std::process::Command;
+
+/// Blocking, from any thread. `error` for `crash`.
+pub(crate) fn message_box(
+    title: &str,
+    text: &str,
+    buttons: MessageBoxButtons,
+    error: bool,
+) -> MessageBoxButton {
+    zenity(title, text, buttons, error)
+        .or_else(|| kdialog(title, text, buttons, error))
+        .unwrap_or_else(|| {
+            eprintln!("{}: {}", title, text);
+            crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                "Message boxes need zenity or kdialog".to_string(),
+            ));
+            buttons.dismissed()
+        })
+}
+
+/// None if zenity is not installed
+fn zenity(
+    title: &str,
+    text: &str,
+    buttons: MessageBoxButtons,
+    error: bool,
+) -> Option<MessageBoxButton> {
+    let mut command = Command::new("zenity");
+    command.args(["--no-markup", "--title", title, "--text", text]);
+    match buttons {
+        MessageBoxButtons::Ok if error => command.arg("--error"),
+        MessageBoxButtons::Ok => command.arg("--info"),
+        MessageBoxButtons::OkCancel => {
+            command.args(["--question", "--ok-label", "OK", "--cancel-label", "Cancel"])
+        }
+        MessageBoxButtons::YesNo => {
+            command.args(["--question", "--ok-label", "Yes", "--cancel-label", "No"])
+        }
+        // Escape and the close button exit like the cancel button, so No is
+        // the extra button, printed on stdout
+        MessageBoxButtons::YesNoCancel => command.args([
+            "--question",
+            "--ok-label",
+            "Yes",
+            "--cancel-label",
+            "Cancel",
+            "--extra-button",
+            "No",
+        ]),
+    };
+    let output = command.output().ok()?;
+    let accepted = output.status.success();
+    Some(match buttons {
+        MessageBoxButtons::Ok => MessageBoxButton::Ok,
+        MessageBoxButtons::OkCancel if accepted => MessageBoxButton::Ok,
+        MessageBoxButtons::YesNo | MessageBoxButtons::YesNoCancel if accepted => {
+            MessageBoxButton::Yes
+        }
+        MessageBoxButtons::YesNoCancel if output.stdout.starts_with(b"No") => MessageBoxButton::No,
+        _ => buttons.dismissed(),
+    })
+}
+
+/// None if kdialog is not installed
+fn kdialog(
+    title: &str,
+    text: &str,
+    buttons: MessageBoxButtons,
+    error: bool,
+) -> Option<MessageBoxButton> {
+    let mut command = Command::new("kdialog");
+    command.args(["--title", title]);
+    match buttons {
+        MessageBoxButtons::Ok if error => command.args(["--error", text]),
+        MessageBoxButtons::Ok => command.args(["--msgbox", text]),
+        MessageBoxButtons::OkCancel => {
+            command.args(["--yesno", text, "--yes-label", "OK", "--no-label", "Cancel"])
+        }
+        MessageBoxButtons::YesNo => command.args(["--yesno", text]),
+        MessageBoxButtons::YesNoCancel => command.args(["--yesnocancel", text]),
+    };
+    let status = command.status().ok()?;
+    // 0 for yes, 1 for no, 2 for cancel
+    Some(match (buttons, status.code()) {
+        (MessageBoxButtons::Ok, _) => MessageBoxButton::Ok,
+        (MessageBoxButtons::OkCancel, Some(0)) => MessageBoxButton::Ok,
+        (MessageBoxButtons::YesNo | MessageBoxButtons::YesNoCancel, Some(0)) => {
+            MessageBoxButton::Yes
+        }
+        (MessageBoxButtons::YesNo | MessageBoxButtons::YesNoCancel, Some(1)) => {
+            MessageBoxButton::No
+        }
+        _ => buttons.dismissed(),
+    })
+}
@@ -0,0 +1,230 @@
+//! XDG desktop portal calls for `colors`, on X11 and Wayland
+//!
+//! org.freedesktop.portal.Settings gives the accent color, and
+//! org.freedesktop.portal.Screenshot.PickColor lets the user pick a color
+//! anywhere on the screen. Both are answered by xdg-desktop-portal and the
+//! desktop's backend (GNOME, KDE, wlroots with the gtk backend).
+
+use super::linux_tray::{
+    c_str, DBusConnection, DBusError, DBusMessage, LibDbus, Reader, Writer, DBUS_BUS_SESSION,
+    DBUS_MESSAGE_TYPE_SIGNAL, DBUS_TYPE_ARRAY, DBUS_TYPE_DICT_ENTRY, DBUS_TYPE_STRUCT,
+    DBUS_TYPE_VARIANT,
+};
+use crate::error::{report_error, PlatformError};
+
+use std::ffi::CString;
+
+const PORTAL_NAME: &str = "org.freedesktop.portal.Desktop";
+const PORTAL_PATH: &str = "/org/freedesktop/portal/desktop";
+
+/// Milliseconds, the portal may have to be started on the first call
+const CALL_TIMEOUT: i32 = 5000;
+
+/// A private session bus connection, closed on drop
+struct Connection {
+    lib: LibDbus,
+    connection: *mut DBusConnection,
+}
+
+impl Connection {
+    fn open() -> Result<Connection, String> {
+        let lib = LibDbus::try_load().map_err(|_| "libdbus-1 not found".to_string())?;
+        unsafe {
+            let mut error: DBusError = std::mem::zeroed();
+            (lib.dbus_error_init)(&mut error);
+            let connection = (lib.dbus_bus_get_private)(DBUS_BUS_SESSION, &mut error);
+            if (lib.dbus_error_is_set)(&error) != 0 || connection.is_null() {
+                let message = format!(
+                    "Can't connect to the D-Bus session bus: {}",
+                    c_str(error.message)
+                );
+                (lib.dbus_error_free)(&mut error);
+                return Err(message);
+            }
+            (lib.dbus_connection_set_exit_on_disconnect)(connection, 0);
+            Ok(Connection { lib, connection })
+        }
+    }
+
+    /// Call a portal method and wait for the reply, which has to be unref'd
+    unsafe fn call(
+        &self,
+        interface: &str,
+        method: &str,
+        args: impl FnOnce(&mut Writer),
+    ) -> Result<*mut DBusMessage, String> {
+        let lib = &self.lib;
+        let destination = CString::new(PORTAL_NAME).unwrap();
+        let path = CString::new(PORTAL_PATH).unwrap();
+        let interface = CString::new(interface).unwrap();
+        let method = CString::new(method).unwrap();
+        let message = (lib.dbus_message_new_method_call)(
+            destination.as_ptr(),
+            path.as_ptr(),
+            interface.as_ptr(),
+            method.as_ptr(),
+        );
+        if message.is_null() {
+            return Err("Out of memory".to_string());
+        }
+        args(&mut Writer::new(lib, message));
+
+        let mut error: DBusError = std::mem::zeroed();
+        (lib.dbus_error_init)(&mut error);
+        let reply = (lib.dbus_connection_send_with_reply_and_block)(
+            self.connection,
+            message,
+            CALL_TIMEOUT,
+            &mut error,
+        );
+        (lib.dbus_message_unref)(message);
+        if (lib.dbus_error_is_set)(&error) != 0 || reply.is_null() {
+            let message = c_str(error.message).to_string();
+            (lib.dbus_error_free)(&mut error);
+            return Err(message);
+        }
+        Ok(reply)
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        unsafe {
+            (self.lib.dbus_connection_close)(self.connection);
+            (self.lib.dbus_connection_unref)(self.connection);
+        }
+    }
+}
+
+/// Skip the variants wrapping a value, Settings.Read nests two of them
+unsafe fn unwrap_variants<'a>(mut reader: Reader<'a>) -> Reader<'a> {
+    while reader.arg_type() == DBUS_TYPE_VARIANT {
+        reader = reader.recurse();
+    }
+    reader
+}
+
+/// A (ddd) struct, sRGB in 0..1
+unsafe fn read_rgb(reader: Reader) -> Option<(f32, f32, f32, f32)> {
+    let mut reader = unwrap_variants(reader);
+    if reader.arg_type() != DBUS_TYPE_STRUCT {
+        return None;
+    }
+    let mut rgb = reader.recurse();
+    let r = rgb.f64()?;
+    let g = rgb.f64()?;
+    let b = rgb.f64()?;
+    // The portal reports an unset accent color as values out of range
+    if [r, g, b].iter().any(|c| !(0.0..=1.0).contains(c)) {
+        return None;
+    }
+    Some((r as f32, g as f32, b as f32, 1.0))
+}
+
+/// org.freedesktop.appearance accent-color, None if the desktop has none
+pub(crate) fn accent_color() -> Option<(f32, f32, f32, f32)> {
+    let connection = Connection::open().ok()?;
+    unsafe {
+        let args = |w: &mut Writer| {
+            w.str("org.freedesktop.appearance");
+            w.str("accent-color");
+        };
+        // ReadOne is version 2 of the interface, older portals only have Read
+        let reply = connection
+            .call("org.freedesktop.portal.Settings", "ReadOne", args)
+            .or_else(|_| connection.call("org.freedesktop.portal.Settings", "Read", args))
+            .ok()?;
+        let color = read_rgb(Reader::new(&connection.lib, reply));
+        (connection.lib.dbus_message_unref)(reply);
+        color
+    }
+}
+
+/// Ask the portal for a color picked on screen, the result is reported to
+/// `colors` from a background thread once the user is done.
+pub(crate) fn pick_color() {
+    std::thread::Builder::new()
+        .name("miniquad color picker".to_string())
+        .spawn(|| {
+            let color = match pick_color_blocking() {
+                Ok(color) => color,
+                Err(err) => {
+                    report_error(PlatformError::FeatureUnsupported(format!(
+                        "Color picker portal: {}",
+                        err
+                    )));
+                    None
+                }
+            };
+            crate::colors::report_picked(color);
+        })
+        .expect("Failed to spawn the color picker thread");
+}
+
+fn pick_color_blocking() -> Result<Option<(f32, f32, f32, f32)>, String> {
+    let connection = Connection::open()?;
+    let lib = &connection.lib;
+    unsafe {
+        // Subscribe before calling, the response may come right after the reply
+        let rule = CString::new(
+            "type='signal',interface='org.freedesktop.portal.Request',member='Response'",
+        )
+        .unwrap();
+        let mut error: DBusError = std::mem::zeroed();
+        (lib.dbus_error_init)(&mut error);
+        (lib.dbus_bus_add_match)(connection.connection, rule.as_ptr(), &mut error);
+        if (lib.dbus_error_is_set)(&error) != 0 {
+            let message = c_str(error.message).to_string();
+            (lib.dbus_error_free)(&mut error);
+            return Err(message);
+        }
+
+        let reply = connection.call("org.freedesktop.portal.Screenshot", "PickColor", |w| {
+            // No parent window, and no options
+            w.str("");
+            w.container(DBUS_TYPE_ARRAY, Some("{sv}"), |_| {});
+        })?;
+        let handle = Reader::new(lib, reply).object_path();
+        (lib.dbus_message_unref)(reply);
+        let handle = handle.ok_or_else(|| "Invalid PickColor reply".to_string())?;
+
+        loop {
+            if (lib.dbus_connection_read_write)(connection.connection, -1) == 0 {
+                return Err("Disconnected from the session bus".to_string());
+            }
+            loop {
+                let message = (lib.dbus_connection_pop_message)(connection.connection);
+                if message.is_null() {
+                    break;
+                }
+                let response = (lib.dbus_message_get_type)(message) == DBUS_MESSAGE_TYPE_SIGNAL
+                    && c_str((lib.dbus_message_get_member)(message)) == "Response"
+                    && c_str((lib.dbus_message_get_path)(message)) == handle;
+                let color = if response {
+                    Some(read_response(Reader::new(lib, message)))
+                } else {
+                    None
+                };
+                (lib.dbus_message_unref)(message);
+                if let Some(color) = color {
+                    return Ok(color);
+                }
+            }
+        }
+    }
+}
+
+/// Response(u response, a{sv} results), 0 is success, 1 cancelled
+unsafe fn read_response(mut reader: Reader) -> Option<(f32, f32, f32, f32)> {
+    if reader.u32()? != 0 || reader.arg_type() != DBUS_TYPE_ARRAY {
+        return None;
+    }
+    let mut results = reader.recurse();
+    while results.arg_type() == DBUS_TYPE_DICT_ENTRY {
+        let mut entry = results.recurse();
+        if entry.str().as_deref() == Some("color") {
+            return read_rgb(entry);
+        }
+    }
+    None
+}
@@ -0,0 +1,94 @@
+//! Screensaver inhibition for X11 and Wayland, for `Kiosk::inhibit_screensaver`
+//!
+//! Calls org.freedesktop.ScreenSaver.Inhibit on the session bus, implemented by
+//! GNOME, KDE, XFCE and most screensaver daemons. The service lifts the
+//! inhibition when the caller leaves the bus, so closing the connection is all
+//! the cleanup there is, and the reply with the cookie is not waited for.
+
+use super::linux_tray::{
+    c_str, DBusConnection, DBusError, DBusMessageIter, LibDbus, DBUS_BUS_SESSION, DBUS_TYPE_STRING,
+};
+
+use std::ffi::CString;
+
+pub(crate) struct ScreenSaverInhibitor {
+    lib: LibDbus,
+    connection: *mut DBusConnection,
+}
+
+impl ScreenSaverInhibitor {
+    pub fn new(application: &str) -> Option<ScreenSaverInhibitor> {
+        let lib = match LibDbus::try_load() {
+            Ok(lib) => lib,
+            Err(_) => {
+                crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                    "libdbus-1 not found, the screensaver is not inhibited".to_string(),
+                ));
+                return None;
+            }
+        };
+
+        unsafe {
+            let mut error: DBusError = std::mem::zeroed();
+            (lib.dbus_error_init)(&mut error);
+            // Private, the inhibition lasts as long as this very connection
+            let connection = (lib.dbus_bus_get_private)(DBUS_BUS_SESSION, &mut error);
+            if (lib.dbus_error_is_set)(&error) != 0 || connection.is_null() {
+                crate::error::report_error(crate::error::PlatformError::OsError(format!(
+                    "Can't connect to the D-Bus session bus, the screensaver is not inhibited: {}",
+                    c_str(error.message)
+                )));
+                (lib.dbus_error_free)(&mut error);
+                return None;
+            }
+            (lib.dbus_connection_set_exit_on_disconnect)(connection, 0);
+
+            let destination = CString::new("org.freedesktop.ScreenSaver").unwrap();
+            let path = CString::new("/org/freedesktop/ScreenSaver").unwrap();
+            let method = CString::new("Inhibit").unwrap();
+            let message = (lib.dbus_message_new_method_call)(
+                destination.as_ptr(),
+                path.as_ptr(),
+                destination.as_ptr(),
+                method.as_ptr(),
+            );
+            if message.is_null() {
+                (lib.dbus_connection_close)(connection);
+                (lib.dbus_connection_unref)(connection);
+                return None;
+            }
+
+            let application = if application.is_empty() {
+                "miniquad"
+            } else {
+                application
+            };
+            let application = CString::new(application.replace('\0', "")).unwrap();
+            let reason = CString::new("Kiosk mode").unwrap();
+            let mut iter = DBusMessageIter::new();
+            (lib.dbus_message_iter_init_append)(message, &mut iter);
+            for arg in [&application, &reason].iter() {
+                let ptr = arg.as_ptr();
+                (lib.dbus_message_iter_append_basic)(
+                    &mut iter,
+                    DBUS_TYPE_STRING,
+                    &ptr as *const _ as _,
+                );
+            }
+            (lib.dbus_connection_send)(connection, message, std::ptr::null_mut());
+            (lib.dbus_connection_flush)(connection);
+            (lib.dbus_message_unref)(message);
+
+            Some(ScreenSaverInhibitor { lib, connection })
+        }
+    }
+}
+
+impl Drop for ScreenSaverInhibitor {
+    fn drop(&mut self) {
+        unsafe {
+            (self.lib.dbus_connection_close)(self.connection);
+            (self.lib.dbus_connection_unref)(self.connection);
+        }
+    }
+}
@@ -0,0 +1,37 @@
+//! SIGTERM, sent by systemd and the session managers on logout and shutdown,
+//! reported as `EventHandler::session_end_event` on X11 and Wayland
+//!
+//! The handler only raises a flag, the event loops turn it into a quit request.
+//! A SIGTERM handler installed by the application is left alone.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static SIGTERM: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_sigterm(_: libc::c_int) {
+    SIGTERM.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn install_sigterm_handler() {
+    unsafe {
+        let previous = libc::signal(
+            libc::SIGTERM,
+            on_sigterm as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+        if previous != libc::SIG_DFL {
+            libc::signal(libc::SIGTERM, previous);
+        }
+    }
+}
+
+/// Turn a SIGTERM received since the last call into a session end quit request,
+/// true if there was one
+pub(crate) fn poll_sigterm() -> bool {
+    if !SIGTERM.swap(false, Ordering::Relaxed) {
+        return false;
+    }
+    let mut d = crate::native_display().lock().unwrap();
+    d.quit_requested = true;
+    d.session_ending = true;
+    true
+}
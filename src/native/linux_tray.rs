@@ -0,0 +1,814 @@
+#![allow(non_upper_case_globals, non_snake_case)]
+
+//! Tray icon for X11 and Wayland, as a StatusNotifierItem
+//! <https://www.freedesktop.org/wiki/Specifications/StatusNotifierItem/>
+//! with its menu exported over com.canonical.dbusmenu.
+//!
+//! libdbus is loaded at runtime; the session bus is polled once per frame from
+//! the event loop, so with `blocking_event_loop` tray clicks wait for the next
+//! window event.
+
+use crate::{
+    event::EventHandler,
+    tray::{Tray, TrayEvent, TrayMenuItem},
+};
+
+use core::ffi::{c_char, c_int, c_uint, c_void};
+use std::ffi::{CStr, CString};
+
+#[repr(C)]
+pub struct DBusConnection {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+pub struct DBusMessage {
+    _unused: [u8; 0],
+}
+
+#[repr(C)]
+pub struct DBusError {
+    name: *const c_char,
+    pub(crate) message: *const c_char,
+    _dummy: c_uint,
+    _padding: *mut c_void,
+}
+
+#[repr(C)]
+pub struct DBusMessageIter {
+    _dummy1: *mut c_void,
+    _dummy2: *mut c_void,
+    _dummy3: u32,
+    _dummy4: [c_int; 9],
+    _pad2: *mut c_void,
+    _pad3: *mut c_void,
+}
+
+impl DBusMessageIter {
+    pub(crate) fn new() -> DBusMessageIter {
+        unsafe { std::mem::zeroed() }
+    }
+}
+
+pub(crate) const DBUS_BUS_SESSION: c_int = 0;
+const DBUS_NAME_FLAG_DO_NOT_QUEUE: c_uint = 4;
+const DBUS_MESSAGE_TYPE_METHOD_CALL: c_int = 1;
+pub(crate) const DBUS_MESSAGE_TYPE_SIGNAL: c_int = 4;
+
+const DBUS_TYPE_BYTE: c_int = b'y' as _;
+const DBUS_TYPE_BOOLEAN: c_int = b'b' as _;
+const DBUS_TYPE_INT32: c_int = b'i' as _;
+pub(crate) const DBUS_TYPE_UINT32: c_int = b'u' as _;
+pub(crate) const DBUS_TYPE_STRING: c_int = b's' as _;
+pub(crate) const DBUS_TYPE_OBJECT_PATH: c_int = b'o' as _;
+pub(crate) const DBUS_TYPE_DOUBLE: c_int = b'd' as _;
+pub(crate) const DBUS_TYPE_ARRAY: c_int = b'a' as _;
+pub(crate) const DBUS_TYPE_VARIANT: c_int = b'v' as _;
+pub(crate) const DBUS_TYPE_STRUCT: c_int = b'r' as _;
+pub(crate) const DBUS_TYPE_DICT_ENTRY: c_int = b'e' as _;
+
+crate::declare_module!(
+    LibDbus,
+    "libdbus-1.so",
+    "libdbus-1.so.3",
+    ...
+    ...
+    pub fn dbus_error_init(*mut DBusError),
+    pub fn dbus_error_is_set(*const DBusError) -> u32,
+    pub fn dbus_error_free(*mut DBusError),
+    pub fn dbus_bus_get_private(c_int, *mut DBusError) -> *mut DBusConnection,
+    pub fn dbus_bus_request_name(*mut DBusConnection, *const c_char, c_uint, *mut DBusError) -> c_int,
+    pub fn dbus_bus_add_match(*mut DBusConnection, *const c_char, *mut DBusError),
+    pub fn dbus_connection_set_exit_on_disconnect(*mut DBusConnection, u32),
+    pub fn dbus_connection_read_write(*mut DBusConnection, c_int) -> u32,
+    pub fn dbus_connection_pop_message(*mut DBusConnection) -> *mut DBusMessage,
+    pub fn dbus_connection_send(*mut DBusConnection, *mut DBusMessage, *mut u32) -> u32,
+    pub fn dbus_connection_send_with_reply_and_block(*mut DBusConnection, *mut DBusMessage, c_int, *mut DBusError) -> *mut DBusMessage,
+    pub fn dbus_connection_flush(*mut DBusConnection),
+    pub fn dbus_connection_close(*mut DBusConnection),
+    pub fn dbus_connection_unref(*mut DBusConnection),
+    pub fn dbus_message_new_method_call(*const c_char, *const c_char, *const c_char, *const c_char) -> *mut DBusMessage,
+    pub fn dbus_message_new_method_return(*mut DBusMessage) -> *mut DBusMessage,
+    pub fn dbus_message_new_signal(*const c_char, *const c_char, *const c_char) -> *mut DBusMessage,
+    pub fn dbus_message_new_error(*mut DBusMessage, *const c_char, *const c_char) -> *mut DBusMessage,
+    pub fn dbus_message_unref(*mut DBusMessage),
+    pub fn dbus_message_get_type(*mut DBusMessage) -> c_int,
+    pub fn dbus_message_get_path(*mut DBusMessage) -> *const c_char,
+    pub fn dbus_message_get_interface(*mut DBusMessage) -> *const c_char,
+    pub fn dbus_message_get_member(*mut DBusMessage) -> *const c_char,
+    pub fn dbus_message_set_no_reply(*mut DBusMessage, u32),
+    pub fn dbus_message_iter_init(*mut DBusMessage, *mut DBusMessageIter) -> u32,
+    pub fn dbus_message_iter_init_append(*mut DBusMessage, *mut DBusMessageIter),
+    pub fn dbus_message_iter_get_arg_type(*mut DBusMessageIter) -> c_int,
+    pub fn dbus_message_iter_get_basic(*mut DBusMessageIter, *mut c_void),
+    pub fn dbus_message_iter_next(*mut DBusMessageIter) -> u32,
+    pub fn dbus_message_iter_recurse(*mut DBusMessageIter, *mut DBusMessageIter),
+    pub fn dbus_message_iter_append_basic(*mut DBusMessageIter, c_int, *const c_void) -> u32,
+    pub fn dbus_message_iter_append_fixed_array(*mut DBusMessageIter, c_int, *const c_void, c_int) -> u32,
+    pub fn dbus_message_iter_open_container(*mut DBusMessageIter, c_int, *const c_char, *mut DBusMessageIter) -> u32,
+    pub fn dbus_message_iter_close_container(*mut DBusMessageIter, *mut DBusMessageIter) -> u32,
+    ...
+    ...
+);
+
+const ITEM_PATH: &str = "/StatusNotifierItem";
+const ITEM_INTERFACE: &str = "org.kde.StatusNotifierItem";
+const MENU_PATH: &str = "/MenuBar";
+const MENU_INTERFACE: &str = "com.canonical.dbusmenu";
+const WATCHER_NAME: &str = "org.kde.StatusNotifierWatcher";
+const PROPERTIES_INTERFACE: &str = "org.freedesktop.DBus.Properties";
+const INTROSPECTABLE_INTERFACE: &str = "org.freedesktop.DBus.Introspectable";
+
+/// A property value, the few D-Bus types the tray needs
+enum Value {
+    Str(String),
+    ObjectPath(String),
+    I32(i32),
+    U32(u32),
+    Bool(bool),
+    StrArray(Vec<String>),
+    /// a(iiay), ARGB32 in network byte order
+    Pixmaps(Vec<(i32, Vec<u8>)>),
+    /// (sa(iiay)ss): icon name, icon pixmaps, title, description
+    ToolTip(String),
+}
+
+impl Value {
+    fn signature(&self) -> &'static str {
+        match self {
+            Value::Str(_) => "s",
+            Value::ObjectPath(_) => "o",
+            Value::I32(_) => "i",
+            Value::U32(_) => "u",
+            Value::Bool(_) => "b",
+            Value::StrArray(_) => "as",
+            Value::Pixmaps(_) => "a(iiay)",
+            Value::ToolTip(_) => "(sa(iiay)ss)",
+        }
+    }
+}
+
+/// Appends to a message, one `DBusMessageIter` per open container
+pub(crate) struct Writer<'a> {
+    lib: &'a LibDbus,
+    iter: DBusMessageIter,
+}
+
+impl<'a> Writer<'a> {
+    pub(crate) unsafe fn new(lib: &'a LibDbus, message: *mut DBusMessage) -> Writer<'a> {
+        let mut iter = DBusMessageIter::new();
+        (lib.dbus_message_iter_init_append)(message, &mut iter);
+        Writer { lib, iter }
+    }
+
+    unsafe fn string(&mut self, type_: c_int, s: &str) {
+        // Interior nul would be a protocol error, drop everything after it
+        let s = CString::new(s.split('\0').next().unwrap()).unwrap();
+        let ptr = s.as_ptr();
+        (self.lib.dbus_message_iter_append_basic)(&mut self.iter, type_, &ptr as *const _ as _);
+    }
+
+    pub(crate) unsafe fn str(&mut self, s: &str) {
+        self.string(DBUS_TYPE_STRING, s);
+    }
+
+    unsafe fn i32(&mut self, value: i32) {
+        (self.lib.dbus_message_iter_append_basic)(
+            &mut self.iter,
+            DBUS_TYPE_INT32,
+            &value as *const _ as _,
+        );
+    }
+
+    unsafe fn u32(&mut self, value: u32) {
+        (self.lib.dbus_message_iter_append_basic)(
+            &mut self.iter,
+            DBUS_TYPE_UINT32,
+            &value as *const _ as _,
+        );
+    }
+
+    unsafe fn bool(&mut self, value: bool) {
+        let value = value as u32;
+        (self.lib.dbus_message_iter_append_basic)(
+            &mut self.iter,
+            DBUS_TYPE_BOOLEAN,
+            &value as *const _ as _,
+        );
+    }
+
+    unsafe fn bytes(&mut self, bytes: &[u8]) {
+        self.container(DBUS_TYPE_ARRAY, Some("y"), |w| {
+            let ptr = bytes.as_ptr();
+            (w.lib.dbus_message_iter_append_fixed_array)(
+                &mut w.iter,
+                DBUS_TYPE_BYTE,
+                &ptr as *const _ as _,
+                bytes.len() as c_int,
+            );
+        });
+    }
+
+    pub(crate) unsafe fn container(
+        &mut self,
+        type_: c_int,
+        signature: Option<&str>,
+        f: impl FnOnce(&mut Writer<'a>),
+    ) {
+        let signature = signature.map(|s| CString::new(s).unwrap());
+        let mut sub = Writer {
+            lib: self.lib,
+            iter: DBusMessageIter::new(),
+        };
+        (self.lib.dbus_message_iter_open_container)(
+            &mut self.iter,
+            type_,
+            signature.as_ref().map_or(std::ptr::null(), |s| s.as_ptr()),
+            &mut sub.iter,
+        );
+        f(&mut sub);
+        (self.lib.dbus_message_iter_close_container)(&mut self.iter, &mut sub.iter);
+    }
+
+    unsafe fn pixmaps(&mut self, pixmaps: &[(i32, Vec<u8>)]) {
+        self.container(DBUS_TYPE_ARRAY, Some("(iiay)"), |w| {
+            for (size, argb) in pixmaps {
+                w.container(DBUS_TYPE_STRUCT, None, |w| {
+                    w.i32(*size);
+                    w.i32(*size);
+                    w.bytes(argb);
+                });
+            }
+        });
+    }
+
+    unsafe fn value(&mut self, value: &Value) {
+        match value {
+            Value::Str(s) => self.str(s),
+            Value::ObjectPath(s) => self.string(DBUS_TYPE_OBJECT_PATH, s),
+            Value::I32(v) => self.i32(*v),
+            Value::U32(v) => self.u32(*v),
+            Value::Bool(v) => self.bool(*v),
+            Value::StrArray(strings) => self.container(DBUS_TYPE_ARRAY, Some("s"), |w| {
+                for s in strings {
+                    w.str(s);
+                }
+            }),
+            Value::Pixmaps(pixmaps) => self.pixmaps(pixmaps),
+            Value::ToolTip(title) => self.container(DBUS_TYPE_STRUCT, None, |w| {
+                w.str("");
+                w.pixmaps(&[]);
+                w.str(title);
+                w.str("");
+            }),
+        }
+    }
+
+    unsafe fn variant(&mut self, value: &Value) {
+        self.container(DBUS_TYPE_VARIANT, Some(value.signature()), |w| {
+            w.value(value)
+        });
+    }
+
+    /// a{sv}
+    unsafe fn properties(&mut self, properties: &[(&str, Value)]) {
+        self.container(DBUS_TYPE_ARRAY, Some("{sv}"), |w| {
+            for (name, value) in properties {
+                w.container(DBUS_TYPE_DICT_ENTRY, None, |w| {
+                    w.str(name);
+                    w.variant(value);
+                });
+            }
+        });
+    }
+}
+
+/// Reads the arguments of a message
+pub(crate) struct Reader<'a> {
+    lib: &'a LibDbus,
+    iter: DBusMessageIter,
+    has_next: bool,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) unsafe fn new(lib: &'a LibDbus, message: *mut DBusMessage) -> Reader<'a> {
+        let mut iter = DBusMessageIter::new();
+        let has_next = (lib.dbus_message_iter_init)(message, &mut iter) != 0;
+        Reader {
+            lib,
+            iter,
+            has_next,
+        }
+    }
+
+    pub(crate) unsafe fn arg_type(&mut self) -> c_int {
+        if self.has_next {
+            (self.lib.dbus_message_iter_get_arg_type)(&mut self.iter)
+        } else {
+            0
+        }
+    }
+
+    unsafe fn advance(&mut self) {
+        self.has_next = (self.lib.dbus_message_iter_next)(&mut self.iter) != 0;
+    }
+
+    pub(crate) unsafe fn str(&mut self) -> Option<String> {
+        if self.arg_type() != DBUS_TYPE_STRING {
+            return None;
+        }
+        let mut ptr: *const c_char = std::ptr::null();
+        (self.lib.dbus_message_iter_get_basic)(&mut self.iter, &mut ptr as *mut _ as _);
+        self.advance();
+        Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+
+    unsafe fn i32(&mut self) -> Option<i32> {
+        if self.arg_type() != DBUS_TYPE_INT32 {
+            return None;
+        }
+        let mut value = 0i32;
+        (self.lib.dbus_message_iter_get_basic)(&mut self.iter, &mut value as *mut _ as _);
+        self.advance();
+        Some(value)
+    }
+
+    pub(crate) unsafe fn u32(&mut self) -> Option<u32> {
+        if self.arg_type() != DBUS_TYPE_UINT32 {
+            return None;
+        }
+        let mut value = 0u32;
+        (self.lib.dbus_message_iter_get_basic)(&mut self.iter, &mut value as *mut _ as _);
+        self.advance();
+        Some(value)
+    }
+
+    pub(crate) unsafe fn f64(&mut self) -> Option<f64> {
+        if self.arg_type() != DBUS_TYPE_DOUBLE {
+            return None;
+        }
+        let mut value = 0f64;
+        (self.lib.dbus_message_iter_get_basic)(&mut self.iter, &mut value as *mut _ as _);
+        self.advance();
+        Some(value)
+    }
+
+    pub(crate) unsafe fn object_path(&mut self) -> Option<String> {
+        if self.arg_type() != DBUS_TYPE_OBJECT_PATH {
+            return None;
+        }
+        let mut ptr: *const c_char = std::ptr::null();
+        (self.lib.dbus_message_iter_get_basic)(&mut self.iter, &mut ptr as *mut _ as _);
+        self.advance();
+        Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+    }
+
+    /// Reader over the contents of the array, struct or variant at the cursor
+    pub(crate) unsafe fn recurse(&mut self) -> Reader<'a> {
+        let mut sub = Reader {
+            lib: self.lib,
+            iter: DBusMessageIter::new(),
+            has_next: false,
+        };
+        if self.has_next {
+            (self.lib.dbus_message_iter_recurse)(&mut self.iter, &mut sub.iter);
+            sub.has_next = (self.lib.dbus_message_iter_get_arg_type)(&mut sub.iter) != 0;
+            self.advance();
+        }
+        sub
+    }
+}
+
+pub(crate) unsafe fn c_str(ptr: *const c_char) -> &'static str {
+    if ptr.is_null() {
+        ""
+    } else {
+        CStr::from_ptr(ptr).to_str().unwrap_or("")
+    }
+}
+
+fn argb_pixmap(size: i32, rgba: &[u8]) -> (i32, Vec<u8>) {
+    let argb = rgba
+        .chunks_exact(4)
+        .flat_map(|p| [p[3], p[0], p[1], p[2]])
+        .collect();
+    (size, argb)
+}
+
+pub struct LinuxTray {
+    lib: LibDbus,
+    connection: *mut DBusConnection,
+    name: CString,
+    tray: Tray,
+    icon: Vec<(i32, Vec<u8>)>,
+    id: String,
+    // dbusmenu layout revision, bumped on every menu change
+    revision: u32,
+}
+
+impl LinuxTray {
+    pub fn new(tray: Tray) -> Option<LinuxTray> {
+        let lib = match LibDbus::try_load() {
+            Ok(lib) => lib,
+            Err(_) => {
+                crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                    "libdbus-1 not found, the tray icon is not shown".to_string(),
+                ));
+                return None;
+            }
+        };
+
+        unsafe {
+            let mut error: DBusError = std::mem::zeroed();
+            (lib.dbus_error_init)(&mut error);
+            // Private, so closing it on hide doesn't pull the bus from under anyone else
+            let connection = (lib.dbus_bus_get_private)(DBUS_BUS_SESSION, &mut error);
+            if (lib.dbus_error_is_set)(&error) != 0 || connection.is_null() {
+                crate::error::report_error(crate::error::PlatformError::OsError(format!(
+                    "Can't connect to the D-Bus session bus, the tray icon is not shown: {}",
+                    c_str(error.message)
+                )));
+                (lib.dbus_error_free)(&mut error);
+                return None;
+            }
+            (lib.dbus_connection_set_exit_on_disconnect)(connection, 0);
+
+            let name = CString::new(format!(
+                "org.kde.StatusNotifierItem-{}-1",
+                std::process::id()
+            ))
+            .unwrap();
+            (lib.dbus_bus_request_name)(
+                connection,
+                name.as_ptr(),
+                DBUS_NAME_FLAG_DO_NOT_QUEUE,
+                &mut error,
+            );
+            (lib.dbus_error_free)(&mut error);
+
+            // Register again whenever a panel (re)starts
+            let rule = CString::new(format!(
+                "type='signal',interface='org.freedesktop.DBus',member='NameOwnerChanged',arg0='{}'",
+                WATCHER_NAME
+            ))
+            .unwrap();
+            (lib.dbus_bus_add_match)(connection, rule.as_ptr(), &mut error);
+            (lib.dbus_error_free)(&mut error);
+
+            let id = std::env::current_exe()
+                .ok()
+                .and_then(|exe| Some(exe.file_stem()?.to_string_lossy().into_owned()))
+                .unwrap_or_else(|| "miniquad".to_string());
+            let mut linux_tray = LinuxTray {
+                lib,
+                connection,
+                name,
+                icon: vec![],
+                tray: Tray::default(),
+                id,
+                revision: 0,
+            };
+            linux_tray.set(tray);
+            linux_tray.register();
+            Some(linux_tray)
+        }
+    }
+
+    pub fn set(&mut self, tray: Tray) {
+        self.icon = vec![
+            argb_pixmap(16, &tray.icon.small),
+            argb_pixmap(32, &tray.icon.medium),
+            argb_pixmap(64, &tray.icon.big),
+        ];
+        let menu_changed = tray.menu != self.tray.menu;
+        self.tray = tray;
+        if menu_changed {
+            self.revision += 1;
+        }
+
+        unsafe {
+            for signal in ["NewIcon", "NewTitle", "NewToolTip"] {
+                self.send(self.signal(ITEM_PATH, ITEM_INTERFACE, signal));
+            }
+            if menu_changed {
+                let message = self.signal(MENU_PATH, MENU_INTERFACE, "LayoutUpdated");
+                let mut w = Writer::new(&self.lib, message);
+                w.u32(self.revision);
+                w.i32(0);
+                self.send(message);
+            }
+            (self.lib.dbus_connection_flush)(self.connection);
+        }
+    }
+
+    /// Answer the calls of the panel, delivering clicks to `event_handler`
+    pub fn poll(&mut self, event_handler: &mut dyn EventHandler) {
+        unsafe {
+            (self.lib.dbus_connection_read_write)(self.connection, 0);
+            loop {
+                let message = (self.lib.dbus_connection_pop_message)(self.connection);
+                if message.is_null() {
+                    break;
+                }
+                let event = self.handle_message(message);
+                (self.lib.dbus_message_unref)(message);
+                if let Some(event) = event {
+                    event_handler.tray_event(event);
+                }
+            }
+            (self.lib.dbus_connection_flush)(self.connection);
+        }
+    }
+
+    unsafe fn register(&mut self) {
+        let watcher = CString::new(WATCHER_NAME).unwrap();
+        let path = CString::new("/StatusNotifierWatcher").unwrap();
+        let method = CString::new("RegisterStatusNotifierItem").unwrap();
+        let message = (self.lib.dbus_message_new_method_call)(
+            watcher.as_ptr(),
+            path.as_ptr(),
+            watcher.as_ptr(),
+            method.as_ptr(),
+        );
+        let mut w = Writer::new(&self.lib, message);
+        w.str(self.name.to_str().unwrap());
+        // Without a watcher the error reply is just dropped in `poll`
+        self.send(message);
+        (self.lib.dbus_connection_flush)(self.connection);
+    }
+
+    unsafe fn signal(&self, path: &str, interface: &str, name: &str) -> *mut DBusMessage {
+        let path = CString::new(path).unwrap();
+        let interface = CString::new(interface).unwrap();
+        let name = CString::new(name).unwrap();
+        (self.lib.dbus_message_new_signal)(path.as_ptr(), interface.as_ptr(), name.as_ptr())
+    }
+
+    unsafe fn send(&self, message: *mut DBusMessage) {
+        (self.lib.dbus_connection_send)(self.connection, message, std::ptr::null_mut());
+        (self.lib.dbus_message_unref)(message);
+    }
+
+    unsafe fn handle_message(&mut self, message: *mut DBusMessage) -> Option<TrayEvent> {
+        let message_type = (self.lib.dbus_message_get_type)(message);
+        let interface = c_str((self.lib.dbus_message_get_interface)(message));
+        let member = c_str((self.lib.dbus_message_get_member)(message));
+
+        if message_type == DBUS_MESSAGE_TYPE_SIGNAL {
+            if member == "NameOwnerChanged" {
+                let mut args = Reader::new(&self.lib, message);
+                let (_name, _old_owner) = (args.str(), args.str());
+                if args.str().is_some_and(|new_owner| !new_owner.is_empty()) {
+                    self.register();
+                }
+            }
+            return None;
+        }
+        // Method returns and errors, e.g. from RegisterStatusNotifierItem
+        if message_type != DBUS_MESSAGE_TYPE_METHOD_CALL {
+            return None;
+        }
+
+        let path = c_str((self.lib.dbus_message_get_path)(message));
+        let reply = (self.lib.dbus_message_new_method_return)(message);
+        let mut args = Reader::new(&self.lib, message);
+        let mut w = Writer::new(&self.lib, reply);
+        let mut event = None;
+
+        match (path, interface, member) {
+            (_, PROPERTIES_INTERFACE, "Get") => {
+                let _interface = args.str();
+                let name = args.str().unwrap_or_default();
+                let properties = self.properties(path);
+                match properties.iter().find(|(property, _)| *property == name) {
+                    Some((_, value)) => w.variant(value),
+                    None => return self.reply_error(message, reply, "UnknownProperty", &name),
+                }
+            }
+            (_, PROPERTIES_INTERFACE, "GetAll") => w.properties(&self.properties(path)),
+            (_, INTROSPECTABLE_INTERFACE, "Introspect") => w.str(&introspection(path)),
+
+            (ITEM_PATH, ITEM_INTERFACE, "Activate") => event = Some(TrayEvent::Click),
+            // The panel shows the menu from the Menu property itself
+            (ITEM_PATH, ITEM_INTERFACE, "SecondaryActivate" | "ContextMenu" | "Scroll") => {}
+
+            (MENU_PATH, MENU_INTERFACE, "GetLayout") => {
+                let parent = args.i32().unwrap_or(0);
+                w.u32(self.revision);
+                self.menu_layout(&mut w, parent);
+            }
+            (MENU_PATH, MENU_INTERFACE, "GetGroupProperties") => {
+                let mut ids_reader = args.recurse();
+                let mut ids = vec![];
+                while let Some(id) = ids_reader.i32() {
+                    ids.push(id);
+                }
+                if ids.is_empty() {
+                    ids = (0..=self.tray.menu.len() as i32).collect();
+                }
+                w.container(DBUS_TYPE_ARRAY, Some("(ia{sv})"), |w| {
+                    for id in ids {
+                        w.container(DBUS_TYPE_STRUCT, None, |w| {
+                            w.i32(id);
+                            w.properties(&self.menu_item_properties(id));
+                        });
+                    }
+                });
+            }
+            (MENU_PATH, MENU_INTERFACE, "GetProperty") => {
+                let id = args.i32().unwrap_or(0);
+                let name = args.str().unwrap_or_default();
+                let properties = self.menu_item_properties(id);
+                match properties.iter().find(|(property, _)| *property == name) {
+                    Some((_, value)) => w.variant(value),
+                    None => return self.reply_error(message, reply, "UnknownProperty", &name),
+                }
+            }
+            (MENU_PATH, MENU_INTERFACE, "Event") => {
+                let id = args.i32().unwrap_or(0);
+                if args.str().as_deref() == Some("clicked") {
+                    event = self.menu_item_event(id);
+                }
+            }
+            (MENU_PATH, MENU_INTERFACE, "EventGroup") => {
+                let mut events = args.recurse();
+                while events.arg_type() == DBUS_TYPE_STRUCT {
+                    let mut entry = events.recurse();
+                    let id = entry.i32().unwrap_or(0);
+                    if entry.str().as_deref() == Some("clicked") {
+                        event = self.menu_item_event(id).or(event);
+                    }
+                }
+                // id errors
+                w.container(DBUS_TYPE_ARRAY, Some("i"), |_| {});
+            }
+            (MENU_PATH, MENU_INTERFACE, "AboutToShow") => w.bool(false),
+            (MENU_PATH, MENU_INTERFACE, "AboutToShowGroup") => {
+                // updates needed, id errors
+                w.container(DBUS_TYPE_ARRAY, Some("i"), |_| {});
+                w.container(DBUS_TYPE_ARRAY, Some("i"), |_| {});
+            }
+            _ => return self.reply_error(message, reply, "UnknownMethod", member),
+        }
+
+        self.send(reply);
+        event
+    }
+
+    unsafe fn reply_error(
+        &self,
+        message: *mut DBusMessage,
+        reply: *mut DBusMessage,
+        error: &str,
+        description: &str,
+    ) -> Option<TrayEvent> {
+        (self.lib.dbus_message_unref)(reply);
+        let name = CString::new(format!("org.freedesktop.DBus.Error.{}", error)).unwrap();
+        let description = CString::new(format!("Unknown: {}", description)).unwrap_or_default();
+        let error = (self.lib.dbus_message_new_error)(message, name.as_ptr(), description.as_ptr());
+        self.send(error);
+        None
+    }
+
+    fn properties(&self, path: &str) -> Vec<(&'static str, Value)> {
+        match path {
+            ITEM_PATH => vec![
+                ("Category", Value::Str("ApplicationStatus".to_string())),
+                ("Id", Value::Str(self.id.clone())),
+                ("Title", Value::Str(self.tray.tooltip.clone())),
+                ("Status", Value::Str("Active".to_string())),
+                ("WindowId", Value::I32(0)),
+                ("IconName", Value::Str(String::new())),
+                ("IconPixmap", Value::Pixmaps(self.icon.clone())),
+                ("ToolTip", Value::ToolTip(self.tray.tooltip.clone())),
+                ("ItemIsMenu", Value::Bool(false)),
+                ("Menu", Value::ObjectPath(MENU_PATH.to_string())),
+            ],
+            MENU_PATH => vec![
+                ("Version", Value::U32(3)),
+                ("TextDirection", Value::Str("ltr".to_string())),
+                ("Status", Value::Str("normal".to_string())),
+                ("IconThemePath", Value::StrArray(vec![])),
+            ],
+            _ => vec![],
+        }
+    }
+
+    /// dbusmenu id 0 is the root, the menu items are 1..=len
+    fn menu_item_properties(&self, id: i32) -> Vec<(&'static str, Value)> {
+        if id == 0 {
+            return vec![("children-display", Value::Str("submenu".to_string()))];
+        }
+        match self.tray.menu.get(id as usize - 1) {
+            Some(TrayMenuItem::Item { label, enabled, .. }) => vec![
+                ("label", Value::Str(label.clone())),
+                ("enabled", Value::Bool(*enabled)),
+                ("visible", Value::Bool(true)),
+            ],
+            Some(TrayMenuItem::Separator) => vec![
+                ("type", Value::Str("separator".to_string())),
+                ("visible", Value::Bool(true)),
+            ],
+            None => vec![],
+        }
+    }
+
+    fn menu_item_event(&self, id: i32) -> Option<TrayEvent> {
+        match self.tray.menu.get((id as usize).checked_sub(1)?)? {
+            TrayMenuItem::Item {
+                id, enabled: true, ..
+            } => Some(TrayEvent::MenuItem(*id)),
+            _ => None,
+        }
+    }
+
+    /// (ia{sv}av), the menu is flat so only the root has children
+    unsafe fn menu_layout(&self, w: &mut Writer, id: i32) {
+        w.container(DBUS_TYPE_STRUCT, None, |w| {
+            w.i32(id);
+            w.properties(&self.menu_item_properties(id));
+            w.container(DBUS_TYPE_ARRAY, Some("v"), |w| {
+                if id == 0 {
+                    for child in 1..=self.tray.menu.len() as i32 {
+                        w.container(DBUS_TYPE_VARIANT, Some("(ia{sv}av)"), |w| {
+                            self.menu_layout(w, child);
+                        });
+                    }
+                }
+            });
+        });
+    }
+}
+
+impl Drop for LinuxTray {
+    fn drop(&mut self) {
+        // Losing the name is what removes the icon from the panel
+        unsafe {
+            (self.lib.dbus_connection_close)(self.connection);
+            (self.lib.dbus_connection_unref)(self.connection);
+        }
+    }
+}
+
+/// Show, update or remove (`None`) the tray icon in `slot`
+pub fn set_tray(slot: &mut Option<LinuxTray>, tray: Option<Box<Tray>>) {
+    match (slot.as_mut(), tray) {
+        (_, None) => *slot = None,
+        (Some(linux_tray), Some(tray)) => linux_tray.set(*tray),
+        (None, Some(tray)) => *slot = LinuxTray::new(*tray),
+    }
+}
+
+fn introspection(path: &str) -> String {
+    let interface = match path {
+        ITEM_PATH => {
+            r#"<interface name="org.kde.StatusNotifierItem">
+    <property name="Category" type="s" access="read"/>
+    <property name="Id" type="s" access="read"/>
+    <property name="Title" type="s" access="read"/>
+    <property name="Status" type="s" access="read"/>
+    <property name="WindowId" type="i" access="read"/>
+    <property name="IconName" type="s" access="read"/>
+    <property name="IconPixmap" type="a(iiay)" access="read"/>
+    <property name="ToolTip" type="(sa(iiay)ss)" access="read"/>
+    <property name="ItemIsMenu" type="b" access="read"/>
+    <property name="Menu" type="o" access="read"/>
+    <method name="Activate"><arg name="x" type="i" direction="in"/><arg name="y" type="i" direction="in"/></method>
+    <method name="SecondaryActivate"><arg name="x" type="i" direction="in"/><arg name="y" type="i" direction="in"/></method>
+    <method name="ContextMenu"><arg name="x" type="i" direction="in"/><arg name="y" type="i" direction="in"/></method>
+    <method name="Scroll"><arg name="delta" type="i" direction="in"/><arg name="orientation" type="s" direction="in"/></method>
+    <signal name="NewTitle"/>
+    <signal name="NewIcon"/>
+    <signal name="NewToolTip"/>
+  </interface>"#
+        }
+        MENU_PATH => {
+            r#"<interface name="com.canonical.dbusmenu">
+    <property name="Version" type="u" access="read"/>
+    <property name="TextDirection" type="s" access="read"/>
+    <property name="Status" type="s" access="read"/>
+    <property name="IconThemePath" type="as" access="read"/>
+    <method name="GetLayout"><arg type="i" direction="in"/><arg type="i" direction="in"/><arg type="as" direction="in"/><arg type="u" direction="out"/><arg type="(ia{sv}av)" direction="out"/></method>
+    <method name="GetGroupProperties"><arg type="ai" direction="in"/><arg type="as" direction="in"/><arg type="a(ia{sv})" direction="out"/></method>
+    <method name="GetProperty"><arg type="i" direction="in"/><arg type="s" direction="in"/><arg type="v" direction="out"/></method>
+    <method name="Event"><arg type="i" direction="in"/><arg type="s" direction="in"/><arg type="v" direction="in"/><arg type="u" direction="in"/></method>
+    <method name="EventGroup"><arg type="a(isvu)" direction="in"/><arg type="ai" direction="out"/></method>
+    <method name="AboutToShow"><arg type="i" direction="in"/><arg type="b" direction="out"/></method>
+    <method name="AboutToShowGroup"><arg type="ai" direction="in"/><arg type="ai" direction="out"/><arg type="ai" direction="out"/></method>
+    <signal name="LayoutUpdated"><arg type="u"/><arg type="i"/></signal>
+  </interface>"#
+        }
+        _ => "",
+    };
+    format!(
+        r#"<!DOCTYPE node PUBLIC "-//freedesktop//DTD D-BUS Object Introspection 1.0//EN" "http://www.freedesktop.org/standards/dbus/1.0/introspect.dtd">
+<node>
+  {}
+  <interface name="org.freedesktop.DBus.Properties">
+    <method name="Get"><arg type="s" direction="in"/><arg type="s" direction="in"/><arg type="v" direction="out"/></method>
+    <method name="GetAll"><arg type="s" direction="in"/><arg type="a{{sv}}" direction="out"/></method>
+  </interface>
+</node>"#,
+        interface
+    )
+}
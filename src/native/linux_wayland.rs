@@ -43,6 +43,8 @@ struct WaylandPayload {
     xdg_wm_base: *mut extensions::xdg_shell::xdg_wm_base,
     surface: *mut wl_surface,
     viewporter: *mut extensions::viewporter::wp_viewporter,
+    presentation: *mut extensions::presentation_time::wp_presentation,
+    presentation_clock: Option<libc::clockid_t>,
     shm: *mut wl_shm,
     seat: *mut wl_seat,
     data_device_manager: *mut wl_data_device_manager,
@@ -64,6 +66,8 @@ struct WaylandPayload {
     keyboard_context: KeyboardContext,
     drag_n_drop: drag_n_drop::WaylandDnD,
     update_requested: bool,
+    /// The xdg_toplevel is in the resizing state
+    live_resize: bool,
 }
 
 impl WaylandPayload {
@@ -179,7 +183,9 @@ impl WaylandPayload {
             );
             assert!(!self.pointer_context.cursor_shape_device.is_null());
         } else {
-            eprintln!("Wayland compositor does not support cursor shape");
+            crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                "Wayland compositor does not support cursor shape".to_string(),
+            ));
         }
     }
     unsafe fn set_fullscreen(&mut self, full: bool) {
@@ -409,7 +415,9 @@ impl PointerContext {
                     );
                     assert!(!self.locked_pointer.is_null());
                 } else {
-                    eprintln!("Wayland compositor does not support locked pointer");
+                    crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                        "Wayland compositor does not support locked pointer".to_string(),
+                    ));
                 }
             }
 
@@ -431,7 +439,9 @@ impl PointerContext {
                         data,
                     );
                 } else {
-                    eprintln!("Wayland compositor does not support relative pointer");
+                    crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                        "Wayland compositor does not support relative pointer".to_string(),
+                    ));
                 }
             }
         } else {
@@ -540,8 +550,10 @@ enum WaylandEvent {
     Touch(crate::TouchPhase, u64, f32, f32),
     FilesDropped(String),
     Resize(f32, f32),
+    LiveResize(bool),
     WindowMinimized,
     WindowRestored,
+    ClipboardChanged,
 }
 
 unsafe extern "C" fn keyboard_handle_keymap(
@@ -637,7 +649,9 @@ unsafe extern "C" fn keyboard_handle_key(
             );
         }
         _ => {
-            eprintln!("Unknown wl_keyboard::key_state");
+            crate::error::report_error(crate::error::PlatformError::OsError(
+                "Unknown wl_keyboard::key_state".to_string(),
+            ));
         }
     };
 }
@@ -671,6 +685,10 @@ unsafe extern "C" fn keyboard_handle_repeat_info(
     display.keyboard_context.repeat_info = if rate == 0 {
         RepeatInfo::NoRepeat
     } else {
+        crate::event::set_key_repeat_settings(crate::event::KeyRepeatSettings {
+            delay: delay as f64 / 1000.,
+            rate: rate as f64,
+        });
         RepeatInfo::Repeat {
             delay: Duration::from_millis(delay as u64),
             gap: Duration::from_micros(1_000_000 / rate as u64),
@@ -737,7 +755,11 @@ unsafe extern "C" fn pointer_handle_button(
             272 => MouseButton::Left,
             273 => MouseButton::Right,
             274 => MouseButton::Middle,
-            _ => MouseButton::Unknown,
+            // BTN_SIDE and BTN_BACK
+            275 | 278 => MouseButton::X1,
+            // BTN_EXTRA and BTN_FORWARD
+            276 | 277 => MouseButton::X2,
+            _ => MouseButton::Other(button as u16),
         };
         display
             .events
@@ -873,6 +895,60 @@ unsafe extern "C" fn touch_handle_cancel(data: *mut std::ffi::c_void, _touch: *m
     }
 }
 
+static mut PRESENTATION_LISTENER: extensions::presentation_time::wp_presentation_listener =
+    extensions::presentation_time::wp_presentation_listener::dummy();
+static mut PRESENTATION_FEEDBACK_LISTENER:
+    extensions::presentation_time::wp_presentation_feedback_listener =
+    extensions::presentation_time::wp_presentation_feedback_listener::dummy();
+
+unsafe extern "C" fn presentation_handle_clock_id(
+    data: *mut ::core::ffi::c_void,
+    _presentation: *mut extensions::presentation_time::wp_presentation,
+    clk_id: core::ffi::c_uint,
+) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    display.presentation_clock = Some(clk_id as _);
+}
+
+unsafe extern "C" fn presentation_feedback_handle_presented(
+    data: *mut ::core::ffi::c_void,
+    feedback: *mut extensions::presentation_time::wp_presentation_feedback,
+    tv_sec_hi: core::ffi::c_uint,
+    tv_sec_lo: core::ffi::c_uint,
+    tv_nsec: core::ffi::c_uint,
+    refresh: core::ffi::c_uint,
+    _seq_hi: core::ffi::c_uint,
+    _seq_lo: core::ffi::c_uint,
+    _flags: core::ffi::c_uint,
+) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    (display.client.wl_proxy_destroy)(feedback as _);
+
+    let Some(clock) = display.presentation_clock else {
+        return;
+    };
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    libc::clock_gettime(clock, &mut now);
+    let presented = ((tv_sec_hi as u64) << 32 | tv_sec_lo as u64) as f64 + tv_nsec as f64 / 1e9;
+    let clock_now = now.tv_sec as f64 + now.tv_nsec as f64 / 1e9;
+    // From the compositor's clock to miniquad's
+    let time = crate::event::monotonic_time() - (clock_now - presented);
+    // 0 with a variable refresh rate
+    let refresh = (refresh != 0).then(|| refresh as f64 / 1e9);
+    crate::frame_stats::record_vblank(time, refresh);
+}
+
+unsafe extern "C" fn presentation_feedback_handle_discarded(
+    data: *mut ::core::ffi::c_void,
+    feedback: *mut extensions::presentation_time::wp_presentation_feedback,
+) {
+    let display: &mut WaylandPayload = &mut *(data as *mut _);
+    (display.client.wl_proxy_destroy)(feedback as _);
+}
+
 unsafe extern "C" fn registry_add_object(
     data: *mut std::ffi::c_void,
     registry: *mut wl_registry,
@@ -955,6 +1031,20 @@ unsafe extern "C" fn registry_add_object(
                 1,
             ) as _;
         }
+        "wp_presentation" => {
+            display.presentation = display.client.wl_registry_bind(
+                registry,
+                name,
+                &extensions::presentation_time::wp_presentation_interface,
+                1,
+            ) as _;
+            PRESENTATION_LISTENER.clock_id = presentation_handle_clock_id;
+            (display.client.wl_proxy_add_listener)(
+                display.presentation as _,
+                &PRESENTATION_LISTENER as *const _ as _,
+                data,
+            );
+        }
         "wp_cursor_shape_manager_v1" => {
             display.pointer_context.cursor_shape_manager = display.client.wl_registry_bind(
                 registry,
@@ -1058,7 +1148,9 @@ where
 
         let wdisplay = (client.wl_display_connect)(std::ptr::null_mut());
         if wdisplay.is_null() {
-            eprintln!("Failed to connect to Wayland display.");
+            crate::error::report_error(crate::error::PlatformError::DisplayInitFailed(
+                "Failed to connect to Wayland display.".to_string(),
+            ));
             return None;
         }
 
@@ -1084,6 +1176,8 @@ where
             xdg_wm_base: std::ptr::null_mut(),
             surface: std::ptr::null_mut(),
             viewporter: std::ptr::null_mut(),
+            presentation: std::ptr::null_mut(),
+            presentation_clock: None,
             shm: std::ptr::null_mut(),
             seat: std::ptr::null_mut(),
             data_device_manager: std::ptr::null_mut(),
@@ -1103,6 +1197,7 @@ where
             keyboard_context: KeyboardContext::new(),
             drag_n_drop: Default::default(),
             update_requested: true,
+            live_resize: false,
         };
 
         let mut registry_listener = wl_registry_listener::dummy();
@@ -1127,6 +1222,11 @@ where
 
         display.init_data_device();
         display.init_pointer_context();
+        crate::native_display().lock().unwrap().window_handle =
+            Some(crate::WindowHandle::Wayland {
+                display: display.display as _,
+                surface: display.surface as _,
+            });
 
         let mut libegl = egl::LibEgl::try_load().ok()?;
         let (context, config, egl_display) = egl::create_egl_context(
@@ -1167,10 +1267,21 @@ where
         if (libegl.eglMakeCurrent)(egl_display, egl_surface, egl_surface, context) == 0 {
             panic!("eglMakeCurrent failed");
         }
+        crate::native_display().lock().unwrap().context_handle = Some(crate::ContextHandle::Egl {
+            display: egl_display as _,
+            config: config as _,
+            context: context as _,
+            surface: egl_surface as _,
+        });
 
-        if (libegl.eglSwapInterval)(egl_display, conf.platform.swap_interval.unwrap_or(1)) == 0 {
-            eprintln!("eglSwapInterval failed");
-        }
+        let swap_interval = egl::swap_interval(
+            &mut libegl,
+            egl_display,
+            conf.platform.swap_interval.unwrap_or(1),
+        );
+        crate::native_display().lock().unwrap().swap_interval =
+            crate::SwapInterval::from_gl(swap_interval);
+        let presenter = egl::Presenter::new(&mut libegl, egl_display);
 
         crate::native::gl::load_gl_funcs(|proc| {
             let name = std::ffi::CString::new(proc).unwrap();
@@ -1203,7 +1314,25 @@ where
         (display.client.wl_display_dispatch)(display.display);
         (display.client.wl_display_dispatch)(display.display);
 
+        // Without a buffer the surface is unmapped, taking the pointer lock and
+        // the keyboard focus with it
+        let (client, surface, wl_display) =
+            (display.client.clone(), display.surface, display.display);
+        crate::crash::set_restore(&conf.window_title, move || {
+            wl_request!(
+                client,
+                surface,
+                WL_SURFACE_ATTACH,
+                std::ptr::null_mut::<std::ffi::c_void>(),
+                0,
+                0
+            );
+            wl_request!(client, surface, WL_SURFACE_COMMIT);
+            (client.wl_display_flush)(wl_display);
+        });
+
         let mut event_handler = (f.take().unwrap())();
+        let mut tray = None;
 
         while !crate::native_display().try_lock().unwrap().quit_ordered {
             while let Ok(request) = rx.try_recv() {
@@ -1211,6 +1340,12 @@ where
                     Request::SetFullscreen(full) => {
                         display.set_fullscreen(full);
                     }
+                    Request::SetSwapInterval(interval) => {
+                        let applied =
+                            egl::swap_interval(&mut libegl, egl_display, interval.to_gl());
+                        crate::native_display().lock().unwrap().swap_interval =
+                            crate::SwapInterval::from_gl(applied);
+                    }
                     Request::ScheduleUpdate => display.update_requested = true,
                     Request::SetMouseCursor(icon) => {
                         display
@@ -1227,6 +1362,18 @@ where
                             show.then_some(crate::CursorIcon::Default),
                         );
                     }
+                    Request::SetMousePosition { .. } => {
+                        crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                            "Wayland does not allow moving the pointer".to_string(),
+                        ))
+                    }
+                    Request::SetWindowVisible(_) => {
+                        crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                            "Wayland does not allow hiding the window".to_string(),
+                        ))
+                    }
+                    Request::SetTray(new_tray) => super::linux_tray::set_tray(&mut tray, new_tray),
+                    Request::PickColor(_) => super::linux_portal::pick_color(),
                     // TODO: implement the other events
                     _ => (),
                 }
@@ -1236,6 +1383,9 @@ where
             // new events but continue without blocking
             let blocking = conf.platform.blocking_event_loop && !display.update_requested;
             display.poll_new_event(blocking);
+            if let Some(tray) = &mut tray {
+                tray.poll(&mut *event_handler);
+            }
 
             for event in display.events.drain(..) {
                 match event {
@@ -1246,7 +1396,7 @@ where
                         event_handler.key_up_event(keycode, keymods)
                     }
                     WaylandEvent::Char(chr, keymods, repeat) => {
-                        event_handler.char_event(chr, keymods, repeat)
+                        crate::event::dispatch_char(&mut *event_handler, chr, keymods, repeat)
                     }
                     WaylandEvent::PointerMotion(x, y) => {
                         event_handler.mouse_motion_event(x, y);
@@ -1269,8 +1419,19 @@ where
                     WaylandEvent::Resize(width, height) => {
                         event_handler.resize_event(width, height)
                     }
-                    WaylandEvent::WindowMinimized => event_handler.window_minimized_event(),
-                    WaylandEvent::WindowRestored => event_handler.window_restored_event(),
+                    // The compositor does not tell the edge
+                    WaylandEvent::LiveResize(active) => {
+                        crate::event::dispatch_live_resize(&mut *event_handler, active, None)
+                    }
+                    WaylandEvent::WindowMinimized => {
+                        crate::accessibility::focus_changed(false);
+                        event_handler.window_minimized_event();
+                    }
+                    WaylandEvent::WindowRestored => {
+                        crate::accessibility::focus_changed(true);
+                        event_handler.window_restored_event();
+                    }
+                    WaylandEvent::ClipboardChanged => event_handler.clipboard_changed_event(),
                     WaylandEvent::FilesDropped(filenames) => {
                         let mut d = crate::native_display().try_lock().unwrap();
                         d.dropped_files = Default::default();
@@ -1287,11 +1448,12 @@ where
                 }
             }
 
+            super::linux_session::poll_sigterm();
             {
                 let d = crate::native_display().try_lock().unwrap();
                 if d.quit_requested && !d.quit_ordered {
                     drop(d);
-                    event_handler.quit_requested_event();
+                    crate::event::dispatch_quit_requested(&mut *event_handler);
                     let mut d = crate::native_display().try_lock().unwrap();
                     if d.quit_requested {
                         d.quit_ordered = true
@@ -1301,11 +1463,42 @@ where
 
             if !conf.platform.blocking_event_loop || display.update_requested {
                 display.update_requested = false;
+                crate::frame_stats::begin_frame();
+                crate::event::dispatch_errors(&mut *event_handler);
+                crate::accessibility::dispatch_actions(&mut *event_handler);
+                crate::colors::dispatch_picked(&mut *event_handler);
+                crate::message_box::dispatch_answers(&mut *event_handler);
                 event_handler.update();
+                presenter.begin_frame(&mut libegl, egl_display, egl_surface);
                 event_handler.draw();
-                (libegl.eglSwapBuffers)(egl_display, egl_surface);
+                crate::frame_stats::begin_present();
+                if !display.presentation.is_null() {
+                    // Reports when the frame committed by the swap is shown
+                    let feedback = wl_request_constructor!(
+                        display.client,
+                        display.presentation,
+                        extensions::presentation_time::wp_presentation::feedback,
+                        &extensions::presentation_time::wp_presentation_feedback_interface,
+                        display.surface
+                    );
+                    PRESENTATION_FEEDBACK_LISTENER.presented =
+                        presentation_feedback_handle_presented;
+                    PRESENTATION_FEEDBACK_LISTENER.discarded =
+                        presentation_feedback_handle_discarded;
+                    (display.client.wl_proxy_add_listener)(
+                        feedback as _,
+                        &PRESENTATION_FEEDBACK_LISTENER as *const _ as _,
+                        &mut display as *mut _ as _,
+                    );
+                }
+                presenter.swap_buffers(&mut libegl, egl_display, egl_surface);
+                crate::frame_pacing::sync_after_swap();
+                crate::frame_stats::end_frame();
+                crate::frame_pacing::wait();
             }
         }
+        crate::accessibility::remove_adapter();
+        crate::crash::clear_restore();
     }
 
     Some(())
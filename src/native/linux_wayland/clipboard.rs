@@ -12,7 +12,13 @@ pub(super) unsafe extern "C" fn data_device_handle_selection(
 ) {
     let display: &mut WaylandPayload = &mut *(data as *mut _);
     assert_eq!(data_device, display.data_device);
-    CLIPBOARD.get_mut().unwrap().data_offer = (!data_offer.is_null()).then_some(data_offer);
+    let clipboard = CLIPBOARD.get_mut().unwrap();
+    clipboard.data_offer = (!data_offer.is_null()).then_some(data_offer);
+    // Our own data source is cancelled before someone else's selection arrives,
+    // while it's alive the selection is the one we set
+    if clipboard.data_source.is_none() {
+        display.events.push(WaylandEvent::ClipboardChanged);
+    }
 }
 
 use std::sync::OnceLock;
@@ -241,9 +241,33 @@ unsafe extern "C" fn xdg_toplevel_handle_configure(
     _toplevel: *mut extensions::xdg_shell::xdg_toplevel,
     width: i32,
     height: i32,
-    _states: *mut wl_array,
+    states: *mut wl_array,
 ) {
+    const XDG_TOPLEVEL_STATE_RESIZING: u32 = 3;
+
+    assert!(!data.is_null());
+    let states = if states.is_null() || (*states).data.is_null() {
+        &[][..]
+    } else {
+        std::slice::from_raw_parts((*states).data as *const u32, (*states).size / 4)
+    };
+    let resizing = states.contains(&XDG_TOPLEVEL_STATE_RESIZING);
+    let changed = {
+        let payload: &mut WaylandPayload = &mut *(data as *mut _);
+        std::mem::replace(&mut payload.live_resize, resizing) != resizing
+    };
+    // The resizes of a drag go between its start and its end
+    if changed && resizing {
+        (*(data as *mut WaylandPayload))
+            .events
+            .push(WaylandEvent::LiveResize(true));
+    }
     handle_configure(data, width, height);
+    if changed && !resizing {
+        (*(data as *mut WaylandPayload))
+            .events
+            .push(WaylandEvent::LiveResize(false));
+    }
 }
 
 unsafe extern "C" fn libdecor_frame_handle_configure(
@@ -295,7 +319,10 @@ unsafe extern "C" fn libdecor_handle_error(
     message: *const c_char,
 ) {
     let message = core::ffi::CStr::from_ptr(message).to_str().unwrap();
-    eprintln!("{}", message);
+    crate::error::report_error(crate::error::PlatformError::OsError(format!(
+        "libdecor: {}",
+        message
+    )));
 }
 static mut LIBDECOR_INTERFACE: libdecor_interface = libdecor_interface {
     error: libdecor_handle_error,
@@ -123,5 +123,27 @@ pub fn translate_cursor(icon: crate::CursorIcon) -> core::ffi::c_uint {
         crate::CursorIcon::NSResize => 27,
         crate::CursorIcon::NESWResize => 28,
         crate::CursorIcon::NWSEResize => 29,
+        crate::CursorIcon::ContextMenu => 2,
+        crate::CursorIcon::Progress => 5,
+        crate::CursorIcon::Cell => 7,
+        crate::CursorIcon::VerticalText => 10,
+        crate::CursorIcon::Alias => 11,
+        crate::CursorIcon::Copy => 12,
+        crate::CursorIcon::NoDrop => 14,
+        crate::CursorIcon::Grab => 16,
+        crate::CursorIcon::Grabbing => 17,
+        crate::CursorIcon::EResize => 18,
+        crate::CursorIcon::NResize => 19,
+        crate::CursorIcon::NEResize => 20,
+        crate::CursorIcon::NWResize => 21,
+        crate::CursorIcon::SResize => 22,
+        crate::CursorIcon::SEResize => 23,
+        crate::CursorIcon::SWResize => 24,
+        crate::CursorIcon::WResize => 25,
+        crate::CursorIcon::ColResize => 30,
+        crate::CursorIcon::RowResize => 31,
+        crate::CursorIcon::AllScroll => 32,
+        crate::CursorIcon::ZoomIn => 33,
+        crate::CursorIcon::ZoomOut => 34,
     }
 }
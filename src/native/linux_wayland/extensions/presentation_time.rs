@@ -0,0 +1,52 @@
+// presentation-time.xml
+
+use super::super::libwayland_client::{wl_interface, wl_message, wl_output};
+use crate::wayland_interface;
+
+#[rustfmt::skip]
+wayland_interface!(
+    wp_presentation_interface,
+    wp_presentation,
+    1,
+    [
+        (destroy, "", ()),
+        (feedback, "on", (wp_presentation_feedback_interface))
+    ],
+    [("clock_id", "u")]
+);
+
+wayland_interface!(
+    wp_presentation_feedback_interface,
+    wp_presentation_feedback,
+    1,
+    [],
+    [
+        ("sync_output", "o"),
+        ("presented", "uuuuuuu"),
+        ("discarded", "")
+    ]
+);
+
+crate::wl_listener!(
+    wp_presentation_listener,
+    wp_presentation,
+    wp_presentation_dummy,
+    fn clock_id(clk_id: core::ffi::c_uint),
+);
+
+crate::wl_listener!(
+    wp_presentation_feedback_listener,
+    wp_presentation_feedback,
+    wp_presentation_feedback_dummy,
+    fn sync_output(output: *mut wl_output),
+    fn presented(
+        tv_sec_hi: core::ffi::c_uint,
+        tv_sec_lo: core::ffi::c_uint,
+        tv_nsec: core::ffi::c_uint,
+        refresh: core::ffi::c_uint,
+        seq_hi: core::ffi::c_uint,
+        seq_lo: core::ffi::c_uint,
+        flags: core::ffi::c_uint,
+    ),
+    fn discarded(),
+);
@@ -7,6 +7,7 @@ mod keycodes;
 pub mod libx11;
 mod libx11_ex;
 mod x_cursor;
+mod xfixes;
 mod xi_input;
 
 use crate::{
@@ -42,11 +43,19 @@ impl std::error::Error for X11Error {}
 pub struct X11Display {
     libx11: LibX11,
     libxkbcommon: LibXkbCommon,
+    /// None if the locale has no Compose file, dead keys then type nothing
+    compose: Option<keycodes::Compose>,
     libxi: xi_input::LibXi,
+    libxfixes: Option<xfixes::LibXfixes>,
+    tray: Option<super::linux_tray::LinuxTray>,
     display: *mut Display,
     root: Window,
     window: Window,
     repeated_keycodes: [bool; 256],
+    cursor_grabbed: bool,
+    mouse_captured: bool,
+    /// `Kiosk::block_system_shortcuts`, the keyboard is grabbed while focused
+    keyboard_grab: bool,
     empty_cursor: libx11::Cursor,
     cursor_cache: HashMap<CursorIcon, libx11::Cursor>,
     update_requested: bool,
@@ -70,11 +79,12 @@ impl X11Display {
                     &mut keysym,
                     std::ptr::null_mut(),
                 );
-                let chr = keycodes::keysym_to_unicode(&mut self.libxkbcommon, keysym);
-                if chr > 0 {
-                    if let Some(chr) = char::from_u32(chr as u32) {
-                        event_handler.char_event(chr, mods, repeat);
-                    }
+                let text = match &mut self.compose {
+                    Some(compose) => compose.feed(&mut self.libxkbcommon, keysym),
+                    None => Some(keycodes::keysym_text(&mut self.libxkbcommon, keysym)),
+                };
+                if let Some(text) = text {
+                    crate::event::dispatch_text(event_handler, &text, mods, repeat);
                 }
                 event_handler.key_down_event(key, mods, repeat);
             }
@@ -131,24 +141,32 @@ impl X11Display {
                 event_handler.mouse_motion_event(x, y);
             }
             9 => {
+                // NotifyNormal only, the grab itself sends focus events
+                if self.keyboard_grab && event.xfocus.mode == 0 {
+                    // GrabModeAsync, CurrentTime. The window manager's shortcuts
+                    // go to the window as long as the grab holds.
+                    (self.libx11.XGrabKeyboard)(self.display, self.window, true as _, 1, 1, 0);
+                }
+                crate::accessibility::focus_changed(true);
                 event_handler.window_restored_event();
             }
             10 => {
+                crate::accessibility::focus_changed(false);
                 event_handler.window_minimized_event();
             }
             22 => {
                 let mut d = crate::native_display().try_lock().unwrap();
                 let left = event.xconfigure.x;
                 let top = event.xconfigure.y;
+                let width = event.xconfigure.width;
+                let height = event.xconfigure.height;
                 d.screen_position = (left as _, top as _);
-                if event.xconfigure.width != d.screen_width
-                    || event.xconfigure.height != d.screen_height
-                {
-                    let width = event.xconfigure.width;
-                    let height = event.xconfigure.height;
-                    d.screen_width = width;
-                    d.screen_height = height;
-                    drop(d);
+                let resized = width != d.screen_width || height != d.screen_height;
+                d.screen_width = width;
+                d.screen_height = height;
+                drop(d);
+                crate::accessibility::bounds_changed(left as _, top as _, width as _, height as _);
+                if resized {
                     event_handler.resize_event(width as _, height as _);
                 }
             }
@@ -231,18 +249,11 @@ impl X11Display {
                     event_handler.raw_mouse_motion(dx as f32, dy as f32);
                 }
             }
+            _ if self.is_clipboard_change(event) => event_handler.clipboard_changed_event(),
             _ => {}
         };
 
-        let d = crate::native_display().try_lock().unwrap();
-        if d.quit_requested && !d.quit_ordered {
-            drop(d);
-            event_handler.quit_requested_event();
-            let mut d = crate::native_display().try_lock().unwrap();
-            if d.quit_requested {
-                d.quit_ordered = true
-            }
-        }
+        resolve_quit(event_handler);
     }
 
     // TODO: right now it just exits early if fullscreen is false.
@@ -318,7 +329,11 @@ impl X11Display {
         }
     }
 
+    /// `new_width` and `new_height` are logical, like the size in `Conf`
     unsafe fn set_window_size(&mut self, window: Window, new_width: i32, new_height: i32) {
+        let dpi_scale = crate::native_display().lock().unwrap().dpi_scale;
+        let new_width = (new_width as f32 * dpi_scale) as i32;
+        let new_height = (new_height as f32 * dpi_scale) as i32;
         (self.libx11.XResizeWindow)(self.display, window, new_width, new_height);
         (self.libx11.XFlush)(self.display);
     }
@@ -338,11 +353,40 @@ impl X11Display {
         }
     }
 
+    /// Ungrab everything and unmap the window on a panic, see `crash`
+    fn set_crash_restore(&self, window_title: &str) {
+        let (display, window) = (self.display, self.window);
+        let ungrab_pointer = self.libx11.XUngrabPointer;
+        let ungrab_keyboard = self.libx11.XUngrabKeyboard;
+        let unmap_window = self.libx11.XUnmapWindow;
+        let flush = self.libx11.XFlush;
+        crate::crash::set_restore(window_title, move || unsafe {
+            ungrab_pointer(display, 0);
+            ungrab_keyboard(display, 0);
+            unmap_window(display, window);
+            flush(display);
+        });
+    }
+
     pub unsafe fn set_cursor_grab(&mut self, window: Window, grab: bool) {
+        self.cursor_grabbed = grab;
+        self.update_pointer_grab(window);
+    }
+
+    pub unsafe fn capture_mouse(&mut self, window: Window, capture: bool) {
+        self.mouse_captured = capture;
+        self.update_pointer_grab(window);
+    }
+
+    // Both the cursor grab and the mouse capture are an active pointer grab,
+    // the cursor grab also confines the pointer to the window.
+    // Drags need neither, X already grabs the pointer while a button is held.
+    unsafe fn update_pointer_grab(&mut self, window: Window) {
         (self.libx11.XUngrabPointer)(self.display, 0);
 
-        if grab {
-            (self.libx11.XGrabPointer)(
+        if self.cursor_grabbed || self.mouse_captured {
+            let confine_to = if self.cursor_grabbed { window } else { 0 };
+            let status = (self.libx11.XGrabPointer)(
                 self.display,
                 window,
                 true as _,
@@ -361,10 +405,17 @@ impl X11Display {
                     | KeymapStateMask) as libc::c_uint,
                 GrabModeAsync,
                 GrabModeAsync,
-                window,
+                confine_to,
                 0,
                 0, // CurrentTime
             );
+            // 0 is GrabSuccess
+            if status != 0 {
+                crate::error::report_error(crate::error::PlatformError::OsError(format!(
+                    "XGrabPointer failed with status {}, the pointer is not grabbed",
+                    status
+                )));
+            }
         }
 
         (self.libx11.XFlush)(self.display);
@@ -391,6 +442,28 @@ impl X11Display {
                         CursorIcon::NSResize => libx11::XC_sb_v_double_arrow,
                         CursorIcon::NESWResize => libx11::XC_top_right_corner,
                         CursorIcon::NWSEResize => libx11::XC_top_left_corner,
+                        // The cursor font has no counterpart for these
+                        CursorIcon::ContextMenu
+                        | CursorIcon::Alias
+                        | CursorIcon::Copy
+                        | CursorIcon::ZoomIn
+                        | CursorIcon::ZoomOut => libx11::XC_left_ptr,
+                        CursorIcon::Progress => libx11::XC_watch,
+                        CursorIcon::Cell => libx11::XC_plus,
+                        CursorIcon::VerticalText => libx11::XC_xterm,
+                        CursorIcon::NoDrop => libx11::XC_pirate,
+                        CursorIcon::Grab => libx11::XC_hand1,
+                        CursorIcon::Grabbing | CursorIcon::AllScroll => libx11::XC_fleur,
+                        CursorIcon::NResize => libx11::XC_top_side,
+                        CursorIcon::EResize => libx11::XC_right_side,
+                        CursorIcon::SResize => libx11::XC_bottom_side,
+                        CursorIcon::WResize => libx11::XC_left_side,
+                        CursorIcon::NEResize => libx11::XC_top_right_corner,
+                        CursorIcon::NWResize => libx11::XC_top_left_corner,
+                        CursorIcon::SEResize => libx11::XC_bottom_right_corner,
+                        CursorIcon::SWResize => libx11::XC_bottom_left_corner,
+                        CursorIcon::ColResize => libx11::XC_sb_h_double_arrow,
+                        CursorIcon::RowResize => libx11::XC_sb_v_double_arrow,
                     },
                 )
             }),
@@ -407,6 +480,21 @@ impl X11Display {
                 }
                 SetCursorGrab(grab) => self.set_cursor_grab(self.window, grab),
                 ShowMouse(show) => self.show_mouse(show),
+                SetMousePosition { x, y } => {
+                    (self.libx11.XWarpPointer)(
+                        self.display,
+                        0,
+                        self.window,
+                        0,
+                        0,
+                        0,
+                        0,
+                        x as _,
+                        y as _,
+                    );
+                    (self.libx11.XFlush)(self.display);
+                }
+                CaptureMouse(capture) => self.capture_mouse(self.window, capture),
                 SetMouseCursor(icon) => self.set_cursor(self.window, Some(icon)),
                 SetWindowSize {
                     new_width,
@@ -416,14 +504,40 @@ impl X11Display {
                     self.set_window_position(self.window, new_x as _, new_y as _)
                 }
                 SetFullscreen(fullscreen) => self.set_fullscreen(self.window, fullscreen),
+                SetWindowVisible(true) => self.libx11.show_window(self.display, self.window),
+                SetWindowVisible(false) => {
+                    (self.libx11.XUnmapWindow)(self.display, self.window);
+                    (self.libx11.XFlush)(self.display);
+                }
+                SetTray(tray) => super::linux_tray::set_tray(&mut self.tray, tray),
+                // The portal's picker ignores the initial color
+                PickColor(_) => super::linux_portal::pick_color(),
+                MessageBox(request) => request.run(),
+                // Needs the GL context, handled by the main loops
+                SetSwapInterval(..) => {}
                 ShowKeyboard(..) => {
-                    eprintln!("Not implemented for X11")
+                    crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                        "ShowKeyboard is not implemented for X11".to_string(),
+                    ))
                 }
             }
         }
     }
 }
 
+/// Ask the handler about a pending quit request, order the quit unless it was cancelled
+fn resolve_quit(event_handler: &mut dyn EventHandler) {
+    let d = crate::native_display().try_lock().unwrap();
+    if d.quit_requested && !d.quit_ordered {
+        drop(d);
+        crate::event::dispatch_quit_requested(event_handler);
+        let mut d = crate::native_display().try_lock().unwrap();
+        if d.quit_requested {
+            d.quit_ordered = true
+        }
+    }
+}
+
 unsafe fn glx_main_loop<F>(
     mut display: X11Display,
     conf: &crate::conf::Conf,
@@ -445,7 +559,7 @@ where
             .create_window(display.root, display.display, visual, depth, conf);
 
     let (glx_context, glx_window) = glx.create_context(display.display, display.window);
-    glx.swap_interval(
+    let swap_interval = glx.swap_interval(
         display.display,
         glx_window,
         glx_context,
@@ -454,6 +568,7 @@ where
     gl::load_gl_funcs(|proc| glx.libgl.get_procaddr(proc));
 
     display.init_drag_n_drop();
+    display.init_clipboard_notifications();
     display.libx11.show_window(display.display, display.window);
 
     (display.libx11.XFlush)(display.display);
@@ -470,18 +585,44 @@ where
     ));
     crate::set_display(NativeDisplayData {
         high_dpi: conf.high_dpi,
-        dpi_scale: display.libx11.update_system_dpi(display.display),
+        // Window pixels are framebuffer pixels, dpi_scale only says how many
+        // make a logical unit
+        dpi_scale: if conf.high_dpi {
+            display.libx11.update_system_dpi(display.display)
+        } else {
+            1.0
+        },
         blocking_event_loop: conf.platform.blocking_event_loop,
+        window_handle: Some(crate::WindowHandle::Xlib {
+            display: display.display as _,
+            window: display.window as _,
+        }),
+        context_handle: Some(crate::ContextHandle::Glx {
+            display: display.display as _,
+            fb_config: glx.fbconfig as _,
+            visual_id: (*visual).visualid as _,
+            drawable: glx_window as _,
+            context: glx_context as _,
+        }),
+        swap_interval: crate::SwapInterval::from_gl(swap_interval),
         ..NativeDisplayData::new(w, h, tx, clipboard)
     });
     if conf.fullscreen {
         display.set_fullscreen(display.window, true);
     }
 
+    display.set_crash_restore(&conf.window_title);
     let mut event_handler = (f.take().unwrap())();
 
     while !crate::native_display().try_lock().unwrap().quit_ordered {
         while let Ok(request) = rx.try_recv() {
+            if let Request::SetSwapInterval(interval) = request {
+                let applied =
+                    glx.swap_interval(display.display, glx_window, glx_context, interval.to_gl());
+                crate::native_display().lock().unwrap().swap_interval =
+                    crate::SwapInterval::from_gl(applied);
+                continue;
+            }
             display.process_request(request);
         }
         glx.make_current(display.display, glx_window, glx_context);
@@ -501,18 +642,36 @@ where
             (display.libx11.XNextEvent)(display.display, &mut xevent);
             display.process_event(&mut xevent, &mut *event_handler);
         }
+        // Only noticed once XNextEvent returns with `blocking_event_loop`
+        if super::linux_session::poll_sigterm() {
+            resolve_quit(&mut *event_handler);
+        }
+        if let Some(tray) = &mut display.tray {
+            tray.poll(&mut *event_handler);
+        }
 
         if !conf.platform.blocking_event_loop || display.update_requested {
             display.update_requested = false;
+            crate::frame_stats::begin_frame();
+            crate::event::dispatch_errors(&mut *event_handler);
+            crate::accessibility::dispatch_actions(&mut *event_handler);
+            crate::colors::dispatch_picked(&mut *event_handler);
+            crate::message_box::dispatch_answers(&mut *event_handler);
             event_handler.update();
             event_handler.draw();
 
+            crate::frame_stats::begin_present();
             glx.swap_buffers(display.display, glx_window);
+            crate::frame_pacing::sync_after_swap();
+            crate::frame_stats::end_frame();
+            crate::frame_pacing::wait();
             (display.libx11.XFlush)(display.display);
         }
     }
 
     glx.destroy_context(display.display, glx_window, glx_context);
+    crate::accessibility::remove_adapter();
+    crate::crash::clear_restore();
     (display.libx11.XUnmapWindow)(display.display, display.window);
     (display.libx11.XDestroyWindow)(display.display, display.window);
     (display.libx11.XCloseDisplay)(display.display);
@@ -557,9 +716,12 @@ where
         panic!("eglMakeCurrent failed");
     }
 
-    if (egl_lib.eglSwapInterval)(egl_display, conf.platform.swap_interval.unwrap_or(1)) == 0 {
-        eprintln!("eglSwapInterval failed");
-    }
+    let swap_interval = egl::swap_interval(
+        &mut egl_lib,
+        egl_display,
+        conf.platform.swap_interval.unwrap_or(1),
+    );
+    let presenter = egl::Presenter::new(&mut egl_lib, egl_display);
 
     crate::native::gl::load_gl_funcs(|proc| {
         let name = std::ffi::CString::new(proc).unwrap();
@@ -567,6 +729,7 @@ where
     });
 
     display.init_drag_n_drop();
+    display.init_clipboard_notifications();
     display.libx11.show_window(display.display, display.window);
     let (w, h) = display
         .libx11
@@ -580,8 +743,25 @@ where
     ));
     crate::set_display(NativeDisplayData {
         high_dpi: conf.high_dpi,
-        dpi_scale: display.libx11.update_system_dpi(display.display),
+        // Window pixels are framebuffer pixels, dpi_scale only says how many
+        // make a logical unit
+        dpi_scale: if conf.high_dpi {
+            display.libx11.update_system_dpi(display.display)
+        } else {
+            1.0
+        },
         blocking_event_loop: conf.platform.blocking_event_loop,
+        window_handle: Some(crate::WindowHandle::Xlib {
+            display: display.display as _,
+            window: display.window as _,
+        }),
+        context_handle: Some(crate::ContextHandle::Egl {
+            display: egl_display as _,
+            config: config as _,
+            context: context as _,
+            surface: egl_surface as _,
+        }),
+        swap_interval: crate::SwapInterval::from_gl(swap_interval),
         ..NativeDisplayData::new(w, h, tx, clipboard)
     });
     if conf.fullscreen {
@@ -590,10 +770,17 @@ where
 
     (display.libx11.XFlush)(display.display);
 
+    display.set_crash_restore(&conf.window_title);
     let mut event_handler = (f.take().unwrap())();
 
     while !crate::native_display().try_lock().unwrap().quit_ordered {
         while let Ok(request) = rx.try_recv() {
+            if let Request::SetSwapInterval(interval) = request {
+                let applied = egl::swap_interval(&mut egl_lib, egl_display, interval.to_gl());
+                crate::native_display().lock().unwrap().swap_interval =
+                    crate::SwapInterval::from_gl(applied);
+                continue;
+            }
             display.process_request(request);
         }
 
@@ -608,17 +795,36 @@ where
             (display.libx11.XNextEvent)(display.display, &mut xevent);
             display.process_event(&mut xevent, &mut *event_handler);
         }
+        // Only noticed once XNextEvent returns with `blocking_event_loop`
+        if super::linux_session::poll_sigterm() {
+            resolve_quit(&mut *event_handler);
+        }
+        if let Some(tray) = &mut display.tray {
+            tray.poll(&mut *event_handler);
+        }
 
         if !conf.platform.blocking_event_loop || display.update_requested {
             display.update_requested = false;
+            crate::frame_stats::begin_frame();
+            crate::event::dispatch_errors(&mut *event_handler);
+            crate::accessibility::dispatch_actions(&mut *event_handler);
+            crate::colors::dispatch_picked(&mut *event_handler);
+            crate::message_box::dispatch_answers(&mut *event_handler);
             event_handler.update();
+            presenter.begin_frame(&mut egl_lib, egl_display, egl_surface);
             event_handler.draw();
 
-            (egl_lib.eglSwapBuffers)(egl_display, egl_surface);
+            crate::frame_stats::begin_present();
+            presenter.swap_buffers(&mut egl_lib, egl_display, egl_surface);
+            crate::frame_pacing::sync_after_swap();
+            crate::frame_stats::end_frame();
+            crate::frame_pacing::wait();
             (display.libx11.XFlush)(display.display);
         }
     }
 
+    crate::accessibility::remove_adapter();
+    crate::crash::clear_restore();
     (display.libx11.XUnmapWindow)(display.display, display.window);
     (display.libx11.XDestroyWindow)(display.display, display.window);
     (display.libx11.XCloseDisplay)(display.display);
@@ -632,7 +838,8 @@ where
 {
     unsafe {
         let mut libx11 = LibX11::try_load()?;
-        let libxkbcommon = LibXkbCommon::try_load()?;
+        let mut libxkbcommon = LibXkbCommon::try_load()?;
+        let compose = keycodes::Compose::new(&mut libxkbcommon);
         let libxi = xi_input::LibXi::try_load()?;
 
         (libx11.XInitThreads)();
@@ -660,6 +867,18 @@ where
         // repeating KeyPress event it generates.
         (libx11.XkbSetDetectableAutoRepeat)(x11_display, true as _, std::ptr::null_mut());
 
+        const XKB_USE_CORE_KBD: libc::c_uint = 0x0100;
+        let (mut delay, mut interval) = (0, 0);
+        if (libx11.XkbGetAutoRepeatRate)(x11_display, XKB_USE_CORE_KBD, &mut delay, &mut interval)
+            != 0
+            && interval != 0
+        {
+            crate::event::set_key_repeat_settings(crate::event::KeyRepeatSettings {
+                delay: delay as f64 / 1000.,
+                rate: 1000. / interval as f64,
+            });
+        }
+
         libx11.load_extensions(x11_display);
         let mut display = X11Display {
             empty_cursor: x_cursor::create_empty_cursor(x11_display, x11_root, &mut libx11),
@@ -668,8 +887,14 @@ where
             window: 0,
             libx11,
             libxkbcommon,
+            compose,
             libxi,
+            libxfixes: None,
+            tray: None,
             repeated_keycodes: [false; 256],
+            cursor_grabbed: false,
+            mouse_captured: false,
+            keyboard_grab: conf.kiosk.is_some_and(|kiosk| kiosk.block_system_shortcuts),
             cursor_cache: HashMap::new(),
             update_requested: true,
             drag_n_drop: Default::default(),
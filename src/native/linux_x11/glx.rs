@@ -205,7 +205,7 @@ pub struct Glx {
     pub libgl: LibGlx,
     multisample: bool,
     extensions: GlxExtensions,
-    fbconfig: GLXFBConfig,
+    pub fbconfig: GLXFBConfig,
     pub visual: *mut Visual,
     pub depth: i32,
 }
@@ -377,20 +377,33 @@ impl Glx {
         self.libgl.glxSwapBuffers.unwrap()(display, window);
     }
 
+    /// Returns the interval applied: negative (adaptive) needs
+    /// GLX_EXT_swap_control_tear, and without any swap control the driver's
+    /// default, normally vsync, stays.
     pub unsafe fn swap_interval(
         &mut self,
         display: *mut Display,
         window: GLXWindow,
         ctx: GLXContext,
         interval: i32,
-    ) {
+    ) -> i32 {
         self.libgl.glxMakeCurrent.unwrap()(display, window, ctx);
 
-        if self.extensions.glxSwapIntervalExt.is_some() {
-            self.extensions.glxSwapIntervalExt.unwrap()(display, window, interval);
-        } else if self.extensions.glxSwapIntervalMesa.is_some() {
-            self.extensions.glxSwapIntervalMesa.unwrap()(interval);
-        };
+        if let Some(swap_interval_ext) = self.extensions.glxSwapIntervalExt {
+            let tear = self
+                .extensions
+                .extensions_string
+                .contains("GLX_EXT_swap_control_tear");
+            let interval = if interval < 0 && !tear { 1 } else { interval };
+            swap_interval_ext(display, window, interval);
+            interval
+        } else if let Some(swap_interval_mesa) = self.extensions.glxSwapIntervalMesa {
+            let interval = if interval < 0 { 1 } else { interval };
+            swap_interval_mesa(interval);
+            interval
+        } else {
+            1
+        }
     }
 }
 
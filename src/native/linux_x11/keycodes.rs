@@ -161,6 +161,10 @@ pub unsafe fn translate_mouse_button(button: i32) -> MouseButton {
         1 => MouseButton::Left,
         2 => MouseButton::Middle,
         3 => MouseButton::Right,
+        // 4 to 7 are the wheel
+        8 => MouseButton::X1,
+        9 => MouseButton::X2,
+        10.. => MouseButton::Other(button as u16),
         _ => MouseButton::Unknown,
     }
 }
@@ -171,3 +175,84 @@ pub unsafe extern "C" fn keysym_to_unicode(
 ) -> i32 {
     (libxkbcommon.xkb_keysym_to_utf32)(keysym as u32) as i32
 }
+
+/// Dead keys and Multi_key sequences, from the locale's Compose file
+pub struct Compose {
+    state: *mut core::ffi::c_void,
+}
+
+impl Compose {
+    /// For the locale of LC_ALL, LC_CTYPE or LANG, None if it has no Compose file
+    pub unsafe fn new(libxkbcommon: &mut LibXkbCommon) -> Option<Compose> {
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .unwrap_or_else(|| "C".to_string());
+        let locale = std::ffi::CString::new(locale).ok()?;
+
+        // Kept for the lifetime of the window, as the X11 display
+        let context = (libxkbcommon.xkb_context_new)(0);
+        if context.is_null() {
+            return None;
+        }
+        let table = (libxkbcommon.xkb_compose_table_new_from_locale)(context, locale.as_ptr(), 0);
+        if table.is_null() {
+            return None;
+        }
+        let state = (libxkbcommon.xkb_compose_state_new)(table, 0);
+        if state.is_null() {
+            return None;
+        }
+        Some(Compose { state })
+    }
+
+    /// The text a key press commits: the keysym's own character or a finished
+    /// sequence. None while a sequence goes on, or when it was cancelled.
+    pub unsafe fn feed(
+        &mut self,
+        libxkbcommon: &mut LibXkbCommon,
+        keysym: super::libx11::KeySym,
+    ) -> Option<String> {
+        const FEED_ACCEPTED: i32 = 1;
+        const COMPOSING: i32 = 1;
+        const COMPOSED: i32 = 2;
+        const CANCELLED: i32 = 3;
+
+        // Modifier keysyms are ignored and don't break a sequence
+        if (libxkbcommon.xkb_compose_state_feed)(self.state, keysym as u32) == FEED_ACCEPTED {
+            match (libxkbcommon.xkb_compose_state_get_status)(self.state) {
+                COMPOSING => return None,
+                CANCELLED => {
+                    (libxkbcommon.xkb_compose_state_reset)(self.state);
+                    return None;
+                }
+                COMPOSED => {
+                    let mut buffer = [0u8; 64];
+                    let len = (libxkbcommon.xkb_compose_state_get_utf8)(
+                        self.state,
+                        buffer.as_mut_ptr() as _,
+                        buffer.len(),
+                    );
+                    (libxkbcommon.xkb_compose_state_reset)(self.state);
+                    let len = (len.max(0) as usize).min(buffer.len() - 1);
+                    return Some(String::from_utf8_lossy(&buffer[..len]).into_owned());
+                }
+                _ => {}
+            }
+        }
+        Some(keysym_text(libxkbcommon, keysym))
+    }
+}
+
+/// The character of `keysym`, empty for function keys and dead keys
+pub unsafe fn keysym_text(
+    libxkbcommon: &mut LibXkbCommon,
+    keysym: super::libx11::KeySym,
+) -> String {
+    let chr = keysym_to_unicode(libxkbcommon, keysym);
+    match char::from_u32(chr as u32) {
+        Some(chr) if chr != '\0' => chr.to_string(),
+        _ => String::new(),
+    }
+}
@@ -814,16 +814,24 @@ pub struct XColor {
 }
 
 // See https://tronche.com/gui/x/xlib/appendix/b/
+pub const XC_bottom_left_corner: libc::c_ushort = 12;
+pub const XC_bottom_right_corner: libc::c_ushort = 14;
+pub const XC_bottom_side: libc::c_ushort = 16;
 pub const XC_crosshair: libc::c_ushort = 34;
 pub const XC_fleur: libc::c_ushort = 52;
+pub const XC_hand1: libc::c_ushort = 58;
 pub const XC_hand2: libc::c_ushort = 60;
 pub const XC_left_ptr: libc::c_ushort = 68;
+pub const XC_left_side: libc::c_ushort = 70;
 pub const XC_pirate: libc::c_ushort = 88;
+pub const XC_plus: libc::c_ushort = 90;
 pub const XC_question_arrow: libc::c_ushort = 92;
+pub const XC_right_side: libc::c_ushort = 96;
 pub const XC_sb_h_double_arrow: libc::c_ushort = 108;
 pub const XC_sb_v_double_arrow: libc::c_ushort = 116;
 pub const XC_top_left_corner: libc::c_ushort = 134;
 pub const XC_top_right_corner: libc::c_ushort = 136;
+pub const XC_top_side: libc::c_ushort = 138;
 pub const XC_watch: libc::c_ushort = 150;
 pub const XC_xterm: libc::c_ushort = 152;
 
@@ -915,11 +923,15 @@ crate::declare_module!(
     pub fn XCloseDisplay(*mut Display) -> c_int,
     pub fn XGrabPointer(*mut Display, Window, c_int, c_uint, c_int, c_int, Window, Cursor, Time) -> c_int,
     pub fn XUngrabPointer(*mut Display, Time) -> c_int,
+    pub fn XGrabKeyboard(*mut Display, Window, c_int, c_int, c_int, Time) -> c_int,
+    pub fn XUngrabKeyboard(*mut Display, Time) -> c_int,
+    pub fn XWarpPointer(*mut Display, Window, Window, c_int, c_int, c_uint, c_uint, c_int, c_int) -> c_int,
     pub fn XSendEvent(*mut Display, Window, c_int, c_long, *mut XEvent) -> c_int,
     pub fn XrmGetResource(XrmDatabase, *const c_char, *const c_char, *mut *mut c_char, *mut XrmValue) -> c_int,
     pub fn XrmDestroyDatabase(XrmDatabase),
     pub fn XrmGetStringDatabase(*const c_char) -> XrmDatabase,
     pub fn XkbSetDetectableAutoRepeat(*mut Display, c_int, *mut c_int) -> c_int,
+    pub fn XkbGetAutoRepeatRate(*mut Display, c_uint, *mut c_uint, *mut c_uint) -> c_int,
     pub fn XQueryExtension(*mut Display, *const c_char, *mut c_int, *mut c_int, *mut c_int) -> c_int,
     pub fn XConvertSelection(*mut Display, Atom, Atom, Atom, Window, Time) -> c_int,
     pub fn XSetSelectionOwner(*mut Display, Atom, Window, Time) -> c_int,
@@ -952,6 +964,13 @@ crate::declare_module!(
     ...
     ...
     pub fn xkb_keysym_to_utf32(u32) -> u32,
+    pub fn xkb_context_new(c_int) -> *mut c_void,
+    pub fn xkb_compose_table_new_from_locale(*mut c_void, *const c_char, c_int) -> *mut c_void,
+    pub fn xkb_compose_state_new(*mut c_void, c_int) -> *mut c_void,
+    pub fn xkb_compose_state_feed(*mut c_void, u32) -> c_int,
+    pub fn xkb_compose_state_reset(*mut c_void),
+    pub fn xkb_compose_state_get_status(*mut c_void) -> c_int,
+    pub fn xkb_compose_state_get_utf8(*mut c_void, *mut c_char, usize) -> c_int,
     ...
     ...
 );
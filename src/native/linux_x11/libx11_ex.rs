@@ -36,7 +36,10 @@ impl LibX11 {
             mut _display: *mut Display,
             event: *mut XErrorEvent,
         ) -> libc::c_int {
-            eprintln!("Error: {}", (*event).error_code);
+            crate::error::report_error(crate::error::PlatformError::OsError(format!(
+                "X11 error: {}",
+                (*event).error_code
+            )));
             0 as libc::c_int
         }
 
@@ -202,13 +205,23 @@ impl LibX11 {
             | PropertyChangeMask;
         self.grab_error_handler();
 
+        // X11 has no logical units, the window gets the pixels of the asked
+        // logical size instead
+        let dpi_scale = if conf.high_dpi {
+            self.update_system_dpi(display)
+        } else {
+            1.0
+        };
+        let window_width = (conf.window_width as f32 * dpi_scale) as i32;
+        let window_height = (conf.window_height as f32 * dpi_scale) as i32;
+
         let (win_x, win_y) = if conf.desktop_center {
             // For desktop centering, calculate center position
             let screen = (self.XDefaultScreen)(display);
             let screen_width = (self.XDisplayWidth)(display, screen) as i32;
             let screen_height = (self.XDisplayHeight)(display, screen) as i32;
-            let center_x = (screen_width - conf.window_width) / 2;
-            let center_y = (screen_height - conf.window_height) / 2;
+            let center_x = (screen_width - window_width) / 2;
+            let center_y = (screen_height - window_height) / 2;
             (center_x as libc::c_int, center_y as libc::c_int)
         } else {
             (0 as libc::c_int, 0 as libc::c_int)
@@ -219,8 +232,8 @@ impl LibX11 {
             root,
             win_x,
             win_y,
-            conf.window_width as _,
-            conf.window_height as _,
+            window_width as _,
+            window_height as _,
             0 as libc::c_int as libc::c_uint,
             depth,
             InputOutput as libc::c_uint,
@@ -237,10 +250,10 @@ impl LibX11 {
         (*hints).flags |= PWinGravity;
         if !conf.window_resizable {
             (*hints).flags |= PMinSize | PMaxSize;
-            (*hints).min_width = conf.window_width;
-            (*hints).min_height = conf.window_height;
-            (*hints).max_width = conf.window_width;
-            (*hints).max_height = conf.window_height;
+            (*hints).min_width = window_width;
+            (*hints).min_height = window_height;
+            (*hints).max_width = window_width;
+            (*hints).max_height = window_height;
         }
         (*hints).win_gravity = StaticGravity;
         (self.XSetWMNormalHints)(display, window, hints);
@@ -259,10 +272,78 @@ impl LibX11 {
         // For an unknown reason, this should happen after update_window_icon,
         // otherwise X11 will skip ChangeProperty(WM_ICON)
         self.update_window_title(display, window, &conf.window_title);
+        self.update_window_style(display, window, conf);
 
         window
     }
 
+    /// `Conf::window_decorations` and `Conf::utility_window`, before the window is mapped
+    unsafe fn update_window_style(
+        &mut self,
+        display: *mut Display,
+        window: Window,
+        conf: &crate::conf::Conf,
+    ) {
+        let atom = |name: &[u8]| (self.XInternAtom)(display, name.as_ptr() as *const _, false as _);
+
+        if !conf.window_decorations {
+            // flags: MWM_HINTS_DECORATIONS, decorations: none
+            let mut hints: [libc::c_ulong; 5] = [2, 0, 0, 0, 0];
+            let motif_wm_hints = atom(b"_MOTIF_WM_HINTS\x00");
+            (self.XChangeProperty)(
+                display,
+                window,
+                motif_wm_hints,
+                motif_wm_hints,
+                32,
+                PropModeReplace,
+                hints.as_mut_ptr() as *mut _,
+                hints.len() as _,
+            );
+        }
+
+        if conf.utility_window {
+            let mut window_type = [atom(b"_NET_WM_WINDOW_TYPE_UTILITY\x00")];
+            (self.XChangeProperty)(
+                display,
+                window,
+                atom(b"_NET_WM_WINDOW_TYPE\x00"),
+                4 as _,
+                32,
+                PropModeReplace,
+                window_type.as_mut_ptr() as *mut _,
+                1,
+            );
+            let mut state = [
+                atom(b"_NET_WM_STATE_SKIP_TASKBAR\x00"),
+                atom(b"_NET_WM_STATE_SKIP_PAGER\x00"),
+            ];
+            (self.XChangeProperty)(
+                display,
+                window,
+                atom(b"_NET_WM_STATE\x00"),
+                4 as _,
+                32,
+                PropModeReplace,
+                state.as_mut_ptr() as *mut _,
+                state.len() as _,
+            );
+            // A user time of 0 asks the window manager not to focus the window
+            // when it is mapped
+            let mut user_time: [libc::c_ulong; 1] = [0];
+            (self.XChangeProperty)(
+                display,
+                window,
+                atom(b"_NET_WM_USER_TIME\x00"),
+                self.extensions.cardinal,
+                32,
+                PropModeReplace,
+                user_time.as_mut_ptr() as *mut _,
+                1,
+            );
+        }
+    }
+
     pub unsafe fn show_window(&mut self, display: *mut Display, window: Window) {
         (self.XMapWindow)(display, window);
         (self.XRaiseWindow)(display, window);
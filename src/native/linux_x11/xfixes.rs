@@ -0,0 +1,72 @@
+#![allow(non_upper_case_globals, non_snake_case)]
+
+//! XFixes selection events, to learn when another application takes the clipboard
+
+use super::libx11::*;
+
+pub const XFixesSelectionNotify: libc::c_int = 0;
+pub const XFixesSetSelectionOwnerNotifyMask: libc::c_ulong = 1;
+
+#[derive(Copy, Clone)]
+#[repr(C)]
+pub struct XFixesSelectionNotifyEvent {
+    pub type_0: libc::c_int,
+    pub serial: libc::c_ulong,
+    pub send_event: libc::c_int,
+    pub display: *mut Display,
+    pub window: Window,
+    pub subtype: libc::c_int,
+    pub owner: Window,
+    pub selection: Atom,
+    pub timestamp: Time,
+    pub selection_timestamp: Time,
+}
+
+use core::ffi::c_int;
+crate::declare_module!(
+    LibXfixes,
+    "libXfixes.so",
+    "libXfixes.so.3",
+    ...
+    ...
+    pub fn XFixesQueryExtension(*mut Display, *mut c_int, *mut c_int) -> c_int,
+    pub fn XFixesSelectSelectionInput(*mut Display, Window, Atom, libc::c_ulong),
+    ...
+    ...
+    pub event_base: Option<i32>,
+);
+
+impl super::X11Display {
+    /// Ask for an event whenever the CLIPBOARD selection changes owner.
+    /// Without libXfixes there are just no clipboard notifications.
+    pub(super) unsafe fn init_clipboard_notifications(&mut self) {
+        let Ok(mut libxfixes) = LibXfixes::try_load() else {
+            return;
+        };
+        let mut event_base = 0;
+        let mut error_base = 0;
+        if (libxfixes.XFixesQueryExtension)(self.display, &mut event_base, &mut error_base) == 0 {
+            return;
+        }
+        (libxfixes.XFixesSelectSelectionInput)(
+            self.display,
+            self.window,
+            self.libx11.extensions.clipboard,
+            XFixesSetSelectionOwnerNotifyMask,
+        );
+        libxfixes.event_base = Some(event_base);
+        self.libxfixes = Some(libxfixes);
+    }
+
+    /// Whether `event` is another window taking the clipboard
+    pub(super) unsafe fn is_clipboard_change(&self, event: &XEvent) -> bool {
+        let Some(event_base) = self.libxfixes.as_ref().and_then(|lib| lib.event_base) else {
+            return false;
+        };
+        if event.type_0 != event_base + XFixesSelectionNotify {
+            return false;
+        }
+        let event = &*(event as *const XEvent as *const XFixesSelectionNotifyEvent);
+        event.selection == self.libx11.extensions.clipboard && event.owner != self.window
+    }
+}
@@ -4,11 +4,13 @@
 //!
 use {
     crate::{
-        conf::{AppleGfxApi, Icon},
-        event::{EventHandler, MouseButton},
+        conf::{AppleGfxApi, GpuPreference, Icon},
+        event::{EventHandler, MouseButton, PointerKind},
         native::{
             apple::{apple_util::*, frameworks::*},
-            gl, NativeDisplayData, Request,
+            gl,
+            render_thread::{self, RenderThread},
+            NativeDisplayData, Request,
         },
         native_display, CursorIcon,
     },
@@ -20,6 +22,11 @@ use {
     },
 };
 
+mod color;
+mod tray;
+
+pub(crate) use color::system_color;
+
 pub struct MacosDisplay {
     window: ObjcId,
     view: ObjcId,
@@ -42,6 +49,83 @@ pub struct MacosDisplay {
     native_requests: Receiver<Request>,
     update_requested: bool,
     last_paint_start_time: Instant,
+    // There is no notification for pasteboard changes, changeCount is polled every frame
+    pasteboard_change_count: i64,
+    tray: Option<tray::MacosTray>,
+    /// With `start_with_render_thread`, `event_handler` only forwards the events
+    render_thread: Option<RenderThread>,
+    /// `Kiosk::block_system_shortcuts`, applied to the fullscreen presentation options
+    block_system_shortcuts: bool,
+    /// The keyDown being interpreted by the input context, nil otherwise
+    key_event: ObjcId,
+    /// An input method composition is in progress
+    marked_text: bool,
+}
+
+/// The OpenGL context, when it is moved to the render thread
+struct CglSurface {
+    gl_context: ObjcId,
+}
+
+// NSOpenGLContext may be used from any thread, the CGL lock keeps `update`
+// on the main thread away from the frames
+unsafe impl Send for CglSurface {}
+
+impl render_thread::Surface for CglSurface {
+    fn make_current(&mut self) {
+        unsafe { msg_send_![self.gl_context, makeCurrentContext] };
+    }
+    fn release(&mut self) {
+        unsafe { msg_send_![class!(NSOpenGLContext), clearCurrentContext] };
+    }
+    fn begin_frame(&mut self) {
+        unsafe {
+            let cgl_context: *mut c_void = msg_send![self.gl_context, CGLContextObj];
+            CGLLockContext(cgl_context);
+        }
+    }
+    fn present(&mut self) {
+        unsafe {
+            msg_send_![self.gl_context, flushBuffer];
+            let cgl_context: *mut c_void = msg_send![self.gl_context, CGLContextObj];
+            CGLUnlockContext(cgl_context);
+        }
+        crate::frame_pacing::sync_after_swap();
+    }
+    fn set_swap_interval(&mut self, interval: crate::SwapInterval) -> crate::SwapInterval {
+        // NSOpenGLContext can't tear on late frames only
+        let vsync = interval != crate::SwapInterval::Off;
+        let mut swap_interval = vsync as i32;
+        unsafe {
+            let () = msg_send![self.gl_context,
+                setValues:&mut swap_interval
+                forParameter:NSOpenGLContextParameterSwapInterval];
+        }
+        if vsync {
+            crate::SwapInterval::On
+        } else {
+            crate::SwapInterval::Off
+        }
+    }
+    fn wake_platform_thread(&self) {
+        // An empty application defined event, the way GLFW's glfwPostEmptyEvent does it
+        unsafe {
+            let pool: ObjcId = msg_send![class!(NSAutoreleasePool), new];
+            let event: ObjcId = msg_send![class!(NSEvent),
+                otherEventWithType: NSEventType::NSApplicationDefined as u64
+                location: NSPoint { x: 0., y: 0. }
+                modifierFlags: 0u64
+                timestamp: 0f64
+                windowNumber: 0i64
+                context: nil
+                subtype: 0i16
+                data1: 0i64
+                data2: 0i64];
+            let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+            let () = msg_send![ns_app, postEvent: event atStart: YES];
+            let () = msg_send![pool, drain];
+        }
+    }
 }
 
 impl MacosDisplay {
@@ -63,6 +147,32 @@ impl MacosDisplay {
             }
         }
     }
+    fn set_mouse_position(&mut self, x: f32, y: f32) {
+        let dpi_scale = native_display().lock().unwrap().dpi_scale as f64;
+        unsafe {
+            let bounds: NSRect = msg_send![self.view, bounds];
+            // Mouse events are in pixels from the top left, the window in points from the bottom left
+            let in_window = NSRect {
+                origin: NSPoint {
+                    x: x as f64 / dpi_scale,
+                    y: bounds.size.height - y as f64 / dpi_scale,
+                },
+                size: NSSize {
+                    width: 0.,
+                    height: 0.,
+                },
+            };
+            let on_screen: NSRect = msg_send![self.window, convertRectToScreen: in_window];
+            CGWarpMouseCursorPosition(NSPoint {
+                x: on_screen.origin.x,
+                y: bottom_left_to_top_left(on_screen),
+            });
+            // Otherwise the cursor ignores the mouse for a moment after the warp
+            if !self.cursor_grabbed {
+                CGAssociateMouseAndMouseCursorPosition(true);
+            }
+        }
+    }
     fn show_mouse(&mut self, show: bool) {
         if show && !self.cursor_shown {
             unsafe {
@@ -130,6 +240,7 @@ impl MacosDisplay {
             let arr: ObjcId = msg_send![class!(NSArray), arrayWithObject: str];
             let () = msg_send![pasteboard, writeObjects: arr];
         }
+        self.pasteboard_change_count = pasteboard_change_count();
     }
 
     pub fn context(&mut self) -> Option<&mut dyn EventHandler> {
@@ -161,12 +272,10 @@ impl MacosDisplay {
     }
 
     unsafe fn update_dimensions(&mut self) -> Option<(i32, i32)> {
+        // Before locking native_display, the render thread may be waiting for it
+        // while holding the context lock
+        self.update_gl_context();
         let mut d = native_display().lock().unwrap();
-        unsafe {
-            if self.gl_context != nil {
-                msg_send_![self.gl_context, update];
-            }
-        }
         if d.high_dpi {
             let dpi_scale: f64 = msg_send![self.window, backingScaleFactor];
             d.dpi_scale = dpi_scale as f32;
@@ -199,6 +308,39 @@ impl MacosDisplay {
         }
     }
 
+    /// `[NSOpenGLContext update]` after the view changed, locked against
+    /// a frame in progress on the render thread
+    fn update_gl_context(&self) {
+        if self.gl_context == nil {
+            return;
+        }
+        unsafe {
+            let cgl_context: *mut c_void = msg_send![self.gl_context, CGLContextObj];
+            CGLLockContext(cgl_context);
+            msg_send_![self.gl_context, update];
+            CGLUnlockContext(cgl_context);
+        }
+    }
+
+    unsafe fn set_swap_interval(&mut self, interval: crate::SwapInterval) {
+        // Neither NSOpenGLContext nor CAMetalLayer can tear on late frames only
+        let vsync = interval != crate::SwapInterval::Off;
+        if self.gl_context != nil {
+            let mut swap_interval = vsync as i32;
+            let () = msg_send![self.gl_context,
+                setValues:&mut swap_interval
+                forParameter:NSOpenGLContextParameterSwapInterval];
+        } else {
+            let layer: ObjcId = msg_send![self.view, layer];
+            let () = msg_send![layer, setDisplaySyncEnabled: if vsync { YES } else { NO }];
+        }
+        native_display().lock().unwrap().swap_interval = if vsync {
+            crate::SwapInterval::On
+        } else {
+            crate::SwapInterval::Off
+        };
+    }
+
     fn process_request(&mut self, request: Request) {
         use Request::*;
         match request {
@@ -207,6 +349,7 @@ impl MacosDisplay {
             }
             SetCursorGrab(grab) => self.set_cursor_grab(self.window, grab),
             ShowMouse(show) => self.show_mouse(show),
+            SetMousePosition { x, y } => self.set_mouse_position(x, y),
             SetMouseCursor(icon) => self.set_mouse_cursor(icon),
             SetWindowSize {
                 new_width,
@@ -214,6 +357,25 @@ impl MacosDisplay {
             } => self.set_window_size(new_width as _, new_height as _),
             SetFullscreen(fullscreen) => self.set_fullscreen(fullscreen),
             SetWindowPosition { new_x, new_y } => self.set_window_position(new_x, new_y),
+            SetWindowVisible(visible) => unsafe {
+                if visible {
+                    let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+                    let () = msg_send![ns_app, activateIgnoringOtherApps: YES];
+                    let () = msg_send![self.window, makeKeyAndOrderFront: nil];
+                } else {
+                    let () = msg_send![self.window, orderOut: nil];
+                }
+            },
+            SetTray(tray) => unsafe {
+                match (self.tray.as_mut(), tray) {
+                    (_, None) => self.tray = None,
+                    (Some(macos_tray), Some(tray)) => macos_tray.set(*tray),
+                    (None, Some(tray)) => self.tray = Some(tray::MacosTray::new(self, *tray)),
+                }
+            },
+            SetSwapInterval(interval) => unsafe { self.set_swap_interval(interval) },
+            PickColor(initial) => unsafe { color::pick_color(initial) },
+            MessageBox(request) => request.run(),
             _ => {}
         }
     }
@@ -272,6 +434,52 @@ pub fn define_app_delegate() -> *const Class {
         }
     }
 
+    // Cmd-Q from the menu, or a quit Apple event sent on logout, shutdown and reboot
+    extern "C" fn application_should_terminate(this: &Object, _: Sel, _: ObjcId) -> u64 {
+        // NSApplicationTerminateReply
+        const TERMINATE_CANCEL: u64 = 0;
+        const TERMINATE_NOW: u64 = 1;
+
+        let display_ptr: *mut c_void = unsafe { *this.get_ivar("display_ptr") };
+        if display_ptr.is_null() {
+            return TERMINATE_NOW;
+        }
+        let payload = get_window_payload(this);
+        // Only the Apple events of the login window have a reason
+        let session_ending = unsafe {
+            let manager: ObjcId = msg_send![class!(NSAppleEventManager), sharedAppleEventManager];
+            let event: ObjcId = msg_send![manager, currentAppleEvent];
+            let why_quit = u32::from_be_bytes(*b"why?");
+            event != nil && {
+                let reason: ObjcId = msg_send![event, attributeDescriptorForKeyword: why_quit];
+                reason != nil
+            }
+        };
+        {
+            let mut d = native_display().lock().unwrap();
+            d.quit_requested = true;
+            d.session_ending = session_ending;
+        }
+        if let Some(thread) = &payload.render_thread {
+            // The render thread asks the handler and orders the quit
+            thread.send(render_thread::Message::Redraw);
+        } else if !native_display().lock().unwrap().quit_ordered {
+            if let Some(event_handler) = payload.context() {
+                crate::event::dispatch_quit_requested(event_handler);
+            }
+            let mut d = native_display().lock().unwrap();
+            if d.quit_requested {
+                d.quit_ordered = true;
+            }
+        }
+        if native_display().lock().unwrap().quit_ordered {
+            crate::accessibility::remove_adapter();
+            TERMINATE_NOW
+        } else {
+            TERMINATE_CANCEL
+        }
+    }
+
     let superclass = class!(NSObject);
     let mut decl = ClassDecl::new("NSAppDelegate", superclass).unwrap();
     unsafe {
@@ -279,6 +487,10 @@ pub fn define_app_delegate() -> *const Class {
             sel!(applicationShouldTerminateAfterLastWindowClosed:),
             yes1 as extern "C" fn(&Object, Sel, ObjcId) -> BOOL,
         );
+        decl.add_method(
+            sel!(applicationShouldTerminate:),
+            application_should_terminate as extern "C" fn(&Object, Sel, ObjcId) -> u64,
+        );
         decl.add_method(
             sel!(applicationDidUpdate:),
             application_did_update as extern "C" fn(&mut Object, Sel, ObjcId),
@@ -286,6 +498,7 @@ pub fn define_app_delegate() -> *const Class {
     }
 
     decl.add_ivar::<bool>("activated");
+    decl.add_ivar::<*mut c_void>("display_ptr");
     decl.register()
 }
 
@@ -298,6 +511,14 @@ pub fn define_cocoa_window_delegate() -> *const Class {
             msg_send_![capture_manager, stopCapture];
         }
 
+        if let Some(thread) = &payload.render_thread {
+            // The render thread asks the handler and orders the quit
+            let mut d = native_display().lock().unwrap();
+            d.quit_requested = true;
+            thread.send(render_thread::Message::Redraw);
+            return if d.quit_ordered { YES } else { NO };
+        }
+
         // only give user-code a chance to intervene when sapp_quit() wasn't already called
         if !native_display().lock().unwrap().quit_ordered {
             // if window should be closed and event handling is enabled, give user code
@@ -313,6 +534,7 @@ pub fn define_cocoa_window_delegate() -> *const Class {
             }
         }
         if native_display().lock().unwrap().quit_ordered {
+            crate::accessibility::remove_adapter();
             YES
         } else {
             NO
@@ -328,6 +550,42 @@ pub fn define_cocoa_window_delegate() -> *const Class {
         }
     }
 
+    extern "C" fn window_will_start_live_resize(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        // AppKit does not tell the edge, the mouse is next to it
+        let edge = unsafe {
+            const MARGIN: f64 = 12.;
+            let mouse: NSPoint = msg_send![class!(NSEvent), mouseLocation];
+            let frame: NSRect = msg_send![payload.window, frame];
+            let left = mouse.x < frame.origin.x + MARGIN;
+            let right = mouse.x > frame.origin.x + frame.size.width - MARGIN;
+            // Y goes up
+            let bottom = mouse.y < frame.origin.y + MARGIN;
+            let top = mouse.y > frame.origin.y + frame.size.height - MARGIN;
+            match (left, right, top, bottom) {
+                (true, _, true, _) => Some(crate::ResizeEdge::TopLeft),
+                (true, _, _, true) => Some(crate::ResizeEdge::BottomLeft),
+                (_, true, true, _) => Some(crate::ResizeEdge::TopRight),
+                (_, true, _, true) => Some(crate::ResizeEdge::BottomRight),
+                (true, ..) => Some(crate::ResizeEdge::Left),
+                (_, true, ..) => Some(crate::ResizeEdge::Right),
+                (_, _, true, _) => Some(crate::ResizeEdge::Top),
+                (.., true) => Some(crate::ResizeEdge::Bottom),
+                _ => None,
+            }
+        };
+        if let Some(event_handler) = payload.context() {
+            crate::event::dispatch_live_resize(event_handler, true, edge);
+        }
+    }
+
+    extern "C" fn window_did_end_live_resize(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        if let Some(event_handler) = payload.context() {
+            crate::event::dispatch_live_resize(event_handler, false, None);
+        }
+    }
+
     extern "C" fn window_did_move(this: &Object, _: Sel, _: ObjcId) {
         let payload = get_window_payload(this);
         if payload.gl_context.is_null() {
@@ -337,8 +595,8 @@ pub fn define_cocoa_window_delegate() -> *const Class {
         // Update screen position when window moves
         unsafe {
             payload.update_dimensions();
-            msg_send_![payload.gl_context, update];
         }
+        payload.update_gl_context();
     }
 
     extern "C" fn window_did_change_screen(this: &Object, _: Sel, _: ObjcId) {
@@ -357,6 +615,32 @@ pub fn define_cocoa_window_delegate() -> *const Class {
         let payload = get_window_payload(this);
         payload.fullscreen = false;
     }
+    extern "C" fn window_will_use_full_screen_presentation_options(
+        this: &Object,
+        _: Sel,
+        _: ObjcId,
+        proposed: u64,
+    ) -> u64 {
+        // NSApplicationPresentationOptions. Process switching and the Apple menu
+        // can only be disabled with the dock and the menu bar hidden, which
+        // fullscreen already proposes.
+        const DISABLE_APPLE_MENU: u64 = 1 << 4;
+        const DISABLE_PROCESS_SWITCHING: u64 = 1 << 5;
+        const DISABLE_FORCE_QUIT: u64 = 1 << 6;
+        const DISABLE_SESSION_TERMINATION: u64 = 1 << 7;
+        const DISABLE_HIDE_APPLICATION: u64 = 1 << 8;
+
+        let payload = get_window_payload(this);
+        if !payload.block_system_shortcuts {
+            return proposed;
+        }
+        proposed
+            | DISABLE_APPLE_MENU
+            | DISABLE_PROCESS_SWITCHING
+            | DISABLE_FORCE_QUIT
+            | DISABLE_SESSION_TERMINATION
+            | DISABLE_HIDE_APPLICATION
+    }
     extern "C" fn window_did_change_occlusion_state(this: &Object, _: Sel, _: ObjcId) {
         unsafe {
             let payload = get_window_payload(this);
@@ -368,12 +652,14 @@ pub fn define_cocoa_window_delegate() -> *const Class {
         }
     }
     extern "C" fn window_did_become_key(this: &Object, _: Sel, _: ObjcId) {
+        crate::accessibility::focus_changed(true);
         let payload = get_window_payload(this);
         if let Some(event_handler) = payload.context() {
             event_handler.window_restored_event();
         }
     }
     extern "C" fn window_did_resign_key(this: &Object, _: Sel, _: ObjcId) {
+        crate::accessibility::focus_changed(false);
         let payload = get_window_payload(this);
         if let Some(event_handler) = payload.context() {
             event_handler.window_minimized_event();
@@ -394,6 +680,14 @@ pub fn define_cocoa_window_delegate() -> *const Class {
             sel!(windowDidResize:),
             window_did_resize as extern "C" fn(&Object, Sel, ObjcId),
         );
+        decl.add_method(
+            sel!(windowWillStartLiveResize:),
+            window_will_start_live_resize as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(windowDidEndLiveResize:),
+            window_did_end_live_resize as extern "C" fn(&Object, Sel, ObjcId),
+        );
         decl.add_method(
             sel!(windowDidMove:),
             window_did_move as extern "C" fn(&Object, Sel, ObjcId),
@@ -410,6 +704,11 @@ pub fn define_cocoa_window_delegate() -> *const Class {
             sel!(windowDidExitFullScreen:),
             window_did_exit_fullscreen as extern "C" fn(&Object, Sel, ObjcId),
         );
+        decl.add_method(
+            sel!(window:willUseFullScreenPresentationOptions:),
+            window_will_use_full_screen_presentation_options
+                as extern "C" fn(&Object, Sel, ObjcId, u64) -> u64,
+        );
         decl.add_method(
             sel!(windowDidChangeOcclusionState:),
             window_did_change_occlusion_state as extern "C" fn(&Object, Sel, ObjcId),
@@ -459,6 +758,18 @@ unsafe fn get_proc_address(name: *const u8) -> Option<unsafe extern "C" fn()> {
 
 // methods for both metal or OPENGL view
 unsafe fn view_base_decl(decl: &mut ClassDecl) {
+    /// Tablets drive the mouse with NSEventSubtypeTabletPoint events
+    fn set_tablet_source(event: ObjcId) {
+        const NS_EVENT_SUBTYPE_TABLET_POINT: i16 = 1;
+        unsafe {
+            let subtype: i16 = msg_send![event, subtype];
+            if subtype == NS_EVENT_SUBTYPE_TABLET_POINT {
+                let pressure: f32 = msg_send![event, pressure];
+                crate::event::set_pointer_source(PointerKind::Pen, Some(pressure));
+            }
+        }
+    }
+
     extern "C" fn mouse_moved(this: &Object, _sel: Sel, event: ObjcId) {
         let payload = get_window_payload(this);
 
@@ -473,6 +784,7 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
                 let point: NSPoint = msg_send!(event, locationInWindow);
                 let point = payload.transform_mouse_point(&point);
                 if let Some(event_handler) = payload.context() {
+                    set_tablet_source(event);
                     event_handler.mouse_motion_event(point.0, point.1);
                 }
             }
@@ -486,6 +798,7 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
             let point: NSPoint = msg_send!(event, locationInWindow);
             let point = payload.transform_mouse_point(&point);
             if let Some(event_handler) = payload.context() {
+                set_tablet_source(event);
                 if down {
                     event_handler.mouse_button_down_event(btn, point.0, point.1);
                 } else {
@@ -506,11 +819,21 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
     extern "C" fn right_mouse_up(this: &Object, _sel: Sel, event: ObjcId) {
         fire_mouse_event(this, event, false, MouseButton::Right);
     }
+    fn other_mouse_button(event: ObjcId) -> MouseButton {
+        // NSInteger
+        let button_number: i64 = unsafe { msg_send![event, buttonNumber] };
+        match button_number {
+            2 => MouseButton::Middle,
+            3 => MouseButton::X1,
+            4 => MouseButton::X2,
+            n => MouseButton::Other(n as u16),
+        }
+    }
     extern "C" fn other_mouse_down(this: &Object, _sel: Sel, event: ObjcId) {
-        fire_mouse_event(this, event, true, MouseButton::Middle);
+        fire_mouse_event(this, event, true, other_mouse_button(event));
     }
     extern "C" fn other_mouse_up(this: &Object, _sel: Sel, event: ObjcId) {
-        fire_mouse_event(this, event, false, MouseButton::Middle);
+        fire_mouse_event(this, event, false, other_mouse_button(event));
     }
     extern "C" fn scroll_wheel(this: &Object, _sel: Sel, event: ObjcId) {
         let payload = get_window_payload(this);
@@ -560,12 +883,132 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
             }
         }
 
-        if let Some(character) = unsafe { get_event_char(event) } {
+        // The input context composes dead keys and runs the input methods,
+        // committed text comes back through insertText:replacementRange:
+        payload.key_event = event;
+        unsafe {
+            let events: ObjcId = msg_send![class!(NSArray), arrayWithObject: event];
+            msg_send_![this, interpretKeyEvents: events];
+        }
+        payload.key_event = nil;
+    }
+
+    // NSTextInputClient. Marked text, the composition in progress, is not
+    // shown by miniquad, only its state is tracked.
+    extern "C" fn insert_text(this: &Object, _sel: Sel, string: ObjcId, _: NSRange) {
+        let payload = get_window_payload(this);
+        payload.marked_text = false;
+        let text = unsafe {
+            let attributed: BOOL = msg_send![string, isKindOfClass: class!(NSAttributedString)];
+            let string: ObjcId = if attributed == YES {
+                msg_send![string, string]
+            } else {
+                string
+            };
+            nsstring_to_string(string)
+        };
+        // Outside of keyDown, e.g. from the emoji picker
+        let (mods, repeat) = if payload.key_event.is_null() {
+            (Default::default(), false)
+        } else {
+            unsafe {
+                (
+                    get_event_key_modifier(payload.key_event),
+                    msg_send![payload.key_event, isARepeat],
+                )
+            }
+        };
+        if let Some(event_handler) = payload.context() {
+            crate::event::dispatch_text(event_handler, &text, mods, repeat);
+        }
+    }
+    extern "C" fn insert_text_legacy(this: &Object, sel: Sel, string: ObjcId) {
+        insert_text(this, sel, string, NSRange::new(NSNotFound, 0));
+    }
+    extern "C" fn do_command_by_selector(this: &Object, _sel: Sel, _command: Sel) {
+        // Not text: Backspace, Enter, arrows, shortcuts. The character of the
+        // key still goes to char_event, as it always did.
+        let payload = get_window_payload(this);
+        if payload.key_event.is_null() {
+            return;
+        }
+        let mods = unsafe { get_event_key_modifier(payload.key_event) };
+        let repeat: bool = unsafe { msg_send!(payload.key_event, isARepeat) };
+        if let Some(character) = unsafe { get_event_char(payload.key_event) } {
             if let Some(event_handler) = payload.context() {
                 event_handler.char_event(character, mods, repeat);
             }
         }
     }
+    extern "C" fn set_marked_text(
+        this: &Object,
+        _sel: Sel,
+        string: ObjcId,
+        _: NSRange,
+        _: NSRange,
+    ) {
+        let payload = get_window_payload(this);
+        let len: u64 = unsafe { msg_send![string, length] };
+        payload.marked_text = len > 0;
+    }
+    extern "C" fn unmark_text(this: &Object, _sel: Sel) {
+        let payload = get_window_payload(this);
+        payload.marked_text = false;
+    }
+    extern "C" fn has_marked_text(this: &Object, _sel: Sel) -> BOOL {
+        let payload = get_window_payload(this);
+        if payload.marked_text {
+            YES
+        } else {
+            NO
+        }
+    }
+    extern "C" fn marked_range(this: &Object, _sel: Sel) -> NSRange {
+        let payload = get_window_payload(this);
+        if payload.marked_text {
+            NSRange::new(0, 1)
+        } else {
+            NSRange::new(NSNotFound, 0)
+        }
+    }
+    extern "C" fn selected_range(_: &Object, _sel: Sel) -> NSRange {
+        NSRange::new(NSNotFound, 0)
+    }
+    extern "C" fn valid_attributes_for_marked_text(_: &Object, _sel: Sel) -> ObjcId {
+        unsafe { msg_send![class!(NSArray), array] }
+    }
+    extern "C" fn attributed_substring(
+        _: &Object,
+        _sel: Sel,
+        _: NSRange,
+        _: *mut c_void,
+    ) -> ObjcId {
+        nil
+    }
+    extern "C" fn character_index_for_point(_: &Object, _sel: Sel, _: NSPoint) -> u64 {
+        NSNotFound
+    }
+    extern "C" fn first_rect_for_character_range(
+        this: &Object,
+        _sel: Sel,
+        _: NSRange,
+        _: *mut c_void,
+    ) -> NSRect {
+        // The candidate window goes to the bottom left corner of the view
+        unsafe {
+            let window: ObjcId = msg_send![this, window];
+            let bounds: NSRect = msg_send![this, bounds];
+            let rect = NSRect {
+                origin: bounds.origin,
+                size: NSSize {
+                    width: 0.,
+                    height: 0.,
+                },
+            };
+            let rect: NSRect = msg_send![this, convertRect: rect toView: nil];
+            msg_send![window, convertRectToScreen: rect]
+        }
+    }
 
     extern "C" fn key_up(this: &Object, _sel: Sel, event: ObjcId) {
         let payload = get_window_payload(this);
@@ -824,6 +1267,57 @@ unsafe fn view_base_decl(decl: &mut ClassDecl) {
     );
     decl.add_method(sel!(keyUp:), key_up as extern "C" fn(&Object, Sel, ObjcId));
 
+    // Without the protocol NSView has no input context
+    if let Some(protocol) = objc::runtime::Protocol::get("NSTextInputClient") {
+        decl.add_protocol(protocol);
+    }
+    decl.add_method(
+        sel!(insertText:replacementRange:),
+        insert_text as extern "C" fn(&Object, Sel, ObjcId, NSRange),
+    );
+    decl.add_method(
+        sel!(insertText:),
+        insert_text_legacy as extern "C" fn(&Object, Sel, ObjcId),
+    );
+    decl.add_method(
+        sel!(doCommandBySelector:),
+        do_command_by_selector as extern "C" fn(&Object, Sel, Sel),
+    );
+    decl.add_method(
+        sel!(setMarkedText:selectedRange:replacementRange:),
+        set_marked_text as extern "C" fn(&Object, Sel, ObjcId, NSRange, NSRange),
+    );
+    decl.add_method(sel!(unmarkText), unmark_text as extern "C" fn(&Object, Sel));
+    decl.add_method(
+        sel!(hasMarkedText),
+        has_marked_text as extern "C" fn(&Object, Sel) -> BOOL,
+    );
+    decl.add_method(
+        sel!(markedRange),
+        marked_range as extern "C" fn(&Object, Sel) -> NSRange,
+    );
+    decl.add_method(
+        sel!(selectedRange),
+        selected_range as extern "C" fn(&Object, Sel) -> NSRange,
+    );
+    decl.add_method(
+        sel!(validAttributesForMarkedText),
+        valid_attributes_for_marked_text as extern "C" fn(&Object, Sel) -> ObjcId,
+    );
+    decl.add_method(
+        sel!(attributedSubstringForProposedRange:actualRange:),
+        attributed_substring as extern "C" fn(&Object, Sel, NSRange, *mut c_void) -> ObjcId,
+    );
+    decl.add_method(
+        sel!(characterIndexForPoint:),
+        character_index_for_point as extern "C" fn(&Object, Sel, NSPoint) -> u64,
+    );
+    decl.add_method(
+        sel!(firstRectForCharacterRange:actualRange:),
+        first_rect_for_character_range
+            as extern "C" fn(&Object, Sel, NSRange, *mut c_void) -> NSRect,
+    );
+
     // Add drag and drop methods
     decl.add_method(
         sel!(draggingEntered:),
@@ -938,8 +1432,37 @@ fn get_window_payload(this: &Object) -> &mut MacosDisplay {
     }
 }
 
-unsafe fn create_metal_view(_: &mut MacosDisplay, sample_count: i32, _: bool) -> ObjcId {
-    let mtl_device_obj = MTLCreateSystemDefaultDevice();
+/// The system default device, or the first one with the wanted `isLowPower`
+unsafe fn metal_device(gpu_preference: GpuPreference) -> ObjcId {
+    let low_power = match gpu_preference {
+        GpuPreference::Default => return MTLCreateSystemDefaultDevice(),
+        GpuPreference::HighPerformance => false,
+        GpuPreference::LowPower => true,
+    };
+    let devices = MTLCopyAllDevices();
+    let count: u64 = msg_send![devices, count];
+    for i in 0..count {
+        let device: ObjcId = msg_send![devices, objectAtIndex: i];
+        let is_low_power: BOOL = msg_send![device, isLowPower];
+        // eGPUs and compute-only cards can't drive the display
+        let is_headless: BOOL = msg_send![device, isHeadless];
+        if (is_low_power == YES) == low_power && is_headless == NO {
+            let () = msg_send![device, retain];
+            let () = msg_send![devices, release];
+            return device;
+        }
+    }
+    let () = msg_send![devices, release];
+    MTLCreateSystemDefaultDevice()
+}
+
+unsafe fn create_metal_view(
+    _: &mut MacosDisplay,
+    sample_count: i32,
+    _: bool,
+    gpu_preference: GpuPreference,
+) -> ObjcId {
+    let mtl_device_obj = metal_device(gpu_preference);
     let view_class = define_metal_view_class();
     let view: ObjcId = msg_send![view_class, alloc];
     let view: ObjcId = msg_send![view, init];
@@ -961,12 +1484,17 @@ unsafe fn create_opengl_view(
     display: &mut MacosDisplay,
     sample_count: i32,
     high_dpi: bool,
+    gpu_preference: GpuPreference,
 ) -> ObjcId {
     use NSOpenGLPixelFormatAttribute::*;
 
     let mut attrs: Vec<u32> = vec![];
 
     attrs.push(NSOpenGLPFAAccelerated as _);
+    // Without it the system switches to the discrete GPU for the context
+    if gpu_preference == GpuPreference::LowPower {
+        attrs.push(NSOpenGLPFAAllowOfflineRenderers as _);
+    }
     attrs.push(NSOpenGLPFADoubleBuffer as _);
     attrs.push(NSOpenGLPFAOpenGLProfile as _);
     attrs.push(NSOpenGLPFAOpenGLProfiles::NSOpenGLProfileVersion3_2Core as _);
@@ -1016,6 +1544,13 @@ unsafe fn create_opengl_view(
     view
 }
 
+fn pasteboard_change_count() -> i64 {
+    unsafe {
+        let pasteboard: ObjcId = msg_send![class!(NSPasteboard), generalPasteboard];
+        msg_send![pasteboard, changeCount]
+    }
+}
+
 struct MacosClipboard;
 impl crate::native::Clipboard for MacosClipboard {
     fn get(&mut self) -> Option<String> {
@@ -1113,9 +1648,27 @@ unsafe fn perform_redraw(
         display.event_handler = Some(f());
     }
 
+    let change_count = pasteboard_change_count();
+    if change_count != display.pasteboard_change_count {
+        display.pasteboard_change_count = change_count;
+        if let Some(event_handler) = display.context() {
+            event_handler.clipboard_changed_event();
+        }
+    }
+
+    // The frames are drawn on the render thread
+    if display.render_thread.is_some() {
+        return;
+    }
+
     let mut updated = false;
 
+    crate::frame_stats::begin_frame();
     if let Some(event_handler) = display.context() {
+        crate::event::dispatch_errors(event_handler);
+        crate::accessibility::dispatch_actions(event_handler);
+        crate::colors::dispatch_picked(event_handler);
+        crate::message_box::dispatch_answers(event_handler);
         event_handler.update();
         event_handler.draw();
         updated = true;
@@ -1147,9 +1700,11 @@ unsafe fn perform_redraw(
                 }
             }
         }
+        crate::frame_stats::begin_present();
         match apple_gfx_api {
             AppleGfxApi::OpenGl => {
                 msg_send_!(display.gl_context, flushBuffer);
+                crate::frame_pacing::sync_after_swap();
             }
             AppleGfxApi::Metal => {
                 if !in_draw_rect {
@@ -1157,6 +1712,8 @@ unsafe fn perform_redraw(
                 }
             }
         };
+        crate::frame_stats::end_frame();
+        crate::frame_pacing::wait();
     }
 }
 
@@ -1172,6 +1729,19 @@ where
         blocking_event_loop: conf.platform.blocking_event_loop,
         ..NativeDisplayData::new(conf.window_width, conf.window_height, tx, clipboard)
     });
+    let double_click_time: f64 = msg_send![class!(NSEvent), doubleClickInterval];
+    crate::event::set_double_click_settings(crate::event::DoubleClickSettings {
+        time: double_click_time,
+        ..Default::default()
+    });
+    let repeat_delay: f64 = msg_send![class!(NSEvent), keyRepeatDelay];
+    let repeat_interval: f64 = msg_send![class!(NSEvent), keyRepeatInterval];
+    if repeat_interval > 0. {
+        crate::event::set_key_repeat_settings(crate::event::KeyRepeatSettings {
+            delay: repeat_delay,
+            rate: 1. / repeat_interval,
+        });
+    }
 
     let mut display = MacosDisplay {
         view: std::ptr::null_mut(),
@@ -1190,11 +1760,18 @@ where
         modifiers: Modifiers::default(),
         update_requested: true,
         last_paint_start_time: Instant::now(),
+        pasteboard_change_count: pasteboard_change_count(),
+        tray: None,
+        render_thread: None,
+        block_system_shortcuts: conf.kiosk.is_some_and(|k| k.block_system_shortcuts),
+        key_event: nil,
+        marked_text: false,
     };
 
     let app_delegate_class = define_app_delegate();
     let app_delegate_instance: ObjcId = msg_send![app_delegate_class, new];
-    (*app_delegate_instance).set_ivar("activated", false);
+    // A utility window does not activate the app on launch either
+    (*app_delegate_instance).set_ivar("activated", conf.utility_window);
 
     let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
     let () = msg_send![ns_app, setDelegate: app_delegate_instance];
@@ -1210,13 +1787,23 @@ where
 
     initialize_menu_bar(ns_app);
 
-    let mut window_masks = NSWindowStyleMask::NSTitledWindowMask as u64
-        | NSWindowStyleMask::NSClosableWindowMask as u64
-        | NSWindowStyleMask::NSMiniaturizableWindowMask as u64;
+    let mut window_masks = if conf.window_decorations {
+        NSWindowStyleMask::NSTitledWindowMask as u64
+            | NSWindowStyleMask::NSClosableWindowMask as u64
+            | NSWindowStyleMask::NSMiniaturizableWindowMask as u64
+    } else {
+        NSWindowStyleMask::NSBorderlessWindowMask as u64
+    };
 
     if conf.window_resizable {
         window_masks |= NSWindowStyleMask::NSResizableWindowMask as u64;
     }
+    if conf.utility_window {
+        window_masks |= NSWindowStyleMask::NSNonactivatingPanelMask as u64;
+        if conf.window_decorations {
+            window_masks |= NSWindowStyleMask::NSUtilityWindowMask as u64;
+        }
+    }
 
     let window_frame = NSRect {
         origin: NSPoint { x: 0., y: 0. },
@@ -1226,7 +1813,11 @@ where
         },
     };
 
-    let window: ObjcId = msg_send![class!(NSWindow), alloc];
+    let window: ObjcId = if conf.utility_window {
+        msg_send![class!(NSPanel), alloc]
+    } else {
+        msg_send![class!(NSWindow), alloc]
+    };
     let window: ObjcId = msg_send![
         window,
         initWithContentRect: window_frame
@@ -1235,24 +1826,54 @@ where
         defer: NO
     ];
     assert!(!window.is_null());
+    if conf.utility_window {
+        // Panels hide when the app is deactivated, and a non-activating panel's
+        // app usually is not active
+        let () = msg_send![window, setHidesOnDeactivate: NO];
+        let () = msg_send![window, setFloatingPanel: YES];
+    }
 
     let window_delegate_class = define_cocoa_window_delegate();
     let window_delegate: ObjcId = msg_send![window_delegate_class, new];
     let () = msg_send![window, setDelegate: window_delegate];
 
     (*window_delegate).set_ivar("display_ptr", &mut display as *mut _ as *mut c_void);
+    (*app_delegate_instance).set_ivar("display_ptr", &mut display as *mut _ as *mut c_void);
+    crate::crash::set_restore(&conf.window_title, move || {
+        CGAssociateMouseAndMouseCursorPosition(true);
+        // Hidden by the cursor grab and by show_mouse, the hides are counted
+        let () = msg_send![class!(NSCursor), unhide];
+        let () = msg_send![class!(NSCursor), unhide];
+        // Undo the kiosk options
+        let () = msg_send![ns_app, setPresentationOptions: 0u64];
+        let () = msg_send![window, orderOut: nil];
+    });
 
     let title = str_to_nsstring(&conf.window_title);
     //let () = msg_send![window, setReleasedWhenClosed: NO];
     let () = msg_send![window, setTitle: title];
 
     let view = match conf.platform.apple_gfx_api {
-        AppleGfxApi::OpenGl => create_opengl_view(&mut display, conf.sample_count, conf.high_dpi),
-        AppleGfxApi::Metal => create_metal_view(&mut display, conf.sample_count, conf.high_dpi),
+        AppleGfxApi::OpenGl => create_opengl_view(
+            &mut display,
+            conf.sample_count,
+            conf.high_dpi,
+            conf.platform.gpu_preference,
+        ),
+        AppleGfxApi::Metal => create_metal_view(
+            &mut display,
+            conf.sample_count,
+            conf.high_dpi,
+            conf.platform.gpu_preference,
+        ),
     };
     {
         let mut d = native_display().lock().unwrap();
         d.view = view;
+        d.window_handle = Some(crate::WindowHandle::AppKit {
+            ns_window: window as _,
+            ns_view: view as _,
+        });
     }
     (*view).set_ivar("display_ptr", &mut display as *mut _ as *mut c_void);
 
@@ -1269,10 +1890,24 @@ where
 
             get_proc_address(name.as_ptr() as _)
         });
+
+        if render_thread::is_pending() {
+            let f = display.f.take().unwrap();
+            display.event_handler = Some(f());
+            msg_send_![class!(NSOpenGLContext), clearCurrentContext];
+            let surface = CglSurface {
+                gl_context: display.gl_context,
+            };
+            display.render_thread =
+                render_thread::spawn(surface, conf.platform.blocking_event_loop);
+        }
     }
 
     let () = msg_send![window, setContentView: view];
     let () = msg_send![window, makeFirstResponder: view];
+    display.set_swap_interval(crate::SwapInterval::from_gl(
+        conf.platform.swap_interval.unwrap_or(1),
+    ));
 
     let _ = display.update_dimensions();
 
@@ -1291,8 +1926,23 @@ where
         let () = msg_send![window, toggleFullScreen: nil];
     }
 
+    if conf.kiosk.is_some_and(|k| k.inhibit_screensaver) {
+        // NSActivityIdleDisplaySleepDisabled | NSActivityIdleSystemSleepDisabled,
+        // held until the process exits
+        let options: u64 = (1 << 40) | (1 << 20);
+        let process_info: ObjcId = msg_send![class!(NSProcessInfo), processInfo];
+        let activity: ObjcId = msg_send![
+            process_info,
+            beginActivityWithOptions: options
+            reason: str_to_nsstring("Kiosk mode")
+        ];
+        let () = msg_send![activity, retain];
+    }
+
     msg_send_![window, orderFront: nil];
-    let () = msg_send![window, makeKeyAndOrderFront: nil];
+    if !conf.utility_window {
+        let () = msg_send![window, makeKeyAndOrderFront: nil];
+    }
 
     if conf.desktop_center {
         // Get the primary monitor's frame for proper centering
@@ -1339,17 +1989,26 @@ where
     let mut done = false;
     while !(done || crate::native_display().lock().unwrap().quit_ordered) {
         while let Ok(request) = display.native_requests.try_recv() {
+            if let (Some(thread), Request::SetSwapInterval(interval)) =
+                (&display.render_thread, &request)
+            {
+                thread.send(render_thread::Message::SwapInterval(*interval));
+                continue;
+            }
             display.process_request(request);
         }
 
-        {
+        // The render thread takes care of the quit requests
+        if display.render_thread.is_none() {
             let d = native_display().lock().unwrap();
             if d.quit_requested || d.quit_ordered {
                 done = true;
             }
         }
 
-        let block_on_wait = conf.platform.blocking_event_loop && !display.update_requested;
+        // The render thread wakes this one up after each frame
+        let block_on_wait = display.render_thread.is_some()
+            || (conf.platform.blocking_event_loop && !display.update_requested);
         if block_on_wait {
             let event: ObjcId = msg_send![ns_app, nextEventMatchingMask: NSEventMask::NSAnyEventMask untilDate: distant_future inMode:NSDefaultRunLoopMode dequeue:YES];
 
@@ -1364,9 +2023,121 @@ where
             }
         }
 
-        if !conf.platform.blocking_event_loop || display.update_requested {
+        if let Some(thread) = &display.render_thread {
+            if display.update_requested {
+                display.update_requested = false;
+                thread.send(render_thread::Message::Redraw);
+            }
             perform_redraw(&mut display, conf.platform.apple_gfx_api, false);
+        } else if !conf.platform.blocking_event_loop || display.update_requested {
+            perform_redraw(&mut display, conf.platform.apple_gfx_api, false);
+        }
+    }
+
+    if let Some(thread) = &mut display.render_thread {
+        thread.join();
+    }
+    crate::crash::clear_restore();
+}
+
+/// An NSAlert, on the main thread. Called from another thread the alert is shown
+/// by the event loop, `error` for `crash`.
+pub(crate) fn message_box(
+    title: &str,
+    text: &str,
+    buttons: crate::MessageBoxButtons,
+    error: bool,
+) -> crate::MessageBoxButton {
+    use crate::{
+        message_box::{MessageBoxRequest, Reply},
+        MessageBoxButton, MessageBoxButtons,
+    };
+
+    unsafe {
+        let main_thread: BOOL = msg_send![class!(NSThread), isMainThread];
+        if main_thread == NO {
+            let (tx, rx) = std::sync::mpsc::channel();
+            native_display()
+                .lock()
+                .unwrap()
+                .native_requests
+                .send(Request::MessageBox(Box::new(MessageBoxRequest {
+                    title: title.to_string(),
+                    text: text.to_string(),
+                    buttons,
+                    reply: Reply::Channel(tx),
+                })))
+                .unwrap();
+            return rx.recv().unwrap_or_else(|_| buttons.dismissed());
+        }
+
+        let labels: &[(&str, MessageBoxButton)] = match buttons {
+            MessageBoxButtons::Ok => &[("OK", MessageBoxButton::Ok)],
+            MessageBoxButtons::OkCancel => &[
+                ("OK", MessageBoxButton::Ok),
+                ("Cancel", MessageBoxButton::Cancel),
+            ],
+            MessageBoxButtons::YesNo => {
+                &[("Yes", MessageBoxButton::Yes), ("No", MessageBoxButton::No)]
+            }
+            MessageBoxButtons::YesNoCancel => &[
+                ("Yes", MessageBoxButton::Yes),
+                ("No", MessageBoxButton::No),
+                ("Cancel", MessageBoxButton::Cancel),
+            ],
+        };
+        let alert: ObjcId = msg_send![class!(NSAlert), new];
+        let () = msg_send![alert, setMessageText: str_to_nsstring(title)];
+        let () = msg_send![alert, setInformativeText: str_to_nsstring(text)];
+        // NSAlertStyleCritical or NSAlertStyleInformational
+        let style: u64 = if error { 2 } else { 1 };
+        let () = msg_send![alert, setAlertStyle: style];
+        for (label, _) in labels {
+            let _: ObjcId = msg_send![alert, addButtonWithTitle: str_to_nsstring(label)];
+        }
+        let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+        let () = msg_send![ns_app, activateIgnoringOtherApps: YES];
+        // NSAlertFirstButtonReturn is 1000, the others follow
+        let response: i64 = msg_send![alert, runModal];
+        let () = msg_send![alert, release];
+        labels
+            .get((response - 1000) as usize)
+            .map_or(buttons.dismissed(), |(_, button)| *button)
+    }
+}
+
+pub fn key_label(keycode: crate::KeyCode) -> Option<String> {
+    let scan_code = (0..128).find(|&code| translate_scan_code(code) == Some(keycode))?;
+    unsafe {
+        let source = TISCopyCurrentKeyboardLayoutInputSource();
+        if source.is_null() {
+            return None;
+        }
+        // Null for input methods without a key layout, e.g. some CJK ones
+        let layout_data = TISGetInputSourceProperty(source, kTISPropertyUnicodeKeyLayoutData);
+        let mut label = None;
+        if !layout_data.is_null() {
+            let mut dead_key_state = 0;
+            let mut chars = [0u16; 4];
+            let mut len = 0;
+            let status = UCKeyTranslate(
+                CFDataGetBytePtr(layout_data),
+                scan_code,
+                kUCKeyActionDisplay,
+                0,
+                LMGetKbdType() as u32,
+                kUCKeyTranslateNoDeadKeysMask,
+                &mut dead_key_state,
+                chars.len() as u64,
+                &mut len,
+                chars.as_mut_ptr(),
+            );
+            if status == 0 && len > 0 {
+                label = Some(String::from_utf16_lossy(&chars[..len as usize]));
+            }
         }
+        CFRelease(source);
+        label
     }
 }
 
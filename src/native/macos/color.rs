@@ -0,0 +1,86 @@
+//! NSColor system colors and the shared NSColorPanel, for `colors`
+
+use crate::{colors::SystemColor, native::apple::frameworks::*};
+
+/// RGBA of any NSColor, converted to sRGB first. Catalog colors like
+/// `windowBackgroundColor` have no components before the conversion.
+unsafe fn ns_color_to_rgba(color: ObjcId) -> Option<(f32, f32, f32, f32)> {
+    if color == nil {
+        return None;
+    }
+    let srgb: ObjcId = msg_send![class!(NSColorSpace), sRGBColorSpace];
+    let color: ObjcId = msg_send![color, colorUsingColorSpace: srgb];
+    if color == nil {
+        return None;
+    }
+    let r: f64 = msg_send![color, redComponent];
+    let g: f64 = msg_send![color, greenComponent];
+    let b: f64 = msg_send![color, blueComponent];
+    let a: f64 = msg_send![color, alphaComponent];
+    Some((r as f32, g as f32, b as f32, a as f32))
+}
+
+pub fn system_color(color: SystemColor) -> Option<(f32, f32, f32, f32)> {
+    unsafe {
+        let class = class!(NSColor);
+        let ns_color: ObjcId = match color {
+            SystemColor::Accent => {
+                // macOS 10.14+
+                let available: BOOL =
+                    msg_send![class, respondsToSelector: sel!(controlAccentColor)];
+                if available == NO {
+                    return None;
+                }
+                msg_send![class, controlAccentColor]
+            }
+            SystemColor::Highlight => msg_send![class, selectedTextBackgroundColor],
+            SystemColor::HighlightText => msg_send![class, selectedTextColor],
+            SystemColor::WindowBackground => msg_send![class, windowBackgroundColor],
+            SystemColor::WindowText => msg_send![class, textColor],
+        };
+        ns_color_to_rgba(ns_color)
+    }
+}
+
+/// Show the shared color panel, every change is reported until it is closed
+pub unsafe fn pick_color(initial: (f32, f32, f32, f32)) {
+    let panel: ObjcId = msg_send![class!(NSColorPanel), sharedColorPanel];
+    let (r, g, b, a) = initial;
+    let color: ObjcId = msg_send![class!(NSColor),
+        colorWithSRGBRed: r as f64
+        green: g as f64
+        blue: b as f64
+        alpha: a as f64];
+    let () = msg_send![panel, setShowsAlpha: YES];
+    // Set the color before the target, not to report the initial one
+    let () = msg_send![panel, setTarget: nil];
+    let () = msg_send![panel, setColor: color];
+    let () = msg_send![panel, setTarget: color_target()];
+    let () = msg_send![panel, setAction: sel!(colorChanged:)];
+    let () = msg_send![panel, makeKeyAndOrderFront: nil];
+}
+
+/// The panel does not retain its target, this one lives as long as the app
+fn color_target() -> ObjcId {
+    extern "C" fn color_changed(_: &Object, _: Sel, panel: ObjcId) {
+        unsafe {
+            let color: ObjcId = msg_send![panel, color];
+            crate::colors::report_picked(ns_color_to_rgba(color));
+        }
+    }
+
+    static TARGET: std::sync::OnceLock<usize> = std::sync::OnceLock::new();
+    *TARGET.get_or_init(|| {
+        let superclass = class!(NSObject);
+        let mut decl = ClassDecl::new("MiniquadColorTarget", superclass).unwrap();
+        unsafe {
+            decl.add_method(
+                sel!(colorChanged:),
+                color_changed as extern "C" fn(&Object, Sel, ObjcId),
+            );
+        }
+        let class = decl.register();
+        let target: ObjcId = unsafe { msg_send![class, new] };
+        target as usize
+    }) as ObjcId
+}
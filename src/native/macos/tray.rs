@@ -0,0 +1,188 @@
+//! NSStatusItem in the menu bar, the menu is built on every right click
+
+use {
+    super::{get_window_payload, MacosDisplay},
+    crate::{
+        native::apple::{apple_util::*, frameworks::*},
+        tray::{Tray, TrayEvent, TrayMenuItem},
+    },
+};
+
+// NSVariableStatusItemLength
+const VARIABLE_LENGTH: f64 = -1.0;
+// Menu bar icons are 18pt, the 32px icon keeps them sharp on retina screens
+const ICON_POINTS: f64 = 18.0;
+
+pub struct MacosTray {
+    status_item: ObjcId,
+    target: ObjcId,
+    pub(super) menu: Vec<TrayMenuItem>,
+}
+
+impl MacosTray {
+    pub unsafe fn new(display: &mut MacosDisplay, tray: Tray) -> MacosTray {
+        let status_bar: ObjcId = msg_send![class!(NSStatusBar), systemStatusBar];
+        let status_item: ObjcId = msg_send![status_bar, statusItemWithLength: VARIABLE_LENGTH];
+        let () = msg_send![status_item, retain];
+
+        let target: ObjcId = msg_send![tray_target_class(), new];
+        (*target).set_ivar("display_ptr", display as *mut _ as *mut c_void);
+
+        let button: ObjcId = msg_send![status_item, button];
+        let () = msg_send![button, setTarget: target];
+        let () = msg_send![button, setAction: sel!(statusItemClicked:)];
+        let mask = NSEventMask::NSLeftMouseUpMask as u64 | NSEventMask::NSRightMouseUpMask as u64;
+        let _: i64 = msg_send![button, sendActionOn: mask];
+
+        let mut macos_tray = MacosTray {
+            status_item,
+            target,
+            menu: vec![],
+        };
+        macos_tray.set(tray);
+        macos_tray
+    }
+
+    pub unsafe fn set(&mut self, tray: Tray) {
+        let button: ObjcId = msg_send![self.status_item, button];
+        let image = ns_image(&tray.icon.medium, 32);
+        let () = msg_send![button, setImage: image];
+        let () = msg_send![image, release];
+        let () = msg_send![button, setToolTip: str_to_nsstring(&tray.tooltip)];
+        self.menu = tray.menu;
+    }
+
+    unsafe fn pop_up_menu(&self) {
+        let menu: ObjcId = msg_send![class!(NSMenu), new];
+        let () = msg_send![menu, setAutoenablesItems: NO];
+        for (i, item) in self.menu.iter().enumerate() {
+            let menu_item = match item {
+                TrayMenuItem::Item { label, enabled, .. } => {
+                    let menu_item: ObjcId = msg_send![class!(NSMenuItem), alloc];
+                    let menu_item: ObjcId = msg_send![
+                        menu_item,
+                        initWithTitle: str_to_nsstring(label)
+                        action: sel!(menuItemChosen:)
+                        keyEquivalent: str_to_nsstring("")
+                    ];
+                    let () = msg_send![menu_item, setTarget: self.target];
+                    let () = msg_send![menu_item, setTag: i as i64];
+                    let () = msg_send![menu_item, setEnabled: if *enabled { YES } else { NO }];
+                    let () = msg_send![menu_item, autorelease];
+                    menu_item
+                }
+                TrayMenuItem::Separator => msg_send![class!(NSMenuItem), separatorItem],
+            };
+            let () = msg_send![menu, addItem: menu_item];
+        }
+        // Deprecated, but the replacement (assigning `menu`) would also take the left click
+        let () = msg_send![self.status_item, popUpStatusItemMenu: menu];
+        let () = msg_send![menu, release];
+    }
+}
+
+impl Drop for MacosTray {
+    fn drop(&mut self) {
+        unsafe {
+            let status_bar: ObjcId = msg_send![class!(NSStatusBar), systemStatusBar];
+            let () = msg_send![status_bar, removeStatusItem: self.status_item];
+            let () = msg_send![self.status_item, release];
+            let () = msg_send![self.target, release];
+        }
+    }
+}
+
+unsafe extern "C" fn release_data(info: *mut c_void, _: *const c_void, _: usize) {
+    drop(Box::from_raw(info as *mut Vec<u8>));
+}
+
+/// A retained NSImage from square RGBA pixels
+unsafe fn ns_image(rgba: &[u8], size: usize) -> ObjcId {
+    let rgb = CGColorSpaceCreateDeviceRGB();
+    // CoreGraphics keeps the data until the image is gone
+    let pixels = Box::new(rgba.to_vec());
+    let data = pixels.as_ptr();
+    let len = pixels.len();
+    let info = Box::into_raw(pixels);
+    let provider = CGDataProviderCreateWithData(info as *mut c_void, data, len, release_data);
+    let image = CGImageCreate(
+        size,
+        size,
+        8,
+        32,
+        size * 4,
+        rgb,
+        kCGBitmapByteOrderDefault | kCGImageAlphaLast,
+        provider,
+        std::ptr::null(),
+        false,
+        kCGRenderingIntentDefault,
+    );
+
+    let points = NSSize {
+        width: ICON_POINTS,
+        height: ICON_POINTS,
+    };
+    let ns_image: ObjcId = msg_send![class!(NSImage), alloc];
+    let ns_image: ObjcId = msg_send![ns_image, initWithCGImage: image size: points];
+
+    CGDataProviderRelease(provider);
+    CGColorSpaceRelease(rgb);
+    CGImageRelease(image);
+    ns_image
+}
+
+fn tray_target_class() -> *const Class {
+    extern "C" fn status_item_clicked(this: &Object, _: Sel, _: ObjcId) {
+        let payload = get_window_payload(this);
+        unsafe {
+            let ns_app: ObjcId = msg_send![class!(NSApplication), sharedApplication];
+            let event: ObjcId = msg_send![ns_app, currentEvent];
+            let event_type: u64 = msg_send![event, type];
+            let Some(tray) = payload.tray.as_ref() else {
+                return;
+            };
+            if event_type == NSEventType::NSRightMouseUp as u64 {
+                if !tray.menu.is_empty() {
+                    tray.pop_up_menu();
+                }
+            } else if let Some(event_handler) = payload.context() {
+                event_handler.tray_event(TrayEvent::Click);
+            }
+        }
+    }
+    extern "C" fn menu_item_chosen(this: &Object, _: Sel, sender: ObjcId) {
+        let payload = get_window_payload(this);
+        let index: i64 = unsafe { msg_send![sender, tag] };
+        let item = payload
+            .tray
+            .as_ref()
+            .and_then(|tray| tray.menu.get(index as usize));
+        if let Some(TrayMenuItem::Item { id, .. }) = item {
+            let id = *id;
+            if let Some(event_handler) = payload.context() {
+                event_handler.tray_event(TrayEvent::MenuItem(id));
+            }
+        }
+    }
+
+    // The tray may be shown again after being hidden, the class is registered once
+    if let Some(class) = Class::get("MiniquadTrayTarget") {
+        return class;
+    }
+    let superclass = class!(NSObject);
+    let mut decl = ClassDecl::new("MiniquadTrayTarget", superclass).unwrap();
+    unsafe {
+        decl.add_method(
+            sel!(statusItemClicked:),
+            status_item_clicked as extern "C" fn(&Object, Sel, ObjcId),
+        );
+        decl.add_method(
+            sel!(menuItemChosen:),
+            menu_item_chosen as extern "C" fn(&Object, Sel, ObjcId),
+        );
+    }
+    decl.add_ivar::<*mut c_void>("display_ptr");
+
+    decl.register()
+}
@@ -0,0 +1,331 @@
+//! Opt-in render thread, see `start_with_render_thread`
+//!
+//! The platform thread keeps pumping OS events, while the user's `EventHandler`
+//! lives on a dedicated thread with the GL context current. The platform side
+//! gets an `EventForwarder` as its event handler: every event becomes a closure
+//! sent through a channel and is replayed on the render thread right before the
+//! next `update`. A window drag or a modal resize loop on the platform thread
+//! only delays the events, not the frames.
+//!
+//! The render thread also takes the quit decision: the platform thread only sets
+//! `quit_requested`, the render thread calls `quit_requested_event` and orders
+//! the quit if it was not cancelled.
+
+use crate::event::{
+    EventHandler, KeyCode, KeyMods, MouseButton, PointerEvent, ResizeEdge, TouchPhase,
+};
+use crate::native_display;
+
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::Mutex;
+use std::thread::JoinHandle;
+
+type EventFn = Box<dyn FnOnce(&mut dyn EventHandler) + Send>;
+
+pub(crate) enum Message {
+    Event(EventFn),
+    /// `schedule_update` with `blocking_event_loop`
+    Redraw,
+    SwapInterval(crate::SwapInterval),
+}
+
+/// The platform's GL context, moved to the render thread
+pub(crate) trait Surface: Send {
+    fn make_current(&mut self);
+    /// Unbind the context, before the render thread exits
+    fn release(&mut self);
+    /// Called before each frame
+    fn begin_frame(&mut self) {}
+    /// SwapBuffers or flushBuffer
+    fn present(&mut self);
+    /// Returns the interval that was applied
+    fn set_swap_interval(&mut self, interval: crate::SwapInterval) -> crate::SwapInterval;
+    /// Wake the platform thread up, so the requests made during the frame,
+    /// including a quit, are processed even while it waits for OS events.
+    fn wake_platform_thread(&self);
+}
+
+struct Pending {
+    f: Box<dyn FnOnce() -> Box<dyn EventHandler> + Send>,
+    tx: Sender<Message>,
+    rx: Receiver<Message>,
+}
+
+static PENDING: Mutex<Option<Pending>> = Mutex::new(None);
+
+/// Keep the user's handler factory for the render thread, returns the handler
+/// for the platform thread.
+pub(crate) fn prepare<F>(f: F) -> EventForwarder
+where
+    F: 'static + Send + FnOnce() -> Box<dyn EventHandler>,
+{
+    let (tx, rx) = mpsc::channel();
+    *PENDING.lock().unwrap() = Some(Pending {
+        f: Box::new(f),
+        tx: tx.clone(),
+        rx,
+    });
+    EventForwarder { tx }
+}
+
+/// Was the app started with `start_with_render_thread`, and the render thread
+/// not spawned yet.
+pub(crate) fn is_pending() -> bool {
+    PENDING.lock().unwrap().is_some()
+}
+
+/// Start the render thread, if the app was started with `start_with_render_thread`.
+/// The platform thread must not have `surface`'s context current anymore.
+pub(crate) fn spawn(surface: impl Surface + 'static, blocking: bool) -> Option<RenderThread> {
+    let Pending { f, tx, rx } = PENDING.lock().unwrap().take()?;
+    let thread = std::thread::Builder::new()
+        .name("miniquad render".to_string())
+        .spawn(move || {
+            let mut surface = surface;
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+                render_loop(f, rx, &mut surface, blocking)
+            }));
+            // The platform thread stops once the render thread is gone, panic or not
+            if let Ok(mut d) = native_display().lock() {
+                d.quit_ordered = true;
+            }
+            surface.wake_platform_thread();
+            if let Err(panic) = result {
+                std::panic::resume_unwind(panic);
+            }
+        })
+        .expect("Failed to spawn the render thread");
+    Some(RenderThread {
+        tx,
+        thread: Some(thread),
+    })
+}
+
+/// Platform thread handle of the render thread
+pub(crate) struct RenderThread {
+    tx: Sender<Message>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RenderThread {
+    pub fn send(&self, message: Message) {
+        let _ = self.tx.send(message);
+    }
+
+    /// Order the quit, if not done yet, and wait for the render thread to
+    /// drop the event handler and release the context.
+    pub fn join(&mut self) {
+        native_display().lock().unwrap().quit_ordered = true;
+        self.send(Message::Redraw);
+        if let Some(thread) = self.thread.take() {
+            // A panic was already printed by the panic hook
+            if thread.join().is_err() {
+                crate::crash::render_thread_panicked();
+            }
+        }
+    }
+}
+
+fn render_loop(
+    f: Box<dyn FnOnce() -> Box<dyn EventHandler> + Send>,
+    rx: Receiver<Message>,
+    surface: &mut impl Surface,
+    blocking: bool,
+) {
+    surface.make_current();
+    let mut event_handler = f();
+    let mut redraw = true;
+
+    'frames: loop {
+        let mut handle = |message: Message, redraw: &mut bool| match message {
+            Message::Event(event) => event(&mut *event_handler),
+            Message::Redraw => *redraw = true,
+            Message::SwapInterval(interval) => {
+                let applied = surface.set_swap_interval(interval);
+                native_display().lock().unwrap().swap_interval = applied;
+            }
+        };
+        if blocking && !redraw {
+            match rx.recv() {
+                Ok(message) => handle(message, &mut redraw),
+                Err(_) => break 'frames,
+            }
+        }
+        loop {
+            match rx.try_recv() {
+                Ok(message) => handle(message, &mut redraw),
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'frames,
+            }
+        }
+
+        if resolve_quit(&mut *event_handler) {
+            break;
+        }
+
+        if !blocking || redraw {
+            redraw = false;
+            surface.begin_frame();
+            crate::frame_stats::begin_frame();
+            crate::event::dispatch_errors(&mut *event_handler);
+            crate::accessibility::dispatch_actions(&mut *event_handler);
+            crate::colors::dispatch_picked(&mut *event_handler);
+            crate::message_box::dispatch_answers(&mut *event_handler);
+            event_handler.update();
+            event_handler.draw();
+
+            crate::frame_stats::begin_present();
+            surface.present();
+            crate::frame_stats::end_frame();
+            crate::frame_pacing::wait();
+        }
+        surface.wake_platform_thread();
+    }
+
+    // Resources the handler owns are deleted while the context is still current
+    drop(event_handler);
+    surface.release();
+}
+
+/// What the platforms do on a window close: let the handler cancel
+/// the request, order the quit otherwise.
+fn resolve_quit(event_handler: &mut dyn EventHandler) -> bool {
+    {
+        let d = native_display().lock().unwrap();
+        if d.quit_ordered || !d.quit_requested {
+            return d.quit_ordered;
+        }
+    }
+    // The handler may call `cancel_quit`, which needs native_display
+    crate::event::dispatch_quit_requested(event_handler);
+    let mut d = native_display().lock().unwrap();
+    if d.quit_requested {
+        d.quit_ordered = true;
+    }
+    d.quit_ordered
+}
+
+/// The platform thread's event handler: forwards everything to the render thread
+pub(crate) struct EventForwarder {
+    tx: Sender<Message>,
+}
+
+impl EventForwarder {
+    fn forward(&self, event: impl FnOnce(&mut dyn EventHandler) + Send + 'static) {
+        let _ = self.tx.send(Message::Event(Box::new(event)));
+    }
+}
+
+impl EventHandler for EventForwarder {
+    fn update(&mut self) {}
+    fn draw(&mut self) {}
+    fn resize_event(&mut self, width: f32, height: f32) {
+        self.forward(move |h| h.resize_event(width, height));
+    }
+    fn live_resize_event(&mut self, active: bool, edge: Option<ResizeEdge>) {
+        self.forward(move |h| h.live_resize_event(active, edge));
+    }
+    fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.forward(move |h| h.mouse_motion_event(x, y));
+    }
+    fn mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.forward(move |h| h.mouse_wheel_event(x, y));
+    }
+    fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.forward(move |h| h.mouse_button_down_event(button, x, y));
+    }
+    fn mouse_button_up_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.forward(move |h| h.mouse_button_up_event(button, x, y));
+    }
+    fn double_click_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.forward(move |h| h.double_click_event(button, x, y));
+    }
+    fn char_event(&mut self, character: char, keymods: KeyMods, repeat: bool) {
+        self.forward(move |h| h.char_event(character, keymods, repeat));
+    }
+    fn text_input_event(&mut self, text: &str) {
+        let text = text.to_string();
+        self.forward(move |h| h.text_input_event(&text));
+    }
+    fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        self.forward(move |h| h.key_down_event(keycode, keymods, repeat));
+    }
+    fn key_up_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
+        self.forward(move |h| h.key_up_event(keycode, keymods));
+    }
+    fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.forward(move |h| h.touch_event(phase, id, x, y));
+    }
+    // Made on the platform thread, which knows the pointer source of the
+    // mouse and touch events
+    fn pointer_event(&mut self, event: PointerEvent) {
+        self.forward(move |h| h.pointer_event(event));
+    }
+    fn raw_mouse_motion(&mut self, dx: f32, dy: f32) {
+        self.forward(move |h| h.raw_mouse_motion(dx, dy));
+    }
+    fn window_minimized_event(&mut self) {
+        self.forward(|h| h.window_minimized_event());
+    }
+    fn window_restored_event(&mut self) {
+        self.forward(|h| h.window_restored_event());
+    }
+    // Called by the render thread itself, see `resolve_quit`
+    fn quit_requested_event(&mut self) {}
+    fn session_end_event(&mut self) {}
+    fn low_memory_event(&mut self) {
+        self.forward(|h| h.low_memory_event());
+    }
+    fn files_dropped_event(&mut self) {
+        self.forward(|h| h.files_dropped_event());
+    }
+    fn clipboard_changed_event(&mut self) {
+        self.forward(|h| h.clipboard_changed_event());
+    }
+    fn tray_event(&mut self, event: crate::tray::TrayEvent) {
+        self.forward(move |h| h.tray_event(event));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::{PointerKind, PointerPhase, TimedEventHandler};
+
+    #[derive(Default)]
+    struct Recorder {
+        button_downs: usize,
+        pointer_events: Vec<PointerEvent>,
+    }
+
+    impl EventHandler for Recorder {
+        fn update(&mut self) {}
+        fn draw(&mut self) {}
+        fn mouse_button_down_event(&mut self, _button: MouseButton, _x: f32, _y: f32) {
+            self.button_downs += 1;
+        }
+        fn pointer_event(&mut self, event: PointerEvent) {
+            self.pointer_events.push(event);
+        }
+    }
+
+    #[test]
+    fn mouse_event_delivers_one_pointer_event() {
+        // What `start_with_render_thread` gives the platform thread
+        let (tx, rx) = mpsc::channel();
+        let mut platform = TimedEventHandler::new(Box::new(EventForwarder { tx }));
+        platform.mouse_button_down_event(MouseButton::Left, 10.0, 20.0);
+
+        let mut render = Recorder::default();
+        while let Ok(Message::Event(event)) = rx.try_recv() {
+            event(&mut render);
+        }
+        assert_eq!(render.button_downs, 1);
+        assert_eq!(render.pointer_events.len(), 1);
+        let event = render.pointer_events[0];
+        assert_eq!(event.kind, PointerKind::Mouse);
+        assert_eq!(event.phase, PointerPhase::Down);
+        assert_eq!(event.button, Some(MouseButton::Left));
+        assert_eq!((event.x, event.y), (10.0, 20.0));
+    }
+}
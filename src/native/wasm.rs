@@ -2,6 +2,8 @@ pub mod fs;
 pub mod webgl;
 
 mod keycodes;
+#[cfg(feature = "webxr")]
+pub(crate) mod webxr;
 
 use std::{
     cell::RefCell,
@@ -93,6 +95,8 @@ where
         *g.borrow_mut() = Some(f());
     });
 
+    crate::frame_pacing::set_web_frame_strategy(conf.platform.web_frame_strategy);
+
     // start requestAnimationFrame loop
     unsafe {
         run_animation_loop(conf.platform.blocking_event_loop);
@@ -128,14 +132,35 @@ extern "C" {
     /// "mouse_down"/"key_down" event handler functions.
     pub fn sapp_set_cursor_grab(grab: bool);
 
+    pub fn sapp_capture_mouse(capture: bool);
+
+    /// Null terminated string allocated with `allocate_vec_u8`, or null when the
+    /// browser doesn't tell.
+    pub fn sapp_key_label(keycode: i32) -> *mut u8;
+
     pub fn sapp_set_cursor(cursor: *const u8, len: usize);
 
+    /// alert() or confirm(), returns 1 when confirmed
+    pub fn sapp_message_box(
+        title: *const u8,
+        title_len: usize,
+        text: *const u8,
+        text_len: usize,
+        confirm: bool,
+    ) -> i32;
+
     pub fn sapp_is_elapsed_timer_supported() -> bool;
 
     pub fn sapp_set_fullscreen(fullscreen: bool);
     pub fn sapp_is_fullscreen() -> bool;
     pub fn sapp_set_window_size(new_width: u32, new_height: u32);
     pub fn sapp_schedule_update();
+    /// See `frame_pacing::set_web_frame_strategy`
+    pub fn sapp_set_frame_strategy(kind: u32, param: u32);
+    /// Upload the current frame of the HTMLVideoElement with the given id into
+    /// the bound TEXTURE_2D and write its width and height to `size`.
+    /// False when there is no such video or it has no frame yet.
+    pub fn sapp_upload_video(element_id: *const u8, len: usize, size: *mut u32) -> bool;
     pub fn init_webgl(version: i32);
     pub fn now() -> f64;
 }
@@ -173,11 +198,48 @@ pub unsafe fn update_cursor() {
             crate::CursorIcon::NSResize => "ns-resize",
             crate::CursorIcon::NESWResize => "nesw-resize",
             crate::CursorIcon::NWSEResize => "nwse-resize",
+            crate::CursorIcon::ContextMenu => "context-menu",
+            crate::CursorIcon::Progress => "progress",
+            crate::CursorIcon::Cell => "cell",
+            crate::CursorIcon::VerticalText => "vertical-text",
+            crate::CursorIcon::Alias => "alias",
+            crate::CursorIcon::Copy => "copy",
+            crate::CursorIcon::NoDrop => "no-drop",
+            crate::CursorIcon::Grab => "grab",
+            crate::CursorIcon::Grabbing => "grabbing",
+            crate::CursorIcon::AllScroll => "all-scroll",
+            crate::CursorIcon::ZoomIn => "zoom-in",
+            crate::CursorIcon::ZoomOut => "zoom-out",
+            crate::CursorIcon::NResize => "n-resize",
+            crate::CursorIcon::EResize => "e-resize",
+            crate::CursorIcon::SResize => "s-resize",
+            crate::CursorIcon::WResize => "w-resize",
+            crate::CursorIcon::NEResize => "ne-resize",
+            crate::CursorIcon::NWResize => "nw-resize",
+            crate::CursorIcon::SEResize => "se-resize",
+            crate::CursorIcon::SWResize => "sw-resize",
+            crate::CursorIcon::ColResize => "col-resize",
+            crate::CursorIcon::RowResize => "row-resize",
         }
     };
     sapp_set_cursor(css_name.as_ptr(), css_name.len());
 }
 
+pub fn key_label(keycode: crate::KeyCode) -> Option<String> {
+    // gl.js sends sokol keycodes, all below 512
+    let keycode = (0..512).find(|&code| keycodes::translate_keycode(code) == keycode)?;
+    unsafe {
+        let label = sapp_key_label(keycode);
+        if label.is_null() {
+            return None;
+        }
+        let len = std::ffi::CStr::from_ptr(label as _).to_bytes().len();
+        let mut label = Vec::from_raw_parts(label, len + 1, len + 1);
+        label.pop();
+        String::from_utf8(label).ok()
+    }
+}
+
 // gl.js version required to be shipped alongside this rust code.
 // "crate_version" is a misleading, but it can't be changed for legacy reasons.
 #[no_mangle]
@@ -218,29 +280,113 @@ pub extern "C" fn on_clipboard_paste(msg: *mut u8, len: usize) {
     *CLIPBOARD.get_or_init(|| Mutex::new(None)).lock().unwrap() = Some(msg);
 }
 
-#[no_mangle]
-pub extern "C" fn frame() {
+/// Browsers have two buttons at most, and no title: it goes above the text
+pub(crate) fn message_box(
+    title: &str,
+    text: &str,
+    buttons: crate::MessageBoxButtons,
+) -> crate::MessageBoxButton {
+    use crate::{MessageBoxButton, MessageBoxButtons};
+
+    let confirmed = unsafe {
+        sapp_message_box(
+            title.as_ptr(),
+            title.len(),
+            text.as_ptr(),
+            text.len(),
+            buttons != MessageBoxButtons::Ok,
+        ) != 0
+    };
+    match buttons {
+        MessageBoxButtons::Ok => MessageBoxButton::Ok,
+        MessageBoxButtons::OkCancel if confirmed => MessageBoxButton::Ok,
+        MessageBoxButtons::YesNo | MessageBoxButtons::YesNoCancel if confirmed => {
+            MessageBoxButton::Yes
+        }
+        MessageBoxButtons::YesNo | MessageBoxButtons::YesNoCancel => MessageBoxButton::No,
+        _ => buttons.dismissed(),
+    }
+}
+
+fn process_requests() {
     REQUESTS.with(|r| {
         while let Ok(request) = r.borrow_mut().as_mut().unwrap().try_recv() {
             match request {
                 Request::SetCursorGrab(grab) => unsafe { sapp_set_cursor_grab(grab) },
                 Request::ShowMouse(show) => unsafe { show_mouse(show) },
+                Request::SetMousePosition { .. } => {
+                    crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                        "Browsers do not allow moving the pointer".to_string(),
+                    ))
+                }
+                Request::CaptureMouse(capture) => unsafe { sapp_capture_mouse(capture) },
                 Request::SetMouseCursor(cursor) => unsafe {
                     set_mouse_cursor(cursor);
                 },
                 Request::SetFullscreen(fullscreen) => unsafe {
                     sapp_set_fullscreen(fullscreen);
                 },
+                Request::SetSwapInterval(crate::SwapInterval::On) => {}
+                Request::SetSwapInterval(_) => {
+                    crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                        "Browsers always wait for the vertical blank".to_string(),
+                    ))
+                }
+                Request::SetTray(Some(_)) => {
+                    crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                        "Browsers have no system tray".to_string(),
+                    ))
+                }
+                Request::PickColor(_) => {
+                    crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                        "Browsers have no color picker dialog".to_string(),
+                    ));
+                    crate::colors::report_picked(None);
+                }
                 _ => {}
             }
         }
     });
+}
+
+fn run_frame(event_handler: &mut dyn EventHandler) {
+    crate::frame_stats::begin_frame();
+    crate::event::dispatch_errors(event_handler);
+    crate::accessibility::dispatch_actions(event_handler);
+    crate::colors::dispatch_picked(event_handler);
+    crate::message_box::dispatch_answers(event_handler);
+    #[cfg(feature = "webxr")]
+    crate::xr::dispatch_events(event_handler);
+    event_handler.update();
+    event_handler.draw();
+    // The browser presents once this returns
+    crate::frame_stats::begin_present();
+    crate::frame_stats::end_frame();
+}
+
+#[no_mangle]
+pub extern "C" fn frame() {
+    process_requests();
+    // The headset drives the frames while an XR session runs
+    #[cfg(feature = "webxr")]
+    if crate::xr::session_running() {
+        return;
+    }
     tl_event_handler(|event_handler| {
-        event_handler.update();
-        event_handler.draw();
+        if !crate::frame_pacing::frame_due() {
+            return;
+        }
+        run_frame(event_handler);
     });
 }
 
+/// An XR frame, called by webxr.js from the session's animation loop
+#[cfg(feature = "webxr")]
+fn xr_frame() {
+    process_requests();
+    tl_event_handler(run_frame);
+}
+
 #[no_mangle]
 pub extern "C" fn mouse_move(x: i32, y: i32) {
     tl_event_handler(|event_handler| {
@@ -291,10 +437,10 @@ pub extern "C" fn key_down(key: u32, modifiers: u32, repeat: bool) {
 }
 
 #[no_mangle]
-pub extern "C" fn key_press(key: u32) {
+pub extern "C" fn key_press(key: u32, repeat: bool) {
     if let Some(key) = char::from_u32(key) {
         tl_event_handler(|event_handler| {
-            event_handler.char_event(key, crate::KeyMods::default(), false);
+            crate::event::dispatch_char(event_handler, key, crate::KeyMods::default(), repeat);
         });
     }
 }
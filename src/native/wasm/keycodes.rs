@@ -9,6 +9,9 @@ pub fn translate_mouse_button(button: i32) -> MouseButton {
         0 => MouseButton::Left,
         1 => MouseButton::Right,
         2 => MouseButton::Middle,
+        3 => MouseButton::X1,
+        4 => MouseButton::X2,
+        5.. => MouseButton::Other(button as u16),
         _ => MouseButton::Unknown,
     }
 }
@@ -40,6 +40,8 @@ pub const GL_COLOR_ATTACHMENT0: u32 = 0x8CE0;
 pub const GL_COLOR_ATTACHMENT22: u32 = 0x8CF6;
 pub const GL_DRAW_FRAMEBUFFER: u32 = 0x8CA9;
 pub const GL_FRAMEBUFFER_COMPLETE: u32 = 0x8CD5;
+pub const GL_FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER: u32 = 0x8CDB;
+pub const GL_FRAMEBUFFER_INCOMPLETE_READ_BUFFER: u32 = 0x8CDC;
 pub const GL_NUM_EXTENSIONS: u32 = 0x821D;
 pub const GL_INFO_LOG_LENGTH: u32 = 0x8B84;
 pub const GL_VERTEX_SHADER: u32 = 0x8B31;
@@ -73,6 +75,9 @@ pub const GL_NEAREST_MIPMAP_NEAREST: u32 = 0x2700;
 pub const GL_UNSIGNED_SHORT_4_4_4_4: u32 = 0x8033;
 pub const GL_SRC_ALPHA_SATURATE: u32 = 0x0308;
 pub const GL_STREAM_DRAW: u32 = 0x88E0;
+pub const GL_STREAM_READ: u32 = 0x88E1;
+pub const GL_PIXEL_PACK_BUFFER: u32 = 0x88EB;
+pub const GL_MAP_READ_BIT: u32 = 0x0001;
 pub const GL_ONE: u32 = 1;
 pub const GL_NEAREST_MIPMAP_LINEAR: u32 = 0x2702;
 pub const GL_RGB10_A2: u32 = 0x8059;
@@ -83,8 +88,15 @@ pub const GL_RGB8: u32 = 0x8051;
 pub const GL_ARRAY_BUFFER: u32 = 0x8892;
 pub const GL_STENCIL: u32 = 0x1802;
 pub const GL_TEXTURE_2D: u32 = 0x0DE1;
+pub const GL_TEXTURE_2D_MULTISAMPLE: u32 = 0x9100;
+pub const GL_TEXTURE_EXTERNAL_OES: u32 = 0x8D65;
+pub const GL_COMPRESSED_RGBA_S3TC_DXT5_EXT: u32 = 0x83F3;
+pub const GL_COMPRESSED_RGBA_BPTC_UNORM: u32 = 0x8E8C;
+pub const GL_COMPRESSED_RGBA8_ETC2_EAC: u32 = 0x9278;
+pub const GL_COMPRESSED_RGBA_ASTC_4x4_KHR: u32 = 0x93B0;
 pub const GL_DEPTH: u32 = 0x1801;
 pub const GL_FRONT: u32 = 0x0404;
+pub const GL_FRONT_AND_BACK: u32 = 0x0408;
 pub const GL_STENCIL_BUFFER_BIT: u32 = 0x00000400;
 pub const GL_REPEAT: u32 = 0x2901;
 pub const GL_RGBA: u32 = 0x1908;
@@ -239,6 +251,7 @@ pub const GL_MAX_CUBE_MAP_TEXTURE_SIZE: u32 = 0x851C;
 pub const GL_MAX_3D_TEXTURE_SIZE: u32 = 0x8073;
 pub const GL_MAX_ARRAY_TEXTURE_LAYERS: u32 = 0x88FF;
 pub const GL_MAX_VERTEX_ATTRIBS: u32 = 0x8869;
+pub const GL_MAX_TEXTURE_IMAGE_UNITS: u32 = 0x8872;
 pub const GL_CLAMP_TO_BORDER: u32 = 0x812D;
 pub const GL_TEXTURE_BORDER_COLOR: u32 = 0x1004;
 pub const GL_UNPACK_ALIGNMENT: u32 = 3317;
@@ -249,9 +262,38 @@ pub const GL_TEXTURE_SWIZZLE_A: u32 = 36421;
 pub const GL_TEXTURE_SWIZZLE_RGBA: u32 = 36422;
 pub const GL_DRAW_FRAMEBUFFER_BINDING: u32 = 36006;
 pub const GL_TIME_ELAPSED: u32 = 35007;
+pub const GL_TIMESTAMP: u32 = 36392;
 pub const GL_QUERY_RESULT: u32 = 34918;
 pub const GL_QUERY_RESULT_AVAILABLE: u32 = 34919;
 pub const GL_VENDOR: u32 = 0x1F00;
+pub const GL_RENDERER: u32 = 0x1F01;
+pub const GL_ACTIVE_UNIFORMS: u32 = 0x8B86;
+pub const GL_ACTIVE_ATTRIBUTES: u32 = 0x8B89;
+pub const GL_DEPTH_BITS: u32 = 0x0D56;
+pub const GL_STENCIL_BITS: u32 = 0x0D57;
+pub const GL_FLOAT_VEC2: u32 = 0x8B50;
+pub const GL_FLOAT_VEC3: u32 = 0x8B51;
+pub const GL_FLOAT_VEC4: u32 = 0x8B52;
+pub const GL_INT_VEC2: u32 = 0x8B53;
+pub const GL_INT_VEC3: u32 = 0x8B54;
+pub const GL_INT_VEC4: u32 = 0x8B55;
+pub const GL_BOOL: u32 = 0x8B56;
+pub const GL_BOOL_VEC2: u32 = 0x8B57;
+pub const GL_BOOL_VEC3: u32 = 0x8B58;
+pub const GL_BOOL_VEC4: u32 = 0x8B59;
+pub const GL_FLOAT_MAT2: u32 = 0x8B5A;
+pub const GL_FLOAT_MAT3: u32 = 0x8B5B;
+pub const GL_FLOAT_MAT4: u32 = 0x8B5C;
+pub const GL_UNSIGNED_INT_VEC2: u32 = 0x8DC6;
+pub const GL_UNSIGNED_INT_VEC3: u32 = 0x8DC7;
+pub const GL_UNSIGNED_INT_VEC4: u32 = 0x8DC8;
+pub const GL_SAMPLER_2D: u32 = 0x8B5E;
+pub const GL_SAMPLER_CUBE: u32 = 0x8B60;
+pub const GL_SAMPLER_2D_SHADOW: u32 = 0x8B62;
+pub const GL_INT_SAMPLER_2D: u32 = 0x8DCA;
+pub const GL_UNSIGNED_INT_SAMPLER_2D: u32 = 0x8DD2;
+pub const GL_SAMPLER_2D_MULTISAMPLE: u32 = 0x9108;
+pub const GL_SAMPLER_EXTERNAL_OES: u32 = 0x8D66;
 pub const GL_VERSION: u32 = 0x1F02;
 pub const GL_SHADING_LANGUAGE_VERSION: GLenum = 0x8B8C;
 pub const GL_TEXTURE_BASE_LEVEL: GLenum = 0x813C;
@@ -313,6 +355,16 @@ pub struct __GLsync {
 }
 pub type GLsync = *mut __GLsync;
 
+pub const GL_MAP_WRITE_BIT: u32 = 0x0002;
+pub const GL_MAP_PERSISTENT_BIT: u32 = 0x0040;
+pub const GL_MAP_COHERENT_BIT: u32 = 0x0080;
+pub const GL_SYNC_GPU_COMMANDS_COMPLETE: u32 = 0x9117;
+pub const GL_SYNC_FLUSH_COMMANDS_BIT: u32 = 0x00000001;
+pub const GL_ALREADY_SIGNALED: u32 = 0x911A;
+pub const GL_TIMEOUT_EXPIRED: u32 = 0x911B;
+pub const GL_CONDITION_SATISFIED: u32 = 0x911C;
+pub const GL_WAIT_FAILED: u32 = 0x911D;
+
 extern "C" {
     pub fn glActiveTexture(texture: GLenum);
     pub fn glAttachShader(program: GLuint, shader: GLuint);
@@ -800,6 +852,18 @@ extern "C" {
         length: GLsizeiptr,
         access: GLbitfield,
     ) -> *mut ::core::ffi::c_void;
+    pub fn glBufferStorage(
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const ::core::ffi::c_void,
+        flags: GLbitfield,
+    );
+    pub fn glBufferStorageEXT(
+        target: GLenum,
+        size: GLsizeiptr,
+        data: *const ::core::ffi::c_void,
+        flags: GLbitfield,
+    );
     pub fn glFlushMappedBufferRange(target: GLenum, offset: GLintptr, length: GLsizeiptr);
     pub fn glBindVertexArray(array: GLuint);
     pub fn glDeleteVertexArrays(n: GLsizei, arrays: *const GLuint);
@@ -0,0 +1,118 @@
+//! The browser side of `xr`, called by js/webxr.js
+
+use crate::xr::*;
+
+use std::cell::RefCell;
+
+extern "C" {
+    pub fn webxr_is_supported() -> bool;
+    pub fn webxr_request_session();
+    pub fn webxr_end_session();
+}
+
+#[no_mangle]
+pub extern "C" fn miniquad_webxr_crate_version() -> u32 {
+    1
+}
+
+thread_local! {
+    /// Filled by webxr.js before each `webxr_view` and `webxr_controller` call
+    static BUFFER: RefCell<Vec<f32>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Room for `len` floats, valid until the next call
+#[no_mangle]
+pub extern "C" fn webxr_buffer(len: usize) -> *mut f32 {
+    BUFFER.with(|buffer| {
+        let mut buffer = buffer.borrow_mut();
+        buffer.resize(len, 0.0);
+        buffer.as_mut_ptr()
+    })
+}
+
+/// 0: started, 1: ended, 2: failed
+#[no_mangle]
+pub extern "C" fn webxr_session_event(event: u32) {
+    session_event(match event {
+        0 => XrSessionEvent::Started,
+        1 => XrSessionEvent::Ended,
+        _ => XrSessionEvent::Failed,
+    });
+}
+
+#[no_mangle]
+pub extern "C" fn webxr_begin_frame(framebuffer: u32, width: i32, height: i32) {
+    begin_frame(framebuffer, (width, height));
+}
+
+/// The buffer holds the projection matrix, then the view matrix
+#[no_mangle]
+pub extern "C" fn webxr_view(eye: u32, x: i32, y: i32, width: i32, height: i32) {
+    BUFFER.with(|buffer| {
+        let buffer = buffer.borrow();
+        let mut projection = [0.0; 16];
+        let mut view = [0.0; 16];
+        projection.copy_from_slice(&buffer[0..16]);
+        view.copy_from_slice(&buffer[16..32]);
+        push_view(XrView {
+            eye: match eye {
+                1 => XrEye::Left,
+                2 => XrEye::Right,
+                _ => XrEye::None,
+            },
+            projection,
+            view,
+            viewport: (x, y, width, height),
+        });
+    });
+}
+
+fn read_pose(values: &mut impl Iterator<Item = f32>) -> XrPose {
+    let mut next = || values.next().unwrap_or(0.0);
+    XrPose {
+        position: [next(), next(), next()],
+        orientation: [next(), next(), next(), next()],
+    }
+}
+
+/// The buffer holds 1 if there is a grip pose, the grip pose, the aim pose, the
+/// button count, pressed, touched and value for each button, the axis count and
+/// the axes. Poses are a position and an orientation.
+#[no_mangle]
+pub extern "C" fn webxr_controller(handedness: u32) {
+    BUFFER.with(|buffer| {
+        let buffer = buffer.borrow();
+        let mut values = buffer.iter().copied();
+        let has_grip = values.next() == Some(1.0);
+        let grip = read_pose(&mut values);
+        let aim = read_pose(&mut values);
+        let button_count = values.next().unwrap_or(0.0) as usize;
+        let buttons = (0..button_count)
+            .map(|_| XrButton {
+                pressed: values.next() == Some(1.0),
+                touched: values.next() == Some(1.0),
+                value: values.next().unwrap_or(0.0),
+            })
+            .collect();
+        let axis_count = values.next().unwrap_or(0.0) as usize;
+        let axes = values.by_ref().take(axis_count).collect();
+        push_controller(XrController {
+            handedness: match handedness {
+                1 => XrHandedness::Left,
+                2 => XrHandedness::Right,
+                _ => XrHandedness::None,
+            },
+            grip: has_grip.then_some(grip),
+            aim,
+            buttons,
+            axes,
+        });
+    });
+}
+
+/// The views and controllers are in, run the frame
+#[no_mangle]
+pub extern "C" fn webxr_end_frame() {
+    super::xr_frame();
+    end_frame();
+}
@@ -1,9 +1,17 @@
-use std::{ffi::OsString, os::windows::ffi::OsStringExt, path::PathBuf};
+use std::{
+    ffi::OsString,
+    os::windows::ffi::OsStringExt,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+};
 
 use crate::{
-    conf::{Conf, Icon},
-    event::{KeyMods, MouseButton},
-    native::{NativeDisplayData, Request},
+    conf::{Conf, GpuPreference, Icon},
+    event::{KeyMods, MouseButton, PointerKind},
+    native::{
+        render_thread::{self, RenderThread},
+        NativeDisplayData, Request,
+    },
     CursorIcon, EventHandler,
 };
 
@@ -12,30 +20,154 @@ use winapi::{
         hidusage::{HID_USAGE_GENERIC_MOUSE, HID_USAGE_PAGE_GENERIC},
         minwindef::{DWORD, HIWORD, LOWORD, LPARAM, LRESULT, MAX_PATH, TRUE, UINT, WPARAM},
         ntdef::NULL,
-        windef::{HBRUSH, HCURSOR, HDC, HICON, HWND, POINT, RECT},
+        windef::{HBRUSH, HCURSOR, HDC, HGLRC, HICON, HWND, POINT, RECT},
         windowsx::{GET_X_LPARAM, GET_Y_LPARAM},
     },
     um::{
+        dwmapi::{DwmGetCompositionTimingInfo, DWM_TIMING_INFO},
         libloaderapi::{GetModuleHandleW, GetProcAddress},
+        profileapi::{QueryPerformanceCounter, QueryPerformanceFrequency},
         shellapi::{DragAcceptFiles, DragQueryFileW, HDROP},
         shellscalingapi::*,
+        winbase::SetThreadExecutionState,
         wingdi::*,
+        winnt::{ES_CONTINUOUS, ES_DISPLAY_REQUIRED, ES_SYSTEM_REQUIRED},
         winuser::*,
     },
 };
 
+// imm32, not part of the winapi features
+#[link(name = "imm32")]
+extern "system" {
+    fn ImmGetContext(hwnd: HWND) -> *mut std::ffi::c_void;
+    fn ImmReleaseContext(hwnd: HWND, himc: *mut std::ffi::c_void) -> i32;
+    fn ImmGetCompositionStringW(
+        himc: *mut std::ffi::c_void,
+        index: DWORD,
+        buf: *mut std::ffi::c_void,
+        len: DWORD,
+    ) -> i32;
+}
+const GCS_RESULTSTR: DWORD = 0x0800;
+
+// comdlg32 and DwmGetColorizationColor, not part of the winapi features either
+#[repr(C)]
+#[allow(non_snake_case)]
+struct CHOOSECOLORW {
+    lStructSize: DWORD,
+    hwndOwner: HWND,
+    hInstance: HWND,
+    rgbResult: DWORD,
+    lpCustColors: *mut DWORD,
+    Flags: DWORD,
+    lCustData: LPARAM,
+    lpfnHook: *mut std::ffi::c_void,
+    lpTemplateName: *const u16,
+}
+#[link(name = "comdlg32")]
+extern "system" {
+    fn ChooseColorW(cc: *mut CHOOSECOLORW) -> i32;
+}
+#[link(name = "dwmapi")]
+extern "system" {
+    fn DwmGetColorizationColor(colorization: *mut DWORD, opaque_blend: *mut i32) -> i32;
+}
+const CC_RGBINIT: DWORD = 0x1;
+const CC_FULLOPEN: DWORD = 0x2;
+
+// Signature in the extra info of the mouse messages Windows makes up for pens
+// and touch screens, with MI_WP_TOUCH set for touch
+const MI_WP_SIGNATURE: usize = 0xFF515700;
+const MI_WP_SIGNATURE_MASK: usize = 0xFFFFFF00;
+const MI_WP_TOUCH: usize = 0x80;
+
 mod clipboard;
 mod keycodes;
 mod libopengl32;
+mod tray;
 mod wgl;
 
 use libopengl32::LibOpengl32;
 
+// Read by the NVIDIA and AMD drivers of hybrid laptops when they load, which
+// happens with the first pixel format, see `conf::GpuPreference`
+#[no_mangle]
+#[used]
+#[allow(non_upper_case_globals)]
+pub static mut NvOptimusEnablement: u32 = 0;
+#[no_mangle]
+#[used]
+#[allow(non_upper_case_globals)]
+pub static mut AmdPowerXpressRequestHighPerformance: u32 = 0;
+
+/// The GL context, when it is moved to the render thread
+struct WglSurface {
+    wnd: HWND,
+    dc: HDC,
+    gl_ctx: HGLRC,
+    make_current: libopengl32::wglMakeCurrent,
+    wgl: wgl::Wgl,
+}
+
+// The window, its DC and the context may be used from any thread of the process,
+// the context being current on one thread at a time
+unsafe impl Send for WglSurface {}
+
+impl render_thread::Surface for WglSurface {
+    fn make_current(&mut self) {
+        (self.make_current)(self.dc, self.gl_ctx);
+    }
+    fn release(&mut self) {
+        (self.make_current)(NULL as _, NULL as _);
+    }
+    fn begin_frame(&mut self) {
+        unsafe { record_dwm_vblank() };
+    }
+    fn present(&mut self) {
+        unsafe { SwapBuffers(self.dc) };
+        crate::frame_pacing::sync_after_swap();
+    }
+    fn set_swap_interval(&mut self, interval: crate::SwapInterval) -> crate::SwapInterval {
+        crate::SwapInterval::from_gl(unsafe { self.wgl.swap_interval(interval.to_gl()) })
+    }
+    fn wake_platform_thread(&self) {
+        unsafe { PostMessageW(self.wnd, WM_NULL, 0, 0) };
+    }
+}
+
+/// The compositor presents the frames, its last vertical blank is the
+/// reference for `window::present_timing`
+unsafe fn record_dwm_vblank() {
+    let mut info: DWM_TIMING_INFO = std::mem::zeroed();
+    info.cbSize = std::mem::size_of::<DWM_TIMING_INFO>() as _;
+    // NULL: the desktop composition, the only one supported since Windows 8.1
+    if DwmGetCompositionTimingInfo(NULL as _, &mut info) < 0 {
+        return;
+    }
+    let mut frequency = std::mem::zeroed();
+    let mut counter = std::mem::zeroed();
+    QueryPerformanceFrequency(&mut frequency);
+    QueryPerformanceCounter(&mut counter);
+    let frequency = *frequency.QuadPart() as f64;
+    let counter = *counter.QuadPart() as f64;
+    // The struct is packed, fields are copied out before use
+    let vblank = { info.qpcVBlank } as f64;
+    let refresh_period = { info.qpcRefreshPeriod } as f64;
+    if frequency <= 0.0 || refresh_period <= 0.0 {
+        return;
+    }
+    let time = crate::event::monotonic_time() - (counter - vblank) / frequency;
+    crate::frame_stats::record_vblank(time, Some(refresh_period / frequency));
+}
+
 pub(crate) struct WindowsDisplay {
     fullscreen: bool,
     dpi_aware: bool,
     window_resizable: bool,
+    window_decorations: bool,
     cursor_grabbed: bool,
+    mouse_captured: bool,
+    mouse_buttons_down: u32,
     iconified: bool,
     content_scale: f32,
     window_scale: f32,
@@ -53,6 +185,13 @@ pub(crate) struct WindowsDisplay {
     event_handler: Option<Box<dyn EventHandler>>,
     modal_resizing_timer: usize,
     update_requested: bool,
+    tray: Option<tray::WindowsTray>,
+    /// With `start_with_render_thread`, `event_handler` only forwards the events
+    render_thread: Option<RenderThread>,
+    /// First half of a surrogate pair, waiting for the second WM_CHAR
+    high_surrogate: u16,
+    /// WM_CHAR units of an IME result already sent to `text_input_event`
+    ime_units_pending: usize,
 }
 
 impl WindowsDisplay {
@@ -66,6 +205,40 @@ impl WindowsDisplay {
             }
         }
     }
+    fn capture_mouse(&mut self, capture: bool) {
+        self.mouse_captured = capture;
+        unsafe {
+            if capture {
+                SetCapture(self.wnd);
+            } else if self.mouse_buttons_down == 0 {
+                ReleaseCapture();
+            }
+        }
+    }
+    // Windows has no implicit grab: capture the mouse while any button is held,
+    // so a drag leaving the window keeps getting moves and the button up
+    fn mouse_button_pressed(&mut self) {
+        if self.mouse_buttons_down == 0 && !self.mouse_captured {
+            unsafe { SetCapture(self.wnd) };
+        }
+        self.mouse_buttons_down += 1;
+    }
+    fn mouse_button_released(&mut self) {
+        self.mouse_buttons_down = self.mouse_buttons_down.saturating_sub(1);
+        if self.mouse_buttons_down == 0 && !self.mouse_captured {
+            unsafe { ReleaseCapture() };
+        }
+    }
+    fn set_mouse_position(&mut self, x: f32, y: f32) {
+        let mut point = POINT {
+            x: (x / self.mouse_scale) as i32,
+            y: (y / self.mouse_scale) as i32,
+        };
+        unsafe {
+            ClientToScreen(self.wnd, &mut point);
+            SetCursorPos(point.x, point.y);
+        }
+    }
     fn show_mouse(&mut self, shown: bool) {
         if self.show_cursor != shown {
             self.show_cursor = shown;
@@ -86,6 +259,21 @@ impl WindowsDisplay {
             CursorIcon::NSResize => IDC_SIZENS,
             CursorIcon::NESWResize => IDC_SIZENESW,
             CursorIcon::NWSEResize => IDC_SIZENWSE,
+            CursorIcon::ContextMenu
+            | CursorIcon::Alias
+            | CursorIcon::Copy
+            | CursorIcon::ZoomIn
+            | CursorIcon::ZoomOut => IDC_ARROW,
+            CursorIcon::Progress => IDC_APPSTARTING,
+            CursorIcon::Cell => IDC_CROSS,
+            CursorIcon::VerticalText => IDC_IBEAM,
+            CursorIcon::NoDrop => IDC_NO,
+            CursorIcon::Grab => IDC_HAND,
+            CursorIcon::Grabbing | CursorIcon::AllScroll => IDC_SIZEALL,
+            CursorIcon::NResize | CursorIcon::SResize | CursorIcon::RowResize => IDC_SIZENS,
+            CursorIcon::EResize | CursorIcon::WResize | CursorIcon::ColResize => IDC_SIZEWE,
+            CursorIcon::NEResize | CursorIcon::SWResize => IDC_SIZENESW,
+            CursorIcon::NWResize | CursorIcon::SEResize => IDC_SIZENWSE,
         };
         self.cursor = unsafe { LoadCursorW(NULL as _, cursor_name) };
         unsafe { SetCursor(self.cursor) };
@@ -107,7 +295,11 @@ impl WindowsDisplay {
         rect.right = (rect.left + new_width as i32) as _;
         rect.top = (rect.bottom - new_height as i32) as _;
 
-        let win_style = get_win_style(self.fullscreen, self.window_resizable);
+        let win_style = get_win_style(
+            self.fullscreen,
+            self.window_resizable,
+            self.window_decorations,
+        );
         let win_style_ex: DWORD = unsafe { GetWindowLongA(self.wnd, GWL_EXSTYLE) as _ };
         if unsafe {
             AdjustWindowRectEx(
@@ -158,7 +350,11 @@ impl WindowsDisplay {
     fn set_fullscreen(&mut self, fullscreen: bool) {
         self.fullscreen = fullscreen as _;
 
-        let win_style: DWORD = get_win_style(self.fullscreen, self.window_resizable);
+        let win_style: DWORD = get_win_style(
+            self.fullscreen,
+            self.window_resizable,
+            self.window_decorations,
+        );
 
         unsafe {
             #[cfg(target_arch = "x86_64")]
@@ -199,9 +395,11 @@ impl WindowsDisplay {
     }
 }
 
-fn get_win_style(is_fullscreen: bool, is_resizable: bool) -> DWORD {
+fn get_win_style(is_fullscreen: bool, is_resizable: bool, is_decorated: bool) -> DWORD {
     if is_fullscreen {
         WS_POPUP | WS_SYSMENU | WS_VISIBLE
+    } else if !is_decorated {
+        WS_POPUP | WS_CLIPSIBLINGS | WS_CLIPCHILDREN
     } else {
         let mut win_style: DWORD =
             WS_CLIPSIBLINGS | WS_CLIPCHILDREN | WS_CAPTION | WS_SYSMENU | WS_MINIMIZEBOX;
@@ -285,9 +483,36 @@ unsafe extern "system" fn win32_wndproc(
     let payload = &mut *(display_ptr as *mut WindowsDisplay);
     let event_handler = payload.event_handler.as_mut().unwrap();
 
+    if matches!(
+        umsg,
+        WM_MOUSEMOVE
+            | WM_LBUTTONDOWN
+            | WM_LBUTTONUP
+            | WM_RBUTTONDOWN
+            | WM_RBUTTONUP
+            | WM_MBUTTONDOWN
+            | WM_MBUTTONUP
+    ) {
+        let extra_info = GetMessageExtraInfo() as usize;
+        if extra_info & MI_WP_SIGNATURE_MASK == MI_WP_SIGNATURE {
+            let kind = if extra_info & MI_WP_TOUCH != 0 {
+                PointerKind::Touch
+            } else {
+                PointerKind::Pen
+            };
+            crate::event::set_pointer_source(kind, None);
+        }
+    }
+
     match umsg {
         WM_CLOSE => {
             let mut d = crate::native_display().lock().unwrap();
+            if let Some(thread) = &payload.render_thread {
+                // The render thread asks the handler and orders the quit
+                d.quit_requested = true;
+                thread.send(render_thread::Message::Redraw);
+                return 0;
+            }
             // only give user a chance to intervene when sapp_quit() wasn't already called
             if !d.quit_ordered {
                 // if window should be closed and event handling is enabled, give user code
@@ -308,6 +533,33 @@ unsafe extern "system" fn win32_wndproc(
             }
             return 0;
         }
+        WM_QUERYENDSESSION => {
+            let mut d = crate::native_display().lock().unwrap();
+            d.quit_requested = true;
+            d.session_ending = true;
+            if let Some(thread) = &payload.render_thread {
+                // Can't wait for the render thread's answer, the session ends
+                thread.send(render_thread::Message::Redraw);
+                return TRUE as _;
+            }
+            if !d.quit_ordered {
+                drop(d);
+                crate::event::dispatch_quit_requested(&mut **event_handler);
+                d = crate::native_display().lock().unwrap();
+                if d.quit_requested {
+                    d.quit_ordered = true;
+                }
+            }
+            // FALSE holds the logout off, Windows asks the user what to do
+            return d.quit_ordered as _;
+        }
+        WM_ENDSESSION => {
+            // The process is terminated once this returns
+            if wparam != 0 && crate::native_display().lock().unwrap().quit_ordered {
+                PostQuitMessage(0);
+            }
+            return 0;
+        }
         WM_SYSCOMMAND => {
             match wparam & 0xFFF0 {
                 SC_SCREENSAVE | SC_MONITORPOWER => {
@@ -330,6 +582,14 @@ unsafe extern "system" fn win32_wndproc(
             if payload.cursor_grabbed {
                 update_clip_rect(hwnd);
             }
+            // During a live resize the frames come from WM_TIMER inside the
+            // modal loop, they have to be laid out for the new size
+            if crate::native_display().lock().unwrap().live_resize
+                && payload.update_dimensions(hwnd)
+            {
+                let (width, height) = crate::window::screen_size();
+                event_handler.resize_event(width, height);
+            }
 
             let iconified = wparam == SIZE_MINIMIZED;
             if iconified != payload.iconified {
@@ -351,38 +611,63 @@ unsafe extern "system" fn win32_wndproc(
         WM_LBUTTONDOWN => {
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
+            payload.mouse_button_pressed();
             event_handler.mouse_button_down_event(MouseButton::Left, mouse_x, mouse_y);
         }
         WM_RBUTTONDOWN => {
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
 
+            payload.mouse_button_pressed();
             event_handler.mouse_button_down_event(MouseButton::Right, mouse_x, mouse_y);
         }
         WM_MBUTTONDOWN => {
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
 
+            payload.mouse_button_pressed();
             event_handler.mouse_button_down_event(MouseButton::Middle, mouse_x, mouse_y);
         }
         WM_LBUTTONUP => {
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
 
+            payload.mouse_button_released();
             event_handler.mouse_button_up_event(MouseButton::Left, mouse_x, mouse_y);
         }
         WM_RBUTTONUP => {
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
 
+            payload.mouse_button_released();
             event_handler.mouse_button_up_event(MouseButton::Right, mouse_x, mouse_y);
         }
         WM_MBUTTONUP => {
             let mouse_x = payload.mouse_x;
             let mouse_y = payload.mouse_y;
 
+            payload.mouse_button_released();
             event_handler.mouse_button_up_event(MouseButton::Middle, mouse_x, mouse_y);
         }
+        WM_XBUTTONDOWN | WM_XBUTTONUP => {
+            let mouse_x = payload.mouse_x;
+            let mouse_y = payload.mouse_y;
+            let button = match GET_XBUTTON_WPARAM(wparam) {
+                XBUTTON1 => MouseButton::X1,
+                XBUTTON2 => MouseButton::X2,
+                _ => MouseButton::Unknown,
+            };
+
+            if umsg == WM_XBUTTONDOWN {
+                payload.mouse_button_pressed();
+                event_handler.mouse_button_down_event(button, mouse_x, mouse_y);
+            } else {
+                payload.mouse_button_released();
+                event_handler.mouse_button_up_event(button, mouse_x, mouse_y);
+            }
+            // Handled, DefWindowProc would also send WM_APPCOMMAND back/forward
+            return 1;
+        }
 
         WM_MOUSEMOVE => {
             payload.mouse_x = GET_X_LPARAM(lparam) as f32 * payload.mouse_scale;
@@ -409,6 +694,12 @@ unsafe extern "system" fn win32_wndproc(
             event_handler.mouse_motion_event(mouse_x, mouse_y);
         }
 
+        WM_CAPTURECHANGED if lparam as HWND != hwnd => {
+            // Another window took the capture, the button ups will go there
+            payload.mouse_buttons_down = 0;
+            payload.mouse_captured = false;
+        }
+
         WM_MOVE if payload.cursor_grabbed => {
             update_clip_rect(hwnd);
         }
@@ -461,13 +752,59 @@ unsafe extern "system" fn win32_wndproc(
             event_handler.mouse_wheel_event((HIWORD(wparam as _) as i16) as f32, 0.0);
         }
         WM_CHAR => {
-            let chr = wparam as u32;
+            let unit = wparam as u16;
             let repeat = !!(lparam & 0x40000000) != 0;
             let mods = key_mods();
-            if chr > 0 {
-                if let Some(chr) = char::from_u32(chr) {
-                    event_handler.char_event(chr, mods, repeat);
+            if (0xD800..0xDC00).contains(&unit) {
+                // Characters outside of the BMP, emojis included, come as two
+                // WM_CHAR, the high surrogate first
+                payload.high_surrogate = unit;
+                return 0;
+            }
+            let high_surrogate = std::mem::replace(&mut payload.high_surrogate, 0);
+            let pair = [high_surrogate, unit];
+            let units = if high_surrogate != 0 && (0xDC00..0xE000).contains(&unit) {
+                &pair[..]
+            } else {
+                &pair[1..]
+            };
+            if unit != 0 {
+                if let Ok(text) = String::from_utf16(units) {
+                    if payload.ime_units_pending > 0 {
+                        // Already sent as a whole by WM_IME_COMPOSITION
+                        payload.ime_units_pending =
+                            payload.ime_units_pending.saturating_sub(units.len());
+                        for chr in text.chars() {
+                            event_handler.char_event(chr, mods, repeat);
+                        }
+                    } else {
+                        crate::event::dispatch_text(&mut **event_handler, &text, mods, repeat);
+                    }
+                }
+            }
+        }
+        WM_IME_COMPOSITION if lparam as DWORD & GCS_RESULTSTR != 0 => {
+            // The result also comes as WM_CHAR messages, one per UTF-16 unit,
+            // once DefWindowProc has seen this one
+            let himc = ImmGetContext(hwnd);
+            if !himc.is_null() {
+                let len = ImmGetCompositionStringW(himc, GCS_RESULTSTR, std::ptr::null_mut(), 0);
+                if len > 0 {
+                    let mut units = vec![0u16; len as usize / 2];
+                    ImmGetCompositionStringW(
+                        himc,
+                        GCS_RESULTSTR,
+                        units.as_mut_ptr() as _,
+                        len as _,
+                    );
+                    let text = String::from_utf16_lossy(&units);
+                    let text: String = text.chars().filter(|c| !c.is_control()).collect();
+                    if !text.is_empty() {
+                        event_handler.text_input_event(&text);
+                    }
+                    payload.ime_units_pending = units.len();
                 }
+                ImmReleaseContext(hwnd, himc);
             }
         }
         WM_KEYDOWN | WM_SYSKEYDOWN => {
@@ -491,12 +828,42 @@ unsafe extern "system" fn win32_wndproc(
                 None,
             );
         }
+        WM_SIZING => {
+            // The first one of a drag, WM_ENTERSIZEMOVE is also sent for moves
+            // WMSZ_LEFT to WMSZ_BOTTOMRIGHT
+            let edge = match wparam {
+                1 => Some(crate::ResizeEdge::Left),
+                2 => Some(crate::ResizeEdge::Right),
+                3 => Some(crate::ResizeEdge::Top),
+                4 => Some(crate::ResizeEdge::TopLeft),
+                5 => Some(crate::ResizeEdge::TopRight),
+                6 => Some(crate::ResizeEdge::Bottom),
+                7 => Some(crate::ResizeEdge::BottomLeft),
+                8 => Some(crate::ResizeEdge::BottomRight),
+                _ => None,
+            };
+            crate::event::dispatch_live_resize(&mut **event_handler, true, edge);
+        }
         WM_TIMER => {
-            if wparam == &mut payload.modal_resizing_timer as *mut _ as usize {
+            if wparam == &mut payload.modal_resizing_timer as *mut _ as usize
+                && payload.render_thread.is_none()
+            {
+                record_dwm_vblank();
+                crate::frame_stats::begin_frame();
+                crate::event::dispatch_errors(payload.event_handler.as_deref_mut().unwrap());
+                crate::accessibility::dispatch_actions(
+                    payload.event_handler.as_deref_mut().unwrap(),
+                );
+                crate::colors::dispatch_picked(payload.event_handler.as_deref_mut().unwrap());
+                crate::message_box::dispatch_answers(payload.event_handler.as_deref_mut().unwrap());
                 payload.event_handler.as_mut().unwrap().update();
                 payload.event_handler.as_mut().unwrap().draw();
 
+                crate::frame_stats::begin_present();
                 SwapBuffers(payload.dc);
+                crate::frame_pacing::sync_after_swap();
+                crate::frame_stats::end_frame();
+                crate::frame_pacing::wait();
 
                 if payload.update_dimensions(hwnd) {
                     let d = crate::native_display().lock().unwrap();
@@ -513,6 +880,7 @@ unsafe extern "system" fn win32_wndproc(
         }
         WM_EXITSIZEMOVE | WM_EXITMENULOOP => {
             KillTimer(hwnd, &mut payload.modal_resizing_timer as *mut _ as usize);
+            crate::event::dispatch_live_resize(&mut **event_handler, false, None);
         }
         WM_DROPFILES => {
             let hdrop = wparam as HDROP;
@@ -537,19 +905,54 @@ unsafe extern "system" fn win32_wndproc(
                 }
             }
         }
+        tray::WM_TRAY => {
+            if let Some(event) = payload.tray.as_ref().and_then(|t| t.handle_message(lparam)) {
+                event_handler.tray_event(event);
+            }
+        }
+        WM_CLIPBOARDUPDATE => {
+            if !clipboard::is_own_change() {
+                event_handler.clipboard_changed_event();
+            }
+        }
         WM_ACTIVATE => {
             if LOWORD(wparam as _) == WA_ACTIVE || LOWORD(wparam as _) == WA_CLICKACTIVE {
+                crate::accessibility::focus_changed(true);
                 event_handler.window_restored_event();
             } else {
+                crate::accessibility::focus_changed(false);
                 event_handler.window_minimized_event();
             }
         }
+        WM_GETOBJECT => {
+            if let Some(result) = crate::accessibility::wm_getobject(wparam, lparam) {
+                return result;
+            }
+        }
         _ => {}
     }
 
     DefWindowProcW(hwnd, umsg, wparam, lparam)
 }
 
+pub(crate) fn key_label(keycode: KeyCode) -> Option<String> {
+    let scan_code = (0..0x200).find(|&code| keycodes::translate_keycode(code) == keycode)?;
+    // MapVirtualKey wants the E0 prefix of extended keys instead of bit 8
+    let scan_code = if scan_code & 0x100 != 0 {
+        0xe000 | (scan_code & 0xff)
+    } else {
+        scan_code
+    };
+    unsafe {
+        let vk = MapVirtualKeyW(scan_code, MAPVK_VSC_TO_VK_EX);
+        // The high bit marks dead keys, the character is in the low word either way
+        let chr = MapVirtualKeyW(vk, MAPVK_VK_TO_CHAR) & 0xffff;
+        char::from_u32(chr)
+            .filter(|chr| *chr != '\0')
+            .map(String::from)
+    }
+}
+
 unsafe fn create_win_icon_from_image(width: u32, height: u32, colors: &[u8]) -> Option<HICON> {
     let mut bi: BITMAPV5HEADER = std::mem::zeroed();
 
@@ -636,14 +1039,7 @@ unsafe fn set_icon(wnd: HWND, icon: &Icon) {
     }
 }
 
-unsafe fn create_window(
-    window_title: &str,
-    fullscreen: bool,
-    resizable: bool,
-    width: i32,
-    height: i32,
-    desktop_center: bool,
-) -> (HWND, HDC) {
+unsafe fn create_window(conf: &Conf) -> (HWND, HDC) {
     let mut wndclassw: WNDCLASSW = std::mem::zeroed();
 
     wndclassw.style = CS_HREDRAW | CS_VREDRAW | CS_OWNDC;
@@ -657,8 +1053,17 @@ unsafe fn create_window(
     wndclassw.cbWndExtra = std::mem::size_of::<*mut std::ffi::c_void>() as i32;
     RegisterClassW(&wndclassw);
 
-    let win_style: DWORD;
-    let win_ex_style: DWORD = WS_EX_APPWINDOW | WS_EX_WINDOWEDGE;
+    let win_style: DWORD = get_win_style(
+        conf.fullscreen,
+        conf.window_resizable,
+        conf.window_decorations,
+    );
+    // A tool window has no taskbar entry, WS_EX_APPWINDOW would force one
+    let win_ex_style: DWORD = if conf.utility_window {
+        WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE
+    } else {
+        WS_EX_APPWINDOW | WS_EX_WINDOWEDGE
+    };
     let mut rect = RECT {
         left: 0,
         top: 0,
@@ -666,35 +1071,22 @@ unsafe fn create_window(
         bottom: 0,
     };
 
-    if fullscreen {
-        win_style = WS_POPUP | WS_SYSMENU | WS_VISIBLE;
+    if conf.fullscreen {
         rect.right = GetSystemMetrics(SM_CXSCREEN);
         rect.bottom = GetSystemMetrics(SM_CYSCREEN);
     } else {
-        win_style = if resizable {
-            WS_CLIPSIBLINGS
-                | WS_CLIPCHILDREN
-                | WS_CAPTION
-                | WS_SYSMENU
-                | WS_MINIMIZEBOX
-                | WS_MAXIMIZEBOX
-                | WS_SIZEBOX
-        } else {
-            WS_CLIPSIBLINGS | WS_CLIPCHILDREN | WS_CAPTION | WS_SYSMENU | WS_MINIMIZEBOX
-        };
-
-        rect.right = width;
-        rect.bottom = height;
+        rect.right = conf.window_width;
+        rect.bottom = conf.window_height;
     }
 
     AdjustWindowRectEx(&rect as *const _ as _, win_style, false as _, win_ex_style);
     let win_width = rect.right - rect.left;
     let win_height = rect.bottom - rect.top;
     let class_name = "MINIQUADAPP\0".encode_utf16().collect::<Vec<u16>>();
-    let mut window_name = window_title.encode_utf16().collect::<Vec<u16>>();
+    let mut window_name = conf.window_title.encode_utf16().collect::<Vec<u16>>();
     window_name.push(0);
 
-    let (win_x, win_y) = if desktop_center {
+    let (win_x, win_y) = if conf.desktop_center {
         // For desktop centering, calculate center position
         let screen_width = GetSystemMetrics(SM_CXSCREEN);
         let screen_height = GetSystemMetrics(SM_CYSCREEN);
@@ -735,11 +1127,20 @@ unsafe fn create_window(
         "Win32: failed to register for raw mouse input!"
     );
 
-    ShowWindow(hwnd, SW_SHOW);
+    ShowWindow(
+        hwnd,
+        if conf.utility_window {
+            SW_SHOWNOACTIVATE
+        } else {
+            SW_SHOW
+        },
+    );
     let dc = GetDC(hwnd);
     assert!(!dc.is_null());
 
     DragAcceptFiles(hwnd, TRUE);
+    // WM_CLIPBOARDUPDATE
+    AddClipboardFormatListener(hwnd);
 
     (hwnd, dc)
 }
@@ -790,7 +1191,10 @@ impl WindowsDisplay {
             proc_ptr = GetProcAddress(self.libopengl32.module.0, proc.as_ptr());
         }
         if proc_ptr.is_null() {
-            eprintln!("Load GL func {:?} failed.", proc);
+            crate::error::report_error(crate::error::PlatformError::LibraryLoadFailed(format!(
+                "Load GL func {:?} failed.",
+                proc
+            )));
             return None;
         }
         Some(std::mem::transmute::<
@@ -881,6 +1285,8 @@ impl WindowsDisplay {
             }
             SetCursorGrab(grab) => self.set_cursor_grab(grab),
             ShowMouse(show) => self.show_mouse(show),
+            SetMousePosition { x, y } => self.set_mouse_position(x, y),
+            CaptureMouse(capture) => self.capture_mouse(capture),
             SetMouseCursor(icon) => self.set_mouse_cursor(icon),
             SetWindowSize {
                 new_width,
@@ -888,13 +1294,86 @@ impl WindowsDisplay {
             } => self.set_window_size(new_width as _, new_height as _),
             SetWindowPosition { new_x, new_y } => self.set_window_position(new_x, new_y),
             SetFullscreen(fullscreen) => self.set_fullscreen(fullscreen),
+            SetWindowVisible(visible) => unsafe {
+                ShowWindow(self.wnd, if visible { SW_SHOW } else { SW_HIDE });
+            },
+            SetTray(tray) => unsafe {
+                match (self.tray.as_mut(), tray) {
+                    (_, None) => self.tray = None,
+                    (Some(windows_tray), Some(tray)) => windows_tray.set(*tray),
+                    (None, Some(tray)) => self.tray = Some(tray::WindowsTray::new(self.wnd, *tray)),
+                }
+            },
+            // Needs the GL context, handled by the main loop
+            SetSwapInterval(..) => {}
             ShowKeyboard(_show) => {
-                eprintln!("Not implemented for windows")
+                crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+                    "ShowKeyboard is not implemented for windows".to_string(),
+                ))
+            }
+            MessageBox(request) => request.run(),
+            PickColor(initial) => {
+                crate::colors::report_picked(unsafe { choose_color(self.wnd, initial) })
             }
         }
     }
 }
 
+fn colorref_to_rgba(colorref: DWORD) -> (f32, f32, f32, f32) {
+    let channel = |shift: u32| ((colorref >> shift) & 0xff) as f32 / 255.;
+    (channel(0), channel(8), channel(16), 1.)
+}
+
+/// The modal ChooseColor dialog, the window keeps being painted meanwhile
+unsafe fn choose_color(wnd: HWND, initial: (f32, f32, f32, f32)) -> Option<(f32, f32, f32, f32)> {
+    // The dialog's custom colors row, kept for the next time it is opened
+    static mut CUSTOM_COLORS: [DWORD; 16] = [0x00ff_ffff; 16];
+
+    let channel = |c: f32| (c.clamp(0., 1.) * 255.).round() as DWORD;
+    let mut cc = CHOOSECOLORW {
+        lStructSize: std::mem::size_of::<CHOOSECOLORW>() as _,
+        hwndOwner: wnd,
+        hInstance: NULL as _,
+        rgbResult: channel(initial.0) | channel(initial.1) << 8 | channel(initial.2) << 16,
+        lpCustColors: std::ptr::addr_of_mut!(CUSTOM_COLORS) as *mut DWORD,
+        Flags: CC_RGBINIT | CC_FULLOPEN,
+        lCustData: 0,
+        lpfnHook: NULL as _,
+        lpTemplateName: NULL as _,
+    };
+    if ChooseColorW(&mut cc) != 0 {
+        // No alpha in the dialog, the initial one is kept
+        let (r, g, b, _) = colorref_to_rgba(cc.rgbResult);
+        Some((r, g, b, initial.3))
+    } else {
+        None
+    }
+}
+
+pub(crate) fn system_color(color: crate::colors::SystemColor) -> Option<(f32, f32, f32, f32)> {
+    use crate::colors::SystemColor;
+
+    unsafe {
+        let index = match color {
+            SystemColor::Accent => {
+                // 0xAARRGGBB
+                let mut argb: DWORD = 0;
+                let mut opaque = 0;
+                if DwmGetColorizationColor(&mut argb, &mut opaque) < 0 {
+                    return None;
+                }
+                let channel = |shift: u32| ((argb >> shift) & 0xff) as f32 / 255.;
+                return Some((channel(16), channel(8), channel(0), 1.));
+            }
+            SystemColor::Highlight => COLOR_HIGHLIGHT,
+            SystemColor::HighlightText => COLOR_HIGHLIGHTTEXT,
+            SystemColor::WindowBackground => COLOR_WINDOW,
+            SystemColor::WindowText => COLOR_WINDOWTEXT,
+        };
+        Some(colorref_to_rgba(GetSysColor(index)))
+    }
+}
+
 pub fn run<F>(conf: &Conf, f: F)
 where
     F: 'static + FnOnce() -> Box<dyn EventHandler>,
@@ -903,18 +1382,56 @@ where
         if conf.high_dpi {
             SetProcessDPIAware();
         }
-        let (wnd, dc) = create_window(
-            &conf.window_title,
-            conf.fullscreen,
-            conf.window_resizable,
-            conf.window_width as _,
-            conf.window_height as _,
-            conf.desktop_center,
-        );
+        if conf.platform.gpu_preference == GpuPreference::HighPerformance {
+            NvOptimusEnablement = 1;
+            AmdPowerXpressRequestHighPerformance = 1;
+        }
+        crate::event::set_double_click_settings(crate::event::DoubleClickSettings {
+            time: GetDoubleClickTime() as f64 / 1000.,
+            // Size of the rectangle around the first click
+            distance: GetSystemMetrics(SM_CXDOUBLECLK) as f32 / 2.,
+        });
+        // 0..=3 for 250ms..=1s, and 0..=31 for about 2.5..=30 repeats per second
+        let (mut delay, mut speed): (UINT, UINT) = (1, 31);
+        SystemParametersInfoW(SPI_GETKEYBOARDDELAY, 0, &mut delay as *mut _ as _, 0);
+        SystemParametersInfoW(SPI_GETKEYBOARDSPEED, 0, &mut speed as *mut _ as _, 0);
+        crate::event::set_key_repeat_settings(crate::event::KeyRepeatSettings {
+            delay: (delay + 1) as f64 * 0.25,
+            rate: 2.5 + speed as f64 * 27.5 / 31.,
+        });
+        let (wnd, dc) = create_window(&conf);
         if let Some(icon) = &conf.icon {
             set_icon(wnd, icon);
         }
 
+        let block_shortcuts = conf.kiosk.is_some_and(|k| k.block_system_shortcuts);
+        let inhibit_screensaver = conf.kiosk.is_some_and(|k| k.inhibit_screensaver);
+        let keyboard_hook = if block_shortcuts {
+            KIOSK_WINDOW.store(wnd as usize, Ordering::Relaxed);
+            SetWindowsHookExW(
+                WH_KEYBOARD_LL,
+                Some(kiosk_keyboard_hook),
+                GetModuleHandleW(NULL as _),
+                0,
+            )
+        } else {
+            std::ptr::null_mut()
+        };
+        if inhibit_screensaver {
+            SetThreadExecutionState(ES_CONTINUOUS | ES_DISPLAY_REQUIRED | ES_SYSTEM_REQUIRED);
+        }
+        crate::crash::set_restore(&conf.window_title, move || {
+            ClipCursor(NULL as _);
+            ReleaseCapture();
+            while ShowCursor(TRUE) < 0 {}
+            ChangeDisplaySettingsW(NULL as _, 0);
+            if !keyboard_hook.is_null() {
+                UnhookWindowsHookEx(keyboard_hook);
+            }
+            SetThreadExecutionState(ES_CONTINUOUS);
+            ShowWindow(wnd, SW_HIDE);
+        });
+
         let libopengl32 = LibOpengl32::try_load().expect("Failed to load opengl32.dll.");
 
         let (msg_wnd, msg_dc) = create_msg_window();
@@ -922,7 +1439,10 @@ where
             fullscreen: false,
             dpi_aware: false,
             window_resizable: conf.window_resizable,
+            window_decorations: conf.window_decorations,
             cursor_grabbed: false,
+            mouse_captured: false,
+            mouse_buttons_down: 0,
             iconified: false,
             content_scale: 1.,
             mouse_scale: 1.,
@@ -940,6 +1460,10 @@ where
             event_handler: None,
             modal_resizing_timer: 0,
             update_requested: true,
+            tray: None,
+            render_thread: None,
+            high_surrogate: 0,
+            ime_units_pending: 0,
         };
         display.init_dpi(conf.high_dpi);
 
@@ -949,22 +1473,47 @@ where
             high_dpi: conf.high_dpi,
             dpi_scale: display.window_scale,
             blocking_event_loop: conf.platform.blocking_event_loop,
+            window_handle: Some(crate::WindowHandle::Win32 {
+                hwnd: wnd as _,
+                hinstance: GetModuleHandleW(NULL as _) as _,
+            }),
             ..NativeDisplayData::new(conf.window_width, conf.window_height, tx, clipboard)
         });
 
         display.update_dimensions(wnd);
 
         let mut wgl = wgl::Wgl::new(&mut display);
-        let gl_ctx = wgl.create_context(
+        let (gl_ctx, swap_interval) = wgl.create_context(
             &mut display,
             conf.sample_count,
             conf.platform.swap_interval.unwrap_or(1),
         );
+        {
+            let mut d = crate::native_display().lock().unwrap();
+            d.swap_interval = crate::SwapInterval::from_gl(swap_interval);
+            d.context_handle = Some(crate::ContextHandle::Wgl {
+                hdc: dc as _,
+                hglrc: gl_ctx as _,
+            });
+        }
 
         super::gl::load_gl_funcs(|proc| display.get_proc_address(proc));
 
         display.event_handler = Some(f());
 
+        if render_thread::is_pending() {
+            (display.libopengl32.wglMakeCurrent)(NULL as _, NULL as _);
+            let surface = WglSurface {
+                wnd,
+                dc,
+                gl_ctx,
+                make_current: display.libopengl32.wglMakeCurrent,
+                wgl: wgl.clone(),
+            };
+            display.render_thread =
+                render_thread::spawn(surface, conf.platform.blocking_event_loop);
+        }
+
         #[cfg(target_arch = "x86_64")]
         SetWindowLongPtrA(wnd, GWLP_USERDATA, &mut display as *mut _ as isize);
         #[cfg(target_arch = "x86")]
@@ -973,6 +1522,18 @@ where
         let mut done = false;
         while !(done || crate::native_display().lock().unwrap().quit_ordered) {
             while let Ok(request) = rx.try_recv() {
+                if let (Some(thread), Request::SetSwapInterval(interval)) =
+                    (&display.render_thread, &request)
+                {
+                    thread.send(render_thread::Message::SwapInterval(*interval));
+                    continue;
+                }
+                if let Request::SetSwapInterval(interval) = request {
+                    let applied = wgl.swap_interval(interval.to_gl());
+                    crate::native_display().lock().unwrap().swap_interval =
+                        crate::SwapInterval::from_gl(applied);
+                    continue;
+                }
                 display.process_request(request);
             }
 
@@ -985,7 +1546,9 @@ where
                 }
             };
             let mut msg: MSG = std::mem::zeroed();
-            let block_on_wait = conf.platform.blocking_event_loop && !display.update_requested;
+            // The render thread wakes this one up after each frame
+            let block_on_wait = display.render_thread.is_some()
+                || (conf.platform.blocking_event_loop && !display.update_requested);
             if block_on_wait {
                 GetMessageW(&mut msg as *mut _ as _, NULL as _, 0, 0);
                 dispatch_message(msg);
@@ -995,12 +1558,29 @@ where
                 }
             }
 
-            if !conf.platform.blocking_event_loop || display.update_requested {
+            if let Some(thread) = &display.render_thread {
+                if display.update_requested {
+                    display.update_requested = false;
+                    thread.send(render_thread::Message::Redraw);
+                }
+            } else if !conf.platform.blocking_event_loop || display.update_requested {
                 display.update_requested = false;
+                record_dwm_vblank();
+                crate::frame_stats::begin_frame();
+                crate::event::dispatch_errors(display.event_handler.as_deref_mut().unwrap());
+                crate::accessibility::dispatch_actions(
+                    display.event_handler.as_deref_mut().unwrap(),
+                );
+                crate::colors::dispatch_picked(display.event_handler.as_deref_mut().unwrap());
+                crate::message_box::dispatch_answers(display.event_handler.as_deref_mut().unwrap());
                 display.event_handler.as_mut().unwrap().update();
                 display.event_handler.as_mut().unwrap().draw();
 
+                crate::frame_stats::begin_present();
                 SwapBuffers(display.dc);
+                crate::frame_pacing::sync_after_swap();
+                crate::frame_stats::end_frame();
+                crate::frame_pacing::wait();
             }
 
             if display.update_dimensions(wnd) {
@@ -1014,16 +1594,88 @@ where
                     .unwrap()
                     .resize_event(width, height);
             }
-            if crate::native_display().lock().unwrap().quit_requested {
+            // The render thread takes care of the requests made on it
+            if display.render_thread.is_none()
+                && crate::native_display().lock().unwrap().quit_requested
+            {
                 PostMessageW(display.wnd, WM_CLOSE, 0, 0);
             }
         }
 
+        if let Some(thread) = &mut display.render_thread {
+            thread.join();
+        }
         (display.libopengl32.wglDeleteContext)(gl_ctx);
+        display.tray = None;
+        crate::accessibility::remove_adapter();
+        if !keyboard_hook.is_null() {
+            UnhookWindowsHookEx(keyboard_hook);
+        }
+        if inhibit_screensaver {
+            SetThreadExecutionState(ES_CONTINUOUS);
+        }
+        crate::crash::clear_restore();
         DestroyWindow(wnd);
     }
 }
 
+/// Works without a window and from any thread, `error` for `crash`
+pub(crate) fn message_box(
+    title: &str,
+    text: &str,
+    buttons: crate::MessageBoxButtons,
+    error: bool,
+) -> crate::MessageBoxButton {
+    use crate::{MessageBoxButton, MessageBoxButtons};
+
+    let title: Vec<u16> = title.encode_utf16().chain(Some(0)).collect();
+    let text: Vec<u16> = text.encode_utf16().chain(Some(0)).collect();
+    let mut flags = match buttons {
+        MessageBoxButtons::Ok => MB_OK,
+        MessageBoxButtons::OkCancel => MB_OKCANCEL,
+        MessageBoxButtons::YesNo => MB_YESNO,
+        MessageBoxButtons::YesNoCancel => MB_YESNOCANCEL,
+    } | MB_SETFOREGROUND;
+    if error {
+        flags |= MB_ICONERROR | MB_TOPMOST;
+    }
+    match unsafe { MessageBoxW(NULL as _, text.as_ptr(), title.as_ptr(), flags) } {
+        IDOK => MessageBoxButton::Ok,
+        IDYES => MessageBoxButton::Yes,
+        IDNO => MessageBoxButton::No,
+        IDCANCEL => MessageBoxButton::Cancel,
+        _ => buttons.dismissed(),
+    }
+}
+
+/// The window `kiosk_keyboard_hook` guards
+static KIOSK_WINDOW: AtomicUsize = AtomicUsize::new(0);
+
+/// Low level keyboard hook for `Kiosk::block_system_shortcuts`: swallows the
+/// shortcuts switching away from the window while it is in the foreground.
+/// Runs on the thread that installed it, from its message loop.
+unsafe extern "system" fn kiosk_keyboard_hook(
+    code: i32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    if code == HC_ACTION && GetForegroundWindow() as usize == KIOSK_WINDOW.load(Ordering::Relaxed) {
+        let key = &*(lparam as *const KBDLLHOOKSTRUCT);
+        let alt = key.flags & LLKHF_ALTDOWN != 0;
+        let ctrl = GetAsyncKeyState(VK_CONTROL) as u16 & 0x8000 != 0;
+        let blocked = match key.vkCode as i32 {
+            VK_TAB => alt,
+            VK_ESCAPE => alt || ctrl,
+            VK_LWIN | VK_RWIN => true,
+            _ => false,
+        };
+        if blocked {
+            return 1;
+        }
+    }
+    CallNextHookEx(std::ptr::null_mut(), code, wparam, lparam)
+}
+
 pub fn primary_monitor() -> crate::MonitorMetrics {
     use winapi::um::winuser::*;
     unsafe {
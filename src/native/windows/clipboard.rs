@@ -1,10 +1,20 @@
 use winapi::um::winbase::{GlobalAlloc, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE};
 use winapi::um::winuser::CF_UNICODETEXT;
 use winapi::um::winuser::{
-    CloseClipboard, EmptyClipboard, GetClipboardData, OpenClipboard, SetClipboardData,
+    CloseClipboard, EmptyClipboard, GetClipboardData, GetClipboardSequenceNumber, OpenClipboard,
+    SetClipboardData,
 };
 
 use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Clipboard sequence number right after our last `set`, to tell our own
+// WM_CLIPBOARDUPDATE from other applications'
+static OWN_SEQUENCE_NUMBER: AtomicU32 = AtomicU32::new(0);
+
+pub fn is_own_change() -> bool {
+    unsafe { GetClipboardSequenceNumber() == OWN_SEQUENCE_NUMBER.load(Ordering::Relaxed) }
+}
 
 struct ClipboardGuard;
 impl ClipboardGuard {
@@ -31,7 +41,9 @@ unsafe fn get_raw_clipboard() -> Option<Vec<u16>> {
     let guard = ClipboardGuard::open();
 
     if guard.is_none() {
-        eprintln!("Failed to open clipboard");
+        crate::error::report_error(crate::error::PlatformError::OsError(
+            "Failed to open clipboard".to_string(),
+        ));
         return None;
     }
 
@@ -62,14 +74,18 @@ unsafe fn set_raw_clipboard(data: *const u8, len: usize) {
     let guard = ClipboardGuard::open();
 
     if guard.is_none() {
-        eprintln!("Failed to open clipboard");
+        crate::error::report_error(crate::error::PlatformError::OsError(
+            "Failed to open clipboard".to_string(),
+        ));
         return;
     }
 
     let alloc_handle = GlobalAlloc(GMEM_MOVEABLE, len);
 
     if alloc_handle.is_null() {
-        eprintln!("Failed to set clipboard: memory not allocated");
+        crate::error::report_error(crate::error::PlatformError::OsError(
+            "Failed to set clipboard: memory not allocated".to_string(),
+        ));
         return;
     }
 
@@ -97,6 +113,7 @@ impl crate::native::Clipboard for WindowsClipboard {
         unsafe {
             let text_w = format!("{}\0", data).encode_utf16().collect::<Vec<u16>>();
             set_raw_clipboard(text_w.as_ptr() as _, text_w.len() * 2);
+            OWN_SEQUENCE_NUMBER.store(GetClipboardSequenceNumber(), Ordering::Relaxed);
         }
     }
 }
@@ -0,0 +1,135 @@
+use winapi::{
+    shared::{
+        minwindef::{LPARAM, UINT},
+        windef::{HICON, HWND, POINT},
+    },
+    um::{shellapi::*, winuser::*},
+};
+
+use crate::tray::{Tray, TrayEvent, TrayMenuItem};
+
+/// Sent to the main window by the notification area, with the mouse message in lparam
+pub const WM_TRAY: UINT = WM_APP + 1;
+
+pub struct WindowsTray {
+    wnd: HWND,
+    icon: HICON,
+    menu: Vec<TrayMenuItem>,
+}
+
+fn to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+impl WindowsTray {
+    pub unsafe fn new(wnd: HWND, tray: Tray) -> WindowsTray {
+        let mut windows_tray = WindowsTray {
+            wnd,
+            icon: std::ptr::null_mut(),
+            menu: vec![],
+        };
+        windows_tray.notify_icon(NIM_ADD, tray);
+        windows_tray
+    }
+
+    pub unsafe fn set(&mut self, tray: Tray) {
+        self.notify_icon(NIM_MODIFY, tray);
+    }
+
+    unsafe fn notify_icon(&mut self, message: u32, tray: Tray) {
+        let icon = if GetSystemMetrics(SM_CXSMICON) <= 16 {
+            super::create_win_icon_from_image(16, 16, &tray.icon.small)
+        } else {
+            super::create_win_icon_from_image(32, 32, &tray.icon.medium)
+        };
+
+        let mut data: NOTIFYICONDATAW = std::mem::zeroed();
+        data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as _;
+        data.hWnd = self.wnd;
+        data.uID = 1;
+        data.uFlags = NIF_MESSAGE | NIF_ICON | NIF_TIP;
+        data.uCallbackMessage = WM_TRAY;
+        data.hIcon = icon.unwrap_or(std::ptr::null_mut());
+        let tooltip = to_wide(&tray.tooltip);
+        let len = tooltip.len().min(data.szTip.len() - 1);
+        data.szTip[..len].copy_from_slice(&tooltip[..len]);
+
+        if Shell_NotifyIconW(message, &mut data) == 0 {
+            crate::error::report_error(crate::error::PlatformError::OsError(
+                "Shell_NotifyIconW failed, the tray icon is not shown".to_string(),
+            ));
+        }
+
+        if !self.icon.is_null() {
+            DestroyIcon(self.icon);
+        }
+        self.icon = data.hIcon;
+        self.menu = tray.menu;
+    }
+
+    /// Handle `WM_TRAY`
+    pub unsafe fn handle_message(&self, lparam: LPARAM) -> Option<TrayEvent> {
+        match lparam as UINT {
+            WM_LBUTTONUP => Some(TrayEvent::Click),
+            WM_RBUTTONUP if !self.menu.is_empty() => self.track_menu(),
+            _ => None,
+        }
+    }
+
+    unsafe fn track_menu(&self) -> Option<TrayEvent> {
+        let menu = CreatePopupMenu();
+        for (i, item) in self.menu.iter().enumerate() {
+            match item {
+                TrayMenuItem::Item { label, enabled, .. } => {
+                    let flags = if *enabled {
+                        MF_STRING
+                    } else {
+                        MF_STRING | MF_GRAYED
+                    };
+                    // 0 is what TrackPopupMenu returns when nothing is chosen
+                    AppendMenuW(menu, flags, i + 1, to_wide(label).as_ptr());
+                }
+                TrayMenuItem::Separator => {
+                    AppendMenuW(menu, MF_SEPARATOR, 0, std::ptr::null());
+                }
+            }
+        }
+
+        let mut point = POINT { x: 0, y: 0 };
+        GetCursorPos(&mut point);
+        // Without the foreground window the menu does not close when clicking elsewhere,
+        // and without the WM_NULL it does not open the second time
+        SetForegroundWindow(self.wnd);
+        let command = TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON,
+            point.x,
+            point.y,
+            0,
+            self.wnd,
+            std::ptr::null(),
+        );
+        PostMessageW(self.wnd, WM_NULL, 0, 0);
+        DestroyMenu(menu);
+
+        match self.menu.get((command as usize).checked_sub(1)?)? {
+            TrayMenuItem::Item { id, .. } => Some(TrayEvent::MenuItem(*id)),
+            TrayMenuItem::Separator => None,
+        }
+    }
+}
+
+impl Drop for WindowsTray {
+    fn drop(&mut self) {
+        unsafe {
+            let mut data: NOTIFYICONDATAW = std::mem::zeroed();
+            data.cbSize = std::mem::size_of::<NOTIFYICONDATAW>() as _;
+            data.hWnd = self.wnd;
+            data.uID = 1;
+            Shell_NotifyIconW(NIM_DELETE, &mut data);
+            if !self.icon.is_null() {
+                DestroyIcon(self.icon);
+            }
+        }
+    }
+}
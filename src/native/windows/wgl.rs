@@ -187,6 +187,7 @@ pub unsafe fn gl_choose_fbconfig(
     closest
 }
 
+#[derive(Clone)]
 pub struct Wgl {
     GetPixelFormatAttribivARB: Option<GetPixelFormatAttribivARB>,
     GetExtensionsStringEXT: Option<GetExtensionsStringEXT>,
@@ -198,6 +199,7 @@ pub struct Wgl {
     arb_create_context: bool,
     arb_create_context_profile: bool,
     ext_swap_control: bool,
+    ext_swap_control_tear: bool,
     arb_pixel_format: bool,
 }
 
@@ -275,6 +277,7 @@ impl Wgl {
         let arb_create_context = wgl_ext_supported("WGL_ARB_create_context");
         let arb_create_context_profile = wgl_ext_supported("WGL_ARB_create_context_profile");
         let ext_swap_control = wgl_ext_supported("WGL_EXT_swap_control");
+        let ext_swap_control_tear = wgl_ext_supported("WGL_EXT_swap_control_tear");
         let arb_pixel_format = wgl_ext_supported("WGL_ARB_pixel_format");
         assert!(arb_pixel_format, "WGL_ARB_pixel_format is required");
 
@@ -291,6 +294,7 @@ impl Wgl {
             arb_create_context,
             arb_create_context_profile,
             ext_swap_control,
+            ext_swap_control_tear,
             arb_pixel_format,
         }
     }
@@ -379,7 +383,7 @@ impl Wgl {
         display: &mut WindowsDisplay,
         sample_count: i32,
         swap_interval: i32,
-    ) -> HGLRC {
+    ) -> (HGLRC, i32) {
         let pixel_format = self.wgl_find_pixel_format(display, sample_count);
         if 0 == pixel_format {
             panic!("WGL: Didn't find matching pixel format.");
@@ -427,7 +431,9 @@ impl Wgl {
         );
 
         if gl_ctx.is_null() {
-            eprintln!("WGL: failed to create 3.2 context, trying 2.1");
+            crate::error::report_error(crate::error::GraphicsError::CreationFailed(
+                "WGL: failed to create 3.2 context, trying 2.1".to_string(),
+            ));
 
             let attrs = [
                 WGL_CONTEXT_MAJOR_VERSION_ARB,
@@ -458,11 +464,24 @@ impl Wgl {
             }
         }
         (display.libopengl32.wglMakeCurrent)(display.dc, gl_ctx);
-        if self.ext_swap_control {
-            /* FIXME: DwmIsCompositionEnabled() (see GLFW) */
-            (self.SwapIntervalEXT.unwrap())(swap_interval);
-        }
+        let swap_interval = self.swap_interval(swap_interval);
+
+        (gl_ctx, swap_interval)
+    }
 
-        gl_ctx
+    /// Set the swap interval of the current context, returns the interval applied.
+    /// Negative (adaptive) needs WGL_EXT_swap_control_tear.
+    pub(crate) unsafe fn swap_interval(&self, interval: i32) -> i32 {
+        if !self.ext_swap_control {
+            return 1;
+        }
+        let interval = if interval < 0 && !self.ext_swap_control_tear {
+            1
+        } else {
+            interval
+        };
+        /* FIXME: DwmIsCompositionEnabled() (see GLFW) */
+        (self.SwapIntervalEXT.unwrap())(interval);
+        interval
     }
 }
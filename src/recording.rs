@@ -0,0 +1,802 @@
+//! Input recording and deterministic replay
+//!
+//! `Recorder` wraps an `EventHandler`, forwards every callback to it and writes the
+//! input events to a file. `Player` reads such a file back and feeds the events to
+//! another handler, frame by frame: events recorded before the Nth `update` are
+//! delivered right before the Nth `update` of the replay, whatever the real frame
+//! times are. Live input is ignored while replaying.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::recording::*;
+//! # struct Stage;
+//! # impl EventHandler for Stage { fn update(&mut self) {} fn draw(&mut self) {} }
+//! // record a session
+//! miniquad::start(conf::Conf::default(), || {
+//!     Box::new(Recorder::to_file("session.mqrec", Stage).unwrap())
+//! });
+//!
+//! // and replay it headlessly in a test
+//! let mut player = Player::from_file("session.mqrec", Stage).unwrap();
+//! while !player.is_finished() {
+//!     player.update();
+//! }
+//! ```
+//!
+//! The file is plain text, one event per line:
+//! `<frame> <seconds since the recording started> <event> <arguments>`.
+
+use crate::error::{report_error, MiniquadError, PlatformError};
+use crate::event::*;
+use crate::tray::TrayEvent;
+use crate::MessageBoxButton;
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+
+const HEADER: &str = "miniquad-recording 1";
+
+/// An input callback of `EventHandler`, with its arguments
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    Resize(f32, f32),
+    LiveResize(bool, Option<ResizeEdge>),
+    MouseMotion(f32, f32),
+    MouseWheel(f32, f32),
+    MouseButtonDown(MouseButton, f32, f32),
+    MouseButtonUp(MouseButton, f32, f32),
+    DoubleClick(MouseButton, f32, f32),
+    Char(char, KeyMods, bool),
+    TextInput(String),
+    KeyDown(KeyCode, KeyMods, bool),
+    KeyUp(KeyCode, KeyMods),
+    Touch(TouchPhase, u64, f32, f32),
+    Pointer(PointerEvent),
+    RawMouseMotion(f32, f32),
+    WindowMinimized,
+    WindowRestored,
+    QuitRequested,
+    SessionEnd,
+    LowMemory,
+    /// Only the event is recorded, not the dropped files
+    FilesDropped,
+    /// Only the event is recorded, not the clipboard contents
+    ClipboardChanged,
+    Tray(TrayEvent),
+    ColorPicked(Option<(f32, f32, f32, f32)>),
+    MessageBox(u32, MessageBoxButton),
+}
+
+impl Event {
+    /// Call the matching callback of `handler`
+    pub fn dispatch(&self, handler: &mut dyn EventHandler) {
+        match *self {
+            Event::Resize(w, h) => handler.resize_event(w, h),
+            Event::LiveResize(active, edge) => handler.live_resize_event(active, edge),
+            Event::MouseMotion(x, y) => handler.mouse_motion_event(x, y),
+            Event::MouseWheel(x, y) => handler.mouse_wheel_event(x, y),
+            Event::MouseButtonDown(button, x, y) => handler.mouse_button_down_event(button, x, y),
+            Event::MouseButtonUp(button, x, y) => handler.mouse_button_up_event(button, x, y),
+            Event::DoubleClick(button, x, y) => handler.double_click_event(button, x, y),
+            Event::Char(character, keymods, repeat) => {
+                handler.char_event(character, keymods, repeat)
+            }
+            Event::TextInput(ref text) => handler.text_input_event(text),
+            Event::KeyDown(keycode, keymods, repeat) => {
+                handler.key_down_event(keycode, keymods, repeat)
+            }
+            Event::KeyUp(keycode, keymods) => handler.key_up_event(keycode, keymods),
+            Event::Touch(phase, id, x, y) => handler.touch_event(phase, id, x, y),
+            Event::Pointer(event) => handler.pointer_event(event),
+            Event::RawMouseMotion(dx, dy) => handler.raw_mouse_motion(dx, dy),
+            Event::WindowMinimized => handler.window_minimized_event(),
+            Event::WindowRestored => handler.window_restored_event(),
+            Event::QuitRequested => handler.quit_requested_event(),
+            Event::SessionEnd => handler.session_end_event(),
+            Event::LowMemory => handler.low_memory_event(),
+            Event::FilesDropped => handler.files_dropped_event(),
+            Event::ClipboardChanged => handler.clipboard_changed_event(),
+            Event::Tray(event) => handler.tray_event(event),
+            Event::ColorPicked(color) => handler.color_picked_event(color),
+            Event::MessageBox(id, button) => handler.message_box_event(id, button),
+        }
+    }
+}
+
+/// An `Event` with the frame it happened in and its time, in seconds since the
+/// recording started
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecordedEvent {
+    pub frame: u64,
+    pub time: f64,
+    pub event: Event,
+}
+
+fn mouse_button_name(button: MouseButton) -> String {
+    match button {
+        MouseButton::Left => "left".to_string(),
+        MouseButton::Middle => "middle".to_string(),
+        MouseButton::Right => "right".to_string(),
+        MouseButton::X1 => "x1".to_string(),
+        MouseButton::X2 => "x2".to_string(),
+        MouseButton::Other(n) => format!("other{}", n),
+        MouseButton::Unknown => "unknown".to_string(),
+    }
+}
+
+fn parse_mouse_button(s: &str) -> Option<MouseButton> {
+    Some(match s {
+        "left" => MouseButton::Left,
+        "middle" => MouseButton::Middle,
+        "right" => MouseButton::Right,
+        "x1" => MouseButton::X1,
+        "x2" => MouseButton::X2,
+        "unknown" => MouseButton::Unknown,
+        _ => MouseButton::Other(s.strip_prefix("other")?.parse().ok()?),
+    })
+}
+
+fn resize_edge_name(edge: Option<ResizeEdge>) -> &'static str {
+    match edge {
+        Some(ResizeEdge::Left) => "left",
+        Some(ResizeEdge::Right) => "right",
+        Some(ResizeEdge::Top) => "top",
+        Some(ResizeEdge::Bottom) => "bottom",
+        Some(ResizeEdge::TopLeft) => "top_left",
+        Some(ResizeEdge::TopRight) => "top_right",
+        Some(ResizeEdge::BottomLeft) => "bottom_left",
+        Some(ResizeEdge::BottomRight) => "bottom_right",
+        None => "none",
+    }
+}
+
+fn parse_resize_edge(s: &str) -> Option<Option<ResizeEdge>> {
+    Some(Some(match s {
+        "left" => ResizeEdge::Left,
+        "right" => ResizeEdge::Right,
+        "top" => ResizeEdge::Top,
+        "bottom" => ResizeEdge::Bottom,
+        "top_left" => ResizeEdge::TopLeft,
+        "top_right" => ResizeEdge::TopRight,
+        "bottom_left" => ResizeEdge::BottomLeft,
+        "bottom_right" => ResizeEdge::BottomRight,
+        "none" => return Some(None),
+        _ => return None,
+    }))
+}
+
+fn message_box_button_name(button: MessageBoxButton) -> &'static str {
+    match button {
+        MessageBoxButton::Ok => "ok",
+        MessageBoxButton::Cancel => "cancel",
+        MessageBoxButton::Yes => "yes",
+        MessageBoxButton::No => "no",
+    }
+}
+
+fn parse_message_box_button(s: &str) -> Option<MessageBoxButton> {
+    Some(match s {
+        "ok" => MessageBoxButton::Ok,
+        "cancel" => MessageBoxButton::Cancel,
+        "yes" => MessageBoxButton::Yes,
+        "no" => MessageBoxButton::No,
+        _ => return None,
+    })
+}
+
+fn touch_phase_name(phase: TouchPhase) -> &'static str {
+    match phase {
+        TouchPhase::Started => "started",
+        TouchPhase::Moved => "moved",
+        TouchPhase::Ended => "ended",
+        TouchPhase::Cancelled => "cancelled",
+    }
+}
+
+fn parse_touch_phase(s: &str) -> Option<TouchPhase> {
+    Some(match s {
+        "started" => TouchPhase::Started,
+        "moved" => TouchPhase::Moved,
+        "ended" => TouchPhase::Ended,
+        "cancelled" => TouchPhase::Cancelled,
+        _ => return None,
+    })
+}
+
+fn pointer_kind_name(kind: PointerKind) -> &'static str {
+    match kind {
+        PointerKind::Mouse => "mouse",
+        PointerKind::Touch => "touch",
+        PointerKind::Pen => "pen",
+    }
+}
+
+fn parse_pointer_kind(s: &str) -> Option<PointerKind> {
+    Some(match s {
+        "mouse" => PointerKind::Mouse,
+        "touch" => PointerKind::Touch,
+        "pen" => PointerKind::Pen,
+        _ => return None,
+    })
+}
+
+fn pointer_phase_name(phase: PointerPhase) -> &'static str {
+    match phase {
+        PointerPhase::Down => "down",
+        PointerPhase::Up => "up",
+        PointerPhase::Moved => "moved",
+        PointerPhase::Cancelled => "cancelled",
+    }
+}
+
+fn parse_pointer_phase(s: &str) -> Option<PointerPhase> {
+    Some(match s {
+        "down" => PointerPhase::Down,
+        "up" => PointerPhase::Up,
+        "moved" => PointerPhase::Moved,
+        "cancelled" => PointerPhase::Cancelled,
+        _ => return None,
+    })
+}
+
+/// Left, middle, right, x1 and x2 as 5 bits
+fn pointer_buttons_bits(buttons: PointerButtons) -> u8 {
+    buttons.left as u8
+        | (buttons.middle as u8) << 1
+        | (buttons.right as u8) << 2
+        | (buttons.x1 as u8) << 3
+        | (buttons.x2 as u8) << 4
+}
+
+fn parse_pointer_buttons(s: &str) -> Option<PointerButtons> {
+    let bits: u8 = s.parse().ok()?;
+    Some(PointerButtons {
+        left: bits & 1 != 0,
+        middle: bits & 2 != 0,
+        right: bits & 4 != 0,
+        x1: bits & 8 != 0,
+        x2: bits & 16 != 0,
+    })
+}
+
+/// Shift, ctrl, alt and logo as 4 bits
+fn keymods_bits(keymods: KeyMods) -> u8 {
+    keymods.shift as u8
+        | (keymods.ctrl as u8) << 1
+        | (keymods.alt as u8) << 2
+        | (keymods.logo as u8) << 3
+}
+
+/// A string written with `{:?}`
+fn parse_quoted(s: &str) -> Option<String> {
+    let mut chars = s.strip_prefix('"')?.strip_suffix('"')?.chars();
+    let mut text = String::new();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            text.push(c);
+            continue;
+        }
+        let escaped = match chars.next()? {
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            '0' => '\0',
+            c @ ('\\' | '"' | '\'') => c,
+            'u' if chars.next()? == '{' => {
+                let code: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                u32::from_str_radix(&code, 16)
+                    .ok()
+                    .and_then(char::from_u32)?
+            }
+            _ => return None,
+        };
+        text.push(escaped);
+    }
+    Some(text)
+}
+
+fn parse_keymods(s: &str) -> Option<KeyMods> {
+    let bits: u8 = s.parse().ok()?;
+    Some(KeyMods {
+        shift: bits & 1 != 0,
+        ctrl: bits & 2 != 0,
+        alt: bits & 4 != 0,
+        logo: bits & 8 != 0,
+    })
+}
+
+impl std::fmt::Display for RecordedEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {:.6} ", self.frame, self.time)?;
+        match self.event {
+            Event::Resize(w, h) => write!(f, "resize {} {}", w, h),
+            Event::LiveResize(active, edge) => {
+                write!(f, "live_resize {} {}", active as u8, resize_edge_name(edge))
+            }
+            Event::MouseMotion(x, y) => write!(f, "mouse_motion {} {}", x, y),
+            Event::MouseWheel(x, y) => write!(f, "mouse_wheel {} {}", x, y),
+            Event::MouseButtonDown(button, x, y) => {
+                write!(f, "mouse_down {} {} {}", mouse_button_name(button), x, y)
+            }
+            Event::MouseButtonUp(button, x, y) => {
+                write!(f, "mouse_up {} {} {}", mouse_button_name(button), x, y)
+            }
+            Event::DoubleClick(button, x, y) => {
+                write!(f, "double_click {} {} {}", mouse_button_name(button), x, y)
+            }
+            Event::Char(character, keymods, repeat) => write!(
+                f,
+                "char {} {} {}",
+                character as u32,
+                keymods_bits(keymods),
+                repeat as u8
+            ),
+            // Quoted and escaped, the text may be empty or contain spaces
+            Event::TextInput(ref text) => write!(f, "text_input {:?}", text),
+            Event::KeyDown(keycode, keymods, repeat) => write!(
+                f,
+                "key_down {} {} {}",
+                keycode as u16,
+                keymods_bits(keymods),
+                repeat as u8
+            ),
+            Event::KeyUp(keycode, keymods) => {
+                write!(f, "key_up {} {}", keycode as u16, keymods_bits(keymods))
+            }
+            Event::Touch(phase, id, x, y) => {
+                write!(f, "touch {} {} {} {}", touch_phase_name(phase), id, x, y)
+            }
+            Event::Pointer(event) => write!(
+                f,
+                "pointer {} {} {} {} {} {} {} {}",
+                pointer_kind_name(event.kind),
+                event.id,
+                pointer_phase_name(event.phase),
+                event.button.map_or("none".to_string(), mouse_button_name),
+                event.x,
+                event.y,
+                event.pressure,
+                pointer_buttons_bits(event.buttons)
+            ),
+            Event::RawMouseMotion(dx, dy) => write!(f, "raw_mouse_motion {} {}", dx, dy),
+            Event::WindowMinimized => write!(f, "window_minimized"),
+            Event::WindowRestored => write!(f, "window_restored"),
+            Event::QuitRequested => write!(f, "quit_requested"),
+            Event::SessionEnd => write!(f, "session_end"),
+            Event::LowMemory => write!(f, "low_memory"),
+            Event::FilesDropped => write!(f, "files_dropped"),
+            Event::ClipboardChanged => write!(f, "clipboard_changed"),
+            Event::Tray(TrayEvent::Click) => write!(f, "tray_click"),
+            Event::Tray(TrayEvent::MenuItem(id)) => write!(f, "tray_menu_item {}", id),
+            Event::ColorPicked(Some((r, g, b, a))) => {
+                write!(f, "color_picked {} {} {} {}", r, g, b, a)
+            }
+            Event::ColorPicked(None) => write!(f, "color_picked none"),
+            Event::MessageBox(id, button) => {
+                write!(f, "message_box {} {}", id, message_box_button_name(button))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for RecordedEvent {
+    type Err = String;
+
+    fn from_str(line: &str) -> Result<RecordedEvent, String> {
+        let invalid = || format!("Invalid recorded event: {:?}", line);
+        let mut words = line.split_whitespace();
+        let mut next = || words.next().ok_or_else(invalid);
+        fn parse<T: std::str::FromStr>(s: &str) -> Option<T> {
+            s.parse().ok()
+        }
+
+        let frame = parse(next()?).ok_or_else(invalid)?;
+        let time = parse(next()?).ok_or_else(invalid)?;
+        let event = match next()? {
+            "resize" => parse(next()?)
+                .zip(parse(next()?))
+                .map(|(w, h)| Event::Resize(w, h)),
+            "live_resize" => {
+                let active = parse::<u8>(next()?).map(|active| active != 0);
+                let edge = parse_resize_edge(next()?);
+                active
+                    .zip(edge)
+                    .map(|(active, edge)| Event::LiveResize(active, edge))
+            }
+            "mouse_motion" => parse(next()?)
+                .zip(parse(next()?))
+                .map(|(x, y)| Event::MouseMotion(x, y)),
+            "mouse_wheel" => parse(next()?)
+                .zip(parse(next()?))
+                .map(|(x, y)| Event::MouseWheel(x, y)),
+            "mouse_down" => {
+                let button = parse_mouse_button(next()?);
+                let pos = parse(next()?).zip(parse(next()?));
+                button
+                    .zip(pos)
+                    .map(|(button, (x, y))| Event::MouseButtonDown(button, x, y))
+            }
+            "mouse_up" => {
+                let button = parse_mouse_button(next()?);
+                let pos = parse(next()?).zip(parse(next()?));
+                button
+                    .zip(pos)
+                    .map(|(button, (x, y))| Event::MouseButtonUp(button, x, y))
+            }
+            "double_click" => {
+                let button = parse_mouse_button(next()?);
+                let pos = parse(next()?).zip(parse(next()?));
+                button
+                    .zip(pos)
+                    .map(|(button, (x, y))| Event::DoubleClick(button, x, y))
+            }
+            "char" => {
+                let character = parse(next()?).and_then(char::from_u32);
+                let keymods = parse_keymods(next()?);
+                let repeat = parse::<u8>(next()?).map(|repeat| repeat != 0);
+                character
+                    .zip(keymods)
+                    .zip(repeat)
+                    .map(|((character, keymods), repeat)| Event::Char(character, keymods, repeat))
+            }
+            // The rest of the line, not a word
+            "text_input" => line
+                .split_once("text_input ")
+                .and_then(|(_, text)| parse_quoted(text.trim_end()))
+                .map(Event::TextInput),
+            "key_down" => {
+                let keycode = parse::<u16>(next()?).map(KeyCode::from);
+                let keymods = parse_keymods(next()?);
+                let repeat = parse::<u8>(next()?).map(|repeat| repeat != 0);
+                keycode
+                    .zip(keymods)
+                    .zip(repeat)
+                    .map(|((keycode, keymods), repeat)| Event::KeyDown(keycode, keymods, repeat))
+            }
+            "key_up" => {
+                let keycode = parse::<u16>(next()?).map(KeyCode::from);
+                let keymods = parse_keymods(next()?);
+                keycode
+                    .zip(keymods)
+                    .map(|(keycode, keymods)| Event::KeyUp(keycode, keymods))
+            }
+            "touch" => {
+                let phase = parse_touch_phase(next()?);
+                let id = parse(next()?);
+                let pos = parse(next()?).zip(parse(next()?));
+                phase
+                    .zip(id)
+                    .zip(pos)
+                    .map(|((phase, id), (x, y))| Event::Touch(phase, id, x, y))
+            }
+            "pointer" => {
+                let kind = parse_pointer_kind(next()?);
+                let id = parse(next()?);
+                let phase = parse_pointer_phase(next()?);
+                let button = match next()? {
+                    "none" => Some(None),
+                    button => parse_mouse_button(button).map(Some),
+                };
+                let pos = parse(next()?).zip(parse(next()?));
+                let pressure = parse(next()?);
+                let buttons = parse_pointer_buttons(next()?);
+                kind.zip(id)
+                    .zip(phase.zip(button))
+                    .zip(pos.zip(pressure.zip(buttons)))
+                    .map(
+                        |(((kind, id), (phase, button)), ((x, y), (pressure, buttons)))| {
+                            Event::Pointer(PointerEvent {
+                                kind,
+                                id,
+                                phase,
+                                button,
+                                x,
+                                y,
+                                pressure,
+                                buttons,
+                            })
+                        },
+                    )
+            }
+            "raw_mouse_motion" => parse(next()?)
+                .zip(parse(next()?))
+                .map(|(dx, dy)| Event::RawMouseMotion(dx, dy)),
+            "window_minimized" => Some(Event::WindowMinimized),
+            "window_restored" => Some(Event::WindowRestored),
+            "quit_requested" => Some(Event::QuitRequested),
+            "session_end" => Some(Event::SessionEnd),
+            "low_memory" => Some(Event::LowMemory),
+            "files_dropped" => Some(Event::FilesDropped),
+            "clipboard_changed" => Some(Event::ClipboardChanged),
+            "tray_click" => Some(Event::Tray(TrayEvent::Click)),
+            "tray_menu_item" => parse(next()?).map(|id| Event::Tray(TrayEvent::MenuItem(id))),
+            "color_picked" => match next()? {
+                "none" => Some(Event::ColorPicked(None)),
+                r => parse(r)
+                    .zip(parse(next()?))
+                    .zip(parse(next()?).zip(parse(next()?)))
+                    .map(|((r, g), (b, a))| Event::ColorPicked(Some((r, g, b, a)))),
+            },
+            "message_box" => {
+                let id = parse(next()?);
+                let button = parse_message_box_button(next()?);
+                id.zip(button)
+                    .map(|(id, button)| Event::MessageBox(id, button))
+            }
+            _ => None,
+        }
+        .ok_or_else(invalid)?;
+
+        Ok(RecordedEvent { frame, time, event })
+    }
+}
+
+/// Read a recording written by `Recorder`
+pub fn read_recording(reader: impl io::Read) -> io::Result<Vec<RecordedEvent>> {
+    let mut lines = BufReader::new(reader).lines();
+    let header = lines.next().transpose()?;
+    if header.as_deref().map(str::trim) != Some(HEADER) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Not a miniquad input recording",
+        ));
+    }
+    let mut events = vec![];
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let event = line
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        events.push(event);
+    }
+    Ok(events)
+}
+
+/// Forwards every callback to the wrapped handler and writes the input events
+/// down. The output is flushed every frame, so a crash loses at most one frame.
+pub struct Recorder<H: EventHandler, W: Write = BufWriter<File>> {
+    handler: H,
+    output: W,
+    frame: u64,
+    start_time: f64,
+    failed: bool,
+}
+
+impl<H: EventHandler> Recorder<H> {
+    pub fn to_file(path: impl AsRef<Path>, handler: H) -> io::Result<Recorder<H>> {
+        Recorder::new(BufWriter::new(File::create(path)?), handler)
+    }
+}
+
+impl<H: EventHandler, W: Write> Recorder<H, W> {
+    pub fn new(mut output: W, handler: H) -> io::Result<Recorder<H, W>> {
+        writeln!(output, "{}", HEADER)?;
+        Ok(Recorder {
+            handler,
+            output,
+            frame: 0,
+            start_time: crate::date::now(),
+            failed: false,
+        })
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    /// Flush the output and give back the handler and the output
+    pub fn into_inner(mut self) -> (H, W) {
+        let _ = self.output.flush();
+        (self.handler, self.output)
+    }
+
+    fn record(&mut self, event: Event) {
+        event.dispatch(&mut self.handler);
+        if self.failed {
+            return;
+        }
+        let event = RecordedEvent {
+            frame: self.frame,
+            time: crate::date::now() - self.start_time,
+            event,
+        };
+        if let Err(err) = writeln!(self.output, "{}", event) {
+            self.fail(err);
+        }
+    }
+
+    /// Report the first write error, recording stops there but the app goes on
+    fn fail(&mut self, err: io::Error) {
+        self.failed = true;
+        report_error(PlatformError::OsError(format!(
+            "Input recording stopped: {}",
+            err
+        )));
+    }
+}
+
+impl<H: EventHandler, W: Write> EventHandler for Recorder<H, W> {
+    fn update(&mut self) {
+        self.handler.update();
+        self.frame += 1;
+        if !self.failed {
+            if let Err(err) = self.output.flush() {
+                self.fail(err);
+            }
+        }
+    }
+    fn draw(&mut self) {
+        self.handler.draw();
+    }
+    fn resize_event(&mut self, width: f32, height: f32) {
+        self.record(Event::Resize(width, height));
+    }
+    fn live_resize_event(&mut self, active: bool, edge: Option<ResizeEdge>) {
+        self.record(Event::LiveResize(active, edge));
+    }
+    fn mouse_motion_event(&mut self, x: f32, y: f32) {
+        self.record(Event::MouseMotion(x, y));
+    }
+    fn mouse_wheel_event(&mut self, x: f32, y: f32) {
+        self.record(Event::MouseWheel(x, y));
+    }
+    fn mouse_button_down_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.record(Event::MouseButtonDown(button, x, y));
+    }
+    fn mouse_button_up_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.record(Event::MouseButtonUp(button, x, y));
+    }
+    fn double_click_event(&mut self, button: MouseButton, x: f32, y: f32) {
+        self.record(Event::DoubleClick(button, x, y));
+    }
+    fn char_event(&mut self, character: char, keymods: KeyMods, repeat: bool) {
+        self.record(Event::Char(character, keymods, repeat));
+    }
+    fn text_input_event(&mut self, text: &str) {
+        self.record(Event::TextInput(text.to_string()));
+    }
+    fn key_down_event(&mut self, keycode: KeyCode, keymods: KeyMods, repeat: bool) {
+        self.record(Event::KeyDown(keycode, keymods, repeat));
+    }
+    fn key_up_event(&mut self, keycode: KeyCode, keymods: KeyMods) {
+        self.record(Event::KeyUp(keycode, keymods));
+    }
+    fn touch_event(&mut self, phase: TouchPhase, id: u64, x: f32, y: f32) {
+        self.record(Event::Touch(phase, id, x, y));
+    }
+    fn pointer_event(&mut self, event: PointerEvent) {
+        self.record(Event::Pointer(event));
+    }
+    fn raw_mouse_motion(&mut self, dx: f32, dy: f32) {
+        self.record(Event::RawMouseMotion(dx, dy));
+    }
+    fn window_minimized_event(&mut self) {
+        self.record(Event::WindowMinimized);
+    }
+    fn window_restored_event(&mut self) {
+        self.record(Event::WindowRestored);
+    }
+    fn quit_requested_event(&mut self) {
+        self.record(Event::QuitRequested);
+        let _ = self.output.flush();
+    }
+    fn session_end_event(&mut self) {
+        self.record(Event::SessionEnd);
+        let _ = self.output.flush();
+    }
+    fn low_memory_event(&mut self) {
+        self.record(Event::LowMemory);
+    }
+    fn files_dropped_event(&mut self) {
+        self.record(Event::FilesDropped);
+    }
+    fn clipboard_changed_event(&mut self) {
+        self.record(Event::ClipboardChanged);
+    }
+    fn tray_event(&mut self, event: TrayEvent) {
+        self.record(Event::Tray(event));
+    }
+    fn color_picked_event(&mut self, color: Option<(f32, f32, f32, f32)>) {
+        self.record(Event::ColorPicked(color));
+    }
+    fn message_box_event(&mut self, id: u32, button: MessageBoxButton) {
+        self.record(Event::MessageBox(id, button));
+    }
+    // Opaque, so passed on but not recorded
+    fn accessibility_action_event(&mut self, request: Box<dyn std::any::Any + Send>) {
+        self.handler.accessibility_action_event(request);
+    }
+    // A replay has no XR session to go with them, so passed on but not recorded
+    #[cfg(feature = "webxr")]
+    fn xr_session_event(&mut self, event: crate::xr::XrSessionEvent) {
+        self.handler.xr_session_event(event);
+    }
+    #[cfg(feature = "webxr")]
+    fn xr_controller_event(&mut self, controller: crate::xr::XrController) {
+        self.handler.xr_controller_event(controller);
+    }
+    fn error(&mut self, error: MiniquadError) {
+        self.handler.error(error);
+    }
+}
+
+/// Replays recorded events into the wrapped handler, see the module docs.
+/// Can run inside a real window or be driven by calling `update` directly.
+pub struct Player<H: EventHandler> {
+    handler: H,
+    events: Vec<RecordedEvent>,
+    next_event: usize,
+    frame: u64,
+}
+
+impl<H: EventHandler> Player<H> {
+    pub fn from_file(path: impl AsRef<Path>, handler: H) -> io::Result<Player<H>> {
+        Ok(Player::new(read_recording(File::open(path)?)?, handler))
+    }
+
+    pub fn new(mut events: Vec<RecordedEvent>, handler: H) -> Player<H> {
+        // Stable, events of the same frame keep their order
+        events.sort_by_key(|event| event.frame);
+        Player {
+            handler,
+            events,
+            next_event: 0,
+            frame: 0,
+        }
+    }
+
+    /// All the recorded events were delivered
+    pub fn is_finished(&self) -> bool {
+        self.next_event == self.events.len()
+    }
+
+    /// Frames replayed so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut H {
+        &mut self.handler
+    }
+
+    pub fn into_inner(self) -> H {
+        self.handler
+    }
+}
+
+impl<H: EventHandler> EventHandler for Player<H> {
+    fn update(&mut self) {
+        while let Some(event) = self.events.get(self.next_event) {
+            if event.frame > self.frame {
+                break;
+            }
+            event.event.dispatch(&mut self.handler);
+            self.next_event += 1;
+        }
+        self.handler.update();
+        self.frame += 1;
+    }
+    fn draw(&mut self) {
+        self.handler.draw();
+    }
+    // Everything else is live input and ignored, the replay must not depend on it
+    fn touch_event(&mut self, _phase: TouchPhase, _id: u64, _x: f32, _y: f32) {}
+    fn quit_requested_event(&mut self) {
+        self.handler.quit_requested_event();
+    }
+    fn error(&mut self, error: MiniquadError) {
+        self.handler.error(error);
+    }
+}
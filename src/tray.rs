@@ -0,0 +1,114 @@
+//! Status icon in the system tray, with a menu
+//!
+//! Windows puts it in the notification area, macOS in the menu bar and Linux
+//! exposes it as a StatusNotifierItem over D-Bus (KDE, GNOME with the AppIndicator
+//! extension, most other panels). Browsers have no tray, `show` reports
+//! `FeatureUnsupported` there; on Android and iOS it does nothing.
+//!
+//! Clicks arrive as `EventHandler::tray_event`. Together with
+//! `window::set_window_visible` this is enough to minimize to the tray:
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # use miniquad::tray::*;
+//! # struct Stage;
+//! # impl EventHandler for Stage {
+//! # fn update(&mut self) {} fn draw(&mut self) {}
+//! fn window_minimized_event(&mut self) {
+//!     window::set_window_visible(false);
+//! }
+//!
+//! fn tray_event(&mut self, event: TrayEvent) {
+//!     match event {
+//!         TrayEvent::Click => window::set_window_visible(true),
+//!         TrayEvent::MenuItem(QUIT) => window::order_quit(),
+//!         _ => {}
+//!     }
+//! }
+//! # }
+//! const QUIT: u32 = 1;
+//!
+//! tray::show(Tray {
+//!     tooltip: "My app".to_string(),
+//!     menu: vec![TrayMenuItem::item(QUIT, "Quit")],
+//!     ..Default::default()
+//! });
+//! ```
+
+use crate::{conf::Icon, native, native_display};
+
+/// What the tray icon looks like and what its menu contains
+#[derive(Clone)]
+pub struct Tray {
+    /// The sizes closest to what the platform wants are used
+    pub icon: Icon,
+    pub tooltip: String,
+    /// Shown on right click. On Linux the panel decides, most show it on right
+    /// click as well.
+    pub menu: Vec<TrayMenuItem>,
+}
+
+impl Default for Tray {
+    fn default() -> Self {
+        Tray {
+            icon: Icon::miniquad_logo(),
+            tooltip: String::new(),
+            menu: vec![],
+        }
+    }
+}
+
+impl std::fmt::Debug for Tray {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tray")
+            .field("tooltip", &self.tooltip)
+            .field("menu", &self.menu)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrayMenuItem {
+    /// Sends `TrayEvent::MenuItem(id)` when chosen
+    Item {
+        id: u32,
+        label: String,
+        enabled: bool,
+    },
+    Separator,
+}
+
+impl TrayMenuItem {
+    /// An enabled item
+    pub fn item(id: u32, label: &str) -> TrayMenuItem {
+        TrayMenuItem::Item {
+            id,
+            label: label.to_string(),
+            enabled: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TrayEvent {
+    /// The icon itself was clicked with the primary button
+    Click,
+    /// A menu item was chosen, with its `id`
+    MenuItem(u32),
+}
+
+/// Show the tray icon, or update it when already shown
+pub fn show(tray: Tray) {
+    let d = native_display().lock().unwrap();
+    d.native_requests
+        .send(native::Request::SetTray(Some(Box::new(tray))))
+        .unwrap();
+}
+
+/// Remove the tray icon
+pub fn hide() {
+    let d = native_display().lock().unwrap();
+    d.native_requests
+        .send(native::Request::SetTray(None))
+        .unwrap();
+}
@@ -0,0 +1,241 @@
+//! Immersive WebXR sessions, with the `webxr` feature
+//!
+//! Browsers only, with `js/webxr.js` loaded after `gl.js`. `request_session`
+//! enters VR, which browsers only allow from a user gesture: call it from
+//! `mouse_button_down_event`, `touch_event` or `key_down_event`.
+//!
+//! While the session runs the headset drives the frames: `update` and `draw` are
+//! called once per XR frame, and the page's own animation frames are skipped.
+//! In `draw`, `views` tells what to render for each eye and the default pass
+//! renders into the session's framebuffer: apply each view's viewport, then draw
+//! with its matrices. Tracked controllers are reported right before each `update`
+//! with `EventHandler::xr_controller_event`, the session starting and ending with
+//! `xr_session_event`.
+//!
+//! ```no_run
+//! # use miniquad::*;
+//! # struct Stage { ctx: Box<dyn RenderingBackend> }
+//! # impl EventHandler for Stage {
+//! # fn update(&mut self) {}
+//! fn draw(&mut self) {
+//!     self.ctx.begin_default_pass(PassAction::default());
+//!     for view in xr::views() {
+//!         let (x, y, w, h) = view.viewport;
+//!         self.ctx.apply_viewport(x, y, w, h);
+//!         // draw the scene with view.projection * view.view
+//!     }
+//!     self.ctx.end_render_pass();
+//!     self.ctx.commit_frame();
+//! }
+//! # }
+//! ```
+
+use crate::event::EventHandler;
+
+use std::sync::Mutex;
+
+/// Which eye a view is for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XrEye {
+    /// The only view of a monoscopic display
+    None,
+    Left,
+    Right,
+}
+
+/// What to render for one eye
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct XrView {
+    pub eye: XrEye,
+    /// Column major, as `glam::Mat4::from_cols_array` takes it
+    pub projection: [f32; 16],
+    /// From the reference space to the eye, column major
+    pub view: [f32; 16],
+    /// `(x, y, width, height)` in the session's framebuffer, for `apply_viewport`
+    pub viewport: (i32, i32, i32, i32),
+}
+
+/// A position, in meters, and an orientation quaternion `(x, y, z, w)`.
+/// The origin is on the floor when the headset knows where it is, else where
+/// the headset was when the session started.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct XrPose {
+    pub position: [f32; 3],
+    pub orientation: [f32; 4],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XrHandedness {
+    None,
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct XrButton {
+    pub pressed: bool,
+    pub touched: bool,
+    /// 0..1, for analog triggers
+    pub value: f32,
+}
+
+/// A tracked controller, reported on every XR frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct XrController {
+    pub handedness: XrHandedness,
+    /// Where the hand holds the controller, to draw it. None for input without
+    /// one, e.g. gaze.
+    pub grip: Option<XrPose>,
+    /// The pointing ray, starting at `position` and going along -z
+    pub aim: XrPose,
+    /// In the `xr-standard` gamepad order: trigger, squeeze, touchpad,
+    /// thumbstick, then the face buttons
+    pub buttons: Vec<XrButton>,
+    /// Touchpad x and y, then thumbstick x and y, in -1..1
+    pub axes: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum XrSessionEvent {
+    Started,
+    /// Ended with `end_session`, by the user or by the browser
+    Ended,
+    /// `request_session` failed: no headset, or the user refused
+    Failed,
+}
+
+struct Frame {
+    framebuffer: u32,
+    size: (i32, i32),
+    views: Vec<XrView>,
+}
+
+struct State {
+    running: bool,
+    // The XR frame being drawn
+    frame: Option<Frame>,
+    session_events: Vec<XrSessionEvent>,
+    controllers: Vec<XrController>,
+}
+
+static STATE: Mutex<State> = Mutex::new(State {
+    running: false,
+    frame: None,
+    session_events: Vec::new(),
+    controllers: Vec::new(),
+});
+
+/// Whether the browser implements WebXR. A session may still fail without a
+/// headset, reported as `XrSessionEvent::Failed`.
+pub fn is_supported() -> bool {
+    #[cfg(target_arch = "wasm32")]
+    return unsafe { crate::native::wasm::webxr::webxr_is_supported() };
+    #[cfg(not(target_arch = "wasm32"))]
+    false
+}
+
+/// Enter an immersive VR session, from a user gesture, see the module docs
+pub fn request_session() {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        crate::native::wasm::webxr::webxr_request_session();
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    crate::error::report_error(crate::error::PlatformError::FeatureUnsupported(
+        "WebXR sessions are only available in browsers".to_string(),
+    ));
+}
+
+pub fn end_session() {
+    #[cfg(target_arch = "wasm32")]
+    unsafe {
+        crate::native::wasm::webxr::webxr_end_session();
+    }
+}
+
+pub fn session_running() -> bool {
+    STATE.lock().map(|state| state.running).unwrap_or(false)
+}
+
+/// The views of the XR frame being drawn, empty outside of one
+pub fn views() -> Vec<XrView> {
+    STATE
+        .lock()
+        .ok()
+        .and_then(|state| Some(state.frame.as_ref()?.views.clone()))
+        .unwrap_or_default()
+}
+
+/// The session's framebuffer and its size, while drawing an XR frame
+pub(crate) fn framebuffer() -> Option<(u32, i32, i32)> {
+    let state = STATE.lock().ok()?;
+    let frame = state.frame.as_ref()?;
+    Some((frame.framebuffer, frame.size.0, frame.size.1))
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub(crate) fn session_event(event: XrSessionEvent) {
+    if let Ok(mut state) = STATE.lock() {
+        state.running = event == XrSessionEvent::Started;
+        if !state.running {
+            state.frame = None;
+        }
+        state.session_events.push(event);
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub(crate) fn begin_frame(framebuffer: u32, size: (i32, i32)) {
+    if let Ok(mut state) = STATE.lock() {
+        state.frame = Some(Frame {
+            framebuffer,
+            size,
+            views: vec![],
+        });
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub(crate) fn push_view(view: XrView) {
+    if let Ok(mut state) = STATE.lock() {
+        if let Some(frame) = &mut state.frame {
+            frame.views.push(view);
+        }
+    }
+}
+
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub(crate) fn push_controller(controller: XrController) {
+    if let Ok(mut state) = STATE.lock() {
+        state.controllers.push(controller);
+    }
+}
+
+/// The XR frame was drawn, the default pass targets the window again
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub(crate) fn end_frame() {
+    if let Ok(mut state) = STATE.lock() {
+        state.frame = None;
+    }
+}
+
+/// Deliver the session changes and the controllers since the last frame,
+/// called before `update`
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+pub(crate) fn dispatch_events(event_handler: &mut dyn EventHandler) {
+    let (session_events, controllers) = STATE
+        .lock()
+        .map(|mut state| {
+            (
+                std::mem::take(&mut state.session_events),
+                std::mem::take(&mut state.controllers),
+            )
+        })
+        .unwrap_or_default();
+    for event in session_events {
+        event_handler.xr_session_event(event);
+    }
+    for controller in controllers {
+        event_handler.xr_controller_event(controller);
+    }
+}